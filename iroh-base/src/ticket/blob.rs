@@ -22,30 +22,92 @@ pub struct BlobTicket {
     format: BlobFormat,
     /// The hash to retrieve.
     hash: Hash,
+    /// The named blob store on the provider to get the data from.
+    ///
+    /// `None` refers to the provider's default store.
+    store: Option<String>,
 }
 
 /// Wire format for [`BlobTicket`].
 ///
-/// In the future we might have multiple variants (not versions, since they
-/// might be both equally valid), so this is a single variant enum to force
-/// postcard to add a discriminator.
+/// Tickets for the default store are encoded as [`TicketWireFormat::Variant0`], so they stay
+/// readable by nodes that do not know about named stores.
 #[derive(Serialize, Deserialize)]
 enum TicketWireFormat {
-    Variant0(BlobTicket),
+    Variant0(Variant0BlobTicket),
+    Variant1(Variant1BlobTicket),
+}
+
+#[derive(Serialize, Deserialize)]
+struct Variant0BlobTicket {
+    node: NodeAddr,
+    format: BlobFormat,
+    hash: Hash,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Variant1BlobTicket {
+    node: NodeAddr,
+    format: BlobFormat,
+    hash: Hash,
+    store: String,
+}
+
+impl From<BlobTicket> for TicketWireFormat {
+    fn from(ticket: BlobTicket) -> Self {
+        let BlobTicket {
+            node,
+            format,
+            hash,
+            store,
+        } = ticket;
+        match store {
+            None => Self::Variant0(Variant0BlobTicket { node, format, hash }),
+            Some(store) => Self::Variant1(Variant1BlobTicket {
+                node,
+                format,
+                hash,
+                store,
+            }),
+        }
+    }
+}
+
+impl From<TicketWireFormat> for BlobTicket {
+    fn from(wire: TicketWireFormat) -> Self {
+        match wire {
+            TicketWireFormat::Variant0(Variant0BlobTicket { node, format, hash }) => Self {
+                node,
+                format,
+                hash,
+                store: None,
+            },
+            TicketWireFormat::Variant1(Variant1BlobTicket {
+                node,
+                format,
+                hash,
+                store,
+            }) => Self {
+                node,
+                format,
+                hash,
+                store: Some(store),
+            },
+        }
+    }
 }
 
 impl Ticket for BlobTicket {
     const KIND: &'static str = "blob";
 
     fn to_bytes(&self) -> Vec<u8> {
-        let data = TicketWireFormat::Variant0(self.clone());
+        let data = TicketWireFormat::from(self.clone());
         postcard::to_stdvec(&data).expect("postcard serialization failed")
     }
 
     fn from_bytes(bytes: &[u8]) -> std::result::Result<Self, ticket::Error> {
         let res: TicketWireFormat = postcard::from_bytes(bytes).map_err(ticket::Error::Postcard)?;
-        let TicketWireFormat::Variant0(res) = res;
-        Ok(res)
+        Ok(res.into())
     }
 }
 
@@ -60,7 +122,23 @@ impl FromStr for BlobTicket {
 impl BlobTicket {
     /// Creates a new ticket.
     pub fn new(node: NodeAddr, hash: Hash, format: BlobFormat) -> Result<Self> {
-        Ok(Self { hash, format, node })
+        Ok(Self {
+            hash,
+            format,
+            node,
+            store: None,
+        })
+    }
+
+    /// Sets the named blob store on the provider that this ticket refers to.
+    pub fn with_store(mut self, store: impl Into<String>) -> Self {
+        self.store = Some(store.into());
+        self
+    }
+
+    /// The named blob store on the provider, or `None` for its default store.
+    pub fn store(&self) -> Option<&str> {
+        self.store.as_deref()
     }
 
     /// The hash of the item this ticket can retrieve.
@@ -85,18 +163,69 @@ impl BlobTicket {
 
     /// Get the contents of the ticket, consuming it.
     pub fn into_parts(self) -> (NodeAddr, Hash, BlobFormat) {
-        let BlobTicket { node, hash, format } = self;
+        let BlobTicket {
+            node, hash, format, ..
+        } = self;
         (node, hash, format)
     }
 }
 
+/// The format of a [`BlobTicket`] in non human readable serde formats.
+///
+/// The first two variants match [`BlobFormat`], so that tickets for the default store keep the
+/// `(node, format, hash)` tuple encoding of older versions. Tickets for a named store use the
+/// other variants and append the name of the store to the tuple.
+#[derive(Serialize, Deserialize)]
+enum SerdeFormat {
+    Raw,
+    HashSeq,
+    RawInStore,
+    HashSeqInStore,
+}
+
+impl SerdeFormat {
+    fn new(format: BlobFormat, in_store: bool) -> Self {
+        match (format, in_store) {
+            (BlobFormat::Raw, false) => Self::Raw,
+            (BlobFormat::HashSeq, false) => Self::HashSeq,
+            (BlobFormat::Raw, true) => Self::RawInStore,
+            (BlobFormat::HashSeq, true) => Self::HashSeqInStore,
+        }
+    }
+
+    fn format(&self) -> BlobFormat {
+        match self {
+            Self::Raw | Self::RawInStore => BlobFormat::Raw,
+            Self::HashSeq | Self::HashSeqInStore => BlobFormat::HashSeq,
+        }
+    }
+
+    fn in_store(&self) -> bool {
+        matches!(self, Self::RawInStore | Self::HashSeqInStore)
+    }
+}
+
 impl Serialize for BlobTicket {
     fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
         if serializer.is_human_readable() {
             serializer.serialize_str(&self.to_string())
         } else {
-            let BlobTicket { node, format, hash } = self;
-            (node, format, hash).serialize(serializer)
+            use serde::ser::SerializeTuple;
+            let BlobTicket {
+                node,
+                format,
+                hash,
+                store,
+            } = self;
+            let len = if store.is_some() { 4 } else { 3 };
+            let mut tuple = serializer.serialize_tuple(len)?;
+            tuple.serialize_element(node)?;
+            tuple.serialize_element(&SerdeFormat::new(*format, store.is_some()))?;
+            tuple.serialize_element(hash)?;
+            if let Some(store) = store {
+                tuple.serialize_element(store)?;
+            }
+            tuple.end()
         }
     }
 }
@@ -107,12 +236,49 @@ impl<'de> Deserialize<'de> for BlobTicket {
             let s = String::deserialize(deserializer)?;
             Self::from_str(&s).map_err(serde::de::Error::custom)
         } else {
-            let (peer, format, hash) = Deserialize::deserialize(deserializer)?;
-            Self::new(peer, hash, format).map_err(serde::de::Error::custom)
+            deserializer.deserialize_tuple(4, TicketVisitor)
         }
     }
 }
 
+/// Reads the tuple written by the [`Serialize`] impl of [`BlobTicket`].
+struct TicketVisitor;
+
+impl<'de> serde::de::Visitor<'de> for TicketVisitor {
+    type Value = BlobTicket;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a blob ticket")
+    }
+
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        use serde::de::Error;
+        let node = seq
+            .next_element()?
+            .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+        let format: SerdeFormat = seq
+            .next_element()?
+            .ok_or_else(|| A::Error::invalid_length(1, &self))?;
+        let hash = seq
+            .next_element()?
+            .ok_or_else(|| A::Error::invalid_length(2, &self))?;
+        let store = if format.in_store() {
+            let store = seq
+                .next_element()?
+                .ok_or_else(|| A::Error::invalid_length(3, &self))?;
+            Some(store)
+        } else {
+            None
+        };
+        Ok(BlobTicket {
+            node,
+            format: format.format(),
+            hash,
+            store,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::net::SocketAddr;
@@ -133,6 +299,7 @@ mod tests {
             hash,
             node: NodeAddr::from_parts(peer, relay_url, vec![addr]),
             format: BlobFormat::HashSeq,
+            store: None,
         }
     }
 
@@ -165,6 +332,7 @@ mod tests {
             node: NodeAddr::from_parts(node_id, None, vec![]),
             format: BlobFormat::Raw,
             hash,
+            store: None,
         };
         let base32 = base32::parse_vec(ticket.to_string().strip_prefix("blob").unwrap()).unwrap();
         let expected = parse_hexdump("
@@ -177,4 +345,31 @@ mod tests {
         ").unwrap();
         assert_eq_hex!(base32, expected);
     }
+
+    #[test]
+    fn test_ticket_store_roundtrip() {
+        let ticket = make_ticket().with_store("tenant-a");
+        let parsed = BlobTicket::from_str(&ticket.to_string()).unwrap();
+        assert_eq!(parsed.store(), Some("tenant-a"));
+        assert_eq!(parsed, ticket);
+
+        let bytes = postcard::to_stdvec(&ticket).unwrap();
+        let ticket2: BlobTicket = postcard::from_bytes(&bytes).unwrap();
+        assert_eq!(ticket2, ticket);
+    }
+
+    #[test]
+    fn test_ticket_postcard_compat() {
+        // tickets for the default store keep the tuple encoding of older versions
+        let ticket = make_ticket();
+        let old = postcard::to_stdvec(&(&ticket.node, ticket.format, ticket.hash)).unwrap();
+        assert_eq!(postcard::to_stdvec(&ticket).unwrap(), old);
+        let ticket2: BlobTicket = postcard::from_bytes(&old).unwrap();
+        assert_eq!(ticket2, ticket);
+
+        // older versions reject tickets for a named store instead of dropping the store
+        let ticket = make_ticket().with_store("tenant-a");
+        let bytes = postcard::to_stdvec(&ticket).unwrap();
+        assert!(postcard::from_bytes::<(NodeAddr, BlobFormat, Hash)>(&bytes).is_err());
+    }
 }
@@ -0,0 +1,1018 @@
+//! Import and export of collections as [CARv1] files.
+//!
+//! CAR files are the exchange format of IPFS. A CAR file contains a header with a list of
+//! root [CIDs](Cid), followed by a sequence of blocks, each prefixed with its CID.
+//!
+//! Iroh content is mapped to IPFS blocks as follows:
+//!
+//! - A blob of up to [`MAX_BLOCK_SIZE`] bytes is a single block with the [`Cid::RAW`] codec.
+//!   Since the CID uses a BLAKE3 multihash, its digest is the iroh [`Hash`] of the blob.
+//! - A larger blob is chunked into a UnixFS file DAG like IPFS does: raw leaves of
+//!   [`FILE_CHUNK_SIZE`] bytes, linked from file nodes with the [`Cid::DAG_PB`] codec. The CID
+//!   of the root node is not the iroh hash of the blob, so computing it requires the data of
+//!   the blob.
+//! - A [`Collection`] is a UnixFS directory node with the [`Cid::DAG_PB`] codec, linking to
+//!   the blocks of its entries by name. The links are sorted by name, as DAG-PB requires, so
+//!   a collection is imported with its entries sorted by name. The directory node itself is
+//!   not stored in the blob store, it is computed from the collection when exporting.
+//!
+//! [`MAX_BLOCK_SIZE`] is the block size limit of most IPFS implementations, larger sections
+//! are rejected on import. Only CAR files with blocks hashed with BLAKE3 can be imported,
+//! since the data is verified with BLAKE3 on import.
+//!
+//! [CARv1]: https://ipld.io/specs/transport/car/carv1/
+use std::{collections::BTreeMap, fmt, io, str::FromStr};
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use bao_tree::blake3;
+use bytes::Bytes;
+use iroh_io::{AsyncSliceReader, AsyncStreamReader, AsyncStreamWriter};
+
+use crate::{
+    format::collection::Collection,
+    store::{Map, MapEntry, Store},
+    util::{progress::IgnoreProgressSender, TempTag},
+    BlobFormat, Hash, HashAndFormat,
+};
+
+/// The multicodec of a CID version 1.
+const CID_V1: u64 = 0x01;
+/// The multicodec of the BLAKE3 multihash.
+const BLAKE3_MULTIHASH: u64 = 0x1e;
+/// The CBOR tag for CIDs in DAG-CBOR.
+const CBOR_TAG_CID: u64 = 42;
+/// The chunk size used to copy blob data into a CAR file.
+const COPY_CHUNK_SIZE: usize = 1024 * 1024;
+/// The maximum size of a CAR header we accept.
+const MAX_HEADER_SIZE: u64 = 1024 * 1024;
+/// The maximum size of a block in a CAR file. Larger blobs are chunked into a file DAG.
+pub const MAX_BLOCK_SIZE: u64 = 2 * 1024 * 1024;
+/// The size of the leaves of a file DAG, the default chunk size of IPFS.
+pub const FILE_CHUNK_SIZE: u64 = 256 * 1024;
+/// The maximum number of links of a file node, the default of IPFS.
+const MAX_FILE_LINKS: usize = 174;
+/// The maximum depth of a file DAG we import.
+const MAX_FILE_DEPTH: usize = 32;
+/// The maximum encoded length of a CID in a CAR section.
+const MAX_CID_SIZE: u64 = 64;
+/// The UnixFS data of a directory node: `Data { Type: Directory }`.
+const UNIXFS_DIRECTORY: &[u8] = &[0x08, 0x01];
+/// The UnixFS type of raw file data.
+const UNIXFS_TYPE_RAW: u64 = 0;
+/// The UnixFS type of a directory.
+const UNIXFS_TYPE_DIRECTORY: u64 = 1;
+/// The UnixFS type of a file.
+const UNIXFS_TYPE_FILE: u64 = 2;
+
+/// A content identifier (CID) version 1 with a BLAKE3 multihash.
+///
+/// This is the subset of CIDs that can be mapped to iroh hashes.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Cid {
+    codec: u64,
+    hash: Hash,
+}
+
+impl Cid {
+    /// The multicodec for raw binary blocks.
+    pub const RAW: u64 = 0x55;
+    /// The multicodec for DAG-PB blocks, which are used for UnixFS nodes.
+    pub const DAG_PB: u64 = 0x70;
+
+    /// Create a new CID from a multicodec and a BLAKE3 hash.
+    pub const fn new(codec: u64, hash: Hash) -> Self {
+        Self { codec, hash }
+    }
+
+    /// Create the CID of a raw block.
+    pub const fn raw(hash: Hash) -> Self {
+        Self::new(Self::RAW, hash)
+    }
+
+    /// The multicodec of the content this CID refers to.
+    pub fn codec(&self) -> u64 {
+        self.codec
+    }
+
+    /// The BLAKE3 hash of the content this CID refers to.
+    ///
+    /// For a raw CID, this is the iroh hash of the blob.
+    pub fn hash(&self) -> Hash {
+        self.hash
+    }
+
+    /// Encode the CID in its binary form.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut res = Vec::with_capacity(38);
+        write_varint(&mut res, CID_V1);
+        write_varint(&mut res, self.codec);
+        write_varint(&mut res, BLAKE3_MULTIHASH);
+        write_varint(&mut res, 32);
+        res.extend_from_slice(self.hash.as_bytes());
+        res
+    }
+
+    /// Decode a CID from its binary form.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let (cid, len) = Self::parse(bytes)?;
+        ensure!(len == bytes.len(), "trailing bytes after CID");
+        Ok(cid)
+    }
+
+    /// Decode a CID from the start of `bytes`, returning it and its encoded length.
+    fn parse(bytes: &[u8]) -> Result<(Self, usize)> {
+        let mut reader = SliceReader::new(bytes);
+        let version = reader.varint()?;
+        ensure!(version == CID_V1, "unsupported CID version {version}");
+        let codec = reader.varint()?;
+        let multihash = reader.varint()?;
+        ensure!(
+            multihash == BLAKE3_MULTIHASH,
+            "unsupported multihash {multihash:#x}, only BLAKE3 is supported"
+        );
+        let len = reader.varint()?;
+        ensure!(len == 32, "invalid BLAKE3 digest length {len}");
+        let digest: [u8; 32] = reader.bytes(32)?.try_into().expect("checked length");
+        let cid = Self::new(codec, Hash::from_bytes(digest));
+        Ok((cid, reader.pos))
+    }
+}
+
+impl From<Hash> for Cid {
+    fn from(hash: Hash) -> Self {
+        Self::raw(hash)
+    }
+}
+
+impl fmt::Display for Cid {
+    /// Formats the CID as a multibase base32 string, like IPFS does.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "b{}", iroh_base::base32::fmt(self.to_bytes()))
+    }
+}
+
+impl fmt::Debug for Cid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Cid({self})")
+    }
+}
+
+impl FromStr for Cid {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some(base32) = s.strip_prefix('b') else {
+            bail!("unsupported multibase, only base32 CIDs are supported");
+        };
+        let bytes = iroh_base::base32::parse_vec(base32)?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+/// Compute the CID of some content in the store.
+///
+/// For [`BlobFormat::Raw`], this is the raw CID of the hash for a blob of up to
+/// [`MAX_BLOCK_SIZE`] bytes, and the CID of the root of its file DAG for a larger blob. For
+/// [`BlobFormat::HashSeq`], the content must be a [`Collection`], and the CID is the one of
+/// the UnixFS directory node it is exported as, see [`export_car`]. This requires the sizes
+/// of all blobs to be known to the store, and the data of the blobs larger than
+/// [`MAX_BLOCK_SIZE`] to be complete.
+///
+/// To get the CID for a [`BlobTicket`](iroh_base::ticket::BlobTicket), pass its hash and
+/// format.
+pub async fn content_cid<D: Map>(db: &D, content: HashAndFormat) -> Result<Cid> {
+    match content.format {
+        BlobFormat::Raw => Ok(file_dag(db, content.hash).await?.root),
+        BlobFormat::HashSeq => {
+            let collection = Collection::load_db(db, &content.hash).await?;
+            let links = directory_links(db, &collection).await?;
+            let node = directory_node(&links);
+            Ok(Cid::new(Cid::DAG_PB, blake3::hash(&node).into()))
+        }
+    }
+}
+
+/// Export some content from the store as a CAR file.
+///
+/// See the [module docs](self) for how the content is mapped to blocks. All blobs of the
+/// content must be completely stored. Blobs larger than [`MAX_BLOCK_SIZE`] are read twice,
+/// once to compute the CIDs of their file DAG, and once to write it. Returns the root CID of
+/// the CAR file.
+pub async fn export_car<D: Map, W: AsyncStreamWriter>(
+    db: &D,
+    content: HashAndFormat,
+    mut writer: W,
+) -> Result<Cid> {
+    match content.format {
+        BlobFormat::Raw => {
+            let dag = file_dag(db, content.hash).await?;
+            write_header(&mut writer, &dag.root).await?;
+            write_file(db, content.hash, &dag, &mut writer).await?;
+            writer.sync().await?;
+            Ok(dag.root)
+        }
+        BlobFormat::HashSeq => {
+            let collection = Collection::load_db(db, &content.hash).await?;
+            let links = directory_links(db, &collection).await?;
+            let node = directory_node(&links);
+            ensure!(
+                node.len() as u64 <= MAX_BLOCK_SIZE,
+                "collection has too many entries to be exported as a single directory block"
+            );
+            let root = Cid::new(Cid::DAG_PB, blake3::hash(&node).into());
+            write_header(&mut writer, &root).await?;
+            write_block(&mut writer, &root, node.into()).await?;
+            for (_name, hash, dag) in &links {
+                write_file(db, *hash, dag, &mut writer).await?;
+            }
+            writer.sync().await?;
+            Ok(root)
+        }
+    }
+}
+
+/// Import a CAR file into the store.
+///
+/// The CAR file must have a single root, which is either a file or a UnixFS directory of
+/// files. A file is a raw block or a UnixFS file DAG, and is imported as a single blob. A
+/// directory is imported as a [`Collection`]. All blocks must be hashed with BLAKE3 and are
+/// verified on import.
+///
+/// Each block is held in memory while it is imported, and blocks larger than
+/// [`MAX_BLOCK_SIZE`] are rejected. The raw leaves of file DAGs are imported as blobs, and
+/// then concatenated into the blob of the file. Returns a temp tag for the imported content,
+/// its format tells whether a blob or a collection was imported.
+pub async fn import_car<D: Store, R: AsyncStreamReader>(db: &D, mut reader: R) -> Result<TempTag> {
+    let root = read_header(&mut reader).await?;
+    let mut blobs = BTreeMap::new();
+    let mut nodes = BTreeMap::new();
+    while let Some((cid, data)) = read_block(&mut reader).await? {
+        let hash = Hash::from(blake3::hash(&data));
+        ensure!(hash == cid.hash(), "hash mismatch for block {cid}");
+        match cid.codec() {
+            Cid::RAW => {
+                let tag = db.import_bytes(data, BlobFormat::Raw).await?;
+                blobs.insert(hash, tag);
+            }
+            Cid::DAG_PB => {
+                nodes.insert(hash, data);
+            }
+            codec => bail!("unsupported codec {codec:#x} for block {cid}"),
+        }
+    }
+    let blocks = Blocks { blobs, nodes };
+    match root.codec() {
+        Cid::RAW => import_file(db, &blocks, root).await,
+        Cid::DAG_PB => {
+            let node = blocks.node(&root)?;
+            if node.kind != UNIXFS_TYPE_DIRECTORY {
+                return import_file(db, &blocks, root).await;
+            }
+            let mut collection = Collection::default();
+            let mut tags = Vec::new();
+            for (name, cid) in node.links {
+                let name = name.context("directory link without name")?;
+                let tag = import_file(db, &blocks, cid).await?;
+                collection.push(name, *tag.hash());
+                tags.push(tag);
+            }
+            // the temp tag of the hash seq protects all blobs of the collection
+            collection.store(db).await
+        }
+        codec => bail!("unsupported codec {codec:#x} for root {root}"),
+    }
+}
+
+/// The blocks read from a CAR file.
+struct Blocks {
+    /// The raw blocks, imported as blobs.
+    blobs: BTreeMap<Hash, TempTag>,
+    /// The DAG-PB nodes.
+    nodes: BTreeMap<Hash, Bytes>,
+}
+
+impl Blocks {
+    /// Decode the DAG-PB node with the given CID.
+    fn node(&self, cid: &Cid) -> Result<PbNode<'_>> {
+        let node = self
+            .nodes
+            .get(&cid.hash())
+            .with_context(|| format!("block {cid} not found"))?;
+        parse_node(node)
+    }
+}
+
+/// Import the file with the given root CID as a blob.
+///
+/// A raw block is already imported. The data of a file DAG is concatenated from its leaves,
+/// which are read from the store.
+async fn import_file<D: Store>(db: &D, blocks: &Blocks, root: Cid) -> Result<TempTag> {
+    if root.codec() == Cid::RAW {
+        ensure!(
+            blocks.blobs.contains_key(&root.hash()),
+            "block {root} not found"
+        );
+        return Ok(db.temp_tag(HashAndFormat::raw(root.hash())));
+    }
+    let (sender, receiver) = async_channel::bounded(2);
+    let send = async move {
+        let res = send_file_data(db, blocks, root, &sender).await;
+        drop(sender);
+        res
+    };
+    let import = db.import_stream(
+        Box::pin(receiver),
+        BlobFormat::Raw,
+        IgnoreProgressSender::default(),
+    );
+    let (res, import) = futures_lite::future::zip(send, import).await;
+    res?;
+    let (tag, _size) = import?;
+    Ok(tag)
+}
+
+/// Send the data of the file DAG with the given root in order.
+async fn send_file_data<D: Map>(
+    db: &D,
+    blocks: &Blocks,
+    root: Cid,
+    sender: &async_channel::Sender<io::Result<Bytes>>,
+) -> Result<()> {
+    let send = |data: Bytes| async move {
+        sender
+            .send(Ok(data))
+            .await
+            .map_err(|_| anyhow!("import of file {root} was aborted"))
+    };
+    let mut stack = vec![(root, 0)];
+    while let Some((cid, depth)) = stack.pop() {
+        match cid.codec() {
+            Cid::RAW => {
+                ensure!(
+                    blocks.blobs.contains_key(&cid.hash()),
+                    "block {cid} not found"
+                );
+                let entry = db.get(&cid.hash()).await?.context("blob not found")?;
+                let size = entry.size().value();
+                let mut reader = entry.data_reader().await?;
+                let data = read_chunk(&mut reader, cid.hash(), 0, size).await?;
+                send(data).await?;
+            }
+            Cid::DAG_PB => {
+                ensure!(depth < MAX_FILE_DEPTH, "file {root} is nested too deeply");
+                let node = blocks.node(&cid)?;
+                ensure!(
+                    node.kind == UNIXFS_TYPE_FILE || node.kind == UNIXFS_TYPE_RAW,
+                    "unsupported UnixFS node {cid}, only files are supported"
+                );
+                if let Some(data) = node.data {
+                    send(Bytes::copy_from_slice(data)).await?;
+                }
+                for (_name, link) in node.links.into_iter().rev() {
+                    stack.push((link, depth + 1));
+                }
+            }
+            codec => bail!("unsupported codec {codec:#x} for block {cid}"),
+        }
+    }
+    Ok(())
+}
+
+/// The blocks a blob is exported as.
+struct FileDag {
+    /// The CID of the root block.
+    root: Cid,
+    /// The cumulative size of all blocks.
+    tsize: u64,
+    /// The file nodes, root first, or none if the blob is a single raw block.
+    nodes: Vec<(Cid, Bytes)>,
+    /// The CIDs of the raw leaves, in order.
+    leaves: Vec<Cid>,
+}
+
+/// A link in a file DAG.
+#[derive(Clone, Copy)]
+struct FileLink {
+    cid: Cid,
+    /// The cumulative size of the linked blocks.
+    tsize: u64,
+    /// The size of the file data below the link.
+    filesize: u64,
+}
+
+/// Compute the blocks a blob is exported as.
+///
+/// Blobs larger than [`MAX_BLOCK_SIZE`] are chunked into leaves of [`FILE_CHUNK_SIZE`] bytes,
+/// which are linked from a balanced tree of file nodes with up to [`MAX_FILE_LINKS`] links.
+async fn file_dag<D: Map>(db: &D, hash: Hash) -> Result<FileDag> {
+    let entry = db.get(&hash).await?.context("blob not found")?;
+    let size = entry.size().value();
+    if size <= MAX_BLOCK_SIZE {
+        return Ok(FileDag {
+            root: Cid::raw(hash),
+            tsize: size,
+            nodes: Vec::new(),
+            leaves: Vec::new(),
+        });
+    }
+    ensure!(entry.is_complete(), "blob {hash} is not complete");
+    let mut reader = entry.data_reader().await?;
+    let mut level = Vec::new();
+    let mut offset = 0;
+    while offset < size {
+        let len = FILE_CHUNK_SIZE.min(size - offset);
+        let chunk = read_chunk(&mut reader, hash, offset, len).await?;
+        level.push(FileLink {
+            cid: Cid::raw(blake3::hash(&chunk).into()),
+            tsize: len,
+            filesize: len,
+        });
+        offset += len;
+    }
+    let leaves = level.iter().map(|link| link.cid).collect();
+    let mut nodes = Vec::new();
+    while level.len() > 1 {
+        level = level
+            .chunks(MAX_FILE_LINKS)
+            .map(|children| {
+                let node = file_node(children);
+                let cid = Cid::new(Cid::DAG_PB, blake3::hash(&node).into());
+                let link = FileLink {
+                    cid,
+                    tsize: node.len() as u64 + children.iter().map(|c| c.tsize).sum::<u64>(),
+                    filesize: children.iter().map(|c| c.filesize).sum(),
+                };
+                nodes.push((cid, node.into()));
+                link
+            })
+            .collect();
+    }
+    nodes.reverse();
+    Ok(FileDag {
+        root: level[0].cid,
+        tsize: level[0].tsize,
+        nodes,
+        leaves,
+    })
+}
+
+/// Encode a UnixFS file node linking to `children`.
+///
+/// The node is encoded as DAG-PB, with the links before the data.
+fn file_node(children: &[FileLink]) -> Vec<u8> {
+    let mut node = Vec::new();
+    for child in children {
+        let mut link = Vec::new();
+        write_pb_bytes(&mut link, 1, &child.cid.to_bytes());
+        write_pb_bytes(&mut link, 2, b"");
+        write_pb_varint(&mut link, 3, child.tsize);
+        write_pb_bytes(&mut node, 2, &link);
+    }
+    let mut data = Vec::new();
+    write_pb_varint(&mut data, 1, UNIXFS_TYPE_FILE);
+    write_pb_varint(
+        &mut data,
+        3,
+        children.iter().map(|child| child.filesize).sum(),
+    );
+    for child in children {
+        write_pb_varint(&mut data, 4, child.filesize);
+    }
+    write_pb_bytes(&mut node, 1, &data);
+    node
+}
+
+/// Compute the links of the UnixFS directory node of a collection, sorted by name.
+async fn directory_links<D: Map>(
+    db: &D,
+    collection: &Collection,
+) -> Result<Vec<(String, Hash, FileDag)>> {
+    let mut links = Vec::new();
+    for (name, hash) in collection.iter() {
+        links.push((name.clone(), *hash, file_dag(db, *hash).await?));
+    }
+    links.sort_by(|(a, _, _), (b, _, _)| a.as_bytes().cmp(b.as_bytes()));
+    for pair in links.windows(2) {
+        ensure!(
+            pair[0].0 != pair[1].0,
+            "duplicate name {} in collection",
+            pair[0].0
+        );
+    }
+    Ok(links)
+}
+
+/// Encode the UnixFS directory node of a collection.
+///
+/// The node is encoded as DAG-PB, with the links before the data.
+fn directory_node(links: &[(String, Hash, FileDag)]) -> Vec<u8> {
+    let mut node = Vec::new();
+    for (name, _hash, dag) in links {
+        let mut link = Vec::new();
+        write_pb_bytes(&mut link, 1, &dag.root.to_bytes());
+        write_pb_bytes(&mut link, 2, name.as_bytes());
+        write_pb_varint(&mut link, 3, dag.tsize);
+        write_pb_bytes(&mut node, 2, &link);
+    }
+    write_pb_bytes(&mut node, 1, UNIXFS_DIRECTORY);
+    node
+}
+
+/// A decoded DAG-PB node with UnixFS data.
+struct PbNode<'a> {
+    /// The links of the node, with their names.
+    links: Vec<(Option<String>, Cid)>,
+    /// The UnixFS type of the node.
+    kind: u64,
+    /// The file data stored in the node itself.
+    data: Option<&'a [u8]>,
+}
+
+/// Decode a DAG-PB node with UnixFS data.
+fn parse_node(node: &[u8]) -> Result<PbNode<'_>> {
+    let mut links = Vec::new();
+    let mut data = None;
+    let mut reader = SliceReader::new(node);
+    while let Some((field, value)) = reader.pb_field()? {
+        match (field, value) {
+            (1, PbValue::Bytes(value)) => data = Some(value),
+            (2, PbValue::Bytes(value)) => {
+                let mut cid = None;
+                let mut name = None;
+                let mut link = SliceReader::new(value);
+                while let Some((field, value)) = link.pb_field()? {
+                    match (field, value) {
+                        (1, PbValue::Bytes(value)) => cid = Some(Cid::from_bytes(value)?),
+                        (2, PbValue::Bytes(value)) => {
+                            name = Some(std::str::from_utf8(value)?.to_string())
+                        }
+                        _ => {}
+                    }
+                }
+                let cid = cid.context("DAG-PB link without CID")?;
+                links.push((name, cid));
+            }
+            _ => {}
+        }
+    }
+    let data = data.context("DAG-PB node without UnixFS data")?;
+    let mut reader = SliceReader::new(data);
+    let mut kind = None;
+    let mut file_data = None;
+    while let Some((field, value)) = reader.pb_field()? {
+        match (field, value) {
+            (1, PbValue::Varint(value)) => kind = Some(value),
+            (2, PbValue::Bytes(value)) => file_data = Some(value),
+            _ => {}
+        }
+    }
+    let kind = kind.context("UnixFS data without type")?;
+    Ok(PbNode {
+        links,
+        kind,
+        data: file_data,
+    })
+}
+
+/// Write the CAR header with a single root.
+///
+/// The header is the DAG-CBOR map `{ "roots": [root], "version": 1 }`.
+async fn write_header<W: AsyncStreamWriter>(writer: &mut W, root: &Cid) -> Result<()> {
+    let mut cid = vec![0u8];
+    cid.extend(root.to_bytes());
+    let mut header = Vec::new();
+    write_cbor_head(&mut header, 5, 2);
+    write_cbor_head(&mut header, 3, 5);
+    header.extend_from_slice(b"roots");
+    write_cbor_head(&mut header, 4, 1);
+    write_cbor_head(&mut header, 6, CBOR_TAG_CID);
+    write_cbor_head(&mut header, 2, cid.len() as u64);
+    header.extend(cid);
+    write_cbor_head(&mut header, 3, 7);
+    header.extend_from_slice(b"version");
+    write_cbor_head(&mut header, 0, 1);
+    let mut prefix = Vec::new();
+    write_varint(&mut prefix, header.len() as u64);
+    writer.write(&prefix).await?;
+    writer.write_bytes(header.into()).await?;
+    Ok(())
+}
+
+/// Read the CAR header and return its single root.
+async fn read_header<R: AsyncStreamReader>(reader: &mut R) -> Result<Cid> {
+    let len = read_varint(reader).await?.context("empty CAR file")?;
+    ensure!(len <= MAX_HEADER_SIZE, "CAR header too large");
+    let header = read_exact(reader, len as usize).await?;
+    let mut reader = SliceReader::new(&header);
+    let mut roots = Vec::new();
+    let mut version = None;
+    let fields = reader.cbor_head(5)?;
+    for _ in 0..fields {
+        let len = reader.cbor_head(3)?;
+        match reader.bytes(len as usize)? {
+            b"roots" => {
+                let count = reader.cbor_head(4)?;
+                for _ in 0..count {
+                    ensure!(reader.cbor_head(6)? == CBOR_TAG_CID, "expected CID tag");
+                    let len = reader.cbor_head(2)?;
+                    let bytes = reader.bytes(len as usize)?;
+                    let (prefix, cid) = bytes.split_first().context("empty CID")?;
+                    ensure!(*prefix == 0, "invalid CID prefix");
+                    roots.push(Cid::from_bytes(cid)?);
+                }
+            }
+            b"version" => version = Some(reader.cbor_head(0)?),
+            _ => bail!("unexpected field in CAR header"),
+        }
+    }
+    ensure!(version == Some(1), "unsupported CAR version {version:?}");
+    match roots[..] {
+        [root] => Ok(root),
+        _ => bail!("expected a single root, found {}", roots.len()),
+    }
+}
+
+/// Write a block as a CAR section.
+async fn write_block<W: AsyncStreamWriter>(writer: &mut W, cid: &Cid, data: Bytes) -> Result<()> {
+    let cid = cid.to_bytes();
+    let mut prefix = Vec::new();
+    write_varint(&mut prefix, (cid.len() + data.len()) as u64);
+    prefix.extend(cid);
+    writer.write(&prefix).await?;
+    writer.write_bytes(data).await?;
+    Ok(())
+}
+
+/// Write the blocks of a blob, see [`file_dag`].
+async fn write_file<D: Map, W: AsyncStreamWriter>(
+    db: &D,
+    hash: Hash,
+    dag: &FileDag,
+    writer: &mut W,
+) -> Result<()> {
+    if dag.nodes.is_empty() {
+        return write_blob_block(db, hash, writer).await;
+    }
+    for (cid, node) in &dag.nodes {
+        write_block(writer, cid, node.clone()).await?;
+    }
+    let entry = db.get(&hash).await?.context("blob not found")?;
+    let size = entry.size().value();
+    let mut reader = entry.data_reader().await?;
+    let mut offset = 0;
+    for cid in &dag.leaves {
+        let len = FILE_CHUNK_SIZE.min(size - offset);
+        let chunk = read_chunk(&mut reader, hash, offset, len).await?;
+        write_block(writer, cid, chunk).await?;
+        offset += len;
+    }
+    Ok(())
+}
+
+/// Write a blob from the store as a raw block, copying the data in chunks.
+async fn write_blob_block<D: Map, W: AsyncStreamWriter>(
+    db: &D,
+    hash: Hash,
+    writer: &mut W,
+) -> Result<()> {
+    let entry = db.get(&hash).await?.context("blob not found")?;
+    ensure!(entry.is_complete(), "blob {hash} is not complete");
+    let size = entry.size().value();
+    ensure!(
+        size <= MAX_BLOCK_SIZE,
+        "blob {hash} is too large to be exported as a single block ({size} > {MAX_BLOCK_SIZE} bytes)"
+    );
+    let cid = Cid::raw(hash).to_bytes();
+    let mut prefix = Vec::new();
+    write_varint(&mut prefix, cid.len() as u64 + size);
+    prefix.extend(cid);
+    writer.write(&prefix).await?;
+    let mut reader = entry.data_reader().await?;
+    let mut offset = 0;
+    while offset < size {
+        let chunk = reader.read_at(offset, COPY_CHUNK_SIZE).await?;
+        ensure!(!chunk.is_empty(), "unexpected end of blob {hash}");
+        offset += chunk.len() as u64;
+        writer.write_bytes(chunk).await?;
+    }
+    Ok(())
+}
+
+/// Read `len` bytes at `offset` of a blob.
+async fn read_chunk<R: AsyncSliceReader>(
+    reader: &mut R,
+    hash: Hash,
+    offset: u64,
+    len: u64,
+) -> Result<Bytes> {
+    let first = reader.read_at(offset, len as usize).await?;
+    if first.len() as u64 == len {
+        return Ok(first);
+    }
+    let mut buf = Vec::with_capacity(len as usize);
+    buf.extend_from_slice(&first);
+    while (buf.len() as u64) < len {
+        let chunk = reader
+            .read_at(offset + buf.len() as u64, len as usize - buf.len())
+            .await?;
+        ensure!(!chunk.is_empty(), "unexpected end of blob {hash}");
+        buf.extend_from_slice(&chunk);
+    }
+    Ok(buf.into())
+}
+
+/// Read the next CAR section, returning `None` at the end of the file.
+async fn read_block<R: AsyncStreamReader>(reader: &mut R) -> Result<Option<(Cid, Bytes)>> {
+    let Some(len) = read_varint(reader).await? else {
+        return Ok(None);
+    };
+    // the length is untrusted, so check it before allocating the section
+    ensure!(
+        len <= MAX_BLOCK_SIZE + MAX_CID_SIZE,
+        "CAR section too large ({len} bytes)"
+    );
+    let section = read_exact(reader, usize::try_from(len)?).await?;
+    let (cid, cid_len) = Cid::parse(&section)?;
+    Ok(Some((cid, section.slice(cid_len..))))
+}
+
+/// Read an unsigned LEB128 varint, returning `None` on a clean end of the stream.
+async fn read_varint<R: AsyncStreamReader>(reader: &mut R) -> Result<Option<u64>> {
+    let mut value = 0u64;
+    for i in 0..10 {
+        let byte = reader.read_bytes(1).await?;
+        let Some(&byte) = byte.first() else {
+            ensure!(i == 0, "unexpected end of varint");
+            return Ok(None);
+        };
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+    }
+    bail!("varint too long")
+}
+
+/// Read exactly `len` bytes.
+async fn read_exact<R: AsyncStreamReader>(reader: &mut R, len: usize) -> Result<Bytes> {
+    let first = reader.read_bytes(len).await?;
+    if first.len() == len {
+        return Ok(first);
+    }
+    let mut buf = Vec::with_capacity(len);
+    buf.extend_from_slice(&first);
+    while buf.len() < len {
+        let chunk = reader.read_bytes(len - buf.len()).await?;
+        ensure!(!chunk.is_empty(), "unexpected end of CAR file");
+        buf.extend_from_slice(&chunk);
+    }
+    Ok(buf.into())
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Write the head of a CBOR data item with the given major type and argument.
+fn write_cbor_head(out: &mut Vec<u8>, major: u8, value: u64) {
+    let major = major << 5;
+    if value < 24 {
+        out.push(major | value as u8);
+    } else if value <= u8::MAX as u64 {
+        out.extend([major | 24, value as u8]);
+    } else if value <= u16::MAX as u64 {
+        out.push(major | 25);
+        out.extend((value as u16).to_be_bytes());
+    } else if value <= u32::MAX as u64 {
+        out.push(major | 26);
+        out.extend((value as u32).to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend(value.to_be_bytes());
+    }
+}
+
+/// Write a length delimited protobuf field.
+fn write_pb_bytes(out: &mut Vec<u8>, field: u64, value: &[u8]) {
+    write_varint(out, (field << 3) | 2);
+    write_varint(out, value.len() as u64);
+    out.extend_from_slice(value);
+}
+
+/// Write a varint protobuf field.
+fn write_pb_varint(out: &mut Vec<u8>, field: u64, value: u64) {
+    write_varint(out, field << 3);
+    write_varint(out, value);
+}
+
+/// The value of a protobuf field.
+enum PbValue<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+/// A cursor for decoding the small binary structures of the CAR format.
+struct SliceReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> SliceReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(len).context("length overflow")?;
+        let res = self
+            .data
+            .get(self.pos..end)
+            .context("unexpected end of data")?;
+        self.pos = end;
+        Ok(res)
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let mut value = 0u64;
+        for i in 0..10 {
+            let byte = self.bytes(1)?[0];
+            value |= u64::from(byte & 0x7f) << (7 * i);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        bail!("varint too long")
+    }
+
+    /// Read the head of a CBOR data item of the expected major type, returning its argument.
+    fn cbor_head(&mut self, major: u8) -> Result<u64> {
+        let initial = self.bytes(1)?[0];
+        ensure!(initial >> 5 == major, "unexpected CBOR major type");
+        let value = match initial & 0x1f {
+            value @ 0..=23 => u64::from(value),
+            24 => u64::from(self.bytes(1)?[0]),
+            25 => u64::from(u16::from_be_bytes(self.bytes(2)?.try_into()?)),
+            26 => u64::from(u32::from_be_bytes(self.bytes(4)?.try_into()?)),
+            27 => u64::from_be_bytes(self.bytes(8)?.try_into()?),
+            _ => bail!("unsupported CBOR argument"),
+        };
+        Ok(value)
+    }
+
+    /// Read the next protobuf field, returning `None` at the end of the data.
+    fn pb_field(&mut self) -> Result<Option<(u64, PbValue<'a>)>> {
+        if self.pos == self.data.len() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let value = match key & 0x07 {
+            0 => PbValue::Varint(self.varint()?),
+            1 => {
+                self.bytes(8)?;
+                PbValue::Fixed
+            }
+            2 => {
+                let len = self.varint()?;
+                PbValue::Bytes(self.bytes(usize::try_from(len)?)?)
+            }
+            5 => {
+                self.bytes(4)?;
+                PbValue::Fixed
+            }
+            wire_type => bail!("unsupported protobuf wire type {wire_type}"),
+        };
+        Ok(Some((key >> 3, value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use iroh_io::TokioStreamWriter;
+
+    use super::*;
+
+    #[test]
+    fn cid_roundtrip() {
+        let cid = Cid::raw(Hash::new(b"hello world"));
+        assert!(cid.to_string().starts_with("bafkr4i"));
+        let parsed: Cid = cid.to_string().parse().unwrap();
+        assert_eq!(parsed, cid);
+        assert_eq!(Cid::from_bytes(&cid.to_bytes()).unwrap(), cid);
+    }
+
+    #[tokio::test]
+    async fn car_roundtrip_collection() -> Result<()> {
+        let store = crate::store::mem::Store::new();
+        let collection: Collection = [("a", b"hello".as_slice()), ("b", b"world".as_slice())]
+            .into_iter()
+            .map(|(name, data)| (name, Hash::new(data)))
+            .collect();
+        let mut tags = Vec::new();
+        for data in [b"hello".as_slice(), b"world".as_slice()] {
+            tags.push(
+                store
+                    .import_bytes(Bytes::copy_from_slice(data), BlobFormat::Raw)
+                    .await?,
+            );
+        }
+        let root = collection.clone().store(&store).await?;
+
+        let mut car = Vec::new();
+        let cid = export_car(&store, root.hash_and_format(), TokioStreamWriter(&mut car)).await?;
+        assert_eq!(cid.codec(), Cid::DAG_PB);
+        assert_eq!(content_cid(&store, root.hash_and_format()).await?, cid);
+
+        let target = crate::store::mem::Store::new();
+        let imported = import_car(&target, Cursor::new(Bytes::from(car))).await?;
+        assert_eq!(imported.hash_and_format(), root.hash_and_format());
+        let loaded = Collection::load_db(&target, imported.hash()).await?;
+        assert_eq!(loaded, collection);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn car_roundtrip_raw() -> Result<()> {
+        let store = crate::store::mem::Store::new();
+        let tag = store
+            .import_bytes(Bytes::from_static(b"hello world"), BlobFormat::Raw)
+            .await?;
+        let mut car = Vec::new();
+        let cid = export_car(&store, tag.hash_and_format(), TokioStreamWriter(&mut car)).await?;
+        assert_eq!(cid, Cid::raw(*tag.hash()));
+
+        let target = crate::store::mem::Store::new();
+        let imported = import_car(&target, Cursor::new(Bytes::from(car))).await?;
+        assert_eq!(imported.hash_and_format(), tag.hash_and_format());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn car_roundtrip_large_raw() -> Result<()> {
+        let store = crate::store::mem::Store::new();
+        let data: Vec<u8> = (0..3 * 1024 * 1024 + 5).map(|i| (i % 251) as u8).collect();
+        let tag = store.import_bytes(data.into(), BlobFormat::Raw).await?;
+        let mut car = Vec::new();
+        let cid = export_car(&store, tag.hash_and_format(), TokioStreamWriter(&mut car)).await?;
+        assert_eq!(cid.codec(), Cid::DAG_PB);
+        assert_eq!(content_cid(&store, tag.hash_and_format()).await?, cid);
+
+        let target = crate::store::mem::Store::new();
+        let imported = import_car(&target, Cursor::new(Bytes::from(car))).await?;
+        assert_eq!(imported.hash_and_format(), tag.hash_and_format());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn car_collection_links_sorted() -> Result<()> {
+        let store = crate::store::mem::Store::new();
+        let large: Bytes = (0..MAX_BLOCK_SIZE + 1).map(|i| (i % 7) as u8).collect();
+        let entries = [
+            ("c", Bytes::from_static(b"c")),
+            ("a", large),
+            ("b", Bytes::from_static(b"b")),
+        ];
+        let mut tags = Vec::new();
+        let mut collection = Collection::default();
+        for (name, data) in entries {
+            let tag = store.import_bytes(data, BlobFormat::Raw).await?;
+            collection.push(name.to_string(), *tag.hash());
+            tags.push(tag);
+        }
+        let root = collection.clone().store(&store).await?;
+        let links = directory_links(&store, &collection).await?;
+        let names: Vec<_> = links.iter().map(|(name, _, _)| name.as_str()).collect();
+        assert_eq!(names, ["a", "b", "c"]);
+
+        let mut car = Vec::new();
+        export_car(&store, root.hash_and_format(), TokioStreamWriter(&mut car)).await?;
+        let target = crate::store::mem::Store::new();
+        let imported = import_car(&target, Cursor::new(Bytes::from(car))).await?;
+        let loaded = Collection::load_db(&target, imported.hash()).await?;
+        let expected: Collection = links
+            .iter()
+            .map(|(name, hash, _)| (name.clone(), *hash))
+            .collect();
+        assert_eq!(loaded, expected);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn car_block_size_limit() -> Result<()> {
+        // a section length larger than the limit is rejected before reading the section
+        let mut car = Vec::new();
+        let root = Cid::raw(Hash::new(b"hello"));
+        write_header(&mut TokioStreamWriter(&mut car), &root).await?;
+        write_varint(&mut car, u64::MAX >> 1);
+        let target = crate::store::mem::Store::new();
+        let res = import_car(&target, Cursor::new(Bytes::from(car))).await;
+        assert!(res.unwrap_err().to_string().contains("too large"));
+        Ok(())
+    }
+}
@@ -13,6 +13,7 @@ use iroh_io::stats::{
 };
 use iroh_io::{AsyncSliceReader, AsyncStreamWriter, TokioStreamWriter};
 use iroh_net::endpoint::{self, RecvStream, SendStream};
+use iroh_net::NodeId;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, debug_span, info, trace, warn};
 use tracing_futures::Instrument;
@@ -25,7 +26,10 @@ use crate::util::Tag;
 use crate::{BlobFormat, Hash};
 
 /// Events emitted by the provider informing about the current status.
+///
+/// New events and fields may be added in future releases.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum Event {
     /// A new collection or tagged blob has been added
     TaggedBlobAdded {
@@ -40,6 +44,8 @@ pub enum Event {
     ClientConnected {
         /// An unique connection id.
         connection_id: u64,
+        /// The node id of the client, if it could be determined.
+        node_id: Option<NodeId>,
     },
     /// A request was received from a client.
     GetRequestReceived {
@@ -416,6 +422,7 @@ pub async fn handle_connection<D: Map>(
 ) {
     let remote_addr = connection.remote_address();
     let connection_id = connection.stable_id() as u64;
+    let node_id = endpoint::get_remote_node_id(&connection).ok();
     let span = debug_span!("connection", connection_id, %remote_addr);
     async move {
         while let Ok((writer, reader)) = connection.accept_bi().await {
@@ -429,7 +436,10 @@ pub async fn handle_connection<D: Map>(
                 inner: writer,
             };
             events
-                .send(|| Event::ClientConnected { connection_id })
+                .send(|| Event::ClientConnected {
+                    connection_id,
+                    node_id,
+                })
                 .await;
             let db = db.clone();
             rt.spawn_detached(|| {
@@ -85,7 +85,7 @@ use iroh_io::AsyncSliceReader;
 use redb::{AccessGuard, DatabaseError, ReadableTable, StorageError};
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
-use tokio::io::AsyncWriteExt;
+use tokio::{io::AsyncWriteExt, sync::broadcast};
 use tracing::trace_span;
 
 mod migrate_redb_v1_v2;
@@ -124,8 +124,9 @@ use self::test_support::EntryData;
 
 use super::{
     bao_file::{BaoFileConfig, BaoFileHandle, BaoFileHandleWeak, CreateCb},
-    temp_name, BaoBatchWriter, BaoBlobSize, ConsistencyCheckProgress, EntryStatus, ExportMode,
-    ExportProgressCb, ImportMode, ImportProgress, Map, ReadableStore, TempCounterMap,
+    temp_name, BaoBatchWriter, BaoBlobSize, ConsistencyCheckProgress, EntryStatus, Event,
+    ExportMode, ExportProgressCb, ImportMode, ImportProgress, Map, ReadableStore, TempCounterMap,
+    EVENT_CHANNEL_CAP,
 };
 
 /// Location of the data.
@@ -753,6 +754,7 @@ impl Store {
 struct StoreInner {
     tx: async_channel::Sender<ActorMessage>,
     temp: Arc<RwLock<TempCounterMap>>,
+    events: broadcast::Sender<Event>,
     handle: Option<std::thread::JoinHandle<()>>,
     path_options: Arc<PathOptions>,
 }
@@ -787,7 +789,14 @@ impl StoreInner {
         );
         std::fs::create_dir_all(path.parent().unwrap())?;
         let temp: Arc<RwLock<TempCounterMap>> = Default::default();
-        let (actor, tx) = Actor::new(&path, options.clone(), temp.clone(), rt.clone())?;
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAP);
+        let (actor, tx) = Actor::new(
+            &path,
+            options.clone(),
+            temp.clone(),
+            events.clone(),
+            rt.clone(),
+        )?;
         let handle = std::thread::Builder::new()
             .name("redb-actor".to_string())
             .spawn(move || {
@@ -801,6 +810,7 @@ impl StoreInner {
         Ok(Self {
             tx,
             temp,
+            events,
             handle: Some(handle),
             path_options: Arc::new(options.path),
         })
@@ -1150,6 +1160,7 @@ struct ActorState {
     handles: BTreeMap<Hash, BaoFileHandleWeak>,
     protected: BTreeSet<Hash>,
     temp: Arc<RwLock<TempCounterMap>>,
+    events: broadcast::Sender<Event>,
     msgs_rx: async_channel::Receiver<ActorMessage>,
     create_options: Arc<BaoFileConfig>,
     options: Options,
@@ -1472,6 +1483,10 @@ impl super::Store for Store {
     async fn shutdown(&self) {
         self.0.shutdown().await;
     }
+
+    fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.0.events.subscribe()
+    }
 }
 
 pub(super) async fn gc_sweep_task<'a>(
@@ -1509,6 +1524,7 @@ impl Actor {
         path: &Path,
         options: Options,
         temp: Arc<RwLock<TempCounterMap>>,
+        events: broadcast::Sender<Event>,
         rt: tokio::runtime::Handle,
     ) -> ActorResult<(Self, async_channel::Sender<ActorMessage>)> {
         let db = match redb::Database::create(path) {
@@ -1545,6 +1561,7 @@ impl Actor {
                 db,
                 state: ActorState {
                     temp,
+                    events,
                     handles: BTreeMap::new(),
                     protected: BTreeSet::new(),
                     msgs_rx: rx,
@@ -1890,6 +1907,7 @@ impl ActorState {
         }
         let entry = tables.blobs.get(hash)?;
         let entry = entry.map(|x| x.value()).unwrap_or_default();
+        let was_complete = matches!(entry, EntryState::Complete { .. });
         let data_location = data_location.discard_inline_data();
         let outboard_location = outboard_location.discard_extra_data();
         let entry = entry.union(EntryState::Complete {
@@ -1897,6 +1915,9 @@ impl ActorState {
             outboard_location,
         })?;
         tables.blobs.insert(hash, entry)?;
+        if !was_complete {
+            self.events.send(Event::EntryComplete { hash }).ok();
+        }
         Ok((tag, data_size))
     }
 
@@ -1935,6 +1956,7 @@ impl ActorState {
                 }
             }
         } else {
+            self.events.send(Event::EntryPartial { hash }).ok();
             BaoFileHandle::incomplete_mem(self.create_options.clone(), hash)
         };
         self.handles.insert(hash, handle.downgrade());
@@ -2266,6 +2288,7 @@ impl ActorState {
                     tables.inline_outboard.insert(hash, outboard.as_ref())?;
                 }
             }
+            self.events.send(Event::EntryComplete { hash }).ok();
         }
         Ok(())
     }
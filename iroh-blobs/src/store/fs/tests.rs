@@ -209,6 +209,37 @@ async fn get_or_create_cases() {
     }
 }
 
+#[tokio::test]
+async fn entry_events() {
+    let (_tempdir, db) = create_test_db().await;
+    let mut events = db.subscribe();
+    // a new partial entry that is completed
+    let data = random_test_data(MID_SIZE as usize);
+    let (hash, reader) = simulate_remote(&data);
+    let entry = db.get_or_create(hash, 0).await.unwrap();
+    assert_eq!(events.recv().await.unwrap(), Event::EntryPartial { hash });
+    let writer = entry.batch_writer().await.unwrap();
+    decode_response_into_batch(hash, IROH_BLOCK_SIZE, ChunkRanges::all(), reader, writer)
+        .await
+        .unwrap();
+    db.insert_complete(entry).await.unwrap();
+    assert_eq!(events.recv().await.unwrap(), Event::EntryComplete { hash });
+    // an import
+    let tt = db
+        .import_bytes(Bytes::from_static(b"events"), BlobFormat::Raw)
+        .await
+        .unwrap();
+    let hash = *tt.hash();
+    assert_eq!(events.recv().await.unwrap(), Event::EntryComplete { hash });
+    // importing the same data again does not produce another event
+    let _tt = db
+        .import_bytes(Bytes::from_static(b"events"), BlobFormat::Raw)
+        .await
+        .unwrap();
+    db.sync().await.unwrap();
+    assert!(events.try_recv().is_err());
+}
+
 /// Import mem cases, small (data inline, outboard none), mid (data file, outboard inline), large (data file, outboard file)
 #[tokio::test]
 async fn import_mem_cases() {
@@ -17,6 +17,7 @@ use std::{
     sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
     time::SystemTime,
 };
+use tokio::sync::broadcast;
 
 use crate::{
     store::{
@@ -30,15 +31,25 @@ use crate::{
 };
 
 use super::{
-    temp_name, BaoBatchWriter, ConsistencyCheckProgress, ExportMode, ExportProgressCb, ImportMode,
-    ImportProgress, Map, TempCounterMap,
+    temp_name, BaoBatchWriter, ConsistencyCheckProgress, Event, ExportMode, ExportProgressCb,
+    ImportMode, ImportProgress, Map, TempCounterMap, EVENT_CHANNEL_CAP,
 };
 
 /// A fully featured in memory database for iroh-blobs, including support for
 /// partial blobs.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct Store {
     inner: Arc<StoreInner>,
+    events: broadcast::Sender<Event>,
+}
+
+impl Default for Store {
+    fn default() -> Self {
+        Self {
+            inner: Default::default(),
+            events: broadcast::channel(EVENT_CHANNEL_CAP).0,
+        }
+    }
 }
 
 #[derive(Debug, Default)]
@@ -101,7 +112,14 @@ impl Store {
             }),
             complete: true,
         };
-        self.write_lock().entries.insert(hash, entry);
+        let prev = {
+            let mut state = self.write_lock();
+            state.partial.remove(&hash);
+            state.entries.insert(hash, entry)
+        };
+        if !prev.is_some_and(|entry| entry.complete) {
+            self.events.send(Event::EntryComplete { hash }).ok();
+        }
         Ok(tag)
     }
 
@@ -242,6 +260,7 @@ impl super::Store for Store {
         for hash in hashes {
             if !state.temp.contains(&hash) {
                 state.entries.remove(&hash);
+                state.partial.remove(&hash);
             }
         }
         Ok(())
@@ -252,11 +271,17 @@ impl super::Store for Store {
     async fn sync(&self) -> io::Result<()> {
         Ok(())
     }
+
+    fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.events.subscribe()
+    }
 }
 
 #[derive(Debug, Default)]
 struct StateInner {
     entries: BTreeMap<Hash, Entry>,
+    /// Hashes of the incomplete entries that were handed out, to announce each only once.
+    partial: BTreeSet<Hash>,
     tags: BTreeMap<Tag, HashAndFormat>,
     temp: TempCounterMap,
 }
@@ -364,6 +389,14 @@ impl super::MapMut for Store {
     }
 
     async fn get_or_create(&self, hash: Hash, _size: u64) -> std::io::Result<Entry> {
+        let created = {
+            let mut state = self.write_lock();
+            !state.entries.get(&hash).is_some_and(|entry| entry.complete)
+                && state.partial.insert(hash)
+        };
+        if created {
+            self.events.send(Event::EntryPartial { hash }).ok();
+        }
         let entry = Entry {
             inner: Arc::new(EntryInner {
                 hash,
@@ -401,7 +434,9 @@ impl super::MapMut for Store {
             .unwrap_or_default();
         if !complete {
             entry.complete = true;
+            inner.partial.remove(&hash);
             inner.entries.insert(hash, entry);
+            self.events.send(Event::EntryComplete { hash }).ok();
         }
         Ok(())
     }
@@ -11,7 +11,7 @@ use genawaiter::rc::{Co, Gen};
 use iroh_base::rpc::RpcError;
 use iroh_io::AsyncSliceReader;
 use serde::{Deserialize, Serialize};
-use tokio::io::AsyncRead;
+use tokio::{io::AsyncRead, sync::broadcast};
 
 use crate::{
     hashseq::parse_hash_seq,
@@ -373,6 +373,17 @@ pub trait Store: ReadableStore + MapMut + std::fmt::Debug {
     /// Sync the store.
     fn sync(&self) -> impl Future<Output = io::Result<()>> + Send;
 
+    /// Subscribe to events of this store.
+    ///
+    /// Only events that happen after subscribing are reported. A subscriber that does not keep
+    /// up with the events will get a [`broadcast::error::RecvError::Lagged`] error.
+    ///
+    /// The default implementation reports no events: the returned channel is closed right away.
+    /// Stores that change have to override it.
+    fn subscribe(&self) -> broadcast::Receiver<Event> {
+        broadcast::channel(1).1
+    }
+
     /// Validate the database
     ///
     /// This will check that the file and outboard content is correct for all complete
@@ -952,10 +963,26 @@ pub enum ValidateProgress {
 }
 
 /// Database events
+///
+/// New events may be added in future releases.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum Event {
     /// A GC was started
     GcStarted,
     /// A GC was completed
     GcCompleted,
+    /// A partial entry was created, data for it is being added
+    EntryPartial {
+        /// The hash of the entry
+        hash: Hash,
+    },
+    /// An entry became complete
+    EntryComplete {
+        /// The hash of the entry
+        hash: Hash,
+    },
 }
+
+/// Capacity of the event channel of the stores in this crate.
+pub(super) const EVENT_CHANNEL_CAP: usize = 1024;
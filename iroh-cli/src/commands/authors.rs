@@ -1,16 +1,34 @@
 //! Define the commands to manage authors.
 
+use std::path::PathBuf;
+
 use crate::config::ConsoleEnv;
-use anyhow::{bail, Result};
-use clap::Parser;
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
 use derive_more::FromStr;
+use dialoguer::Password;
 use futures_lite::StreamExt;
 use iroh::{
     base::base32::fmt_short,
     client::Iroh,
-    docs::{Author, AuthorId},
+    docs::{is_armored, Author, AuthorId},
 };
 
+/// Commands to manage the keystore.
+#[derive(Debug, Clone, Subcommand)]
+pub enum KeystoreCommands {
+    /// Print whether the keystore is disabled, locked or unlocked.
+    Status,
+    /// Encrypt the stored secret keys with a passphrase.
+    Enable,
+    /// Store the secret keys in cleartext again.
+    Disable,
+    /// Unlock the keystore, so that the secret keys can be used.
+    Unlock,
+    /// Lock the keystore.
+    Lock,
+}
+
 /// Commands to manage authors.
 #[derive(Debug, Clone, Parser)]
 pub enum AuthorCommands {
@@ -25,9 +43,30 @@ pub enum AuthorCommands {
     /// Delete an author.
     Delete { author: AuthorId },
     /// Export an author.
-    Export { author: AuthorId },
+    ///
+    /// By default, the author key is encrypted with a passphrase and printed as armored text.
+    Export {
+        author: AuthorId,
+        /// Write the armored key to this file instead of printing it.
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+        /// Print the secret key without encrypting it.
+        #[clap(long, conflicts_with = "output")]
+        plain: bool,
+    },
     /// Import an author.
+    ///
+    /// Accepts a secret key, or the path to an armored key file created with `export`.
     Import { author: String },
+    /// Manage the encryption of the stored secret keys with a passphrase.
+    ///
+    /// When the keystore is enabled, the secret keys of authors and documents are stored encrypted.
+    /// After a restart, the node has to be unlocked before it can write to documents. Set the
+    /// IROH_DOCS_PASSPHRASE environment variable to unlock the node when it starts.
+    Keystore {
+        #[clap(subcommand)]
+        command: KeystoreCommands,
+    },
     /// Print the default author for this node.
     Default {
         /// Switch to the default author (Note: only works in the Iroh console).
@@ -81,25 +120,99 @@ impl AuthorCommands {
                 iroh.authors().delete(author).await?;
                 println!("Deleted author {}", fmt_short(author.as_bytes()));
             }
-            Self::Export { author } => match iroh.authors().export(author).await? {
-                Some(author) => {
-                    println!("{}", author);
+            Self::Export {
+                author,
+                output,
+                plain,
+            } => {
+                if plain {
+                    match iroh.authors().export(author).await? {
+                        Some(author) => println!("{}", author),
+                        None => println!("No author found {}", fmt_short(author)),
+                    }
+                    return Ok(());
                 }
-                None => {
-                    println!("No author found {}", fmt_short(author));
+                let passphrase = read_passphrase("Passphrase for the key file", true)?;
+                match iroh.authors().export_armored(author, &passphrase).await? {
+                    Some(armored) => match output {
+                        Some(path) => {
+                            tokio::fs::write(&path, armored).await?;
+                            println!("Exported {} to {}", fmt_short(author), path.display());
+                        }
+                        None => print!("{}", armored),
+                    },
+                    None => println!("No author found {}", fmt_short(author)),
                 }
-            },
-            Self::Import { author } => match Author::from_str(&author) {
-                Ok(author) => {
-                    let id = author.id();
-                    iroh.authors().import(author).await?;
+            }
+            Self::Import { author } => {
+                let path = PathBuf::from(&author);
+                let text = if path.is_file() {
+                    tokio::fs::read_to_string(&path)
+                        .await
+                        .with_context(|| format!("Failed to read {}", path.display()))?
+                } else {
+                    author
+                };
+                if is_armored(&text) {
+                    let passphrase = read_passphrase("Passphrase for the key file", false)?;
+                    let id = iroh.authors().import_armored(&text, &passphrase).await?;
                     println!("Imported {}", fmt_short(id));
+                } else {
+                    match Author::from_str(text.trim()) {
+                        Ok(author) => {
+                            let id = author.id();
+                            iroh.authors().import(author).await?;
+                            println!("Imported {}", fmt_short(id));
+                        }
+                        Err(err) => {
+                            eprintln!("Invalid author key: {}", err);
+                        }
+                    }
                 }
-                Err(err) => {
-                    eprintln!("Invalid author key: {}", err);
-                }
-            },
+            }
+            Self::Keystore { command } => command.run(iroh).await?,
+        }
+        Ok(())
+    }
+}
+
+impl KeystoreCommands {
+    /// Runs the keystore command given an iroh client.
+    pub async fn run(self, iroh: &Iroh) -> Result<()> {
+        let authors = iroh.authors();
+        match self {
+            Self::Status => {
+                println!("{}", authors.keystore_status().await?);
+            }
+            Self::Enable => {
+                let passphrase = read_passphrase("New passphrase", true)?;
+                authors.enable_keystore(passphrase).await?;
+                println!("Keystore enabled");
+            }
+            Self::Disable => {
+                let passphrase = read_passphrase("Passphrase", false)?;
+                authors.disable_keystore(passphrase).await?;
+                println!("Keystore disabled");
+            }
+            Self::Unlock => {
+                let passphrase = read_passphrase("Passphrase", false)?;
+                authors.unlock(passphrase).await?;
+                println!("Keystore unlocked");
+            }
+            Self::Lock => {
+                authors.lock().await?;
+                println!("Keystore locked");
+            }
         }
         Ok(())
     }
 }
+
+/// Reads a passphrase from the terminal, asking twice if `confirm` is set.
+fn read_passphrase(prompt: &str, confirm: bool) -> Result<String> {
+    let mut input = Password::new().with_prompt(prompt);
+    if confirm {
+        input = input.with_confirmation("Repeat passphrase", "Passphrases do not match");
+    }
+    Ok(input.interact()?)
+}
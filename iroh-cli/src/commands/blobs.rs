@@ -23,7 +23,7 @@ use iroh::{
     client::{
         blobs::{
             BlobInfo, BlobStatus, CollectionInfo, DownloadMode, DownloadOptions,
-            IncompleteBlobInfo, WrapOption,
+            IncompleteBlobInfo, ProviderEvent, WrapOption,
         },
         Iroh,
     },
@@ -144,6 +144,14 @@ pub enum BlobCommands {
     /// Delete content on the node.
     #[clap(subcommand)]
     Delete(DeleteCommands),
+    /// Print the transfers served by the running node to other nodes.
+    ///
+    /// Shows which nodes connect and what they download. Runs until interrupted.
+    ServeLog {
+        /// Also print progress updates of running transfers.
+        #[clap(long, default_value_t = false)]
+        progress: bool,
+    },
     /// Get a ticket to share this blob.
     Share {
         /// Hash of the blob to share.
@@ -199,8 +207,9 @@ impl BlobCommands {
                 tag,
                 queued,
             } => {
-                let (node_addr, hash, format) = match ticket {
+                let (node_addr, hash, format, remote_store) = match ticket {
                     TicketOrHash::Ticket(ticket) => {
+                        let remote_store = ticket.store().map(ToOwned::to_owned);
                         let (node_addr, hash, blob_format) = ticket.into_parts();
 
                         // create the node address with the appropriate overrides
@@ -228,7 +237,7 @@ impl BlobCommands {
                             None => blob_format,
                         };
 
-                        (node_addr, hash, blob_format)
+                        (node_addr, hash, blob_format, remote_store)
                     }
                     TicketOrHash::Hash(hash) => {
                         // check if the blob format has an override
@@ -243,7 +252,7 @@ impl BlobCommands {
                         };
 
                         let node_addr = NodeAddr::from_parts(node, relay_url, address);
-                        (node_addr, hash, blob_format)
+                        (node_addr, hash, blob_format, None)
                     }
                 };
 
@@ -270,6 +279,7 @@ impl BlobCommands {
                             nodes: vec![node_addr],
                             tag,
                             mode,
+                            remote_store,
                         },
                     )
                     .await?;
@@ -358,6 +368,7 @@ impl BlobCommands {
                 source: path,
                 options,
             } => add_with_opts(iroh, path, options).await,
+            Self::ServeLog { progress } => serve_log(iroh, progress).await,
             Self::Share {
                 hash,
                 addr_options,
@@ -587,6 +598,92 @@ pub async fn consistency_check(iroh: &Iroh, verbose: u8, repair: bool) -> Result
     Ok(())
 }
 
+/// Prints the events emitted by the running node while it provides blobs to other nodes.
+pub async fn serve_log(iroh: &Iroh, progress: bool) -> Result<()> {
+    let mut events = iroh.blobs().provider_events().await?;
+    while let Some(event) = events.next().await {
+        match event? {
+            ProviderEvent::ClientConnected {
+                connection_id,
+                node_id,
+            } => {
+                let node_id = node_id.map_or("unknown node".to_string(), |id| id.to_string());
+                println!("[{connection_id}] connected: {node_id}");
+            }
+            ProviderEvent::GetRequestReceived {
+                connection_id,
+                request_id,
+                hash,
+            } => {
+                println!("[{connection_id}/{request_id}] requested {hash}");
+            }
+            ProviderEvent::TransferHashSeqStarted {
+                connection_id,
+                request_id,
+                num_blobs,
+            } => {
+                println!("[{connection_id}/{request_id}] sending sequence of {num_blobs} blobs");
+            }
+            ProviderEvent::TransferProgress {
+                connection_id,
+                request_id,
+                hash,
+                end_offset,
+            } => {
+                if progress {
+                    println!(
+                        "[{connection_id}/{request_id}] sent {} of {hash}",
+                        HumanBytes(end_offset)
+                    );
+                }
+            }
+            ProviderEvent::TransferBlobCompleted {
+                connection_id,
+                request_id,
+                hash,
+                index,
+                size,
+            } => {
+                println!(
+                    "[{connection_id}/{request_id}] sent blob {index} {hash} ({})",
+                    HumanBytes(size)
+                );
+            }
+            ProviderEvent::TransferCompleted {
+                connection_id,
+                request_id,
+                stats,
+            } => {
+                println!(
+                    "[{connection_id}/{request_id}] {} {} in {}",
+                    style("completed").green(),
+                    HumanBytes(stats.bytes_sent),
+                    HumanDuration(stats.duration)
+                );
+            }
+            ProviderEvent::TransferAborted {
+                connection_id,
+                request_id,
+                stats,
+            } => {
+                let sent = stats.map_or(0, |stats| stats.bytes_sent);
+                println!(
+                    "[{connection_id}/{request_id}] {} after {}",
+                    style("aborted").red(),
+                    HumanBytes(sent)
+                );
+            }
+            ProviderEvent::TaggedBlobAdded { hash, format, tag } => {
+                println!("added {format} {hash} with tag {tag}");
+            }
+            ProviderEvent::Lagged { missed } => {
+                eprintln!("{}", style(format!("missed {missed} events")).yellow());
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Checks the validity of the blobs on the running node, and repairs anything invalid if instructed.
 pub async fn validate(iroh: &Iroh, verbose: u8, repair: bool) -> Result<()> {
     let mut state = ValidateProgressState::new();
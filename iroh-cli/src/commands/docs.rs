@@ -13,13 +13,14 @@ use iroh::{
     blobs::{provider::AddProgress, util::SetTagOption, Hash, Tag},
     client::{
         blobs::WrapOption,
-        docs::{Doc, Entry, LiveEvent, Origin, ShareMode},
+        docs::{Doc, Entry, FolderSyncEvent, FolderSyncOpts, LiveEvent, Origin, ShareMode},
         Iroh,
     },
     docs::{
         store::{DownloadPolicy, FilterKind, Query, SortDirection},
-        AuthorId, DocTicket, NamespaceId,
+        AuthorId, DocTicket, InviteTicket, NamespaceId,
     },
+    net::NodeId,
     util::fs::{path_content_info, path_to_key, PathContent},
 };
 use std::{
@@ -27,7 +28,7 @@ use std::{
     collections::BTreeMap,
     path::{Path, PathBuf},
     rc::Rc,
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 use tokio::io::AsyncReadExt;
 
@@ -123,6 +124,52 @@ pub enum DocCommands {
         #[clap(long, default_value_t = AddrInfoOptions::Id)]
         addr_options: AddrInfoOptions,
     },
+    /// Create an invitation to a document.
+    ///
+    /// Unlike the tickets created with `doc share`, an invitation can expire and can be limited to
+    /// a single node. If the document has a restricted access policy, the invited node is added to
+    /// it once it redeems the invitation. The invited node only presents the invitation to this
+    /// node.
+    Invite {
+        /// Document to operate on.
+        ///
+        /// Required unless the document is set through the IROH_DOC environment variable.
+        /// Within the Iroh console, the active document can also set with `doc switch`.
+        #[clap(short, long)]
+        doc: Option<NamespaceId>,
+        /// The access granted by the invitation.
+        mode: ShareMode,
+        /// Number of seconds after which the invitation expires.
+        #[clap(long)]
+        expires_in: Option<u64>,
+        /// Only allow a single node to redeem the invitation.
+        #[clap(long)]
+        single_use: bool,
+        /// Only allow the node with this id to redeem the invitation.
+        #[clap(long)]
+        invitee: Option<NodeId>,
+        /// Options to configure the address information in the generated ticket.
+        ///
+        /// Use `relay-and-addresses` in networks with no internet connectivity.
+        #[clap(long, default_value_t = AddrInfoOptions::Id)]
+        addr_options: AddrInfoOptions,
+    },
+    /// Join a document from an invitation ticket.
+    JoinInvite {
+        ticket: InviteTicket,
+        /// Switch to the joined document (only in the Iroh console).
+        #[clap(long)]
+        switch: bool,
+    },
+    /// List the redeemed invitations of a document.
+    Redemptions {
+        /// Document to operate on.
+        ///
+        /// Required unless the document is set through the IROH_DOC environment variable.
+        /// Within the Iroh console, the active document can also set with `doc switch`.
+        #[clap(short, long)]
+        doc: Option<NamespaceId>,
+    },
     /// Set an entry in a document.
     Set {
         /// Document to operate on.
@@ -255,6 +302,62 @@ pub enum DocCommands {
         #[clap(short, long)]
         out: String,
     },
+    /// Export signed entries of a document to a bundle file
+    ///
+    /// The bundle can be imported on another node with `doc import-bundle`, without a connection
+    /// between the nodes.
+    ExportBundle {
+        /// Document to operate on.
+        ///
+        /// Required unless the document is set through the IROH_DOC environment variable.
+        /// Within the Iroh console, the active document can also be set with `doc switch`.
+        #[clap(short, long)]
+        doc: Option<NamespaceId>,
+        /// Only export entries with keys that start with this prefix (parsed as UTF-8 string).
+        #[clap(short, long)]
+        prefix: Option<String>,
+        /// Include the content of the entries that is available on this node.
+        #[clap(long)]
+        content: bool,
+        /// Path to export to
+        #[clap(short, long)]
+        out: String,
+    },
+    /// Import the signed entries of a bundle file into a document
+    ///
+    /// All signatures are verified before any entry is inserted. The document is imported with
+    /// read access if it does not exist on this node.
+    ImportBundle {
+        /// Path to the bundle file
+        path: String,
+        /// Switch to the imported document (only in the Iroh console).
+        #[clap(long)]
+        switch: bool,
+    },
+    /// Continuously sync a document with a local folder
+    ///
+    /// Changes to files in the folder are imported into the document, and entries inserted or
+    /// deleted by peers are written to the folder, until the command is stopped.
+    SyncFolder {
+        /// Document to operate on.
+        ///
+        /// Required unless the document is set through the IROH_DOC environment variable.
+        /// Within the Iroh console, the active document can also be set with `doc switch`.
+        #[clap(short, long)]
+        doc: Option<NamespaceId>,
+        /// Author of the entries for local changes.
+        ///
+        /// Required unless the author is set through the IROH_AUTHOR environment variable.
+        /// Within the Iroh console, the active author can also be set with `author switch`.
+        #[clap(long)]
+        author: Option<AuthorId>,
+        /// Prefix of the keys that are synced with the folder (parsed as UTF-8 string). Defaults
+        /// to no prefix
+        #[clap(long)]
+        prefix: Option<String>,
+        /// Path to the local folder
+        path: String,
+    },
     /// Watch for changes and events on a document
     Watch {
         /// Document to operate on.
@@ -295,6 +398,8 @@ pub enum Sorting {
     Author,
     /// Sort by key, then author
     Key,
+    /// Sort by timestamp, then author, then key
+    Timestamp,
 }
 
 impl From<Sorting> for iroh::docs::store::SortBy {
@@ -302,6 +407,7 @@ impl From<Sorting> for iroh::docs::store::SortBy {
         match value {
             Sorting::Author => Self::AuthorKey,
             Sorting::Key => Self::KeyAuthor,
+            Sorting::Timestamp => Self::Timestamp,
         }
     }
 }
@@ -355,6 +461,52 @@ impl DocCommands {
                 let ticket = doc.share(mode, addr_options).await?;
                 println!("{}", ticket);
             }
+            Self::Invite {
+                doc,
+                mode,
+                expires_in,
+                single_use,
+                invitee,
+                addr_options,
+            } => {
+                let doc = get_doc(iroh, env, doc).await?;
+                let expires = expires_in.map(|secs| {
+                    let now = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .expect("time drift")
+                        .as_micros() as u64;
+                    now + secs * 1_000_000
+                });
+                let ticket = doc
+                    .invite(mode, expires, single_use, invitee, addr_options)
+                    .await?;
+                println!("{}", ticket);
+            }
+            Self::JoinInvite { ticket, switch } => {
+                if switch && !env.is_console() {
+                    bail!("The --switch flag is only supported within the Iroh console.");
+                }
+
+                let doc = iroh.docs().import_invite(ticket).await?;
+                println!("{}", doc.id());
+
+                if switch {
+                    env.set_doc(doc.id())?;
+                    println!("Active doc is now {}", fmt_short(doc.id().as_bytes()));
+                }
+            }
+            Self::Redemptions { doc } => {
+                let doc = get_doc(iroh, env, doc).await?;
+                for redemption in doc.redemptions().await? {
+                    println!(
+                        "{} {} {} {}",
+                        redemption.invite,
+                        fmt_short(redemption.node),
+                        redemption.mode,
+                        redemption.timestamp,
+                    );
+                }
+            }
             Self::Set {
                 doc,
                 author,
@@ -542,6 +694,84 @@ impl DocCommands {
                     Err(err) => println!("<failed to get content: {err}>"),
                 }
             }
+            Self::ExportBundle {
+                doc,
+                prefix,
+                content,
+                out,
+            } => {
+                let doc = get_doc(iroh, env, doc).await?;
+                let mut query = Query::all().include_empty();
+                if let Some(prefix) = prefix {
+                    query = query.key_prefix(prefix);
+                }
+                let path = std::env::current_dir()?.join(canonicalize_path(&out)?);
+                let outcome = doc.export_bundle(query, &path, content).await?;
+                println!(
+                    "exported {} entries and {} blobs to {}",
+                    outcome.entries,
+                    outcome.blobs,
+                    path.display()
+                );
+            }
+            Self::ImportBundle { path, switch } => {
+                if switch && !env.is_console() {
+                    bail!("The --switch flag is only supported within the Iroh console.");
+                }
+
+                let path = canonicalize_path(&path)?.canonicalize()?;
+                let (doc, outcome) = iroh.docs().import_bundle(&path).await?;
+                println!(
+                    "imported {} entries and {} blobs into {}",
+                    outcome.entries,
+                    outcome.blobs,
+                    doc.id()
+                );
+
+                if switch {
+                    env.set_doc(doc.id())?;
+                    println!("Active doc is now {}", fmt_short(doc.id().as_bytes()));
+                }
+            }
+            Self::SyncFolder {
+                doc,
+                author,
+                prefix,
+                path,
+            } => {
+                let doc = get_doc(iroh, env, doc).await?;
+                let author = author.unwrap_or(env.author());
+                let root = canonicalize_path(&path)?;
+                std::fs::create_dir_all(&root)?;
+                let opts = FolderSyncOpts {
+                    author,
+                    root: root.clone(),
+                    prefix: prefix.unwrap_or_default(),
+                };
+                let mut sync = doc.sync_folder(opts).await?;
+                println!("Syncing {} with the document...", root.display());
+                while let Some(event) = sync.next().await {
+                    match event? {
+                        FolderSyncEvent::Imported { path } => {
+                            println!("imported: {}", path.display())
+                        }
+                        FolderSyncEvent::Deleted { path } => {
+                            println!("deleted:  {}", path.display())
+                        }
+                        FolderSyncEvent::Exported { path } => {
+                            println!("exported: {}", path.display())
+                        }
+                        FolderSyncEvent::Removed { path } => {
+                            println!("removed:  {}", path.display())
+                        }
+                        FolderSyncEvent::Conflict { path, copy } => println!(
+                            "conflict: {} (local version moved to {})",
+                            path.display(),
+                            copy.display()
+                        ),
+                    }
+                }
+            }
             Self::Watch { doc } => {
                 let doc = get_doc(iroh, env, doc).await?;
                 let mut stream = doc.subscribe().await?;
@@ -554,6 +784,20 @@ impl DocCommands {
                                 fmt_entry(&doc, &entry, DisplayContentMode::Auto).await
                             )
                         }
+                        LiveEvent::InsertLocalBatch { entries } => {
+                            for entry in entries {
+                                println!(
+                                    "local change:  {}",
+                                    fmt_entry(&doc, &entry, DisplayContentMode::Auto).await
+                                )
+                            }
+                        }
+                        LiveEvent::Replayed { entry } => {
+                            println!(
+                                "existing:      {}",
+                                fmt_entry(&doc, &entry, DisplayContentMode::Auto).await
+                            )
+                        }
                         LiveEvent::InsertRemote {
                             entry,
                             from,
@@ -605,6 +849,16 @@ impl DocCommands {
                                 ),
                             }
                         }
+                        LiveEvent::SessionFinished(event) => {
+                            println!(
+                                "sync session with peer {} finished ({} docs, {} failed, received {}, sent {})",
+                                fmt_short(event.peer),
+                                event.namespaces.len(),
+                                event.failed,
+                                event.entries_received,
+                                event.entries_sent
+                            )
+                        }
                         LiveEvent::NeighborUp(peer) => {
                             println!("neighbor peer up: {peer:?}");
                         }
@@ -614,6 +868,9 @@ impl DocCommands {
                         LiveEvent::PendingContentReady => {
                             println!("all pending content is now ready")
                         }
+                        LiveEvent::Migrated { to, author } => {
+                            println!("document moved to {to} by author {author}")
+                        }
                     }
                 }
             }
@@ -1,10 +1,11 @@
 //! Define commands to manage the start of the iroh node.
 
-use crate::config::NodeConfig;
-use anyhow::Result;
+use crate::config::{env_docs_passphrase, NodeConfig};
+use anyhow::{Context, Result};
 use colored::Colorize;
 use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
 use iroh::{
+    client::authors::KeystoreStatus,
     net::relay::{RelayMap, RelayMode},
     node::{Node, RpcStatus, DEFAULT_RPC_ADDR},
 };
@@ -153,14 +154,25 @@ pub(crate) async fn start_node(
     };
 
     let rpc_addr = rpc_addr.unwrap_or(DEFAULT_RPC_ADDR);
-    Node::persistent(iroh_data_root)
+    let node = Node::persistent(iroh_data_root)
         .await?
         .relay_mode(relay_mode)
         .enable_docs()
         .enable_rpc_with_addr(rpc_addr)
         .await?
         .spawn()
-        .await
+        .await?;
+
+    if let Some(passphrase) = env_docs_passphrase() {
+        let authors = node.authors();
+        if authors.keystore_status().await? == KeystoreStatus::Locked {
+            authors
+                .unlock(passphrase)
+                .await
+                .context("Failed to unlock the docs keystore with IROH_DOCS_PASSPHRASE")?;
+        }
+    }
+    Ok(node)
 }
 
 /// Creates a welcome message for the given [`Node`].
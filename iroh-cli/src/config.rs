@@ -24,6 +24,7 @@ const ENV_AUTHOR: &str = "IROH_AUTHOR";
 const ENV_DOC: &str = "IROH_DOC";
 const ENV_CONFIG_DIR: &str = "IROH_CONFIG_DIR";
 const ENV_FILE_RUST_LOG: &str = "IROH_FILE_RUST_LOG";
+const ENV_DOCS_PASSPHRASE: &str = "IROH_DOCS_PASSPHRASE";
 
 /// CONFIG_FILE_NAME is the name of the optional config file located in the iroh home directory
 pub(crate) const CONFIG_FILE_NAME: &str = "iroh.config.toml";
@@ -337,6 +338,11 @@ fn env_doc() -> Result<Option<NamespaceId>> {
         .transpose()
 }
 
+/// Read the passphrase to unlock the docs keystore from [`ENV_DOCS_PASSPHRASE`], if set.
+pub(crate) fn env_docs_passphrase() -> Option<String> {
+    env::var(ENV_DOCS_PASSPHRASE).ok()
+}
+
 /// Parse [`ENV_FILE_RUST_LOG`] as [`tracing_subscriber::EnvFilter`]. Returns `None` if not
 /// present.
 fn env_file_rust_log() -> Option<Result<crate::logging::EnvFilter>> {
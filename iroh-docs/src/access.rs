@@ -0,0 +1,237 @@
+//! Read access control for namespaces.
+//!
+//! Knowing a [`NamespaceId`] is enough to request a sync of the namespace from any node that
+//! holds it. A node can set a restricted [`AccessPolicy`] for a namespace, after which it only
+//! syncs the namespace with peers on the allowlist of the policy, and with peers that present a
+//! valid [`ReadToken`] for their node id. Read tokens are signed with the [`NamespaceSecret`], so
+//! only nodes with write access can issue them.
+//!
+//! The access policy is local to each node: a peer that was allowed to sync the namespace can
+//! share it further, unless it restricts access to the namespace as well.
+//!
+//! The policy also applies to the gossip swarm of a namespace. A node only joins the swarm
+//! through peers on the allowlist, and ignores neighbors that are not on it: it does not sync
+//! with them, does not report them as neighbors, and drops the gossip messages they deliver.
+//! Peers with a read token are not admitted as neighbors, they have to dial us to sync.
+//!
+//! The gossip topic is the namespace id, and iroh-gossip keeps the connection to a neighbor that
+//! knows the topic, so an ignored neighbor still receives what we broadcast. A node therefore
+//! never gossips the entries or content hashes of a restricted namespace. Its neighbors only
+//! receive the latest timestamp of each author that changed, and the allowed peers then fetch
+//! the entries with a sync, which enforces the policy.
+
+use std::{collections::BTreeSet, fmt, str::FromStr};
+
+use ed25519_dalek::Signature;
+use iroh_base::base32;
+use serde::{Deserialize, Serialize};
+
+use crate::{store::PublicKeyStore, NamespaceId, NamespaceSecret, PeerIdBytes};
+
+/// Domain separator for the signatures of read tokens.
+const READ_TOKEN_DOMAIN: &[u8] = b"iroh-docs read token";
+
+/// Which peers may sync a namespace with us.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum AccessPolicy {
+    /// Any peer that knows the namespace id may sync it.
+    #[default]
+    Open,
+    /// Only the listed peers, and peers with a valid [`ReadToken`], may sync the namespace.
+    Restricted {
+        /// The node ids of the peers that may sync the namespace.
+        nodes: BTreeSet<PeerIdBytes>,
+    },
+}
+
+impl AccessPolicy {
+    /// Create a restricted policy that allows the given nodes.
+    pub fn restricted(nodes: impl IntoIterator<Item = PeerIdBytes>) -> Self {
+        Self::Restricted {
+            nodes: nodes.into_iter().collect(),
+        }
+    }
+
+    /// Whether this policy restricts access to the namespace.
+    pub fn is_restricted(&self) -> bool {
+        matches!(self, Self::Restricted { .. })
+    }
+
+    /// Whether `node` may sync the namespace without presenting a [`ReadToken`].
+    pub fn allows(&self, node: &PeerIdBytes) -> bool {
+        match self {
+            Self::Open => true,
+            Self::Restricted { nodes } => nodes.contains(node),
+        }
+    }
+}
+
+/// A signed token granting a node read access to a namespace.
+///
+/// The token is presented by the node when it requests to sync the namespace, and is accepted
+/// by peers with a restricted [`AccessPolicy`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ReadToken {
+    namespace: NamespaceId,
+    node: PeerIdBytes,
+    expires: Option<u64>,
+    signature: Signature,
+}
+
+impl ReadToken {
+    /// Create a new token for `node`, signed with the namespace secret.
+    ///
+    /// `expires` is an optional timestamp in microseconds since the Unix epoch after which the
+    /// token is no longer valid.
+    pub fn new(namespace: &NamespaceSecret, node: PeerIdBytes, expires: Option<u64>) -> Self {
+        let id = namespace.id();
+        let signature = namespace.sign(&signing_bytes(&id, &node, expires));
+        Self {
+            namespace: id,
+            node,
+            expires,
+            signature,
+        }
+    }
+
+    /// The namespace this token grants access to.
+    pub fn namespace(&self) -> NamespaceId {
+        self.namespace
+    }
+
+    /// The node this token was issued for.
+    pub fn node(&self) -> PeerIdBytes {
+        self.node
+    }
+
+    /// The time after which the token is no longer valid, in microseconds since the Unix epoch.
+    pub fn expires(&self) -> Option<u64> {
+        self.expires
+    }
+
+    /// Verify that this token grants `node` access to `namespace` at time `now`.
+    pub fn verify<S: PublicKeyStore>(
+        &self,
+        store: &S,
+        namespace: &NamespaceId,
+        node: &PeerIdBytes,
+        now: u64,
+    ) -> Result<(), ReadTokenError> {
+        if self.namespace != *namespace {
+            return Err(ReadTokenError::NamespaceMismatch);
+        }
+        if self.node != *node {
+            return Err(ReadTokenError::NodeMismatch);
+        }
+        if self.expires.is_some_and(|expires| now > expires) {
+            return Err(ReadTokenError::Expired);
+        }
+        let bytes = signing_bytes(&self.namespace, &self.node, self.expires);
+        self.namespace
+            .public_key(store)?
+            .verify(&bytes, &self.signature)?;
+        Ok(())
+    }
+
+    /// Serialize this token to bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        postcard::to_stdvec(self).expect("postcard serialization failed")
+    }
+
+    /// Deserialize a token from bytes.
+    ///
+    /// The signature is not verified, see [`Self::verify`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ReadTokenError> {
+        Ok(postcard::from_bytes(bytes)?)
+    }
+}
+
+fn signing_bytes(namespace: &NamespaceId, node: &PeerIdBytes, expires: Option<u64>) -> Vec<u8> {
+    let mut out = READ_TOKEN_DOMAIN.to_vec();
+    out.extend_from_slice(namespace.as_bytes());
+    out.extend_from_slice(node);
+    postcard::to_extend(&expires, out).expect("postcard serialization failed")
+}
+
+impl fmt::Display for ReadToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", base32::fmt(self.to_bytes()))
+    }
+}
+
+impl FromStr for ReadToken {
+    type Err = ReadTokenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = base32::parse_vec(s).map_err(|_| ReadTokenError::Encoding)?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+/// Errors for [`ReadToken`] operations.
+#[derive(Debug, thiserror::Error)]
+pub enum ReadTokenError {
+    /// The signature of the token is invalid.
+    #[error("invalid read token signature")]
+    BadSignature(#[from] ed25519_dalek::SignatureError),
+    /// The token is for a different namespace.
+    #[error("read token is for a different namespace")]
+    NamespaceMismatch,
+    /// The token was issued for a different node.
+    #[error("read token was issued for a different node")]
+    NodeMismatch,
+    /// The token has expired.
+    #[error("read token has expired")]
+    Expired,
+    /// The token could not be decoded.
+    #[error("invalid read token encoding")]
+    Encoding,
+    /// The token could not be deserialized.
+    #[error("invalid read token encoding")]
+    Postcard(#[from] postcard::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_token() {
+        let mut rng = rand::thread_rng();
+        let namespace = NamespaceSecret::new(&mut rng);
+        let other = NamespaceSecret::new(&mut rng);
+        let node = [1u8; 32];
+
+        let token = ReadToken::new(&namespace, node, Some(1000));
+        token.verify(&(), &namespace.id(), &node, 500).unwrap();
+        assert!(matches!(
+            token.verify(&(), &namespace.id(), &node, 1001),
+            Err(ReadTokenError::Expired)
+        ));
+        assert!(matches!(
+            token.verify(&(), &namespace.id(), &[2u8; 32], 500),
+            Err(ReadTokenError::NodeMismatch)
+        ));
+        assert!(matches!(
+            token.verify(&(), &other.id(), &node, 500),
+            Err(ReadTokenError::NamespaceMismatch)
+        ));
+
+        // roundtrip
+        let parsed: ReadToken = token.to_string().parse().unwrap();
+        assert_eq!(parsed, token);
+
+        // tampering with the expiry invalidates the signature
+        let mut tampered = token.clone();
+        tampered.expires = None;
+        assert!(matches!(
+            tampered.verify(&(), &namespace.id(), &node, 500),
+            Err(ReadTokenError::BadSignature(_))
+        ));
+
+        let policy = AccessPolicy::restricted([node]);
+        assert!(policy.allows(&node));
+        assert!(!policy.allows(&[2u8; 32]));
+        assert!(AccessPolicy::Open.allows(&[2u8; 32]));
+    }
+}
@@ -1,7 +1,7 @@
 //! This contains an actor spawned on a separate thread to process replica and store operations.
 
 use std::{
-    collections::{hash_map, HashMap},
+    collections::{hash_map, BTreeSet, HashMap, HashSet},
     num::NonZeroU64,
     sync::Arc,
     thread::JoinHandle,
@@ -15,23 +15,32 @@ use iroh_base::hash::Hash;
 use iroh_metrics::inc;
 use serde::{Deserialize, Serialize};
 use tokio::{sync::oneshot, task::JoinSet};
-use tracing::{debug, error, error_span, trace, warn};
+use tracing::{debug, error, error_span, info, trace, warn};
 
 use crate::{
     metrics::Metrics,
     ranger::Message,
     store::{
         fs::{ContentHashesIterator, StoreInstance},
-        DownloadPolicy, ImportNamespaceOutcome, Query, Store,
+        ClockMode, Cursor, DownloadPolicy, HistoryPolicy, ImportNamespaceOutcome, IndexSpec, Query,
+        Store, SyncPolicy, TombstonePolicy,
     },
-    Author, AuthorHeads, AuthorId, Capability, CapabilityKind, ContentStatus,
-    ContentStatusCallback, Event, NamespaceId, NamespaceSecret, PeerIdBytes, Replica, ReplicaInfo,
-    SignedEntry, SyncOutcome,
+    sync::{system_time_now, InsertError},
+    AccessPolicy, AreaOfInterest, Author, AuthorHeads, AuthorId, BatchOp, Capability,
+    CapabilityKind, ContentReaderCallback, ContentStatus, ContentStatusCallback, DelegationScope,
+    DocEncryptionKey, Event, Invite, KeystoreStatus, NamespaceId, NamespaceSecret, PeerIdBytes,
+    ReadToken, RecordIdentifier, Redemption, Replica, ReplicaInfo, SignedEntry, SyncOutcome,
+    WriteDelegation,
 };
 
 const ACTION_CAP: usize = 1024;
+/// Number of entries copied per batch when migrating a namespace.
+const MIGRATE_BATCH_SIZE: u64 = 1024;
 pub(crate) const MAX_COMMIT_DELAY: Duration = Duration::from_millis(500);
 
+/// A stored invite together with the nodes it was presented to.
+type StoredInvite = (Invite, Vec<PeerIdBytes>);
+
 #[derive(derive_more::Debug, derive_more::Display)]
 enum Action {
     #[display("NewAuthor")]
@@ -52,6 +61,43 @@ enum Action {
         #[debug("reply")]
         reply: oneshot::Sender<Result<()>>,
     },
+    #[display("HasAuthor")]
+    HasAuthor {
+        author: AuthorId,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<bool>>,
+    },
+    #[display("KeystoreStatus")]
+    KeystoreStatus {
+        #[debug("reply")]
+        reply: oneshot::Sender<KeystoreStatus>,
+    },
+    #[display("EnableKeystore")]
+    EnableKeystore {
+        #[debug("passphrase")]
+        passphrase: String,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<()>>,
+    },
+    #[display("DisableKeystore")]
+    DisableKeystore {
+        #[debug("passphrase")]
+        passphrase: String,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<()>>,
+    },
+    #[display("UnlockKeystore")]
+    UnlockKeystore {
+        #[debug("passphrase")]
+        passphrase: String,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<()>>,
+    },
+    #[display("LockKeystore")]
+    LockKeystore {
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<()>>,
+    },
     #[display("NewReplica")]
     ImportNamespace {
         capability: Capability,
@@ -78,6 +124,12 @@ enum Action {
         #[debug("reply")]
         reply: oneshot::Sender<Result<()>>,
     },
+    #[display("SubscribeAll")]
+    SubscribeAll {
+        sender: async_channel::Sender<Event>,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<()>>,
+    },
     #[display("Replica({}, {})", _0.fmt_short(), _1)]
     Replica(NamespaceId, ReplicaAction),
     #[display("Shutdown")]
@@ -131,6 +183,12 @@ enum ReplicaAction {
         #[debug("reply")]
         reply: oneshot::Sender<Result<usize>>,
     },
+    ApplyBatch {
+        author: AuthorId,
+        ops: Vec<BatchOp>,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<usize>>,
+    },
     InsertRemote {
         entry: SignedEntry,
         from: PeerIdBytes,
@@ -138,14 +196,34 @@ enum ReplicaAction {
         #[debug("reply")]
         reply: oneshot::Sender<Result<()>>,
     },
+    InsertRemoteBatch {
+        entries: Vec<SignedEntry>,
+        from: PeerIdBytes,
+        content_status: ContentStatus,
+        #[debug("content")]
+        content: HashMap<Hash, Bytes>,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<usize>>,
+    },
+    ImportEntries {
+        entries: Vec<SignedEntry>,
+        from: PeerIdBytes,
+        content_status: ContentStatus,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<usize>>,
+    },
     SyncInitialMessage {
+        area: AreaOfInterest,
         #[debug("reply")]
         reply: oneshot::Sender<Result<Message<SignedEntry>>>,
     },
     SyncProcessMessage {
         message: Message<SignedEntry>,
         from: PeerIdBytes,
+        area: AreaOfInterest,
         state: SyncOutcome,
+        #[debug("content")]
+        content: HashMap<Hash, Bytes>,
         #[debug("reply")]
         reply: oneshot::Sender<Result<(Option<Message<SignedEntry>>, SyncOutcome)>>,
     },
@@ -188,6 +266,206 @@ enum ReplicaAction {
         #[debug("reply")]
         reply: oneshot::Sender<Result<DownloadPolicy>>,
     },
+    SetHistoryPolicy {
+        policy: HistoryPolicy,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<()>>,
+    },
+    GetHistoryPolicy {
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<HistoryPolicy>>,
+    },
+    SetTombstonePolicy {
+        policy: TombstonePolicy,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<()>>,
+    },
+    GetTombstonePolicy {
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<TombstonePolicy>>,
+    },
+    SetSyncPolicy {
+        policy: SyncPolicy,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<()>>,
+    },
+    GetSyncPolicy {
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<SyncPolicy>>,
+    },
+    SetInlineLimit {
+        limit: u64,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<()>>,
+    },
+    GetInlineLimit {
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<u64>>,
+    },
+    GetInlineContent {
+        author: AuthorId,
+        key: Bytes,
+        hash: Hash,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<Option<Bytes>>>,
+    },
+    CreateIndex {
+        name: String,
+        spec: IndexSpec,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<()>>,
+    },
+    RemoveIndex {
+        name: String,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<bool>>,
+    },
+    ListIndexes {
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<Vec<(String, IndexSpec)>>>,
+    },
+    IndexContent {
+        author: AuthorId,
+        key: Bytes,
+        hash: Hash,
+        #[debug("content")]
+        content: Bytes,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<()>>,
+    },
+    CompactTombstones {
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<usize>>,
+    },
+    GetCompactionCutoff {
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<u64>>,
+    },
+    AdoptCompactionCutoff {
+        cutoff: u64,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<usize>>,
+    },
+    SetClockMode {
+        mode: ClockMode,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<()>>,
+    },
+    GetClockMode {
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<ClockMode>>,
+    },
+    SetSyncInterest {
+        area: AreaOfInterest,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<()>>,
+    },
+    GetSyncInterest {
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<AreaOfInterest>>,
+    },
+    Delegate {
+        scope: DelegationScope,
+        issuer: Option<AuthorId>,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<WriteDelegation>>,
+    },
+    AddDelegation {
+        delegation: WriteDelegation,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<bool>>,
+    },
+    ListDelegations {
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<Vec<WriteDelegation>>>,
+    },
+    SetAccessPolicy {
+        policy: AccessPolicy,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<()>>,
+    },
+    GetAccessPolicy {
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<AccessPolicy>>,
+    },
+    SetTrustedMarkerAuthors {
+        authors: BTreeSet<AuthorId>,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<()>>,
+    },
+    GetTrustedMarkerAuthors {
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<BTreeSet<AuthorId>>>,
+    },
+    CreateReadToken {
+        node: PeerIdBytes,
+        expires: Option<u64>,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<ReadToken>>,
+    },
+    SetReadToken {
+        token: ReadToken,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<()>>,
+    },
+    GetReadToken {
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<Option<ReadToken>>>,
+    },
+    AuthorizeRead {
+        peer: PeerIdBytes,
+        token: Option<ReadToken>,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<bool>>,
+    },
+    CreateInvite {
+        mode: CapabilityKind,
+        expires: Option<u64>,
+        single_use: bool,
+        invitee: Option<PeerIdBytes>,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<Invite>>,
+    },
+    SetInvite {
+        invite: Invite,
+        nodes: Vec<PeerIdBytes>,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<()>>,
+    },
+    GetInvite {
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<Option<StoredInvite>>>,
+    },
+    RemoveInvite {
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<()>>,
+    },
+    RedeemInvite {
+        peer: PeerIdBytes,
+        invite: Invite,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<Option<Capability>>>,
+    },
+    ListRedemptions {
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<Vec<Redemption>>>,
+    },
+    SetEncryptionKey {
+        key: Option<DocEncryptionKey>,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<DocEncryptionKey>>,
+    },
+    GetEncryptionKey {
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<Option<DocEncryptionKey>>>,
+    },
+    Migrate {
+        to: NamespaceSecret,
+        author: AuthorId,
+        keep_authors: bool,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<usize>>,
+    },
 }
 
 /// The state for an open replica.
@@ -220,10 +498,12 @@ struct OpenReplica {
 /// and await its result before dropping the last [`SyncHandle`]. This ensures that
 /// waiting for the actor to finish happens in an async context, and therefore that the final
 /// [`SyncHandle::drop`] will not block.
-#[derive(Debug, Clone)]
+#[derive(derive_more::Debug, Clone)]
 pub struct SyncHandle {
     tx: async_channel::Sender<Action>,
     join_handle: Arc<Option<JoinHandle<()>>>,
+    #[debug("ContentReaderCallback")]
+    content_reader: Option<ContentReaderCallback>,
 }
 
 /// Options when opening a replica.
@@ -262,6 +542,7 @@ impl SyncHandle {
             action_rx,
             content_status_callback,
             tasks: Default::default(),
+            subscribers: Default::default(),
         };
         let join_handle = std::thread::Builder::new()
             .name("sync-actor".to_string())
@@ -278,7 +559,50 @@ impl SyncHandle {
         SyncHandle {
             tx: action_tx,
             join_handle,
+            content_reader: None,
+        }
+    }
+
+    /// Set the callback to read the content of small values, to send them inline when syncing.
+    ///
+    /// Without a content reader, no values are sent inline.
+    pub fn with_content_reader(mut self, content_reader: ContentReaderCallback) -> Self {
+        self.content_reader = Some(content_reader);
+        self
+    }
+
+    /// Read the content for `hash` with the content reader, if one is set.
+    pub(crate) async fn read_content(&self, hash: Hash) -> Option<Bytes> {
+        let content_reader = self.content_reader.as_ref()?;
+        content_reader(hash).await
+    }
+
+    /// Read the content of the values of `entries` that are not longer than `limit`, to send
+    /// them inline with the entries.
+    ///
+    /// Values without complete content are skipped.
+    pub(crate) async fn read_inline_content<'a>(
+        &self,
+        entries: impl IntoIterator<Item = &'a SignedEntry>,
+        limit: u64,
+    ) -> Vec<Bytes> {
+        if limit == 0 {
+            return Vec::new();
         }
+        let mut seen = HashSet::new();
+        let hashes: Vec<Hash> = entries
+            .into_iter()
+            .filter(|entry| entry.content_len() > 0 && entry.content_len() <= limit)
+            .map(|entry| entry.content_hash())
+            .filter(|hash| seen.insert(*hash))
+            .collect();
+        let mut content = Vec::new();
+        for hash in hashes {
+            if let Some(data) = self.read_content(hash).await {
+                content.push(data);
+            }
+        }
+        content
     }
 
     pub async fn open(&self, namespace: NamespaceId, opts: OpenOpts) -> Result<()> {
@@ -306,6 +630,13 @@ impl SyncHandle {
         rx.await?
     }
 
+    /// Subscribe to the events of all replicas, including the replicas which are opened later.
+    pub async fn subscribe_all(&self, sender: async_channel::Sender<Event>) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        self.send(Action::SubscribeAll { sender, reply }).await?;
+        rx.await?
+    }
+
     pub async fn unsubscribe(
         &self,
         namespace: NamespaceId,
@@ -356,6 +687,18 @@ impl SyncHandle {
         rx.await?
     }
 
+    pub async fn apply_batch(
+        &self,
+        namespace: NamespaceId,
+        author: AuthorId,
+        ops: Vec<BatchOp>,
+    ) -> Result<usize> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::ApplyBatch { author, ops, reply };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
     pub async fn insert_remote(
         &self,
         namespace: NamespaceId,
@@ -374,12 +717,59 @@ impl SyncHandle {
         rx.await?
     }
 
+    /// Insert entries received together from a remote peer, with the content of their small
+    /// values that was received inline, see [`crate::Replica::insert_remote_batch`].
+    pub async fn insert_remote_batch(
+        &self,
+        namespace: NamespaceId,
+        entries: Vec<SignedEntry>,
+        from: PeerIdBytes,
+        content_status: ContentStatus,
+        content: HashMap<Hash, Bytes>,
+    ) -> Result<usize> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::InsertRemoteBatch {
+            entries,
+            from,
+            content_status,
+            content,
+            reply,
+        };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    /// Insert entries from an offline [`Bundle`](crate::Bundle) into an open replica.
+    ///
+    /// The entries are inserted like entries received from `from`, whether or not the replica
+    /// syncs. Entries that fail to validate or are superseded by newer entries are skipped.
+    ///
+    /// Returns the number of entries inserted.
+    pub async fn import_entries(
+        &self,
+        namespace: NamespaceId,
+        entries: Vec<SignedEntry>,
+        from: PeerIdBytes,
+        content_status: ContentStatus,
+    ) -> Result<usize> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::ImportEntries {
+            entries,
+            from,
+            content_status,
+            reply,
+        };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
     pub async fn sync_initial_message(
         &self,
         namespace: NamespaceId,
+        area: AreaOfInterest,
     ) -> Result<Message<SignedEntry>> {
         let (reply, rx) = oneshot::channel();
-        let action = ReplicaAction::SyncInitialMessage { reply };
+        let action = ReplicaAction::SyncInitialMessage { area, reply };
         self.send_replica(namespace, action).await?;
         rx.await?
     }
@@ -389,14 +779,39 @@ impl SyncHandle {
         namespace: NamespaceId,
         message: Message<SignedEntry>,
         from: PeerIdBytes,
+        area: AreaOfInterest,
+        state: SyncOutcome,
+    ) -> Result<(Option<Message<SignedEntry>>, SyncOutcome)> {
+        self.sync_process_message_with_content(
+            namespace,
+            message,
+            from,
+            area,
+            state,
+            HashMap::new(),
+        )
+        .await
+    }
+
+    /// Process a sync message together with the content of small values received inline, see
+    /// [`crate::Replica::sync_process_message_with_content`].
+    pub async fn sync_process_message_with_content(
+        &self,
+        namespace: NamespaceId,
+        message: Message<SignedEntry>,
+        from: PeerIdBytes,
+        area: AreaOfInterest,
         state: SyncOutcome,
+        content: HashMap<Hash, Bytes>,
     ) -> Result<(Option<Message<SignedEntry>>, SyncOutcome)> {
         let (reply, rx) = oneshot::channel();
         let action = ReplicaAction::SyncProcessMessage {
             reply,
             message,
             from,
+            area,
             state,
+            content,
         };
         self.send_replica(namespace, action).await?;
         rx.await?
@@ -518,6 +933,45 @@ impl SyncHandle {
         rx.await?
     }
 
+    pub async fn has_author(&self, author: AuthorId) -> Result<bool> {
+        let (reply, rx) = oneshot::channel();
+        self.send(Action::HasAuthor { author, reply }).await?;
+        rx.await?
+    }
+
+    pub async fn keystore_status(&self) -> Result<KeystoreStatus> {
+        let (reply, rx) = oneshot::channel();
+        self.send(Action::KeystoreStatus { reply }).await?;
+        Ok(rx.await?)
+    }
+
+    pub async fn enable_keystore(&self, passphrase: String) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        self.send(Action::EnableKeystore { passphrase, reply })
+            .await?;
+        rx.await?
+    }
+
+    pub async fn disable_keystore(&self, passphrase: String) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        self.send(Action::DisableKeystore { passphrase, reply })
+            .await?;
+        rx.await?
+    }
+
+    pub async fn unlock_keystore(&self, passphrase: String) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        self.send(Action::UnlockKeystore { passphrase, reply })
+            .await?;
+        rx.await?
+    }
+
+    pub async fn lock_keystore(&self) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        self.send(Action::LockKeystore { reply }).await?;
+        rx.await?
+    }
+
     pub async fn import_namespace(&self, capability: Capability) -> Result<NamespaceId> {
         let (reply, rx) = oneshot::channel();
         self.send(Action::ImportNamespace { capability, reply })
@@ -543,24 +997,457 @@ impl SyncHandle {
         rx.await?
     }
 
-    pub async fn content_hashes(&self) -> Result<ContentHashesIterator> {
+    pub async fn get_history_policy(&self, namespace: NamespaceId) -> Result<HistoryPolicy> {
         let (reply, rx) = oneshot::channel();
-        self.send(Action::ContentHashes { reply }).await?;
+        let action = ReplicaAction::GetHistoryPolicy { reply };
+        self.send_replica(namespace, action).await?;
         rx.await?
     }
 
-    /// Makes sure that all pending database operations are persisted to disk.
-    ///
-    /// Otherwise, database operations are batched into bigger transactions for speed.
-    /// Use this if you need to make sure something is written to the database
-    /// before another operation, e.g. to make sure sudden process exits don't corrupt
-    /// your application state.
-    ///
-    /// It's not necessary to call this function before shutdown, as `shutdown` will
-    /// trigger a flush on its own.
-    pub async fn flush_store(&self) -> Result<()> {
+    pub async fn set_history_policy(
+        &self,
+        namespace: NamespaceId,
+        policy: HistoryPolicy,
+    ) -> Result<()> {
         let (reply, rx) = oneshot::channel();
-        self.send(Action::FlushStore { reply }).await?;
+        let action = ReplicaAction::SetHistoryPolicy { reply, policy };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn get_tombstone_policy(&self, namespace: NamespaceId) -> Result<TombstonePolicy> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::GetTombstonePolicy { reply };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn set_tombstone_policy(
+        &self,
+        namespace: NamespaceId,
+        policy: TombstonePolicy,
+    ) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::SetTombstonePolicy { reply, policy };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn get_sync_policy(&self, namespace: NamespaceId) -> Result<SyncPolicy> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::GetSyncPolicy { reply };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn set_sync_policy(&self, namespace: NamespaceId, policy: SyncPolicy) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::SetSyncPolicy { reply, policy };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn get_inline_limit(&self, namespace: NamespaceId) -> Result<u64> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::GetInlineLimit { reply };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn set_inline_limit(&self, namespace: NamespaceId, limit: u64) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::SetInlineLimit { reply, limit };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    /// Get the content of an entry that was received inline with it, see
+    /// [`Store::get_inline_content`].
+    pub async fn get_inline_content(
+        &self,
+        namespace: NamespaceId,
+        author: AuthorId,
+        key: Bytes,
+        hash: Hash,
+    ) -> Result<Option<Bytes>> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::GetInlineContent {
+            author,
+            key,
+            hash,
+            reply,
+        };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn create_index(
+        &self,
+        namespace: NamespaceId,
+        name: String,
+        spec: IndexSpec,
+    ) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::CreateIndex { reply, name, spec };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn remove_index(&self, namespace: NamespaceId, name: String) -> Result<bool> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::RemoveIndex { reply, name };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn list_indexes(&self, namespace: NamespaceId) -> Result<Vec<(String, IndexSpec)>> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::ListIndexes { reply };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    /// Add an entry to the secondary indexes of a namespace, see [`Store::index_content`].
+    pub async fn index_content(
+        &self,
+        namespace: NamespaceId,
+        author: AuthorId,
+        key: Bytes,
+        hash: Hash,
+        content: Bytes,
+    ) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::IndexContent {
+            author,
+            key,
+            hash,
+            content,
+            reply,
+        };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn compact_tombstones(&self, namespace: NamespaceId) -> Result<usize> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::CompactTombstones { reply };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn get_compaction_cutoff(&self, namespace: NamespaceId) -> Result<u64> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::GetCompactionCutoff { reply };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn adopt_compaction_cutoff(
+        &self,
+        namespace: NamespaceId,
+        cutoff: u64,
+    ) -> Result<usize> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::AdoptCompactionCutoff { reply, cutoff };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn get_clock_mode(&self, namespace: NamespaceId) -> Result<ClockMode> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::GetClockMode { reply };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn set_clock_mode(&self, namespace: NamespaceId, mode: ClockMode) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::SetClockMode { reply, mode };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn get_sync_interest(&self, namespace: NamespaceId) -> Result<AreaOfInterest> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::GetSyncInterest { reply };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn set_sync_interest(
+        &self,
+        namespace: NamespaceId,
+        area: AreaOfInterest,
+    ) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::SetSyncInterest { reply, area };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn delegate(
+        &self,
+        namespace: NamespaceId,
+        scope: DelegationScope,
+        issuer: Option<AuthorId>,
+    ) -> Result<WriteDelegation> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::Delegate {
+            scope,
+            issuer,
+            reply,
+        };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn add_delegation(
+        &self,
+        namespace: NamespaceId,
+        delegation: WriteDelegation,
+    ) -> Result<bool> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::AddDelegation { delegation, reply };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn list_delegations(&self, namespace: NamespaceId) -> Result<Vec<WriteDelegation>> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::ListDelegations { reply };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn get_access_policy(&self, namespace: NamespaceId) -> Result<AccessPolicy> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::GetAccessPolicy { reply };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn set_access_policy(
+        &self,
+        namespace: NamespaceId,
+        policy: AccessPolicy,
+    ) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::SetAccessPolicy { reply, policy };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    /// Set the authors whose moved-to markers are followed automatically, see
+    /// [`Store::set_trusted_marker_authors`].
+    pub async fn set_trusted_marker_authors(
+        &self,
+        namespace: NamespaceId,
+        authors: BTreeSet<AuthorId>,
+    ) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::SetTrustedMarkerAuthors { reply, authors };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn get_trusted_marker_authors(
+        &self,
+        namespace: NamespaceId,
+    ) -> Result<BTreeSet<AuthorId>> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::GetTrustedMarkerAuthors { reply };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn create_read_token(
+        &self,
+        namespace: NamespaceId,
+        node: PeerIdBytes,
+        expires: Option<u64>,
+    ) -> Result<ReadToken> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::CreateReadToken {
+            node,
+            expires,
+            reply,
+        };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn set_read_token(&self, namespace: NamespaceId, token: ReadToken) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::SetReadToken { token, reply };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn get_read_token(&self, namespace: NamespaceId) -> Result<Option<ReadToken>> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::GetReadToken { reply };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    /// Check whether `peer` may sync `namespace` with us.
+    ///
+    /// Returns `true` if the access policy of the namespace allows the peer, or if `token` is a
+    /// valid [`ReadToken`] for the peer.
+    pub async fn authorize_read(
+        &self,
+        namespace: NamespaceId,
+        peer: PeerIdBytes,
+        token: Option<ReadToken>,
+    ) -> Result<bool> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::AuthorizeRead { peer, token, reply };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn create_invite(
+        &self,
+        namespace: NamespaceId,
+        mode: CapabilityKind,
+        expires: Option<u64>,
+        single_use: bool,
+        invitee: Option<PeerIdBytes>,
+    ) -> Result<Invite> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::CreateInvite {
+            mode,
+            expires,
+            single_use,
+            invitee,
+            reply,
+        };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    /// Set the invite to present to the inviting `nodes` when syncing a namespace.
+    pub async fn set_invite(
+        &self,
+        namespace: NamespaceId,
+        invite: Invite,
+        nodes: Vec<PeerIdBytes>,
+    ) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::SetInvite {
+            invite,
+            nodes,
+            reply,
+        };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn get_invite(&self, namespace: NamespaceId) -> Result<Option<StoredInvite>> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::GetInvite { reply };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn remove_invite(&self, namespace: NamespaceId) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::RemoveInvite { reply };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    /// Redeem an invite presented by `peer`.
+    ///
+    /// The redemption is logged, and `peer` is added to a restricted access policy of the
+    /// namespace. Returns the write capability to hand over to the peer for write invites.
+    pub async fn redeem_invite(
+        &self,
+        namespace: NamespaceId,
+        peer: PeerIdBytes,
+        invite: Invite,
+    ) -> Result<Option<Capability>> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::RedeemInvite {
+            peer,
+            invite,
+            reply,
+        };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn list_redemptions(&self, namespace: NamespaceId) -> Result<Vec<Redemption>> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::ListRedemptions { reply };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    /// Set the encryption key of a namespace.
+    ///
+    /// If `key` is `None`, the key is derived from the namespace secret. Returns the key.
+    pub async fn set_encryption_key(
+        &self,
+        namespace: NamespaceId,
+        key: Option<DocEncryptionKey>,
+    ) -> Result<DocEncryptionKey> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::SetEncryptionKey { key, reply };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn get_encryption_key(
+        &self,
+        namespace: NamespaceId,
+    ) -> Result<Option<DocEncryptionKey>> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::GetEncryptionKey { reply };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    /// Copy the latest entries of a namespace to the namespace of `to`.
+    ///
+    /// The entries are re-signed by their original authors if `keep_authors` is set and the
+    /// author secret is available, and by `author` otherwise. Returns the number of copied entries.
+    pub async fn migrate_namespace(
+        &self,
+        namespace: NamespaceId,
+        to: NamespaceSecret,
+        author: AuthorId,
+        keep_authors: bool,
+    ) -> Result<usize> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::Migrate {
+            to,
+            author,
+            keep_authors,
+            reply,
+        };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    pub async fn content_hashes(&self) -> Result<ContentHashesIterator> {
+        let (reply, rx) = oneshot::channel();
+        self.send(Action::ContentHashes { reply }).await?;
+        rx.await?
+    }
+
+    /// Makes sure that all pending database operations are persisted to disk.
+    ///
+    /// Otherwise, database operations are batched into bigger transactions for speed.
+    /// Use this if you need to make sure something is written to the database
+    /// before another operation, e.g. to make sure sudden process exits don't corrupt
+    /// your application state.
+    ///
+    /// It's not necessary to call this function before shutdown, as `shutdown` will
+    /// trigger a flush on its own.
+    pub async fn flush_store(&self) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        self.send(Action::FlushStore { reply }).await?;
         rx.await?
     }
 
@@ -599,6 +1486,8 @@ struct Actor {
     action_rx: async_channel::Receiver<Action>,
     content_status_callback: Option<ContentStatusCallback>,
     tasks: JoinSet<()>,
+    /// Subscribers to the events of all replicas.
+    subscribers: Vec<async_channel::Sender<Event>>,
 }
 
 impl Actor {
@@ -658,6 +1547,16 @@ impl Actor {
         }
     }
 
+    /// Restore the write capability of replicas that were opened while the keystore was locked.
+    fn restore_capabilities(&mut self) -> Result<()> {
+        for (namespace, state) in self.states.0.iter_mut() {
+            if let Some(capability) = self.store.get_capability(namespace)? {
+                state.info.merge_capability(capability)?;
+            }
+        }
+        Ok(())
+    }
+
     fn on_action(&mut self, action: Action) -> Result<(), SendReplyError> {
         match action {
             Action::Shutdown { .. } => {
@@ -673,6 +1572,31 @@ impl Actor {
             Action::DeleteAuthor { author, reply } => {
                 send_reply(reply, self.store.delete_author(author))
             }
+            Action::HasAuthor { author, reply } => {
+                send_reply(reply, self.store.has_author(&author))
+            }
+            Action::KeystoreStatus { reply } => send_reply(reply, self.store.keystore_status()),
+            Action::EnableKeystore { passphrase, reply } => {
+                send_reply(reply, self.store.enable_keystore(&passphrase))
+            }
+            Action::DisableKeystore { passphrase, reply } => send_reply_with(reply, self, |this| {
+                this.store.disable_keystore(&passphrase)?;
+                this.restore_capabilities()
+            }),
+            Action::UnlockKeystore { passphrase, reply } => send_reply_with(reply, self, |this| {
+                this.store.unlock_keystore(&passphrase)?;
+                this.restore_capabilities()
+            }),
+            Action::LockKeystore { reply } => send_reply_with(reply, self, |this| {
+                this.store.lock_keystore()?;
+                // Drop the namespace secrets of open replicas, so that they are opened read-only
+                // until the keystore is unlocked again.
+                for state in this.states.0.values_mut() {
+                    let id = state.info.capability.id();
+                    state.info.capability = Capability::Read(id);
+                }
+                Ok(())
+            }),
             Action::ImportNamespace { capability, reply } => send_reply_with(reply, self, |this| {
                 let id = capability.id();
                 let outcome = this.store.import_namespace(capability.clone())?;
@@ -684,10 +1608,7 @@ impl Actor {
                 Ok(id)
             }),
             Action::ListAuthors { reply } => {
-                let iter = self
-                    .store
-                    .list_authors()
-                    .map(|a| a.map(|a| a.map(|a| a.id())));
+                let iter = self.store.list_author_ids();
                 self.tasks
                     .spawn_local(iter_to_channel_async(reply, iter).map(|_| ()));
                 Ok(())
@@ -702,6 +1623,13 @@ impl Actor {
                 send_reply_with(reply, self, |this| this.store.content_hashes())
             }
             Action::FlushStore { reply } => send_reply(reply, self.store.flush()),
+            Action::SubscribeAll { sender, reply } => {
+                for state in self.states.0.values_mut() {
+                    state.info.subscribe(sender.clone());
+                }
+                self.subscribers.push(sender);
+                send_reply(reply, Ok(()))
+            }
             Action::Replica(namespace, action) => self.on_replica_action(namespace, action),
         }
     }
@@ -760,6 +1688,14 @@ impl Actor {
                     Ok(res)
                 })
             }
+            ReplicaAction::ApplyBatch { author, ops, reply } => {
+                send_reply_with(reply, self, move |this| {
+                    let author = get_author(&mut this.store, &author)?;
+                    let mut replica = this.states.replica(namespace, &mut this.store)?;
+                    let removed = replica.apply_batch(&author, ops)?;
+                    Ok(removed)
+                })
+            }
             ReplicaAction::InsertRemote {
                 entry,
                 from,
@@ -772,26 +1708,60 @@ impl Actor {
                 replica.insert_remote_entry(entry, from, content_status)?;
                 Ok(())
             }),
+            ReplicaAction::InsertRemoteBatch {
+                entries,
+                from,
+                content_status,
+                content,
+                reply,
+            } => send_reply_with(reply, self, move |this| {
+                let mut replica = this
+                    .states
+                    .replica_if_syncing(&namespace, &mut this.store)?;
+                let inserted =
+                    replica.insert_remote_batch(entries, from, content_status, content)?;
+                Ok(inserted)
+            }),
+            ReplicaAction::ImportEntries {
+                entries,
+                from,
+                content_status,
+                reply,
+            } => send_reply_with(reply, self, move |this| {
+                let mut replica = this.states.replica(namespace, &mut this.store)?;
+                let mut inserted = 0;
+                for entry in entries {
+                    match replica.insert_remote_entry(entry, from, content_status) {
+                        Ok(_) => inserted += 1,
+                        Err(InsertError::Store(err)) => return Err(err),
+                        Err(err) => tracing::debug!(?err, "skipping imported entry"),
+                    }
+                }
+                Ok(inserted)
+            }),
 
-            ReplicaAction::SyncInitialMessage { reply } => {
+            ReplicaAction::SyncInitialMessage { area, reply } => {
                 send_reply_with(reply, self, move |this| {
                     let mut replica = this
                         .states
                         .replica_if_syncing(&namespace, &mut this.store)?;
-                    let res = replica.sync_initial_message()?;
+                    let res = replica.sync_initial_message(&area)?;
                     Ok(res)
                 })
             }
             ReplicaAction::SyncProcessMessage {
                 message,
                 from,
+                area,
                 mut state,
+                content,
                 reply,
             } => send_reply_with(reply, self, move |this| {
                 let mut replica = this
                     .states
                     .replica_if_syncing(&namespace, &mut this.store)?;
-                let res = replica.sync_process_message(message, from, &mut state)?;
+                let res = replica
+                    .sync_process_message_with_content(message, from, &area, &mut state, content)?;
                 Ok((res, state))
             }),
             ReplicaAction::GetSyncPeers { reply } => send_reply_with(reply, self, move |this| {
@@ -836,7 +1806,11 @@ impl Actor {
                 let state = this.states.get_mut(&namespace)?;
                 let handles = state.handles;
                 let sync = state.sync;
-                let subscribers = state.info.subscribers_count();
+                // subscribers to all replicas are internal and not reported per replica
+                let subscribers = state
+                    .info
+                    .subscribers_count()
+                    .saturating_sub(this.subscribers.len());
                 Ok(OpenState {
                     handles,
                     sync,
@@ -853,9 +1827,330 @@ impl Actor {
             ReplicaAction::GetDownloadPolicy { reply } => {
                 send_reply(reply, self.store.get_download_policy(&namespace))
             }
+            ReplicaAction::SetHistoryPolicy { policy, reply } => {
+                send_reply(reply, self.store.set_history_policy(&namespace, policy))
+            }
+            ReplicaAction::GetHistoryPolicy { reply } => {
+                send_reply(reply, self.store.get_history_policy(&namespace))
+            }
+            ReplicaAction::SetTombstonePolicy { policy, reply } => {
+                send_reply(reply, self.store.set_tombstone_policy(&namespace, &policy))
+            }
+            ReplicaAction::GetTombstonePolicy { reply } => {
+                send_reply(reply, self.store.get_tombstone_policy(&namespace))
+            }
+            ReplicaAction::SetSyncPolicy { policy, reply } => {
+                send_reply(reply, self.store.set_sync_policy(&namespace, &policy))
+            }
+            ReplicaAction::GetSyncPolicy { reply } => {
+                send_reply(reply, self.store.get_sync_policy(&namespace))
+            }
+            ReplicaAction::SetInlineLimit { limit, reply } => {
+                send_reply(reply, self.store.set_inline_limit(&namespace, limit))
+            }
+            ReplicaAction::GetInlineLimit { reply } => {
+                send_reply(reply, self.store.get_inline_limit(&namespace))
+            }
+            ReplicaAction::GetInlineContent {
+                author,
+                key,
+                hash,
+                reply,
+            } => send_reply_with(reply, self, move |this| {
+                this.states.ensure_open(&namespace)?;
+                let id = RecordIdentifier::new(namespace, author, key);
+                this.store.get_inline_content(&id, &hash)
+            }),
+            ReplicaAction::CreateIndex { name, spec, reply } => {
+                send_reply(reply, self.store.create_index(&namespace, &name, &spec))
+            }
+            ReplicaAction::RemoveIndex { name, reply } => {
+                send_reply(reply, self.store.remove_index(&namespace, &name))
+            }
+            ReplicaAction::ListIndexes { reply } => {
+                send_reply(reply, self.store.list_indexes(&namespace))
+            }
+            ReplicaAction::IndexContent {
+                author,
+                key,
+                hash,
+                content,
+                reply,
+            } => send_reply(
+                reply,
+                self.store
+                    .index_content(&namespace, &author, &key, &hash, &content),
+            ),
+            ReplicaAction::CompactTombstones { reply } => {
+                send_reply(reply, self.store.compact_tombstones(&namespace))
+            }
+            ReplicaAction::GetCompactionCutoff { reply } => {
+                send_reply(reply, self.store.get_compaction_cutoff(&namespace))
+            }
+            ReplicaAction::AdoptCompactionCutoff { cutoff, reply } => send_reply(
+                reply,
+                self.store.adopt_compaction_cutoff(&namespace, cutoff),
+            ),
+            ReplicaAction::SetClockMode { mode, reply } => {
+                send_reply(reply, self.store.set_clock_mode(&namespace, &mode))
+            }
+            ReplicaAction::GetClockMode { reply } => {
+                send_reply(reply, self.store.get_clock_mode(&namespace))
+            }
+            ReplicaAction::SetSyncInterest { area, reply } => {
+                let res = self.store.set_sync_interest(&namespace, &area);
+                if res.is_ok() {
+                    if let Ok(state) = self.states.get_mut(&namespace) {
+                        state.info.set_sync_interest(area);
+                    }
+                }
+                send_reply(reply, res)
+            }
+            ReplicaAction::GetSyncInterest { reply } => {
+                send_reply(reply, self.store.get_sync_interest(&namespace))
+            }
+            ReplicaAction::Delegate {
+                scope,
+                issuer,
+                reply,
+            } => send_reply_with(reply, self, move |this| {
+                let issuer = match issuer {
+                    Some(id) => Some(get_author(&mut this.store, &id)?),
+                    None => None,
+                };
+                let mut replica = this.states.replica(namespace, &mut this.store)?;
+                let delegation = replica.delegate(scope, issuer.as_ref())?;
+                Ok(delegation)
+            }),
+            ReplicaAction::AddDelegation { delegation, reply } => {
+                send_reply_with(reply, self, move |this| {
+                    let mut replica = this.states.replica(namespace, &mut this.store)?;
+                    let inserted = replica.add_delegation(delegation)?;
+                    Ok(inserted)
+                })
+            }
+            ReplicaAction::ListDelegations { reply } => send_reply_with(reply, self, |this| {
+                this.states.ensure_open(&namespace)?;
+                this.store.get_delegations(&namespace)
+            }),
+            ReplicaAction::SetAccessPolicy { policy, reply } => {
+                send_reply(reply, self.store.set_access_policy(&namespace, policy))
+            }
+            ReplicaAction::GetAccessPolicy { reply } => {
+                send_reply(reply, self.store.get_access_policy(&namespace))
+            }
+            ReplicaAction::SetTrustedMarkerAuthors { authors, reply } => send_reply(
+                reply,
+                self.store.set_trusted_marker_authors(&namespace, authors),
+            ),
+            ReplicaAction::GetTrustedMarkerAuthors { reply } => {
+                send_reply(reply, self.store.get_trusted_marker_authors(&namespace))
+            }
+            ReplicaAction::CreateReadToken {
+                node,
+                expires,
+                reply,
+            } => send_reply_with(reply, self, move |this| {
+                let state = this.states.get_mut(&namespace)?;
+                let secret = state.info.capability.secret_key()?;
+                Ok(ReadToken::new(secret, node, expires))
+            }),
+            ReplicaAction::SetReadToken { token, reply } => {
+                send_reply_with(reply, self, move |this| {
+                    let now = system_time_now();
+                    token.verify(&this.store, &namespace, &token.node(), now)?;
+                    this.store.set_read_token(&token)
+                })
+            }
+            ReplicaAction::GetReadToken { reply } => {
+                send_reply(reply, self.store.get_read_token(&namespace))
+            }
+            ReplicaAction::SetEncryptionKey { key, reply } => {
+                send_reply_with(reply, self, move |this| {
+                    let key = match key {
+                        Some(key) => key,
+                        None => {
+                            let state = this.states.get_mut(&namespace)?;
+                            let secret = state.info.capability.secret_key()?;
+                            DocEncryptionKey::from_namespace(secret)
+                        }
+                    };
+                    this.store.set_encryption_key(&namespace, &key)?;
+                    Ok(key)
+                })
+            }
+            ReplicaAction::GetEncryptionKey { reply } => {
+                send_reply(reply, self.store.get_encryption_key(&namespace))
+            }
+            ReplicaAction::Migrate {
+                to,
+                author,
+                keep_authors,
+                reply,
+            } => send_reply_with(reply, self, move |this| {
+                this.migrate(namespace, to, author, keep_authors)
+            }),
+            ReplicaAction::CreateInvite {
+                mode,
+                expires,
+                single_use,
+                invitee,
+                reply,
+            } => send_reply_with(reply, self, move |this| {
+                let state = this.states.get_mut(&namespace)?;
+                let secret = state.info.capability.secret_key()?;
+                Ok(Invite::new(secret, mode, expires, single_use, invitee))
+            }),
+            ReplicaAction::SetInvite {
+                invite,
+                nodes,
+                reply,
+            } => send_reply_with(reply, self, move |this| {
+                invite.verify(&this.store, &namespace, system_time_now())?;
+                this.store.set_invite(&invite, &nodes)
+            }),
+            ReplicaAction::GetInvite { reply } => {
+                send_reply(reply, self.store.get_invite(&namespace))
+            }
+            ReplicaAction::RemoveInvite { reply } => {
+                send_reply(reply, self.store.remove_invite(&namespace))
+            }
+            ReplicaAction::RedeemInvite {
+                peer,
+                invite,
+                reply,
+            } => send_reply_with(reply, self, move |this| {
+                let now = system_time_now();
+                invite.verify(&this.store, &namespace, now)?;
+                invite.verify_redeemer(&peer)?;
+                // Check that we can hand over the capability before the redemption is logged.
+                let capability = match invite.mode() {
+                    CapabilityKind::Read => None,
+                    CapabilityKind::Write => match this.store.get_capability(&namespace)? {
+                        Some(capability @ Capability::Write(_)) => Some(capability),
+                        _ => anyhow::bail!("write capability required to redeem write invite"),
+                    },
+                };
+                if this.store.redeem_invite(&invite, &peer, now)? {
+                    info!(
+                        namespace = %namespace.fmt_short(),
+                        invite = %invite.id(),
+                        peer = %iroh_base::base32::fmt_short(peer),
+                        mode = %invite.mode(),
+                        "invite redeemed"
+                    );
+                }
+                Ok(capability)
+            }),
+            ReplicaAction::ListRedemptions { reply } => {
+                send_reply(reply, self.store.list_redemptions(&namespace))
+            }
+            ReplicaAction::AuthorizeRead { peer, token, reply } => {
+                send_reply_with(reply, self, move |this| {
+                    let policy = this.store.get_access_policy(&namespace)?;
+                    if policy.allows(&peer) {
+                        return Ok(true);
+                    }
+                    let Some(token) = token else {
+                        return Ok(false);
+                    };
+                    let now = system_time_now();
+                    match token.verify(&this.store, &namespace, &peer, now) {
+                        Ok(()) => Ok(true),
+                        Err(err) => {
+                            debug!(?err, "rejecting invalid read token");
+                            Ok(false)
+                        }
+                    }
+                })
+            }
         }
     }
 
+    fn migrate(
+        &mut self,
+        namespace: NamespaceId,
+        to: NamespaceSecret,
+        author: AuthorId,
+        keep_authors: bool,
+    ) -> Result<usize> {
+        let capability = self.store.get_capability(&namespace)?;
+        anyhow::ensure!(
+            matches!(capability, Some(Capability::Write(_))),
+            "write capability required to migrate a namespace"
+        );
+        let fallback = get_author(&mut self.store, &author)?;
+        let id = to.id();
+        self.store.import_namespace(Capability::Write(to))?;
+        if let Some(key) = self.store.get_encryption_key(&namespace)? {
+            self.store.set_encryption_key(&id, &key)?;
+        }
+        self.open(id, OpenOpts::default())?;
+        let res = self.copy_entries(namespace, id, &fallback, keep_authors);
+        self.close(id);
+        res
+    }
+
+    /// Copy the entries of `namespace` to the open replica `to`.
+    ///
+    /// The entries are read in batches, so that large documents are not loaded into memory.
+    fn copy_entries(
+        &mut self,
+        namespace: NamespaceId,
+        to: NamespaceId,
+        fallback: &Author,
+        keep_authors: bool,
+    ) -> Result<usize> {
+        let mut authors = HashMap::new();
+        let mut cursor = None;
+        let mut copied = 0;
+        loop {
+            let query = match keep_authors {
+                true => Query::all().limit(MIGRATE_BATCH_SIZE).build(),
+                false => Query::single_latest_per_key()
+                    .limit(MIGRATE_BATCH_SIZE)
+                    .build(),
+            };
+            let query = match cursor.take() {
+                Some(cursor) => query.continue_after(cursor),
+                None => query,
+            };
+            let entries = self
+                .store
+                .get_many(namespace, query)?
+                .collect::<Result<Vec<_>>>()?;
+            let Some(last) = entries.last() else {
+                break;
+            };
+            cursor = Some(Cursor::from_entry(last.entry()));
+
+            if keep_authors {
+                for entry in &entries {
+                    let id = entry.author();
+                    if let hash_map::Entry::Vacant(e) = authors.entry(id) {
+                        if let Some(author) = self.store.get_author(&id)? {
+                            e.insert(author);
+                        }
+                    }
+                }
+            }
+            let mut replica = self.states.replica(to, &mut self.store)?;
+            for entry in entries {
+                let record = entry.entry().record().clone();
+                if record.is_empty() || crate::is_moved_to_key(entry.key()) {
+                    continue;
+                }
+                let author = authors.get(&entry.author()).unwrap_or(fallback);
+                match replica.insert_record(entry.key(), author, record) {
+                    Ok(_) => copied += 1,
+                    Err(InsertError::NewerEntryExists) => {}
+                    Err(err) => return Err(err.into()),
+                }
+            }
+        }
+        Ok(copied)
+    }
+
     fn close(&mut self, namespace: NamespaceId) -> bool {
         let res = self.states.close(namespace);
         if res {
@@ -876,6 +2171,9 @@ impl Actor {
             if let Some(cb) = &self.content_status_callback {
                 info.set_content_status_callback(Arc::clone(cb));
             }
+            for sender in &self.subscribers {
+                info.subscribe(sender.clone());
+            }
             Ok(info)
         };
         self.states.open_with(namespace, opts, open_cb)
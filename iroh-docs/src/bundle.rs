@@ -0,0 +1,362 @@
+//! Offline bundles of signed entries.
+//!
+//! A bundle holds a set of [`SignedEntry`]s of a single namespace, and optionally the content
+//! blobs they reference, so that document state can be moved between nodes without a connection.
+//! Entries are kept with their original signatures, so that the importing node can check every
+//! namespace and author signature before it inserts the entries like entries received from a
+//! remote peer.
+//!
+//! Bundles are streamed: a [`BundleWriter`] writes one length-prefixed record per entry or blob,
+//! and a [`BundleReader`] reads and verifies them one record at a time, so that neither side
+//! needs to hold the whole bundle in memory. The content of a blob follows its record and is
+//! read in chunks.
+//!
+//! Format: the magic bytes, the version, the namespace id, then a sequence of records, each a
+//! big-endian `u32` length followed by the postcard encoded record, terminated by an end record.
+
+use bytes::{Bytes, BytesMut};
+use iroh_base::hash::Hash;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{NamespaceId, SignedEntry};
+
+/// Magic bytes at the start of a bundle.
+const BUNDLE_MAGIC: &[u8; 8] = b"iroh-dbn";
+
+/// Version of the serialization format of bundles.
+const BUNDLE_VERSION: u8 = 2;
+
+/// Maximum length of a single record, excluding the content of blobs.
+const MAX_RECORD_LEN: usize = 1024 * 1024;
+
+/// Size of the chunks in which the content of blobs is read.
+const BLOB_CHUNK_SIZE: u64 = 64 * 1024;
+
+/// A record in a bundle.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum BundleRecord {
+    /// A signed entry of the namespace of the bundle.
+    Entry(SignedEntry),
+    /// The content of an entry, followed by `size` bytes of content.
+    Blob {
+        /// The hash of the content.
+        hash: Hash,
+        /// The length of the content.
+        size: u64,
+    },
+    /// The end of the bundle.
+    End,
+}
+
+/// Writes a bundle record by record.
+#[derive(Debug)]
+pub struct BundleWriter<W> {
+    inner: W,
+    namespace: NamespaceId,
+    /// Content bytes of the current blob that still have to be written.
+    blob_remaining: u64,
+}
+
+impl<W: AsyncWrite + Unpin> BundleWriter<W> {
+    /// Start a bundle for `namespace`, writing its header to `inner`.
+    pub async fn new(mut inner: W, namespace: NamespaceId) -> Result<Self, BundleError> {
+        inner.write_all(BUNDLE_MAGIC).await?;
+        inner.write_u8(BUNDLE_VERSION).await?;
+        inner.write_all(namespace.as_bytes()).await?;
+        Ok(Self {
+            inner,
+            namespace,
+            blob_remaining: 0,
+        })
+    }
+
+    /// Add an entry to the bundle.
+    ///
+    /// Fails if the entry belongs to another namespace.
+    pub async fn write_entry(&mut self, entry: &SignedEntry) -> Result<(), BundleError> {
+        if entry.namespace() != self.namespace {
+            return Err(BundleError::NamespaceMismatch);
+        }
+        self.write_record(&BundleRecord::Entry(entry.clone())).await
+    }
+
+    /// Start the content of an entry with the given hash and length.
+    ///
+    /// The content has to be written with [`Self::write_blob_data`] before the next record.
+    pub async fn start_blob(&mut self, hash: Hash, size: u64) -> Result<(), BundleError> {
+        self.write_record(&BundleRecord::Blob { hash, size })
+            .await?;
+        self.blob_remaining = size;
+        Ok(())
+    }
+
+    /// Write a chunk of the content of the current blob.
+    pub async fn write_blob_data(&mut self, data: &[u8]) -> Result<(), BundleError> {
+        if data.len() as u64 > self.blob_remaining {
+            return Err(BundleError::BlobLength);
+        }
+        self.inner.write_all(data).await?;
+        self.blob_remaining -= data.len() as u64;
+        Ok(())
+    }
+
+    /// Write the end record and flush the bundle.
+    pub async fn finish(mut self) -> Result<W, BundleError> {
+        self.write_record(&BundleRecord::End).await?;
+        self.inner.flush().await?;
+        Ok(self.inner)
+    }
+
+    async fn write_record(&mut self, record: &BundleRecord) -> Result<(), BundleError> {
+        if self.blob_remaining != 0 {
+            return Err(BundleError::BlobLength);
+        }
+        let bytes = postcard::to_stdvec(record)?;
+        if bytes.len() > MAX_RECORD_LEN {
+            return Err(BundleError::RecordTooLarge);
+        }
+        self.inner.write_u32(bytes.len() as u32).await?;
+        self.inner.write_all(&bytes).await?;
+        Ok(())
+    }
+}
+
+/// Reads and verifies a bundle record by record.
+#[derive(Debug)]
+pub struct BundleReader<R> {
+    inner: R,
+    namespace: NamespaceId,
+    /// Content bytes of the current blob that were not read yet.
+    blob_remaining: u64,
+    done: bool,
+}
+
+impl<R: AsyncRead + Unpin> BundleReader<R> {
+    /// Read the header of a bundle from `inner`.
+    pub async fn new(mut inner: R) -> Result<Self, BundleError> {
+        let mut magic = [0u8; 8];
+        inner
+            .read_exact(&mut magic)
+            .await
+            .map_err(|_| BundleError::InvalidFormat)?;
+        if &magic != BUNDLE_MAGIC {
+            return Err(BundleError::InvalidFormat);
+        }
+        match inner.read_u8().await? {
+            BUNDLE_VERSION => {}
+            version => return Err(BundleError::UnsupportedVersion(version)),
+        }
+        let mut namespace = [0u8; 32];
+        inner.read_exact(&mut namespace).await?;
+        Ok(Self {
+            inner,
+            namespace: NamespaceId::from(namespace),
+            blob_remaining: 0,
+            done: false,
+        })
+    }
+
+    /// The namespace of the entries in this bundle.
+    pub fn namespace(&self) -> NamespaceId {
+        self.namespace
+    }
+
+    /// Read the next record, or `None` after the end record.
+    ///
+    /// The namespace and the signatures of entries are verified. The content of a blob that was
+    /// not read with [`Self::read_blob_data`] is skipped.
+    pub async fn next_record(&mut self) -> Result<Option<BundleRecord>, BundleError> {
+        if self.done {
+            return Ok(None);
+        }
+        while self.read_blob_data().await?.is_some() {}
+        let len = self.inner.read_u32().await? as usize;
+        if len > MAX_RECORD_LEN {
+            return Err(BundleError::RecordTooLarge);
+        }
+        let mut buf = vec![0u8; len];
+        self.inner.read_exact(&mut buf).await?;
+        let record: BundleRecord = postcard::from_bytes(&buf)?;
+        match &record {
+            BundleRecord::Entry(entry) => {
+                if entry.namespace() != self.namespace {
+                    return Err(BundleError::NamespaceMismatch);
+                }
+                entry.verify_delegated(&())?;
+            }
+            BundleRecord::Blob { size, .. } => self.blob_remaining = *size,
+            BundleRecord::End => {
+                self.done = true;
+                return Ok(None);
+            }
+        }
+        Ok(Some(record))
+    }
+
+    /// Read the next chunk of the content of the current blob, or `None` once it was read.
+    pub async fn read_blob_data(&mut self) -> Result<Option<Bytes>, BundleError> {
+        if self.blob_remaining == 0 {
+            return Ok(None);
+        }
+        let len = self.blob_remaining.min(BLOB_CHUNK_SIZE) as usize;
+        let mut buf = BytesMut::zeroed(len);
+        self.inner.read_exact(&mut buf).await?;
+        self.blob_remaining -= len as u64;
+        Ok(Some(buf.freeze()))
+    }
+
+    /// Read the content of the current blob and check that it matches `hash`.
+    pub async fn verify_blob_data(&mut self, hash: Hash) -> Result<(), BundleError> {
+        let mut hasher = blake3::Hasher::new();
+        while let Some(chunk) = self.read_blob_data().await? {
+            hasher.update(&chunk);
+        }
+        if Hash::from(hasher.finalize()) != hash {
+            return Err(BundleError::HashMismatch(hash));
+        }
+        Ok(())
+    }
+
+    /// Read all records and verify the entries and the content of all blobs.
+    ///
+    /// Returns the number of entries and blobs in the bundle.
+    pub async fn verify_all(mut self) -> Result<(usize, usize), BundleError> {
+        let (mut entries, mut blobs) = (0, 0);
+        while let Some(record) = self.next_record().await? {
+            match record {
+                BundleRecord::Entry(_) => entries += 1,
+                BundleRecord::Blob { hash, .. } => {
+                    self.verify_blob_data(hash).await?;
+                    blobs += 1;
+                }
+                BundleRecord::End => unreachable!("end is not returned"),
+            }
+        }
+        Ok((entries, blobs))
+    }
+}
+
+/// Errors for bundle operations.
+#[derive(Debug, thiserror::Error)]
+pub enum BundleError {
+    /// The bytes are not a bundle.
+    #[error("not a document bundle")]
+    InvalidFormat,
+    /// The bundle was written with an unsupported version of the format.
+    #[error("unsupported bundle version {0}")]
+    UnsupportedVersion(u8),
+    /// An entry belongs to another namespace than the bundle.
+    #[error("entry belongs to another namespace")]
+    NamespaceMismatch,
+    /// The signature of an entry is invalid.
+    #[error("invalid entry signature")]
+    BadSignature(#[from] ed25519_dalek::SignatureError),
+    /// The content of a blob does not match its hash.
+    #[error("content of blob {0} does not match its hash")]
+    HashMismatch(Hash),
+    /// The content written for a blob does not match its length.
+    #[error("content of blob does not match its length")]
+    BlobLength,
+    /// A record is larger than the maximum record length.
+    #[error("bundle record too large")]
+    RecordTooLarge,
+    /// A record could not be serialized or deserialized.
+    #[error("invalid bundle encoding")]
+    Postcard(#[from] postcard::Error),
+    /// Reading or writing the bundle failed.
+    #[error("bundle io error")]
+    Io(#[from] std::io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Author, NamespaceSecret, Record};
+
+    #[tokio::test]
+    async fn bundle() {
+        let mut rng = rand::thread_rng();
+        let namespace = NamespaceSecret::new(&mut rng);
+        let other = NamespaceSecret::new(&mut rng);
+        let author = Author::new(&mut rng);
+
+        let data = vec![7u8; BLOB_CHUNK_SIZE as usize + 5];
+        let hash = Hash::new(&data);
+        let record = Record::new_current(hash, data.len() as u64);
+        let entry = SignedEntry::from_parts(&namespace, &author, b"a", record.clone());
+
+        let mut writer = BundleWriter::new(Vec::new(), namespace.id()).await.unwrap();
+        writer.write_entry(&entry).await.unwrap();
+        // entries of other namespaces are rejected
+        let foreign = SignedEntry::from_parts(&other, &author, b"a", record);
+        assert!(matches!(
+            writer.write_entry(&foreign).await,
+            Err(BundleError::NamespaceMismatch)
+        ));
+        writer.start_blob(hash, data.len() as u64).await.unwrap();
+        // records can't start before the content of the blob was written
+        assert!(matches!(
+            writer.write_entry(&entry).await,
+            Err(BundleError::BlobLength)
+        ));
+        writer.write_blob_data(&data[..10]).await.unwrap();
+        writer.write_blob_data(&data[10..]).await.unwrap();
+        let bytes = writer.finish().await.unwrap();
+
+        // roundtrip
+        let mut reader = BundleReader::new(&bytes[..]).await.unwrap();
+        assert_eq!(reader.namespace(), namespace.id());
+        assert_eq!(
+            reader.next_record().await.unwrap(),
+            Some(BundleRecord::Entry(entry.clone()))
+        );
+        assert_eq!(
+            reader.next_record().await.unwrap(),
+            Some(BundleRecord::Blob {
+                hash,
+                size: data.len() as u64
+            })
+        );
+        let mut content = Vec::new();
+        while let Some(chunk) = reader.read_blob_data().await.unwrap() {
+            content.extend_from_slice(&chunk);
+        }
+        assert_eq!(content, data);
+        assert_eq!(reader.next_record().await.unwrap(), None);
+        let reader = BundleReader::new(&bytes[..]).await.unwrap();
+        assert_eq!(reader.verify_all().await.unwrap(), (1, 1));
+
+        assert!(matches!(
+            BundleReader::new(&b"not a bundle"[..]).await,
+            Err(BundleError::InvalidFormat)
+        ));
+
+        // truncated bundles are detected
+        let reader = BundleReader::new(&bytes[..bytes.len() - 1]).await.unwrap();
+        assert!(matches!(reader.verify_all().await, Err(BundleError::Io(_))));
+
+        // tampered blobs are detected
+        let mut tampered = bytes.clone();
+        let end = tampered.len() - 6;
+        tampered[end] ^= 1;
+        let reader = BundleReader::new(&tampered[..]).await.unwrap();
+        assert!(matches!(
+            reader.verify_all().await,
+            Err(BundleError::HashMismatch(_))
+        ));
+
+        // tampered entries are detected
+        let record = Record::new_current(Hash::new(b"world"), 5);
+        let forged = SignedEntry::from_parts(&namespace, &author, b"a", record);
+        let forged = SignedEntry::new(entry.signature().clone(), forged.entry().clone());
+        let mut writer = BundleWriter::new(Vec::new(), namespace.id()).await.unwrap();
+        writer.write_entry(&forged).await.unwrap();
+        let bytes = writer.finish().await.unwrap();
+        let reader = BundleReader::new(&bytes[..]).await.unwrap();
+        assert!(matches!(
+            reader.verify_all().await,
+            Err(BundleError::BadSignature(_))
+        ));
+    }
+}
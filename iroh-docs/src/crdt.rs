@@ -0,0 +1,823 @@
+//! Conflict-free replicated data types on top of documents.
+//!
+//! The entries of a document are last-writer-wins per author and key, so concurrent edits of
+//! different authors to a shared value cannot be merged. The types in this module merge them:
+//! each author writes its edits to its own entries below the key [`prefix`] of a value, and the
+//! merged value is materialized from the latest entries of all authors below that prefix, as
+//! returned by a [`Query::key_prefix`](crate::store::Query::key_prefix) query.
+//!
+//! - [`Counter`]: an integer that is incremented and decremented.
+//! - [`OrSet`]: a set in which adding an element wins over a concurrent removal of it.
+//! - [`Map`]: a map with a last-writer-wins value for each map key.
+//! - [`Text`]: a sequence of characters with concurrent inserts and deletions.
+//!
+//! To read a value, pass the entries below its prefix as [`CrdtEntry`]s to
+//! [`Crdt::materialize`]. The edit methods of the materialized value update it and return the
+//! [`CrdtWrite`]s that the editing author has to write to the document. Entries that cannot be
+//! decoded are ignored, so that a misbehaving author cannot break a value for everyone else.
+//! Entries whose content is not available yet are passed with empty content, which is never
+//! valid, and are ignored as well.
+//!
+//! An author must not edit a value from several nodes at the same time, because the state of a
+//! [`Counter`] and the clock of [`Text`] edits are kept per author.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+};
+
+use bytes::Bytes;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::AuthorId;
+
+/// Key prefix of the entries of CRDT values.
+pub const CRDT_PREFIX: &[u8] = b"\0iroh/crdt/";
+
+/// Version of the encoding of the content of CRDT entries.
+const CONTENT_VERSION: u8 = 1;
+
+/// The key prefix of the entries of the value `name` of type `C`.
+///
+/// The name is prefixed with its length, so that the prefix of a value is never a prefix of the
+/// prefix of another value.
+pub fn prefix<C: Crdt>(name: &[u8]) -> Bytes {
+    let mut key = CRDT_PREFIX.to_vec();
+    key.extend_from_slice(C::KIND.as_bytes());
+    key.push(b'/');
+    key.extend_from_slice(&(name.len() as u32).to_be_bytes());
+    key.extend_from_slice(name);
+    key.into()
+}
+
+/// A data type whose value is merged from the entries of all authors.
+pub trait Crdt: Sized {
+    /// Name of the type in the key prefix of its values.
+    const KIND: &'static str;
+
+    /// Merge the entries of all authors below the prefix of a value.
+    fn materialize(entries: impl IntoIterator<Item = CrdtEntry>) -> Self;
+}
+
+/// An entry below the key prefix of a CRDT value, with its content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrdtEntry {
+    /// The author of the entry.
+    pub author: AuthorId,
+    /// The key of the entry, without the prefix of the value.
+    pub key: Bytes,
+    /// The timestamp of the entry.
+    pub timestamp: u64,
+    /// The content of the entry, empty if the content is not available.
+    pub content: Bytes,
+}
+
+/// An entry to write for an edit of a CRDT value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrdtWrite {
+    /// The key of the entry, without the prefix of the value.
+    pub key: Bytes,
+    /// The content of the entry, never empty.
+    pub content: Bytes,
+}
+
+fn encode<T: Serialize + ?Sized>(value: &T) -> anyhow::Result<Bytes> {
+    let out = postcard::to_extend(value, vec![CONTENT_VERSION])?;
+    Ok(out.into())
+}
+
+fn decode<T: DeserializeOwned>(content: &[u8]) -> Option<T> {
+    match content.split_first() {
+        Some((&CONTENT_VERSION, rest)) => postcard::from_bytes(rest).ok(),
+        _ => None,
+    }
+}
+
+/// Key of the entry with the state of an author in a [`Counter`].
+const COUNTER_KEY: &[u8] = b"c";
+
+/// A counter that is incremented and decremented concurrently.
+///
+/// Each author writes the sums of its own increments and decrements to a single entry, and the
+/// value is the difference of the sums over all authors.
+///
+/// An author whose entry could not be decoded, e.g. because its content is not available yet,
+/// cannot edit the counter: its new sums would replace the earlier ones without including them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Counter {
+    authors: BTreeMap<AuthorId, CounterState>,
+    /// Authors whose entry could not be decoded.
+    unknown: BTreeSet<AuthorId>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct CounterState {
+    incr: u64,
+    decr: u64,
+}
+
+impl Crdt for Counter {
+    const KIND: &'static str = "counter";
+
+    fn materialize(entries: impl IntoIterator<Item = CrdtEntry>) -> Self {
+        let mut counter = Self::default();
+        for entry in entries {
+            if entry.key != COUNTER_KEY {
+                continue;
+            }
+            match decode(&entry.content) {
+                Some(state) => {
+                    counter.authors.insert(entry.author, state);
+                }
+                None => {
+                    counter.unknown.insert(entry.author);
+                }
+            }
+        }
+        counter
+    }
+}
+
+impl Counter {
+    /// The value of the counter, saturated to the range of `i64`.
+    pub fn value(&self) -> i64 {
+        let value = self.authors.values().fold(0i128, |value, state| {
+            value + i128::from(state.incr) - i128::from(state.decr)
+        });
+        value.clamp(i64::MIN.into(), i64::MAX.into()) as i64
+    }
+
+    /// Add `delta` to the counter as `author`.
+    ///
+    /// Fails if the entry of `author` could not be decoded when the counter was materialized.
+    pub fn add(&mut self, author: AuthorId, delta: i64) -> anyhow::Result<CrdtWrite> {
+        anyhow::ensure!(
+            !self.unknown.contains(&author),
+            "the counter state of this author is not available"
+        );
+        let state = self.authors.entry(author).or_default();
+        if delta >= 0 {
+            state.incr = state.incr.saturating_add(delta.unsigned_abs());
+        } else {
+            state.decr = state.decr.saturating_add(delta.unsigned_abs());
+        }
+        Ok(CrdtWrite {
+            key: Bytes::from_static(COUNTER_KEY),
+            content: encode(state)?,
+        })
+    }
+}
+
+/// Unique tag of an element added to an [`OrSet`].
+type Tag = [u8; 16];
+
+/// Key prefix of the entries for added elements of an [`OrSet`].
+const SET_ADD_PREFIX: &[u8] = b"a/";
+/// Key prefix of the entries for removed elements of an [`OrSet`].
+const SET_REMOVE_PREFIX: &[u8] = b"r/";
+
+/// An observed-remove set.
+///
+/// Every addition of an element is written to an entry with a new random tag, and a removal marks
+/// the tags of the element that the removing node has seen as removed. An element is in the set
+/// while it has a tag that is not removed, so adding an element wins over a concurrent removal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrSet<T> {
+    added: BTreeMap<Tag, T>,
+    removed: BTreeSet<Tag>,
+}
+
+impl<T> Default for OrSet<T> {
+    fn default() -> Self {
+        Self {
+            added: Default::default(),
+            removed: Default::default(),
+        }
+    }
+}
+
+fn set_key(prefix: &[u8], tag: &Tag) -> Bytes {
+    [prefix, tag.as_slice()].concat().into()
+}
+
+fn parse_set_key(key: &[u8], prefix: &[u8]) -> Option<Tag> {
+    key.strip_prefix(prefix)?.try_into().ok()
+}
+
+impl<T: DeserializeOwned> Crdt for OrSet<T> {
+    const KIND: &'static str = "set";
+
+    fn materialize(entries: impl IntoIterator<Item = CrdtEntry>) -> Self {
+        let mut set = Self::default();
+        for entry in entries {
+            if let Some(tag) = parse_set_key(&entry.key, SET_ADD_PREFIX) {
+                if let Some(value) = decode(&entry.content) {
+                    set.added.insert(tag, value);
+                }
+            } else if let Some(tag) = parse_set_key(&entry.key, SET_REMOVE_PREFIX) {
+                set.removed.insert(tag);
+            }
+        }
+        set
+    }
+}
+
+impl<T: Serialize + Ord> OrSet<T> {
+    /// The elements of the set.
+    pub fn elements(&self) -> BTreeSet<&T> {
+        self.live().map(|(_, value)| value).collect()
+    }
+
+    /// Whether `value` is in the set.
+    pub fn contains(&self, value: &T) -> bool {
+        self.live().any(|(_, v)| v == value)
+    }
+
+    /// The number of elements in the set.
+    pub fn len(&self) -> usize {
+        self.elements().len()
+    }
+
+    /// Whether the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.live().next().is_none()
+    }
+
+    /// Add `value` to the set.
+    pub fn insert(&mut self, value: T) -> anyhow::Result<CrdtWrite> {
+        let tag: Tag = rand::random();
+        let content = encode(&value)?;
+        self.added.insert(tag, value);
+        Ok(CrdtWrite {
+            key: set_key(SET_ADD_PREFIX, &tag),
+            content,
+        })
+    }
+
+    /// Remove `value` from the set.
+    ///
+    /// Returns no writes if the value is not in the set.
+    pub fn remove(&mut self, value: &T) -> anyhow::Result<Vec<CrdtWrite>> {
+        let tags: Vec<Tag> = self
+            .live()
+            .filter(|(_, v)| *v == value)
+            .map(|(tag, _)| *tag)
+            .collect();
+        let mut writes = Vec::with_capacity(tags.len());
+        for tag in tags {
+            self.removed.insert(tag);
+            writes.push(CrdtWrite {
+                key: set_key(SET_REMOVE_PREFIX, &tag),
+                content: encode(&())?,
+            });
+        }
+        Ok(writes)
+    }
+
+    fn live(&self) -> impl Iterator<Item = (&Tag, &T)> {
+        self.added
+            .iter()
+            .filter(|(tag, _)| !self.removed.contains(*tag))
+    }
+}
+
+/// Key prefix of the entries of a [`Map`].
+const MAP_PREFIX: &[u8] = b"m/";
+
+/// A map with a last-writer-wins value for each map key.
+///
+/// Each author writes its value for a map key, or its removal, to its own entry. The entry with
+/// the latest timestamp wins, and ties are broken by the author.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Map<K, V> {
+    entries: BTreeMap<K, MapValue<V>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MapValue<V> {
+    timestamp: u64,
+    author: AuthorId,
+    value: Option<V>,
+}
+
+impl<K, V> Default for Map<K, V> {
+    fn default() -> Self {
+        Self {
+            entries: Default::default(),
+        }
+    }
+}
+
+impl<K: DeserializeOwned + Ord, V: DeserializeOwned> Crdt for Map<K, V> {
+    const KIND: &'static str = "map";
+
+    fn materialize(entries: impl IntoIterator<Item = CrdtEntry>) -> Self {
+        let mut map = Self::default();
+        for entry in entries {
+            let Some(key) = entry.key.strip_prefix(MAP_PREFIX) else {
+                continue;
+            };
+            let (Ok(key), Some(value)) = (postcard::from_bytes(key), decode(&entry.content)) else {
+                continue;
+            };
+            let value = MapValue {
+                timestamp: entry.timestamp,
+                author: entry.author,
+                value,
+            };
+            match map.entries.get(&key) {
+                Some(current)
+                    if (current.timestamp, current.author) >= (value.timestamp, value.author) => {}
+                _ => {
+                    map.entries.insert(key, value);
+                }
+            }
+        }
+        map
+    }
+}
+
+impl<K: Serialize + Ord, V: Serialize> Map<K, V> {
+    /// The value for `key`.
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.entries.get(key)?.value.as_ref()
+    }
+
+    /// The keys and values of the map, ordered by key.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries
+            .iter()
+            .filter_map(|(key, entry)| Some((key, entry.value.as_ref()?)))
+    }
+
+    /// The number of keys in the map.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// Whether the map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    /// Set the value for `key` as `author`.
+    pub fn insert(&mut self, author: AuthorId, key: K, value: V) -> anyhow::Result<CrdtWrite> {
+        self.write(author, key, Some(value))
+    }
+
+    /// Remove `key` from the map as `author`.
+    pub fn remove(&mut self, author: AuthorId, key: K) -> anyhow::Result<CrdtWrite> {
+        self.write(author, key, None)
+    }
+
+    fn write(&mut self, author: AuthorId, key: K, value: Option<V>) -> anyhow::Result<CrdtWrite> {
+        let entry_key = postcard::to_extend(&key, MAP_PREFIX.to_vec())?;
+        let content = encode(&value)?;
+        // The timestamp of the entry is only known once it is written, so the local edit wins
+        // until the value is materialized again.
+        self.entries.insert(
+            key,
+            MapValue {
+                timestamp: u64::MAX,
+                author,
+                value,
+            },
+        );
+        Ok(CrdtWrite {
+            key: entry_key.into(),
+            content,
+        })
+    }
+}
+
+/// Key prefix of the entries for inserts into a [`Text`].
+const TEXT_INSERT_PREFIX: &[u8] = b"i/";
+/// Key prefix of the entries for deletions from a [`Text`].
+const TEXT_DELETE_PREFIX: &[u8] = b"d/";
+
+/// Identifier of an edit of a [`Text`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+struct OpId {
+    clock: u64,
+    author: AuthorId,
+}
+
+impl OpId {
+    fn to_key(self, prefix: &[u8]) -> Bytes {
+        [prefix, &self.clock.to_be_bytes(), self.author.as_bytes()]
+            .concat()
+            .into()
+    }
+
+    fn from_key(key: &[u8], prefix: &[u8]) -> Option<Self> {
+        let key = key.strip_prefix(prefix)?;
+        if key.len() != 40 {
+            return None;
+        }
+        let (clock, author) = key.split_at(8);
+        let clock = u64::from_be_bytes(clock.try_into().ok()?);
+        let author: [u8; 32] = author.try_into().ok()?;
+        Some(Self {
+            clock,
+            author: author.into(),
+        })
+    }
+}
+
+/// Identifier of a character inserted into a [`Text`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+struct CharId {
+    op: OpId,
+    index: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct InsertOp {
+    after: Option<CharId>,
+    text: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DeleteOp {
+    ranges: Vec<CharRange>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct CharRange {
+    op: OpId,
+    start: u32,
+    end: u32,
+}
+
+/// A sequence of characters that is edited concurrently.
+///
+/// Every insert is written to an entry that places the inserted characters after an existing
+/// character, and every deletion to an entry that marks characters as deleted. Edits are ordered
+/// by a logical clock, and characters inserted after the same character are ordered with the
+/// latest edit first, so that the characters of concurrent inserts at the same position are not
+/// interleaved.
+///
+/// The entries of a text only grow: deleted characters are kept, because concurrent inserts may
+/// refer to them, and every edit is written to an entry of its own. To bound the size of a text
+/// that is edited a lot, write its current content as a single insert to a new value from time to
+/// time, and delete the prefix of the old value.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Text {
+    chars: Vec<(CharId, char)>,
+    clock: u64,
+}
+
+impl Crdt for Text {
+    const KIND: &'static str = "text";
+
+    fn materialize(entries: impl IntoIterator<Item = CrdtEntry>) -> Self {
+        let mut clock = 0;
+        let mut inserts: HashMap<OpId, (Option<CharId>, Vec<char>)> = HashMap::new();
+        let mut deletes = Vec::new();
+        for entry in entries {
+            if let Some(op) = OpId::from_key(&entry.key, TEXT_INSERT_PREFIX) {
+                let Some(InsertOp { after, text }) = decode(&entry.content) else {
+                    continue;
+                };
+                if op.author == entry.author && !text.is_empty() {
+                    clock = clock.max(op.clock);
+                    inserts.insert(op, (after, text.chars().collect()));
+                }
+            } else if let Some(op) = OpId::from_key(&entry.key, TEXT_DELETE_PREFIX) {
+                let Some(DeleteOp { ranges }) = decode(&entry.content) else {
+                    continue;
+                };
+                if op.author == entry.author {
+                    clock = clock.max(op.clock);
+                    deletes.extend(ranges);
+                }
+            }
+        }
+
+        let mut deleted: HashMap<OpId, Vec<bool>> = HashMap::new();
+        for range in deletes {
+            let Some((_, chars)) = inserts.get(&range.op) else {
+                continue;
+            };
+            let flags = deleted
+                .entry(range.op)
+                .or_insert_with(|| vec![false; chars.len()]);
+            let end = (range.end as usize).min(chars.len());
+            let start = (range.start as usize).min(end);
+            flags[start..end].fill(true);
+        }
+
+        let mut children: HashMap<Option<CharId>, Vec<OpId>> = HashMap::new();
+        for (op, (after, _)) in &inserts {
+            children.entry(*after).or_default().push(*op);
+        }
+
+        // Walk the tree of characters depth first. The children of a character are the first
+        // characters of the inserts after it and the next character of its own insert, with the
+        // latest edit first.
+        let mut chars = Vec::new();
+        let mut stack = Vec::new();
+        let mut next: Vec<CharId> = children
+            .get(&None)
+            .into_iter()
+            .flatten()
+            .map(|op| CharId { op: *op, index: 0 })
+            .collect();
+        loop {
+            next.sort_by_key(|id| id.op);
+            stack.append(&mut next);
+            let Some(id) = stack.pop() else {
+                break;
+            };
+            let op_chars = &inserts[&id.op].1;
+            let index = id.index as usize;
+            let is_deleted = deleted.get(&id.op).is_some_and(|flags| flags[index]);
+            if !is_deleted {
+                chars.push((id, op_chars[index]));
+            }
+            next.extend(
+                children
+                    .get(&Some(id))
+                    .into_iter()
+                    .flatten()
+                    .map(|op| CharId { op: *op, index: 0 }),
+            );
+            if index + 1 < op_chars.len() {
+                next.push(CharId {
+                    op: id.op,
+                    index: id.index + 1,
+                });
+            }
+        }
+        Self { chars, clock }
+    }
+}
+
+impl Text {
+    /// The number of characters in the text.
+    pub fn len(&self) -> usize {
+        self.chars.len()
+    }
+
+    /// Whether the text is empty.
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+
+    /// Insert `text` at the character position `pos` as `author`.
+    pub fn insert(
+        &mut self,
+        author: AuthorId,
+        pos: usize,
+        text: &str,
+    ) -> anyhow::Result<CrdtWrite> {
+        anyhow::ensure!(pos <= self.len(), "position out of bounds");
+        anyhow::ensure!(!text.is_empty(), "cannot insert empty text");
+        let op = self.next_op(author);
+        let after = pos.checked_sub(1).map(|pos| self.chars[pos].0);
+        let content = encode(&InsertOp {
+            after,
+            text: text.to_string(),
+        })?;
+        let inserted = text.chars().enumerate().map(|(index, c)| {
+            let index = index as u32;
+            (CharId { op, index }, c)
+        });
+        self.chars.splice(pos..pos, inserted);
+        Ok(CrdtWrite {
+            key: op.to_key(TEXT_INSERT_PREFIX),
+            content,
+        })
+    }
+
+    /// Delete `len` characters starting at the character position `pos` as `author`.
+    pub fn delete(
+        &mut self,
+        author: AuthorId,
+        pos: usize,
+        len: usize,
+    ) -> anyhow::Result<CrdtWrite> {
+        let end = pos
+            .checked_add(len)
+            .filter(|end| *end <= self.len())
+            .ok_or_else(|| anyhow::anyhow!("range out of bounds"))?;
+        anyhow::ensure!(len > 0, "cannot delete empty range");
+        let mut ranges: Vec<CharRange> = Vec::new();
+        for (id, _) in self.chars.drain(pos..end) {
+            match ranges.last_mut() {
+                Some(range) if range.op == id.op && range.end == id.index => range.end += 1,
+                _ => ranges.push(CharRange {
+                    op: id.op,
+                    start: id.index,
+                    end: id.index + 1,
+                }),
+            }
+        }
+        let op = self.next_op(author);
+        Ok(CrdtWrite {
+            key: op.to_key(TEXT_DELETE_PREFIX),
+            content: encode(&DeleteOp { ranges })?,
+        })
+    }
+
+    fn next_op(&mut self, author: AuthorId) -> OpId {
+        self.clock += 1;
+        OpId {
+            clock: self.clock,
+            author,
+        }
+    }
+}
+
+impl fmt::Display for Text {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.chars
+            .iter()
+            .try_for_each(|(_, c)| fmt::Write::write_char(f, *c))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Author;
+
+    /// The entries of a single value in a document, as last-writer-wins per author and key.
+    #[derive(Debug, Default, Clone)]
+    struct Entries {
+        entries: BTreeMap<(AuthorId, Bytes), (u64, Bytes)>,
+        timestamp: u64,
+    }
+
+    impl Entries {
+        fn write(&mut self, author: AuthorId, writes: impl IntoIterator<Item = CrdtWrite>) {
+            for write in writes {
+                self.timestamp += 1;
+                self.entries
+                    .insert((author, write.key), (self.timestamp, write.content));
+            }
+        }
+
+        fn merge(&mut self, other: &Entries) {
+            for (key, value) in &other.entries {
+                match self.entries.get(key) {
+                    Some(current) if current.0 >= value.0 => {}
+                    _ => {
+                        self.entries.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+            self.timestamp = self.timestamp.max(other.timestamp);
+        }
+
+        fn load<C: Crdt>(&self) -> C {
+            C::materialize(
+                self.entries
+                    .iter()
+                    .map(|((author, key), (timestamp, content))| CrdtEntry {
+                        author: *author,
+                        key: key.clone(),
+                        timestamp: *timestamp,
+                        content: content.clone(),
+                    }),
+            )
+        }
+    }
+
+    #[test]
+    fn counter() -> anyhow::Result<()> {
+        let mut rng = rand::thread_rng();
+        let alice = Author::new(&mut rng).id();
+        let bob = Author::new(&mut rng).id();
+        let mut a = Entries::default();
+        let mut b = Entries::default();
+
+        let mut counter = a.load::<Counter>();
+        a.write(alice, [counter.add(alice, 5)?, counter.add(alice, -2)?]);
+        let mut counter = b.load::<Counter>();
+        b.write(bob, [counter.add(bob, 10)?]);
+
+        a.merge(&b);
+        b.merge(&a);
+        assert_eq!(a.load::<Counter>().value(), 13);
+        assert_eq!(b.load::<Counter>().value(), 13);
+
+        // garbage is ignored
+        a.write(
+            bob,
+            [CrdtWrite {
+                key: Bytes::from_static(COUNTER_KEY),
+                content: Bytes::from_static(b"garbage"),
+            }],
+        );
+        assert_eq!(a.load::<Counter>().value(), 3);
+
+        // an author whose state is not available cannot add, which would lose its changes
+        let mut counter = a.load::<Counter>();
+        assert!(counter.add(bob, 1).is_err());
+        assert!(counter.add(alice, 1).is_ok());
+        b.entries
+            .get_mut(&(bob, Bytes::from_static(COUNTER_KEY)))
+            .unwrap()
+            .1 = Bytes::new();
+        let mut counter = b.load::<Counter>();
+        assert_eq!(counter.value(), 3);
+        assert!(counter.add(bob, 1).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn or_set() -> anyhow::Result<()> {
+        let mut rng = rand::thread_rng();
+        let alice = Author::new(&mut rng).id();
+        let bob = Author::new(&mut rng).id();
+        let mut a = Entries::default();
+
+        let mut set = a.load::<OrSet<String>>();
+        a.write(alice, [set.insert("x".to_string())?]);
+        a.write(alice, [set.insert("y".to_string())?]);
+        let mut b = a.clone();
+
+        // concurrent remove and add of the same element: the add wins
+        let mut set = a.load::<OrSet<String>>();
+        a.write(alice, set.remove(&"x".to_string())?);
+        assert!(!set.contains(&"x".to_string()));
+        let mut set = b.load::<OrSet<String>>();
+        b.write(bob, [set.insert("x".to_string())?]);
+        b.write(bob, set.remove(&"y".to_string())?);
+
+        a.merge(&b);
+        b.merge(&a);
+        let set = a.load::<OrSet<String>>();
+        assert_eq!(set, b.load::<OrSet<String>>());
+        assert_eq!(set.elements(), BTreeSet::from([&"x".to_string()]));
+        Ok(())
+    }
+
+    #[test]
+    fn map() -> anyhow::Result<()> {
+        let mut rng = rand::thread_rng();
+        let alice = Author::new(&mut rng).id();
+        let bob = Author::new(&mut rng).id();
+        let mut a = Entries::default();
+        let mut b = Entries::default();
+
+        let mut map = a.load::<Map<String, u32>>();
+        a.write(alice, [map.insert(alice, "x".to_string(), 1)?]);
+        a.write(alice, [map.insert(alice, "y".to_string(), 2)?]);
+        // bob writes later
+        b.timestamp = 10;
+        let mut map = b.load::<Map<String, u32>>();
+        b.write(bob, [map.insert(bob, "x".to_string(), 3)?]);
+        b.write(bob, [map.insert(bob, "y".to_string(), 4)?]);
+        b.write(bob, [map.remove(bob, "x".to_string())?]);
+        assert_eq!(map.get(&"x".to_string()), None);
+
+        a.merge(&b);
+        b.merge(&a);
+        let map = a.load::<Map<String, u32>>();
+        assert_eq!(map, b.load::<Map<String, u32>>());
+        let values: Vec<_> = map.iter().collect();
+        assert_eq!(values, vec![(&"y".to_string(), &4)]);
+        Ok(())
+    }
+
+    #[test]
+    fn text() -> anyhow::Result<()> {
+        let mut rng = rand::thread_rng();
+        let alice = Author::new(&mut rng).id();
+        let bob = Author::new(&mut rng).id();
+        let mut a = Entries::default();
+
+        let mut text = a.load::<Text>();
+        a.write(alice, [text.insert(alice, 0, "hello world")?]);
+        assert_eq!(text.to_string(), "hello world");
+        let mut b = a.clone();
+
+        // concurrent edits at different and at the same positions
+        let mut text = a.load::<Text>();
+        a.write(alice, [text.insert(alice, 5, ", dear")?]);
+        a.write(alice, [text.insert(alice, 0, "A: ")?]);
+        assert_eq!(text.to_string(), "A: hello, dear world");
+        let mut text = b.load::<Text>();
+        b.write(bob, [text.delete(bob, 0, 6)?]);
+        b.write(bob, [text.insert(bob, 0, "B: ")?]);
+        b.write(bob, [text.insert(bob, 8, "!")?]);
+        assert_eq!(text.to_string(), "B: world!");
+
+        a.merge(&b);
+        b.merge(&a);
+        let merged = a.load::<Text>();
+        assert_eq!(merged, b.load::<Text>());
+        let merged = merged.to_string();
+        assert!(merged == "A: B: , dearworld!" || merged == "B: A: , dearworld!");
+
+        // edits on the merged text
+        let mut text = a.load::<Text>();
+        let len = text.len();
+        a.write(alice, [text.delete(alice, 0, len)?]);
+        assert!(a.load::<Text>().is_empty());
+        assert!(text.insert(alice, 1, "x").is_err());
+        Ok(())
+    }
+}
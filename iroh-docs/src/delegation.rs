@@ -0,0 +1,360 @@
+//! Delegated write access to a namespace.
+//!
+//! Holding the [`NamespaceSecret`] allows writing any key as any author, and cannot be taken
+//! back once shared. A [`WriteDelegation`] instead allows a single [`AuthorId`] to write keys
+//! below a prefix, optionally only within a time window. Delegations are signed by the
+//! namespace secret, and a delegate may pass on a narrower delegation to another author if
+//! the delegation allows it.
+//!
+//! Entries written under a delegation carry a signature of the author in place of the
+//! namespace signature, see [`crate::EntrySignature::from_entry_delegated`]. Such entries are
+//! only accepted by a replica which knows a delegation covering the entry's author, key and
+//! timestamp, and only while the time window of the delegation is open. Delegations are
+//! exchanged between peers at the start of each sync session.
+
+use std::{fmt, str::FromStr};
+
+use bytes::Bytes;
+use ed25519_dalek::Signature;
+use iroh_base::base32;
+use serde::{Deserialize, Serialize};
+
+use crate::{store::PublicKeyStore, Author, AuthorId, Entry, NamespaceId, NamespaceSecret};
+
+/// Domain separator for the signatures of delegations.
+const DELEGATION_DOMAIN: &[u8] = b"iroh-docs write delegation";
+
+/// The restrictions of a [`WriteDelegation`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DelegationScope {
+    /// The author that may write entries.
+    pub author: AuthorId,
+    /// The prefix that the keys of the entries must start with.
+    pub prefix: Bytes,
+    /// The earliest entry timestamp allowed, in microseconds since the Unix epoch.
+    pub not_before: Option<u64>,
+    /// The latest entry timestamp allowed, in microseconds since the Unix epoch.
+    pub not_after: Option<u64>,
+    /// Whether the author may delegate its access further.
+    pub delegable: bool,
+}
+
+impl DelegationScope {
+    /// Create a scope that allows `author` to write keys starting with `prefix`.
+    pub fn new(author: AuthorId, prefix: impl Into<Bytes>) -> Self {
+        Self {
+            author,
+            prefix: prefix.into(),
+            not_before: None,
+            not_after: None,
+            delegable: false,
+        }
+    }
+
+    /// Only allow entries with a timestamp at or after `timestamp`.
+    pub fn not_before(mut self, timestamp: u64) -> Self {
+        self.not_before = Some(timestamp);
+        self
+    }
+
+    /// Only allow entries with a timestamp at or before `timestamp`.
+    pub fn not_after(mut self, timestamp: u64) -> Self {
+        self.not_after = Some(timestamp);
+        self
+    }
+
+    /// Allow the author to delegate its access further.
+    pub fn delegable(mut self) -> Self {
+        self.delegable = true;
+        self
+    }
+
+    /// Whether an entry by `author` with `key` and `timestamp` is within this scope.
+    pub fn allows(&self, author: &AuthorId, key: &[u8], timestamp: u64) -> bool {
+        self.author == *author && key.starts_with(&self.prefix) && self.is_active(timestamp)
+    }
+
+    /// Whether `time` is within the time window of this scope.
+    pub fn is_active(&self, time: u64) -> bool {
+        self.not_before.map_or(true, |t| time >= t) && self.not_after.map_or(true, |t| time <= t)
+    }
+
+    /// Whether this scope is equal to or narrower than `parent`.
+    pub fn is_within(&self, parent: &DelegationScope) -> bool {
+        let not_before = match (parent.not_before, self.not_before) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(parent), Some(this)) => this >= parent,
+        };
+        let not_after = match (parent.not_after, self.not_after) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(parent), Some(this)) => this <= parent,
+        };
+        self.prefix.starts_with(&parent.prefix) && not_before && not_after
+    }
+}
+
+/// A single link in a delegation chain.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+struct DelegationLink {
+    scope: DelegationScope,
+    signature: Signature,
+}
+
+/// A signed token granting an author write access to part of a namespace.
+///
+/// The first link of the chain is signed by the namespace secret. Every further link is
+/// signed by the author of the previous link, and must be narrower than it. The last link
+/// determines the access granted by the delegation, see [`Self::scope`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WriteDelegation {
+    namespace: NamespaceId,
+    chain: Vec<DelegationLink>,
+}
+
+impl WriteDelegation {
+    /// Create a new delegation, signed with the namespace secret.
+    pub fn new(namespace: &NamespaceSecret, scope: DelegationScope) -> Self {
+        let id = namespace.id();
+        let signature = namespace.sign(&signing_bytes(&id, None, &scope));
+        Self {
+            namespace: id,
+            chain: vec![DelegationLink { scope, signature }],
+        }
+    }
+
+    /// Delegate the access of this delegation further.
+    ///
+    /// `issuer` must be the author of this delegation, the delegation must be
+    /// [delegable](DelegationScope::delegable) and `scope` must be [within](DelegationScope::is_within)
+    /// the scope of this delegation.
+    pub fn delegate(
+        &self,
+        issuer: &Author,
+        scope: DelegationScope,
+    ) -> Result<WriteDelegation, DelegationError> {
+        let parent = self.last();
+        if parent.scope.author != issuer.id() {
+            return Err(DelegationError::IssuerMismatch);
+        }
+        if !parent.scope.delegable {
+            return Err(DelegationError::NotDelegable);
+        }
+        if !scope.is_within(&parent.scope) {
+            return Err(DelegationError::ScopeExceeded);
+        }
+        let signature = issuer.sign(&signing_bytes(
+            &self.namespace,
+            Some(&parent.signature),
+            &scope,
+        ));
+        let mut chain = self.chain.clone();
+        chain.push(DelegationLink { scope, signature });
+        Ok(Self {
+            namespace: self.namespace,
+            chain,
+        })
+    }
+
+    /// The namespace this delegation grants access to.
+    pub fn namespace(&self) -> NamespaceId {
+        self.namespace
+    }
+
+    /// The access granted by this delegation.
+    pub fn scope(&self) -> &DelegationScope {
+        &self.last().scope
+    }
+
+    /// The author that may write under this delegation.
+    pub fn author(&self) -> AuthorId {
+        self.scope().author
+    }
+
+    /// Verify the signatures of the delegation chain, and that each link is within the scope
+    /// of its predecessor.
+    pub fn verify<S: PublicKeyStore>(&self, store: &S) -> Result<(), DelegationError> {
+        let namespace_key = self.namespace.public_key(store)?;
+        let mut parent: Option<&DelegationLink> = None;
+        for link in &self.chain {
+            let bytes = signing_bytes(&self.namespace, parent.map(|p| &p.signature), &link.scope);
+            match parent {
+                None => namespace_key.verify(&bytes, &link.signature)?,
+                Some(parent) => {
+                    if !parent.scope.delegable {
+                        return Err(DelegationError::NotDelegable);
+                    }
+                    if !link.scope.is_within(&parent.scope) {
+                        return Err(DelegationError::ScopeExceeded);
+                    }
+                    let issuer_key = parent.scope.author.public_key(store)?;
+                    issuer_key.verify(&bytes, &link.signature)?;
+                }
+            }
+            parent = Some(link);
+        }
+        match parent {
+            Some(_) => Ok(()),
+            None => Err(DelegationError::Empty),
+        }
+    }
+
+    /// Whether this delegation allows writing `entry`.
+    ///
+    /// Only the timestamp of the entry is checked against the time window of the delegation, so
+    /// that entries written while the delegation was active are accepted by all peers, also
+    /// after the window closed.
+    ///
+    /// This does not verify the signatures of the delegation, see [`Self::verify`].
+    pub fn covers(&self, entry: &Entry) -> bool {
+        entry.namespace() == self.namespace
+            && self
+                .scope()
+                .allows(&entry.author(), entry.key(), entry.timestamp())
+    }
+
+    /// A unique identifier for this delegation.
+    pub fn id(&self) -> [u8; 32] {
+        blake3::hash(&self.to_bytes()).into()
+    }
+
+    /// Serialize this delegation to bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        postcard::to_stdvec(self).expect("postcard serialization failed")
+    }
+
+    /// Deserialize a delegation from bytes.
+    ///
+    /// The signatures are not verified, see [`Self::verify`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DelegationError> {
+        let delegation: Self = postcard::from_bytes(bytes)?;
+        if delegation.chain.is_empty() {
+            return Err(DelegationError::Empty);
+        }
+        Ok(delegation)
+    }
+
+    fn last(&self) -> &DelegationLink {
+        self.chain.last().expect("delegation chain is never empty")
+    }
+}
+
+fn signing_bytes(
+    namespace: &NamespaceId,
+    parent: Option<&Signature>,
+    scope: &DelegationScope,
+) -> Vec<u8> {
+    let mut out = DELEGATION_DOMAIN.to_vec();
+    out.extend_from_slice(namespace.as_bytes());
+    if let Some(parent) = parent {
+        out.extend_from_slice(&parent.to_bytes());
+    }
+    postcard::to_extend(scope, out).expect("postcard serialization failed")
+}
+
+impl fmt::Display for WriteDelegation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", base32::fmt(self.to_bytes()))
+    }
+}
+
+impl FromStr for WriteDelegation {
+    type Err = DelegationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = base32::parse_vec(s).map_err(|_| DelegationError::Encoding)?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+/// Errors for [`WriteDelegation`] operations.
+#[derive(Debug, thiserror::Error)]
+pub enum DelegationError {
+    /// A signature of the delegation chain is invalid.
+    #[error("invalid delegation signature")]
+    BadSignature(#[from] ed25519_dalek::SignatureError),
+    /// The delegation chain is empty.
+    #[error("delegation chain is empty")]
+    Empty,
+    /// The issuer of a delegation is not the author of its parent delegation.
+    #[error("issuer is not the author of the parent delegation")]
+    IssuerMismatch,
+    /// The parent delegation does not allow further delegation.
+    #[error("delegation may not be delegated further")]
+    NotDelegable,
+    /// The scope of a delegation is wider than the scope of its parent.
+    #[error("delegation scope exceeds the scope of its parent")]
+    ScopeExceeded,
+    /// No delegation covering the requested scope exists.
+    #[error("no delegation covering the requested scope")]
+    NotFound,
+    /// The namespace secret is required but not available.
+    #[error("namespace is read only")]
+    ReadOnly,
+    /// The delegation is for a different namespace.
+    #[error("delegation is for a different namespace")]
+    NamespaceMismatch,
+    /// The delegation could not be decoded.
+    #[error("invalid delegation encoding")]
+    Encoding,
+    /// The delegation could not be deserialized.
+    #[error("invalid delegation encoding")]
+    Postcard(#[from] postcard::Error),
+    /// Storage error.
+    #[error("storage error")]
+    Store(#[from] anyhow::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delegation_chain() {
+        let mut rng = rand::thread_rng();
+        let namespace = NamespaceSecret::new(&mut rng);
+        let alice = Author::new(&mut rng);
+        let bob = Author::new(&mut rng);
+
+        let scope = DelegationScope::new(alice.id(), "/uploads/")
+            .not_after(2000)
+            .delegable();
+        let delegation = WriteDelegation::new(&namespace, scope);
+        delegation.verify(&()).unwrap();
+
+        // wider scopes cannot be delegated
+        let wider = DelegationScope::new(bob.id(), "/");
+        assert!(matches!(
+            delegation.delegate(&alice, wider.not_after(2000)),
+            Err(DelegationError::ScopeExceeded)
+        ));
+        let longer = DelegationScope::new(bob.id(), "/uploads/bob/");
+        assert!(matches!(
+            delegation.delegate(&alice, longer),
+            Err(DelegationError::ScopeExceeded)
+        ));
+        // only the delegate can delegate further
+        let scope = DelegationScope::new(bob.id(), "/uploads/bob/").not_after(1000);
+        assert!(matches!(
+            delegation.delegate(&bob, scope.clone()),
+            Err(DelegationError::IssuerMismatch)
+        ));
+        let sub = delegation.delegate(&alice, scope).unwrap();
+        sub.verify(&()).unwrap();
+        assert_eq!(sub.author(), bob.id());
+        assert!(matches!(
+            sub.delegate(&bob, DelegationScope::new(alice.id(), "/uploads/bob/x")),
+            Err(DelegationError::NotDelegable)
+        ));
+
+        // roundtrip
+        let parsed: WriteDelegation = sub.to_string().parse().unwrap();
+        assert_eq!(parsed, sub);
+
+        // tampering with the scope invalidates the chain
+        let mut tampered = sub.clone();
+        tampered.chain[1].scope.prefix = Bytes::from_static(b"/uploads/");
+        assert!(tampered.verify(&()).is_err());
+    }
+}
@@ -0,0 +1,225 @@
+//! End-to-end encryption of document entries.
+//!
+//! A document can be used in encrypted mode by sharing a [`DocEncryptionKey`] between the
+//! nodes that should be able to read it. The keys of entries are encrypted deterministically, so
+//! that the same key always maps to the same ciphertext and entries for a key can still be looked
+//! up and replaced. The content of entries is encrypted with a random nonce before it is added to
+//! the blob store. Both are sealed with XChaCha20-Poly1305, under a cipher key derived from the
+//! document key.
+//!
+//! Encryption happens before entries are inserted into a replica, so replicas, the sync protocol
+//! and the blob store only ever see ciphertext. Nodes without the key can store and forward an
+//! encrypted document, but cannot read it.
+//!
+//! Since the ciphertext of a key reveals nothing about its cleartext, prefix queries, prefix
+//! deletions and ordering by key do not work as expected on encrypted documents.
+
+use std::{fmt, str::FromStr};
+
+use bytes::Bytes;
+use chacha20poly1305::{aead::Aead, KeyInit, XChaCha20Poly1305, XNonce};
+use iroh_base::base32;
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    store::{KeyFilter, Query},
+    Entry, NamespaceSecret, RecordIdentifier,
+};
+
+/// Context for deriving the document key from the namespace secret.
+const NAMESPACE_KEY_CONTEXT: &str = "iroh-docs 2024 document encryption key";
+/// Context for deriving the key used to compute the nonces for entry keys.
+const NONCE_KEY_CONTEXT: &str = "iroh-docs 2024 entry key nonce";
+/// Context for deriving the cipher key from the document key.
+const CIPHER_KEY_CONTEXT: &str = "iroh-docs 2024 entry cipher key";
+/// Length of the nonce that is prepended to the ciphertext.
+const NONCE_LEN: usize = 24;
+
+/// A symmetric key to encrypt the keys and content of the entries of a document.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DocEncryptionKey([u8; 32]);
+
+impl DocEncryptionKey {
+    /// Derive the encryption key of a document from its namespace secret.
+    pub fn from_namespace(namespace: &NamespaceSecret) -> Self {
+        Self(blake3::derive_key(
+            NAMESPACE_KEY_CONTEXT,
+            &namespace.to_bytes(),
+        ))
+    }
+
+    /// Create a key from a byte array.
+    pub fn from_bytes(bytes: &[u8; 32]) -> Self {
+        Self(*bytes)
+    }
+
+    /// Convert to a byte array.
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0
+    }
+
+    /// Encrypt the key of an entry.
+    ///
+    /// The encryption is deterministic: encrypting the same key twice gives the same result.
+    pub fn encrypt_key(&self, key: &[u8]) -> Bytes {
+        let nonce_key = blake3::derive_key(NONCE_KEY_CONTEXT, &self.0);
+        let hash = blake3::keyed_hash(&nonce_key, key);
+        let nonce: [u8; NONCE_LEN] = hash.as_bytes()[..NONCE_LEN]
+            .try_into()
+            .expect("hash is long enough");
+        self.seal(nonce, key)
+    }
+
+    /// Decrypt the key of an entry.
+    pub fn decrypt_key(&self, key: &[u8]) -> Result<Bytes, EncryptionError> {
+        self.open(key)
+    }
+
+    /// Encrypt the content of an entry.
+    pub fn encrypt_content(&self, content: &[u8]) -> Bytes {
+        let mut nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+        self.seal(nonce, content)
+    }
+
+    /// Decrypt the content of an entry.
+    pub fn decrypt_content(&self, content: &[u8]) -> Result<Bytes, EncryptionError> {
+        self.open(content)
+    }
+
+    /// Return the entry with its key decrypted.
+    ///
+    /// The signatures of the original entry do not cover the returned entry.
+    pub fn decrypt_entry(&self, entry: &Entry) -> Result<Entry, EncryptionError> {
+        let key = self.decrypt_key(entry.key())?;
+        let id = RecordIdentifier::new(entry.namespace(), entry.author(), key);
+        Ok(Entry::new(id, entry.record().clone()))
+    }
+
+    /// Return the query with the key filter encrypted.
+    ///
+    /// Fails for queries with a prefix or range filter, which cannot be applied to encrypted
+    /// keys.
+    pub fn encrypt_query(&self, mut query: Query) -> Result<Query, EncryptionError> {
+        query.filter_key = self.encrypt_key_filter(query.filter_key)?;
+        Ok(query)
+    }
+
+    /// Return the key filter with the key encrypted.
+    ///
+    /// Fails for prefix and range filters, which cannot be applied to encrypted keys.
+    pub fn encrypt_key_filter(&self, filter: KeyFilter) -> Result<KeyFilter, EncryptionError> {
+        match filter {
+            KeyFilter::Any => Ok(KeyFilter::Any),
+            KeyFilter::Exact(key) => Ok(KeyFilter::Exact(self.encrypt_key(&key))),
+            KeyFilter::Prefix(_) => Err(EncryptionError::PrefixQuery),
+            KeyFilter::Range { .. } => Err(EncryptionError::RangeQuery),
+        }
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        let key = blake3::derive_key(CIPHER_KEY_CONTEXT, &self.0);
+        XChaCha20Poly1305::new(&key.into())
+    }
+
+    /// Encrypt `cleartext` and prepend the nonce to the ciphertext.
+    ///
+    /// Sealing two different messages with the same nonce breaks the confidentiality of both, so
+    /// the nonce must either be random or derived from the cleartext with a secret key.
+    fn seal(&self, nonce: [u8; NONCE_LEN], cleartext: &[u8]) -> Bytes {
+        let nonce = XNonce::from(nonce);
+        let ciphertext = self
+            .cipher()
+            .encrypt(&nonce, cleartext)
+            .expect("encryption failed");
+        let mut buffer = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+        buffer.extend_from_slice(&nonce);
+        buffer.extend_from_slice(&ciphertext);
+        buffer.into()
+    }
+
+    fn open(&self, ciphertext: &[u8]) -> Result<Bytes, EncryptionError> {
+        if ciphertext.len() < NONCE_LEN {
+            return Err(EncryptionError::Decrypt);
+        }
+        let (nonce, ciphertext) = ciphertext.split_at(NONCE_LEN);
+        let cleartext = self
+            .cipher()
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| EncryptionError::Decrypt)?;
+        Ok(cleartext.into())
+    }
+}
+
+impl fmt::Debug for DocEncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DocEncryptionKey(..)")
+    }
+}
+
+impl fmt::Display for DocEncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", base32::fmt(self.0))
+    }
+}
+
+impl FromStr for DocEncryptionKey {
+    type Err = EncryptionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = base32::parse_array(s).map_err(|_| EncryptionError::Encoding)?;
+        Ok(Self(bytes))
+    }
+}
+
+/// Errors for [`DocEncryptionKey`] operations.
+#[derive(Debug, thiserror::Error)]
+pub enum EncryptionError {
+    /// The ciphertext was not encrypted with this key, or was modified.
+    #[error("failed to decrypt")]
+    Decrypt,
+    /// Prefix queries are not supported on encrypted keys.
+    #[error("prefix queries are not supported on encrypted documents")]
+    PrefixQuery,
+    /// Key range queries are not supported on encrypted keys.
+    #[error("key range queries are not supported on encrypted documents")]
+    RangeQuery,
+    /// The key could not be decoded.
+    #[error("invalid encryption key encoding")]
+    Encoding,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encryption_roundtrip() {
+        let mut rng = rand::thread_rng();
+        let namespace = NamespaceSecret::new(&mut rng);
+        let key = DocEncryptionKey::from_namespace(&namespace);
+        assert_eq!(key, DocEncryptionKey::from_namespace(&namespace));
+
+        // keys are encrypted deterministically
+        let encrypted = key.encrypt_key(b"/secret/path");
+        assert_eq!(encrypted, key.encrypt_key(b"/secret/path"));
+        assert_ne!(encrypted, key.encrypt_key(b"/secret/other"));
+        assert_eq!(&key.decrypt_key(&encrypted).unwrap()[..], b"/secret/path");
+
+        // content is not
+        let content = key.encrypt_content(b"hello");
+        assert_ne!(content, key.encrypt_content(b"hello"));
+        assert_eq!(&key.decrypt_content(&content).unwrap()[..], b"hello");
+
+        // other keys cannot decrypt
+        let other = DocEncryptionKey::from_namespace(&NamespaceSecret::new(&mut rng));
+        assert!(other.decrypt_key(&encrypted).is_err());
+        assert!(other.decrypt_content(&content).is_err());
+
+        let parsed: DocEncryptionKey = key.to_string().parse().unwrap();
+        assert_eq!(parsed, key);
+
+        assert!(key.encrypt_query(Query::key_prefix("/").build()).is_err());
+    }
+}
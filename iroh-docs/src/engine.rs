@@ -4,28 +4,40 @@
 
 use std::path::PathBuf;
 use std::{
+    collections::VecDeque,
     io,
     str::FromStr,
     sync::{Arc, RwLock},
 };
 
 use anyhow::{bail, Context, Result};
-use futures_lite::{Stream, StreamExt};
+use bytes::Bytes;
+use futures_lite::{FutureExt, Stream, StreamExt};
+use futures_util::future::Either;
 use iroh_blobs::downloader::Downloader;
-use iroh_blobs::{store::EntryStatus, Hash};
+use iroh_blobs::util::local_pool::{LocalPool, LocalPoolHandle};
+use iroh_blobs::{
+    store::{EntryStatus, MapEntry},
+    Hash,
+};
 use iroh_gossip::net::Gossip;
+use iroh_io::AsyncSliceReader;
 use iroh_net::{key::PublicKey, Endpoint, NodeAddr};
 use serde::{Deserialize, Serialize};
 use tokio::sync::{mpsc, oneshot};
 use tokio_util::task::AbortOnDropHandle;
-use tracing::{error, error_span, Instrument};
+use tracing::{debug, error, error_span, Instrument};
 
-use crate::{actor::SyncHandle, ContentStatus, ContentStatusCallback, Entry, NamespaceId};
+use crate::store::{AuthorFilter, IndexSpec, KeyFilter, Query, SyncPolicy, MAX_INLINE_LIMIT};
+use crate::{
+    actor::SyncHandle, migration::moved_to_event, Capability, ContentReaderCallback, ContentStatus,
+    ContentStatusCallback, Entry, NamespaceId,
+};
 use crate::{Author, AuthorId};
 
 use self::live::{LiveActor, ToLiveActor};
 
-pub use self::live::SyncEvent;
+pub use self::live::{SessionEvent, SyncEvent};
 pub use self::state::{Origin, SyncReason};
 
 mod gossip;
@@ -50,6 +62,9 @@ pub struct Engine {
     to_live_actor: mpsc::Sender<ToLiveActor>,
     #[allow(dead_code)]
     actor_handle: Arc<AbortOnDropHandle<()>>,
+    /// The pool owned by the engine, if it was not spawned on an existing pool.
+    #[allow(dead_code)]
+    local_pool: Option<Arc<LocalPool>>,
     #[debug("ContentStatusCallback")]
     content_status_cb: ContentStatusCallback,
 }
@@ -58,7 +73,9 @@ impl Engine {
     /// Start the sync engine.
     ///
     /// This will spawn two tokio tasks for the live sync coordination and gossip actors, and a
-    /// thread for the [`crate::actor::SyncHandle`].
+    /// thread for the [`crate::actor::SyncHandle`]. Content is read from `bao_store` on a
+    /// [`LocalPool`] owned by the engine, see [`Self::spawn_with_local_pool`] to use an existing
+    /// pool instead.
     pub async fn spawn<B: iroh_blobs::store::Store>(
         endpoint: Endpoint,
         gossip: Gossip,
@@ -66,6 +83,34 @@ impl Engine {
         bao_store: B,
         downloader: Downloader,
         default_author_storage: DefaultAuthorStorage,
+    ) -> anyhow::Result<Self> {
+        let local_pool = Arc::new(LocalPool::single());
+        let mut engine = Self::spawn_with_local_pool(
+            endpoint,
+            gossip,
+            replica_store,
+            bao_store,
+            downloader,
+            local_pool.handle().clone(),
+            default_author_storage,
+        )
+        .await?;
+        engine.local_pool = Some(local_pool);
+        Ok(engine)
+    }
+
+    /// Start the sync engine, reading content on an existing pool.
+    ///
+    /// Content is read from `bao_store` on the `local_pool` to send small values inline and to
+    /// maintain the secondary indexes of documents. See [`Self::spawn`].
+    pub async fn spawn_with_local_pool<B: iroh_blobs::store::Store>(
+        endpoint: Endpoint,
+        gossip: Gossip,
+        replica_store: crate::store::Store,
+        bao_store: B,
+        downloader: Downloader,
+        local_pool: LocalPoolHandle,
+        default_author_storage: DefaultAuthorStorage,
     ) -> anyhow::Result<Self> {
         let (live_actor_tx, to_live_actor_recv) = mpsc::channel(ACTOR_CHANNEL_CAP);
         let me = endpoint.node_id().fmt_short();
@@ -74,7 +119,20 @@ impl Engine {
             let bao_store = bao_store.clone();
             Arc::new(move |hash| entry_to_content_status(bao_store.entry_status_sync(&hash)))
         };
-        let sync = SyncHandle::spawn(replica_store, Some(content_status_cb.clone()), me.clone());
+        // blob readers are not `Send`, so the content is read on the local pool
+        let content_reader: ContentReaderCallback = {
+            let bao_store = bao_store.clone();
+            let local_pool = local_pool.clone();
+            Arc::new(move |hash| {
+                let bao_store = bao_store.clone();
+                let run = local_pool.try_spawn(move || async move {
+                    read_inline_content(&bao_store, hash).await.ok().flatten()
+                });
+                async move { run.ok()?.await.ok().flatten() }.boxed()
+            })
+        };
+        let sync = SyncHandle::spawn(replica_store, Some(content_status_cb.clone()), me.clone())
+            .with_content_reader(content_reader);
 
         let actor = LiveActor::new(
             sync.clone(),
@@ -82,6 +140,7 @@ impl Engine {
             gossip.clone(),
             bao_store,
             downloader,
+            local_pool,
             to_live_actor_recv,
             live_actor_tx.clone(),
         );
@@ -109,6 +168,7 @@ impl Engine {
             sync,
             to_live_actor: live_actor_tx,
             actor_handle: Arc::new(AbortOnDropHandle::new(actor_handle)),
+            local_pool: None,
             content_status_cb,
             default_author: Arc::new(default_author),
         })
@@ -148,6 +208,87 @@ impl Engine {
         Ok(())
     }
 
+    /// Set the [`SyncPolicy`] of a document.
+    ///
+    /// The policy is stored with the document, and applies to the live sync right away.
+    pub async fn set_sync_policy(&self, namespace: NamespaceId, policy: SyncPolicy) -> Result<()> {
+        self.sync.set_sync_policy(namespace, policy.clone()).await?;
+        let (reply, reply_rx) = oneshot::channel();
+        self.to_live_actor
+            .send(ToLiveActor::SetSyncPolicy {
+                namespace,
+                policy,
+                reply,
+            })
+            .await?;
+        reply_rx.await??;
+        Ok(())
+    }
+
+    /// Create a secondary index of a document, see [`IndexSpec`].
+    ///
+    /// Replaces an existing index with the same name. The entries of the document whose content is
+    /// complete are indexed before this returns. Entries inserted later are indexed in the
+    /// background once their content is available.
+    pub async fn create_index(
+        &self,
+        namespace: NamespaceId,
+        name: String,
+        spec: IndexSpec,
+    ) -> Result<()> {
+        self.sync
+            .create_index(namespace, name, spec.clone())
+            .await?;
+        let (reply, reply_rx) = oneshot::channel();
+        self.to_live_actor
+            .send(ToLiveActor::IndexCreated {
+                namespace,
+                spec,
+                reply,
+            })
+            .await?;
+        reply_rx.await??;
+        Ok(())
+    }
+
+    /// Remove a secondary index of a document.
+    ///
+    /// Returns `false` if the index did not exist.
+    pub async fn remove_index(&self, namespace: NamespaceId, name: String) -> Result<bool> {
+        let removed = self.sync.remove_index(namespace, name).await?;
+        self.to_live_actor
+            .send(ToLiveActor::IndexRemoved { namespace })
+            .await?;
+        Ok(removed)
+    }
+
+    /// Start to sync the successor of a document that was moved to the namespace `to`.
+    ///
+    /// Fails if the document does not contain a valid marker entry pointing to `to`. Anyone who
+    /// knows the secret of the document can write such a marker, so the caller has to decide
+    /// whether to trust the author of the marker, see [`LiveEvent::Migrated`]. Markers of the
+    /// authors set with [`SyncHandle::set_trusted_marker_authors`] are followed automatically.
+    ///
+    /// The successor is imported with read access and synced with the peers of the document. The
+    /// encryption key of the document is kept, because the copied entries are still encrypted
+    /// with it.
+    pub async fn follow_migration(&self, namespace: NamespaceId, to: NamespaceId) -> Result<()> {
+        let query = Query::key_exact(crate::moved_to_key(&to)).build();
+        let (tx, rx) = async_channel::bounded(SUBSCRIBE_CHANNEL_CAP);
+        self.sync.get_many(namespace, query, tx).await?;
+        let mut found = false;
+        while let Ok(entry) = rx.recv().await {
+            if crate::moved_to(&entry?) == Some(to) {
+                found = true;
+            }
+        }
+        anyhow::ensure!(found, "document was not moved to {}", to.fmt_short());
+
+        debug!(namespace=%namespace.fmt_short(), to=%to.fmt_short(), "follow moved document");
+        let peers = import_successor(&self.sync, namespace, to).await?;
+        self.start_sync(to, peers).await
+    }
+
     /// Subscribe to replica and sync progress events.
     pub async fn subscribe(
         &self,
@@ -163,7 +304,13 @@ impl Engine {
         let a = {
             let (s, r) = async_channel::bounded(SUBSCRIBE_CHANNEL_CAP);
             this.sync.subscribe(namespace, s).await?;
-            Box::pin(r).map(move |ev| LiveEvent::from_replica_event(ev, &content_status_cb))
+            Box::pin(r).flat_map(move |ev| {
+                // Emit an additional event if the namespace was moved to a successor.
+                let migrated =
+                    moved_to_event(&ev).map(|(to, author)| Ok(LiveEvent::Migrated { to, author }));
+                let ev = LiveEvent::from_replica_event(ev, &content_status_cb);
+                futures_lite::stream::iter(std::iter::once(ev).chain(migrated))
+            })
         };
 
         // Subscribe to events from the [`live::Actor`].
@@ -185,6 +332,85 @@ impl Engine {
         Ok(a.or(b))
     }
 
+    /// Subscribe to replica and sync progress events, with entry events filtered by `filter`.
+    ///
+    /// Events that carry entries are only emitted for entries that match the filter, all other
+    /// events are passed through. If `replay` is true, the existing entries that match the filter
+    /// are emitted as [`LiveEvent::Replayed`] first. Entries that are inserted while the existing
+    /// entries are replayed may be emitted twice.
+    ///
+    /// The existing entries are streamed to the subscriber as they are read. Live events are
+    /// buffered in the meantime, and emitted once the replay is finished.
+    pub async fn subscribe_with_filter(
+        &self,
+        namespace: NamespaceId,
+        filter: SubscribeFilter,
+        replay: bool,
+    ) -> Result<impl Stream<Item = Result<LiveEvent>> + Unpin + 'static> {
+        let live_filter = filter.clone();
+        let mut live = self
+            .subscribe(namespace)
+            .await?
+            .filter_map(move |event| match event {
+                Ok(event) => live_filter.apply(event).map(Ok),
+                Err(err) => Some(Err(err)),
+            });
+        if !replay {
+            return Ok(Either::Left(live));
+        }
+        let (tx, rx) = async_channel::bounded(SUBSCRIBE_CHANNEL_CAP);
+        let sync = self.sync.clone();
+        tokio::task::spawn(async move {
+            let tx2 = tx.clone();
+            if let Err(err) = sync.get_many(namespace, filter.query(), tx).await {
+                tx2.send(Err(err)).await.ok();
+            }
+        });
+        let (out_tx, out_rx) = mpsc::channel(SUBSCRIBE_CHANNEL_CAP);
+        let task = tokio::task::spawn(async move {
+            // Live events have to be received at all times, also while waiting for a slow
+            // subscriber, because the replica actor blocks on sending events to subscribers with
+            // a full channel. They are buffered until the replay is finished and sent.
+            let mut buffered = VecDeque::new();
+            let mut replaying = true;
+            let mut live_done = false;
+            // the next event to send to the subscriber
+            let mut next = None;
+            loop {
+                if next.is_none() && !replaying {
+                    next = buffered.pop_front();
+                    if next.is_none() {
+                        break;
+                    }
+                }
+                tokio::select! {
+                    entry = rx.recv(), if replaying && next.is_none() => match entry {
+                        Ok(entry) => {
+                            next = Some(entry.map(|entry| LiveEvent::Replayed {
+                                entry: entry.into(),
+                            }));
+                        }
+                        Err(_) => replaying = false,
+                    },
+                    event = live.next(), if !live_done => match event {
+                        Some(event) => buffered.push_back(event),
+                        None => live_done = true,
+                    },
+                    permit = out_tx.reserve(), if next.is_some() => {
+                        permit.ok()?.send(next.take().expect("checked above"));
+                    }
+                }
+            }
+            Some(live)
+        });
+        // the live events are emitted once the replay task finished
+        let live = futures_lite::stream::once_future(AbortOnDropHandle::new(task))
+            .filter_map(|live| live.ok().flatten())
+            .flatten();
+        let replayed = tokio_stream::wrappers::ReceiverStream::new(out_rx);
+        Ok(Either::Right(replayed.chain(live)))
+    }
+
     /// Handle an incoming iroh-docs connection.
     pub async fn handle_connection(
         &self,
@@ -220,6 +446,48 @@ pub fn entry_to_content_status(entry: io::Result<EntryStatus>) -> ContentStatus
     }
 }
 
+/// Read the content of a blob to send it inline, if it is complete and not longer than
+/// [`MAX_INLINE_LIMIT`].
+async fn read_inline_content<B: iroh_blobs::store::Store>(
+    bao_store: &B,
+    hash: Hash,
+) -> Result<Option<Bytes>> {
+    let Some(entry) = bao_store.get(&hash).await? else {
+        return Ok(None);
+    };
+    let size = entry.size().value();
+    if !entry.is_complete() || size > MAX_INLINE_LIMIT {
+        return Ok(None);
+    }
+    let mut reader = entry.data_reader().await?;
+    let content = reader.read_at(0, size as usize).await?;
+    Ok(Some(content))
+}
+
+/// Import the successor `to` of a moved document with read access, and return the peers of the
+/// document to sync the successor with.
+///
+/// The encryption key of the document is kept, because the copied entries are still encrypted
+/// with it.
+async fn import_successor(
+    sync: &SyncHandle,
+    namespace: NamespaceId,
+    to: NamespaceId,
+) -> Result<Vec<NodeAddr>> {
+    sync.import_namespace(Capability::Read(to)).await?;
+    if let Some(key) = sync.get_encryption_key(namespace).await? {
+        sync.set_encryption_key(to, Some(key)).await?;
+    }
+    let peers = sync
+        .get_sync_peers(namespace)
+        .await?
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|peer| PublicKey::from_bytes(&peer).ok().map(NodeAddr::new))
+        .collect();
+    Ok(peers)
+}
+
 /// Events informing about actions of the live sync progress.
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, strum::Display)]
 pub enum LiveEvent {
@@ -228,6 +496,18 @@ pub enum LiveEvent {
         /// The inserted entry.
         entry: Entry,
     },
+    /// Several local insertions that were applied together in a single batch.
+    InsertLocalBatch {
+        /// The inserted entries, in the order of the batch.
+        entries: Vec<Entry>,
+    },
+    /// An existing entry, emitted for filtered subscriptions that replay matching entries.
+    ///
+    /// See [`Engine::subscribe_with_filter`].
+    Replayed {
+        /// The existing entry.
+        entry: Entry,
+    },
     /// Received a remote insert.
     InsertRemote {
         /// The peer that sent us the entry.
@@ -258,6 +538,21 @@ pub enum LiveEvent {
     NeighborDown(PublicKey),
     /// A set-reconciliation sync finished.
     SyncFinished(SyncEvent),
+    /// A sync session covering this and other documents with a single peer finished.
+    ///
+    /// Reports the combined results of the session, emitted after the [`Self::SyncFinished`]
+    /// events of the individual documents.
+    SessionFinished(SessionEvent),
+    /// The document was moved to a successor namespace.
+    ///
+    /// Emitted after a valid marker entry was inserted, see [`crate::moved_to`]. The marker is
+    /// not followed automatically, see [`Engine::follow_migration`].
+    Migrated {
+        /// The id of the successor namespace.
+        to: NamespaceId,
+        /// The author of the marker entry.
+        author: AuthorId,
+    },
 }
 
 impl From<live::Event> for LiveEvent {
@@ -267,6 +562,7 @@ impl From<live::Event> for LiveEvent {
             live::Event::NeighborUp(peer) => Self::NeighborUp(peer),
             live::Event::NeighborDown(peer) => Self::NeighborDown(peer),
             live::Event::SyncFinished(ev) => Self::SyncFinished(ev),
+            live::Event::SessionFinished(ev) => Self::SessionFinished(ev),
             live::Event::PendingContentReady => Self::PendingContentReady,
         }
     }
@@ -281,8 +577,20 @@ impl LiveEvent {
             crate::Event::LocalInsert { entry, .. } => Self::InsertLocal {
                 entry: entry.into(),
             },
-            crate::Event::RemoteInsert { entry, from, .. } => Self::InsertRemote {
-                content_status: content_status_cb(entry.content_hash()),
+            crate::Event::LocalBatch { entries, .. } => Self::InsertLocalBatch {
+                entries: entries.into_iter().map(Into::into).collect(),
+            },
+            crate::Event::RemoteInsert {
+                entry,
+                from,
+                inline_content,
+                ..
+            } => Self::InsertRemote {
+                content_status: match inline_content {
+                    // content received inline is in the docs store before the event is emitted
+                    Some(_) => ContentStatus::Complete,
+                    None => content_status_cb(entry.content_hash()),
+                },
                 entry: entry.into(),
                 from: PublicKey::from_bytes(&from)?,
             },
@@ -290,6 +598,74 @@ impl LiveEvent {
     }
 }
 
+/// Filter for the entry events of a document subscription.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct SubscribeFilter {
+    /// Only entries with a matching key are emitted.
+    pub key: KeyFilter,
+    /// Only entries from a matching author are emitted.
+    pub author: AuthorFilter,
+}
+
+impl SubscribeFilter {
+    /// Create a filter for entries with a key that starts with `prefix`.
+    pub fn key_prefix(prefix: impl AsRef<[u8]>) -> Self {
+        Self {
+            key: KeyFilter::Prefix(Bytes::copy_from_slice(prefix.as_ref())),
+            ..Default::default()
+        }
+    }
+
+    /// Create a filter for entries with exactly `key`.
+    pub fn key_exact(key: impl AsRef<[u8]>) -> Self {
+        Self {
+            key: KeyFilter::Exact(Bytes::copy_from_slice(key.as_ref())),
+            ..Default::default()
+        }
+    }
+
+    /// Restrict this filter to entries from `author`.
+    pub fn author(mut self, author: AuthorId) -> Self {
+        self.author = AuthorFilter::Exact(author);
+        self
+    }
+
+    /// Test if an entry is matched by this filter.
+    pub fn matches(&self, entry: &Entry) -> bool {
+        self.key.matches(entry.key()) && self.author.matches(&entry.author())
+    }
+
+    /// Apply the filter to an event.
+    ///
+    /// Returns `None` if the event only carries entries that do not match.
+    pub fn apply(&self, event: LiveEvent) -> Option<LiveEvent> {
+        match event {
+            LiveEvent::InsertLocal { ref entry }
+            | LiveEvent::Replayed { ref entry }
+            | LiveEvent::InsertRemote { ref entry, .. } => self.matches(entry).then_some(event),
+            LiveEvent::InsertLocalBatch { entries } => {
+                let entries: Vec<_> = entries.into_iter().filter(|e| self.matches(e)).collect();
+                (!entries.is_empty()).then_some(LiveEvent::InsertLocalBatch { entries })
+            }
+            event => Some(event),
+        }
+    }
+
+    /// The query for the existing entries that match this filter.
+    fn query(&self) -> Query {
+        let mut query = match &self.key {
+            KeyFilter::Any => Query::all(),
+            KeyFilter::Exact(key) => Query::key_exact(key),
+            KeyFilter::Prefix(prefix) => Query::key_prefix(prefix),
+            KeyFilter::Range { start, end } => Query::all().key_range((start.clone(), end.clone())),
+        };
+        if let AuthorFilter::Exact(author) = self.author {
+            query = query.author(author);
+        }
+        query.build()
+    }
+}
+
 /// Where to persist the default author.
 ///
 /// If set to `Mem`, a new author will be created in the docs store before spawning the sync
@@ -334,7 +710,7 @@ impl DefaultAuthorStorage {
                             path.to_string_lossy()
                         )
                     })?;
-                    if docs_store.export_author(author_id).await?.is_none() {
+                    if !docs_store.has_author(author_id).await? {
                         bail!("The default author is missing from the docs store. To recover, delete the file `{}`. Then iroh will create a new default author.", path.to_string_lossy())
                     }
                     Ok(author_id)
@@ -400,7 +776,7 @@ impl DefaultAuthor {
 
     /// Set the default author.
     pub async fn set(&self, author_id: AuthorId, docs_store: &SyncHandle) -> Result<()> {
-        if docs_store.export_author(author_id).await?.is_none() {
+        if !docs_store.has_author(author_id).await? {
             bail!("The author does not exist");
         }
         self.storage.persist(author_id).await?;
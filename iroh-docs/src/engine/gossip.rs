@@ -4,6 +4,7 @@ use anyhow::{Context, Result};
 use bytes::Bytes;
 use futures_lite::StreamExt;
 use futures_util::FutureExt;
+use iroh_blobs::Hash;
 use iroh_gossip::net::{Event, Gossip, GossipEvent, GossipReceiver, GossipSender, JoinOptions};
 use iroh_net::NodeId;
 use tokio::{
@@ -151,6 +152,10 @@ async fn receive_loop(
         };
         match event {
             GossipEvent::Received(msg) => {
+                if !is_admitted(&sync, namespace, msg.delivered_from).await {
+                    debug!(peer = %msg.delivered_from.fmt_short(), "ignoring gossip message from neighbor not allowed by access policy");
+                    continue;
+                }
                 let op: Op = postcard::from_bytes(&msg.content)?;
                 match op {
                     Op::Put(entry) => {
@@ -173,6 +178,47 @@ async fn receive_loop(
                             debug!("ignoring entry received via gossip: {err}");
                         }
                     }
+                    Op::PutMany(entries) => {
+                        debug!(peer = %msg.delivered_from.fmt_short(), namespace = %namespace.fmt_short(), count = entries.len(), "received entries via gossip");
+                        // Insert all entries of the batch in a single transaction.
+                        let content_status = match msg.scope.is_direct() {
+                            true => ContentStatus::Complete,
+                            false => ContentStatus::Missing,
+                        };
+                        let from = *msg.delivered_from.as_bytes();
+                        if let Err(err) = sync
+                            .insert_remote_batch(
+                                namespace,
+                                entries,
+                                from,
+                                content_status,
+                                Default::default(),
+                            )
+                            .await
+                        {
+                            debug!("ignoring entries received via gossip: {err}");
+                        }
+                    }
+                    Op::PutInline { entries, content } => {
+                        debug!(peer = %msg.delivered_from.fmt_short(), namespace = %namespace.fmt_short(), count = entries.len(), "received entries with inline content via gossip");
+                        // The content is only stored for the entries that reference it and pass
+                        // validation.
+                        let content_status = match msg.scope.is_direct() {
+                            true => ContentStatus::Complete,
+                            false => ContentStatus::Missing,
+                        };
+                        let content = content
+                            .into_iter()
+                            .map(|data| (Hash::new(&data), data))
+                            .collect();
+                        let from = *msg.delivered_from.as_bytes();
+                        if let Err(err) = sync
+                            .insert_remote_batch(namespace, entries, from, content_status, content)
+                            .await
+                        {
+                            debug!("ignoring entries received via gossip: {err}");
+                        }
+                    }
                     Op::ContentReady(hash) => {
                         to_sync_actor
                             .send(ToLiveActor::NeighborContentReady {
@@ -213,3 +259,15 @@ async fn receive_loop(
     }
     Ok(())
 }
+
+/// Whether `peer` may be our gossip neighbor for `namespace`, see [`crate::AccessPolicy::allows`].
+async fn is_admitted(sync: &SyncHandle, namespace: NamespaceId, peer: NodeId) -> bool {
+    match sync.get_access_policy(namespace).await {
+        Ok(policy) => policy.allows(peer.as_bytes()),
+        Err(err) => {
+            // fail closed: without a policy we cannot know who may read the document
+            warn!(?err, "failed to read access policy");
+            false
+        }
+    }
+}
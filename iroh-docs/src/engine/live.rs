@@ -1,15 +1,21 @@
 #![allow(missing_docs)]
 
 use std::collections::HashSet;
-use std::{collections::HashMap, time::SystemTime};
+use std::{
+    collections::{hash_map, HashMap},
+    time::{Duration, Instant, SystemTime},
+};
 
 use anyhow::{Context, Result};
+use bytes::Bytes;
 use futures_lite::FutureExt;
 use iroh_blobs::downloader::{DownloadError, DownloadRequest, Downloader};
 use iroh_blobs::get::Stats;
-use iroh_blobs::HashAndFormat;
-use iroh_blobs::{store::EntryStatus, Hash};
+use iroh_blobs::store::{EntryStatus, MapEntry};
+use iroh_blobs::util::local_pool::LocalPoolHandle;
+use iroh_blobs::{BlobFormat, Hash, HashAndFormat};
 use iroh_gossip::net::Gossip;
+use iroh_io::AsyncSliceReader;
 use iroh_metrics::inc;
 use iroh_net::NodeId;
 use iroh_net::{key::PublicKey, Endpoint, NodeAddr};
@@ -18,17 +24,22 @@ use tokio::{
     sync::{self, mpsc, oneshot},
     task::JoinSet,
 };
-use tracing::{debug, error, info, instrument, trace, warn, Instrument, Span};
+use tracing::{debug, error, error_span, info, instrument, trace, warn, Instrument, Span};
 
 use crate::{
     actor::{OpenOpts, SyncHandle},
+    moved_to,
     net::{
-        connect_and_sync, handle_connection, AbortReason, AcceptError, AcceptOutcome, ConnectError,
-        SyncFinished,
+        connect_and_sync, connect_and_sync_many, handle_connection, AbortReason, AcceptError,
+        AcceptOutcome, ConnectError, SessionFinished, SyncFinished,
     },
-    AuthorHeads, ContentStatus, NamespaceId, SignedEntry,
+    store::{IndexSpec, Query, SyncPolicy, MAX_INDEXED_CONTENT_LEN},
+    AccessPolicy, AuthorHeads, AuthorId, ContentStatus, NamespaceId, SignedEntry,
+};
+use crate::{
+    engine::{gossip::GossipState, import_successor},
+    metrics::Metrics,
 };
-use crate::{engine::gossip::GossipState, metrics::Metrics};
 
 // use super::gossip::{GossipActor, ToGossipActor};
 use super::state::{NamespaceStates, Origin, SyncReason};
@@ -36,6 +47,14 @@ use super::state::{NamespaceStates, Origin, SyncReason};
 /// Name used for logging when new node addresses are added from the docs engine.
 const SOURCE_NAME: &str = "docs_engine";
 
+/// How long to collect namespaces to sync with a peer before dialing it.
+///
+/// All namespaces collected for a peer are synced in a single session over one connection.
+const SYNC_BATCH_DELAY: Duration = Duration::from_millis(20);
+
+/// How often to check for namespaces whose periodic resync is due.
+const RESYNC_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
 /// An iroh-docs operation
 ///
 /// This is the message that is broadcast over iroh-gossip.
@@ -43,10 +62,22 @@ const SOURCE_NAME: &str = "docs_engine";
 pub enum Op {
     /// A new entry was inserted into the document.
     Put(SignedEntry),
+    /// Several entries were inserted into the document in a single batch.
+    PutMany(Vec<SignedEntry>),
     /// A peer now has content available for a hash.
     ContentReady(Hash),
     /// We synced with another peer, here's the news.
     SyncReport(SyncReport),
+    /// Entries were inserted, together with the content of their small values.
+    ///
+    /// Sent instead of [`Op::Put`] and [`Op::PutMany`] if the document has an inline limit, see
+    /// [`crate::store::fs::Store::set_inline_limit`].
+    PutInline {
+        /// The inserted entries.
+        entries: Vec<SignedEntry>,
+        /// The content of the values of the entries that is sent inline.
+        content: Vec<Bytes>,
+    },
 }
 
 /// Report of a successful sync with the new heads.
@@ -72,6 +103,21 @@ pub enum ToLiveActor {
         #[debug("onsehot::Sender")]
         reply: sync::oneshot::Sender<anyhow::Result<()>>,
     },
+    SetSyncPolicy {
+        namespace: NamespaceId,
+        policy: SyncPolicy,
+        #[debug("onsehot::Sender")]
+        reply: sync::oneshot::Sender<anyhow::Result<()>>,
+    },
+    IndexCreated {
+        namespace: NamespaceId,
+        spec: IndexSpec,
+        #[debug("onsehot::Sender")]
+        reply: sync::oneshot::Sender<anyhow::Result<()>>,
+    },
+    IndexRemoved {
+        namespace: NamespaceId,
+    },
     Shutdown {
         reply: sync::oneshot::Sender<()>,
     },
@@ -125,6 +171,11 @@ pub enum Event {
     NeighborDown(PublicKey),
     /// A set-reconciliation sync finished.
     SyncFinished(SyncEvent),
+    /// A sync session covering several namespaces with a single peer finished.
+    ///
+    /// Emitted once per session to the subscribers of every namespace in the session, after the
+    /// [`Self::SyncFinished`] events of the individual namespaces.
+    SessionFinished(SessionEvent),
     /// All pending content is now ready.
     ///
     /// This event is only emitted after a sync completed and `Self::SyncFinished` was emitted at
@@ -141,6 +192,7 @@ type SyncConnectRes = (
     SyncReason,
     Result<SyncFinished, ConnectError>,
 );
+type SyncSessionRes = (Vec<SyncConnectRes>, Option<SessionEvent>);
 type SyncAcceptRes = Result<SyncFinished, AcceptError>;
 type DownloadRes = (NamespaceId, Hash, Result<Stats, DownloadError>);
 
@@ -154,6 +206,9 @@ pub struct LiveActor<B: iroh_blobs::store::Store> {
     downloader: Downloader,
     replica_events_tx: async_channel::Sender<crate::Event>,
     replica_events_rx: async_channel::Receiver<crate::Event>,
+    /// Events of all open replicas, to maintain their secondary indexes.
+    index_events_tx: async_channel::Sender<crate::Event>,
+    index_events_rx: async_channel::Receiver<crate::Event>,
 
     /// Send messages to self.
     /// Note: Must not be used in methods called from `Self::run` directly to prevent deadlocks.
@@ -161,16 +216,26 @@ pub struct LiveActor<B: iroh_blobs::store::Store> {
     sync_actor_tx: mpsc::Sender<ToLiveActor>,
     gossip: GossipState,
 
+    /// Namespaces waiting to be synced with a peer, see [`SYNC_BATCH_DELAY`].
+    pending_sync_connect: HashMap<PublicKey, Vec<(NamespaceId, SyncReason)>>,
+    /// When to dial the peers in `pending_sync_connect`.
+    pending_sync_deadline: Option<tokio::time::Instant>,
     /// Running sync futures (from connect).
-    running_sync_connect: JoinSet<SyncConnectRes>,
+    running_sync_connect: JoinSet<SyncSessionRes>,
     /// Running sync futures (from accept).
-    running_sync_accept: JoinSet<SyncAcceptRes>,
+    running_sync_accept: JoinSet<Vec<SyncAcceptRes>>,
     /// Running download futures.
     download_tasks: JoinSet<DownloadRes>,
     /// Content hashes which are wanted but not yet queued because no provider was found.
     missing_hashes: HashSet<Hash>,
     /// Content hashes queued in downloader.
     queued_hashes: QueuedHashes,
+    /// Pool to read blob content on, because blob readers are not `Send`.
+    local_pool: LocalPoolHandle,
+    /// Secondary indexes per namespace, loaded on first use.
+    indexes: HashMap<NamespaceId, Vec<IndexSpec>>,
+    /// Entries to add to the secondary indexes once the download of their content completes.
+    pending_index: HashMap<Hash, Vec<(NamespaceId, AuthorId, Bytes)>>,
 
     /// Subscribers to actor events
     subscribers: SubscribersMap,
@@ -187,21 +252,27 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
         gossip: Gossip,
         bao_store: B,
         downloader: Downloader,
+        local_pool: LocalPoolHandle,
         inbox: mpsc::Receiver<ToLiveActor>,
         sync_actor_tx: mpsc::Sender<ToLiveActor>,
     ) -> Self {
         let (replica_events_tx, replica_events_rx) = async_channel::bounded(1024);
+        let (index_events_tx, index_events_rx) = async_channel::bounded(1024);
         let gossip_state = GossipState::new(gossip, sync.clone(), sync_actor_tx.clone());
         Self {
             inbox,
             sync,
             replica_events_rx,
             replica_events_tx,
+            index_events_tx,
+            index_events_rx,
             endpoint,
             gossip: gossip_state,
             bao_store,
             downloader,
             sync_actor_tx,
+            pending_sync_connect: Default::default(),
+            pending_sync_deadline: None,
             running_sync_connect: Default::default(),
             running_sync_accept: Default::default(),
             subscribers: Default::default(),
@@ -209,6 +280,9 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
             state: Default::default(),
             missing_hashes: Default::default(),
             queued_hashes: Default::default(),
+            local_pool,
+            indexes: Default::default(),
+            pending_index: Default::default(),
         }
     }
 
@@ -230,10 +304,19 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
 
     async fn run_inner(&mut self) -> Result<oneshot::Sender<()>> {
         let mut i = 0;
+        let mut resync_check = tokio::time::interval(RESYNC_CHECK_INTERVAL);
+        resync_check.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        self.sync
+            .subscribe_all(self.index_events_tx.clone())
+            .await
+            .context("failed to subscribe to index events")?;
         loop {
             i += 1;
             trace!(?i, "tick wait");
             inc!(Metrics, doc_live_tick_main);
+            let sync_deadline = self
+                .pending_sync_deadline
+                .unwrap_or_else(tokio::time::Instant::now);
             tokio::select! {
                 biased;
                 msg = self.inbox.recv() => {
@@ -257,18 +340,37 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
                         error!(?err, "Failed to process replica event");
                     }
                 }
+                event = self.index_events_rx.recv() => {
+                    trace!(?i, "tick: index_event");
+                    let event = event.context("index_events closed")?;
+                    self.on_index_event(event).await;
+                }
+                _ = tokio::time::sleep_until(sync_deadline), if self.pending_sync_deadline.is_some() => {
+                    trace!(?i, "tick: pending_sync_connect");
+                    self.connect_pending();
+                }
+                _ = resync_check.tick() => {
+                    trace!(?i, "tick: resync_check");
+                    self.on_resync_check().await;
+                }
                 Some(res) = self.running_sync_connect.join_next(), if !self.running_sync_connect.is_empty() => {
                     trace!(?i, "tick: running_sync_connect");
                     inc!(Metrics, doc_live_tick_running_sync_connect);
-                    let (namespace, peer, reason, res) = res.context("running_sync_connect closed")?;
-                    self.on_sync_via_connect_finished(namespace, peer, reason, res).await;
-
+                    let (results, session) = res.context("running_sync_connect closed")?;
+                    for (namespace, peer, reason, res) in results {
+                        self.on_sync_via_connect_finished(namespace, peer, reason, res).await;
+                    }
+                    if let Some(session) = session {
+                        self.on_session_finished(session).await;
+                    }
                 }
                 Some(res) = self.running_sync_accept.join_next(), if !self.running_sync_accept.is_empty() => {
                     trace!(?i, "tick: running_sync_accept");
                     inc!(Metrics, doc_live_tick_running_sync_accept);
-                    let res = res.context("running_sync_accept closed")?;
-                    self.on_sync_via_accept_finished(res).await;
+                    let results = res.context("running_sync_accept closed")?;
+                    for res in results {
+                        self.on_sync_via_accept_finished(res).await;
+                    }
                 }
                 Some(res) = self.download_tasks.join_next(), if !self.download_tasks.is_empty() => {
                     trace!(?i, "tick: pending_downloads");
@@ -294,13 +396,21 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
                 self.on_sync_report(from, report).await
             }
             ToLiveActor::NeighborUp { namespace, peer } => {
+                if !self.access_policy(namespace).await.allows(peer.as_bytes()) {
+                    debug!(peer = %peer.fmt_short(), namespace = %namespace.fmt_short(), "ignoring neighbor not allowed by access policy");
+                    return Ok(true);
+                }
                 debug!(peer = %peer.fmt_short(), namespace = %namespace.fmt_short(), "neighbor up");
-                self.sync_with_peer(namespace, peer, SyncReason::NewNeighbor);
+                self.sync_with_peer(namespace, peer, SyncReason::NewNeighbor)
+                    .await;
                 self.subscribers
                     .send(&namespace, Event::NeighborUp(peer))
                     .await;
             }
             ToLiveActor::NeighborDown { namespace, peer } => {
+                if !self.access_policy(namespace).await.allows(peer.as_bytes()) {
+                    return Ok(true);
+                }
                 debug!(peer = %peer.fmt_short(), namespace = %namespace.fmt_short(), "neighbor down");
                 self.subscribers
                     .send(&namespace, Event::NeighborDown(peer))
@@ -322,6 +432,29 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
                 let res = self.leave(namespace, kill_subscribers).await;
                 reply.send(res).ok();
             }
+            ToLiveActor::SetSyncPolicy {
+                namespace,
+                policy,
+                reply,
+            } => {
+                self.state.set_policy(&namespace, policy);
+                self.connect_deferred(namespace).await;
+                reply.send(Ok(())).ok();
+            }
+            ToLiveActor::IndexCreated {
+                namespace,
+                spec,
+                reply,
+            } => {
+                self.indexes.remove(&namespace);
+                self.backfill_index(namespace, spec, reply);
+            }
+            ToLiveActor::IndexRemoved { namespace } => {
+                self.indexes.remove(&namespace);
+                if !self.has_indexes(namespace).await {
+                    self.remove_pending_index(namespace);
+                }
+            }
             ToLiveActor::Subscribe {
                 namespace,
                 sender,
@@ -353,18 +486,174 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
     }
 
     #[instrument("connect", skip_all, fields(peer = %peer.fmt_short(), namespace = %namespace.fmt_short()))]
-    fn sync_with_peer(&mut self, namespace: NamespaceId, peer: PublicKey, reason: SyncReason) {
+    async fn sync_with_peer(
+        &mut self,
+        namespace: NamespaceId,
+        peer: PublicKey,
+        reason: SyncReason,
+    ) {
+        // Syncing sends our entries to the peer, so only dial peers the access policy allows.
+        // Peers with a read token have to dial us.
+        if !self.access_policy(namespace).await.allows(peer.as_bytes()) {
+            debug!("peer not allowed by access policy, skip sync");
+            return;
+        }
+        if self.state.at_connect_limit(&namespace) {
+            debug!("too many syncs running, defer sync");
+            self.state.defer_connect(&namespace, peer, reason);
+            return;
+        }
         if !self.state.start_connect(&namespace, peer, reason) {
             return;
         }
-        let endpoint = self.endpoint.clone();
-        let sync = self.sync.clone();
-        let fut = async move {
-            let res = connect_and_sync(&endpoint, &sync, namespace, NodeAddr::new(peer)).await;
-            (namespace, peer, reason, res)
+        trace!("queue sync");
+        self.pending_sync_connect
+            .entry(peer)
+            .or_default()
+            .push((namespace, reason));
+        self.pending_sync_deadline
+            .get_or_insert_with(|| tokio::time::Instant::now() + SYNC_BATCH_DELAY);
+    }
+
+    /// Dial deferred syncs of a namespace, as far as the concurrency limit allows.
+    async fn connect_deferred(&mut self, namespace: NamespaceId) {
+        while let Some((peer, reason)) = self.state.next_deferred(&namespace) {
+            self.sync_with_peer(namespace, peer, reason).await;
+        }
+    }
+
+    /// Start the periodic resync of all namespaces for which it is due.
+    ///
+    /// A namespace is resynced with the peers we synced it with before, and its preferred peers.
+    async fn on_resync_check(&mut self) {
+        for namespace in self.state.due_resyncs(Instant::now()) {
+            let mut peers = self.preferred_peers(namespace);
+            match self.sync.get_sync_peers(namespace).await {
+                Ok(known) => {
+                    let known = known
+                        .unwrap_or_default()
+                        .into_iter()
+                        .filter_map(|peer| PublicKey::from_bytes(&peer).ok());
+                    for peer in known {
+                        if !peers.contains(&peer) {
+                            peers.push(peer);
+                        }
+                    }
+                }
+                Err(err) => warn!(?err, "failed to read peers for periodic resync"),
+            }
+            debug!(namespace = %namespace.fmt_short(), peers = peers.len(), "periodic resync");
+            for peer in peers {
+                self.sync_with_peer(namespace, peer, SyncReason::Periodic)
+                    .await;
+            }
+        }
+    }
+
+    /// The preferred peers of a syncing namespace.
+    fn preferred_peers(&self, namespace: NamespaceId) -> Vec<PublicKey> {
+        let Some(policy) = self.state.policy(&namespace) else {
+            return Vec::new();
+        };
+        let me = self.endpoint.node_id();
+        policy
+            .preferred_peers
+            .iter()
+            .filter_map(|peer| PublicKey::from_bytes(peer).ok())
+            .filter(|peer| *peer != me)
+            .collect()
+    }
+
+    /// Dial all peers with pending namespaces.
+    ///
+    /// A single namespace is synced with a plain sync request, several namespaces for the same
+    /// peer are synced in a single session.
+    fn connect_pending(&mut self) {
+        self.pending_sync_deadline = None;
+        for (peer, pending) in self.pending_sync_connect.drain() {
+            let endpoint = self.endpoint.clone();
+            let sync = self.sync.clone();
+            if let [(namespace, reason)] = pending[..] {
+                let span = error_span!(
+                    "connect",
+                    peer = %peer.fmt_short(),
+                    namespace = %namespace.fmt_short()
+                );
+                let fut = async move {
+                    let res =
+                        connect_and_sync(&endpoint, &sync, namespace, NodeAddr::new(peer)).await;
+                    (vec![(namespace, peer, reason, res)], None)
+                }
+                .instrument(span);
+                self.running_sync_connect.spawn(fut);
+                continue;
+            }
+            let span = error_span!(
+                "connect",
+                peer = %peer.fmt_short(),
+                namespaces = pending.len()
+            );
+            let fut = async move {
+                let reasons: HashMap<_, _> = pending.iter().copied().collect();
+                let namespaces = pending
+                    .into_iter()
+                    .map(|(namespace, _)| namespace)
+                    .collect();
+                match connect_and_sync_many(&endpoint, &sync, namespaces, NodeAddr::new(peer)).await
+                {
+                    Ok(session) => {
+                        let report = SessionEvent::from(&session);
+                        let results = session
+                            .results
+                            .into_iter()
+                            .map(|(namespace, res)| (namespace, peer, reasons[&namespace], res))
+                            .collect();
+                        (results, Some(report))
+                    }
+                    Err(err) => {
+                        // The session failed as a whole, report the failure for each namespace.
+                        let err = anyhow::Error::from(err);
+                        let results = reasons
+                            .into_iter()
+                            .map(|(namespace, reason)| {
+                                let res = Err(ConnectError::sync(anyhow::anyhow!("{err:#}")));
+                                (namespace, peer, reason, res)
+                            })
+                            .collect();
+                        (results, None)
+                    }
+                }
+            }
+            .instrument(span);
+            self.running_sync_connect.spawn(fut);
+        }
+    }
+
+    async fn on_session_finished(&mut self, session: SessionEvent) {
+        info!(
+            peer = %session.peer.fmt_short(),
+            namespaces = session.namespaces.len(),
+            sent = session.entries_sent,
+            recv = session.entries_received,
+            failed = session.failed,
+            "sync session finished"
+        );
+        for namespace in session.namespaces.clone() {
+            self.subscribers
+                .send(&namespace, Event::SessionFinished(session.clone()))
+                .await;
+        }
+    }
+
+    async fn access_policy(&self, namespace: NamespaceId) -> AccessPolicy {
+        match self.sync.get_access_policy(namespace).await {
+            Ok(policy) => policy,
+            Err(err) => {
+                // fail closed: without a policy we cannot know who may read the document
+                warn!(?err, "failed to read access policy");
+                AccessPolicy::restricted([])
+            }
         }
-        .instrument(Span::current());
-        self.running_sync_connect.spawn(fut);
     }
 
     async fn shutdown(&mut self) -> anyhow::Result<()> {
@@ -391,7 +680,22 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
                 .subscribe(self.replica_events_tx.clone());
             self.sync.open(namespace, opts).await?;
             self.state.insert(namespace);
+            let policy = self
+                .sync
+                .get_sync_policy(namespace)
+                .await
+                .unwrap_or_else(|err| {
+                    warn!(?err, "failed to read sync policy, using default");
+                    SyncPolicy::default()
+                });
+            self.state.set_policy(&namespace, policy);
         }
+        // always include the preferred peers
+        peers.extend(
+            self.preferred_peers(namespace)
+                .into_iter()
+                .map(NodeAddr::new),
+        );
         // add the peers stored for this document
         match self.sync.get_sync_peers(namespace).await {
             Ok(None) => {
@@ -437,6 +741,7 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
         if kill_subscribers {
             self.subscribers.remove(&namespace);
         }
+        self.remove_pending_index(namespace);
         Ok(())
     }
 
@@ -462,13 +767,28 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
             }
         }
 
-        // tell gossip to join
-        self.gossip.join(namespace, peer_ids.clone()).await?;
+        // sync with the preferred peers first
+        let mut seen = HashSet::new();
+        peer_ids.retain(|peer| seen.insert(*peer));
+        if let Some(policy) = self.state.policy(&namespace) {
+            peer_ids.sort_by_key(|peer| !policy.is_preferred(peer.as_bytes()));
+        }
+
+        // tell gossip to join, only with the peers the access policy admits as neighbors. The
+        // initial sync below is gated by the policy as well.
+        let access = self.access_policy(namespace).await;
+        let neighbors = peer_ids
+            .iter()
+            .filter(|peer| access.allows(peer.as_bytes()))
+            .copied()
+            .collect();
+        self.gossip.join(namespace, neighbors).await?;
 
         if !peer_ids.is_empty() {
             // trigger initial sync with initial peers
             for peer in peer_ids {
-                self.sync_with_peer(namespace, peer, SyncReason::DirectJoin);
+                self.sync_with_peer(namespace, peer, SyncReason::DirectJoin)
+                    .await;
             }
         }
         Ok(())
@@ -608,8 +928,40 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
         }
 
         if resync {
-            self.sync_with_peer(namespace, peer, SyncReason::Resync);
+            self.sync_with_peer(namespace, peer, SyncReason::Resync)
+                .await;
         }
+
+        // a sync slot may have become free for the deferred syncs
+        self.connect_deferred(namespace).await;
+    }
+
+    /// Encode an [`Op::PutInline`] message for locally inserted entries.
+    ///
+    /// Returns `None` if the namespace has no inline limit, no content is available to send
+    /// inline, or the message would exceed the maximum gossip message size.
+    async fn encode_put_inline(
+        &self,
+        namespace: NamespaceId,
+        entries: &[SignedEntry],
+    ) -> Result<Option<Vec<u8>>> {
+        let limit = match self.sync.get_inline_limit(namespace).await {
+            Ok(limit) => limit,
+            Err(err) => {
+                warn!(?err, "failed to read inline limit");
+                0
+            }
+        };
+        let content = self.sync.read_inline_content(entries, limit).await;
+        if content.is_empty() {
+            return Ok(None);
+        }
+        let op = Op::PutInline {
+            entries: entries.to_vec(),
+            content,
+        };
+        let message = postcard::to_stdvec(&op)?;
+        Ok((message.len() <= self.gossip.max_message_size()).then_some(message))
     }
 
     async fn broadcast_neighbors(&self, namespace: NamespaceId, op: &Op) {
@@ -642,9 +994,15 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
             self.subscribers
                 .send(&namespace, Event::ContentReady { hash })
                 .await;
-            // Inform our neighbors that we have new content ready.
-            self.broadcast_neighbors(namespace, &Op::ContentReady(hash))
-                .await;
+            // Inform our neighbors that we have new content ready. Any node may join the gossip
+            // topic, so the hashes of restricted namespaces are not announced.
+            if !self.access_policy(namespace).await.is_restricted() {
+                self.broadcast_neighbors(namespace, &Op::ContentReady(hash))
+                    .await;
+            }
+            if let Some(entries) = self.pending_index.remove(&hash) {
+                self.spawn_index_task(hash, entries);
+            }
         } else {
             self.missing_hashes.insert(hash);
         }
@@ -682,7 +1040,8 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
         match self.sync.has_news_for_us(report.namespace, heads).await {
             Ok(Some(updated_authors)) => {
                 info!(%updated_authors, "news reported: sync now");
-                self.sync_with_peer(report.namespace, from, SyncReason::SyncReport);
+                self.sync_with_peer(report.namespace, from, SyncReason::SyncReport)
+                    .await;
             }
             Ok(None) => {
                 debug!("no news reported: nothing to do");
@@ -699,9 +1058,49 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
                 debug!(namespace=%namespace.fmt_short(), "replica event: LocalInsert");
                 // A new entry was inserted locally. Broadcast a gossip message.
                 if self.state.is_syncing(&namespace) {
-                    let op = Op::Put(entry.clone());
-                    let message = postcard::to_stdvec(&op)?.into();
-                    self.gossip.broadcast(&namespace, message).await;
+                    if self.access_policy(namespace).await.is_restricted() {
+                        // Any node may join the gossip topic, so the entries of restricted
+                        // namespaces are not broadcast. Instead we report the new head to our
+                        // neighbors, and allowed peers will sync with us.
+                        let mut heads = AuthorHeads::default();
+                        heads.insert(entry.author(), entry.timestamp());
+                        let heads = heads.encode(Some(self.gossip.max_message_size()))?;
+                        let report = SyncReport { namespace, heads };
+                        self.broadcast_neighbors(namespace, &Op::SyncReport(report))
+                            .await;
+                    } else {
+                        let entries = vec![entry];
+                        let message = match self.encode_put_inline(namespace, &entries).await? {
+                            Some(message) => message,
+                            None => postcard::to_stdvec(&Op::Put(entries[0].clone()))?,
+                        };
+                        self.gossip.broadcast(&namespace, message.into()).await;
+                    }
+                }
+            }
+            crate::Event::LocalBatch { namespace, entries } => {
+                debug!(namespace=%namespace.fmt_short(), count = entries.len(), "replica event: LocalBatch");
+                // New entries were inserted locally in a single batch. Broadcast them in a single
+                // gossip message, so that peers insert them together.
+                if self.state.is_syncing(&namespace) {
+                    let mut heads = AuthorHeads::default();
+                    for entry in &entries {
+                        heads.insert(entry.author(), entry.timestamp());
+                    }
+                    let message = match self.encode_put_inline(namespace, &entries).await? {
+                        Some(message) => message,
+                        None => postcard::to_stdvec(&Op::PutMany(entries))?,
+                    };
+                    let restricted = self.access_policy(namespace).await.is_restricted();
+                    if restricted || message.len() > self.gossip.max_message_size() {
+                        // Report the new heads instead, and allowed peers will sync with us.
+                        let heads = heads.encode(Some(self.gossip.max_message_size()))?;
+                        let report = SyncReport { namespace, heads };
+                        self.broadcast_neighbors(namespace, &Op::SyncReport(report))
+                            .await;
+                    } else {
+                        self.gossip.broadcast(&namespace, message.into()).await;
+                    }
                 }
             }
             crate::Event::RemoteInsert {
@@ -710,12 +1109,31 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
                 from,
                 should_download,
                 remote_content_status,
+                inline_content,
             } => {
                 debug!(namespace=%namespace.fmt_short(), "replica event: RemoteInsert");
                 // A new entry was inserted from initial sync or gossip. Queue downloading the
-                // content.
-                if should_download {
-                    let hash = entry.content_hash();
+                // content, unless it was received inline.
+                let hash = entry.content_hash();
+                if let Some(content) = &inline_content {
+                    // Content received inline is already stored in the docs store. It is
+                    // imported into the blob store as well, whatever the download policy, so that
+                    // it can be read and provided like any other content.
+                    match self
+                        .bao_store
+                        .import_bytes(content.clone(), BlobFormat::Raw)
+                        .await
+                    {
+                        Ok(_tag) => {
+                            self.subscribers
+                                .send(&namespace, Event::ContentReady { hash })
+                                .await;
+                        }
+                        Err(err) => {
+                            warn!(?err, hash=%hash.fmt_short(), "failed to import inline content")
+                        }
+                    }
+                } else if should_download {
                     if matches!(remote_content_status, ContentStatus::Complete) {
                         let node_id = PublicKey::from_bytes(&from)?;
                         self.start_download(namespace, hash, node_id, false).await;
@@ -723,12 +1141,210 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
                         self.missing_hashes.insert(hash);
                     }
                 }
+                self.index_entry(namespace, &entry, inline_content, should_download)
+                    .await;
+                if let Some(to) = moved_to(&entry) {
+                    let node = PublicKey::from_bytes(&from)?;
+                    if let Err(err) = self
+                        .follow_trusted_migration(namespace, to, entry.author(), node)
+                        .await
+                    {
+                        warn!(?err, to=%to.fmt_short(), "failed to follow moved document");
+                    }
+                }
             }
         }
 
         Ok(())
     }
 
+    /// Start to sync the successor of a moved namespace if the author of the marker is trusted.
+    ///
+    /// Markers of other authors are only reported to subscribers, see
+    /// [`crate::engine::Engine::follow_migration`].
+    async fn follow_trusted_migration(
+        &mut self,
+        namespace: NamespaceId,
+        to: NamespaceId,
+        author: AuthorId,
+        node: PublicKey,
+    ) -> Result<()> {
+        if self.state.is_syncing(&to) {
+            return Ok(());
+        }
+        let trusted = self.sync.get_trusted_marker_authors(namespace).await?;
+        if !trusted.contains(&author) {
+            return Ok(());
+        }
+        debug!(namespace=%namespace.fmt_short(), to=%to.fmt_short(), author=%author.fmt_short(), "follow moved document of trusted author");
+        let mut peers = import_successor(&self.sync, namespace, to).await?;
+        peers.insert(0, NodeAddr::new(node));
+        self.start_sync(to, peers).await
+    }
+
+    /// Add the entries of local insertions to the secondary indexes of their namespace.
+    ///
+    /// Remote entries of syncing namespaces are indexed in [`Self::on_replica_event`], where
+    /// their content is queued for download. Other remote entries, e.g. from imported bundles, are
+    /// indexed if their content is available.
+    async fn on_index_event(&mut self, event: crate::Event) {
+        match event {
+            crate::Event::LocalInsert { namespace, entry } => {
+                self.index_entry(namespace, &entry, None, false).await;
+            }
+            crate::Event::LocalBatch { namespace, entries } => {
+                for entry in &entries {
+                    self.index_entry(namespace, entry, None, false).await;
+                }
+            }
+            crate::Event::RemoteInsert {
+                namespace,
+                entry,
+                inline_content,
+                ..
+            } if !self.state.is_syncing(&namespace) => {
+                self.index_entry(namespace, &entry, inline_content, false)
+                    .await;
+            }
+            _ => {}
+        }
+    }
+
+    /// Load the secondary indexes of a namespace, if not loaded yet.
+    async fn load_indexes(&mut self, namespace: NamespaceId) -> Option<&Vec<IndexSpec>> {
+        if let hash_map::Entry::Vacant(slot) = self.indexes.entry(namespace) {
+            match self.sync.list_indexes(namespace).await {
+                Ok(indexes) => {
+                    slot.insert(indexes.into_iter().map(|(_name, spec)| spec).collect());
+                }
+                Err(err) => {
+                    warn!(?err, namespace=%namespace.fmt_short(), "failed to load indexes");
+                    return None;
+                }
+            }
+        }
+        self.indexes.get(&namespace)
+    }
+
+    /// Whether the namespace has any secondary indexes.
+    async fn has_indexes(&mut self, namespace: NamespaceId) -> bool {
+        self.load_indexes(namespace)
+            .await
+            .is_some_and(|indexes| !indexes.is_empty())
+    }
+
+    /// Whether entries with `key` are indexed by a secondary index of the namespace.
+    async fn is_indexed(&mut self, namespace: NamespaceId, key: &[u8]) -> bool {
+        self.load_indexes(namespace)
+            .await
+            .is_some_and(|indexes| indexes.iter().any(|spec| spec.matches(key)))
+    }
+
+    /// Forget the entries of a namespace which wait for their content to be indexed.
+    fn remove_pending_index(&mut self, namespace: NamespaceId) {
+        self.pending_index.retain(|_hash, entries| {
+            entries.retain(|(entry_namespace, _, _)| *entry_namespace != namespace);
+            !entries.is_empty()
+        });
+    }
+
+    /// Add an entry to the secondary indexes of its namespace once its content is available.
+    ///
+    /// If the content is neither passed in nor complete in the blob store, and `wait_for_download`
+    /// is set, the entry is indexed once the download of its content completes. Entries waiting
+    /// for their content are not persisted, and are only indexed after a restart if the index is
+    /// created again.
+    async fn index_entry(
+        &mut self,
+        namespace: NamespaceId,
+        entry: &SignedEntry,
+        content: Option<Bytes>,
+        wait_for_download: bool,
+    ) {
+        if !self.is_indexed(namespace, entry.key()).await {
+            return;
+        }
+        let author = entry.author();
+        let key = Bytes::copy_from_slice(entry.key());
+        let hash = entry.content_hash();
+        // deleted entries have no content, and are removed from the indexes
+        let content = if entry.is_empty() {
+            Some(Bytes::new())
+        } else {
+            content
+        };
+        if let Some(content) = content {
+            if let Err(err) = self
+                .sync
+                .index_content(namespace, author, key, hash, content)
+                .await
+            {
+                warn!(?err, namespace=%namespace.fmt_short(), "failed to index entry");
+            }
+            return;
+        }
+        match self.bao_store.entry_status(&hash).await {
+            Ok(EntryStatus::Complete) => {
+                self.spawn_index_task(hash, vec![(namespace, author, key)])
+            }
+            _ if wait_for_download => self
+                .pending_index
+                .entry(hash)
+                .or_default()
+                .push((namespace, author, key)),
+            _ => {}
+        }
+    }
+
+    /// Read the content `hash` on the local pool, and add the entries with this content to the
+    /// secondary indexes of their namespaces.
+    fn spawn_index_task(&self, hash: Hash, entries: Vec<(NamespaceId, AuthorId, Bytes)>) {
+        let sync = self.sync.clone();
+        let bao_store = self.bao_store.clone();
+        let res = self.local_pool.try_spawn_detached(move || async move {
+            let content = match read_index_content(&bao_store, hash).await {
+                Ok(Some(content)) => content,
+                Ok(None) => return,
+                Err(err) => {
+                    warn!(?err, hash=%hash.fmt_short(), "failed to read content to index");
+                    return;
+                }
+            };
+            for (namespace, author, key) in entries {
+                if let Err(err) = sync
+                    .index_content(namespace, author, key, hash, content.clone())
+                    .await
+                {
+                    warn!(?err, namespace=%namespace.fmt_short(), "failed to index entry");
+                }
+            }
+        });
+        if let Err(err) = res {
+            debug!(?err, "failed to spawn index task");
+        }
+    }
+
+    /// Add the entries of a namespace that match a new secondary index to the index.
+    ///
+    /// The content of the entries is read on the local pool, and `reply` is sent once all
+    /// entries with complete content are indexed.
+    fn backfill_index(
+        &self,
+        namespace: NamespaceId,
+        spec: IndexSpec,
+        reply: sync::oneshot::Sender<Result<()>>,
+    ) {
+        let sync = self.sync.clone();
+        let bao_store = self.bao_store.clone();
+        let res = self.local_pool.try_spawn_detached(move || async move {
+            let res = backfill_index(sync, bao_store, namespace, spec).await;
+            reply.send(res).ok();
+        });
+        if let Err(err) = res {
+            debug!(?err, "failed to spawn index task");
+        }
+    }
+
     async fn start_download(
         &mut self,
         namespace: NamespaceId,
@@ -832,6 +1448,40 @@ impl From<&SyncFinished> for SyncDetails {
     }
 }
 
+/// Event emitted when a sync session over several namespaces with a single peer completes.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct SessionEvent {
+    /// Peer we synced with
+    pub peer: PublicKey,
+    /// Namespaces that were part of the session
+    pub namespaces: Vec<NamespaceId>,
+    /// Timestamp when the session finished
+    pub finished: SystemTime,
+    /// Number of entries received, over all namespaces
+    pub entries_received: usize,
+    /// Number of entries sent, over all namespaces
+    pub entries_sent: usize,
+    /// Number of namespaces that failed to sync
+    pub failed: usize,
+}
+
+impl From<&SessionFinished> for SessionEvent {
+    fn from(value: &SessionFinished) -> Self {
+        Self {
+            peer: value.peer,
+            namespaces: value
+                .results
+                .iter()
+                .map(|(namespace, _)| *namespace)
+                .collect(),
+            finished: SystemTime::now(),
+            entries_received: value.num_recv(),
+            entries_sent: value.num_sent(),
+            failed: value.num_failed(),
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 struct SubscribersMap(HashMap<NamespaceId, Subscribers>);
 
@@ -921,6 +1571,46 @@ impl Subscribers {
     }
 }
 
+/// Read the content of a blob to index it, if it is complete and not longer than
+/// [`MAX_INDEXED_CONTENT_LEN`].
+async fn read_index_content<B: iroh_blobs::store::Store>(
+    bao_store: &B,
+    hash: Hash,
+) -> Result<Option<Bytes>> {
+    let Some(entry) = bao_store.get(&hash).await? else {
+        return Ok(None);
+    };
+    let size = entry.size().value();
+    if !entry.is_complete() || size > MAX_INDEXED_CONTENT_LEN {
+        return Ok(None);
+    }
+    let mut reader = entry.data_reader().await?;
+    let content = reader.read_at(0, size as usize).await?;
+    Ok(Some(content))
+}
+
+/// Add the entries of a namespace with complete content to a new secondary index.
+async fn backfill_index<B: iroh_blobs::store::Store>(
+    sync: SyncHandle,
+    bao_store: B,
+    namespace: NamespaceId,
+    spec: IndexSpec,
+) -> Result<()> {
+    let (tx, rx) = async_channel::bounded(64);
+    let query = Query::key_prefix(&spec.key_prefix).build();
+    sync.get_many(namespace, query, tx).await?;
+    while let Ok(entry) = rx.recv().await {
+        let entry = entry?;
+        let hash = entry.content_hash();
+        if let Some(content) = read_index_content(&bao_store, hash).await? {
+            let key = Bytes::copy_from_slice(entry.key());
+            sync.index_content(namespace, entry.author(), key, hash, content)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
 fn fmt_accept_peer(res: &Result<SyncFinished, AcceptError>) -> String {
     match res {
         Ok(res) => res.peer.fmt_short(),
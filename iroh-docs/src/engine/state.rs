@@ -1,11 +1,12 @@
 use crate::{
     net::{AbortReason, AcceptOutcome, SyncFinished},
+    store::{SyncBackoff, SyncPolicy},
     NamespaceId,
 };
 use anyhow::Result;
 use iroh_net::NodeId;
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, VecDeque};
 use std::time::{Instant, SystemTime};
 use tracing::{debug, warn};
 
@@ -20,6 +21,8 @@ pub enum SyncReason {
     SyncReport,
     /// We received a sync report while a sync was running, so run again afterwars
     Resync,
+    /// Periodic resync with the known peers, see [`SyncPolicy::resync_interval`]
+    Periodic,
 }
 
 /// Why we performed a sync exchange
@@ -53,6 +56,10 @@ pub struct NamespaceStates(BTreeMap<NamespaceId, NamespaceState>);
 struct NamespaceState {
     nodes: BTreeMap<NodeId, PeerState>,
     may_emit_ready: bool,
+    policy: SyncPolicy,
+    next_resync: Option<Instant>,
+    /// Syncs held back by the concurrency limit of the policy.
+    deferred: VecDeque<(NodeId, SyncReason)>,
 }
 
 impl NamespaceStates {
@@ -66,6 +73,73 @@ impl NamespaceStates {
         self.0.entry(namespace).or_default();
     }
 
+    /// Set the [`SyncPolicy`] of a syncing namespace.
+    ///
+    /// This schedules the next periodic resync one interval from now.
+    pub fn set_policy(&mut self, namespace: &NamespaceId, policy: SyncPolicy) {
+        let Some(state) = self.0.get_mut(namespace) else {
+            return;
+        };
+        state.next_resync = policy
+            .resync_interval
+            .map(|interval| Instant::now() + interval);
+        state.policy = policy;
+    }
+
+    /// Get the [`SyncPolicy`] of a syncing namespace.
+    pub fn policy(&self, namespace: &NamespaceId) -> Option<&SyncPolicy> {
+        self.0.get(namespace).map(|state| &state.policy)
+    }
+
+    /// Get the namespaces whose periodic resync is due, and schedule their next resync.
+    pub fn due_resyncs(&mut self, now: Instant) -> Vec<NamespaceId> {
+        let mut due = Vec::new();
+        for (namespace, state) in self.0.iter_mut() {
+            let Some(interval) = state.policy.resync_interval else {
+                continue;
+            };
+            if state.next_resync.is_some_and(|next| next <= now) {
+                state.next_resync = Some(now + interval);
+                due.push(*namespace);
+            }
+        }
+        due
+    }
+
+    /// Whether we may not initiate another sync of a namespace, because the maximum number of
+    /// concurrent syncs of its [`SyncPolicy`] is reached.
+    pub fn at_connect_limit(&self, namespace: &NamespaceId) -> bool {
+        self.0
+            .get(namespace)
+            .is_some_and(|state| state.at_connect_limit())
+    }
+
+    /// Hold back a sync until a running sync of the namespace finishes.
+    ///
+    /// Syncs with preferred peers are dialed first.
+    pub fn defer_connect(&mut self, namespace: &NamespaceId, node: NodeId, reason: SyncReason) {
+        let Some(state) = self.0.get_mut(namespace) else {
+            return;
+        };
+        if state.deferred.iter().any(|(queued, _)| *queued == node) {
+            return;
+        }
+        if state.policy.is_preferred(node.as_bytes()) {
+            state.deferred.push_front((node, reason));
+        } else {
+            state.deferred.push_back((node, reason));
+        }
+    }
+
+    /// Take the next deferred sync of a namespace, if the concurrency limit allows to dial it.
+    pub fn next_deferred(&mut self, namespace: &NamespaceId) -> Option<(NodeId, SyncReason)> {
+        let state = self.0.get_mut(namespace)?;
+        if state.at_connect_limit() {
+            return None;
+        }
+        state.deferred.pop_front()
+    }
+
     /// Start a sync request.
     ///
     /// Returns true if the request should be performed, and false if it should be aborted.
@@ -80,7 +154,7 @@ impl NamespaceStates {
                 debug!("abort connect: namespace is not in sync set");
                 false
             }
-            Some(state) => state.start_connect(reason),
+            Some(state) => state.start_connect(reason, Instant::now()),
         }
     }
 
@@ -113,7 +187,13 @@ impl NamespaceStates {
         origin: &Origin,
         result: Result<SyncFinished>,
     ) -> Option<(SystemTime, bool)> {
-        let state = self.entry(namespace, node)?;
+        let namespace_state = self.0.get_mut(namespace)?;
+        let backoff = namespace_state.policy.backoff.as_ref();
+        let state = namespace_state.nodes.entry(node).or_default();
+        // only failures to reach the node delay dialing it, syncs it started are not counted
+        if let (Some(backoff), Origin::Connect(_)) = (backoff, origin) {
+            state.update_backoff(backoff, result.is_ok(), Instant::now());
+        }
         state.finish(origin, result)
     }
 
@@ -158,15 +238,54 @@ impl NamespaceStates {
     }
 }
 
+impl NamespaceState {
+    fn at_connect_limit(&self) -> bool {
+        // a limit of zero is rejected when the policy is set, treat it as no limit regardless
+        let Some(max) = self.policy.max_concurrent.filter(|max| *max > 0) else {
+            return false;
+        };
+        let running = self
+            .nodes
+            .values()
+            .filter(|node| {
+                matches!(
+                    node.state,
+                    SyncState::Running {
+                        origin: Origin::Connect(_),
+                        ..
+                    }
+                )
+            })
+            .count();
+        running >= max as usize
+    }
+}
+
 /// State of a node with regard to a namespace.
 #[derive(Default)]
 struct PeerState {
     state: SyncState,
     resync_requested: bool,
     last_sync: Option<(Instant, Result<SyncFinished>)>,
+    /// Number of consecutive failed syncs.
+    failures: u32,
+    /// Do not dial the node before this time, after failed syncs.
+    backoff_until: Option<Instant>,
 }
 
 impl PeerState {
+    fn update_backoff(&mut self, backoff: &SyncBackoff, success: bool, now: Instant) {
+        if success {
+            self.failures = 0;
+            self.backoff_until = None;
+        } else {
+            self.failures = self.failures.saturating_add(1);
+            let delay = backoff.delay(self.failures);
+            debug!(failures = self.failures, ?delay, "sync failed, backing off");
+            self.backoff_until = Some(now + delay);
+        }
+    }
+
     fn finish(
         &mut self,
         origin: &Origin,
@@ -193,8 +312,13 @@ impl PeerState {
         start.map(|s| (s, self.resync_requested))
     }
 
-    fn start_connect(&mut self, reason: SyncReason) -> bool {
+    fn start_connect(&mut self, reason: SyncReason, now: Instant) -> bool {
         debug!(?reason, "start connect");
+        // explicit joins are not delayed by failures of earlier syncs
+        if reason != SyncReason::DirectJoin && self.backoff_until.is_some_and(|until| until > now) {
+            debug!("abort connect: peer is backing off after failed syncs");
+            return false;
+        }
         match self.state {
             // never run two syncs at the same time
             SyncState::Running { .. } => {
@@ -254,3 +378,116 @@ fn expected_sync_direction(self_node_id: &NodeId, other_node_id: &NodeId) -> Syn
         SyncDirection::Connect
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use iroh_net::key::SecretKey;
+
+    use super::*;
+
+    fn node(i: u8) -> NodeId {
+        SecretKey::from_bytes(&[i; 32]).public()
+    }
+
+    fn finished(namespace: NamespaceId, node: NodeId) -> Result<SyncFinished> {
+        Ok(SyncFinished {
+            namespace,
+            peer: node,
+            outcome: Default::default(),
+            timings: Default::default(),
+        })
+    }
+
+    fn states(policy: SyncPolicy) -> (NamespaceStates, NamespaceId) {
+        let namespace = NamespaceId::from(&[1u8; 32]);
+        let mut states = NamespaceStates::default();
+        states.insert(namespace);
+        states.set_policy(&namespace, policy);
+        (states, namespace)
+    }
+
+    #[test]
+    fn test_deferred_connect() {
+        let policy = SyncPolicy {
+            max_concurrent: Some(1),
+            preferred_peers: vec![*node(3).as_bytes()],
+            ..Default::default()
+        };
+        let (mut states, ns) = states(policy);
+        let reason = SyncReason::NewNeighbor;
+
+        assert!(!states.at_connect_limit(&ns));
+        assert!(states.start_connect(&ns, node(1), reason));
+        assert!(states.at_connect_limit(&ns));
+        // syncs started by peers are not limited
+        assert!(matches!(
+            states.accept_request(&node(0), &ns, node(5)),
+            AcceptOutcome::Allow
+        ));
+
+        // preferred peers are dialed first, and each peer is queued once
+        states.defer_connect(&ns, node(2), reason);
+        states.defer_connect(&ns, node(3), reason);
+        states.defer_connect(&ns, node(2), reason);
+        assert_eq!(states.next_deferred(&ns), None);
+
+        let origin = Origin::Connect(reason);
+        states.finish(&ns, node(1), &origin, finished(ns, node(1)));
+        assert_eq!(states.next_deferred(&ns), Some((node(3), reason)));
+        assert!(states.start_connect(&ns, node(3), reason));
+        assert_eq!(states.next_deferred(&ns), None);
+
+        states.finish(&ns, node(3), &origin, finished(ns, node(3)));
+        assert_eq!(states.next_deferred(&ns), Some((node(2), reason)));
+        assert_eq!(states.next_deferred(&ns), None);
+    }
+
+    #[test]
+    fn test_zero_connect_limit() {
+        let policy = SyncPolicy {
+            max_concurrent: Some(0),
+            ..Default::default()
+        };
+        let (mut states, ns) = states(policy);
+        assert!(!states.at_connect_limit(&ns));
+        states.defer_connect(&ns, node(1), SyncReason::NewNeighbor);
+        assert!(states.next_deferred(&ns).is_some());
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = SyncPolicy {
+            backoff: Some(SyncBackoff {
+                initial: Duration::from_secs(60),
+                max: Duration::from_secs(600),
+            }),
+            ..Default::default()
+        };
+        let (mut states, ns) = states(policy);
+        let reason = SyncReason::NewNeighbor;
+        let origin = Origin::Connect(reason);
+
+        // failed syncs started by the peer do not delay dialing it
+        assert!(matches!(
+            states.accept_request(&node(0), &ns, node(1)),
+            AcceptOutcome::Allow
+        ));
+        states.finish(&ns, node(1), &Origin::Accept, Err(anyhow::anyhow!("fail")));
+        assert!(states.start_connect(&ns, node(1), reason));
+
+        // failed dials do
+        states.finish(&ns, node(1), &origin, Err(anyhow::anyhow!("fail")));
+        assert!(!states.start_connect(&ns, node(1), reason));
+        assert!(!states.start_connect(&ns, node(1), SyncReason::Periodic));
+
+        // unless the sync is started explicitly
+        assert!(states.start_connect(&ns, node(1), SyncReason::DirectJoin));
+        let origin = Origin::Connect(SyncReason::DirectJoin);
+        states.finish(&ns, node(1), &origin, finished(ns, node(1)));
+
+        // a successful sync resets the backoff
+        assert!(states.start_connect(&ns, node(1), reason));
+    }
+}
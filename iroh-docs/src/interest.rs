@@ -0,0 +1,511 @@
+//! Partial replication of namespaces.
+//!
+//! By default, a node replicates all entries of a namespace. A node can instead set an
+//! [`AreaOfInterest`] for a namespace, which restricts the entries it replicates to a set of key
+//! prefixes and authors.
+//!
+//! When two nodes sync a namespace, the dialing peer announces its area of interest, and the
+//! accepting peer replies with the intersection of both areas if it differs. Set reconciliation
+//! then runs on the entries within the intersection only, so entries outside of it are never sent
+//! to or stored by either peer.
+//!
+//! Note that entries with a key that is a prefix of an area, for example prefix deletions of a
+//! parent key, are outside the area and thus are not replicated.
+
+use std::collections::BTreeSet;
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ranger::{self, Fingerprint, Range},
+    store::PublicKeyStore,
+    AuthorId, NamespaceId, RecordIdentifier, SignedEntry,
+};
+
+/// The part of a namespace a node replicates.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AreaOfInterest {
+    /// Entries with a key that starts with any of the prefixes are included.
+    prefixes: BTreeSet<Bytes>,
+    /// Only entries from these authors are included, or from all authors if `None`.
+    authors: Option<BTreeSet<AuthorId>>,
+}
+
+impl Default for AreaOfInterest {
+    fn default() -> Self {
+        Self::full()
+    }
+}
+
+impl AreaOfInterest {
+    /// The area that includes all entries of a namespace.
+    pub fn full() -> Self {
+        Self {
+            prefixes: [Bytes::new()].into(),
+            authors: None,
+        }
+    }
+
+    /// Create an area that includes the entries with a key that starts with any of `prefixes`.
+    pub fn prefixes<T: AsRef<[u8]>>(prefixes: impl IntoIterator<Item = T>) -> Self {
+        let prefixes = prefixes
+            .into_iter()
+            .map(|prefix| Bytes::copy_from_slice(prefix.as_ref()))
+            .collect();
+        Self::normalized(prefixes, None)
+    }
+
+    /// Restrict this area to the entries from `authors`.
+    pub fn with_authors(self, authors: impl IntoIterator<Item = AuthorId>) -> Self {
+        let authors: BTreeSet<_> = authors.into_iter().collect();
+        let authors = match self.authors {
+            None => authors,
+            Some(existing) => existing.intersection(&authors).copied().collect(),
+        };
+        Self::normalized(self.prefixes, Some(authors))
+    }
+
+    /// Whether this area includes all entries.
+    pub fn is_full(&self) -> bool {
+        self.authors.is_none() && self.prefixes.contains(&Bytes::new())
+    }
+
+    /// Whether this area includes no entries at all.
+    pub fn is_empty(&self) -> bool {
+        self.prefixes.is_empty() || self.authors.as_ref().is_some_and(|a| a.is_empty())
+    }
+
+    /// Whether the entry for `author` and `key` is within this area.
+    pub fn includes(&self, author: &AuthorId, key: &[u8]) -> bool {
+        self.authors.as_ref().map_or(true, |a| a.contains(author))
+            && self.prefixes.iter().any(|prefix| key.starts_with(prefix))
+    }
+
+    /// Whether the record identified by `id` is within this area.
+    pub fn includes_id(&self, id: &RecordIdentifier) -> bool {
+        self.includes(&id.author(), id.key())
+    }
+
+    /// The area of entries that are within both areas.
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut prefixes = BTreeSet::new();
+        for a in &self.prefixes {
+            for b in &other.prefixes {
+                if a.starts_with(b) {
+                    prefixes.insert(a.clone());
+                } else if b.starts_with(a) {
+                    prefixes.insert(b.clone());
+                }
+            }
+        }
+        let authors = match (&self.authors, &other.authors) {
+            (None, None) => None,
+            (Some(a), None) | (None, Some(a)) => Some(a.clone()),
+            (Some(a), Some(b)) => Some(a.intersection(b).copied().collect()),
+        };
+        Self::normalized(prefixes, authors)
+    }
+
+    /// Remove prefixes that are covered by shorter prefixes.
+    fn normalized(prefixes: BTreeSet<Bytes>, authors: Option<BTreeSet<AuthorId>>) -> Self {
+        let mut normalized: BTreeSet<Bytes> = BTreeSet::new();
+        // in sorted order, a prefix comes before all keys that start with it
+        for prefix in prefixes {
+            if !normalized.iter().any(|p| prefix.starts_with(p)) {
+                normalized.insert(prefix);
+            }
+        }
+        Self {
+            prefixes: normalized,
+            authors,
+        }
+    }
+}
+
+/// A view on a replica store that only contains the entries within an [`AreaOfInterest`].
+///
+/// Set reconciliation on this view only exchanges entries within the area.
+#[derive(Debug)]
+pub(crate) struct AreaStore<'a, S> {
+    store: &'a mut S,
+    area: &'a AreaOfInterest,
+}
+
+impl<'a, S> AreaStore<'a, S> {
+    pub(crate) fn new(store: &'a mut S, area: &'a AreaOfInterest) -> Self {
+        Self { store, area }
+    }
+}
+
+/// A range of record identifiers from `start` (inclusive) to `end` (exclusive), or to the end of
+/// the namespace if `end` is `None`.
+type IdRange = (RecordIdentifier, Option<RecordIdentifier>);
+
+impl<'a, S: ranger::Store<SignedEntry>> AreaStore<'a, S> {
+    /// The sorted, disjoint identifier ranges that together contain exactly the entries of the
+    /// area.
+    ///
+    /// If the area is not restricted to a set of authors, the authors are discovered by skipping
+    /// from one author to the next, so entries outside of the area are never iterated.
+    fn area_ranges(&mut self) -> Result<(NamespaceId, Vec<IdRange>), S::Error> {
+        let first = self.store.get_first()?;
+        let namespace = first.namespace();
+        let mut ranges = Vec::new();
+        if first == RecordIdentifier::default() || self.area.is_empty() {
+            return Ok((namespace, ranges));
+        }
+        match &self.area.authors {
+            Some(authors) => {
+                for author in authors {
+                    author_ranges(self.area, namespace, *author, &mut ranges);
+                }
+            }
+            None => {
+                let mut author = first.author();
+                loop {
+                    author_ranges(self.area, namespace, author, &mut ranges);
+                    let Some(next) = next_author(&author) else {
+                        break;
+                    };
+                    let start = RecordIdentifier::new(namespace, next, b"");
+                    let range = id_range(namespace, start, None);
+                    match self.store.get_range(range)?.next() {
+                        Some(entry) => author = entry?.id().author(),
+                        None => break,
+                    }
+                }
+            }
+        }
+        Ok((namespace, ranges))
+    }
+}
+
+/// Append the ranges of the area's prefixes for `author` to `ranges`.
+fn author_ranges(
+    area: &AreaOfInterest,
+    namespace: NamespaceId,
+    author: AuthorId,
+    ranges: &mut Vec<IdRange>,
+) {
+    let author_end = next_author(&author).map(|next| RecordIdentifier::new(namespace, next, b""));
+    for prefix in &area.prefixes {
+        let start = RecordIdentifier::new(namespace, author, prefix);
+        let end = match prefix_end(prefix) {
+            Some(key) => Some(RecordIdentifier::new(namespace, author, key)),
+            None => author_end.clone(),
+        };
+        ranges.push((start, end));
+    }
+}
+
+/// The author following `author` in byte order, if any.
+fn next_author(author: &AuthorId) -> Option<AuthorId> {
+    let mut bytes = *author.as_bytes();
+    for byte in bytes.iter_mut().rev() {
+        if *byte != u8::MAX {
+            *byte += 1;
+            return Some(AuthorId::from(&bytes));
+        }
+        *byte = 0;
+    }
+    None
+}
+
+/// The smallest key that is greater than all keys starting with `prefix`, if any.
+fn prefix_end(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut end = prefix.to_vec();
+    while let Some(last) = end.pop() {
+        if last != u8::MAX {
+            end.push(last + 1);
+            return Some(end);
+        }
+    }
+    None
+}
+
+/// Convert an [`IdRange`] into a [`Range`] of the namespace.
+///
+/// An unbounded end is expressed as a range that wraps around at the start of the namespace.
+fn id_range(
+    namespace: NamespaceId,
+    start: RecordIdentifier,
+    end: Option<RecordIdentifier>,
+) -> Range<RecordIdentifier> {
+    let end =
+        end.unwrap_or_else(|| RecordIdentifier::new(namespace, AuthorId::from(&[0u8; 32]), b""));
+    Range::new(start, end)
+}
+
+/// The parts of the [`IdRange`] `(start, end)` that are contained in `range`.
+fn intersect(
+    (start, end): &IdRange,
+    range: &Range<RecordIdentifier>,
+) -> impl Iterator<Item = IdRange> {
+    let (x, y) = (range.x(), range.y());
+    let min_end = |end: &Option<RecordIdentifier>| match end {
+        Some(end) if end < y => end.clone(),
+        _ => y.clone(),
+    };
+    let parts = match x.cmp(y) {
+        std::cmp::Ordering::Equal => [Some((start.clone(), end.clone())), None],
+        // x <= t < y
+        std::cmp::Ordering::Less => [Some((start.max(x).clone(), Some(min_end(end)))), None],
+        // t >= x or t < y
+        std::cmp::Ordering::Greater => [
+            Some((start.max(x).clone(), end.clone())),
+            Some((start.clone(), Some(min_end(end)))),
+        ],
+    };
+    parts
+        .into_iter()
+        .flatten()
+        .filter(|(start, end)| end.as_ref().map_or(true, |end| start < end))
+}
+
+/// Iterator over the entries of a [`AreaStore`] range.
+#[derive(Debug)]
+pub(crate) struct AreaIter<'a, I> {
+    inner: I,
+    area: &'a AreaOfInterest,
+}
+
+impl<'a, I, E> Iterator for AreaIter<'a, I>
+where
+    I: Iterator<Item = Result<SignedEntry, E>>,
+{
+    type Item = Result<SignedEntry, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.inner.next()? {
+                Ok(entry) if !self.area.includes_id(entry.id()) => continue,
+                res => return Some(res),
+            }
+        }
+    }
+}
+
+impl<'a, S: PublicKeyStore> PublicKeyStore for AreaStore<'a, S> {
+    fn public_key(
+        &self,
+        id: &[u8; 32],
+    ) -> Result<ed25519_dalek::VerifyingKey, ed25519_dalek::SignatureError> {
+        self.store.public_key(id)
+    }
+}
+
+impl<'a, S: ranger::Store<SignedEntry>> ranger::Store<SignedEntry> for AreaStore<'a, S> {
+    type Error = S::Error;
+    type RangeIterator<'x> = AreaIter<'x, S::RangeIterator<'x>>
+        where Self: 'x;
+    type ParentIterator<'x> = S::ParentIterator<'x>
+        where Self: 'x;
+
+    fn get_first(&mut self) -> Result<RecordIdentifier, Self::Error> {
+        if self.area.is_full() {
+            return self.store.get_first();
+        }
+        let (namespace, ranges) = self.area_ranges()?;
+        for (start, end) in ranges {
+            if let Some(entry) = self
+                .store
+                .get_range(id_range(namespace, start, end))?
+                .next()
+            {
+                return Ok(entry?.id().clone());
+            }
+        }
+        Ok(RecordIdentifier::default())
+    }
+
+    fn get(&mut self, id: &RecordIdentifier) -> Result<Option<SignedEntry>, Self::Error> {
+        if !self.area.includes_id(id) {
+            return Ok(None);
+        }
+        self.store.get(id)
+    }
+
+    fn len(&mut self) -> Result<usize, Self::Error> {
+        if self.area.is_full() {
+            return self.store.len();
+        }
+        let (namespace, ranges) = self.area_ranges()?;
+        let mut count = 0;
+        for (start, end) in ranges {
+            for entry in self.store.get_range(id_range(namespace, start, end))? {
+                entry?;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    fn is_empty(&mut self) -> Result<bool, Self::Error> {
+        if self.area.is_full() {
+            return self.store.is_empty();
+        }
+        Ok(self.get_first()? == RecordIdentifier::default())
+    }
+
+    fn get_fingerprint(
+        &mut self,
+        range: &Range<RecordIdentifier>,
+    ) -> Result<Fingerprint, Self::Error> {
+        if self.area.is_full() {
+            return self.store.get_fingerprint(range);
+        }
+        let (namespace, ranges) = self.area_ranges()?;
+        let empty = Fingerprint::empty();
+        let mut fp = empty;
+        for part in ranges.iter().flat_map(|r| intersect(r, range)) {
+            fp ^= self
+                .store
+                .get_fingerprint(&id_range(namespace, part.0, part.1))?;
+            // the fingerprint of each part includes the fingerprint of the empty set
+            fp ^= empty;
+        }
+        Ok(fp)
+    }
+
+    fn entry_put(&mut self, entry: SignedEntry) -> Result<(), Self::Error> {
+        self.store.entry_put(entry)
+    }
+
+    fn get_range(
+        &mut self,
+        range: Range<RecordIdentifier>,
+    ) -> Result<Self::RangeIterator<'_>, Self::Error> {
+        let area = self.area;
+        let inner = self.store.get_range(range)?;
+        Ok(AreaIter { inner, area })
+    }
+
+    fn prefixed_by(
+        &mut self,
+        id: &RecordIdentifier,
+    ) -> Result<Self::RangeIterator<'_>, Self::Error> {
+        let area = self.area;
+        let inner = self.store.prefixed_by(id)?;
+        Ok(AreaIter { inner, area })
+    }
+
+    fn prefixes_of(
+        &mut self,
+        id: &RecordIdentifier,
+    ) -> Result<Self::ParentIterator<'_>, Self::Error> {
+        // entries outside of the area can still supersede entries within the area
+        self.store.prefixes_of(id)
+    }
+
+    fn all(&mut self) -> Result<Self::RangeIterator<'_>, Self::Error> {
+        let area = self.area;
+        let inner = self.store.all()?;
+        Ok(AreaIter { inner, area })
+    }
+
+    fn entry_remove(&mut self, id: &RecordIdentifier) -> Result<Option<SignedEntry>, Self::Error> {
+        self.store.entry_remove(id)
+    }
+
+    fn remove_prefix_filtered(
+        &mut self,
+        id: &RecordIdentifier,
+        predicate: impl Fn(&crate::Record) -> bool,
+    ) -> Result<usize, Self::Error> {
+        self.store.remove_prefix_filtered(id, predicate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn area_intersection() {
+        let me = AuthorId::from(&[1u8; 32]);
+        let other = AuthorId::from(&[2u8; 32]);
+
+        let full = AreaOfInterest::full();
+        assert!(full.is_full());
+        assert!(full.includes(&me, b"anything"));
+
+        let users = AreaOfInterest::prefixes(["/users/", "/users/me/"]);
+        assert_eq!(users, AreaOfInterest::prefixes(["/users/"]));
+        assert_eq!(full.intersection(&users), users);
+
+        let mine = AreaOfInterest::prefixes(["/users/me/", "/config"]).with_authors([me]);
+        let both = users.intersection(&mine);
+        assert_eq!(
+            both,
+            AreaOfInterest::prefixes(["/users/me/"]).with_authors([me])
+        );
+        assert!(both.includes(&me, b"/users/me/photo"));
+        assert!(!both.includes(&other, b"/users/me/photo"));
+        assert!(!both.includes(&me, b"/users/"));
+        assert!(!both.includes(&me, b"/config"));
+
+        let disjoint = users.intersection(&AreaOfInterest::prefixes(["/groups/"]));
+        assert!(disjoint.is_empty());
+        assert!(!disjoint.includes(&me, b"/users/me/photo"));
+    }
+
+    #[test]
+    fn area_store() -> anyhow::Result<()> {
+        use ranger::{RangeEntry, Store as _};
+
+        use crate::{
+            store::{fs::StoreInstance, Store},
+            Author, Entry, NamespaceSecret, Record,
+        };
+
+        let mut rng = rand::thread_rng();
+        let mut store = Store::memory();
+        let namespace = NamespaceSecret::new(&mut rng);
+        store.new_replica(namespace.clone())?;
+        let authors: Vec<_> = (0..3).map(|_| Author::new(&mut rng)).collect();
+        let keys: [&[u8]; 6] = [b"a", b"a/1", b"a/2", b"ab", b"c\xff", b"d"];
+
+        let mut instance = StoreInstance::new(namespace.id(), &mut store);
+        for author in &authors {
+            for key in keys {
+                let id = RecordIdentifier::new(namespace.id(), author.id(), key);
+                let entry = Entry::new(id, Record::current_from_data(key));
+                instance.entry_put(SignedEntry::from_entry(entry, &namespace, author))?;
+            }
+        }
+        let all: Vec<_> = instance
+            .all()?
+            .map(|entry| entry.map(|entry| entry.id().clone()))
+            .collect::<Result<_, _>>()?;
+
+        let areas = [
+            AreaOfInterest::full(),
+            AreaOfInterest::prefixes([&b"a/"[..], b"c\xff"]),
+            AreaOfInterest::prefixes(["a", "d"]).with_authors([authors[0].id(), authors[2].id()]),
+            AreaOfInterest::prefixes(["x"]),
+        ];
+        for area in &areas {
+            let expected: Vec<_> = all.iter().filter(|id| area.includes_id(id)).collect();
+            let mut area_store = AreaStore::new(&mut instance, area);
+            assert_eq!(area_store.len()?, expected.len());
+            assert_eq!(area_store.is_empty()?, expected.is_empty());
+            let first = expected.first().map(|id| (*id).clone()).unwrap_or_default();
+            assert_eq!(area_store.get_first()?, first);
+
+            let ranges = [
+                Range::new(all[0].clone(), all[0].clone()),
+                Range::new(all[2].clone(), all[10].clone()),
+                Range::new(all[10].clone(), all[2].clone()),
+            ];
+            for range in ranges {
+                let mut fp = Fingerprint::empty();
+                for id in expected.iter().filter(|id| range.contains(id)) {
+                    fp ^= area_store.get(id)?.expect("in area").as_fingerprint();
+                }
+                assert_eq!(area_store.get_fingerprint(&range)?, fp);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,295 @@
+//! Expiring and single-use invitations to namespaces.
+//!
+//! A [`DocTicket`](crate::DocTicket) embeds a [`Capability`](crate::Capability), and grants
+//! access to anyone it is forwarded to, for as long as the namespace exists. An [`Invite`] instead
+//! carries a token signed with the [`NamespaceSecret`], which can expire and can be limited to a
+//! single redemption.
+//!
+//! The invited node presents the invite only to the inviting nodes listed in the
+//! [`InviteTicket`](crate::InviteTicket), when it first syncs the namespace with them. The inviting
+//! node verifies the invite and logs the redemption. It then adds the invited node to the
+//! allowlist of a restricted [`AccessPolicy`](crate::AccessPolicy), and hands over the namespace
+//! secret for write invites. An invite can be bound to the node id of the invited node when it is
+//! created, and a single-use invite is bound to the first node that redeems it, so a forwarded
+//! invite is rejected.
+
+use std::fmt;
+
+use ed25519_dalek::Signature;
+use iroh_base::base32;
+use serde::{Deserialize, Serialize};
+
+use crate::{store::PublicKeyStore, CapabilityKind, NamespaceId, NamespaceSecret, PeerIdBytes};
+
+/// Domain separator for the signatures of invites.
+const INVITE_DOMAIN: &[u8] = b"iroh-docs invite";
+
+/// The unique id of an [`Invite`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct InviteId([u8; 16]);
+
+impl InviteId {
+    /// Create an id from bytes.
+    pub fn from_bytes(bytes: [u8; 16]) -> Self {
+        Self(bytes)
+    }
+
+    /// The bytes of this id.
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+}
+
+impl fmt::Display for InviteId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", base32::fmt(self.0))
+    }
+}
+
+/// A signed invitation to a namespace.
+///
+/// The invite is presented by the invited node on its first sync with the inviting node.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Invite {
+    namespace: NamespaceId,
+    id: InviteId,
+    mode: CapabilityKind,
+    expires: Option<u64>,
+    single_use: bool,
+    invitee: Option<PeerIdBytes>,
+    signature: Signature,
+}
+
+impl Invite {
+    /// Create a new invite, signed with the namespace secret.
+    ///
+    /// `expires` is an optional timestamp in microseconds since the Unix epoch after which the
+    /// invite can no longer be redeemed. A `single_use` invite can only be redeemed by one node.
+    /// If `invitee` is set, the invite can only be redeemed by the node with this id.
+    pub fn new(
+        namespace: &NamespaceSecret,
+        mode: CapabilityKind,
+        expires: Option<u64>,
+        single_use: bool,
+        invitee: Option<PeerIdBytes>,
+    ) -> Self {
+        let namespace_id = namespace.id();
+        let id = InviteId(rand::random());
+        let signature = namespace.sign(&signing_bytes(
+            &namespace_id,
+            &id,
+            mode,
+            expires,
+            single_use,
+            invitee,
+        ));
+        Self {
+            namespace: namespace_id,
+            id,
+            mode,
+            expires,
+            single_use,
+            invitee,
+            signature,
+        }
+    }
+
+    /// The namespace this invite grants access to.
+    pub fn namespace(&self) -> NamespaceId {
+        self.namespace
+    }
+
+    /// The unique id of this invite.
+    pub fn id(&self) -> InviteId {
+        self.id
+    }
+
+    /// The capability that is granted on redemption.
+    pub fn mode(&self) -> CapabilityKind {
+        self.mode
+    }
+
+    /// The time after which the invite can no longer be redeemed, in microseconds since the Unix
+    /// epoch.
+    pub fn expires(&self) -> Option<u64> {
+        self.expires
+    }
+
+    /// Whether the invite can only be redeemed by a single node.
+    pub fn is_single_use(&self) -> bool {
+        self.single_use
+    }
+
+    /// The node that can redeem the invite, if it is bound to a node.
+    pub fn invitee(&self) -> Option<PeerIdBytes> {
+        self.invitee
+    }
+
+    /// Verify that this invite is valid for `namespace` at time `now`.
+    ///
+    /// This does not check whether the invite is bound to the redeeming node, see
+    /// [`Self::verify_redeemer`], or whether a single-use invite was already redeemed.
+    pub fn verify<S: PublicKeyStore>(
+        &self,
+        store: &S,
+        namespace: &NamespaceId,
+        now: u64,
+    ) -> Result<(), InviteError> {
+        if self.namespace != *namespace {
+            return Err(InviteError::NamespaceMismatch);
+        }
+        if self.expires.is_some_and(|expires| now > expires) {
+            return Err(InviteError::Expired);
+        }
+        let bytes = signing_bytes(
+            &self.namespace,
+            &self.id,
+            self.mode,
+            self.expires,
+            self.single_use,
+            self.invitee,
+        );
+        self.namespace
+            .public_key(store)?
+            .verify(&bytes, &self.signature)?;
+        Ok(())
+    }
+
+    /// Verify that `node` may redeem this invite.
+    pub fn verify_redeemer(&self, node: &PeerIdBytes) -> Result<(), InviteError> {
+        match self.invitee {
+            Some(invitee) if invitee != *node => Err(InviteError::WrongInvitee),
+            _ => Ok(()),
+        }
+    }
+
+    /// Serialize this invite to bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        postcard::to_stdvec(self).expect("postcard serialization failed")
+    }
+
+    /// Deserialize an invite from bytes.
+    ///
+    /// The signature is not verified, see [`Self::verify`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, InviteError> {
+        Ok(postcard::from_bytes(bytes)?)
+    }
+}
+
+fn signing_bytes(
+    namespace: &NamespaceId,
+    id: &InviteId,
+    mode: CapabilityKind,
+    expires: Option<u64>,
+    single_use: bool,
+    invitee: Option<PeerIdBytes>,
+) -> Vec<u8> {
+    let mut out = INVITE_DOMAIN.to_vec();
+    out.extend_from_slice(namespace.as_bytes());
+    out.extend_from_slice(id.as_bytes());
+    postcard::to_extend(&(mode, expires, single_use, invitee), out)
+        .expect("postcard serialization failed")
+}
+
+/// A logged redemption of an [`Invite`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Redemption {
+    /// The id of the redeemed invite.
+    pub invite: InviteId,
+    /// The node that redeemed the invite.
+    pub node: PeerIdBytes,
+    /// The capability that was granted.
+    pub mode: CapabilityKind,
+    /// The time of the first redemption by the node, in microseconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+/// Errors for [`Invite`] operations.
+#[derive(Debug, thiserror::Error)]
+pub enum InviteError {
+    /// The signature of the invite is invalid.
+    #[error("invalid invite signature")]
+    BadSignature(#[from] ed25519_dalek::SignatureError),
+    /// The invite is for a different namespace.
+    #[error("invite is for a different namespace")]
+    NamespaceMismatch,
+    /// The invite has expired.
+    #[error("invite has expired")]
+    Expired,
+    /// The single-use invite was already redeemed by another node.
+    #[error("invite was already redeemed")]
+    AlreadyRedeemed,
+    /// The invite is bound to another node.
+    #[error("invite is for a different node")]
+    WrongInvitee,
+    /// The invite could not be deserialized.
+    #[error("invalid invite encoding")]
+    Postcard(#[from] postcard::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invite() {
+        let mut rng = rand::thread_rng();
+        let namespace = NamespaceSecret::new(&mut rng);
+        let other = NamespaceSecret::new(&mut rng);
+
+        let invite = Invite::new(&namespace, CapabilityKind::Read, Some(1000), true, None);
+        invite.verify(&(), &namespace.id(), 500).unwrap();
+        invite.verify_redeemer(&[1u8; 32]).unwrap();
+        assert!(matches!(
+            invite.verify(&(), &namespace.id(), 1001),
+            Err(InviteError::Expired)
+        ));
+        assert!(matches!(
+            invite.verify(&(), &other.id(), 500),
+            Err(InviteError::NamespaceMismatch)
+        ));
+
+        // roundtrip
+        let parsed = Invite::from_bytes(&invite.to_bytes()).unwrap();
+        assert_eq!(parsed, invite);
+
+        // tampering with the mode or the use limit invalidates the signature
+        let mut tampered = invite.clone();
+        tampered.mode = CapabilityKind::Write;
+        assert!(matches!(
+            tampered.verify(&(), &namespace.id(), 500),
+            Err(InviteError::BadSignature(_))
+        ));
+        let mut tampered = invite.clone();
+        tampered.single_use = false;
+        assert!(matches!(
+            tampered.verify(&(), &namespace.id(), 500),
+            Err(InviteError::BadSignature(_))
+        ));
+
+        // every invite has a new id
+        let second = Invite::new(&namespace, CapabilityKind::Read, Some(1000), true, None);
+        assert_ne!(second.id(), invite.id());
+
+        // a bound invite can only be redeemed by the invitee, and the binding cannot be removed
+        let bound = Invite::new(
+            &namespace,
+            CapabilityKind::Read,
+            None,
+            false,
+            Some([1u8; 32]),
+        );
+        bound.verify(&(), &namespace.id(), 500).unwrap();
+        bound.verify_redeemer(&[1u8; 32]).unwrap();
+        assert!(matches!(
+            bound.verify_redeemer(&[2u8; 32]),
+            Err(InviteError::WrongInvitee)
+        ));
+        let mut tampered = bound.clone();
+        tampered.invitee = None;
+        assert!(matches!(
+            tampered.verify(&(), &namespace.id(), 500),
+            Err(InviteError::BadSignature(_))
+        ));
+    }
+}
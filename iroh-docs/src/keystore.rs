@@ -0,0 +1,274 @@
+//! Passphrase protection of the secret keys in a docs store.
+//!
+//! By default, the secret keys of authors and namespaces are stored in cleartext. Once the
+//! keystore of a store is enabled, they are stored encrypted with a key derived from a passphrase,
+//! and the store has to be unlocked with the passphrase before entries can be signed. While the
+//! store is locked, documents can still be listed, read and synced.
+//!
+//! The passphrase key is derived with Argon2, and each secret is sealed with XChaCha20-Poly1305
+//! under a random nonce, with its kind and public id as associated data, so that sealed secrets
+//! cannot be swapped between ids. A value derived from the passphrase key is stored next to the
+//! salt, to reject wrong passphrases on unlock.
+//!
+//! This module also defines an armored text format to export single author keys, encrypted with
+//! a passphrase, see [`armor_author`] and [`dearmor_author`].
+
+use std::fmt;
+
+use chacha20poly1305::{
+    aead::{Aead, Payload},
+    KeyInit, XChaCha20Poly1305, XNonce,
+};
+use iroh_base::base32;
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::Author;
+
+/// Length of the random salt for the passphrase key derivation.
+pub(crate) const SALT_LEN: usize = 16;
+
+/// Context for deriving the key that is used to verify a passphrase.
+const CHECK_CONTEXT: &str = "iroh-docs 2024 keystore passphrase check";
+/// Context for deriving the cipher key for stored secrets.
+const SECRET_CONTEXT: &str = "iroh-docs 2024 keystore secret cipher key";
+/// Context for deriving the cipher key for armored key files.
+const ARMOR_CONTEXT: &str = "iroh-docs 2024 armored key cipher key";
+/// Length of the nonce that is prepended to sealed secrets.
+const NONCE_LEN: usize = 24;
+
+const ARMOR_BEGIN: &str = "-----BEGIN IROH AUTHOR KEY-----";
+const ARMOR_END: &str = "-----END IROH AUTHOR KEY-----";
+const ARMOR_VERSION: u8 = 2;
+const ARMOR_LINE_LEN: usize = 64;
+
+/// The state of the keystore of a docs store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, strum::Display)]
+pub enum KeystoreStatus {
+    /// Secrets are stored in cleartext.
+    Disabled,
+    /// Secrets are encrypted and cannot be used until the store is unlocked.
+    Locked,
+    /// Secrets are encrypted and the store is unlocked.
+    Unlocked,
+}
+
+/// The kind of secret that is stored encrypted.
+///
+/// Secrets of different kinds may share an id, so the kind is part of the associated data.
+#[derive(Debug, Clone, Copy)]
+#[repr(u8)]
+pub(crate) enum SecretKind {
+    Author = 1,
+    Namespace = 2,
+    EncryptionKey = 3,
+}
+
+/// A key derived from a passphrase.
+#[derive(Clone)]
+pub(crate) struct PassphraseKey([u8; 32]);
+
+impl PassphraseKey {
+    /// Derive the key from a passphrase and salt.
+    pub(crate) fn derive(passphrase: &str, salt: &[u8]) -> Result<Self, KeystoreError> {
+        let mut key = [0u8; 32];
+        argon2::Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|_| KeystoreError::Kdf)?;
+        Ok(Self(key))
+    }
+
+    /// A value that is stored to verify the passphrase on unlock.
+    pub(crate) fn check(&self) -> [u8; 32] {
+        blake3::derive_key(CHECK_CONTEXT, &self.0)
+    }
+
+    /// Encrypt a secret for storage.
+    ///
+    /// Returns the nonce followed by the ciphertext.
+    pub(crate) fn seal(&self, kind: SecretKind, id: &[u8; 32], secret: &[u8; 32]) -> Vec<u8> {
+        let aad = secret_aad(kind, id);
+        seal(&self.cipher(SECRET_CONTEXT), secret, &aad)
+    }
+
+    /// Decrypt a secret sealed with [`Self::seal`].
+    pub(crate) fn open(
+        &self,
+        kind: SecretKind,
+        id: &[u8; 32],
+        sealed: &[u8],
+    ) -> Result<[u8; 32], KeystoreError> {
+        let aad = secret_aad(kind, id);
+        let secret = open(&self.cipher(SECRET_CONTEXT), sealed, &aad)?;
+        secret.try_into().map_err(|_| KeystoreError::Corrupt)
+    }
+
+    fn cipher(&self, context: &str) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new(&blake3::derive_key(context, &self.0).into())
+    }
+}
+
+/// The associated data for a stored secret.
+fn secret_aad(kind: SecretKind, id: &[u8; 32]) -> [u8; 33] {
+    let mut aad = [0u8; 33];
+    aad[0] = kind as u8;
+    aad[1..].copy_from_slice(id);
+    aad
+}
+
+/// Encrypt `msg` with a random nonce, and return the nonce followed by the ciphertext.
+fn seal(cipher: &XChaCha20Poly1305, msg: &[u8], aad: &[u8]) -> Vec<u8> {
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+    let ciphertext = cipher
+        .encrypt(XNonce::from_slice(&nonce), Payload { msg, aad })
+        .expect("encryption does not fail");
+    let mut out = nonce.to_vec();
+    out.extend_from_slice(&ciphertext);
+    out
+}
+
+/// Decrypt the output of [`seal`].
+fn open(cipher: &XChaCha20Poly1305, sealed: &[u8], aad: &[u8]) -> Result<Vec<u8>, KeystoreError> {
+    if sealed.len() < NONCE_LEN {
+        return Err(KeystoreError::Corrupt);
+    }
+    let (nonce, msg) = sealed.split_at(NONCE_LEN);
+    cipher
+        .decrypt(XNonce::from_slice(nonce), Payload { msg, aad })
+        .map_err(|_| KeystoreError::WrongPassphrase)
+}
+
+impl fmt::Debug for PassphraseKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PassphraseKey(..)")
+    }
+}
+
+/// Encrypt an author key with a passphrase and encode it as armored text.
+pub fn armor_author(author: &Author, passphrase: &str) -> Result<String, KeystoreError> {
+    let salt: [u8; SALT_LEN] = rand::random();
+    let key = PassphraseKey::derive(passphrase, &salt)?;
+    let sealed = seal(
+        &key.cipher(ARMOR_CONTEXT),
+        &author.to_bytes(),
+        &[ARMOR_VERSION],
+    );
+
+    let mut payload = vec![ARMOR_VERSION];
+    payload.extend_from_slice(&salt);
+    payload.extend_from_slice(&sealed);
+    let encoded = base32::fmt(payload);
+
+    let mut out = String::from(ARMOR_BEGIN);
+    out.push('\n');
+    for line in encoded.as_bytes().chunks(ARMOR_LINE_LEN) {
+        out.push_str(std::str::from_utf8(line).expect("base32 is ascii"));
+        out.push('\n');
+    }
+    out.push_str(ARMOR_END);
+    out.push('\n');
+    Ok(out)
+}
+
+/// Decode an author key from armored text and decrypt it with a passphrase.
+pub fn dearmor_author(armored: &str, passphrase: &str) -> Result<Author, KeystoreError> {
+    let body = armored
+        .trim()
+        .strip_prefix(ARMOR_BEGIN)
+        .and_then(|s| s.strip_suffix(ARMOR_END))
+        .ok_or(KeystoreError::Armor)?;
+    let encoded: String = body.split_whitespace().collect();
+    let payload = base32::parse_vec(&encoded).map_err(|_| KeystoreError::Armor)?;
+    let (version, rest) = payload.split_first().ok_or(KeystoreError::Armor)?;
+    if *version != ARMOR_VERSION || rest.len() < SALT_LEN {
+        return Err(KeystoreError::Armor);
+    }
+    let (salt, sealed) = rest.split_at(SALT_LEN);
+    let key = PassphraseKey::derive(passphrase, salt)?;
+    let secret = match open(&key.cipher(ARMOR_CONTEXT), sealed, &[ARMOR_VERSION]) {
+        Err(KeystoreError::Corrupt) => return Err(KeystoreError::Armor),
+        res => res?,
+    };
+    let bytes: [u8; 32] = secret.try_into().map_err(|_| KeystoreError::Armor)?;
+    Ok(Author::from_bytes(&bytes))
+}
+
+/// Whether the text looks like an armored key.
+pub fn is_armored(text: &str) -> bool {
+    text.trim_start().starts_with(ARMOR_BEGIN)
+}
+
+/// Errors for keystore operations.
+#[derive(Debug, thiserror::Error)]
+pub enum KeystoreError {
+    /// The secrets are encrypted and the store has not been unlocked.
+    #[error("the keystore is locked")]
+    Locked,
+    /// The keystore is not enabled.
+    #[error("the keystore is not enabled")]
+    Disabled,
+    /// The keystore is already enabled.
+    #[error("the keystore is already enabled")]
+    Enabled,
+    /// The passphrase does not match.
+    #[error("wrong passphrase")]
+    WrongPassphrase,
+    /// The key could not be derived from the passphrase.
+    #[error("failed to derive key from passphrase")]
+    Kdf,
+    /// The armored key is malformed.
+    #[error("invalid armored key")]
+    Armor,
+    /// A stored secret is malformed.
+    #[error("invalid stored secret")]
+    Corrupt,
+    /// A stored secret does not belong to the id it is stored under.
+    #[error("stored secret does not match its id")]
+    IdMismatch,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn armor_roundtrip() {
+        let author = Author::new(&mut rand::thread_rng());
+        let armored = armor_author(&author, "hunter2").unwrap();
+        assert!(is_armored(&armored));
+        assert!(!is_armored(&author.to_string()));
+
+        let decoded = dearmor_author(&armored, "hunter2").unwrap();
+        assert_eq!(decoded.to_bytes(), author.to_bytes());
+
+        assert!(matches!(
+            dearmor_author(&armored, "hunter3"),
+            Err(KeystoreError::WrongPassphrase)
+        ));
+        assert!(matches!(
+            dearmor_author(&author.to_string(), "hunter2"),
+            Err(KeystoreError::Armor)
+        ));
+    }
+
+    #[test]
+    fn seal_roundtrip() {
+        let key = PassphraseKey::derive("hunter2", &[0u8; SALT_LEN]).unwrap();
+        let id = [1u8; 32];
+        let secret = [2u8; 32];
+        let sealed = key.seal(SecretKind::Author, &id, &secret);
+        assert!(!sealed.windows(32).any(|w| w == secret));
+        assert_ne!(sealed, key.seal(SecretKind::Author, &id, &secret));
+        assert_eq!(key.open(SecretKind::Author, &id, &sealed).unwrap(), secret);
+
+        // sealed secrets are bound to their kind and id
+        assert!(key.open(SecretKind::Namespace, &id, &sealed).is_err());
+        assert!(key.open(SecretKind::Author, &[3u8; 32], &sealed).is_err());
+        let other = PassphraseKey::derive("hunter3", &[0u8; SALT_LEN]).unwrap();
+        assert!(matches!(
+            other.open(SecretKind::Author, &id, &sealed),
+            Err(KeystoreError::WrongPassphrase)
+        ));
+    }
+}
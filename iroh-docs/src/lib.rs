@@ -13,6 +13,9 @@
 //! * The [Author] key, as a proof of authorship. Any number of authors may be created, and
 //!   their semantic meaning is application-specific. The public key of an author is the [AuthorId].
 //!
+//! Instead of the namespace signature, an entry may carry a second signature of its author if a
+//! [`WriteDelegation`] signed by the namespace key allows the author to write the entry's key.
+//!
 //! Replicas can be synchronized between peers by exchanging messages. The synchronization algorithm
 //! is based on a technique called *range-based set reconciliation*, based on [this paper][paper] by
 //! Aljoscha Meyer:
@@ -21,6 +24,19 @@
 //! > sets over a network, based on recursively partitioning the sets and comparing fingerprints of
 //! > the partitions to probabilistically detect whether a partition requires further work.
 //!
+//! By default, any peer that knows a [`NamespaceId`] may sync the replica. An [`AccessPolicy`]
+//! limits this to a set of peers and to holders of a [`ReadToken`]. Peers are added to the set
+//! when they redeem an expiring or single-use [`Invite`]. Independently, the keys and content of
+//! entries can be encrypted with a [`DocEncryptionKey`], so that peers without the key can store
+//! and forward the replica without reading it.
+//!
+//! A node can also replicate only part of a namespace, by setting an [`AreaOfInterest`] of key
+//! prefixes and authors. Sync sessions then only reconcile the entries within the area.
+//! Without a connection, signed entries can be moved between nodes in a [`Bundle`] file.
+//!
+//! Entries are last-writer-wins per author and key. The [`crdt`] module provides counters, sets,
+//! maps and text that merge concurrent edits of different authors on top of entries.
+//!
 //! The crate exposes a [generic storage interface](store::Store). There is an implementation
 //! of this interface, [store::fs::Store], that can be used either
 //! [in-memory](store::fs::Store::memory) or in
@@ -47,16 +63,37 @@ mod ticket;
 pub mod engine;
 
 pub mod actor;
+pub mod crdt;
 pub mod store;
 pub mod sync;
 
+mod access;
+mod bundle;
+mod delegation;
+mod encryption;
 mod heads;
+mod interest;
+mod invite;
 mod keys;
+mod keystore;
+mod migration;
 mod ranger;
 
+pub use self::access::*;
+pub use self::bundle::{BundleError, BundleReader, BundleRecord, BundleWriter};
+pub use self::delegation::*;
+pub use self::encryption::*;
 pub use self::heads::*;
+pub use self::interest::AreaOfInterest;
+pub use self::invite::{Invite, InviteError, InviteId, Redemption};
 pub use self::keys::*;
+pub use self::keystore::{
+    armor_author, dearmor_author, is_armored, KeystoreError, KeystoreStatus,
+};
+pub use self::migration::{
+    is_moved_to_key, moved_to, moved_to_content, moved_to_key, MOVED_TO_PREFIX,
+};
 pub use self::sync::*;
 #[cfg(feature = "net")]
 #[cfg_attr(iroh_docsrs, doc(cfg(feature = "net")))]
-pub use self::ticket::DocTicket;
+pub use self::ticket::{DocTicket, InviteTicket};
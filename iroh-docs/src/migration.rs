@@ -0,0 +1,101 @@
+//! Migration of documents to a new namespace.
+//!
+//! If the [`NamespaceSecret`](crate::NamespaceSecret) of a document leaks, the document can be
+//! moved to a successor namespace with a fresh secret. The latest entries are copied to the
+//! successor, and a marker entry is written to the old namespace that points to the successor.
+//!
+//! The key of the marker entry is [`MOVED_TO_PREFIX`] followed by the id of the successor, and
+//! its content is the id of the successor. The marker is only valid if it carries a namespace
+//! signature, so that authors with a [`WriteDelegation`](crate::WriteDelegation) cannot redirect
+//! the document.
+//!
+//! A namespace signature does not prove that the marker was written by the owner of the document:
+//! whoever obtained the leaked secret can write a marker as well. Therefore markers are never
+//! followed automatically. Nodes that sync the old namespace report the marker together with its
+//! author, and the application decides whether to follow it, e.g. if the author is trusted, see
+//! [`Engine::follow_migration`](crate::engine::Engine::follow_migration). Following a marker
+//! starts to sync the successor with read access. Write access has to be shared again.
+
+use bytes::Bytes;
+use iroh_base::hash::Hash;
+
+use crate::{AuthorId, Event, NamespaceId, SignedEntry};
+
+/// Key prefix of the marker entry that points to the successor of a namespace.
+pub const MOVED_TO_PREFIX: &[u8] = b"\0iroh/moved-to/";
+
+/// The key of the marker entry that points to `successor`.
+pub fn moved_to_key(successor: &NamespaceId) -> Bytes {
+    let mut key = MOVED_TO_PREFIX.to_vec();
+    key.extend_from_slice(successor.as_bytes());
+    key.into()
+}
+
+/// The content of the marker entry that points to `successor`.
+pub fn moved_to_content(successor: &NamespaceId) -> Bytes {
+    Bytes::copy_from_slice(successor.as_bytes())
+}
+
+/// Whether `key` is the key of a marker entry.
+///
+/// Marker keys are never encrypted, even in encrypted documents.
+pub fn is_moved_to_key(key: &[u8]) -> bool {
+    key.starts_with(MOVED_TO_PREFIX)
+}
+
+/// Return the successor namespace if `entry` is a valid marker entry.
+pub fn moved_to(entry: &SignedEntry) -> Option<NamespaceId> {
+    let id: [u8; 32] = entry.key().strip_prefix(MOVED_TO_PREFIX)?.try_into().ok()?;
+    let successor = NamespaceId::from(&id);
+    if entry.content_hash() != Hash::new(moved_to_content(&successor))
+        || entry.content_len() != id.len() as u64
+    {
+        return None;
+    }
+    // the marker has to be signed with the namespace secret, not under a delegation
+    match entry.verify_delegated(&()) {
+        Ok(false) => Some(successor),
+        _ => None,
+    }
+}
+
+/// Return the successor namespace and the author of the marker if `event` inserted a valid
+/// marker entry.
+#[cfg(feature = "engine")]
+pub(crate) fn moved_to_event(event: &Event) -> Option<(NamespaceId, AuthorId)> {
+    let with_author = |entry: &SignedEntry| moved_to(entry).map(|to| (to, entry.author()));
+    match event {
+        Event::LocalInsert { entry, .. } | Event::RemoteInsert { entry, .. } => with_author(entry),
+        Event::LocalBatch { entries, .. } => entries.iter().find_map(with_author),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Author, NamespaceSecret, Record, SignedEntry};
+
+    #[test]
+    fn moved_to_marker() {
+        let mut rng = rand::thread_rng();
+        let namespace = NamespaceSecret::new(&mut rng);
+        let author = Author::new(&mut rng);
+        let successor = NamespaceSecret::new(&mut rng).id();
+
+        let content = moved_to_content(&successor);
+        let record = Record::new_current(Hash::new(&content), content.len() as u64);
+        let key = moved_to_key(&successor);
+        assert!(is_moved_to_key(&key));
+        let marker = SignedEntry::from_parts(&namespace, &author, &key, record.clone());
+        assert_eq!(moved_to(&marker), Some(successor));
+
+        // markers written under a delegation are ignored
+        let delegated = SignedEntry::from_entry_delegated(marker.entry().clone(), &author);
+        assert_eq!(moved_to(&delegated), None);
+
+        // the content has to match the key
+        let other = NamespaceSecret::new(&mut rng).id();
+        let marker = SignedEntry::from_parts(&namespace, &author, moved_to_key(&other), record);
+        assert_eq!(moved_to(&marker), None);
+    }
+}
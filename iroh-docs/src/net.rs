@@ -5,13 +5,18 @@ use std::{
     time::{Duration, Instant},
 };
 
-use iroh_net::{endpoint::get_remote_node_id, key::PublicKey, Endpoint, NodeAddr};
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use iroh_net::{
+    endpoint::{get_remote_node_id, Connection, ConnectionError, RecvStream, SendStream},
+    key::PublicKey,
+    Endpoint, NodeAddr,
+};
 use serde::{Deserialize, Serialize};
 use tracing::{debug, error_span, trace, Instrument};
 
 use crate::{
     actor::SyncHandle,
-    net::codec::{run_alice, BobState},
+    net::codec::{open_session, run_alice, BobState},
     NamespaceId, SyncOutcome,
 };
 
@@ -21,7 +26,43 @@ use crate::metrics::Metrics;
 use iroh_metrics::inc;
 
 /// The ALPN identifier for the iroh-docs protocol
-pub const DOCS_ALPN: &[u8] = b"/iroh-sync/1";
+///
+/// Version 2 added sessions over several namespaces and inline content to the wire messages.
+/// Peers speaking version 1 can't decode them, so connections to peers that reject this ALPN
+/// fall back to [`DOCS_ALPN_V1`].
+pub const DOCS_ALPN: &[u8] = b"/iroh-sync/2";
+
+/// The ALPN identifier for version 1 of the iroh-docs protocol
+///
+/// Version 1 syncs a single namespace per connection and does not send inline content.
+/// Nodes accept connections under both ALPNs.
+pub const DOCS_ALPN_V1: &[u8] = b"/iroh-sync/1";
+
+/// QUIC error code for the TLS `no_application_protocol` alert, sent by peers that support none
+/// of the ALPNs we offered.
+const NO_APPLICATION_PROTOCOL: u64 = 0x100 + 120;
+
+/// Version of the iroh-docs protocol spoken on a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ProtocolVersion {
+    /// Single namespace per connection, negotiated under [`DOCS_ALPN_V1`].
+    V1,
+    /// Sessions and inline content, negotiated under [`DOCS_ALPN`].
+    V2,
+}
+
+impl ProtocolVersion {
+    fn from_alpn(alpn: &[u8]) -> Option<Self> {
+        match alpn {
+            DOCS_ALPN => Some(Self::V2),
+            DOCS_ALPN_V1 => Some(Self::V1),
+            _ => None,
+        }
+    }
+}
+
+/// The maximum number of namespaces that are synced concurrently in a session.
+const MAX_SESSION_STREAMS: usize = 16;
 
 mod codec;
 
@@ -35,23 +76,211 @@ pub async fn connect_and_sync(
     let t_start = Instant::now();
     let peer_id = peer.node_id;
     trace!("connect");
-    let connection = endpoint
-        .connect(peer, DOCS_ALPN)
-        .await
-        .map_err(ConnectError::connect)?;
+    let (connection, version) = connect(endpoint, peer).await?;
+
+    let t_connect = t_start.elapsed();
+    debug!(?t_connect, ?version, "connected");
+
+    sync_stream(&connection, sync, namespace, peer_id, version, t_connect).await
+}
+
+/// Connect to a peer with the newest protocol version it supports.
+async fn connect(
+    endpoint: &Endpoint,
+    peer: NodeAddr,
+) -> Result<(Connection, ProtocolVersion), ConnectError> {
+    match endpoint.connect(peer.clone(), DOCS_ALPN).await {
+        Ok(connection) => Ok((connection, ProtocolVersion::V2)),
+        Err(err) if is_alpn_rejected(&err) => {
+            debug!("peer rejected protocol version 2, falling back to version 1");
+            let connection = endpoint
+                .connect(peer, DOCS_ALPN_V1)
+                .await
+                .map_err(ConnectError::connect)?;
+            Ok((connection, ProtocolVersion::V1))
+        }
+        Err(err) => Err(ConnectError::connect(err)),
+    }
+}
+
+/// Whether the peer closed the connection because it does not support the offered ALPN.
+fn is_alpn_rejected(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<ConnectionError>(),
+        Some(ConnectionError::ConnectionClosed(close))
+            if u64::from(close.error_code) == NO_APPLICATION_PROTOCOL
+    )
+}
+
+/// Connect to a peer and sync several replicas over a single connection.
+///
+/// The namespaces both peers have in common are negotiated once, and then reconciled
+/// concurrently, each on its own stream. The peer only lists namespaces we may read without
+/// credentials, namespaces for which we hold a read token or invite are synced on their own
+/// connection instead. Other namespaces the peer does not list are reported as aborted with
+/// [`AbortReason::NotFound`].
+///
+/// Peers that only speak version 1 of the protocol, see [`DOCS_ALPN_V1`], can't open a session,
+/// so each namespace is synced on its own connection.
+pub async fn connect_and_sync_many(
+    endpoint: &Endpoint,
+    sync: &SyncHandle,
+    namespaces: Vec<NamespaceId>,
+    peer: NodeAddr,
+) -> Result<SessionFinished, ConnectError> {
+    let t_start = Instant::now();
+    let peer_id = peer.node_id;
+    trace!(namespaces = namespaces.len(), "connect");
+    let (connection, version) = connect(endpoint, peer.clone()).await?;
+    if version == ProtocolVersion::V1 {
+        let t_connect = t_start.elapsed();
+        debug!(?t_connect, ?version, "connected");
+        return sync_many_v1(endpoint, sync, namespaces, peer, connection, t_start).await;
+    }
 
     let (mut send_stream, mut recv_stream) =
         connection.open_bi().await.map_err(ConnectError::connect)?;
 
     let t_connect = t_start.elapsed();
-    debug!(?t_connect, "connected");
+    debug!(?t_connect, ?version, "connected");
+
+    let common = open_session(&mut send_stream, &mut recv_stream, &namespaces).await;
+    close_streams(send_stream, recv_stream)
+        .await
+        .map_err(ConnectError::close)?;
+    let common = common?;
+    debug!(
+        requested = namespaces.len(),
+        common = common.len(),
+        "session opened"
+    );
+
+    let mut results = Vec::new();
+    let mut credentialed = Vec::new();
+    for namespace in namespaces {
+        if common.contains(&namespace) {
+            continue;
+        }
+        if has_credentials(sync, namespace, peer_id).await {
+            credentialed.push(namespace);
+        } else {
+            let res = Err(ConnectError::remote_abort(AbortReason::NotFound));
+            results.push((namespace, res));
+        }
+    }
+    let synced = futures_util::stream::iter(common)
+        .map(|namespace| {
+            let connection = &connection;
+            let span = error_span!("sync", namespace = %namespace.fmt_short());
+            async move {
+                let res =
+                    sync_stream(connection, sync, namespace, peer_id, version, t_connect).await;
+                (namespace, res)
+            }
+            .instrument(span)
+        })
+        .buffer_unordered(MAX_SESSION_STREAMS)
+        .collect::<Vec<_>>()
+        .await;
+    results.extend(synced);
+    for namespace in credentialed {
+        let res = connect_and_sync(endpoint, sync, namespace, peer.clone()).await;
+        results.push((namespace, res));
+    }
+
+    let t_process = t_start.elapsed() - t_connect;
+    debug!(?t_connect, ?t_process, "session done");
+
+    Ok(SessionFinished {
+        peer: peer_id,
+        results,
+        timings: Timings {
+            connect: t_connect,
+            process: t_process,
+        },
+    })
+}
+
+/// Sync several replicas with a peer that only speaks version 1 of the protocol.
+///
+/// The first namespace is synced on the already established `connection`, every other namespace
+/// on a new connection.
+async fn sync_many_v1(
+    endpoint: &Endpoint,
+    sync: &SyncHandle,
+    namespaces: Vec<NamespaceId>,
+    peer: NodeAddr,
+    connection: Connection,
+    t_start: Instant,
+) -> Result<SessionFinished, ConnectError> {
+    let peer_id = peer.node_id;
+    let t_connect = t_start.elapsed();
+    let mut connection = Some(connection);
+    let mut results = Vec::with_capacity(namespaces.len());
+    for namespace in namespaces {
+        let t_start = Instant::now();
+        let connection = match connection.take() {
+            Some(connection) => Ok(connection),
+            None => endpoint
+                .connect(peer.clone(), DOCS_ALPN_V1)
+                .await
+                .map_err(ConnectError::connect),
+        };
+        let res = match connection {
+            Ok(connection) => {
+                let t_connect = t_start.elapsed();
+                let version = ProtocolVersion::V1;
+                sync_stream(&connection, sync, namespace, peer_id, version, t_connect).await
+            }
+            Err(err) => Err(err),
+        };
+        results.push((namespace, res));
+    }
+
+    let t_process = t_start.elapsed() - t_connect;
+    debug!(?t_connect, ?t_process, "done");
+
+    Ok(SessionFinished {
+        peer: peer_id,
+        results,
+        timings: Timings {
+            connect: t_connect,
+            process: t_process,
+        },
+    })
+}
+
+/// Whether we hold a read token for `namespace` or an invite to present to `peer`.
+async fn has_credentials(sync: &SyncHandle, namespace: NamespaceId, peer: PublicKey) -> bool {
+    let token = sync.get_read_token(namespace).await.ok().flatten();
+    let invite = sync.get_invite(namespace).await.ok().flatten();
+    token.is_some() || invite.is_some_and(|(_invite, nodes)| nodes.contains(peer.as_bytes()))
+}
+
+/// Sync a replica on a new stream of an established connection.
+async fn sync_stream(
+    connection: &Connection,
+    sync: &SyncHandle,
+    namespace: NamespaceId,
+    peer: PublicKey,
+    version: ProtocolVersion,
+    t_connect: Duration,
+) -> Result<SyncFinished, ConnectError> {
+    let t_start = Instant::now();
+    let (mut send_stream, mut recv_stream) =
+        connection.open_bi().await.map_err(ConnectError::connect)?;
 
-    let res = run_alice(&mut send_stream, &mut recv_stream, sync, namespace, peer_id).await;
+    let res = run_alice(
+        &mut send_stream,
+        &mut recv_stream,
+        sync,
+        namespace,
+        peer,
+        version,
+    )
+    .await;
 
-    send_stream.finish().map_err(ConnectError::close)?;
-    send_stream.stopped().await.map_err(ConnectError::close)?;
-    recv_stream
-        .read_to_end(0)
+    close_streams(send_stream, recv_stream)
         .await
         .map_err(ConnectError::close)?;
 
@@ -62,7 +291,7 @@ pub async fn connect_and_sync(
         inc!(Metrics, sync_via_connect_failure);
     }
 
-    let t_process = t_start.elapsed() - t_connect;
+    let t_process = t_start.elapsed();
     match &res {
         Ok(res) => {
             debug!(
@@ -87,7 +316,7 @@ pub async fn connect_and_sync(
 
     let res = SyncFinished {
         namespace,
-        peer: peer_id,
+        peer,
         outcome,
         timings,
     };
@@ -95,6 +324,17 @@ pub async fn connect_and_sync(
     Ok(res)
 }
 
+/// Finish our side of a stream and wait for the peer to finish its side.
+async fn close_streams(
+    mut send_stream: SendStream,
+    mut recv_stream: RecvStream,
+) -> anyhow::Result<()> {
+    send_stream.finish()?;
+    send_stream.stopped().await?;
+    recv_stream.read_to_end(0).await?;
+    Ok(())
+}
+
 /// Whether we want to accept or reject an incoming sync request.
 #[derive(Debug, Clone)]
 pub enum AcceptOutcome {
@@ -105,35 +345,155 @@ pub enum AcceptOutcome {
 }
 
 /// Handle an iroh-docs connection and sync all shared documents in the replica store.
+///
+/// The protocol version is chosen by the ALPN of the connection, see [`DOCS_ALPN`] and
+/// [`DOCS_ALPN_V1`].
+///
+/// Returns one result per synced namespace: a single one for a plain sync request, or one for
+/// each namespace of a sync session. An error before the first namespace is known is returned
+/// as the only result.
 pub async fn handle_connection<F, Fut>(
     sync: SyncHandle,
     connecting: iroh_net::endpoint::Connecting,
     accept_cb: F,
-) -> Result<SyncFinished, AcceptError>
+) -> Vec<Result<SyncFinished, AcceptError>>
+where
+    F: Fn(NamespaceId, PublicKey) -> Fut,
+    Fut: Future<Output = AcceptOutcome>,
+{
+    accept_connection(sync, connecting, accept_cb)
+        .await
+        .unwrap_or_else(|err| vec![Err(err)])
+}
+
+async fn accept_connection<F, Fut>(
+    sync: SyncHandle,
+    mut connecting: iroh_net::endpoint::Connecting,
+    accept_cb: F,
+) -> Result<Vec<Result<SyncFinished, AcceptError>>, AcceptError>
 where
     F: Fn(NamespaceId, PublicKey) -> Fut,
     Fut: Future<Output = AcceptOutcome>,
 {
     let t_start = Instant::now();
+    let alpn = connecting.alpn().await.map_err(AcceptError::connect)?;
+    let version = ProtocolVersion::from_alpn(&alpn).ok_or_else(|| {
+        AcceptError::connect(anyhow::anyhow!(
+            "unsupported ALPN {}",
+            String::from_utf8_lossy(&alpn)
+        ))
+    })?;
     let connection = connecting.await.map_err(AcceptError::connect)?;
     let peer = get_remote_node_id(&connection).map_err(AcceptError::connect)?;
-    let (mut send_stream, mut recv_stream) = connection
+    let (send_stream, recv_stream) = connection
         .accept_bi()
         .await
         .map_err(|e| AcceptError::open(peer, e))?;
 
     let t_connect = t_start.elapsed();
+    let namespaces = match accept_stream(
+        &sync,
+        peer,
+        version,
+        &accept_cb,
+        send_stream,
+        recv_stream,
+        t_connect,
+    )
+    .await?
+    {
+        Accepted::Sync(res) => return Ok(vec![Ok(res)]),
+        Accepted::Session(namespaces) => namespaces,
+    };
+    debug!(peer = %peer.fmt_short(), namespaces = namespaces.len(), "session opened");
+
+    // Accept one stream per namespace of the session. The peer only opens a limited number of
+    // streams at once, so the running streams have to make progress while we wait for new ones.
+    let mut remaining = namespaces.len();
+    let mut running = FuturesUnordered::new();
+    let mut results = Vec::with_capacity(remaining);
+    loop {
+        tokio::select! {
+            res = connection.accept_bi(), if remaining > 0 => {
+                remaining -= 1;
+                match res {
+                    Ok((send_stream, recv_stream)) => running.push(accept_stream(
+                        &sync,
+                        peer,
+                        version,
+                        &accept_cb,
+                        send_stream,
+                        recv_stream,
+                        t_connect,
+                    )),
+                    Err(err) => {
+                        results.push(Err(AcceptError::open(peer, err)));
+                        remaining = 0;
+                    }
+                }
+            }
+            Some(res) = running.next(), if !running.is_empty() => {
+                let res = match res {
+                    Ok(Accepted::Sync(res)) => Ok(res),
+                    Ok(Accepted::Session(_)) => Err(AcceptError::sync(
+                        peer,
+                        None,
+                        anyhow::anyhow!("unexpected session within session"),
+                    )),
+                    Err(err) => Err(err),
+                };
+                results.push(res);
+            }
+            else => break,
+        }
+    }
+    Ok(results)
+}
+
+/// A stream accepted by [`accept_stream`].
+enum Accepted {
+    /// A namespace was synced.
+    Sync(SyncFinished),
+    /// The peer opened a session for these namespaces.
+    Session(Vec<NamespaceId>),
+}
+
+/// Run the accepting side of the protocol on a single stream.
+async fn accept_stream<F, Fut>(
+    sync: &SyncHandle,
+    peer: PublicKey,
+    version: ProtocolVersion,
+    accept_cb: &F,
+    mut send_stream: SendStream,
+    mut recv_stream: RecvStream,
+    t_connect: Duration,
+) -> Result<Accepted, AcceptError>
+where
+    F: Fn(NamespaceId, PublicKey) -> Fut,
+    Fut: Future<Output = AcceptOutcome>,
+{
+    let t_start = Instant::now();
     let span = error_span!("accept", peer = %peer.fmt_short(), namespace = tracing::field::Empty);
     span.in_scope(|| {
         debug!(?t_connect, "connection established");
     });
 
-    let mut state = BobState::new(peer);
+    let mut state = BobState::new(peer, version);
     let res = state
-        .run(&mut send_stream, &mut recv_stream, sync, accept_cb)
+        .run(&mut send_stream, &mut recv_stream, sync.clone(), accept_cb)
         .instrument(span.clone())
         .await;
 
+    let namespace = state.namespace();
+    close_streams(send_stream, recv_stream)
+        .await
+        .map_err(|error| AcceptError::close(peer, namespace, error))?;
+
+    if let Some(namespaces) = state.session() {
+        res?;
+        return Ok(Accepted::Session(namespaces.to_vec()));
+    }
+
     #[cfg(feature = "metrics")]
     if res.is_ok() {
         inc!(Metrics, sync_via_accept_success);
@@ -141,22 +501,9 @@ where
         inc!(Metrics, sync_via_accept_failure);
     }
 
-    let namespace = state.namespace();
     let outcome = state.into_outcome();
 
-    send_stream
-        .finish()
-        .map_err(|error| AcceptError::close(peer, namespace, error))?;
-    send_stream
-        .stopped()
-        .await
-        .map_err(|error| AcceptError::close(peer, namespace, error))?;
-    recv_stream
-        .read_to_end(0)
-        .await
-        .map_err(|error| AcceptError::close(peer, namespace, error))?;
-
-    let t_process = t_start.elapsed() - t_connect;
+    let t_process = t_start.elapsed();
     span.in_scope(|| match &res {
         Ok(_res) => {
             debug!(
@@ -172,7 +519,8 @@ where
         }
     });
 
-    let namespace = res?;
+    res?;
+    let namespace = namespace.expect("namespace is set after init message");
 
     let timings = Timings {
         connect: t_connect,
@@ -185,7 +533,7 @@ where
         timings,
     };
 
-    Ok(res)
+    Ok(Accepted::Sync(res))
 }
 
 /// Details of a finished sync operation.
@@ -201,6 +549,38 @@ pub struct SyncFinished {
     pub timings: Timings,
 }
 
+/// Details of a finished sync session, see [`connect_and_sync_many`].
+#[derive(Debug)]
+pub struct SessionFinished {
+    /// The peer we synced with.
+    pub peer: PublicKey,
+    /// The result for each requested namespace.
+    pub results: Vec<(NamespaceId, Result<SyncFinished, ConnectError>)>,
+    /// The time the whole session took.
+    pub timings: Timings,
+}
+
+impl SessionFinished {
+    /// The number of entries sent to the peer, over all namespaces.
+    pub fn num_sent(&self) -> usize {
+        self.synced().map(|res| res.outcome.num_sent).sum()
+    }
+
+    /// The number of entries received from the peer, over all namespaces.
+    pub fn num_recv(&self) -> usize {
+        self.synced().map(|res| res.outcome.num_recv).sum()
+    }
+
+    /// The number of namespaces that failed to sync.
+    pub fn num_failed(&self) -> usize {
+        self.results.iter().filter(|(_, res)| res.is_err()).count()
+    }
+
+    fn synced(&self) -> impl Iterator<Item = &SyncFinished> {
+        self.results.iter().filter_map(|(_, res)| res.as_ref().ok())
+    }
+}
+
 /// Time a sync operation took
 #[derive(Debug, Default, Clone)]
 pub struct Timings {
@@ -288,6 +668,11 @@ pub enum AbortReason {
     AlreadySyncing,
     /// We experienced an error while trying to provide the requested resource
     InternalServerError,
+    /// The access policy of the namespace does not allow the peer to sync it.
+    AccessDenied,
+    /// The compaction cutoffs of the namespace differ, and the peer behind did not adopt the newer
+    /// one.
+    Compacted,
 }
 
 impl AcceptError {
@@ -367,3 +752,81 @@ impl ConnectError {
         Self::RemoteAbort(reason)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use iroh_net::relay::RelayMode;
+
+    use super::*;
+    use crate::{actor::OpenOpts, store, NamespaceSecret};
+
+    #[tokio::test]
+    async fn test_sync_with_version_1_peer() -> anyhow::Result<()> {
+        let _guard = iroh_test::logging::setup();
+        let mut rng = rand::thread_rng();
+        let alice_ep = Endpoint::builder()
+            .relay_mode(RelayMode::Disabled)
+            .bind()
+            .await?;
+        // bob only speaks version 1 of the protocol
+        let bob_ep = Endpoint::builder()
+            .alpns(vec![DOCS_ALPN_V1.to_vec()])
+            .relay_mode(RelayMode::Disabled)
+            .bind()
+            .await?;
+        let bob_addr = bob_ep.node_addr().await?;
+
+        let mut alice_store = store::Store::memory();
+        let mut bob_store = store::Store::memory();
+        let author = bob_store.new_author(&mut rng)?;
+        let mut namespaces = Vec::new();
+        for _ in 0..2 {
+            let namespace = NamespaceSecret::new(&mut rng);
+            alice_store.new_replica(namespace.clone())?;
+            alice_store.close_replica(namespace.id());
+            let mut replica = bob_store.new_replica(namespace.clone())?;
+            replica.hash_and_insert("hello alice", &author, "from bob")?;
+            bob_store.close_replica(namespace.id());
+            namespaces.push(namespace.id());
+        }
+        let alice_sync = SyncHandle::spawn(alice_store, None, "alice".to_string());
+        let bob_sync = SyncHandle::spawn(bob_store, None, "bob".to_string());
+        for namespace in &namespaces {
+            alice_sync
+                .open(*namespace, OpenOpts::default().sync())
+                .await?;
+            bob_sync
+                .open(*namespace, OpenOpts::default().sync())
+                .await?;
+        }
+
+        let bob_sync2 = bob_sync.clone();
+        let bob_task = tokio::task::spawn(async move {
+            while let Some(incoming) = bob_ep.accept().await {
+                let Ok(connecting) = incoming.accept() else {
+                    continue;
+                };
+                handle_connection(bob_sync2.clone(), connecting, |_namespace, _peer| {
+                    std::future::ready(AcceptOutcome::Allow)
+                })
+                .await;
+            }
+        });
+
+        let res =
+            connect_and_sync_many(&alice_ep, &alice_sync, namespaces.clone(), bob_addr).await?;
+        assert_eq!(res.results.len(), 2);
+        for (_namespace, res) in res.results {
+            assert_eq!(res?.outcome.num_recv, 1);
+        }
+        bob_task.abort();
+
+        let mut alice_store = alice_sync.shutdown().await?;
+        for namespace in namespaces {
+            let entry = alice_store.get_exact(namespace, author.id(), "hello alice", false)?;
+            assert!(entry.is_some());
+        }
+        bob_sync.shutdown().await?;
+        Ok(())
+    }
+}
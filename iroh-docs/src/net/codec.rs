@@ -1,8 +1,12 @@
-use std::future::Future;
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+};
 
 use anyhow::{anyhow, ensure};
-use bytes::{Buf, BufMut, BytesMut};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 use futures_util::SinkExt;
+use iroh_base::hash::Hash;
 use iroh_net::key::PublicKey;
 use serde::{Deserialize, Serialize};
 use tokio::io::{AsyncRead, AsyncWrite};
@@ -12,8 +16,9 @@ use tracing::{debug, trace, Span};
 
 use crate::{
     actor::SyncHandle,
-    net::{AbortReason, AcceptError, AcceptOutcome, ConnectError},
-    NamespaceId, SyncOutcome,
+    net::{AbortReason, AcceptError, AcceptOutcome, ConnectError, ProtocolVersion},
+    store::MAX_INLINE_LIMIT,
+    AreaOfInterest, Capability, Invite, NamespaceId, ReadToken, SyncOutcome, WriteDelegation,
 };
 
 #[derive(Debug, Default)]
@@ -69,10 +74,47 @@ impl Encoder<Message> for SyncCodec {
 
 /// Sync Protocol
 ///
+/// - ReadToken message: a [`ReadToken`] for the namespace, sent by the dialing peer before the
+///   init message, only if it holds a token
+/// - Invite message: an [`Invite`] to the namespace, sent by the dialing peer before the init
+///   message, only if it holds an invite that it did not redeem yet, and the accepting peer is
+///   one of the inviting nodes of the invite
+/// - Compaction message: the compaction cutoff of the dialing peer, sent before the init
+///   message, only if it ever compacted the namespace
+/// - Interest message: the [`AreaOfInterest`] of the dialing peer, sent before the init message
+///   only if it does not replicate the full namespace
+/// - Content message: the content of small values of the entries in the init message, sent
+///   before the init message, only if the dialing peer has an inline limit for the namespace
 /// - Init message: signals which namespace is being synced
-/// - N Sync messages
+/// - Capability message: the write capability for the namespace, sent by the accepting peer
+///   before its first sync message, only if it redeemed a write invite of the dialing peer
+/// - Interest message: the intersection of both areas of interest, sent by the accepting peer
+///   before its first sync message, only if it differs from the area of the dialing peer
+/// - Compaction message: the compaction cutoff of the accepting peer, sent after its interest
+///   message, only if it is newer than the cutoff of the dialing peer
+/// - Delegations message: the write delegations known for the namespace, sent by each peer
+///   before its first sync message, only if it knows any delegations
+/// - N Sync messages, each preceded by a Content message with the content of small values of
+///   its entries, only if the sending peer has an inline limit for the namespace
 ///
 /// On any error and on success the substream is closed.
+///
+/// Entries older than the compaction cutoff are only reconciled once both peers agree on the
+/// cutoff: the peer with the older cutoff adopts the newer one, dropping all its entries older
+/// than it to fetch them again, see [`crate::store::fs::Store::adopt_compaction_cutoff`]. If it
+/// cannot, the sync is aborted with [`AbortReason::Compacted`].
+///
+/// To sync several namespaces with a single connection, the dialing peer first opens a session:
+/// it sends a Session message with the namespaces it wants to sync as the only message on the
+/// first substream, and the accepting peer answers with a Session message containing the
+/// namespaces both peers have in common and the dialing peer may read without a read token or
+/// invite. The dialing peer then runs the protocol above for each common namespace on a new
+/// substream of the same connection.
+///
+/// Peers that do not know the Session and Content messages fail to decode them, so the protocol
+/// is negotiated under a new ALPN, see [`crate::net::DOCS_ALPN`]. On connections negotiated
+/// under [`crate::net::DOCS_ALPN_V1`] neither message is sent, a Session message is rejected,
+/// and namespaces that were compacted are not synced.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 enum Message {
     /// Init message (sent by the dialing peer)
@@ -86,6 +128,22 @@ enum Message {
     Sync(crate::sync::ProtocolMessage),
     /// Abort message (sent by the accepting peer to decline a request)
     Abort { reason: AbortReason },
+    /// Write delegations for the namespace (sent by both peers)
+    Delegations(Vec<WriteDelegation>),
+    /// Read token for the namespace (sent by the dialing peer)
+    ReadToken(ReadToken),
+    /// Invite to the namespace (sent by the dialing peer)
+    Invite(Invite),
+    /// Capability granted for a redeemed invite (sent by the accepting peer)
+    Capability(Capability),
+    /// Area of interest for the sync session (sent by both peers)
+    Interest(AreaOfInterest),
+    /// Compaction cutoff for the namespace (sent by both peers)
+    Compaction(u64),
+    /// Namespaces to sync in a session (sent by both peers)
+    Session { namespaces: Vec<NamespaceId> },
+    /// Content of small values of the entries in the next Init or Sync message (sent by both peers)
+    Content(Vec<Bytes>),
 }
 
 /// Runs the initiator side of the sync protocol.
@@ -95,6 +153,7 @@ pub(super) async fn run_alice<R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(
     handle: &SyncHandle,
     namespace: NamespaceId,
     peer: PublicKey,
+    version: ProtocolVersion,
 ) -> Result<SyncOutcome, ConnectError> {
     let peer_bytes = *peer.as_bytes();
     let mut reader = FramedRead::new(reader, SyncCodec);
@@ -102,35 +161,150 @@ pub(super) async fn run_alice<R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(
 
     let mut progress = Some(SyncOutcome::default());
 
-    // Init message
+    // Read token and init message
 
+    let token = handle
+        .get_read_token(namespace)
+        .await
+        .map_err(ConnectError::sync)?;
+    if let Some(token) = token {
+        trace!("send read token message");
+        writer
+            .send(Message::ReadToken(token))
+            .await
+            .map_err(ConnectError::sync)?;
+    }
+    // Only present the invite to the inviting nodes, other peers could redeem it themselves.
+    let invite = handle
+        .get_invite(namespace)
+        .await
+        .map_err(ConnectError::sync)?
+        .filter(|(_invite, nodes)| nodes.contains(&peer_bytes))
+        .map(|(invite, _nodes)| invite);
+    if let Some(invite) = invite.clone() {
+        trace!("send invite message");
+        writer
+            .send(Message::Invite(invite))
+            .await
+            .map_err(ConnectError::sync)?;
+    }
+    let cutoff = handle
+        .get_compaction_cutoff(namespace)
+        .await
+        .map_err(ConnectError::sync)?;
+    if cutoff > 0 {
+        if version == ProtocolVersion::V1 {
+            return Err(ConnectError::sync(anyhow!(
+                "peer does not support compacted documents"
+            )));
+        }
+        trace!("send compaction message");
+        writer
+            .send(Message::Compaction(cutoff))
+            .await
+            .map_err(ConnectError::sync)?;
+    }
+    let interest = handle
+        .get_sync_interest(namespace)
+        .await
+        .map_err(ConnectError::sync)?;
+    let mut area = interest.clone();
+    if !area.is_full() {
+        trace!("send interest message");
+        writer
+            .send(Message::Interest(area.clone()))
+            .await
+            .map_err(ConnectError::sync)?;
+    }
+    let inline_limit = match version {
+        ProtocolVersion::V1 => 0,
+        ProtocolVersion::V2 => handle
+            .get_inline_limit(namespace)
+            .await
+            .map_err(ConnectError::sync)?,
+    };
     let message = handle
-        .sync_initial_message(namespace)
+        .sync_initial_message(namespace, area.clone())
         .await
         .map_err(ConnectError::sync)?;
+    let content = inline_content(handle, &message, inline_limit).await;
+    if !content.is_empty() {
+        trace!("send content message");
+        writer
+            .send(Message::Content(content))
+            .await
+            .map_err(ConnectError::sync)?;
+    }
     let init_message = Message::Init { namespace, message };
     trace!("send init message");
     writer
         .send(init_message)
         .await
         .map_err(ConnectError::sync)?;
+    let delegations = handle
+        .list_delegations(namespace)
+        .await
+        .map_err(ConnectError::sync)?;
+    if !delegations.is_empty() {
+        trace!("send delegations message");
+        writer
+            .send(Message::Delegations(delegations))
+            .await
+            .map_err(ConnectError::sync)?;
+    }
 
     // Sync message loop
+    let mut pending_content = Vec::new();
     while let Some(msg) = reader.next().await {
         let msg = msg.map_err(ConnectError::sync)?;
         match msg {
             Message::Init { .. } => {
                 return Err(ConnectError::sync(anyhow!("unexpected init message")));
             }
+            Message::ReadToken(_) => {
+                return Err(ConnectError::sync(anyhow!("unexpected read token message")));
+            }
+            Message::Invite(_) => {
+                return Err(ConnectError::sync(anyhow!("unexpected invite message")));
+            }
+            Message::Session { .. } => {
+                return Err(ConnectError::sync(anyhow!("unexpected session message")));
+            }
+            Message::Capability(capability) => {
+                trace!("recv capability message");
+                if invite.is_none() || capability.id() != namespace {
+                    return Err(ConnectError::sync(anyhow!("unexpected capability message")));
+                }
+                handle
+                    .import_namespace(capability)
+                    .await
+                    .map_err(ConnectError::sync)?;
+            }
             Message::Sync(msg) => {
                 trace!("recv process message");
+                let content = received_content(&msg, std::mem::take(&mut pending_content));
                 let current_progress = progress.take().unwrap();
                 let (reply, next_progress) = handle
-                    .sync_process_message(namespace, msg, peer_bytes, current_progress)
+                    .sync_process_message_with_content(
+                        namespace,
+                        msg,
+                        peer_bytes,
+                        area.clone(),
+                        current_progress,
+                        content,
+                    )
                     .await
                     .map_err(ConnectError::sync)?;
                 progress = Some(next_progress);
                 if let Some(msg) = reply {
+                    let content = inline_content(handle, &msg, inline_limit).await;
+                    if !content.is_empty() {
+                        trace!("send content message");
+                        writer
+                            .send(Message::Content(content))
+                            .await
+                            .map_err(ConnectError::sync)?;
+                    }
                     trace!("send process message");
                     writer
                         .send(Message::Sync(msg))
@@ -143,13 +317,83 @@ pub(super) async fn run_alice<R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(
             Message::Abort { reason } => {
                 return Err(ConnectError::remote_abort(reason));
             }
+            Message::Delegations(delegations) => {
+                trace!("recv delegations message");
+                add_delegations(handle, namespace, delegations).await;
+            }
+            Message::Interest(session) => {
+                trace!("recv interest message");
+                area = area.intersection(&session);
+            }
+            Message::Compaction(peer_cutoff) => {
+                trace!("recv compaction message");
+                if peer_cutoff <= cutoff {
+                    return Err(ConnectError::sync(anyhow!("unexpected compaction message")));
+                }
+                adopt_compaction_cutoff(handle, namespace, peer_cutoff, &area, &interest)
+                    .await
+                    .map_err(ConnectError::sync)?;
+            }
+            Message::Content(content) => {
+                trace!("recv content message");
+                pending_content = content;
+            }
         }
     }
 
+    // The inviting node accepted the sync, so it redeemed our invite.
+    if invite.is_some() {
+        handle
+            .remove_invite(namespace)
+            .await
+            .map_err(ConnectError::sync)?;
+    }
+
     trace!("done");
     Ok(progress.unwrap())
 }
 
+/// Runs the initiator side of the session negotiation.
+///
+/// Returns the namespaces that both peers have in common.
+pub(super) async fn open_session<R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    reader: &mut R,
+    namespaces: &[NamespaceId],
+) -> Result<Vec<NamespaceId>, ConnectError> {
+    let mut reader = FramedRead::new(reader, SyncCodec);
+    let mut writer = FramedWrite::new(writer, SyncCodec);
+
+    trace!(len = namespaces.len(), "send session message");
+    writer
+        .send(Message::Session {
+            namespaces: namespaces.to_vec(),
+        })
+        .await
+        .map_err(ConnectError::sync)?;
+
+    match reader.next().await {
+        Some(Ok(Message::Session { namespaces: common })) => {
+            trace!(len = common.len(), "recv session message");
+            if common
+                .iter()
+                .any(|namespace| !namespaces.contains(namespace))
+            {
+                return Err(ConnectError::sync(anyhow!(
+                    "unexpected namespace in session message"
+                )));
+            }
+            Ok(common)
+        }
+        Some(Ok(Message::Abort { reason })) => Err(ConnectError::remote_abort(reason)),
+        Some(Ok(_)) => Err(ConnectError::sync(anyhow!("unexpected message"))),
+        Some(Err(err)) => Err(ConnectError::sync(err)),
+        None => Err(ConnectError::sync(anyhow!(
+            "stream closed before session message"
+        ))),
+    }
+}
+
 /// Runs the receiver side of the sync protocol.
 #[cfg(test)]
 pub(super) async fn run_bob<R, W, F, Fut>(
@@ -165,8 +409,9 @@ where
     F: Fn(NamespaceId, PublicKey) -> Fut,
     Fut: Future<Output = AcceptOutcome>,
 {
-    let mut state = BobState::new(peer);
-    let namespace = state.run(writer, reader, handle, accept_cb).await?;
+    let mut state = BobState::new(peer, ProtocolVersion::V2);
+    state.run(writer, reader, handle, accept_cb).await?;
+    let namespace = state.namespace().expect("init message was received");
     Ok((namespace, state.into_outcome()))
 }
 
@@ -174,16 +419,32 @@ where
 pub struct BobState {
     namespace: Option<NamespaceId>,
     peer: PublicKey,
+    version: ProtocolVersion,
     progress: Option<SyncOutcome>,
+    read_token: Option<ReadToken>,
+    invite: Option<Invite>,
+    area: AreaOfInterest,
+    compaction_cutoff: u64,
+    session: Option<Vec<NamespaceId>>,
+    inline_limit: u64,
+    inline_content: Vec<Bytes>,
 }
 
 impl BobState {
-    /// Create a new state for a single connection.
-    pub fn new(peer: PublicKey) -> Self {
+    /// Create a new state for a single connection, speaking `version` of the protocol.
+    pub fn new(peer: PublicKey, version: ProtocolVersion) -> Self {
         Self {
             peer,
+            version,
             namespace: None,
             progress: Some(Default::default()),
+            read_token: None,
+            invite: None,
+            area: AreaOfInterest::full(),
+            compaction_cutoff: 0,
+            session: None,
+            inline_limit: 0,
+            inline_content: Vec::new(),
         }
     }
 
@@ -192,13 +453,15 @@ impl BobState {
     }
 
     /// Handle connection and run to end.
+    ///
+    /// Either syncs a single namespace, or negotiates a session, see [`Self::session`].
     pub async fn run<R, W, F, Fut>(
         &mut self,
         writer: W,
         reader: R,
         sync: SyncHandle,
         accept_cb: F,
-    ) -> Result<NamespaceId, AcceptError>
+    ) -> Result<(), AcceptError>
     where
         R: AsyncRead + Unpin,
         W: AsyncWrite + Unpin,
@@ -214,10 +477,41 @@ impl BobState {
                     Span::current()
                         .record("namespace", tracing::field::display(&namespace.fmt_short()));
                     trace!("recv init message");
-                    let accept = accept_cb(namespace, self.peer).await;
+                    let mut accept = accept_cb(namespace, self.peer).await;
+                    let peer = *self.peer.as_bytes();
+                    let mut grant = None;
+                    if let Some(invite) = self.invite.take() {
+                        if let AcceptOutcome::Allow = accept {
+                            match sync.redeem_invite(namespace, peer, invite).await {
+                                Ok(capability) => grant = capability,
+                                Err(err) => {
+                                    debug!(?err, "rejecting invalid invite");
+                                    accept = AcceptOutcome::Reject(AbortReason::AccessDenied);
+                                }
+                            }
+                        }
+                    }
+                    if let AcceptOutcome::Allow = accept {
+                        let token = self.read_token.take();
+                        accept = match sync.authorize_read(namespace, peer, token).await {
+                            Ok(true) => AcceptOutcome::Allow,
+                            Ok(false) => AcceptOutcome::Reject(AbortReason::AccessDenied),
+                            Err(err) => {
+                                debug!(?err, "failed to check access policy");
+                                AcceptOutcome::Reject(AbortReason::InternalServerError)
+                            }
+                        };
+                    }
                     match accept {
                         AcceptOutcome::Allow => {
                             trace!("allow request");
+                            if let Some(capability) = grant {
+                                trace!("send capability message");
+                                writer
+                                    .send(Message::Capability(capability))
+                                    .await
+                                    .map_err(|e| self.fail(e))?;
+                            }
                         }
                         AcceptOutcome::Reject(reason) => {
                             debug!(?reason, "reject request");
@@ -232,28 +526,176 @@ impl BobState {
                             });
                         }
                     }
+                    let own_area = sync
+                        .get_sync_interest(namespace)
+                        .await
+                        .map_err(|e| self.fail(e))?;
+                    let session = own_area.intersection(&self.area);
+                    if session != self.area {
+                        trace!("send interest message");
+                        writer
+                            .send(Message::Interest(session.clone()))
+                            .await
+                            .map_err(|e| self.fail(e))?;
+                    }
+                    self.area = session;
+                    let own_cutoff = sync
+                        .get_compaction_cutoff(namespace)
+                        .await
+                        .map_err(|e| self.fail(e))?;
+                    let compaction = if self.compaction_cutoff > own_cutoff {
+                        adopt_compaction_cutoff(
+                            &sync,
+                            namespace,
+                            self.compaction_cutoff,
+                            &self.area,
+                            &own_area,
+                        )
+                        .await
+                    } else if own_cutoff > self.compaction_cutoff
+                        && self.version == ProtocolVersion::V1
+                    {
+                        Err(anyhow!("peer does not support compacted documents"))
+                    } else {
+                        Ok(())
+                    };
+                    if let Err(err) = compaction {
+                        debug!(?err, "reject sync with a different compaction cutoff");
+                        let reason = AbortReason::Compacted;
+                        writer
+                            .send(Message::Abort { reason })
+                            .await
+                            .map_err(|e| self.fail(e))?;
+                        return Err(AcceptError::Abort {
+                            namespace,
+                            peer: self.peer,
+                            reason,
+                        });
+                    }
+                    if own_cutoff > self.compaction_cutoff {
+                        trace!("send compaction message");
+                        writer
+                            .send(Message::Compaction(own_cutoff))
+                            .await
+                            .map_err(|e| self.fail(e))?;
+                    }
+                    if self.version == ProtocolVersion::V2 {
+                        self.inline_limit = sync
+                            .get_inline_limit(namespace)
+                            .await
+                            .map_err(|e| self.fail(e))?;
+                    }
+                    let content =
+                        received_content(&message, std::mem::take(&mut self.inline_content));
                     let last_progress = self.progress.take().unwrap();
                     let next = sync
-                        .sync_process_message(
+                        .sync_process_message_with_content(
                             namespace,
                             message,
                             *self.peer.as_bytes(),
+                            self.area.clone(),
                             last_progress,
+                            content,
                         )
                         .await;
                     self.namespace = Some(namespace);
+                    let delegations = sync
+                        .list_delegations(namespace)
+                        .await
+                        .map_err(|e| self.fail(e))?;
+                    if !delegations.is_empty() {
+                        trace!("send delegations message");
+                        writer
+                            .send(Message::Delegations(delegations))
+                            .await
+                            .map_err(|e| self.fail(e))?;
+                    }
                     next
                 }
+                (Message::ReadToken(token), None) => {
+                    trace!("recv read token message");
+                    self.read_token = Some(token);
+                    continue;
+                }
+                (Message::Invite(invite), None) => {
+                    trace!("recv invite message");
+                    self.invite = Some(invite);
+                    continue;
+                }
+                (Message::Interest(area), None) => {
+                    trace!("recv interest message");
+                    self.area = area;
+                    continue;
+                }
+                (Message::Compaction(cutoff), None) => {
+                    trace!("recv compaction message");
+                    self.compaction_cutoff = cutoff;
+                    continue;
+                }
+                (Message::Session { .. }, None) if self.version == ProtocolVersion::V1 => {
+                    return Err(self.fail(anyhow!("unexpected session message")))
+                }
+                (Message::Session { namespaces }, None) => {
+                    trace!(len = namespaces.len(), "recv session message");
+                    let common = common_namespaces(&sync, self.peer, namespaces)
+                        .await
+                        .map_err(|e| self.fail(e))?;
+                    trace!(len = common.len(), "send session message");
+                    writer
+                        .send(Message::Session {
+                            namespaces: common.clone(),
+                        })
+                        .await
+                        .map_err(|e| self.fail(e))?;
+                    self.session = Some(common);
+                    break;
+                }
+                (Message::Delegations(delegations), Some(namespace)) => {
+                    trace!("recv delegations message");
+                    add_delegations(&sync, *namespace, delegations).await;
+                    continue;
+                }
+                (Message::Content(content), _) => {
+                    trace!("recv content message");
+                    self.inline_content = content;
+                    continue;
+                }
                 (Message::Sync(msg), Some(namespace)) => {
                     trace!("recv process message");
+                    let content = received_content(&msg, std::mem::take(&mut self.inline_content));
                     let last_progress = self.progress.take().unwrap();
-                    sync.sync_process_message(*namespace, msg, *self.peer.as_bytes(), last_progress)
-                        .await
+                    sync.sync_process_message_with_content(
+                        *namespace,
+                        msg,
+                        *self.peer.as_bytes(),
+                        self.area.clone(),
+                        last_progress,
+                        content,
+                    )
+                    .await
                 }
                 (Message::Init { .. }, Some(_)) => {
                     return Err(self.fail(anyhow!("double init message")))
                 }
-                (Message::Sync(_), None) => {
+                (Message::ReadToken(_), Some(_)) => {
+                    return Err(self.fail(anyhow!("unexpected read token after init message")))
+                }
+                (Message::Invite(_), Some(_)) => {
+                    return Err(self.fail(anyhow!("unexpected invite after init message")))
+                }
+                (Message::Capability(_), _) => {
+                    return Err(self.fail(anyhow!("unexpected capability message")))
+                }
+                (Message::Interest(_), Some(_)) => {
+                    return Err(self.fail(anyhow!("unexpected interest after init message")))
+                }
+                (Message::Compaction(_), Some(_)) => {
+                    return Err(self.fail(anyhow!("unexpected compaction after init message")))
+                }
+                (Message::Session { .. }, Some(_)) => {
+                    return Err(self.fail(anyhow!("unexpected session after init message")))
+                }
+                (Message::Sync(_) | Message::Delegations(_), None) => {
                     return Err(self.fail(anyhow!("unexpected sync message before init")))
                 }
                 (Message::Abort { .. }, _) => {
@@ -264,6 +706,14 @@ impl BobState {
             self.progress = Some(progress);
             match reply {
                 Some(msg) => {
+                    let content = inline_content(&sync, &msg, self.inline_limit).await;
+                    if !content.is_empty() {
+                        trace!("send content message");
+                        writer
+                            .send(Message::Content(content))
+                            .await
+                            .map_err(|e| self.fail(e))?;
+                    }
                     trace!("send process message");
                     writer
                         .send(Message::Sync(msg))
@@ -276,8 +726,10 @@ impl BobState {
 
         trace!("done");
 
-        self.namespace()
-            .ok_or_else(|| self.fail(anyhow!("Stream closed before init message")))
+        if self.namespace.is_none() && self.session.is_none() {
+            return Err(self.fail(anyhow!("Stream closed before init message")));
+        }
+        Ok(())
     }
 
     /// Get the namespace that is synced, if available.
@@ -285,18 +737,129 @@ impl BobState {
         self.namespace
     }
 
+    /// Get the namespaces negotiated for a session, if the peer opened one.
+    pub fn session(&self) -> Option<&[NamespaceId]> {
+        self.session.as_deref()
+    }
+
     /// Consume self and get the [`SyncOutcome`] for this connection.
     pub fn into_outcome(self) -> SyncOutcome {
         self.progress.unwrap()
     }
 }
 
+/// Filter the namespaces requested for a session down to the ones in our store that `peer` may
+/// read without presenting a read token or invite.
+///
+/// Namespaces the peer may not read are left out, so that the answer does not reveal which
+/// restricted namespaces we have.
+async fn common_namespaces(
+    handle: &SyncHandle,
+    peer: PublicKey,
+    namespaces: Vec<NamespaceId>,
+) -> anyhow::Result<Vec<NamespaceId>> {
+    let (tx, rx) = async_channel::bounded(64);
+    handle.list_replicas(tx).await?;
+    let mut ours = HashSet::new();
+    while let Ok(item) = rx.recv().await {
+        let (namespace, _kind) = item?;
+        ours.insert(namespace);
+    }
+    let mut seen = HashSet::new();
+    let mut common = Vec::new();
+    for namespace in namespaces {
+        if !ours.contains(&namespace) || !seen.insert(namespace) {
+            continue;
+        }
+        match handle
+            .authorize_read(namespace, *peer.as_bytes(), None)
+            .await
+        {
+            Ok(true) => common.push(namespace),
+            Ok(false) => {}
+            Err(err) => debug!(?err, "failed to check access policy"),
+        }
+    }
+    Ok(common)
+}
+
+/// Adopt the newer compaction cutoff of the peer before reconciling.
+///
+/// Our entries older than the cutoff are dropped, and only the peer can sync them again. This
+/// fails if the sync does not cover our full area of interest, because the dropped entries
+/// outside of it would not be synced again.
+async fn adopt_compaction_cutoff(
+    handle: &SyncHandle,
+    namespace: NamespaceId,
+    cutoff: u64,
+    area: &AreaOfInterest,
+    interest: &AreaOfInterest,
+) -> anyhow::Result<()> {
+    ensure!(
+        area == interest,
+        "cannot adopt the compaction cutoff of the peer in a partial sync"
+    );
+    let removed = handle.adopt_compaction_cutoff(namespace, cutoff).await?;
+    debug!(removed, "adopted newer compaction cutoff of peer");
+    Ok(())
+}
+
+/// Get the inline content of the values in `message` that are not longer than `limit`.
+///
+/// The content is read with the content reader of the handle, values without complete content
+/// are skipped.
+async fn inline_content(
+    handle: &SyncHandle,
+    message: &crate::sync::ProtocolMessage,
+    limit: u64,
+) -> Vec<Bytes> {
+    let entries = message.values().map(|(entry, _)| entry);
+    handle.read_inline_content(entries, limit).await
+}
+
+/// Match the inline content received from a peer to the values in `message`.
+///
+/// Content that is not referenced by any entry in the message, or is longer than
+/// [`MAX_INLINE_LIMIT`], is dropped. The content is not stored here: it is only passed on with
+/// the entries that pass validation.
+fn received_content(
+    message: &crate::sync::ProtocolMessage,
+    content: Vec<Bytes>,
+) -> HashMap<Hash, Bytes> {
+    if content.is_empty() {
+        return HashMap::new();
+    }
+    let hashes: HashSet<Hash> = message
+        .values()
+        .map(|(entry, _)| entry.content_hash())
+        .collect();
+    content
+        .into_iter()
+        .filter(|data| data.len() as u64 <= MAX_INLINE_LIMIT)
+        .map(|data| (Hash::new(&data), data))
+        .filter(|(hash, _data)| hashes.contains(hash))
+        .collect()
+}
+
+/// Add write delegations received from a peer, ignoring invalid ones.
+async fn add_delegations(
+    handle: &SyncHandle,
+    namespace: NamespaceId,
+    delegations: Vec<WriteDelegation>,
+) {
+    for delegation in delegations {
+        if let Err(err) = handle.add_delegation(namespace, delegation).await {
+            debug!(?err, "ignoring invalid delegation");
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
         actor::OpenOpts,
         store::{self, Query, Store},
-        AuthorId, NamespaceSecret,
+        AccessPolicy, AuthorId, NamespaceSecret, RecordIdentifier,
     };
     use anyhow::Result;
     use iroh_base::hash::Hash;
@@ -369,6 +932,7 @@ mod tests {
                 &alice_handle2,
                 namespace_id,
                 bob_peer_id,
+                ProtocolVersion::V2,
             )
             .await
         });
@@ -418,6 +982,250 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_sync_session() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let alice_peer_id = SecretKey::from_bytes(&[1u8; 32]).public();
+        let shared = NamespaceSecret::new(&mut rng);
+        let bob_only = NamespaceSecret::new(&mut rng);
+        let alice_only = NamespaceSecret::new(&mut rng);
+        let restricted = NamespaceSecret::new(&mut rng);
+
+        let mut bob_store = store::Store::memory();
+        for namespace in [&shared, &bob_only, &restricted] {
+            let replica = bob_store.new_replica(namespace.clone())?;
+            let id = replica.id();
+            bob_store.close_replica(id);
+        }
+        // alice may not read this namespace, so bob must not reveal that he has it
+        bob_store.set_access_policy(&restricted.id(), AccessPolicy::restricted([]))?;
+        let bob_handle = SyncHandle::spawn(bob_store, None, "bob".to_string());
+
+        let (alice, bob) = tokio::io::duplex(64);
+        let (mut alice_reader, mut alice_writer) = tokio::io::split(alice);
+        let (mut bob_reader, mut bob_writer) = tokio::io::split(bob);
+
+        let bob_handle2 = bob_handle.clone();
+        let bob_task = tokio::task::spawn(async move {
+            let mut state = BobState::new(alice_peer_id, ProtocolVersion::V2);
+            state
+                .run(
+                    &mut bob_writer,
+                    &mut bob_reader,
+                    bob_handle2,
+                    |_namespace, _peer| std::future::ready(AcceptOutcome::Allow),
+                )
+                .await?;
+            anyhow::Ok(state.session().map(|namespaces| namespaces.to_vec()))
+        });
+
+        let requested = [alice_only.id(), shared.id(), shared.id(), restricted.id()];
+        let common = open_session(&mut alice_writer, &mut alice_reader, &requested).await?;
+        drop(alice_writer);
+        assert_eq!(common, vec![shared.id()]);
+        assert_eq!(bob_task.await??, Some(vec![shared.id()]));
+
+        bob_handle.shutdown().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sync_version_1() -> Result<()> {
+        let _guard = iroh_test::logging::setup();
+        let mut rng = rand::thread_rng();
+        let alice_peer_id = SecretKey::from_bytes(&[1u8; 32]).public();
+        let bob_peer_id = SecretKey::from_bytes(&[2u8; 32]).public();
+        let namespace = NamespaceSecret::new(&mut rng);
+        let namespace_id = namespace.id();
+
+        for version in [ProtocolVersion::V1, ProtocolVersion::V2] {
+            let mut alice_store = store::Store::memory();
+            let author = alice_store.new_author(&mut rng)?;
+            let mut replica = alice_store.new_replica(namespace.clone())?;
+            let hash = replica.hash_and_insert("hello bob", &author, "small")?;
+            alice_store.close_replica(namespace_id);
+            alice_store.set_inline_limit(&namespace_id, 16)?;
+
+            let mut bob_store = store::Store::memory();
+            bob_store.new_replica(namespace.clone())?;
+            bob_store.close_replica(namespace_id);
+            bob_store.set_inline_limit(&namespace_id, 16)?;
+
+            let content_reader: crate::ContentReaderCallback =
+                std::sync::Arc::new(|_hash| Box::pin(async { Some(Bytes::from_static(b"small")) }));
+            let alice_handle = SyncHandle::spawn(alice_store, None, "alice".to_string())
+                .with_content_reader(content_reader);
+            let bob_handle = SyncHandle::spawn(bob_store, None, "bob".to_string());
+            for handle in [&alice_handle, &bob_handle] {
+                handle
+                    .open(namespace_id, OpenOpts::default().sync())
+                    .await?;
+            }
+
+            let (alice, bob) = tokio::io::duplex(64);
+            let (mut alice_reader, mut alice_writer) = tokio::io::split(alice);
+            let (mut bob_reader, mut bob_writer) = tokio::io::split(bob);
+            let alice_handle2 = alice_handle.clone();
+            let alice_task = tokio::task::spawn(async move {
+                run_alice(
+                    &mut alice_writer,
+                    &mut alice_reader,
+                    &alice_handle2,
+                    namespace_id,
+                    bob_peer_id,
+                    version,
+                )
+                .await
+            });
+            let bob_handle2 = bob_handle.clone();
+            let bob_task = tokio::task::spawn(async move {
+                let mut state = BobState::new(alice_peer_id, version);
+                state
+                    .run(
+                        &mut bob_writer,
+                        &mut bob_reader,
+                        bob_handle2,
+                        |_namespace, _peer| std::future::ready(AcceptOutcome::Allow),
+                    )
+                    .await
+            });
+            alice_task.await??;
+            bob_task.await??;
+
+            // the entry is synced with both versions, its content only with version 2
+            let mut bob_store = bob_handle.shutdown().await?;
+            alice_handle.shutdown().await?;
+            let entry = bob_store.get_exact(namespace_id, author.id(), "hello bob", false)?;
+            assert_eq!(entry.map(|entry| entry.content_hash()), Some(hash));
+            let record = RecordIdentifier::new(namespace_id, author.id(), "hello bob");
+            let expected = match version {
+                ProtocolVersion::V1 => None,
+                ProtocolVersion::V2 => Some(Bytes::from_static(b"small")),
+            };
+            assert_eq!(bob_store.get_inline_content(&record, &hash)?, expected);
+        }
+
+        // a version 1 peer does not open sessions
+        let bob_handle = SyncHandle::spawn(store::Store::memory(), None, "bob".to_string());
+        let (alice, bob) = tokio::io::duplex(64);
+        let (mut alice_reader, mut alice_writer) = tokio::io::split(alice);
+        let (mut bob_reader, mut bob_writer) = tokio::io::split(bob);
+        let bob_handle2 = bob_handle.clone();
+        let bob_task = tokio::task::spawn(async move {
+            let mut state = BobState::new(alice_peer_id, ProtocolVersion::V1);
+            state
+                .run(
+                    &mut bob_writer,
+                    &mut bob_reader,
+                    bob_handle2,
+                    |_namespace, _peer| std::future::ready(AcceptOutcome::Allow),
+                )
+                .await
+        });
+        let res = open_session(&mut alice_writer, &mut alice_reader, &[namespace_id]).await;
+        assert!(res.is_err());
+        assert!(bob_task.await?.is_err());
+
+        bob_handle.shutdown().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sync_area_of_interest() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let alice_peer_id = SecretKey::from_bytes(&[1u8; 32]).public();
+        let bob_peer_id = SecretKey::from_bytes(&[2u8; 32]).public();
+        let namespace = NamespaceSecret::new(&mut rng);
+
+        let mut alice_store = store::Store::memory();
+        let author = alice_store.new_author(&mut rng)?;
+        let mut alice_replica = alice_store.new_replica(namespace.clone())?;
+        alice_replica.hash_and_insert("shared/x", &author, "x")?;
+        alice_replica.hash_and_insert("private/y", &author, "y")?;
+        alice_store.close_replica(namespace.id());
+        let alice_area = AreaOfInterest::prefixes(["shared/", "private/"]);
+        alice_store.set_sync_interest(&namespace.id(), &alice_area)?;
+
+        let mut bob_store = store::Store::memory();
+        let mut bob_replica = bob_store.new_replica(namespace.clone())?;
+        bob_replica.hash_and_insert("shared/z", &author, "z")?;
+        bob_replica.hash_and_insert("other/w", &author, "w")?;
+        bob_store.close_replica(namespace.id());
+        let bob_area = AreaOfInterest::prefixes(["shared/", "other/"]);
+        bob_store.set_sync_interest(&namespace.id(), &bob_area)?;
+
+        let (alice, bob) = tokio::io::duplex(64);
+
+        let (mut alice_reader, mut alice_writer) = tokio::io::split(alice);
+        let alice_handle = SyncHandle::spawn(alice_store, None, "alice".to_string());
+        alice_handle
+            .open(namespace.id(), OpenOpts::default().sync())
+            .await?;
+        let namespace_id = namespace.id();
+        let alice_handle2 = alice_handle.clone();
+        let alice_task = tokio::task::spawn(async move {
+            run_alice(
+                &mut alice_writer,
+                &mut alice_reader,
+                &alice_handle2,
+                namespace_id,
+                bob_peer_id,
+                ProtocolVersion::V2,
+            )
+            .await
+        });
+
+        let (mut bob_reader, mut bob_writer) = tokio::io::split(bob);
+        let bob_handle = SyncHandle::spawn(bob_store, None, "bob".to_string());
+        bob_handle
+            .open(namespace.id(), OpenOpts::default().sync())
+            .await?;
+        let bob_handle2 = bob_handle.clone();
+        let bob_task = tokio::task::spawn(async move {
+            run_bob(
+                &mut bob_writer,
+                &mut bob_reader,
+                bob_handle2,
+                |_namespace, _peer| std::future::ready(AcceptOutcome::Allow),
+                alice_peer_id,
+            )
+            .await
+        });
+
+        let alice_outcome = alice_task.await??;
+        let (_, bob_outcome) = bob_task.await??;
+        assert_eq!(alice_outcome.num_sent, 1);
+        assert_eq!(bob_outcome.num_sent, 1);
+
+        let mut alice_store = alice_handle.shutdown().await?;
+        let mut bob_store = bob_handle.shutdown().await?;
+
+        let keys = |store: &mut Store| -> Result<Vec<Vec<u8>>> {
+            store
+                .get_many(namespace.id(), Query::all())?
+                .map(|entry| entry.map(|entry| entry.key().to_vec()))
+                .collect()
+        };
+        assert_eq!(
+            keys(&mut alice_store)?,
+            vec![
+                b"private/y".to_vec(),
+                b"shared/x".to_vec(),
+                b"shared/z".to_vec()
+            ]
+        );
+        assert_eq!(
+            keys(&mut bob_store)?,
+            vec![
+                b"other/w".to_vec(),
+                b"shared/x".to_vec(),
+                b"shared/z".to_vec()
+            ]
+        );
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_sync_many_authors_memory() -> Result<()> {
         let _guard = iroh_test::logging::setup();
@@ -590,6 +1398,7 @@ mod tests {
                 &alice_handle,
                 namespace,
                 bob_node_pubkey,
+                ProtocolVersion::V2,
             )
             .await
         });
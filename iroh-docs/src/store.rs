@@ -1,11 +1,15 @@
 //! Storage trait and implementation for iroh-docs documents
-use std::num::NonZeroUsize;
+use std::{
+    num::NonZeroUsize,
+    ops::{Bound, RangeBounds},
+    time::Duration,
+};
 
 use anyhow::Result;
 use bytes::Bytes;
 use serde::{Deserialize, Serialize};
 
-use crate::{AuthorId, Entry, NamespaceId};
+use crate::{AuthorId, Entry, NamespaceId, PeerIdBytes};
 
 pub mod fs;
 mod pubkeys;
@@ -20,6 +24,11 @@ pub(crate) const PEERS_PER_DOC_CACHE_SIZE: NonZeroUsize = match NonZeroUsize::ne
     None => panic!("this is clearly non zero"),
 };
 
+/// Maximum length of values that are sent inline in sync messages.
+///
+/// See [`Store::set_inline_limit`].
+pub const MAX_INLINE_LIMIT: u64 = 16 * 1024;
+
 /// Error return from [`Store::open_replica`]
 #[derive(Debug, thiserror::Error)]
 pub enum OpenError {
@@ -162,6 +171,253 @@ impl DownloadPolicy {
     }
 }
 
+/// Policy deciding whether superseded entries of a document are kept.
+///
+/// With history enabled, entries which are replaced by a newer entry for the same key, or which
+/// are removed by a prefix deletion, are moved to a history table instead of being discarded.
+/// They can then be queried with [`QueryBuilder::as_of`] and [`QueryBuilder::include_history`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum HistoryPolicy {
+    /// Superseded entries are discarded.
+    #[default]
+    Disabled,
+    /// Superseded entries are kept, within the bounds of the retention policy.
+    Enabled(HistoryRetention),
+}
+
+impl HistoryPolicy {
+    /// Whether superseded entries are kept.
+    pub fn is_enabled(&self) -> bool {
+        matches!(self, Self::Enabled(_))
+    }
+}
+
+/// Bounds for the growth of the history of a document.
+///
+/// Superseded entries are pruned once any of the limits is exceeded.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HistoryRetention {
+    /// Maximum number of superseded versions kept per author and key.
+    pub max_versions: Option<u64>,
+    /// Maximum age of superseded versions, measured from the timestamp of the entry.
+    pub max_age: Option<Duration>,
+}
+
+/// How long the tombstones of a document are kept.
+///
+/// Deleting entries inserts an empty entry, a tombstone, which keeps the deleted entries from
+/// reappearing when syncing with peers that still have them. With [`TombstonePolicy::Expire`],
+/// tombstones older than the horizon are removed when the document is compacted.
+///
+/// Compaction raises the compaction cutoff of the document, which is exchanged when syncing. A
+/// peer whose cutoff is older, for example because it has been offline for longer than the
+/// horizon, removes all its entries older than the newer cutoff and fetches them again from the
+/// other peer, so that entries deleted by a removed tombstone do not reappear. Entries older than
+/// the cutoff which only such a peer knows are thus lost.
+///
+/// A peer only adopts a newer cutoff if its own policy for the document expires tombstones, and
+/// the cutoff is not newer than its own horizon allows. Otherwise the peers do not sync the
+/// document, so all peers of a document should use the same policy.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum TombstonePolicy {
+    /// Tombstones are kept forever.
+    #[default]
+    Keep,
+    /// Tombstones are removed once they are older than the horizon.
+    Expire {
+        /// Minimum age of tombstones before they are removed.
+        horizon: Duration,
+    },
+}
+
+/// How the live sync of a document schedules syncs with its peers.
+///
+/// A document is synced with a peer when the peer joins the gossip swarm of the document, or
+/// reports news for us. Entries broadcast while a peer is unreachable are only picked up by the
+/// next sync, so replicas drift apart if gossip messages are missed. A periodic resync with the
+/// known peers of the document bounds this drift.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SyncPolicy {
+    /// Interval of the full resync with all known peers of the document.
+    ///
+    /// The known peers are the peers we synced the document with before, and the preferred peers.
+    /// If `None`, the document is only synced when triggered by the gossip swarm.
+    pub resync_interval: Option<Duration>,
+    /// Maximum number of syncs of the document we initiate at the same time.
+    ///
+    /// Further syncs are queued until a running sync finishes. Syncs initiated by peers are not
+    /// limited. If `None`, the number of syncs is not limited. Must not be zero.
+    pub max_concurrent: Option<u32>,
+    /// Backoff for peers whose sync failed.
+    ///
+    /// If `None`, failed syncs are retried on the next trigger.
+    pub backoff: Option<SyncBackoff>,
+    /// Peers that are always included in the sync of the document, and synced before other peers.
+    pub preferred_peers: Vec<PeerIdBytes>,
+}
+
+impl SyncPolicy {
+    /// Whether `peer` is a preferred peer.
+    pub fn is_preferred(&self, peer: &PeerIdBytes) -> bool {
+        self.preferred_peers.contains(peer)
+    }
+}
+
+/// Exponential backoff for peers whose sync failed, see [`SyncPolicy::backoff`].
+///
+/// After a failed sync, a peer is not dialed again until the backoff elapsed, unless the sync is
+/// started explicitly. The backoff doubles with each consecutive failure, up to the maximum, and
+/// is reset by a successful sync.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SyncBackoff {
+    /// Backoff after the first failure.
+    pub initial: Duration,
+    /// Maximum backoff.
+    pub max: Duration,
+}
+
+impl SyncBackoff {
+    /// The backoff after `failures` consecutive failures.
+    pub fn delay(&self, failures: u32) -> Duration {
+        let factor = 1u32 << failures.saturating_sub(1).min(31);
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+}
+
+impl Default for SyncBackoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+/// How the timestamps of new local entries of a document are chosen.
+///
+/// Entries with the same key and author are resolved by last-writer-wins on their timestamps.
+/// With wall clock timestamps, writes from a device whose clock is behind lose against earlier
+/// writes, and entries from a device whose clock is ahead are rejected by its peers.
+///
+/// The clock mode only affects local writes: entries up to [`MAX_TIMESTAMP_FUTURE_SHIFT`] ahead
+/// of the local clock are accepted in every mode, so peers may use different modes.
+///
+/// [`MAX_TIMESTAMP_FUTURE_SHIFT`]: crate::MAX_TIMESTAMP_FUTURE_SHIFT
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ClockMode {
+    /// Entries are timestamped with the wall clock.
+    #[default]
+    WallClock,
+    /// Entries are timestamped with a hybrid logical clock.
+    ///
+    /// The timestamp of a new entry is the wall clock time, or just after the latest timestamp
+    /// observed in the document if that is later. Writes that happen after an entry was inserted
+    /// or received thus win against it, as long as that entry is not ahead of the local clock by
+    /// more than [`MAX_TIMESTAMP_FUTURE_SHIFT`], which all peers accept.
+    ///
+    /// [`MAX_TIMESTAMP_FUTURE_SHIFT`]: crate::MAX_TIMESTAMP_FUTURE_SHIFT
+    Hybrid,
+}
+
+/// Maximum length of the content of entries that are indexed by the secondary indexes of a
+/// document. Entries with larger content are not indexed.
+pub const MAX_INDEXED_CONTENT_LEN: u64 = 1024 * 1024;
+
+/// Maximum length of the values in a secondary index. Longer values are not indexed.
+pub const MAX_INDEX_VALUE_LEN: usize = 1024;
+
+/// A secondary index over the content of the entries of a document.
+///
+/// The index maps values extracted from the content of the entries whose key starts with
+/// `key_prefix` to these entries, and is queried with [`Query::index`]. Indexes are declared per
+/// document on each node, and are not synced with peers.
+///
+/// Entries are added to the index once their content is available on the node. Entries whose
+/// content is longer than [`MAX_INDEXED_CONTENT_LEN`] are not indexed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IndexSpec {
+    /// Only entries whose key starts with this prefix are indexed.
+    pub key_prefix: Bytes,
+    /// How the indexed values are extracted from the content of an entry.
+    pub field: FieldExtractor,
+}
+
+impl IndexSpec {
+    /// Create an index over the entries whose key starts with `key_prefix`.
+    pub fn new(key_prefix: impl AsRef<[u8]>, field: FieldExtractor) -> Self {
+        Self {
+            key_prefix: Bytes::copy_from_slice(key_prefix.as_ref()),
+            field,
+        }
+    }
+
+    /// Whether entries with `key` are indexed.
+    pub fn matches(&self, key: &[u8]) -> bool {
+        key.starts_with(&self.key_prefix)
+    }
+
+    /// Extract the indexed values from the content of an entry.
+    ///
+    /// Empty content, i.e. a deleted entry, has no indexed values.
+    pub fn extract(&self, content: &[u8]) -> Vec<Bytes> {
+        if content.is_empty() {
+            return Vec::new();
+        }
+        let mut values = self.field.extract(content);
+        values.retain(|value| value.len() <= MAX_INDEX_VALUE_LEN);
+        values.sort();
+        values.dedup();
+        values
+    }
+}
+
+/// Extracts the indexed values from the content of an entry, see [`IndexSpec`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum FieldExtractor {
+    /// The content itself is the indexed value.
+    #[default]
+    Content,
+    /// The content is a JSON document, and the indexed value is the value at the contained JSON
+    /// pointer, for example `/status`.
+    ///
+    /// Strings are indexed by their UTF-8 bytes, and other scalars by their JSON text. The
+    /// elements of an array are indexed individually. Objects, and content which is not valid
+    /// JSON, are not indexed. Requires the `json` feature.
+    Json(String),
+}
+
+impl FieldExtractor {
+    fn extract(&self, content: &[u8]) -> Vec<Bytes> {
+        match self {
+            Self::Content => vec![Bytes::copy_from_slice(content)],
+            #[cfg(feature = "json")]
+            Self::Json(pointer) => {
+                let Ok(document) = serde_json::from_slice::<serde_json::Value>(content) else {
+                    return Vec::new();
+                };
+                match document.pointer(pointer) {
+                    None => Vec::new(),
+                    Some(serde_json::Value::Array(items)) => {
+                        items.iter().filter_map(json_index_value).collect()
+                    }
+                    Some(value) => json_index_value(value).into_iter().collect(),
+                }
+            }
+            #[cfg(not(feature = "json"))]
+            Self::Json(_) => Vec::new(),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+fn json_index_value(value: &serde_json::Value) -> Option<Bytes> {
+    match value {
+        serde_json::Value::String(value) => Some(Bytes::copy_from_slice(value.as_bytes())),
+        serde_json::Value::Array(_) | serde_json::Value::Object(_) => None,
+        scalar => Some(scalar.to_string().into()),
+    }
+}
+
 /// A query builder for document queries.
 #[derive(Debug, Default)]
 pub struct QueryBuilder<K> {
@@ -172,6 +428,9 @@ pub struct QueryBuilder<K> {
     offset: u64,
     include_empty: bool,
     sort_direction: SortDirection,
+    as_of: Option<u64>,
+    include_history: bool,
+    cursor: Option<Cursor>,
 }
 
 impl<K> QueryBuilder<K> {
@@ -190,6 +449,17 @@ impl<K> QueryBuilder<K> {
         self.filter_key = KeyFilter::Prefix(key.as_ref().to_vec().into());
         self
     }
+    /// Filter by a range of keys.
+    ///
+    /// Keys are compared bytewise, for example `query.key_range("a".."b")` matches all keys
+    /// that start with `a`.
+    pub fn key_range<T: AsRef<[u8]>>(mut self, range: impl RangeBounds<T>) -> Self {
+        self.filter_key = KeyFilter::Range {
+            start: bytes_bound(range.start_bound()),
+            end: bytes_bound(range.end_bound()),
+        };
+        self
+    }
     /// Filter by author.
     pub fn author(mut self, author: AuthorId) -> Self {
         self.filter_author = AuthorFilter::Exact(author);
@@ -205,6 +475,23 @@ impl<K> QueryBuilder<K> {
         self.offset = offset;
         self
     }
+    /// Query the state of the document as it was at `timestamp`, in microseconds since the Unix
+    /// epoch.
+    ///
+    /// Entries superseded before `timestamp` are only found if the document has a
+    /// [`HistoryPolicy`] enabled, and have not been pruned by its retention policy yet.
+    pub fn as_of(mut self, timestamp: u64) -> Self {
+        self.as_of = Some(timestamp);
+        self
+    }
+    /// Only return the entries after `cursor`, in the sort order of the query.
+    ///
+    /// Unlike [`Self::offset`], continuing a query from a cursor does not need to skip over the
+    /// entries before it, and is not affected by entries inserted before it in the meantime.
+    pub fn after(mut self, cursor: Cursor) -> Self {
+        self.cursor = Some(cursor);
+        self
+    }
 }
 
 /// Query on all entries without aggregation.
@@ -227,6 +514,15 @@ impl QueryBuilder<FlatQuery> {
         self
     }
 
+    /// Include superseded versions of entries kept in the document history.
+    ///
+    /// Combined with [`Self::key_exact`], this returns all versions of a key. Versions of the
+    /// same author and key are ordered by timestamp.
+    pub fn include_history(mut self) -> Self {
+        self.include_history = true;
+        self
+    }
+
     /// Build the query.
     pub fn build(self) -> Query {
         Query::from(self)
@@ -249,6 +545,46 @@ impl QueryBuilder<SingleLatestPerKeyQuery> {
     }
 }
 
+/// Query on the entries with a value in a secondary index of the document.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IndexQuery {
+    name: String,
+    value: Bytes,
+}
+
+impl QueryBuilder<IndexQuery> {
+    /// Set the order direction for the query.
+    ///
+    /// Ordering is always by author, then key, for this query type.
+    /// Default direction is ascending.
+    pub fn sort_direction(mut self, direction: SortDirection) -> Self {
+        self.sort_direction = direction;
+        self
+    }
+
+    /// Build the query.
+    pub fn build(self) -> Query {
+        Query::from(self)
+    }
+}
+
+impl From<QueryBuilder<IndexQuery>> for Query {
+    fn from(builder: QueryBuilder<IndexQuery>) -> Query {
+        Query {
+            kind: QueryKind::Index(builder.kind),
+            filter_author: builder.filter_author,
+            filter_key: builder.filter_key,
+            limit: builder.limit,
+            offset: builder.offset,
+            include_empty: builder.include_empty,
+            sort_direction: builder.sort_direction,
+            as_of: builder.as_of,
+            include_history: builder.include_history,
+            cursor: builder.cursor,
+        }
+    }
+}
+
 impl From<QueryBuilder<SingleLatestPerKeyQuery>> for Query {
     fn from(builder: QueryBuilder<SingleLatestPerKeyQuery>) -> Query {
         Query {
@@ -259,6 +595,9 @@ impl From<QueryBuilder<SingleLatestPerKeyQuery>> for Query {
             offset: builder.offset,
             include_empty: builder.include_empty,
             sort_direction: builder.sort_direction,
+            as_of: builder.as_of,
+            include_history: builder.include_history,
+            cursor: builder.cursor,
         }
     }
 }
@@ -273,6 +612,9 @@ impl From<QueryBuilder<FlatQuery>> for Query {
             offset: builder.offset,
             include_empty: builder.include_empty,
             sort_direction: builder.sort_direction,
+            as_of: builder.as_of,
+            include_history: builder.include_history,
+            cursor: builder.cursor,
         }
     }
 }
@@ -283,11 +625,14 @@ impl From<QueryBuilder<FlatQuery>> for Query {
 pub struct Query {
     kind: QueryKind,
     filter_author: AuthorFilter,
-    filter_key: KeyFilter,
+    pub(crate) filter_key: KeyFilter,
     limit: Option<u64>,
     offset: u64,
     include_empty: bool,
     sort_direction: SortDirection,
+    as_of: Option<u64>,
+    include_history: bool,
+    cursor: Option<Cursor>,
 }
 
 impl Query {
@@ -316,6 +661,21 @@ impl Query {
         Self::all().key_prefix(prefix)
     }
 
+    /// Query the entries with `value` in the secondary index `name` of the document.
+    ///
+    /// The index has to be created with [`Store::create_index`] before. Entries are only found
+    /// once they are indexed, see [`IndexSpec`]. Queries on an index cannot be combined with
+    /// [`QueryBuilder::as_of`].
+    pub fn index(name: impl Into<String>, value: impl AsRef<[u8]>) -> QueryBuilder<IndexQuery> {
+        QueryBuilder {
+            kind: IndexQuery {
+                name: name.into(),
+                value: Bytes::copy_from_slice(value.as_ref()),
+            },
+            ..Default::default()
+        }
+    }
+
     /// Get the limit for this query (max. number of entries to emit).
     pub fn limit(&self) -> Option<u64> {
         self.limit
@@ -325,6 +685,71 @@ impl Query {
     pub fn offset(&self) -> u64 {
         self.offset
     }
+
+    /// Get the timestamp at which the document state is queried, if any.
+    pub fn as_of(&self) -> Option<u64> {
+        self.as_of
+    }
+
+    /// Whether superseded versions of entries are included.
+    pub fn include_history(&self) -> bool {
+        self.include_history
+    }
+
+    /// Get the cursor after which the query continues, if any.
+    pub fn cursor(&self) -> Option<&Cursor> {
+        self.cursor.as_ref()
+    }
+
+    /// Continue the query after `cursor`, see [`QueryBuilder::after`].
+    pub(crate) fn continue_after(mut self, cursor: Cursor) -> Self {
+        self.cursor = Some(cursor);
+        self
+    }
+}
+
+/// A position in the results of a query.
+///
+/// The cursor of the last entry of a page is passed to [`QueryBuilder::after`] to fetch the next
+/// page. The cursor is opaque, and can be stored with [`Self::to_bytes`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Cursor {
+    timestamp: u64,
+    author: AuthorId,
+    key: Bytes,
+}
+
+impl Cursor {
+    /// The position of `entry`.
+    pub fn from_entry(entry: &Entry) -> Self {
+        Self {
+            timestamp: entry.timestamp(),
+            author: entry.author(),
+            key: Bytes::copy_from_slice(entry.key()),
+        }
+    }
+
+    /// Serialize the cursor to bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        postcard::to_stdvec(self).expect("cursor is serializable")
+    }
+
+    /// Deserialize a cursor from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Ok(postcard::from_bytes(bytes)?)
+    }
+
+    pub(crate) fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    pub(crate) fn author(&self) -> AuthorId {
+        self.author
+    }
+
+    pub(crate) fn key(&self) -> &Bytes {
+        &self.key
+    }
 }
 
 /// Sort direction
@@ -343,6 +768,8 @@ enum QueryKind {
     Flat(FlatQuery),
     #[debug("SingleLatestPerKey")]
     SingleLatestPerKey(SingleLatestPerKeyQuery),
+    #[debug("Index {{ name: {:?} }}", _0.name)]
+    Index(IndexQuery),
 }
 
 /// Fields by which the query can be sorted
@@ -353,6 +780,10 @@ pub enum SortBy {
     /// Sort by author, then key.
     #[default]
     AuthorKey,
+    /// Sort by timestamp, then author, then key.
+    ///
+    /// There is no index by timestamp, so each query scans all entries that match its filters.
+    Timestamp,
 }
 
 /// Key matching.
@@ -365,6 +796,13 @@ pub enum KeyFilter {
     Exact(Bytes),
     /// All keys that start with the provided value.
     Prefix(Bytes),
+    /// All keys within the range.
+    Range {
+        /// The start of the range.
+        start: Bound<Bytes>,
+        /// The end of the range.
+        end: Bound<Bytes>,
+    },
 }
 
 impl<T: AsRef<[u8]>> From<T> for KeyFilter {
@@ -380,10 +818,31 @@ impl KeyFilter {
             Self::Any => true,
             Self::Exact(k) => &k[..] == key,
             Self::Prefix(p) => key.starts_with(p),
+            Self::Range { start, end } => {
+                let start = match start {
+                    Bound::Included(start) => key >= &start[..],
+                    Bound::Excluded(start) => key > &start[..],
+                    Bound::Unbounded => true,
+                };
+                let end = match end {
+                    Bound::Included(end) => key <= &end[..],
+                    Bound::Excluded(end) => key < &end[..],
+                    Bound::Unbounded => true,
+                };
+                start && end
+            }
         }
     }
 }
 
+fn bytes_bound<T: AsRef<[u8]>>(bound: Bound<&T>) -> Bound<Bytes> {
+    match bound {
+        Bound::Included(value) => Bound::Included(Bytes::copy_from_slice(value.as_ref())),
+        Bound::Excluded(value) => Bound::Excluded(Bytes::copy_from_slice(value.as_ref())),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
 /// Author matching.
 #[derive(Debug, Serialize, Deserialize, Clone, Default, Eq, PartialEq)]
 pub enum AuthorFilter {
@@ -424,4 +883,17 @@ mod tests {
         );
         assert_eq!(filter.to_string(), REPR)
     }
+
+    #[test]
+    fn test_sync_backoff() {
+        let backoff = SyncBackoff {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(10),
+        };
+        assert_eq!(backoff.delay(1), Duration::from_secs(1));
+        assert_eq!(backoff.delay(2), Duration::from_secs(2));
+        assert_eq!(backoff.delay(4), Duration::from_secs(8));
+        assert_eq!(backoff.delay(5), Duration::from_secs(10));
+        assert_eq!(backoff.delay(u32::MAX), Duration::from_secs(10));
+    }
 }
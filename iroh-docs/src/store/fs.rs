@@ -2,7 +2,7 @@
 
 use std::{
     cmp::Ordering,
-    collections::HashSet,
+    collections::{BTreeSet, HashSet},
     iter::{Chain, Flatten},
     num::NonZeroU64,
     ops::Bound,
@@ -10,23 +10,32 @@ use std::{
 };
 
 use anyhow::{anyhow, Result};
+use bytes::Bytes;
 use ed25519_dalek::{SignatureError, VerifyingKey};
 use iroh_base::hash::Hash;
 use rand_core::CryptoRngCore;
-use redb::{Database, DatabaseError, ReadableMultimapTable, ReadableTable, ReadableTableMetadata};
+use redb::{
+    Database, DatabaseError, ReadableMultimapTable, ReadableTable, ReadableTableMetadata, Table,
+};
 
+use crate::keystore::{PassphraseKey, SecretKind, SALT_LEN};
 use crate::{
     actor::MAX_COMMIT_DELAY,
     keys::Author,
     ranger::{Fingerprint, Range, RangeEntry},
-    sync::{Entry, EntrySignature, Record, RecordIdentifier, Replica, SignedEntry},
-    AuthorHeads, AuthorId, Capability, CapabilityKind, NamespaceId, NamespaceSecret, PeerIdBytes,
-    ReplicaInfo,
+    sync::{
+        system_time_now, Entry, EntrySignature, Record, RecordIdentifier, Replica, SignedEntry,
+        MAX_TIMESTAMP_FUTURE_SHIFT,
+    },
+    AccessPolicy, AreaOfInterest, AuthorHeads, AuthorId, Capability, CapabilityKind,
+    DocEncryptionKey, Invite, InviteError, InviteId, KeystoreError, KeystoreStatus, NamespaceId,
+    NamespaceSecret, PeerIdBytes, ReadToken, Redemption, ReplicaInfo, WriteDelegation,
 };
 
 use super::{
-    pubkeys::MemPublicKeyStore, DownloadPolicy, ImportNamespaceOutcome, OpenError, PublicKeyStore,
-    Query,
+    pubkeys::MemPublicKeyStore, ClockMode, DownloadPolicy, HistoryPolicy, HistoryRetention,
+    ImportNamespaceOutcome, IndexSpec, OpenError, PublicKeyStore, Query, SyncPolicy,
+    TombstonePolicy, MAX_INDEXED_CONTENT_LEN, MAX_INLINE_LIMIT,
 };
 
 mod bounds;
@@ -37,14 +46,15 @@ mod ranges;
 pub(crate) mod tables;
 
 use self::{
-    bounds::{ByKeyBounds, RecordsBounds},
+    bounds::{ByKeyBounds, ByTimestampBounds, HistoryBounds, RecordsBounds},
     ranges::RangeExt,
-    tables::{RecordsTable, TransactionAndTables},
+    tables::{HistoryIdOwned, SealedSecretsKey, TransactionAndTables},
 };
 use self::{
     query::QueryIterator,
     tables::{
-        LatestPerAuthorKey, LatestPerAuthorValue, ReadOnlyTables, RecordsId, RecordsValue, Tables,
+        HistoryId, HistoryValue, LatestPerAuthorKey, LatestPerAuthorValue, ReadOnlyTables,
+        RecordsId, RecordsValue, Tables,
     },
 };
 
@@ -55,8 +65,11 @@ pub use self::ranges::RecordsRange;
 pub struct Store {
     db: Database,
     transaction: CurrentTransaction,
+    /// Whether a batch is in progress, during which the write transaction is not committed.
+    in_batch: bool,
     open_replicas: HashSet<NamespaceId>,
     pubkeys: MemPublicKeyStore,
+    keystore: Keystore,
 }
 
 impl AsRef<Store> for Store {
@@ -79,6 +92,124 @@ enum CurrentTransaction {
     Write(TransactionAndTables),
 }
 
+/// The passphrase protection of the secrets in a [`Store`].
+#[derive(Debug, Clone)]
+enum Keystore {
+    Disabled,
+    Locked,
+    Unlocked(PassphraseKey),
+}
+
+impl Keystore {
+    fn status(&self) -> KeystoreStatus {
+        match self {
+            Self::Disabled => KeystoreStatus::Disabled,
+            Self::Locked => KeystoreStatus::Locked,
+            Self::Unlocked(_) => KeystoreStatus::Unlocked,
+        }
+    }
+
+    /// Prepare a secret for storage.
+    fn seal(
+        &self,
+        kind: SecretKind,
+        id: &[u8; 32],
+        secret: &[u8; 32],
+    ) -> Result<SealedSecret, KeystoreError> {
+        let (slot, sealed) = match self {
+            Self::Disabled => (*secret, None),
+            Self::Locked => return Err(KeystoreError::Locked),
+            Self::Unlocked(key) => ([0u8; 32], Some(key.seal(kind, id, secret))),
+        };
+        Ok(SealedSecret {
+            kind,
+            id: *id,
+            slot,
+            sealed,
+        })
+    }
+
+    /// Recover a secret from storage, given the value of its slot.
+    fn open(
+        &self,
+        sealed_secrets: &impl ReadableTable<SealedSecretsKey<'static>, &'static [u8]>,
+        kind: SecretKind,
+        id: &[u8; 32],
+        slot: &[u8; 32],
+    ) -> Result<[u8; 32]> {
+        match self {
+            Self::Disabled => Ok(*slot),
+            Self::Locked => Err(KeystoreError::Locked.into()),
+            Self::Unlocked(key) => {
+                let sealed = sealed_secrets
+                    .get((kind as u8, id))?
+                    .ok_or(KeystoreError::Corrupt)?;
+                Ok(key.open(kind, id, sealed.value())?)
+            }
+        }
+    }
+
+    fn seal_capability(
+        &self,
+        capability: &Capability,
+    ) -> Result<(u8, SealedSecret), KeystoreError> {
+        let (kind, bytes) = capability.raw();
+        let id = capability.id().to_bytes();
+        let sealed = match capability {
+            Capability::Write(_) => self.seal(SecretKind::Namespace, &id, &bytes)?,
+            Capability::Read(_) => SealedSecret {
+                kind: SecretKind::Namespace,
+                id,
+                slot: bytes,
+                sealed: None,
+            },
+        };
+        Ok((kind, sealed))
+    }
+
+    fn open_capability(
+        &self,
+        sealed_secrets: &impl ReadableTable<SealedSecretsKey<'static>, &'static [u8]>,
+        id: &NamespaceId,
+        (kind, bytes): (u8, [u8; 32]),
+    ) -> Result<Capability> {
+        let bytes = if kind == u8::from(CapabilityKind::Write) {
+            self.open(sealed_secrets, SecretKind::Namespace, id.as_bytes(), &bytes)?
+        } else {
+            bytes
+        };
+        Capability::from_raw(kind, &bytes)
+    }
+}
+
+/// A secret prepared for storage by [`Keystore::seal`].
+///
+/// While the keystore is enabled, the slot of the secret in its table is zeroed and the
+/// encrypted secret is stored in the sealed secrets table.
+struct SealedSecret {
+    kind: SecretKind,
+    id: [u8; 32],
+    /// The value for the slot of the secret in its table.
+    slot: [u8; 32],
+    /// The encrypted secret, if the keystore is enabled.
+    sealed: Option<Vec<u8>>,
+}
+
+impl SealedSecret {
+    /// Update the sealed secrets table for this secret.
+    fn write(
+        &self,
+        sealed_secrets: &mut Table<SealedSecretsKey<'static>, &'static [u8]>,
+    ) -> Result<()> {
+        let key = (self.kind as u8, &self.id);
+        match &self.sealed {
+            Some(sealed) => sealed_secrets.insert(key, sealed.as_slice())?,
+            None => sealed_secrets.remove(key)?,
+        };
+        Ok(())
+    }
+}
+
 impl Store {
     /// Create a new store in memory.
     pub fn memory() -> Self {
@@ -111,11 +242,22 @@ impl Store {
         // Run database migrations
         migrations::run_migrations(&db)?;
 
+        let keystore = {
+            let read_tx = db.begin_read()?;
+            let table = read_tx.open_table(tables::KEYSTORE_TABLE)?;
+            match table.get("salt")? {
+                Some(_) => Keystore::Locked,
+                None => Keystore::Disabled,
+            }
+        };
+
         Ok(Store {
             db,
             transaction: Default::default(),
+            in_batch: false,
             open_replicas: Default::default(),
             pubkeys: Default::default(),
+            keystore,
         })
     }
 
@@ -129,6 +271,32 @@ impl Store {
         Ok(())
     }
 
+    /// Start a batch of changes that are committed or rolled back together.
+    ///
+    /// This commits the current transaction, if any. Until [`Self::commit_batch`] or
+    /// [`Self::abort_batch`] is called, all changes are made in a single write transaction that
+    /// is not committed automatically.
+    pub(crate) fn begin_batch(&mut self) -> Result<()> {
+        self.flush()?;
+        self.in_batch = true;
+        Ok(())
+    }
+
+    /// Commit the changes made since [`Self::begin_batch`].
+    pub(crate) fn commit_batch(&mut self) -> Result<()> {
+        self.in_batch = false;
+        self.flush()
+    }
+
+    /// Roll back the changes made since [`Self::begin_batch`].
+    pub(crate) fn abort_batch(&mut self) -> Result<()> {
+        self.in_batch = false;
+        if let CurrentTransaction::Write(w) = std::mem::take(&mut self.transaction) {
+            w.abort()?;
+        }
+        Ok(())
+    }
+
     /// Get a read-only snapshot of the database.
     ///
     /// This has the side effect of committing any open write transaction,
@@ -181,6 +349,7 @@ impl Store {
     /// As such, there is also no guarantee that the data you see is
     /// already persisted.
     fn tables(&mut self) -> Result<&Tables> {
+        let in_batch = self.in_batch;
         let guard = &mut self.transaction;
         let tables = match std::mem::take(guard) {
             CurrentTransaction::None => {
@@ -188,7 +357,7 @@ impl Store {
                 TransactionAndTables::new(tx)?
             }
             CurrentTransaction::Write(w) => {
-                if w.since.elapsed() > MAX_COMMIT_DELAY {
+                if w.since.elapsed() > MAX_COMMIT_DELAY && !in_batch {
                     tracing::debug!("committing transaction because it's too old");
                     w.commit()?;
                     let tx = self.db.begin_write()?;
@@ -218,6 +387,7 @@ impl Store {
     /// To ensure that the data is persisted, acquire a snapshot of the database
     /// or call flush.
     fn modify<T>(&mut self, f: impl FnOnce(&mut Tables) -> Result<T>) -> Result<T> {
+        let in_batch = self.in_batch;
         let guard = &mut self.transaction;
         let tables = match std::mem::take(guard) {
             CurrentTransaction::None => {
@@ -225,7 +395,7 @@ impl Store {
                 TransactionAndTables::new(tx)?
             }
             CurrentTransaction::Write(w) => {
-                if w.since.elapsed() > MAX_COMMIT_DELAY {
+                if w.since.elapsed() > MAX_COMMIT_DELAY && !in_batch {
                     tracing::debug!("committing transaction because it's too old");
                     w.commit()?;
                     let tx = self.db.begin_write()?;
@@ -300,20 +470,51 @@ impl Store {
         &mut self,
         namespace_id: &NamespaceId,
     ) -> Result<ReplicaInfo, OpenError> {
-        let tables = self.tables()?;
-        let info = match tables.namespaces.get(namespace_id.as_bytes()) {
-            Ok(Some(db_value)) => {
-                let (raw_kind, raw_bytes) = db_value.value();
-                let namespace = Capability::from_raw(raw_kind, raw_bytes)?;
-                ReplicaInfo::new(namespace)
+        let Some(raw) = self.get_raw_capability(namespace_id)? else {
+            return Err(OpenError::NotFound);
+        };
+        let keystore = self.keystore.clone();
+        let capability = match keystore {
+            // While the keystore is locked, writable replicas are opened read-only. The actor
+            // upgrades them again once the keystore is unlocked.
+            Keystore::Locked if raw.0 == u8::from(CapabilityKind::Write) => {
+                Capability::Read(*namespace_id)
+            }
+            _ => {
+                let tables = self.tables()?;
+                keystore.open_capability(&tables.sealed_secrets, namespace_id, raw)?
             }
-            Ok(None) => return Err(OpenError::NotFound),
-            Err(err) => return Err(OpenError::Other(err.into())),
         };
+        let info = ReplicaInfo::new(capability);
         self.open_replicas.insert(info.capability.id());
         Ok(info)
     }
 
+    /// Get the capability for a namespace.
+    ///
+    /// Fails for writable namespaces while the keystore is locked.
+    pub fn get_capability(&mut self, namespace_id: &NamespaceId) -> Result<Option<Capability>> {
+        let Some(raw) = self.get_raw_capability(namespace_id)? else {
+            return Ok(None);
+        };
+        let keystore = self.keystore.clone();
+        let tables = self.tables()?;
+        let capability = keystore.open_capability(&tables.sealed_secrets, namespace_id, raw)?;
+        Ok(Some(capability))
+    }
+
+    fn get_raw_capability(&mut self, namespace_id: &NamespaceId) -> Result<Option<(u8, [u8; 32])>> {
+        let tables = self.tables()?;
+        let raw = tables
+            .namespaces
+            .get(namespace_id.as_bytes())?
+            .map(|value| {
+                let (kind, bytes) = value.value();
+                (kind, *bytes)
+            });
+        Ok(raw)
+    }
+
     /// Close a replica.
     pub fn close_replica(&mut self, id: NamespaceId) {
         self.open_replicas.remove(&id);
@@ -326,28 +527,51 @@ impl Store {
         let snapshot = self.snapshot()?;
         let iter = snapshot.namespaces.range::<&'static [u8; 32]>(..)?;
         let iter = iter.map(|res| {
-            let capability = parse_capability(res?.1.value())?;
-            Ok((capability.id(), capability.kind()))
+            let (key, value) = res?;
+            let kind = CapabilityKind::try_from(value.value().0)?;
+            Ok((NamespaceId::from(key.value()), kind))
         });
         Ok(iter)
     }
 
     /// Get an author key from the store.
     pub fn get_author(&mut self, author_id: &AuthorId) -> Result<Option<Author>> {
+        let keystore = self.keystore.clone();
         let tables = self.tables()?;
-        let Some(author) = tables.authors.get(author_id.as_bytes())? else {
-            return Ok(None);
+        let stored = match tables.authors.get(author_id.as_bytes())? {
+            Some(author) => *author.value(),
+            None => return Ok(None),
         };
-        let author = Author::from_bytes(author.value());
+        let secret = keystore.open(
+            &tables.sealed_secrets,
+            SecretKind::Author,
+            author_id.as_bytes(),
+            &stored,
+        )?;
+        let author = Author::from_bytes(&secret);
+        if author.id() != *author_id {
+            return Err(KeystoreError::IdMismatch.into());
+        }
         Ok(Some(author))
     }
 
+    /// Check if the secret key of an author is in the store.
+    ///
+    /// Unlike [`Self::get_author`], this also works while the keystore is locked.
+    pub fn has_author(&mut self, author_id: &AuthorId) -> Result<bool> {
+        let tables = self.tables()?;
+        Ok(tables.authors.get(author_id.as_bytes())?.is_some())
+    }
+
     /// Import an author key pair.
     pub fn import_author(&mut self, author: Author) -> Result<()> {
+        let id = author.id();
+        let sealed = self
+            .keystore
+            .seal(SecretKind::Author, id.as_bytes(), &author.to_bytes())?;
         self.modify(|tables| {
-            tables
-                .authors
-                .insert(author.id().as_bytes(), &author.to_bytes())?;
+            tables.authors.insert(id.as_bytes(), &sealed.slot)?;
+            sealed.write(&mut tables.sealed_secrets)?;
             Ok(())
         })
     }
@@ -356,46 +580,137 @@ impl Store {
     pub fn delete_author(&mut self, author: AuthorId) -> Result<()> {
         self.modify(|tables| {
             tables.authors.remove(author.as_bytes())?;
+            tables
+                .sealed_secrets
+                .remove((SecretKind::Author as u8, author.as_bytes()))?;
             Ok(())
         })
     }
 
     /// List all author keys in this store.
     pub fn list_authors(&mut self) -> Result<impl Iterator<Item = Result<Author>>> {
+        let keystore = self.keystore.clone();
+        let tables = self.snapshot_owned()?;
+        let iter = tables
+            .authors
+            .range::<&'static [u8; 32]>(..)?
+            .map(move |res| {
+                let (key, value) = res?;
+                let secret = keystore.open(
+                    &tables.sealed_secrets,
+                    SecretKind::Author,
+                    key.value(),
+                    value.value(),
+                )?;
+                Ok(Author::from_bytes(&secret))
+            });
+        Ok(iter)
+    }
+
+    /// List the ids of all authors in this store.
+    ///
+    /// Unlike [`Self::list_authors`], this also works while the keystore is locked.
+    pub fn list_author_ids(&mut self) -> Result<impl Iterator<Item = Result<AuthorId>>> {
         let tables = self.snapshot()?;
         let iter = tables
             .authors
             .range::<&'static [u8; 32]>(..)?
             .map(|res| match res {
-                Ok((_key, value)) => Ok(Author::from_bytes(value.value())),
+                Ok((key, _value)) => Ok(AuthorId::from(key.value())),
                 Err(err) => Err(err.into()),
             });
         Ok(iter)
     }
 
+    /// Get the status of the keystore.
+    pub fn keystore_status(&self) -> KeystoreStatus {
+        self.keystore.status()
+    }
+
+    /// Enable the keystore.
+    ///
+    /// Encrypts the secrets of all authors and namespaces, and the document encryption keys, with
+    /// a key derived from `passphrase`. The store remains unlocked until [`Self::lock_keystore`]
+    /// is called or the store is reopened.
+    pub fn enable_keystore(&mut self, passphrase: &str) -> Result<()> {
+        if !matches!(self.keystore, Keystore::Disabled) {
+            return Err(KeystoreError::Enabled.into());
+        }
+        let salt: [u8; SALT_LEN] = rand::random();
+        let key = PassphraseKey::derive(passphrase, &salt)?;
+        let keystore = Keystore::Unlocked(key.clone());
+        self.modify(|tables| {
+            rewrite_secrets(tables, &Keystore::Disabled, &keystore)?;
+            tables.keystore.insert("salt", &salt[..])?;
+            tables.keystore.insert("check", &key.check()[..])?;
+            Ok(())
+        })?;
+        self.keystore = keystore;
+        Ok(())
+    }
+
+    /// Disable the keystore, storing all secrets in cleartext again.
+    pub fn disable_keystore(&mut self, passphrase: &str) -> Result<()> {
+        let keystore = Keystore::Unlocked(self.verify_passphrase(passphrase)?);
+        self.modify(|tables| {
+            rewrite_secrets(tables, &keystore, &Keystore::Disabled)?;
+            tables.keystore.remove("salt")?;
+            tables.keystore.remove("check")?;
+            Ok(())
+        })?;
+        self.keystore = Keystore::Disabled;
+        Ok(())
+    }
+
+    /// Unlock the keystore, so that the stored secrets can be used.
+    pub fn unlock_keystore(&mut self, passphrase: &str) -> Result<()> {
+        let key = self.verify_passphrase(passphrase)?;
+        self.keystore = Keystore::Unlocked(key);
+        Ok(())
+    }
+
+    /// Lock the keystore, forgetting the key derived from the passphrase.
+    pub fn lock_keystore(&mut self) -> Result<()> {
+        if matches!(self.keystore, Keystore::Disabled) {
+            return Err(KeystoreError::Disabled.into());
+        }
+        self.keystore = Keystore::Locked;
+        Ok(())
+    }
+
+    fn verify_passphrase(&mut self, passphrase: &str) -> Result<PassphraseKey> {
+        let tables = self.tables()?;
+        let (Some(salt), Some(check)) =
+            (tables.keystore.get("salt")?, tables.keystore.get("check")?)
+        else {
+            return Err(KeystoreError::Disabled.into());
+        };
+        let key = PassphraseKey::derive(passphrase, salt.value())?;
+        if check.value() != key.check() {
+            return Err(KeystoreError::WrongPassphrase.into());
+        }
+        Ok(key)
+    }
+
     /// Import a new replica namespace.
     pub fn import_namespace(&mut self, capability: Capability) -> Result<ImportNamespaceOutcome> {
+        let id = capability.id().to_bytes();
+        // Only needed if the capability is stored, so that importing a read capability for a
+        // writable namespace works while the keystore is locked.
+        let sealed = self.keystore.seal_capability(&capability);
         self.modify(|tables| {
-            let outcome = {
-                let (capability, outcome) = {
-                    let existing = tables.namespaces.get(capability.id().as_bytes())?;
-                    if let Some(existing) = existing {
-                        let mut existing = parse_capability(existing.value())?;
-                        let outcome = if existing.merge(capability)? {
-                            ImportNamespaceOutcome::Upgraded
-                        } else {
-                            ImportNamespaceOutcome::NoChange
-                        };
-                        (existing, outcome)
-                    } else {
-                        (capability, ImportNamespaceOutcome::Inserted)
-                    }
-                };
-                let id = capability.id().to_bytes();
-                let (kind, bytes) = capability.raw();
-                tables.namespaces.insert(&id, (kind, &bytes))?;
-                outcome
+            let existing = tables.namespaces.get(&id)?.map(|value| value.value().0);
+            let outcome = match existing {
+                None => ImportNamespaceOutcome::Inserted,
+                Some(kind) if kind == u8::from(CapabilityKind::Read) => match capability {
+                    Capability::Write(_) => ImportNamespaceOutcome::Upgraded,
+                    Capability::Read(_) => return Ok(ImportNamespaceOutcome::NoChange),
+                },
+                Some(_) => return Ok(ImportNamespaceOutcome::NoChange),
             };
+            let (kind, sealed) = sealed?;
+            tables.namespaces.insert(&id, (kind, &sealed.slot))?;
+            sealed.write(&mut tables.sealed_secrets)?;
             Ok(outcome)
         })
     }
@@ -414,13 +729,49 @@ impl Store {
         self.modify(|tables| {
             let bounds = RecordsBounds::namespace(*namespace);
             tables.records.retain_in(bounds.as_ref(), |_k, _v| false)?;
+            tables
+                .inline_content
+                .retain_in(bounds.as_ref(), |_k, _v| false)?;
             let bounds = ByKeyBounds::namespace(*namespace);
             let _ = tables
                 .records_by_key
                 .retain_in(bounds.as_ref(), |_k, _v| false);
+            let bounds = ByTimestampBounds::namespace(*namespace);
+            tables
+                .records_by_timestamp
+                .retain_in(bounds.as_ref(), |_k, _v| false)?;
             tables.namespaces.remove(namespace.as_bytes())?;
             tables.namespace_peers.remove_all(namespace.as_bytes())?;
             tables.download_policy.remove(namespace.as_bytes())?;
+            tables.access_policy.remove(namespace.as_bytes())?;
+            tables.read_tokens.remove(namespace.as_bytes())?;
+            tables.invites.remove(namespace.as_bytes())?;
+            tables.redemptions.retain_in(
+                (namespace.as_bytes(), &[0u8; 16], &[0u8; 32])
+                    ..=(namespace.as_bytes(), &[255u8; 16], &[255u8; 32]),
+                |_k, _v| false,
+            )?;
+            tables.encryption_keys.remove(namespace.as_bytes())?;
+            for kind in [SecretKind::Namespace, SecretKind::EncryptionKey] {
+                tables
+                    .sealed_secrets
+                    .remove((kind as u8, namespace.as_bytes()))?;
+            }
+            tables.history_policy.remove(namespace.as_bytes())?;
+            tables.sync_interest.remove(namespace.as_bytes())?;
+            tables.clock_mode.remove(namespace.as_bytes())?;
+            tables.tombstone_policy.remove(namespace.as_bytes())?;
+            tables.sync_policy.remove(namespace.as_bytes())?;
+            tables.compaction_cutoff.remove(namespace.as_bytes())?;
+            tables.inline_limit.remove(namespace.as_bytes())?;
+            tables.marker_trust.remove(namespace.as_bytes())?;
+            clear_indexes(tables, namespace.as_bytes())?;
+            let bounds = HistoryBounds::namespace(*namespace);
+            tables.history.retain_in(bounds.as_ref(), |_k, _v| false)?;
+            tables.delegations.retain_in(
+                (namespace.as_bytes(), &[0u8; 32])..=(namespace.as_bytes(), &[255u8; 32]),
+                |_k, _v| false,
+            )?;
             Ok(())
         })
     }
@@ -455,7 +806,7 @@ impl Store {
     /// Get all content hashes of all replicas in the store.
     pub fn content_hashes(&mut self) -> Result<ContentHashesIterator> {
         let tables = self.snapshot_owned()?;
-        ContentHashesIterator::all(&tables.records)
+        ContentHashesIterator::all(&tables)
     }
 
     /// Get the latest entry for each author in a namespace.
@@ -595,96 +946,1164 @@ impl Store {
             Some(value) => postcard::from_bytes(value.value())?,
         })
     }
-}
 
-impl PublicKeyStore for Store {
-    fn public_key(&self, id: &[u8; 32]) -> Result<VerifyingKey, SignatureError> {
-        self.pubkeys.public_key(id)
+    /// Add a write delegation for its namespace.
+    ///
+    /// The delegation is not verified, this is the responsibility of the caller.
+    /// Returns `true` if the delegation was not stored before.
+    pub fn put_delegation(&mut self, delegation: &WriteDelegation) -> Result<bool> {
+        self.modify(|tables| {
+            let namespace = delegation.namespace();
+            let id = delegation.id();
+            let key = (namespace.as_bytes(), &id);
+            if tables.delegations.get(key)?.is_some() {
+                return Ok(false);
+            }
+            tables
+                .delegations
+                .insert(key, delegation.to_bytes().as_slice())?;
+            Ok(true)
+        })
     }
-}
 
-fn parse_capability((raw_kind, raw_bytes): (u8, &[u8; 32])) -> Result<Capability> {
-    Capability::from_raw(raw_kind, raw_bytes)
-}
+    /// Get all write delegations stored for a namespace.
+    pub fn get_delegations(&mut self, namespace: &NamespaceId) -> Result<Vec<WriteDelegation>> {
+        let tables = self.tables()?;
+        let namespace = namespace.as_bytes();
+        let mut delegations = Vec::new();
+        for item in tables
+            .delegations
+            .range((namespace, &[0u8; 32])..=(namespace, &[255u8; 32]))?
+        {
+            let (_key, value) = item?;
+            delegations.push(WriteDelegation::from_bytes(value.value())?);
+        }
+        Ok(delegations)
+    }
 
-fn get_exact(
-    record_table: &impl ReadableTable<RecordsId<'static>, RecordsValue<'static>>,
-    namespace: NamespaceId,
-    author: AuthorId,
-    key: impl AsRef<[u8]>,
-    include_empty: bool,
-) -> Result<Option<SignedEntry>> {
-    let id = (namespace.as_bytes(), author.as_bytes(), key.as_ref());
-    let record = record_table.get(id)?;
-    Ok(record
-        .map(|r| into_entry(id, r.value()))
-        .filter(|entry| include_empty || !entry.is_empty()))
-}
+    /// Set the access policy for a namespace.
+    pub fn set_access_policy(
+        &mut self,
+        namespace: &NamespaceId,
+        policy: AccessPolicy,
+    ) -> Result<()> {
+        self.modify(|tables| {
+            let namespace = namespace.as_bytes();
 
-/// A wrapper around [`Store`] for a specific [`NamespaceId`]
-#[derive(Debug)]
-pub struct StoreInstance<'a> {
-    namespace: NamespaceId,
-    pub(crate) store: &'a mut Store,
-}
+            // ensure the document exists
+            anyhow::ensure!(
+                tables.namespaces.get(&namespace)?.is_some(),
+                "document not created"
+            );
 
-impl<'a> StoreInstance<'a> {
-    pub(crate) fn new(namespace: NamespaceId, store: &'a mut Store) -> Self {
-        StoreInstance { namespace, store }
+            let value = postcard::to_stdvec(&policy)?;
+            tables.access_policy.insert(namespace, value.as_slice())?;
+            Ok(())
+        })
     }
-}
 
-impl<'a> PublicKeyStore for StoreInstance<'a> {
-    fn public_key(&self, id: &[u8; 32]) -> std::result::Result<VerifyingKey, SignatureError> {
-        self.store.public_key(id)
+    /// Get the access policy for a namespace.
+    pub fn get_access_policy(&mut self, namespace: &NamespaceId) -> Result<AccessPolicy> {
+        let tables = self.tables()?;
+        let value = tables.access_policy.get(namespace.as_bytes())?;
+        Ok(match value {
+            None => AccessPolicy::default(),
+            Some(value) => postcard::from_bytes(value.value())?,
+        })
     }
-}
 
-impl<'a> super::DownloadPolicyStore for StoreInstance<'a> {
-    fn get_download_policy(&mut self, namespace: &NamespaceId) -> Result<DownloadPolicy> {
-        self.store.get_download_policy(namespace)
-    }
-}
+    /// Set the authors whose moved-to markers are followed automatically for a namespace.
+    ///
+    /// See [`crate::moved_to`]. An empty set, the default, means that markers are only followed
+    /// on explicit confirmation.
+    pub fn set_trusted_marker_authors(
+        &mut self,
+        namespace: &NamespaceId,
+        authors: BTreeSet<AuthorId>,
+    ) -> Result<()> {
+        self.modify(|tables| {
+            let namespace = namespace.as_bytes();
 
-impl<'a> crate::ranger::Store<SignedEntry> for StoreInstance<'a> {
-    type Error = anyhow::Error;
-    type RangeIterator<'x> = Chain<RecordsRange<'x>, Flatten<std::option::IntoIter<RecordsRange<'x>>>>
-        where 'a: 'x;
-    type ParentIterator<'x> = ParentIterator
-        where 'a: 'x;
+            // ensure the document exists
+            anyhow::ensure!(
+                tables.namespaces.get(&namespace)?.is_some(),
+                "document not created"
+            );
 
-    /// Get a the first key (or the default if none is available).
-    fn get_first(&mut self) -> Result<RecordIdentifier> {
-        let tables = self.store.as_mut().tables()?;
-        // TODO: verify this fetches all keys with this namespace
-        let bounds = RecordsBounds::namespace(self.namespace);
-        let mut records = tables.records.range(bounds.as_ref())?;
+            if authors.is_empty() {
+                tables.marker_trust.remove(namespace)?;
+            } else {
+                let value = postcard::to_stdvec(&authors)?;
+                tables.marker_trust.insert(namespace, value.as_slice())?;
+            }
+            Ok(())
+        })
+    }
 
-        let Some(record) = records.next() else {
-            return Ok(RecordIdentifier::default());
-        };
-        let (compound_key, _value) = record?;
-        let (namespace_id, author_id, key) = compound_key.value();
-        let id = RecordIdentifier::new(namespace_id, author_id, key);
-        Ok(id)
+    /// Get the authors whose moved-to markers are followed automatically for a namespace.
+    pub fn get_trusted_marker_authors(
+        &mut self,
+        namespace: &NamespaceId,
+    ) -> Result<BTreeSet<AuthorId>> {
+        let tables = self.tables()?;
+        let value = tables.marker_trust.get(namespace.as_bytes())?;
+        Ok(match value {
+            None => BTreeSet::new(),
+            Some(value) => postcard::from_bytes(value.value())?,
+        })
     }
 
-    fn get(&mut self, id: &RecordIdentifier) -> Result<Option<SignedEntry>> {
-        self.store
-            .as_mut()
-            .get_exact(id.namespace(), id.author(), id.key(), true)
+    /// Set the read token we present to peers when syncing a namespace.
+    ///
+    /// The token is not verified, this is the responsibility of the caller.
+    pub fn set_read_token(&mut self, token: &ReadToken) -> Result<()> {
+        self.modify(|tables| {
+            let namespace = token.namespace();
+            let namespace = namespace.as_bytes();
+
+            // ensure the document exists
+            anyhow::ensure!(
+                tables.namespaces.get(&namespace)?.is_some(),
+                "document not created"
+            );
+
+            tables
+                .read_tokens
+                .insert(namespace, token.to_bytes().as_slice())?;
+            Ok(())
+        })
     }
 
-    fn len(&mut self) -> Result<usize> {
-        let tables = self.store.as_mut().tables()?;
-        let bounds = RecordsBounds::namespace(self.namespace);
-        let records = tables.records.range(bounds.as_ref())?;
-        Ok(records.count())
+    /// Set the invite we present to the inviting `nodes` when syncing a namespace.
+    ///
+    /// The invite is not verified, this is the responsibility of the caller.
+    pub fn set_invite(&mut self, invite: &Invite, nodes: &[PeerIdBytes]) -> Result<()> {
+        self.modify(|tables| {
+            let namespace = invite.namespace();
+            let namespace = namespace.as_bytes();
+
+            // ensure the document exists
+            anyhow::ensure!(
+                tables.namespaces.get(&namespace)?.is_some(),
+                "document not created"
+            );
+
+            let value = postcard::to_stdvec(&(invite, nodes))?;
+            tables.invites.insert(namespace, value.as_slice())?;
+            Ok(())
+        })
     }
 
-    fn is_empty(&mut self) -> Result<bool> {
-        let tables = self.store.as_mut().tables()?;
-        Ok(tables.records.is_empty()?)
+    /// Remove the invite we present to the inviting nodes when syncing a namespace.
+    pub fn remove_invite(&mut self, namespace: &NamespaceId) -> Result<()> {
+        self.modify(|tables| {
+            tables.invites.remove(namespace.as_bytes())?;
+            Ok(())
+        })
+    }
+
+    /// Record the redemption of an invite by `node`.
+    ///
+    /// The first redemption by a node is logged, and a restricted access policy of the namespace
+    /// is extended to allow the node. Fails with [`InviteError::AlreadyRedeemed`] if the invite is
+    /// single-use and was redeemed by another node. The invite is not verified, this is the
+    /// responsibility of the caller.
+    ///
+    /// Returns `true` if the node had not redeemed the invite before.
+    pub fn redeem_invite(&mut self, invite: &Invite, node: &PeerIdBytes, now: u64) -> Result<bool> {
+        self.modify(|tables| {
+            let namespace = invite.namespace();
+            let namespace = namespace.as_bytes();
+            let id = invite.id();
+            let id = id.as_bytes();
+
+            let bounds = (namespace, id, &[0u8; 32])..=(namespace, id, &[255u8; 32]);
+            for item in tables.redemptions.range(bounds)? {
+                let (key, _value) = item?;
+                let (_namespace, _id, redeemer) = key.value();
+                if redeemer == node {
+                    return Ok(false);
+                }
+                if invite.is_single_use() {
+                    return Err(InviteError::AlreadyRedeemed.into());
+                }
+            }
+            tables
+                .redemptions
+                .insert((namespace, id, node), (now, u8::from(invite.mode())))?;
+
+            let policy = match tables.access_policy.get(namespace)? {
+                None => AccessPolicy::default(),
+                Some(value) => postcard::from_bytes(value.value())?,
+            };
+            if let AccessPolicy::Restricted { mut nodes } = policy {
+                if nodes.insert(*node) {
+                    let value = postcard::to_stdvec(&AccessPolicy::Restricted { nodes })?;
+                    tables.access_policy.insert(namespace, value.as_slice())?;
+                }
+            }
+            Ok(true)
+        })
+    }
+
+    /// Set the encryption key for a namespace.
+    ///
+    /// Fails if a different key is already set, since entries encrypted with the previous key
+    /// would become unreadable.
+    pub fn set_encryption_key(
+        &mut self,
+        namespace: &NamespaceId,
+        key: &DocEncryptionKey,
+    ) -> Result<()> {
+        let keystore = self.keystore.clone();
+        let sealed = keystore.seal(
+            SecretKind::EncryptionKey,
+            namespace.as_bytes(),
+            &key.to_bytes(),
+        )?;
+        self.modify(|tables| {
+            let namespace = namespace.as_bytes();
+
+            // ensure the document exists
+            anyhow::ensure!(
+                tables.namespaces.get(&namespace)?.is_some(),
+                "document not created"
+            );
+
+            let existing = tables
+                .encryption_keys
+                .get(namespace)?
+                .map(|value| *value.value());
+            if let Some(existing) = existing {
+                let existing = keystore.open(
+                    &tables.sealed_secrets,
+                    SecretKind::EncryptionKey,
+                    namespace,
+                    &existing,
+                )?;
+                anyhow::ensure!(
+                    existing == key.to_bytes(),
+                    "a different encryption key is already set"
+                );
+                return Ok(());
+            }
+            tables.encryption_keys.insert(namespace, &sealed.slot)?;
+            sealed.write(&mut tables.sealed_secrets)?;
+            Ok(())
+        })
+    }
+
+    /// Get the encryption key for a namespace, if any.
+    pub fn get_encryption_key(
+        &mut self,
+        namespace: &NamespaceId,
+    ) -> Result<Option<DocEncryptionKey>> {
+        let keystore = self.keystore.clone();
+        let tables = self.tables()?;
+        let stored = match tables.encryption_keys.get(namespace.as_bytes())? {
+            Some(value) => *value.value(),
+            None => return Ok(None),
+        };
+        let key = keystore.open(
+            &tables.sealed_secrets,
+            SecretKind::EncryptionKey,
+            namespace.as_bytes(),
+            &stored,
+        )?;
+        Ok(Some(DocEncryptionKey::from_bytes(&key)))
+    }
+
+    /// Set the area of interest for a namespace.
+    ///
+    /// Entries that are already stored outside of the area are kept, but are no longer synced.
+    pub fn set_sync_interest(
+        &mut self,
+        namespace: &NamespaceId,
+        area: &AreaOfInterest,
+    ) -> Result<()> {
+        self.modify(|tables| {
+            let namespace = namespace.as_bytes();
+
+            // ensure the document exists
+            anyhow::ensure!(
+                tables.namespaces.get(&namespace)?.is_some(),
+                "document not created"
+            );
+
+            let value = postcard::to_stdvec(area)?;
+            tables.sync_interest.insert(namespace, value.as_slice())?;
+            Ok(())
+        })
+    }
+
+    /// Get the area of interest for a namespace.
+    pub fn get_sync_interest(&mut self, namespace: &NamespaceId) -> Result<AreaOfInterest> {
+        let tables = self.tables()?;
+        let value = tables.sync_interest.get(namespace.as_bytes())?;
+        Ok(match value {
+            None => AreaOfInterest::default(),
+            Some(value) => postcard::from_bytes(value.value())?,
+        })
+    }
+
+    /// Set the clock mode for a namespace.
+    pub fn set_clock_mode(&mut self, namespace: &NamespaceId, mode: &ClockMode) -> Result<()> {
+        self.modify(|tables| {
+            let namespace = namespace.as_bytes();
+
+            // ensure the document exists
+            anyhow::ensure!(
+                tables.namespaces.get(&namespace)?.is_some(),
+                "document not created"
+            );
+
+            let value = postcard::to_stdvec(mode)?;
+            tables.clock_mode.insert(namespace, value.as_slice())?;
+            Ok(())
+        })
+    }
+
+    /// Get the clock mode for a namespace.
+    pub fn get_clock_mode(&mut self, namespace: &NamespaceId) -> Result<ClockMode> {
+        let tables = self.tables()?;
+        let value = tables.clock_mode.get(namespace.as_bytes())?;
+        Ok(match value {
+            None => ClockMode::default(),
+            Some(value) => postcard::from_bytes(value.value())?,
+        })
+    }
+
+    /// Get the latest timestamp of all entries in a namespace, or 0 if it has no entries.
+    pub fn get_latest_timestamp(&mut self, namespace: NamespaceId) -> Result<u64> {
+        let mut latest = 0;
+        for res in self.get_latest_for_each_author(namespace)? {
+            let (_author, timestamp, _key) = res?;
+            latest = latest.max(timestamp);
+        }
+        Ok(latest)
+    }
+
+    /// Set the tombstone policy for a namespace.
+    pub fn set_tombstone_policy(
+        &mut self,
+        namespace: &NamespaceId,
+        policy: &TombstonePolicy,
+    ) -> Result<()> {
+        self.modify(|tables| {
+            let namespace = namespace.as_bytes();
+
+            // ensure the document exists
+            anyhow::ensure!(
+                tables.namespaces.get(&namespace)?.is_some(),
+                "document not created"
+            );
+
+            let value = postcard::to_stdvec(policy)?;
+            tables
+                .tombstone_policy
+                .insert(namespace, value.as_slice())?;
+            Ok(())
+        })
+    }
+
+    /// Get the tombstone policy for a namespace.
+    pub fn get_tombstone_policy(&mut self, namespace: &NamespaceId) -> Result<TombstonePolicy> {
+        let tables = self.tables()?;
+        let value = tables.tombstone_policy.get(namespace.as_bytes())?;
+        Ok(match value {
+            None => TombstonePolicy::default(),
+            Some(value) => postcard::from_bytes(value.value())?,
+        })
+    }
+
+    /// Set the sync policy for a namespace.
+    pub fn set_sync_policy(&mut self, namespace: &NamespaceId, policy: &SyncPolicy) -> Result<()> {
+        anyhow::ensure!(
+            policy.max_concurrent != Some(0),
+            "the maximum number of concurrent syncs must not be zero"
+        );
+        self.modify(|tables| {
+            let namespace = namespace.as_bytes();
+
+            // ensure the document exists
+            anyhow::ensure!(
+                tables.namespaces.get(&namespace)?.is_some(),
+                "document not created"
+            );
+
+            let value = postcard::to_stdvec(policy)?;
+            tables.sync_policy.insert(namespace, value.as_slice())?;
+            Ok(())
+        })
+    }
+
+    /// Get the sync policy for a namespace.
+    pub fn get_sync_policy(&mut self, namespace: &NamespaceId) -> Result<SyncPolicy> {
+        let tables = self.tables()?;
+        let value = tables.sync_policy.get(namespace.as_bytes())?;
+        Ok(match value {
+            None => SyncPolicy::default(),
+            Some(value) => postcard::from_bytes(value.value())?,
+        })
+    }
+
+    /// Set the maximum length of the values of a namespace that are sent inline.
+    ///
+    /// Values up to this length are sent inline in sync messages and gossip messages together
+    /// with their entries, if their content is available locally. The receiving peer stores the
+    /// content in its docs store for the entries that pass validation, see
+    /// [`Self::put_inline_content`]. A limit of 0, the default, disables inline values. The limit
+    /// must not exceed [`MAX_INLINE_LIMIT`].
+    pub fn set_inline_limit(&mut self, namespace: &NamespaceId, limit: u64) -> Result<()> {
+        anyhow::ensure!(
+            limit <= MAX_INLINE_LIMIT,
+            "inline limit must not exceed {MAX_INLINE_LIMIT} bytes"
+        );
+        self.modify(|tables| {
+            let namespace = namespace.as_bytes();
+
+            // ensure the document exists
+            anyhow::ensure!(
+                tables.namespaces.get(&namespace)?.is_some(),
+                "document not created"
+            );
+
+            tables.inline_limit.insert(namespace, limit)?;
+            Ok(())
+        })
+    }
+
+    /// Get the maximum length of the values of a namespace that are sent inline.
+    pub fn get_inline_limit(&mut self, namespace: &NamespaceId) -> Result<u64> {
+        let tables = self.tables()?;
+        let value = tables.inline_limit.get(namespace.as_bytes())?;
+        Ok(value.map(|v| v.value()).unwrap_or_default())
+    }
+
+    /// Store the content of a record that was received inline with its entry.
+    ///
+    /// The content is only stored if the current record for `id` references it, and is removed
+    /// together with the record, i.e. when the record is replaced by a newer entry or deleted.
+    /// Content longer than [`MAX_INLINE_LIMIT`] is not stored.
+    pub fn put_inline_content(&mut self, id: &RecordIdentifier, content: &[u8]) -> Result<()> {
+        if content.len() as u64 > MAX_INLINE_LIMIT {
+            return Ok(());
+        }
+        let hash = Hash::new(content);
+        self.modify(|tables| {
+            let current = tables
+                .records
+                .get(id.as_byte_tuple())?
+                .is_some_and(|value| {
+                    let (_timestamp, _namespace_sig, _author_sig, _len, record_hash) =
+                        value.value();
+                    record_hash == hash.as_bytes()
+                });
+            if current {
+                tables.inline_content.insert(id.as_byte_tuple(), content)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Get the content of a record that was received inline with its entry.
+    ///
+    /// Returns `None` if no content is stored for the record, or if the stored content does not
+    /// match `hash`.
+    pub fn get_inline_content(
+        &mut self,
+        id: &RecordIdentifier,
+        hash: &Hash,
+    ) -> Result<Option<Bytes>> {
+        let tables = self.tables()?;
+        let Some(value) = tables.inline_content.get(id.as_byte_tuple())? else {
+            return Ok(None);
+        };
+        let content = value.value();
+        Ok((Hash::new(content) == *hash).then(|| Bytes::copy_from_slice(content)))
+    }
+
+    /// Create a secondary index of a namespace, replacing an existing index with the same name.
+    ///
+    /// The index starts out empty. Entries are added with [`Self::index_content`] once their
+    /// content is available.
+    pub fn create_index(
+        &mut self,
+        namespace: &NamespaceId,
+        name: &str,
+        spec: &IndexSpec,
+    ) -> Result<()> {
+        self.modify(|tables| {
+            let namespace = namespace.as_bytes();
+
+            // ensure the document exists
+            anyhow::ensure!(
+                tables.namespaces.get(&namespace)?.is_some(),
+                "document not created"
+            );
+            #[cfg(not(feature = "json"))]
+            anyhow::ensure!(
+                !matches!(spec.field, super::FieldExtractor::Json(_)),
+                "JSON indexes require the json feature"
+            );
+
+            clear_index(tables, namespace, name)?;
+            let value = postcard::to_stdvec(spec)?;
+            tables.indexes.insert((namespace, name), value.as_slice())?;
+            Ok(())
+        })
+    }
+
+    /// Remove a secondary index of a namespace.
+    ///
+    /// Returns `false` if the index did not exist.
+    pub fn remove_index(&mut self, namespace: &NamespaceId, name: &str) -> Result<bool> {
+        self.modify(|tables| {
+            let namespace = namespace.as_bytes();
+            let existed = tables.indexes.remove((namespace, name))?.is_some();
+            clear_index(tables, namespace, name)?;
+            Ok(existed)
+        })
+    }
+
+    /// List the secondary indexes of a namespace, sorted by name.
+    pub fn list_indexes(&mut self, namespace: &NamespaceId) -> Result<Vec<(String, IndexSpec)>> {
+        let tables = self.tables()?;
+        let namespace = namespace.as_bytes();
+        let mut indexes = Vec::new();
+        for item in tables.indexes.range((namespace, "")..)? {
+            let (key, value) = item?;
+            let (index_namespace, name) = key.value();
+            if index_namespace != namespace {
+                break;
+            }
+            indexes.push((name.to_string(), postcard::from_bytes(value.value())?));
+        }
+        Ok(indexes)
+    }
+
+    /// Add an entry of a namespace to the secondary indexes whose key prefix matches its key.
+    ///
+    /// `content` is the content of the entry of `author` for `key` with content `hash`. Its
+    /// previously indexed values are replaced with the values extracted from `content`. If the
+    /// current entry of `author` for `key` has a different content hash, nothing is changed.
+    /// Content longer than [`MAX_INDEXED_CONTENT_LEN`] is not indexed.
+    pub fn index_content(
+        &mut self,
+        namespace: &NamespaceId,
+        author: &AuthorId,
+        key: &[u8],
+        hash: &Hash,
+        content: &[u8],
+    ) -> Result<()> {
+        let indexes = self.list_indexes(namespace)?;
+        if !indexes.iter().any(|(_name, spec)| spec.matches(key)) {
+            return Ok(());
+        }
+        let content = if content.len() as u64 > MAX_INDEXED_CONTENT_LEN {
+            &[][..]
+        } else {
+            content
+        };
+        self.modify(|tables| {
+            let entry = get_exact(&tables.records, *namespace, *author, key, true)?;
+            if entry.map(|entry| entry.content_hash()) != Some(*hash) {
+                return Ok(());
+            }
+            for (name, spec) in indexes {
+                if spec.matches(key) {
+                    let values = spec.extract(content);
+                    update_index(
+                        tables,
+                        namespace.as_bytes(),
+                        &name,
+                        author.as_bytes(),
+                        key,
+                        hash.as_bytes(),
+                        &values,
+                    )?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Get the timestamp before which entries of a namespace were compacted, or 0 if the
+    /// namespace was never compacted.
+    pub fn get_compaction_cutoff(&mut self, namespace: &NamespaceId) -> Result<u64> {
+        let tables = self.tables()?;
+        let value = tables.compaction_cutoff.get(namespace.as_bytes())?;
+        Ok(value.map(|v| v.value()).unwrap_or_default())
+    }
+
+    /// Remove the tombstones of a namespace that are older than the horizon of its
+    /// [`TombstonePolicy`], and raise its compaction cutoff accordingly.
+    ///
+    /// Returns the number of removed tombstones.
+    pub fn compact_tombstones(&mut self, namespace: &NamespaceId) -> Result<usize> {
+        let TombstonePolicy::Expire { horizon } = self.get_tombstone_policy(namespace)? else {
+            return Ok(0);
+        };
+        let cutoff = system_time_now().saturating_sub(horizon.as_micros() as u64);
+        self.modify(|tables| {
+            let current = tables
+                .compaction_cutoff
+                .get(namespace.as_bytes())?
+                .map(|v| v.value())
+                .unwrap_or_default();
+            if cutoff <= current {
+                return Ok(0);
+            }
+            let count = remove_entries_before(tables, namespace, cutoff, true)?;
+            tables
+                .compaction_cutoff
+                .insert(namespace.as_bytes(), cutoff)?;
+            Ok(count)
+        })
+    }
+
+    /// Adopt the compaction cutoff of a peer, if it is newer than ours.
+    ///
+    /// The peer may have removed tombstones older than the cutoff, so our entries older than the
+    /// cutoff may have been deleted without us knowing. All of them are removed, to be synced
+    /// again from the peer.
+    ///
+    /// Fails unless the [`TombstonePolicy`] of the namespace expires tombstones, and the cutoff
+    /// is no newer than its horizon allows, so that a peer cannot make us drop recent entries.
+    ///
+    /// Returns the number of removed entries.
+    pub fn adopt_compaction_cutoff(
+        &mut self,
+        namespace: &NamespaceId,
+        cutoff: u64,
+    ) -> Result<usize> {
+        let TombstonePolicy::Expire { horizon } = self.get_tombstone_policy(namespace)? else {
+            anyhow::bail!("tombstones of the document do not expire");
+        };
+        let max_cutoff = system_time_now()
+            .saturating_sub(horizon.as_micros() as u64)
+            .saturating_add(MAX_TIMESTAMP_FUTURE_SHIFT);
+        anyhow::ensure!(
+            cutoff <= max_cutoff,
+            "compaction cutoff is newer than the tombstone horizon"
+        );
+        self.modify(|tables| {
+            let current = tables
+                .compaction_cutoff
+                .get(namespace.as_bytes())?
+                .map(|v| v.value())
+                .unwrap_or_default();
+            if cutoff <= current {
+                return Ok(0);
+            }
+            let count = remove_entries_before(tables, namespace, cutoff, false)?;
+            tables
+                .compaction_cutoff
+                .insert(namespace.as_bytes(), cutoff)?;
+            Ok(count)
+        })
+    }
+
+    /// Set the history policy for a namespace.
+    ///
+    /// The retention policy is applied to the existing history right away. Disabling the history
+    /// removes all superseded entries of the namespace.
+    pub fn set_history_policy(
+        &mut self,
+        namespace: &NamespaceId,
+        policy: HistoryPolicy,
+    ) -> Result<()> {
+        self.modify(|tables| {
+            // ensure the document exists
+            anyhow::ensure!(
+                tables.namespaces.get(namespace.as_bytes())?.is_some(),
+                "document not created"
+            );
+
+            let value = postcard::to_stdvec(&policy)?;
+            tables
+                .history_policy
+                .insert(namespace.as_bytes(), value.as_slice())?;
+
+            let bounds = HistoryBounds::namespace(*namespace);
+            match policy {
+                HistoryPolicy::Disabled => {
+                    tables.history.retain_in(bounds.as_ref(), |_k, _v| false)?;
+                }
+                HistoryPolicy::Enabled(retention) => {
+                    prune_history(&mut tables.history, bounds, &retention)?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Get the history policy for a namespace.
+    pub fn get_history_policy(&mut self, namespace: &NamespaceId) -> Result<HistoryPolicy> {
+        let tables = self.tables()?;
+        get_history_policy(&tables.history_policy, namespace)
+    }
+
+    /// Get the read token we present to peers when syncing a namespace, if any.
+    pub fn get_read_token(&mut self, namespace: &NamespaceId) -> Result<Option<ReadToken>> {
+        let tables = self.tables()?;
+        let value = tables.read_tokens.get(namespace.as_bytes())?;
+        Ok(match value {
+            None => None,
+            Some(value) => Some(ReadToken::from_bytes(value.value())?),
+        })
+    }
+
+    /// Get the invite we present when syncing a namespace, if any, and the inviting nodes to
+    /// present it to.
+    pub fn get_invite(
+        &mut self,
+        namespace: &NamespaceId,
+    ) -> Result<Option<(Invite, Vec<PeerIdBytes>)>> {
+        let tables = self.tables()?;
+        let value = tables.invites.get(namespace.as_bytes())?;
+        Ok(match value {
+            None => None,
+            Some(value) => Some(postcard::from_bytes(value.value())?),
+        })
+    }
+
+    /// Get the logged redemptions of the invites for a namespace.
+    pub fn list_redemptions(&mut self, namespace: &NamespaceId) -> Result<Vec<Redemption>> {
+        let tables = self.tables()?;
+        let namespace = namespace.as_bytes();
+        let bounds = (namespace, &[0u8; 16], &[0u8; 32])..=(namespace, &[255u8; 16], &[255u8; 32]);
+        let mut redemptions = Vec::new();
+        for item in tables.redemptions.range(bounds)? {
+            let (key, value) = item?;
+            let (_namespace, id, node) = key.value();
+            let (timestamp, mode) = value.value();
+            redemptions.push(Redemption {
+                invite: InviteId::from_bytes(*id),
+                node: *node,
+                mode: CapabilityKind::try_from(mode)?,
+                timestamp,
+            });
+        }
+        Ok(redemptions)
+    }
+}
+
+impl PublicKeyStore for Store {
+    fn public_key(&self, id: &[u8; 32]) -> Result<VerifyingKey, SignatureError> {
+        self.pubkeys.public_key(id)
+    }
+}
+
+fn get_history_policy(
+    table: &impl ReadableTable<&'static [u8; 32], &'static [u8]>,
+    namespace: &NamespaceId,
+) -> Result<HistoryPolicy> {
+    let value = table.get(namespace.as_bytes())?;
+    Ok(match value {
+        None => HistoryPolicy::default(),
+        Some(value) => postcard::from_bytes(value.value())?,
+    })
+}
+
+/// Remove the entries of a namespace with a timestamp before `cutoff`.
+///
+/// If `tombstones_only` is true, only empty entries are removed. The versions superseded by the
+/// removed entries are removed from the history, and the latest entry of the affected authors is
+/// updated.
+fn remove_entries_before(
+    tables: &mut Tables,
+    namespace: &NamespaceId,
+    cutoff: u64,
+    tombstones_only: bool,
+) -> Result<usize> {
+    let bounds = RecordsBounds::namespace(*namespace);
+    let removed = tables
+        .records
+        .extract_from_if(bounds.as_ref(), |_k, v| {
+            let (timestamp, _namespace_sig, _author_sig, _len, hash) = v;
+            timestamp < cutoff && (!tombstones_only || hash == Hash::EMPTY.as_bytes())
+        })?
+        .map(|item| {
+            let (k, v) = item?;
+            let (_namespace, author, key) = k.value();
+            let (timestamp, _namespace_sig, _author_sig, _len, _hash) = v.value();
+            Ok((
+                AuthorId::from(author),
+                Bytes::copy_from_slice(key),
+                timestamp,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut authors = BTreeSet::new();
+    for (author, key, timestamp) in &removed {
+        tables
+            .records_by_key
+            .remove((namespace.as_bytes(), &key[..], author.as_bytes()))?;
+        tables.records_by_timestamp.remove((
+            namespace.as_bytes(),
+            *timestamp,
+            author.as_bytes(),
+            &key[..],
+        ))?;
+        tables
+            .inline_content
+            .remove((namespace.as_bytes(), author.as_bytes(), &key[..]))?;
+        let bounds = HistoryBounds::author_key(*namespace, *author, key.clone());
+        tables.history.retain_in(bounds.as_ref(), |_k, _v| false)?;
+        authors.insert(*author);
+    }
+    for author in authors {
+        update_latest_per_author(tables, namespace, &author, cutoff)?;
+    }
+    Ok(removed.len())
+}
+
+/// Recompute the latest entry of an author, if it is older than `cutoff` and may thus have been
+/// removed.
+fn update_latest_per_author(
+    tables: &mut Tables,
+    namespace: &NamespaceId,
+    author: &AuthorId,
+    cutoff: u64,
+) -> Result<()> {
+    let id = (namespace.as_bytes(), author.as_bytes());
+    let current = tables.latest_per_author.get(id)?.map(|v| v.value().0);
+    if current.map_or(true, |timestamp| timestamp >= cutoff) {
+        return Ok(());
+    }
+    let bounds = RecordsBounds::author_prefix(*namespace, *author, Bytes::new());
+    let mut latest: Option<(u64, Bytes)> = None;
+    for item in tables.records.range(bounds.as_ref())? {
+        let (k, v) = item?;
+        let (_namespace, _author, key) = k.value();
+        let (timestamp, _namespace_sig, _author_sig, _len, _hash) = v.value();
+        if latest
+            .as_ref()
+            .map_or(true, |(latest, _)| timestamp > *latest)
+        {
+            latest = Some((timestamp, Bytes::copy_from_slice(key)));
+        }
+    }
+    match latest {
+        Some((timestamp, key)) => {
+            tables.latest_per_author.insert(id, (timestamp, &key[..]))?;
+        }
+        None => {
+            tables.latest_per_author.remove(id)?;
+        }
+    }
+    Ok(())
+}
+
+/// Move superseded entries into the history table, and prune the history of their keys.
+fn archive_entries(
+    history: &mut Table<HistoryId<'static>, HistoryValue<'static>>,
+    entries: Vec<SignedEntry>,
+    retention: &HistoryRetention,
+) -> Result<()> {
+    for e in &entries {
+        let id = e.id();
+        let hash = e.content_hash(); // let binding is needed
+        let key = (
+            &id.namespace().to_bytes(),
+            &id.author().to_bytes(),
+            id.key(),
+            e.timestamp(),
+            hash.as_bytes(),
+        );
+        let value = (
+            &e.signature().namespace().to_bytes(),
+            &e.signature().author().to_bytes(),
+            e.content_len(),
+        );
+        history.insert(key, value)?;
+    }
+    for e in entries {
+        let id = e.id();
+        let bounds = HistoryBounds::author_key(id.namespace(), id.author(), id.key_bytes());
+        prune_history(history, bounds, retention)?;
+    }
+    Ok(())
+}
+
+/// Remove the versions in `bounds` which exceed the limits of the retention policy.
+fn prune_history(
+    history: &mut Table<HistoryId<'static>, HistoryValue<'static>>,
+    bounds: HistoryBounds,
+    retention: &HistoryRetention,
+) -> Result<()> {
+    let min_timestamp = retention
+        .max_age
+        .map(|age| system_time_now().saturating_sub(age.as_micros() as u64));
+
+    // versions are sorted by author and key, and then by timestamp
+    let mut versions: Vec<HistoryIdOwned> = Vec::new();
+    for item in history.range(bounds.as_ref())? {
+        let (id, _value) = item?;
+        let (namespace, author, key, timestamp, hash) = id.value();
+        versions.push((
+            *namespace,
+            *author,
+            Bytes::copy_from_slice(key),
+            timestamp,
+            *hash,
+        ));
+    }
+
+    let mut expired = Vec::new();
+    let mut start = 0;
+    while start < versions.len() {
+        let (namespace, author, key, _, _) = &versions[start];
+        let len = versions[start..]
+            .iter()
+            .take_while(|(n, a, k, _, _)| n == namespace && a == author && k == key)
+            .count();
+        let excess = retention
+            .max_versions
+            .map_or(0, |max| len.saturating_sub(max as usize));
+        for (i, version) in versions[start..start + len].iter().enumerate() {
+            if i < excess || min_timestamp.is_some_and(|min| version.3 < min) {
+                expired.push(version.clone());
+            }
+        }
+        start += len;
+    }
+
+    for (namespace, author, key, timestamp, hash) in expired {
+        history.remove((&namespace, &author, &key[..], timestamp, &hash))?;
+    }
+    Ok(())
+}
+
+/// Re-encrypt all stored secrets from one keystore state to another.
+fn rewrite_secrets(tables: &mut Tables, from: &Keystore, to: &Keystore) -> Result<()> {
+    let authors = tables
+        .authors
+        .range::<&'static [u8; 32]>(..)?
+        .map(|res| {
+            let (key, value) = res?;
+            Ok((*key.value(), *value.value()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    for (id, stored) in authors {
+        let secret = from.open(&tables.sealed_secrets, SecretKind::Author, &id, &stored)?;
+        let sealed = to.seal(SecretKind::Author, &id, &secret)?;
+        tables.authors.insert(&id, &sealed.slot)?;
+        sealed.write(&mut tables.sealed_secrets)?;
+    }
+
+    let namespaces = tables
+        .namespaces
+        .range::<&'static [u8; 32]>(..)?
+        .filter_map(|res| match res {
+            Ok((key, value)) => {
+                let (kind, bytes) = value.value();
+                (kind == u8::from(CapabilityKind::Write)).then(|| Ok((*key.value(), *bytes)))
+            }
+            Err(err) => Some(Err(err.into())),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    for (id, stored) in namespaces {
+        let secret = from.open(&tables.sealed_secrets, SecretKind::Namespace, &id, &stored)?;
+        let sealed = to.seal(SecretKind::Namespace, &id, &secret)?;
+        let kind = u8::from(CapabilityKind::Write);
+        tables.namespaces.insert(&id, (kind, &sealed.slot))?;
+        sealed.write(&mut tables.sealed_secrets)?;
+    }
+
+    let encryption_keys = tables
+        .encryption_keys
+        .range::<&'static [u8; 32]>(..)?
+        .map(|res| {
+            let (key, value) = res?;
+            Ok((*key.value(), *value.value()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    for (id, stored) in encryption_keys {
+        let secret = from.open(
+            &tables.sealed_secrets,
+            SecretKind::EncryptionKey,
+            &id,
+            &stored,
+        )?;
+        let sealed = to.seal(SecretKind::EncryptionKey, &id, &secret)?;
+        tables.encryption_keys.insert(&id, &sealed.slot)?;
+        sealed.write(&mut tables.sealed_secrets)?;
+    }
+    Ok(())
+}
+
+/// Replace the values of the entry of `author` for `key` in the secondary index `name`.
+fn update_index(
+    tables: &mut Tables,
+    namespace: &[u8; 32],
+    name: &str,
+    author: &[u8; 32],
+    key: &[u8],
+    hash: &[u8; 32],
+    values: &[Bytes],
+) -> Result<()> {
+    let old: Vec<Bytes> = match tables.index_values.remove((namespace, name, author, key))? {
+        Some(value) => postcard::from_bytes(value.value())?,
+        None => Vec::new(),
+    };
+    for value in old {
+        tables
+            .index_entries
+            .remove((namespace, name, &value[..], author, key))?;
+    }
+    if values.is_empty() {
+        return Ok(());
+    }
+    for value in values {
+        tables
+            .index_entries
+            .insert((namespace, name, &value[..], author, key), hash)?;
+    }
+    let values = postcard::to_stdvec(values)?;
+    tables
+        .index_values
+        .insert((namespace, name, author, key), values.as_slice())?;
+    Ok(())
+}
+
+/// Remove all entries of the secondary index `name` of a namespace.
+fn clear_index(tables: &mut Tables, namespace: &[u8; 32], name: &str) -> Result<()> {
+    let empty: &[u8] = &[];
+    let mut entries = Vec::new();
+    for item in tables
+        .index_entries
+        .range((namespace, name, empty, &[0u8; 32], empty)..)?
+    {
+        let (id, _hash) = item?;
+        let (id_namespace, id_name, value, author, key) = id.value();
+        if id_namespace != namespace || id_name != name {
+            break;
+        }
+        entries.push((value.to_vec(), *author, key.to_vec()));
+    }
+    for (value, author, key) in entries {
+        tables
+            .index_entries
+            .remove((namespace, name, &value[..], &author, &key[..]))?;
+    }
+    let mut ids = Vec::new();
+    for item in tables
+        .index_values
+        .range((namespace, name, &[0u8; 32], empty)..)?
+    {
+        let (id, _values) = item?;
+        let (id_namespace, id_name, author, key) = id.value();
+        if id_namespace != namespace || id_name != name {
+            break;
+        }
+        ids.push((*author, key.to_vec()));
+    }
+    for (author, key) in ids {
+        tables
+            .index_values
+            .remove((namespace, name, &author, &key[..]))?;
+    }
+    Ok(())
+}
+
+/// Remove all secondary indexes of a namespace.
+fn clear_indexes(tables: &mut Tables, namespace: &[u8; 32]) -> Result<()> {
+    let mut names = Vec::new();
+    for item in tables.indexes.range((namespace, "")..)? {
+        let (key, _spec) = item?;
+        let (index_namespace, name) = key.value();
+        if index_namespace != namespace {
+            break;
+        }
+        names.push(name.to_string());
+    }
+    for name in names {
+        tables.indexes.remove((namespace, name.as_str()))?;
+        clear_index(tables, namespace, &name)?;
+    }
+    Ok(())
+}
+
+fn get_exact(
+    record_table: &impl ReadableTable<RecordsId<'static>, RecordsValue<'static>>,
+    namespace: NamespaceId,
+    author: AuthorId,
+    key: impl AsRef<[u8]>,
+    include_empty: bool,
+) -> Result<Option<SignedEntry>> {
+    let id = (namespace.as_bytes(), author.as_bytes(), key.as_ref());
+    let record = record_table.get(id)?;
+    Ok(record
+        .map(|r| into_entry(id, r.value()))
+        .filter(|entry| include_empty || !entry.is_empty()))
+}
+
+/// A wrapper around [`Store`] for a specific [`NamespaceId`]
+#[derive(Debug)]
+pub struct StoreInstance<'a> {
+    namespace: NamespaceId,
+    pub(crate) store: &'a mut Store,
+}
+
+impl<'a> StoreInstance<'a> {
+    pub(crate) fn new(namespace: NamespaceId, store: &'a mut Store) -> Self {
+        StoreInstance { namespace, store }
+    }
+}
+
+impl<'a> PublicKeyStore for StoreInstance<'a> {
+    fn public_key(&self, id: &[u8; 32]) -> std::result::Result<VerifyingKey, SignatureError> {
+        self.store.public_key(id)
+    }
+}
+
+impl<'a> super::DownloadPolicyStore for StoreInstance<'a> {
+    fn get_download_policy(&mut self, namespace: &NamespaceId) -> Result<DownloadPolicy> {
+        self.store.get_download_policy(namespace)
+    }
+}
+
+impl<'a> crate::ranger::Store<SignedEntry> for StoreInstance<'a> {
+    type Error = anyhow::Error;
+    type RangeIterator<'x> = Chain<RecordsRange<'x>, Flatten<std::option::IntoIter<RecordsRange<'x>>>>
+        where 'a: 'x;
+    type ParentIterator<'x> = ParentIterator
+        where 'a: 'x;
+
+    /// Get a the first key (or the default if none is available).
+    fn get_first(&mut self) -> Result<RecordIdentifier> {
+        let tables = self.store.as_mut().tables()?;
+        // TODO: verify this fetches all keys with this namespace
+        let bounds = RecordsBounds::namespace(self.namespace);
+        let mut records = tables.records.range(bounds.as_ref())?;
+
+        let Some(record) = records.next() else {
+            return Ok(RecordIdentifier::default());
+        };
+        let (compound_key, _value) = record?;
+        let (namespace_id, author_id, key) = compound_key.value();
+        let id = RecordIdentifier::new(namespace_id, author_id, key);
+        Ok(id)
+    }
+
+    fn get(&mut self, id: &RecordIdentifier) -> Result<Option<SignedEntry>> {
+        self.store
+            .as_mut()
+            .get_exact(id.namespace(), id.author(), id.key(), true)
+    }
+
+    fn len(&mut self) -> Result<usize> {
+        let tables = self.store.as_mut().tables()?;
+        let bounds = RecordsBounds::namespace(self.namespace);
+        let records = tables.records.range(bounds.as_ref())?;
+        Ok(records.count())
+    }
+
+    fn is_empty(&mut self) -> Result<bool> {
+        let tables = self.store.as_mut().tables()?;
+        Ok(tables.records.is_empty()?)
     }
 
     fn get_fingerprint(&mut self, range: &Range<RecordIdentifier>) -> Result<Fingerprint> {
@@ -717,7 +2136,12 @@ impl<'a> crate::ranger::Store<SignedEntry> for StoreInstance<'a> {
                 e.content_len(),
                 hash.as_bytes(),
             );
-            tables.records.insert(key, value)?;
+            let replaced = tables
+                .records
+                .insert(key, value)?
+                .map(|value| value.value().0);
+            // inline content belongs to the replaced record
+            tables.inline_content.remove(key)?;
 
             // insert into by key index table
             let key = (
@@ -727,6 +2151,17 @@ impl<'a> crate::ranger::Store<SignedEntry> for StoreInstance<'a> {
             );
             tables.records_by_key.insert(key, ())?;
 
+            // insert into by timestamp index table, and remove the replaced record from it
+            let (namespace, author, key) = id.as_byte_tuple();
+            if let Some(timestamp) = replaced {
+                tables
+                    .records_by_timestamp
+                    .remove((namespace, timestamp, author, key))?;
+            }
+            tables
+                .records_by_timestamp
+                .insert((namespace, e.timestamp(), author, key), ())?;
+
             // insert into latest table
             let key = (&e.id().namespace().to_bytes(), &e.id().author().to_bytes());
             let value = (e.timestamp(), e.id().key());
@@ -780,7 +2215,17 @@ impl<'a> crate::ranger::Store<SignedEntry> for StoreInstance<'a> {
                 tables.records_by_key.remove(id)?;
                 let id = (namespace, author, key);
                 let value = tables.records.remove(id)?;
-                value.map(|value| into_entry(id, value.value()))
+                tables.inline_content.remove(id)?;
+                let entry = value.map(|value| into_entry(id, value.value()));
+                if let Some(entry) = &entry {
+                    tables.records_by_timestamp.remove((
+                        namespace,
+                        entry.timestamp(),
+                        author,
+                        key,
+                    ))?;
+                }
+                entry
             };
             Ok(entry)
         })
@@ -815,6 +2260,7 @@ impl<'a> crate::ranger::Store<SignedEntry> for StoreInstance<'a> {
     ) -> Result<usize> {
         let bounds = RecordsBounds::author_prefix(id.namespace(), id.author(), id.key_bytes());
         self.store.as_mut().modify(|tables| {
+            let policy = get_history_policy(&tables.history_policy, &id.namespace())?;
             let cb = |_k: RecordsId, v: RecordsValue| {
                 let (timestamp, _namespace_sig, _author_sig, len, hash) = v;
                 let record = Record::new(hash.into(), len, timestamp);
@@ -822,7 +2268,25 @@ impl<'a> crate::ranger::Store<SignedEntry> for StoreInstance<'a> {
                 predicate(&record)
             };
             let iter = tables.records.extract_from_if(bounds.as_ref(), cb)?;
-            let count = iter.count();
+            let mut count = 0;
+            let mut removed = Vec::new();
+            for item in iter {
+                let (k, v) = item?;
+                let (namespace, author, key) = k.value();
+                let (timestamp, _namespace_sig, _author_sig, _len, _hash) = v.value();
+                tables
+                    .records_by_timestamp
+                    .remove((namespace, timestamp, author, key))?;
+                tables.inline_content.remove((namespace, author, key))?;
+                count += 1;
+                // keep the removed entries in the history
+                if matches!(policy, HistoryPolicy::Enabled(_)) {
+                    removed.push(into_entry(k.value(), v.value()));
+                }
+            }
+            if let HistoryPolicy::Enabled(retention) = policy {
+                archive_entries(&mut tables.history, removed, &retention)?;
+            }
             Ok(count)
         })
     }
@@ -884,7 +2348,7 @@ impl Iterator for ParentIterator {
     }
 }
 
-/// Iterator for all content hashes
+/// Iterator for all content hashes, including those of entries kept in the history.
 ///
 /// Note that you might get duplicate hashes. Also, the iterator will keep
 /// a database snapshot open until it is dropped.
@@ -895,13 +2359,16 @@ impl Iterator for ParentIterator {
 pub struct ContentHashesIterator {
     #[debug(skip)]
     range: RecordsRange<'static>,
+    #[debug(skip)]
+    history: redb::Range<'static, HistoryId<'static>, HistoryValue<'static>>,
 }
 
 impl ContentHashesIterator {
     /// Create a new iterator over all content hashes.
-    pub fn all(table: &RecordsTable) -> anyhow::Result<Self> {
-        let range = RecordsRange::all_static(table)?;
-        Ok(Self { range })
+    pub fn all(tables: &ReadOnlyTables) -> anyhow::Result<Self> {
+        let range = RecordsRange::all_static(&tables.records)?;
+        let history = tables.history.range::<HistoryId<'static>>(..)?;
+        Ok(Self { range, history })
     }
 }
 
@@ -909,8 +2376,13 @@ impl Iterator for ContentHashesIterator {
     type Item = Result<Hash>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let v = self.range.next()?;
-        Some(v.map(|e| e.content_hash()))
+        if let Some(v) = self.range.next() {
+            return Some(v.map(|e| e.content_hash()));
+        }
+        self.history.next_map(|key, _value| {
+            let (_namespace, _author, _key, _timestamp, hash) = key;
+            Hash::from_bytes(*hash)
+        })
     }
 }
 
@@ -948,6 +2420,15 @@ impl<'a> Iterator for LatestIterator<'a> {
     }
 }
 
+fn history_into_entry(key: HistoryId, value: HistoryValue) -> SignedEntry {
+    let (namespace, author, key, timestamp, hash) = key;
+    let (namespace_sig, author_sig, len) = value;
+    into_entry(
+        (namespace, author, key),
+        (timestamp, namespace_sig, author_sig, len, hash),
+    )
+}
+
 fn into_entry(key: RecordsId, value: RecordsValue) -> SignedEntry {
     let (namespace, author, key) = key;
     let (timestamp, namespace_sig, author_sig, len, hash) = value;
@@ -960,9 +2441,15 @@ fn into_entry(key: RecordsId, value: RecordsValue) -> SignedEntry {
 
 #[cfg(test)]
 mod tests {
-    use super::tables::LATEST_PER_AUTHOR_TABLE;
+    use std::time::Duration;
+
+    use super::tables::{LATEST_PER_AUTHOR_TABLE, RECORDS_BY_TIMESTAMP_TABLE};
 
-    use crate::ranger::Store as _;
+    use crate::{
+        ranger::Store as _,
+        store::{FieldExtractor, SortBy, SortDirection},
+        ContentStatus, InsertError,
+    };
 
     use super::*;
 
@@ -1070,6 +2557,533 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_keystore() -> Result<()> {
+        let dbfile = tempfile::NamedTempFile::new()?;
+        let mut store = Store::persistent(dbfile.path())?;
+        let author = store.new_author(&mut rand::thread_rng())?;
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+        let read_only = NamespaceSecret::new(&mut rand::thread_rng());
+        store.new_replica(namespace.clone())?;
+        store.import_namespace(Capability::Read(read_only.id()))?;
+        let key = DocEncryptionKey::from_namespace(&namespace);
+        store.set_encryption_key(&namespace.id(), &key)?;
+        store.close_replica(namespace.id());
+        assert_eq!(store.keystore_status(), KeystoreStatus::Disabled);
+        assert!(store.lock_keystore().is_err());
+
+        store.enable_keystore("hunter2")?;
+        assert_eq!(store.keystore_status(), KeystoreStatus::Unlocked);
+        assert!(store.enable_keystore("hunter2").is_err());
+        let second = store.new_author(&mut rand::thread_rng())?;
+        store.flush()?;
+        drop(store);
+
+        // the secrets are not stored in cleartext
+        let mut store = Store::persistent(dbfile.path())?;
+        assert_eq!(store.keystore_status(), KeystoreStatus::Locked);
+        {
+            let tables = store.tables()?;
+            let stored = tables.authors.get(author.id().as_bytes())?.unwrap();
+            assert_eq!(*stored.value(), [0u8; 32]);
+            let sealed = tables
+                .sealed_secrets
+                .get((SecretKind::Author as u8, author.id().as_bytes()))?
+                .unwrap();
+            assert!(!sealed.value().windows(32).any(|w| w == author.to_bytes()));
+        }
+
+        // while locked, secrets cannot be read or written
+        assert!(store.get_author(&author.id()).is_err());
+        assert!(store.has_author(&author.id())?);
+        assert!(store.new_author(&mut rand::thread_rng()).is_err());
+        assert!(store.get_encryption_key(&namespace.id()).is_err());
+        let ids: Vec<_> = store.list_author_ids()?.collect::<Result<_>>()?;
+        assert_eq!(ids.len(), 2);
+        let namespaces: Vec<_> = store.list_namespaces()?.collect::<Result<_>>()?;
+        assert!(namespaces
+            .iter()
+            .any(|(id, kind)| *id == namespace.id() && matches!(kind, CapabilityKind::Write)));
+        assert!(namespaces
+            .iter()
+            .any(|(id, kind)| *id == read_only.id() && matches!(kind, CapabilityKind::Read)));
+        let info = store.load_replica_info(&namespace.id())?;
+        assert!(matches!(info.capability, Capability::Read(_)));
+        store.close_replica(namespace.id());
+        assert!(matches!(
+            store.import_namespace(Capability::Read(namespace.id()))?,
+            ImportNamespaceOutcome::NoChange
+        ));
+
+        assert!(store.unlock_keystore("hunter3").is_err());
+        store.unlock_keystore("hunter2")?;
+        assert_eq!(
+            store.get_author(&author.id())?.unwrap().to_bytes(),
+            author.to_bytes()
+        );
+        assert_eq!(
+            store.get_author(&second.id())?.unwrap().to_bytes(),
+            second.to_bytes()
+        );
+        assert_eq!(
+            store.get_encryption_key(&namespace.id())?,
+            Some(key.clone())
+        );
+        let info = store.load_replica_info(&namespace.id())?;
+        assert!(matches!(info.capability, Capability::Write(_)));
+        assert_eq!(info.capability.id(), namespace.id());
+        store.close_replica(namespace.id());
+
+        store.lock_keystore()?;
+        assert!(store.get_author(&author.id()).is_err());
+        assert!(store.disable_keystore("hunter3").is_err());
+        store.disable_keystore("hunter2")?;
+        assert_eq!(store.keystore_status(), KeystoreStatus::Disabled);
+        let authors: Vec<_> = store.list_authors()?.collect::<Result<_>>()?;
+        assert_eq!(authors.len(), 2);
+        assert_eq!(store.get_encryption_key(&namespace.id())?, Some(key));
+        {
+            let tables = store.tables()?;
+            let stored = tables.authors.get(author.id().as_bytes())?.unwrap();
+            assert_eq!(*stored.value(), author.to_bytes());
+            assert!(tables.sealed_secrets.is_empty()?);
+        }
+
+        // a secret stored under the id of another author is rejected
+        store.modify(|tables| {
+            tables
+                .authors
+                .insert(author.id().as_bytes(), &second.to_bytes())?;
+            Ok(())
+        })?;
+        let err = store.get_author(&author.id()).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<KeystoreError>(),
+            Some(KeystoreError::IdMismatch)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_redeem_invite() -> Result<()> {
+        let mut store = Store::memory();
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+        store.import_namespace(namespace.clone().into())?;
+        store.set_access_policy(&namespace.id(), AccessPolicy::restricted([]))?;
+        let (node1, node2) = ([1u8; 32], [2u8; 32]);
+
+        let invite = Invite::new(&namespace, CapabilityKind::Read, None, true, None);
+        assert!(store.redeem_invite(&invite, &node1, 10)?);
+        // redeeming again is a no-op, but another node is rejected
+        assert!(!store.redeem_invite(&invite, &node1, 20)?);
+        let err = store.redeem_invite(&invite, &node2, 30).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<InviteError>(),
+            Some(InviteError::AlreadyRedeemed)
+        ));
+        assert!(store.get_access_policy(&namespace.id())?.allows(&node1));
+        assert!(!store.get_access_policy(&namespace.id())?.allows(&node2));
+
+        let reusable = Invite::new(&namespace, CapabilityKind::Write, None, false, None);
+        assert!(store.redeem_invite(&reusable, &node1, 40)?);
+        assert!(store.redeem_invite(&reusable, &node2, 50)?);
+        assert!(store.get_access_policy(&namespace.id())?.allows(&node2));
+
+        let redemptions = store.list_redemptions(&namespace.id())?;
+        assert_eq!(redemptions.len(), 3);
+        assert!(redemptions.contains(&Redemption {
+            invite: invite.id(),
+            node: node1,
+            mode: CapabilityKind::Read,
+            timestamp: 10,
+        }));
+
+        store.set_invite(&invite, &[node1])?;
+        assert_eq!(
+            store.get_invite(&namespace.id())?,
+            Some((invite.clone(), vec![node1]))
+        );
+
+        store.remove_replica(&namespace.id())?;
+        assert!(store.list_redemptions(&namespace.id())?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_inline_limit() -> Result<()> {
+        let mut store = Store::memory();
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+        store.import_namespace(namespace.clone().into())?;
+        let id = namespace.id();
+        assert_eq!(store.get_inline_limit(&id)?, 0);
+        store.set_inline_limit(&id, 1024)?;
+        assert_eq!(store.get_inline_limit(&id)?, 1024);
+        assert!(store.set_inline_limit(&id, MAX_INLINE_LIMIT + 1).is_err());
+
+        store.remove_replica(&id)?;
+        assert_eq!(store.get_inline_limit(&id)?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_trusted_marker_authors() -> Result<()> {
+        let mut store = Store::memory();
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+        let id = namespace.id();
+        let author = store.new_author(&mut rand::thread_rng())?.id();
+        assert!(store
+            .set_trusted_marker_authors(&id, BTreeSet::from([author]))
+            .is_err());
+        store.import_namespace(namespace.clone().into())?;
+        assert!(store.get_trusted_marker_authors(&id)?.is_empty());
+        store.set_trusted_marker_authors(&id, BTreeSet::from([author]))?;
+        assert_eq!(
+            store.get_trusted_marker_authors(&id)?,
+            BTreeSet::from([author])
+        );
+        store.set_trusted_marker_authors(&id, BTreeSet::new())?;
+        assert!(store.get_trusted_marker_authors(&id)?.is_empty());
+
+        store.set_trusted_marker_authors(&id, BTreeSet::from([author]))?;
+        store.remove_replica(&id)?;
+        assert!(store.get_trusted_marker_authors(&id)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_inline_content() -> Result<()> {
+        let mut store = Store::memory();
+        let author = store.new_author(&mut rand::thread_rng())?;
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+        let id = namespace.id();
+        let mut replica = store.new_replica(namespace.clone())?;
+        let hash = replica.hash_and_insert(b"a", &author, b"small")?;
+        replica.hash_and_insert(b"b", &author, b"other")?;
+        store.close_replica(id);
+
+        let record = RecordIdentifier::new(id, author.id(), b"a");
+        store.put_inline_content(&record, b"small")?;
+        assert_eq!(
+            store.get_inline_content(&record, &hash)?,
+            Some(Bytes::from_static(b"small"))
+        );
+        // content is only returned for the matching hash
+        assert_eq!(
+            store.get_inline_content(&record, &Hash::new(b"other"))?,
+            None
+        );
+        // content that the record does not reference is not stored
+        let other = RecordIdentifier::new(id, author.id(), b"b");
+        store.put_inline_content(&other, b"small")?;
+        assert_eq!(store.get_inline_content(&other, &hash)?, None);
+        let large = vec![0u8; MAX_INLINE_LIMIT as usize + 1];
+        let mut replica = store.open_replica(&id)?;
+        replica.hash_and_insert(b"b", &author, &large)?;
+        store.close_replica(id);
+        store.put_inline_content(&other, &large)?;
+        assert_eq!(store.get_inline_content(&other, &Hash::new(&large))?, None);
+
+        // the content is removed when the record is replaced, even by the same value
+        let mut replica = store.open_replica(&id)?;
+        replica.hash_and_insert(b"a", &author, b"small")?;
+        store.close_replica(id);
+        assert_eq!(store.get_inline_content(&record, &hash)?, None);
+
+        store.put_inline_content(&record, b"small")?;
+        let mut replica = store.open_replica(&id)?;
+        replica.delete_prefix(b"a", &author)?;
+        store.close_replica(id);
+        assert_eq!(store.get_inline_content(&record, &hash)?, None);
+
+        let mut replica = store.open_replica(&id)?;
+        replica.hash_and_insert(b"a", &author, b"newer")?;
+        store.close_replica(id);
+        store.put_inline_content(&record, b"newer")?;
+        assert!(store
+            .get_inline_content(&record, &Hash::new(b"newer"))?
+            .is_some());
+        store.remove_replica(&id)?;
+        assert_eq!(
+            store.get_inline_content(&record, &Hash::new(b"newer"))?,
+            None
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_index() -> Result<()> {
+        let mut store = Store::memory();
+        let author = store.new_author(&mut rand::thread_rng())?;
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+        let id = namespace.id();
+        let tasks = [
+            ("tasks/1", r#"{"status":"open"}"#),
+            ("tasks/2", r#"{"status":"done"}"#),
+            ("tasks/3", r#"{"status":"open","tags":["a"]}"#),
+            ("notes/1", r#"{"status":"open"}"#),
+        ];
+        let mut replica = store.new_replica(namespace.clone())?;
+        for (key, value) in tasks {
+            replica.hash_and_insert(key, &author, value)?;
+        }
+        store.close_replica(id);
+
+        let spec = IndexSpec::new("tasks/", FieldExtractor::Json("/status".to_string()));
+        store.create_index(&id, "status", &spec)?;
+        assert_eq!(
+            store.list_indexes(&id)?,
+            vec![("status".to_string(), spec.clone())]
+        );
+        for (key, value) in tasks {
+            let hash = Hash::new(value);
+            store.index_content(&id, &author.id(), key.as_bytes(), &hash, value.as_bytes())?;
+        }
+        let keys = |store: &mut Store, value: &str| -> Result<Vec<Bytes>> {
+            store
+                .get_many(id, Query::index("status", value))?
+                .map(|entry| entry.map(|entry| Bytes::copy_from_slice(entry.key())))
+                .collect()
+        };
+        assert_eq!(keys(&mut store, "open")?, vec!["tasks/1", "tasks/3"]);
+        assert_eq!(keys(&mut store, "done")?, vec!["tasks/2"]);
+
+        // a replaced entry is not found by its old value, and stale content is not indexed
+        let done = r#"{"status":"done"}"#;
+        let mut replica = store.open_replica(&id)?;
+        replica.hash_and_insert("tasks/1", &author, done)?;
+        store.close_replica(id);
+        assert_eq!(keys(&mut store, "open")?, vec!["tasks/3"]);
+        let (key, open) = tasks[0];
+        store.index_content(
+            &id,
+            &author.id(),
+            key.as_bytes(),
+            &Hash::new(open),
+            open.as_bytes(),
+        )?;
+        assert_eq!(keys(&mut store, "open")?, vec!["tasks/3"]);
+        store.index_content(
+            &id,
+            &author.id(),
+            key.as_bytes(),
+            &Hash::new(done),
+            done.as_bytes(),
+        )?;
+        assert_eq!(keys(&mut store, "done")?, vec!["tasks/1", "tasks/2"]);
+
+        let tags = IndexSpec::new("", FieldExtractor::Json("/tags".to_string()));
+        assert_eq!(
+            tags.extract(br#"{"tags":["b","a","b",1]}"#),
+            vec!["1", "a", "b"]
+        );
+        assert!(tags.extract(b"not json").is_empty());
+
+        assert!(store.get_many(id, Query::index("missing", "open")).is_err());
+        assert!(store.remove_index(&id, "status")?);
+        assert!(!store.remove_index(&id, "status")?);
+        assert!(store.get_many(id, Query::index("status", "open")).is_err());
+
+        store.create_index(&id, "status", &spec)?;
+        store.remove_replica(&id)?;
+        assert!(store.list_indexes(&id)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_compact_tombstones() -> Result<()> {
+        let mut store = Store::memory();
+        let author = store.new_author(&mut rand::thread_rng())?;
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+        let entry = |key: &str, value: &str, timestamp: u64| {
+            let id = RecordIdentifier::new(namespace.id(), author.id(), key);
+            let record = match value {
+                "" => Record::empty(timestamp),
+                value => Record::new(Hash::new(value), value.len() as u64, timestamp),
+            };
+            SignedEntry::from_entry(Entry::new(id, record), &namespace, &author)
+        };
+
+        let mut replica = store.new_replica(namespace.clone())?;
+        for e in [
+            entry("a", "1", 100),
+            entry("b", "2", 200),
+            entry("b", "", 300),
+        ] {
+            replica.insert_remote_entry(e, [1u8; 32], ContentStatus::Missing)?;
+        }
+        store.close_replica(namespace.id());
+        let count_all = |store: &mut Store| -> Result<usize> {
+            Ok(store
+                .get_many(namespace.id(), Query::all().include_empty())?
+                .count())
+        };
+        assert_eq!(count_all(&mut store)?, 2);
+
+        // tombstones are kept by default
+        assert_eq!(store.compact_tombstones(&namespace.id())?, 0);
+        assert_eq!(store.get_compaction_cutoff(&namespace.id())?, 0);
+
+        let policy = TombstonePolicy::Expire {
+            horizon: Duration::from_secs(60),
+        };
+        store.set_tombstone_policy(&namespace.id(), &policy)?;
+        assert_eq!(store.compact_tombstones(&namespace.id())?, 1);
+        assert_eq!(count_all(&mut store)?, 1);
+        let cutoff = store.get_compaction_cutoff(&namespace.id())?;
+        assert!(cutoff > 300);
+
+        // the deleted entry does not reappear
+        let mut replica = store.open_replica(&namespace.id())?;
+        let res =
+            replica.insert_remote_entry(entry("b", "2", 200), [1u8; 32], ContentStatus::Missing);
+        assert!(matches!(res, Err(InsertError::Compacted)));
+        store.close_replica(namespace.id());
+
+        // the latest entry of the author is updated
+        let latest = store
+            .get_latest_for_each_author(namespace.id())?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(latest.len(), 1);
+        assert_eq!(latest[0].1, 100);
+        assert_eq!(&latest[0].2[..], b"a");
+
+        // a peer with an older cutoff drops all its entries before the newer cutoff, but only if
+        // its own policy expires tombstones, and not after its own horizon
+        let mut other = Store::memory();
+        let mut replica = other.new_replica(namespace.clone())?;
+        for e in [entry("a", "1", 100), entry("b", "2", 200)] {
+            replica.insert_remote_entry(e, [1u8; 32], ContentStatus::Missing)?;
+        }
+        other.close_replica(namespace.id());
+        assert!(other
+            .adopt_compaction_cutoff(&namespace.id(), cutoff)
+            .is_err());
+        let policy = TombstonePolicy::Expire {
+            horizon: Duration::from_secs(3600),
+        };
+        other.set_tombstone_policy(&namespace.id(), &policy)?;
+        assert!(other
+            .adopt_compaction_cutoff(&namespace.id(), cutoff)
+            .is_err());
+        assert_eq!(other.adopt_compaction_cutoff(&namespace.id(), 150)?, 1);
+        assert_eq!(other.get_compaction_cutoff(&namespace.id())?, 150);
+        assert_eq!(other.adopt_compaction_cutoff(&namespace.id(), 150)?, 0);
+        assert_eq!(
+            other
+                .get_many(namespace.id(), Query::all().include_empty())?
+                .count(),
+            1
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_history() -> Result<()> {
+        let mut store = Store::memory();
+        let author = store.new_author(&mut rand::thread_rng())?;
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+        let retention = HistoryRetention {
+            max_versions: Some(2),
+            max_age: None,
+        };
+        let mut replica = store.new_replica(namespace.clone())?;
+        replica
+            .store
+            .store
+            .set_history_policy(&namespace.id(), HistoryPolicy::Enabled(retention))?;
+
+        let writes = [
+            ("config/a", "v1", 100),
+            ("config/a", "v2", 200),
+            ("config/a", "v3", 300),
+            ("config/b", "b1", 400),
+            ("config/", "", 500),
+        ];
+        for (key, value, timestamp) in writes {
+            let id = RecordIdentifier::new(namespace.id(), author.id(), key);
+            let record = match value {
+                "" => Record::empty(timestamp),
+                value => Record::new(Hash::new(value), value.len() as u64, timestamp),
+            };
+            let entry = SignedEntry::from_entry(Entry::new(id, record), &namespace, &author);
+            replica.insert_remote_entry(entry, [1u8; 32], ContentStatus::Missing)?;
+        }
+
+        // two versions with the same timestamp, the one with the larger hash wins
+        let mut same_timestamp = [Hash::new("s1"), Hash::new("s2")];
+        same_timestamp.sort();
+        for hash in same_timestamp {
+            let id = RecordIdentifier::new(namespace.id(), author.id(), "same");
+            let entry = SignedEntry::from_entry(
+                Entry::new(id, Record::new(hash, 2, 600)),
+                &namespace,
+                &author,
+            );
+            replica.insert_remote_entry(entry, [1u8; 32], ContentStatus::Missing)?;
+        }
+        store.close_replica(namespace.id());
+
+        // the prefix deletion removed all entries below `config/`
+        let entries = store
+            .get_many(namespace.id(), Query::key_prefix("config/"))?
+            .collect::<Result<Vec<_>>>()?;
+        assert!(entries.is_empty());
+
+        let mut keys_and_hashes = |query: Query| -> Result<Vec<(Vec<u8>, Hash)>> {
+            store
+                .get_many(namespace.id(), query)?
+                .map(|entry| entry.map(|entry| (entry.key().to_vec(), entry.content_hash())))
+                .collect()
+        };
+
+        // the state after the second write
+        assert_eq!(
+            keys_and_hashes(Query::all().as_of(250).build())?,
+            vec![(b"config/a".to_vec(), Hash::new("v2"))]
+        );
+
+        // the state before the deletion
+        assert_eq!(
+            keys_and_hashes(Query::single_latest_per_key().as_of(450).build())?,
+            vec![
+                (b"config/a".to_vec(), Hash::new("v3")),
+                (b"config/b".to_vec(), Hash::new("b1"))
+            ]
+        );
+
+        // all retained versions of a key, the oldest one was pruned
+        assert_eq!(
+            keys_and_hashes(Query::key_exact("config/a").include_history().build())?,
+            vec![
+                (b"config/a".to_vec(), Hash::new("v2")),
+                (b"config/a".to_vec(), Hash::new("v3"))
+            ]
+        );
+        assert!(keys_and_hashes(Query::all().as_of(150).build())?.is_empty());
+
+        // versions with the same timestamp are both kept
+        assert_eq!(
+            keys_and_hashes(Query::key_exact("same").include_history().build())?,
+            vec![
+                (b"same".to_vec(), same_timestamp[0]),
+                (b"same".to_vec(), same_timestamp[1])
+            ]
+        );
+
+        // the content of superseded entries is protected from garbage collection
+        let hashes = store.content_hashes()?.collect::<Result<HashSet<_>>>()?;
+        assert!(hashes.contains(&Hash::new("v3")));
+
+        // disabling the history removes it
+        store.set_history_policy(&namespace.id(), HistoryPolicy::Disabled)?;
+        let entries = store
+            .get_many(namespace.id(), Query::all().as_of(450))?
+            .collect::<Result<Vec<_>>>()?;
+        assert!(entries.is_empty());
+        Ok(())
+    }
+
     fn copy_and_modify(
         source: &Path,
         modify: impl Fn(&redb::WriteTransaction) -> Result<()>,
@@ -1144,4 +3158,59 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_migration_005_populate_by_timestamp_index() -> Result<()> {
+        let dbfile = tempfile::NamedTempFile::new()?;
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+        let query = || {
+            Query::all()
+                .sort_by(SortBy::Timestamp, SortDirection::Desc)
+                .include_empty()
+        };
+        let keys = |store: &mut Store| -> Result<Vec<Vec<u8>>> {
+            store
+                .get_many(namespace.id(), query())?
+                .map(|entry| entry.map(|entry| entry.key().to_vec()))
+                .collect()
+        };
+
+        // create a store and add some data
+        let expected = {
+            let mut store = Store::persistent(dbfile.path())?;
+            let author = store.new_author(&mut rand::thread_rng())?;
+            let mut replica = store.new_replica(namespace.clone())?;
+            replica.hash_and_insert(b"k1", &author, b"v1")?;
+            replica.hash_and_insert(b"k2", &author, b"v1")?;
+            replica.hash_and_insert(b"k3/a", &author, b"v1")?;
+            replica.hash_and_insert(b"k1", &author, b"v2")?;
+            replica.delete_prefix(b"k3", &author)?;
+            store.close_replica(namespace.id());
+
+            // replaced and removed records are removed from the index
+            {
+                let tables = store.tables()?;
+                assert_eq!(tables.records_by_timestamp.len()?, 3);
+            }
+            let expected = keys(&mut store)?;
+            store.flush()?;
+            drop(store);
+            expected
+        };
+        assert_eq!(
+            expected,
+            vec![b"k3".to_vec(), b"k1".to_vec(), b"k2".to_vec()]
+        );
+
+        // create a copy of our db file with the index table deleted.
+        let dbfile_before_migration = copy_and_modify(dbfile.path(), |tx| {
+            tx.delete_table(RECORDS_BY_TIMESTAMP_TABLE)?;
+            Ok(())
+        })?;
+
+        // open the copied db file, which will run the migration.
+        let mut store = Store::persistent(dbfile_before_migration.path())?;
+        assert_eq!(keys(&mut store)?, expected);
+        Ok(())
+    }
 }
@@ -2,9 +2,16 @@ use std::ops::{Bound, RangeBounds};
 
 use bytes::Bytes;
 
-use crate::{store::KeyFilter, AuthorId, NamespaceId};
+use crate::{
+    store::{KeyFilter, SortDirection},
+    AuthorId, NamespaceId,
+};
 
-use super::tables::{RecordsByKeyId, RecordsByKeyIdOwned, RecordsId, RecordsIdOwned};
+use super::tables::{
+    HistoryId, HistoryIdOwned, IndexEntriesKey, IndexEntriesKeyOwned, RecordsByKeyId,
+    RecordsByKeyIdOwned, RecordsByTimestampId, RecordsByTimestampIdOwned, RecordsId,
+    RecordsIdOwned,
+};
 
 /// Bounds on the records table.
 ///
@@ -17,11 +24,27 @@ impl RecordsBounds {
     }
 
     pub fn author_key(ns: NamespaceId, author: AuthorId, key_matcher: KeyFilter) -> Self {
+        if let KeyFilter::Range { start, end } = key_matcher {
+            let start = match start {
+                Bound::Included(key) => Bound::Included((ns.to_bytes(), author.to_bytes(), key)),
+                Bound::Excluded(key) => Bound::Excluded((ns.to_bytes(), author.to_bytes(), key)),
+                Bound::Unbounded => {
+                    Bound::Included((ns.to_bytes(), author.to_bytes(), Bytes::new()))
+                }
+            };
+            let end = match end {
+                Bound::Included(key) => Bound::Included((ns.to_bytes(), author.to_bytes(), key)),
+                Bound::Excluded(key) => Bound::Excluded((ns.to_bytes(), author.to_bytes(), key)),
+                Bound::Unbounded => Self::author_key(ns, author, KeyFilter::Any).1,
+            };
+            return Self(start, end);
+        }
         let key_is_exact = matches!(key_matcher, KeyFilter::Exact(_));
         let key = match key_matcher {
             KeyFilter::Any => Bytes::new(),
             KeyFilter::Exact(key) => key,
             KeyFilter::Prefix(prefix) => prefix,
+            KeyFilter::Range { .. } => unreachable!("handled above"),
         };
         let author = author.to_bytes();
         let ns = ns.to_bytes();
@@ -62,6 +85,18 @@ impl RecordsBounds {
         Self::new(start, Self::namespace_end(ns))
     }
 
+    /// Restrict the bounds to the records after `id` in `direction`.
+    pub fn after(self, id: RecordsIdOwned, direction: &SortDirection) -> Self {
+        match direction {
+            SortDirection::Asc => Self(max_start(self.0, Bound::Excluded(id)), self.1),
+            SortDirection::Desc => Self(self.0, min_end(self.1, Bound::Excluded(id))),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        is_empty(&self.0, &self.1)
+    }
+
     pub fn as_ref(&self) -> (Bound<RecordsId>, Bound<RecordsId>) {
         fn map(id: &RecordsIdOwned) -> RecordsId {
             (&id.0, &id.1, &id.2[..])
@@ -126,6 +161,29 @@ impl ByKeyBounds {
                 };
                 Self(start, end)
             }
+            KeyFilter::Range { start, end } => {
+                let namespace = Self::namespace(ns);
+                // the bounds of the author part are chosen to include or exclude all authors
+                let start = match start {
+                    Bound::Included(key) => {
+                        Bound::Included((ns.to_bytes(), key.clone(), [0u8; 32]))
+                    }
+                    Bound::Excluded(key) => {
+                        Bound::Excluded((ns.to_bytes(), key.clone(), [255u8; 32]))
+                    }
+                    Bound::Unbounded => namespace.0,
+                };
+                let end = match end {
+                    Bound::Included(key) => {
+                        Bound::Included((ns.to_bytes(), key.clone(), [255u8; 32]))
+                    }
+                    Bound::Excluded(key) => {
+                        Bound::Excluded((ns.to_bytes(), key.clone(), [0u8; 32]))
+                    }
+                    Bound::Unbounded => namespace.1,
+                };
+                Self(start, end)
+            }
         }
     }
 
@@ -140,6 +198,18 @@ impl ByKeyBounds {
         Self(start, end)
     }
 
+    /// Restrict the bounds to the records after `id` in `direction`.
+    pub fn after(self, id: RecordsByKeyIdOwned, direction: &SortDirection) -> Self {
+        match direction {
+            SortDirection::Asc => Self(max_start(self.0, Bound::Excluded(id)), self.1),
+            SortDirection::Desc => Self(self.0, min_end(self.1, Bound::Excluded(id))),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        is_empty(&self.0, &self.1)
+    }
+
     pub fn as_ref(&self) -> (Bound<RecordsByKeyId>, Bound<RecordsByKeyId>) {
         fn map(id: &RecordsByKeyIdOwned) -> RecordsByKeyId {
             (&id.0, &id.1[..], &id.2)
@@ -158,6 +228,137 @@ impl RangeBounds<RecordsByKeyIdOwned> for ByKeyBounds {
     }
 }
 
+/// Bounds for the by-timestamp index table.
+///
+/// Supports bounds by namespace.
+pub struct ByTimestampBounds(
+    Bound<RecordsByTimestampIdOwned>,
+    Bound<RecordsByTimestampIdOwned>,
+);
+
+impl ByTimestampBounds {
+    pub fn namespace(ns: NamespaceId) -> Self {
+        let start = Bound::Included((ns.to_bytes(), 0, [0u8; 32], Bytes::new()));
+        let mut ns_end = ns.to_bytes();
+        let end = if increment_by_one(&mut ns_end) {
+            Bound::Excluded((ns_end, 0, [0u8; 32], Bytes::new()))
+        } else {
+            Bound::Unbounded
+        };
+        Self(start, end)
+    }
+
+    /// Restrict the bounds to the records after `id` in `direction`.
+    pub fn after(self, id: RecordsByTimestampIdOwned, direction: &SortDirection) -> Self {
+        match direction {
+            SortDirection::Asc => Self(max_start(self.0, Bound::Excluded(id)), self.1),
+            SortDirection::Desc => Self(self.0, min_end(self.1, Bound::Excluded(id))),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        is_empty(&self.0, &self.1)
+    }
+
+    pub fn as_ref(&self) -> (Bound<RecordsByTimestampId>, Bound<RecordsByTimestampId>) {
+        fn map(id: &RecordsByTimestampIdOwned) -> RecordsByTimestampId {
+            (&id.0, id.1, &id.2, &id.3[..])
+        }
+        (map_bound(&self.0, map), map_bound(&self.1, map))
+    }
+}
+
+/// Bounds for the index entries table.
+///
+/// Supports bounds by indexed value.
+pub struct IndexEntriesBounds(Bound<IndexEntriesKeyOwned>, Bound<IndexEntriesKeyOwned>);
+
+impl IndexEntriesBounds {
+    pub fn value(ns: NamespaceId, name: &str, value: &[u8]) -> Self {
+        let start = Bound::Included((
+            ns.to_bytes(),
+            name.to_string(),
+            Bytes::copy_from_slice(value),
+            [0u8; 32],
+            Bytes::new(),
+        ));
+        // the smallest value which is greater than `value`
+        let mut value_end = value.to_vec();
+        value_end.push(0);
+        let end = Bound::Excluded((
+            ns.to_bytes(),
+            name.to_string(),
+            value_end.into(),
+            [0u8; 32],
+            Bytes::new(),
+        ));
+        Self(start, end)
+    }
+
+    /// Restrict the bounds to the index entries after `id` in `direction`.
+    pub fn after(self, id: IndexEntriesKeyOwned, direction: &SortDirection) -> Self {
+        match direction {
+            SortDirection::Asc => Self(max_start(self.0, Bound::Excluded(id)), self.1),
+            SortDirection::Desc => Self(self.0, min_end(self.1, Bound::Excluded(id))),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        is_empty(&self.0, &self.1)
+    }
+
+    pub fn as_ref(&self) -> (Bound<IndexEntriesKey>, Bound<IndexEntriesKey>) {
+        fn map(id: &IndexEntriesKeyOwned) -> IndexEntriesKey {
+            (&id.0, &id.1, &id.2[..], &id.3, &id.4[..])
+        }
+        (map_bound(&self.0, map), map_bound(&self.1, map))
+    }
+}
+
+/// Bounds for the history table.
+///
+/// Supports bounds by namespace, and by author and key.
+pub struct HistoryBounds(Bound<HistoryIdOwned>, Bound<HistoryIdOwned>);
+
+impl HistoryBounds {
+    pub fn namespace(ns: NamespaceId) -> Self {
+        Self::from_records(&RecordsBounds::namespace(ns))
+    }
+
+    pub fn author_key(ns: NamespaceId, author: AuthorId, key: Bytes) -> Self {
+        Self::from_records(&RecordsBounds::author_key(
+            ns,
+            author,
+            KeyFilter::Exact(key),
+        ))
+    }
+
+    /// The bounds on the history of the records within `bounds`.
+    pub fn from_records(bounds: &RecordsBounds) -> Self {
+        let first = |(ns, author, key): &RecordsIdOwned| (*ns, *author, key.clone(), 0, [0u8; 32]);
+        let last =
+            |(ns, author, key): &RecordsIdOwned| (*ns, *author, key.clone(), u64::MAX, [255u8; 32]);
+        let start = match &bounds.0 {
+            Bound::Included(id) => Bound::Included(first(id)),
+            Bound::Excluded(id) => Bound::Excluded(last(id)),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        let end = match &bounds.1 {
+            Bound::Included(id) => Bound::Included(last(id)),
+            Bound::Excluded(id) => Bound::Excluded(first(id)),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        Self(start, end)
+    }
+
+    pub fn as_ref(&self) -> (Bound<HistoryId>, Bound<HistoryId>) {
+        fn map(id: &HistoryIdOwned) -> HistoryId {
+            (&id.0, &id.1, &id.2[..], id.3, &id.4)
+        }
+        (map_bound(&self.0, map), map_bound(&self.1, map))
+    }
+}
+
 /// Increment a byte string by one, by incrementing the last byte that is not 255 by one.
 ///
 /// Returns false if all bytes are 255.
@@ -173,6 +374,51 @@ fn increment_by_one(value: &mut [u8]) -> bool {
     false
 }
 
+/// The greater of two start bounds.
+fn max_start<T: Ord>(a: Bound<T>, b: Bound<T>) -> Bound<T> {
+    match (a, b) {
+        (Bound::Unbounded, bound) | (bound, Bound::Unbounded) => bound,
+        (Bound::Included(a), Bound::Included(b)) => Bound::Included(a.max(b)),
+        (Bound::Excluded(a), Bound::Excluded(b)) => Bound::Excluded(a.max(b)),
+        (Bound::Included(i), Bound::Excluded(e)) | (Bound::Excluded(e), Bound::Included(i)) => {
+            if e >= i {
+                Bound::Excluded(e)
+            } else {
+                Bound::Included(i)
+            }
+        }
+    }
+}
+
+/// The lesser of two end bounds.
+fn min_end<T: Ord>(a: Bound<T>, b: Bound<T>) -> Bound<T> {
+    match (a, b) {
+        (Bound::Unbounded, bound) | (bound, Bound::Unbounded) => bound,
+        (Bound::Included(a), Bound::Included(b)) => Bound::Included(a.min(b)),
+        (Bound::Excluded(a), Bound::Excluded(b)) => Bound::Excluded(a.min(b)),
+        (Bound::Included(i), Bound::Excluded(e)) | (Bound::Excluded(e), Bound::Included(i)) => {
+            if e <= i {
+                Bound::Excluded(e)
+            } else {
+                Bound::Included(i)
+            }
+        }
+    }
+}
+
+/// Whether no value is within the bounds.
+///
+/// Ranges with a start after their end are invalid for [`redb`] tables.
+fn is_empty<T: Ord>(start: &Bound<T>, end: &Bound<T>) -> bool {
+    match (start, end) {
+        (Bound::Included(start), Bound::Included(end)) => start > end,
+        (Bound::Included(start), Bound::Excluded(end))
+        | (Bound::Excluded(start), Bound::Included(end))
+        | (Bound::Excluded(start), Bound::Excluded(end)) => start >= end,
+        _ => false,
+    }
+}
+
 fn map_bound<'a, T, U: 'a>(bound: &'a Bound<T>, f: impl Fn(&'a T) -> U) -> Bound<U> {
     match bound {
         Bound::Unbounded => Bound::Unbounded,
@@ -292,5 +538,27 @@ mod tests {
             bounds.end_bound(),
             Bound::Included(&(ns.to_bytes(), vec![1u8].into(), [255u8; 32]))
         );
+
+        let range = KeyFilter::Range {
+            start: Bound::Excluded(vec![1u8].into()),
+            end: Bound::Excluded(vec![3u8].into()),
+        };
+        let bounds = ByKeyBounds::new(ns, &range);
+        assert_eq!(
+            bounds.start_bound(),
+            Bound::Excluded(&(ns.to_bytes(), vec![1u8].into(), [255u8; 32]))
+        );
+        assert_eq!(
+            bounds.end_bound(),
+            Bound::Excluded(&(ns.to_bytes(), vec![3u8].into(), [0u8; 32]))
+        );
+
+        let cursor: RecordsByKeyIdOwned = (ns.to_bytes(), vec![2u8].into(), [1u8; 32]);
+        let bounds = bounds.after(cursor.clone(), &SortDirection::Asc);
+        assert_eq!(bounds.start_bound(), Bound::Excluded(&cursor));
+        assert!(!bounds.is_empty());
+        let cursor = (ns.to_bytes(), vec![4u8].into(), [1u8; 32]);
+        let bounds = bounds.after(cursor, &SortDirection::Asc);
+        assert!(bounds.is_empty());
     }
 }
@@ -8,7 +8,7 @@ use crate::{Capability, NamespaceSecret};
 
 use super::tables::{
     LATEST_PER_AUTHOR_TABLE, NAMESPACES_TABLE, NAMESPACES_TABLE_V1, RECORDS_BY_KEY_TABLE,
-    RECORDS_TABLE,
+    RECORDS_BY_TIMESTAMP_TABLE, RECORDS_TABLE,
 };
 
 /// Run all database migrations, if needed.
@@ -17,6 +17,7 @@ pub fn run_migrations(db: &Database) -> Result<()> {
     run_migration(db, migration_002_namespaces_populate_v2)?;
     run_migration(db, migration_003_namespaces_delete_v1)?;
     run_migration(db, migration_004_populate_by_key_index)?;
+    run_migration(db, migration_005_populate_by_timestamp_index)?;
     Ok(())
 }
 
@@ -137,3 +138,24 @@ fn migration_004_populate_by_key_index(tx: &WriteTransaction) -> Result<MigrateO
     }
     Ok(MigrateOutcome::Execute(len))
 }
+
+/// migration 005: populate the by_timestamp index table (which did not exist before)
+fn migration_005_populate_by_timestamp_index(tx: &WriteTransaction) -> Result<MigrateOutcome> {
+    let mut by_timestamp_table = tx.open_table(RECORDS_BY_TIMESTAMP_TABLE)?;
+    let records_table = tx.open_table(RECORDS_TABLE)?;
+    if !by_timestamp_table.is_empty()? || records_table.is_empty()? {
+        return Ok(MigrateOutcome::Skip);
+    }
+
+    let iter = records_table.iter()?;
+    let mut len = 0;
+    for next in iter {
+        let next = next?;
+        let (namespace, author, key) = next.0.value();
+        let (timestamp, _namespace_sig, _author_sig, _len, _hash) = next.1.value();
+        let id = (namespace, timestamp, author, key);
+        by_timestamp_table.insert(id, ())?;
+        len += 1;
+    }
+    Ok(MigrateOutcome::Execute(len))
+}
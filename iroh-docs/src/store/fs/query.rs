@@ -1,3 +1,9 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BTreeMap, BinaryHeap},
+    iter::Peekable,
+};
+
 use anyhow::Result;
 use iroh_base::hash::Hash;
 
@@ -5,14 +11,15 @@ use crate::{
     store::{
         fs::tables::ReadOnlyTables,
         util::{IndexKind, LatestPerKeySelector, SelectorRes},
-        AuthorFilter, KeyFilter, Query,
+        AuthorFilter, Cursor, FlatQuery, KeyFilter, Query, QueryKind, SortBy, SortDirection,
     },
-    AuthorId, NamespaceId, SignedEntry,
+    AuthorId, NamespaceId, Record, SignedEntry,
 };
 
 use super::{
-    bounds::{ByKeyBounds, RecordsBounds},
-    ranges::{RecordsByKeyRange, RecordsRange},
+    bounds::{ByKeyBounds, ByTimestampBounds, HistoryBounds, IndexEntriesBounds, RecordsBounds},
+    history_into_entry,
+    ranges::{IndexEntriesRange, RecordsByKeyRange, RecordsByTimestampRange, RecordsRange},
     RecordsValue,
 };
 
@@ -26,6 +33,7 @@ pub struct QueryIterator {
 }
 
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 enum QueryRange {
     AuthorKey {
         range: RecordsRange<'static>,
@@ -36,10 +44,86 @@ enum QueryRange {
         author_filter: AuthorFilter,
         selector: Option<LatestPerKeySelector>,
     },
+    Timestamp {
+        range: RecordsByTimestampRange,
+    },
+    Index {
+        range: IndexEntriesRange,
+    },
+    Sorted {
+        entries: std::vec::IntoIter<SignedEntry>,
+    },
 }
 
 impl QueryIterator {
     pub fn new(tables: ReadOnlyTables, namespace: NamespaceId, query: Query) -> Result<Self> {
+        if let QueryKind::Index(index) = &query.kind {
+            anyhow::ensure!(
+                query.as_of.is_none(),
+                "index queries cannot be combined with as_of"
+            );
+            let name = index.name.as_str();
+            anyhow::ensure!(
+                tables.indexes.get((namespace.as_bytes(), name))?.is_some(),
+                "index {name} not found"
+            );
+            let mut bounds = IndexEntriesBounds::value(namespace, name, &index.value);
+            if let Some(cursor) = &query.cursor {
+                // index entries with the same value are sorted by author, then key
+                let id = (
+                    namespace.to_bytes(),
+                    name.to_string(),
+                    index.value.clone(),
+                    cursor.author().to_bytes(),
+                    cursor.key().clone(),
+                );
+                bounds = bounds.after(id, &query.sort_direction);
+            }
+            if bounds.is_empty() {
+                return Ok(Self::sorted(Vec::new(), query));
+            }
+            let range =
+                IndexEntriesRange::with_bounds(tables.index_entries, tables.records, bounds)?;
+            return Ok(Self {
+                range: QueryRange::Index { range },
+                query,
+                offset: 0,
+                count: 0,
+            });
+        }
+        if query.as_of.is_some() || query.include_history {
+            let entries = history_entries(&tables, namespace, &query)?;
+            return Ok(Self::sorted(entries, query));
+        }
+        if let QueryKind::Flat(FlatQuery {
+            sort_by: SortBy::Timestamp,
+        }) = query.kind
+        {
+            let mut bounds = ByTimestampBounds::namespace(namespace);
+            if let Some(cursor) = &query.cursor {
+                let id = (
+                    namespace.to_bytes(),
+                    cursor.timestamp(),
+                    cursor.author().to_bytes(),
+                    cursor.key().clone(),
+                );
+                bounds = bounds.after(id, &query.sort_direction);
+            }
+            if bounds.is_empty() {
+                return Ok(Self::sorted(Vec::new(), query));
+            }
+            let range = RecordsByTimestampRange::with_bounds(
+                tables.records_by_timestamp,
+                tables.records,
+                bounds,
+            )?;
+            return Ok(Self {
+                range: QueryRange::Timestamp { range },
+                query,
+                offset: 0,
+                count: 0,
+            });
+        }
         let index_kind = IndexKind::from(&query);
         let range = match index_kind {
             IndexKind::AuthorKey { range, key_filter } => {
@@ -53,6 +137,20 @@ impl QueryIterator {
                     // no author set => full table scan with the provided key filter
                     AuthorFilter::Any => (RecordsBounds::namespace(namespace), key_filter),
                 };
+                let bounds = match &query.cursor {
+                    Some(cursor) => {
+                        let id = (
+                            namespace.to_bytes(),
+                            cursor.author().to_bytes(),
+                            cursor.key().clone(),
+                        );
+                        bounds.after(id, &query.sort_direction)
+                    }
+                    None => bounds,
+                };
+                if bounds.is_empty() {
+                    return Ok(Self::sorted(Vec::new(), query));
+                }
                 let range = RecordsRange::with_bounds_static(&tables.records, bounds)?;
                 QueryRange::AuthorKey {
                     range,
@@ -64,7 +162,20 @@ impl QueryIterator {
                 author_filter,
                 latest_per_key,
             } => {
-                let bounds = ByKeyBounds::new(namespace, &range);
+                let mut bounds = ByKeyBounds::new(namespace, &range);
+                if let Some(cursor) = &query.cursor {
+                    // latest-per-key queries continue after all entries for the key of the cursor
+                    let author = match (latest_per_key, &query.sort_direction) {
+                        (false, _) => cursor.author().to_bytes(),
+                        (true, SortDirection::Asc) => [255u8; 32],
+                        (true, SortDirection::Desc) => [0u8; 32],
+                    };
+                    let id = (namespace.to_bytes(), cursor.key().clone(), author);
+                    bounds = bounds.after(id, &query.sort_direction);
+                }
+                if bounds.is_empty() {
+                    return Ok(Self::sorted(Vec::new(), query));
+                }
                 let range =
                     RecordsByKeyRange::with_bounds(tables.records_by_key, tables.records, bounds)?;
                 let selector = latest_per_key.then(LatestPerKeySelector::default);
@@ -83,6 +194,18 @@ impl QueryIterator {
             count: 0,
         })
     }
+
+    /// Create an iterator over entries that are already filtered and sorted.
+    fn sorted(entries: Vec<SignedEntry>, query: Query) -> Self {
+        Self {
+            range: QueryRange::Sorted {
+                entries: entries.into_iter(),
+            },
+            query,
+            offset: 0,
+            count: 0,
+        }
+    }
 }
 
 impl Iterator for QueryIterator {
@@ -140,6 +263,44 @@ impl Iterator for QueryIterator {
 
                     break next.map(Result::Ok);
                 },
+
+                QueryRange::Timestamp { range } => loop {
+                    // get the next entry from the query range, filtered by the author and key
+                    // filters
+                    let query = &self.query;
+                    let next = range.next_filtered(
+                        &query.sort_direction,
+                        |(_ns, _timestamp, author, key)| {
+                            query.filter_author.matches(&AuthorId::from(author))
+                                && query.filter_key.matches(key)
+                        },
+                    );
+                    // skip the entry if empty and no empty entries requested
+                    if !query.include_empty && matches!(&next, Some(Ok(e)) if e.is_empty()) {
+                        continue;
+                    }
+                    break next;
+                },
+
+                QueryRange::Index { range } => loop {
+                    // get the next entry from the index, filtered by the author and key filters
+                    let query = &self.query;
+                    let next = range.next_filtered(
+                        &query.sort_direction,
+                        |(_ns, _name, _value, author, key)| {
+                            query.filter_author.matches(&AuthorId::from(author))
+                                && query.filter_key.matches(key)
+                        },
+                    );
+                    // skip the entry if empty and no empty entries requested
+                    if !query.include_empty && matches!(&next, Some(Ok(e)) if e.is_empty()) {
+                        continue;
+                    }
+                    break next;
+                },
+
+                // entries are already filtered and sorted
+                QueryRange::Sorted { entries } => entries.next().map(Result::Ok),
             };
 
             // skip the entry if we didn't get past the requested offset yet.
@@ -158,3 +319,338 @@ fn value_is_empty(value: &RecordsValue) -> bool {
     let (_timestamp, _namespace_sig, _author_sig, _len, hash) = value;
     *hash == Hash::EMPTY.as_bytes()
 }
+
+/// Collect the results of a query that looks into the history of a document.
+///
+/// The state of the document at a point in time is reconstructed from both the current entries
+/// and the superseded entries in the history table. Both tables are iterated together over the
+/// bounds of the query, and only the entries up to the limit of the query are kept in memory.
+fn history_entries(
+    tables: &ReadOnlyTables,
+    namespace: NamespaceId,
+    query: &Query,
+) -> Result<Vec<SignedEntry>> {
+    let as_of = query.as_of.unwrap_or(u64::MAX);
+    // for `SingleLatestPerKey` queries the author filter is applied after the grouping
+    let author_filter = match query.kind {
+        QueryKind::Flat(_) | QueryKind::Index(_) => query.filter_author.clone(),
+        QueryKind::SingleLatestPerKey(_) => AuthorFilter::Any,
+    };
+    // the range is not bounded by the key filter if superseded versions are skipped, because
+    // an entry may be superseded by an entry for a prefix of its key.
+    let key_bounds = match query.include_history {
+        true => query.filter_key.clone(),
+        false => KeyFilter::Any,
+    };
+    let bounds = match author_filter {
+        AuthorFilter::Exact(author) => RecordsBounds::author_key(namespace, author, key_bounds),
+        AuthorFilter::Any => RecordsBounds::namespace(namespace),
+    };
+    let history = tables
+        .history
+        .range(HistoryBounds::from_records(&bounds).as_ref())?
+        .map(|item| {
+            let (key, value) = item?;
+            Ok(history_into_entry(key.value(), value.value()))
+        });
+    let records = RecordsRange::with_bounds(&tables.records, bounds)?;
+    let versions = MergeVersions::new(history, records).filter(|entry| match entry {
+        Ok(entry) => entry.timestamp() <= as_of && author_filter.matches(&entry.author()),
+        Err(_) => true,
+    });
+    let versions: Box<dyn Iterator<Item = Result<SignedEntry>>> = match query.include_history {
+        true => Box::new(versions),
+        false => Box::new(LatestVersions::new(versions)),
+    };
+    let entries = versions.filter(|entry| match entry {
+        Ok(entry) => {
+            query.filter_key.matches(entry.key()) && (query.include_empty || !entry.is_empty())
+        }
+        Err(_) => true,
+    });
+    let count = query
+        .limit
+        .map(|limit| limit.saturating_add(query.offset) as usize);
+    let is_after_cursor = |entry: &Result<SignedEntry>| match (entry, &query.cursor) {
+        (Ok(entry), Some(cursor)) => is_after(query, entry, cursor),
+        _ => true,
+    };
+    let direction = &query.sort_direction;
+    match &query.kind {
+        QueryKind::Flat(FlatQuery {
+            sort_by: SortBy::AuthorKey,
+        })
+        | QueryKind::Index(_) => select_sorted(
+            entries.filter(is_after_cursor),
+            |e| {
+                (
+                    e.author(),
+                    e.key().to_vec(),
+                    e.timestamp(),
+                    e.content_hash(),
+                )
+            },
+            direction,
+            count,
+        ),
+        QueryKind::Flat(FlatQuery {
+            sort_by: SortBy::KeyAuthor,
+        }) => select_sorted(
+            entries.filter(is_after_cursor),
+            |e| {
+                (
+                    e.key().to_vec(),
+                    e.author(),
+                    e.timestamp(),
+                    e.content_hash(),
+                )
+            },
+            direction,
+            count,
+        ),
+        QueryKind::Flat(FlatQuery {
+            sort_by: SortBy::Timestamp,
+        }) => select_sorted(
+            entries.filter(is_after_cursor),
+            |e| {
+                (
+                    e.timestamp(),
+                    e.author(),
+                    e.key().to_vec(),
+                    e.content_hash(),
+                )
+            },
+            direction,
+            count,
+        ),
+        QueryKind::SingleLatestPerKey(_) => {
+            // keep the latest entry for each key. if all authors are selected, only the keys
+            // up to the limit of the query have to be kept.
+            let count = count.filter(|_| matches!(query.filter_author, AuthorFilter::Any));
+            let mut latest: BTreeMap<Vec<u8>, SignedEntry> = BTreeMap::new();
+            for entry in entries.filter(is_after_cursor) {
+                let entry = entry?;
+                match latest.get_mut(entry.key()) {
+                    Some(current) if current.record() >= entry.record() => {}
+                    Some(current) => *current = entry,
+                    None => {
+                        latest.insert(entry.key().to_vec(), entry);
+                        if count.is_some_and(|count| latest.len() > count) {
+                            match direction {
+                                SortDirection::Asc => latest.pop_last(),
+                                SortDirection::Desc => latest.pop_first(),
+                            };
+                        }
+                    }
+                }
+            }
+            let entries = latest
+                .into_values()
+                .filter(|entry| query.filter_author.matches(&entry.author()));
+            Ok(match direction {
+                SortDirection::Asc => entries.collect(),
+                SortDirection::Desc => entries.rev().collect(),
+            })
+        }
+    }
+}
+
+/// An entry ordered by its position in the sort order of a query.
+#[derive(Debug)]
+struct ByPosition<K>(K, SignedEntry);
+
+impl<K: Ord> PartialEq for ByPosition<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<K: Ord> Eq for ByPosition<K> {}
+
+impl<K: Ord> PartialOrd for ByPosition<K> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K: Ord> Ord for ByPosition<K> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+/// Select the first `count` entries in the order given by `position` and `direction`, or all
+/// entries if `count` is `None`.
+fn select_sorted<K: Ord>(
+    entries: impl Iterator<Item = Result<SignedEntry>>,
+    position: impl Fn(&SignedEntry) -> K,
+    direction: &SortDirection,
+    count: Option<usize>,
+) -> Result<Vec<SignedEntry>> {
+    let entries = entries.map(|entry| entry.map(|entry| ByPosition(position(&entry), entry)));
+    Ok(match direction {
+        SortDirection::Asc => select_first(entries, count)?
+            .into_iter()
+            .map(|e| e.1)
+            .collect(),
+        SortDirection::Desc => select_first(entries.map(|e| e.map(Reverse)), count)?
+            .into_iter()
+            .map(|e| e.0 .1)
+            .collect(),
+    })
+}
+
+/// Select the `count` smallest items in ascending order, or all items if `count` is `None`.
+fn select_first<T: Ord>(
+    items: impl Iterator<Item = Result<T>>,
+    count: Option<usize>,
+) -> Result<Vec<T>> {
+    let mut heap = BinaryHeap::new();
+    for item in items {
+        heap.push(item?);
+        if count.is_some_and(|count| heap.len() > count) {
+            heap.pop();
+        }
+    }
+    Ok(heap.into_sorted_vec())
+}
+
+/// Whether `entry` comes after `cursor` in the sort order of `query`.
+fn is_after(query: &Query, entry: &SignedEntry, cursor: &Cursor) -> bool {
+    let entry_pos = (entry.timestamp(), entry.author(), entry.key());
+    let cursor_pos = (cursor.timestamp(), cursor.author(), &cursor.key()[..]);
+    let ordering = match &query.kind {
+        QueryKind::Flat(FlatQuery {
+            sort_by: SortBy::AuthorKey,
+        })
+        | QueryKind::Index(_) => {
+            (entry_pos.1, entry_pos.2, entry_pos.0).cmp(&(cursor_pos.1, cursor_pos.2, cursor_pos.0))
+        }
+        QueryKind::Flat(FlatQuery {
+            sort_by: SortBy::KeyAuthor,
+        }) => {
+            (entry_pos.2, entry_pos.1, entry_pos.0).cmp(&(cursor_pos.2, cursor_pos.1, cursor_pos.0))
+        }
+        QueryKind::Flat(FlatQuery {
+            sort_by: SortBy::Timestamp,
+        }) => entry_pos.cmp(&cursor_pos),
+        QueryKind::SingleLatestPerKey(_) => entry_pos.2.cmp(cursor_pos.2),
+    };
+    match query.sort_direction {
+        SortDirection::Asc => ordering == Ordering::Greater,
+        SortDirection::Desc => ordering == Ordering::Less,
+    }
+}
+
+/// Merges the superseded versions from the history table with the current entries.
+///
+/// Both iterators must be sorted by author and key. The versions are yielded grouped by author
+/// and key, with the superseded versions before the current entry.
+struct MergeVersions<H: Iterator, R: Iterator> {
+    history: Peekable<H>,
+    records: Peekable<R>,
+}
+
+impl<H, R> MergeVersions<H, R>
+where
+    H: Iterator<Item = Result<SignedEntry>>,
+    R: Iterator<Item = Result<SignedEntry>>,
+{
+    fn new(history: H, records: R) -> Self {
+        Self {
+            history: history.peekable(),
+            records: records.peekable(),
+        }
+    }
+}
+
+impl<H, R> Iterator for MergeVersions<H, R>
+where
+    H: Iterator<Item = Result<SignedEntry>>,
+    R: Iterator<Item = Result<SignedEntry>>,
+{
+    type Item = Result<SignedEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let history_first = match (self.history.peek(), self.records.peek()) {
+            (Some(Ok(version)), Some(Ok(entry))) => {
+                (version.author(), version.key()) <= (entry.author(), entry.key())
+            }
+            (Some(_), Some(Err(_))) => false,
+            (Some(_), _) => true,
+            (None, _) => false,
+        };
+        match history_first {
+            true => self.history.next(),
+            false => self.records.next(),
+        }
+    }
+}
+
+/// Selects the versions which are not superseded by any of the other versions.
+///
+/// An entry is superseded by a newer entry from the same author for either the same key or a
+/// prefix of the key. The versions must be grouped by author and key, in the order of the
+/// author and key, so that the prefixes of a key are seen before the key.
+struct LatestVersions<I: Iterator> {
+    versions: Peekable<I>,
+    /// The latest record for each prefix of the current key, from the shortest prefix.
+    ancestors: Vec<(AuthorId, Vec<u8>, Record)>,
+}
+
+impl<I: Iterator<Item = Result<SignedEntry>>> LatestVersions<I> {
+    fn new(versions: I) -> Self {
+        Self {
+            versions: versions.peekable(),
+            ancestors: Vec::new(),
+        }
+    }
+
+    /// Get the latest version of the next author and key.
+    fn next_latest(&mut self) -> Option<Result<SignedEntry>> {
+        let mut latest = match self.versions.next()? {
+            Ok(entry) => entry,
+            Err(err) => return Some(Err(err)),
+        };
+        while let Some(next) = self
+            .versions
+            .next_if(|next| !matches!(next, Ok(next) if next.author() != latest.author() || next.key() != latest.key()))
+        {
+            match next {
+                Ok(next) if next.record() > latest.record() => latest = next,
+                Ok(_) => {}
+                Err(err) => return Some(Err(err)),
+            }
+        }
+        Some(Ok(latest))
+    }
+}
+
+impl<I: Iterator<Item = Result<SignedEntry>>> Iterator for LatestVersions<I> {
+    type Item = Result<SignedEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let entry = match self.next_latest()? {
+                Ok(entry) => entry,
+                Err(err) => return Some(Err(err)),
+            };
+            let (author, key) = (entry.author(), entry.key());
+            while let Some((ancestor_author, ancestor_key, _)) = self.ancestors.last() {
+                if *ancestor_author == author && key.starts_with(ancestor_key) {
+                    break;
+                }
+                self.ancestors.pop();
+            }
+            let superseded = self
+                .ancestors
+                .iter()
+                .any(|(_, key, record)| !key.is_empty() && record >= entry.record());
+            self.ancestors
+                .push((author, key.to_vec(), entry.record().clone()));
+            if !superseded {
+                return Some(Ok(entry));
+            }
+        }
+    }
+}
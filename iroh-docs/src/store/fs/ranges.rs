@@ -5,9 +5,9 @@ use redb::{Key, Range, ReadOnlyTable, ReadableTable, Value};
 use crate::{store::SortDirection, SignedEntry};
 
 use super::{
-    bounds::{ByKeyBounds, RecordsBounds},
+    bounds::{ByKeyBounds, ByTimestampBounds, IndexEntriesBounds, RecordsBounds},
     into_entry,
-    tables::{RecordsByKeyId, RecordsId, RecordsValue},
+    tables::{IndexEntriesKey, RecordsByKeyId, RecordsByTimestampId, RecordsId, RecordsValue},
 };
 
 /// An extension trait for [`Range`] that provides methods for mapped retrieval.
@@ -165,3 +165,100 @@ impl RecordsByKeyRange {
         entry
     }
 }
+
+#[derive(derive_more::Debug)]
+#[debug("RecordsByTimestampRange")]
+pub struct RecordsByTimestampRange {
+    records_table: ReadOnlyTable<RecordsId<'static>, RecordsValue<'static>>,
+    by_timestamp_range: Range<'static, RecordsByTimestampId<'static>, ()>,
+}
+
+impl RecordsByTimestampRange {
+    pub fn with_bounds(
+        records_by_timestamp_table: ReadOnlyTable<RecordsByTimestampId<'static>, ()>,
+        records_table: ReadOnlyTable<RecordsId<'static>, RecordsValue<'static>>,
+        bounds: ByTimestampBounds,
+    ) -> anyhow::Result<Self> {
+        let by_timestamp_range = records_by_timestamp_table.range(bounds.as_ref())?;
+        Ok(Self {
+            records_table,
+            by_timestamp_range,
+        })
+    }
+
+    /// Get the next item in the range.
+    ///
+    /// Omit items for which the `filter` function returns false.
+    pub fn next_filtered(
+        &mut self,
+        direction: &SortDirection,
+        filter: impl for<'x> Fn(RecordsByTimestampId<'x>) -> bool,
+    ) -> Option<anyhow::Result<SignedEntry>> {
+        self.by_timestamp_range
+            .next_try_filter_map(direction, |k, _v| {
+                if !filter(k) {
+                    return None;
+                };
+                let (namespace, _timestamp, author, key) = k;
+                let records_id = (namespace, author, key);
+                let entry = self.records_table.get(&records_id).transpose()?;
+                let entry = entry
+                    .map(|value| into_entry(records_id, value.value()))
+                    .map_err(anyhow::Error::from);
+                Some(entry)
+            })
+    }
+}
+
+/// An iterator over the entries of a secondary index.
+///
+/// The index may still point to entries which were replaced or deleted since they were indexed,
+/// so each indexed entry is only returned if its current content hash is the indexed one.
+#[derive(derive_more::Debug)]
+#[debug("IndexEntriesRange")]
+pub struct IndexEntriesRange {
+    records_table: ReadOnlyTable<RecordsId<'static>, RecordsValue<'static>>,
+    index_entries_range: Range<'static, IndexEntriesKey<'static>, &'static [u8; 32]>,
+}
+
+impl IndexEntriesRange {
+    pub fn with_bounds(
+        index_entries_table: ReadOnlyTable<IndexEntriesKey<'static>, &'static [u8; 32]>,
+        records_table: ReadOnlyTable<RecordsId<'static>, RecordsValue<'static>>,
+        bounds: IndexEntriesBounds,
+    ) -> anyhow::Result<Self> {
+        let index_entries_range = index_entries_table.range(bounds.as_ref())?;
+        Ok(Self {
+            records_table,
+            index_entries_range,
+        })
+    }
+
+    /// Get the next item in the range.
+    ///
+    /// Omit items for which the `filter` function returns false.
+    pub fn next_filtered(
+        &mut self,
+        direction: &SortDirection,
+        filter: impl for<'x> Fn(IndexEntriesKey<'x>) -> bool,
+    ) -> Option<anyhow::Result<SignedEntry>> {
+        self.index_entries_range
+            .next_try_filter_map(direction, |k, hash| {
+                if !filter(k) {
+                    return None;
+                };
+                let (namespace, _name, _value, author, key) = k;
+                let records_id = (namespace, author, key);
+                let value = match self.records_table.get(&records_id) {
+                    Ok(value) => value?,
+                    Err(err) => return Some(Err(err.into())),
+                };
+                let value = value.value();
+                // skip stale index entries
+                if value.4 != hash {
+                    return None;
+                }
+                Some(Ok(into_entry(records_id, value)))
+            })
+    }
+}
@@ -56,6 +56,14 @@ pub const RECORDS_BY_KEY_TABLE: TableDefinition<RecordsByKeyId, ()> =
 pub type RecordsByKeyId<'a> = (&'a [u8; 32], &'a [u8], &'a [u8; 32]);
 pub type RecordsByKeyIdOwned = ([u8; 32], Bytes, [u8; 32]);
 
+/// Table: Records by timestamp
+/// Key:   `([u8; 32], u64, [u8; 32], Vec<u8>)` # (NamespaceId, Timestamp, AuthorId, Key)
+/// Value: `()`
+pub const RECORDS_BY_TIMESTAMP_TABLE: TableDefinition<RecordsByTimestampId, ()> =
+    TableDefinition::new("records-by-timestamp-1");
+pub type RecordsByTimestampId<'a> = (&'a [u8; 32], u64, &'a [u8; 32], &'a [u8]);
+pub type RecordsByTimestampIdOwned = ([u8; 32], u64, [u8; 32], Bytes);
+
 /// Table: Peers per document.
 /// Key:   `[u8; 32]`        # NamespaceId
 /// Value: `(u64, [u8; 32])` # ([`Nanos`], &[`PeerIdBytes`]) representing the last time a peer was used.
@@ -73,6 +81,149 @@ pub type Nanos = u64;
 pub const DOWNLOAD_POLICY_TABLE: TableDefinition<&[u8; 32], &[u8]> =
     TableDefinition::new("download-policy-1");
 
+/// Table: Write delegations
+/// Key:   `([u8; 32], [u8; 32])` # (NamespaceId, DelegationId)
+/// Value: `Vec<u8>`              # Postcard encoded [`crate::WriteDelegation`]
+pub const DELEGATIONS_TABLE: TableDefinition<DelegationsKey, &[u8]> =
+    TableDefinition::new("delegations-1");
+pub type DelegationsKey<'a> = (&'a [u8; 32], &'a [u8; 32]);
+
+/// Table: Access policy
+/// Key:   `[u8; 32]`        # NamespaceId
+/// Value: `Vec<u8>`         # Postcard encoded [`crate::AccessPolicy`]
+pub const ACCESS_POLICY_TABLE: TableDefinition<&[u8; 32], &[u8]> =
+    TableDefinition::new("access-policy-1");
+
+/// Table: Read tokens
+/// Key:   `[u8; 32]`        # NamespaceId
+/// Value: `Vec<u8>`         # Postcard encoded [`crate::ReadToken`] presented to peers on sync
+pub const READ_TOKENS_TABLE: TableDefinition<&[u8; 32], &[u8]> =
+    TableDefinition::new("read-tokens-1");
+
+/// Table: Invites
+/// Key:   `[u8; 32]`        # NamespaceId
+/// Value: `Vec<u8>`         # Postcard encoded ([`crate::Invite`], `Vec<PeerIdBytes>`), the invite
+///                            and the inviting nodes it is presented to on sync
+pub const INVITES_TABLE: TableDefinition<&[u8; 32], &[u8]> = TableDefinition::new("invites-1");
+
+/// Table: Invite redemptions
+/// Key:   `([u8; 32], [u8; 16], [u8; 32])` # (NamespaceId, InviteId, PeerIdBytes)
+/// Value: `(u64, u8)`                      # (timestamp, CapabilityKind)
+pub const REDEMPTIONS_TABLE: TableDefinition<RedemptionsKey, (u64, u8)> =
+    TableDefinition::new("invite-redemptions-1");
+pub type RedemptionsKey<'a> = (&'a [u8; 32], &'a [u8; 16], &'a PeerIdBytes);
+
+/// Table: Encryption keys
+/// Key:   `[u8; 32]`        # NamespaceId
+/// Value: `[u8; 32]`        # [`crate::DocEncryptionKey`]
+pub const ENCRYPTION_KEYS_TABLE: TableDefinition<&[u8; 32], &[u8; 32]> =
+    TableDefinition::new("encryption-keys-1");
+
+/// Table: History policy
+/// Key:   `[u8; 32]`        # NamespaceId
+/// Value: `Vec<u8>`         # Postcard encoded [`crate::store::HistoryPolicy`]
+pub const HISTORY_POLICY_TABLE: TableDefinition<&[u8; 32], &[u8]> =
+    TableDefinition::new("history-policy-1");
+
+/// Table: History
+/// Key:   `([u8; 32], [u8; 32], &[u8], u64, [u8; 32])`
+///      # (NamespaceId, AuthorId, Key, timestamp, hash)
+/// Value: `([u8; 64], [u8; 64], u64)`
+///      # (signature_namespace, signature_author, len)
+///
+/// The hash is part of the key, since versions with the same timestamp are ordered by their hash.
+pub const HISTORY_TABLE: TableDefinition<HistoryId, HistoryValue> =
+    TableDefinition::new("history-2");
+pub type HistoryId<'a> = (&'a [u8; 32], &'a [u8; 32], &'a [u8], u64, &'a [u8; 32]);
+pub type HistoryIdOwned = ([u8; 32], [u8; 32], Bytes, u64, [u8; 32]);
+pub type HistoryValue<'a> = (&'a [u8; 64], &'a [u8; 64], u64);
+
+/// Table: Sync interest
+/// Key:   `[u8; 32]`        # NamespaceId
+/// Value: `Vec<u8>`         # Postcard encoded [`crate::AreaOfInterest`]
+pub const SYNC_INTEREST_TABLE: TableDefinition<&[u8; 32], &[u8]> =
+    TableDefinition::new("sync-interest-1");
+
+/// Table: Tombstone policy
+/// Key:   `[u8; 32]`        # NamespaceId
+/// Value: `Vec<u8>`         # Postcard encoded [`crate::store::TombstonePolicy`]
+pub const TOMBSTONE_POLICY_TABLE: TableDefinition<&[u8; 32], &[u8]> =
+    TableDefinition::new("tombstone-policy-1");
+
+/// Table: Sync policy
+/// Key:   `[u8; 32]`        # NamespaceId
+/// Value: `Vec<u8>`         # Postcard encoded [`crate::store::SyncPolicy`]
+pub const SYNC_POLICY_TABLE: TableDefinition<&[u8; 32], &[u8]> =
+    TableDefinition::new("sync-policy-1");
+
+/// Table: Compaction cutoff
+/// Key:   `[u8; 32]`        # NamespaceId
+/// Value: `u64`             # Timestamp before which entries were compacted
+pub const COMPACTION_CUTOFF_TABLE: TableDefinition<&[u8; 32], u64> =
+    TableDefinition::new("compaction-cutoff-1");
+
+/// Table: Clock mode
+/// Key:   `[u8; 32]`        # NamespaceId
+/// Value: `Vec<u8>`         # Postcard encoded [`crate::store::ClockMode`]
+pub const CLOCK_MODE_TABLE: TableDefinition<&[u8; 32], &[u8]> =
+    TableDefinition::new("clock-mode-1");
+
+/// Table: Inline limit
+/// Key:   `[u8; 32]`        # NamespaceId
+/// Value: `u64`             # Maximum length of values that are sent inline
+pub const INLINE_LIMIT_TABLE: TableDefinition<&[u8; 32], u64> =
+    TableDefinition::new("inline-limit-1");
+
+/// Table: Inline content
+/// Key:   `([u8; 32], [u8; 32], Vec<u8>)` # (NamespaceId, AuthorId, Key)
+/// Value: `Vec<u8>`                       # Content of the record, received inline
+pub const INLINE_CONTENT_TABLE: TableDefinition<RecordsId, &[u8]> =
+    TableDefinition::new("inline-content-1");
+
+/// Table: Trusted marker authors
+/// Key:   `[u8; 32]`        # NamespaceId
+/// Value: `Vec<u8>`         # Postcard encoded set of [`crate::AuthorId`]s
+pub const MARKER_TRUST_TABLE: TableDefinition<&[u8; 32], &[u8]> =
+    TableDefinition::new("marker-trust-1");
+
+/// Table: Indexes
+/// Key:   `([u8; 32], &str)` # (NamespaceId, index name)
+/// Value: `Vec<u8>`          # Postcard encoded [`crate::store::IndexSpec`]
+pub const INDEXES_TABLE: TableDefinition<IndexesKey, &[u8]> = TableDefinition::new("indexes-1");
+pub type IndexesKey<'a> = (&'a [u8; 32], &'a str);
+
+/// Table: Index entries
+/// Key:   `([u8; 32], &str, &[u8], [u8; 32], &[u8])`
+///      # (NamespaceId, index name, indexed value, AuthorId, Key)
+/// Value: `[u8; 32]`
+///      # Hash of the content the value was extracted from
+pub const INDEX_ENTRIES_TABLE: TableDefinition<IndexEntriesKey, &[u8; 32]> =
+    TableDefinition::new("index-entries-1");
+pub type IndexEntriesKey<'a> = (&'a [u8; 32], &'a str, &'a [u8], &'a [u8; 32], &'a [u8]);
+pub type IndexEntriesKeyOwned = ([u8; 32], String, Bytes, [u8; 32], Bytes);
+
+/// Table: Index values
+/// Key:   `([u8; 32], &str, [u8; 32], &[u8])` # (NamespaceId, index name, AuthorId, Key)
+/// Value: `Vec<u8>`                           # Postcard encoded indexed values of the entry
+pub const INDEX_VALUES_TABLE: TableDefinition<IndexValuesKey, &[u8]> =
+    TableDefinition::new("index-values-1");
+pub type IndexValuesKey<'a> = (&'a [u8; 32], &'a str, &'a [u8; 32], &'a [u8]);
+
+/// Table: Keystore
+/// Key:   `&str`            # "salt" or "check"
+/// Value: `Vec<u8>`         # Salt of the passphrase key, and the value to verify the passphrase
+pub const KEYSTORE_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("keystore-1");
+
+/// Table: Sealed secrets
+/// Key:   `(u8, [u8; 32])`  # (SecretKind, Id)
+/// Value: `Vec<u8>`         # Nonce and ciphertext of the secret
+///
+/// While the keystore is enabled, the secrets are stored here, and their slots in the authors,
+/// namespaces and encryption keys tables are zeroed.
+pub const SEALED_SECRETS_TABLE: TableDefinition<SealedSecretsKey, &[u8]> =
+    TableDefinition::new("sealed-secrets-1");
+pub type SealedSecretsKey<'a> = (u8, &'a [u8; 32]);
+
 self_cell::self_cell! {
     struct TransactionAndTablesInner {
         owner: WriteTransaction,
@@ -110,37 +261,107 @@ impl TransactionAndTables {
     pub fn commit(self) -> std::result::Result<(), redb::CommitError> {
         self.inner.into_owner().commit()
     }
+
+    pub fn abort(self) -> std::result::Result<(), redb::StorageError> {
+        self.inner.into_owner().abort()
+    }
 }
 
 #[derive(derive_more::Debug)]
 pub struct Tables<'tx> {
     pub records: Table<'tx, RecordsId<'static>, RecordsValue<'static>>,
     pub records_by_key: Table<'tx, RecordsByKeyId<'static>, ()>,
+    pub records_by_timestamp: Table<'tx, RecordsByTimestampId<'static>, ()>,
     pub namespaces: Table<'tx, &'static [u8; 32], (u8, &'static [u8; 32])>,
     pub latest_per_author: Table<'tx, LatestPerAuthorKey<'static>, LatestPerAuthorValue<'static>>,
     #[debug("MultimapTable")]
     pub namespace_peers: MultimapTable<'tx, &'static [u8; 32], (Nanos, &'static PeerIdBytes)>,
     pub download_policy: Table<'tx, &'static [u8; 32], &'static [u8]>,
     pub authors: Table<'tx, &'static [u8; 32], &'static [u8; 32]>,
+    pub delegations: Table<'tx, DelegationsKey<'static>, &'static [u8]>,
+    pub access_policy: Table<'tx, &'static [u8; 32], &'static [u8]>,
+    pub read_tokens: Table<'tx, &'static [u8; 32], &'static [u8]>,
+    pub invites: Table<'tx, &'static [u8; 32], &'static [u8]>,
+    pub redemptions: Table<'tx, RedemptionsKey<'static>, (u64, u8)>,
+    pub encryption_keys: Table<'tx, &'static [u8; 32], &'static [u8; 32]>,
+    pub history_policy: Table<'tx, &'static [u8; 32], &'static [u8]>,
+    pub history: Table<'tx, HistoryId<'static>, HistoryValue<'static>>,
+    pub sync_interest: Table<'tx, &'static [u8; 32], &'static [u8]>,
+    pub clock_mode: Table<'tx, &'static [u8; 32], &'static [u8]>,
+    pub tombstone_policy: Table<'tx, &'static [u8; 32], &'static [u8]>,
+    pub sync_policy: Table<'tx, &'static [u8; 32], &'static [u8]>,
+    pub compaction_cutoff: Table<'tx, &'static [u8; 32], u64>,
+    pub inline_limit: Table<'tx, &'static [u8; 32], u64>,
+    pub inline_content: Table<'tx, RecordsId<'static>, &'static [u8]>,
+    pub marker_trust: Table<'tx, &'static [u8; 32], &'static [u8]>,
+    pub indexes: Table<'tx, IndexesKey<'static>, &'static [u8]>,
+    pub index_entries: Table<'tx, IndexEntriesKey<'static>, &'static [u8; 32]>,
+    pub index_values: Table<'tx, IndexValuesKey<'static>, &'static [u8]>,
+    pub keystore: Table<'tx, &'static str, &'static [u8]>,
+    pub sealed_secrets: Table<'tx, SealedSecretsKey<'static>, &'static [u8]>,
 }
 
 impl<'tx> Tables<'tx> {
     pub fn new(tx: &'tx WriteTransaction) -> Result<Self, redb::TableError> {
         let records = tx.open_table(RECORDS_TABLE)?;
         let records_by_key = tx.open_table(RECORDS_BY_KEY_TABLE)?;
+        let records_by_timestamp = tx.open_table(RECORDS_BY_TIMESTAMP_TABLE)?;
         let namespaces = tx.open_table(NAMESPACES_TABLE)?;
         let latest_per_author = tx.open_table(LATEST_PER_AUTHOR_TABLE)?;
         let namespace_peers = tx.open_multimap_table(NAMESPACE_PEERS_TABLE)?;
         let download_policy = tx.open_table(DOWNLOAD_POLICY_TABLE)?;
         let authors = tx.open_table(AUTHORS_TABLE)?;
+        let delegations = tx.open_table(DELEGATIONS_TABLE)?;
+        let access_policy = tx.open_table(ACCESS_POLICY_TABLE)?;
+        let read_tokens = tx.open_table(READ_TOKENS_TABLE)?;
+        let invites = tx.open_table(INVITES_TABLE)?;
+        let redemptions = tx.open_table(REDEMPTIONS_TABLE)?;
+        let encryption_keys = tx.open_table(ENCRYPTION_KEYS_TABLE)?;
+        let history_policy = tx.open_table(HISTORY_POLICY_TABLE)?;
+        let history = tx.open_table(HISTORY_TABLE)?;
+        let sync_interest = tx.open_table(SYNC_INTEREST_TABLE)?;
+        let clock_mode = tx.open_table(CLOCK_MODE_TABLE)?;
+        let tombstone_policy = tx.open_table(TOMBSTONE_POLICY_TABLE)?;
+        let sync_policy = tx.open_table(SYNC_POLICY_TABLE)?;
+        let compaction_cutoff = tx.open_table(COMPACTION_CUTOFF_TABLE)?;
+        let inline_limit = tx.open_table(INLINE_LIMIT_TABLE)?;
+        let inline_content = tx.open_table(INLINE_CONTENT_TABLE)?;
+        let marker_trust = tx.open_table(MARKER_TRUST_TABLE)?;
+        let indexes = tx.open_table(INDEXES_TABLE)?;
+        let index_entries = tx.open_table(INDEX_ENTRIES_TABLE)?;
+        let index_values = tx.open_table(INDEX_VALUES_TABLE)?;
+        let keystore = tx.open_table(KEYSTORE_TABLE)?;
+        let sealed_secrets = tx.open_table(SEALED_SECRETS_TABLE)?;
         Ok(Self {
             records,
             records_by_key,
+            records_by_timestamp,
             namespaces,
             latest_per_author,
             namespace_peers,
             download_policy,
             authors,
+            delegations,
+            access_policy,
+            read_tokens,
+            invites,
+            redemptions,
+            encryption_keys,
+            history_policy,
+            history,
+            sync_interest,
+            clock_mode,
+            tombstone_policy,
+            sync_policy,
+            compaction_cutoff,
+            inline_limit,
+            inline_content,
+            marker_trust,
+            indexes,
+            index_entries,
+            index_values,
+            keystore,
+            sealed_secrets,
         })
     }
 }
@@ -148,6 +369,7 @@ impl<'tx> Tables<'tx> {
 pub struct ReadOnlyTables {
     pub records: ReadOnlyTable<RecordsId<'static>, RecordsValue<'static>>,
     pub records_by_key: ReadOnlyTable<RecordsByKeyId<'static>, ()>,
+    pub records_by_timestamp: ReadOnlyTable<RecordsByTimestampId<'static>, ()>,
     pub namespaces: ReadOnlyTable<&'static [u8; 32], (u8, &'static [u8; 32])>,
     pub latest_per_author:
         ReadOnlyTable<LatestPerAuthorKey<'static>, LatestPerAuthorValue<'static>>,
@@ -155,6 +377,27 @@ pub struct ReadOnlyTables {
     pub namespace_peers: ReadOnlyMultimapTable<&'static [u8; 32], (Nanos, &'static PeerIdBytes)>,
     pub download_policy: ReadOnlyTable<&'static [u8; 32], &'static [u8]>,
     pub authors: ReadOnlyTable<&'static [u8; 32], &'static [u8; 32]>,
+    pub delegations: ReadOnlyTable<DelegationsKey<'static>, &'static [u8]>,
+    pub access_policy: ReadOnlyTable<&'static [u8; 32], &'static [u8]>,
+    pub read_tokens: ReadOnlyTable<&'static [u8; 32], &'static [u8]>,
+    pub invites: ReadOnlyTable<&'static [u8; 32], &'static [u8]>,
+    pub redemptions: ReadOnlyTable<RedemptionsKey<'static>, (u64, u8)>,
+    pub encryption_keys: ReadOnlyTable<&'static [u8; 32], &'static [u8; 32]>,
+    pub history_policy: ReadOnlyTable<&'static [u8; 32], &'static [u8]>,
+    pub history: ReadOnlyTable<HistoryId<'static>, HistoryValue<'static>>,
+    pub sync_interest: ReadOnlyTable<&'static [u8; 32], &'static [u8]>,
+    pub clock_mode: ReadOnlyTable<&'static [u8; 32], &'static [u8]>,
+    pub tombstone_policy: ReadOnlyTable<&'static [u8; 32], &'static [u8]>,
+    pub sync_policy: ReadOnlyTable<&'static [u8; 32], &'static [u8]>,
+    pub compaction_cutoff: ReadOnlyTable<&'static [u8; 32], u64>,
+    pub inline_limit: ReadOnlyTable<&'static [u8; 32], u64>,
+    pub inline_content: ReadOnlyTable<RecordsId<'static>, &'static [u8]>,
+    pub marker_trust: ReadOnlyTable<&'static [u8; 32], &'static [u8]>,
+    pub indexes: ReadOnlyTable<IndexesKey<'static>, &'static [u8]>,
+    pub index_entries: ReadOnlyTable<IndexEntriesKey<'static>, &'static [u8; 32]>,
+    pub index_values: ReadOnlyTable<IndexValuesKey<'static>, &'static [u8]>,
+    pub keystore: ReadOnlyTable<&'static str, &'static [u8]>,
+    pub sealed_secrets: ReadOnlyTable<SealedSecretsKey<'static>, &'static [u8]>,
     tx: ReadTransaction,
 }
 
@@ -162,19 +405,63 @@ impl ReadOnlyTables {
     pub fn new(tx: ReadTransaction) -> Result<Self, redb::TableError> {
         let records = tx.open_table(RECORDS_TABLE)?;
         let records_by_key = tx.open_table(RECORDS_BY_KEY_TABLE)?;
+        let records_by_timestamp = tx.open_table(RECORDS_BY_TIMESTAMP_TABLE)?;
         let namespaces = tx.open_table(NAMESPACES_TABLE)?;
         let latest_per_author = tx.open_table(LATEST_PER_AUTHOR_TABLE)?;
         let namespace_peers = tx.open_multimap_table(NAMESPACE_PEERS_TABLE)?;
         let download_policy = tx.open_table(DOWNLOAD_POLICY_TABLE)?;
         let authors = tx.open_table(AUTHORS_TABLE)?;
+        let delegations = tx.open_table(DELEGATIONS_TABLE)?;
+        let access_policy = tx.open_table(ACCESS_POLICY_TABLE)?;
+        let read_tokens = tx.open_table(READ_TOKENS_TABLE)?;
+        let invites = tx.open_table(INVITES_TABLE)?;
+        let redemptions = tx.open_table(REDEMPTIONS_TABLE)?;
+        let encryption_keys = tx.open_table(ENCRYPTION_KEYS_TABLE)?;
+        let history_policy = tx.open_table(HISTORY_POLICY_TABLE)?;
+        let history = tx.open_table(HISTORY_TABLE)?;
+        let sync_interest = tx.open_table(SYNC_INTEREST_TABLE)?;
+        let clock_mode = tx.open_table(CLOCK_MODE_TABLE)?;
+        let tombstone_policy = tx.open_table(TOMBSTONE_POLICY_TABLE)?;
+        let sync_policy = tx.open_table(SYNC_POLICY_TABLE)?;
+        let compaction_cutoff = tx.open_table(COMPACTION_CUTOFF_TABLE)?;
+        let inline_limit = tx.open_table(INLINE_LIMIT_TABLE)?;
+        let inline_content = tx.open_table(INLINE_CONTENT_TABLE)?;
+        let marker_trust = tx.open_table(MARKER_TRUST_TABLE)?;
+        let indexes = tx.open_table(INDEXES_TABLE)?;
+        let index_entries = tx.open_table(INDEX_ENTRIES_TABLE)?;
+        let index_values = tx.open_table(INDEX_VALUES_TABLE)?;
+        let keystore = tx.open_table(KEYSTORE_TABLE)?;
+        let sealed_secrets = tx.open_table(SEALED_SECRETS_TABLE)?;
         Ok(Self {
             records,
             records_by_key,
+            records_by_timestamp,
             namespaces,
             latest_per_author,
             namespace_peers,
             download_policy,
             authors,
+            delegations,
+            access_policy,
+            read_tokens,
+            invites,
+            redemptions,
+            encryption_keys,
+            history_policy,
+            history,
+            sync_interest,
+            clock_mode,
+            tombstone_policy,
+            sync_policy,
+            compaction_cutoff,
+            inline_limit,
+            inline_content,
+            marker_trust,
+            indexes,
+            index_entries,
+            index_values,
+            keystore,
+            sealed_secrets,
             tx,
         })
     }
@@ -37,6 +37,11 @@ impl From<&Query> for IndexKind {
                 author_filter: query.filter_author.clone(),
                 latest_per_key: true,
             },
+            // the results of index queries are sorted by author and key
+            QueryKind::Index(_) => IndexKind::AuthorKey {
+                range: query.filter_author.clone(),
+                key_filter: query.filter_key.clone(),
+            },
         }
     }
 }
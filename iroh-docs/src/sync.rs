@@ -8,6 +8,7 @@
 
 use std::{
     cmp::Ordering,
+    collections::HashMap,
     fmt::Debug,
     sync::Arc,
     time::{Duration, SystemTime},
@@ -19,6 +20,7 @@ use iroh_metrics::{inc, inc_by};
 use std::ops::{Deref, DerefMut};
 
 use ed25519_dalek::{Signature, SignatureError};
+use futures_util::future::BoxFuture;
 use iroh_base::{base32, hash::Hash};
 use serde::{Deserialize, Serialize};
 
@@ -26,9 +28,13 @@ pub use crate::heads::AuthorHeads;
 #[cfg(feature = "metrics")]
 use crate::metrics::Metrics;
 use crate::{
+    delegation::{DelegationError, DelegationScope, WriteDelegation},
+    interest::{AreaOfInterest, AreaStore},
     keys::{Author, AuthorId, AuthorPublicKey, NamespaceId, NamespacePublicKey, NamespaceSecret},
     ranger::{self, Fingerprint, InsertOutcome, RangeEntry, RangeKey, RangeValue, Store},
-    store::{self, fs::StoreInstance, DownloadPolicyStore, PublicKeyStore},
+    store::{
+        self, fs::StoreInstance, ClockMode, DownloadPolicyStore, PublicKeyStore, MAX_INLINE_LIMIT,
+    },
 };
 
 /// Protocol message for the set reconciliation protocol.
@@ -41,12 +47,23 @@ pub type ProtocolMessage = crate::ranger::Message<SignedEntry>;
 pub type PeerIdBytes = [u8; 32];
 
 /// Max time in the future from our wall clock time that we accept entries for.
-/// Value is 10 minutes.
-pub const MAX_TIMESTAMP_FUTURE_SHIFT: u64 = 10 * 60 * Duration::from_secs(1).as_millis() as u64;
+/// Value is 10 minutes, in microseconds like entry timestamps.
+pub const MAX_TIMESTAMP_FUTURE_SHIFT: u64 = 10 * 60 * Duration::from_secs(1).as_micros() as u64;
+
+/// Domain separator for the author signature that replaces the namespace signature on entries
+/// written under a [`WriteDelegation`].
+const DELEGATED_ENTRY_DOMAIN: &[u8] = b"iroh-docs delegated entry";
 
 /// Callback that may be set on a replica to determine the availability status for a content hash.
 pub type ContentStatusCallback = Arc<dyn Fn(Hash) -> ContentStatus + Send + Sync + 'static>;
 
+/// Callback that may be set on a [`crate::actor::SyncHandle`] to read the content for a content
+/// hash, to send small values inline when syncing.
+///
+/// Returns `None` if the content is not complete.
+pub type ContentReaderCallback =
+    Arc<dyn Fn(Hash) -> BoxFuture<'static, Option<Bytes>> + Send + Sync + 'static>;
+
 /// Event emitted by sync when entries are added.
 #[derive(Debug, Clone)]
 pub enum Event {
@@ -57,6 +74,13 @@ pub enum Event {
         /// Inserted entry.
         entry: SignedEntry,
     },
+    /// Several local entries have been added in a single batch.
+    LocalBatch {
+        /// Document in which the entries were inserted.
+        namespace: NamespaceId,
+        /// Inserted entries, in the order of the batch.
+        entries: Vec<SignedEntry>,
+    },
     /// A remote entry has been added.
     RemoteInsert {
         /// Document in which the entry was inserted.
@@ -69,6 +93,30 @@ pub enum Event {
         should_download: bool,
         /// [`ContentStatus`] for this entry in the remote's replica.
         remote_content_status: ContentStatus,
+        /// The content of the entry, if it was received inline with the entry.
+        ///
+        /// The content is stored in the docs store before this event is emitted, see
+        /// [`store::fs::Store::get_inline_content`].
+        inline_content: Option<Bytes>,
+    },
+}
+
+/// A single write in a batch applied with [`Replica::apply_batch`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum BatchOp {
+    /// Insert a record at `key`, like [`Replica::insert`].
+    Insert {
+        /// Key of the entry.
+        key: Bytes,
+        /// Hash of the content.
+        hash: Hash,
+        /// Byte length of the content.
+        len: u64,
+    },
+    /// Delete the entries with a key that starts with `prefix`, like [`Replica::delete_prefix`].
+    Delete {
+        /// Key prefix of the entries to delete.
+        prefix: Bytes,
     },
 }
 
@@ -152,6 +200,8 @@ impl Subscribers {
     Debug,
     Clone,
     Copy,
+    PartialEq,
+    Eq,
     Serialize,
     Deserialize,
     num_enum::IntoPrimitive,
@@ -264,6 +314,11 @@ pub struct ReplicaInfo {
     #[debug("ContentStatusCallback")]
     content_status_cb: Option<ContentStatusCallback>,
     closed: bool,
+    /// Cached area of interest, loaded from the store on first use.
+    sync_interest: Option<AreaOfInterest>,
+    /// Cached write delegations, loaded from the store on first use and cleared when a
+    /// delegation is added.
+    delegations: Option<Arc<[WriteDelegation]>>,
 }
 
 impl ReplicaInfo {
@@ -275,9 +330,16 @@ impl ReplicaInfo {
             // on_insert_sender: RwLock::new(None),
             content_status_cb: None,
             closed: false,
+            sync_interest: None,
+            delegations: None,
         }
     }
 
+    /// Update the cached area of interest after it was changed in the store.
+    pub(crate) fn set_sync_interest(&mut self, area: AreaOfInterest) {
+        self.sync_interest = Some(area);
+    }
+
     /// Subscribe to insert events.
     ///
     /// When subscribing to a replica, you must ensure that the corresponding [`async_channel::Receiver`] is
@@ -377,10 +439,27 @@ where
         }
         self.info.ensure_open()?;
         let id = RecordIdentifier::new(self.id(), author.id(), key);
-        let record = Record::new_current(hash, len);
+        let record = Record::new(hash, len, self.next_timestamp()?);
         let entry = Entry::new(id, record);
-        let secret = self.secret_key()?;
-        let signed_entry = entry.sign(secret, author);
+        let signed_entry = self.sign_entry(entry, author)?;
+        self.insert_entry(signed_entry, InsertOrigin::Local)
+    }
+
+    /// Insert a record at the given key, keeping the timestamp of the record.
+    ///
+    /// This is used to copy entries from another namespace, see [`crate::moved_to`].
+    pub fn insert_record(
+        &mut self,
+        key: impl AsRef<[u8]>,
+        author: &Author,
+        record: Record,
+    ) -> Result<usize, InsertError> {
+        if record.is_empty() {
+            return Err(InsertError::EntryIsEmpty);
+        }
+        self.info.ensure_open()?;
+        let id = RecordIdentifier::new(self.id(), author.id(), key);
+        let signed_entry = self.sign_entry(Entry::new(id, record), author)?;
         self.insert_entry(signed_entry, InsertOrigin::Local)
     }
 
@@ -397,11 +476,136 @@ where
     ) -> Result<usize, InsertError> {
         self.info.ensure_open()?;
         let id = RecordIdentifier::new(self.id(), author.id(), prefix);
-        let entry = Entry::new_empty(id);
-        let signed_entry = entry.sign(self.secret_key()?, author);
+        let entry = Entry::new(id, Record::empty(self.next_timestamp()?));
+        let signed_entry = self.sign_entry(entry, author)?;
         self.insert_entry(signed_entry, InsertOrigin::Local)
     }
 
+    /// Apply several writes by `author` in a single transaction.
+    ///
+    /// Either all writes are applied, or none of them if any write fails. The inserted entries
+    /// are emitted together in a single [`Event::LocalBatch`].
+    ///
+    /// Returns the number of entries removed as a consequence of the batch.
+    pub fn apply_batch(
+        &mut self,
+        author: &Author,
+        ops: impl IntoIterator<Item = BatchOp>,
+    ) -> Result<usize, InsertError> {
+        self.info.ensure_open()?;
+        let timestamp = self.next_timestamp()?;
+        let mut entries = Vec::new();
+        for op in ops {
+            let entry = match op {
+                BatchOp::Insert { key, hash, len } => {
+                    if len == 0 || hash == Hash::EMPTY {
+                        return Err(InsertError::EntryIsEmpty);
+                    }
+                    let id = RecordIdentifier::new(self.id(), author.id(), key);
+                    Entry::new(id, Record::new(hash, len, timestamp))
+                }
+                BatchOp::Delete { prefix } => {
+                    let id = RecordIdentifier::new(self.id(), author.id(), prefix);
+                    Entry::new(id, Record::empty(timestamp))
+                }
+            };
+            entries.push(self.sign_entry(entry, author)?);
+        }
+
+        self.store.store.begin_batch().map_err(InsertError::Store)?;
+        let mut removed = 0;
+        for entry in &entries {
+            match self.insert_entry_inner(entry.clone(), InsertOrigin::Local) {
+                Ok((count, _event)) => removed += count,
+                Err(err) => {
+                    self.store.store.abort_batch().map_err(InsertError::Store)?;
+                    return Err(err);
+                }
+            }
+        }
+        self.store
+            .store
+            .commit_batch()
+            .map_err(InsertError::Store)?;
+
+        let namespace = self.id();
+        self.info
+            .subscribers
+            .send(Event::LocalBatch { namespace, entries });
+        Ok(removed)
+    }
+
+    /// Insert entries which were received together from a remote peer in a single transaction.
+    ///
+    /// Entries that fail to validate are skipped. The `inline_content` received with the entries
+    /// is stored for the inserted entries that reference it. An [`Event::RemoteInsert`] is
+    /// emitted for each inserted entry once all of them are stored.
+    ///
+    /// Returns the number of entries inserted.
+    pub fn insert_remote_batch(
+        &mut self,
+        entries: Vec<SignedEntry>,
+        received_from: PeerIdBytes,
+        content_status: ContentStatus,
+        inline_content: HashMap<Hash, Bytes>,
+    ) -> Result<usize, InsertError> {
+        self.info.ensure_open()?;
+        let interest = self.sync_interest()?.clone();
+        let cutoff = self
+            .store
+            .store
+            .get_compaction_cutoff(&self.id())
+            .map_err(InsertError::Store)?;
+        let origin = InsertOrigin::Sync {
+            from: received_from,
+            remote_content_status: content_status,
+        };
+
+        self.store.store.begin_batch().map_err(InsertError::Store)?;
+        let mut events = Vec::new();
+        for entry in entries {
+            if entry.validate_empty().is_err()
+                || !interest.includes_id(entry.id())
+                || entry.timestamp() < cutoff
+            {
+                continue;
+            }
+            let res =
+                self.insert_entry_inner(entry, origin.clone())
+                    .and_then(|(_removed, mut event)| {
+                        if let Event::RemoteInsert {
+                            entry,
+                            inline_content: content,
+                            ..
+                        } = &mut event
+                        {
+                            *content = self
+                                .put_inline_content(entry, &inline_content)
+                                .map_err(InsertError::Store)?;
+                        }
+                        Ok(event)
+                    });
+            match res {
+                Ok(event) => events.push(event),
+                Err(InsertError::Store(err)) => {
+                    self.store.store.abort_batch().map_err(InsertError::Store)?;
+                    return Err(InsertError::Store(err));
+                }
+                Err(err) => tracing::debug!(?err, "skipping entry of remote batch"),
+            }
+        }
+        self.store
+            .store
+            .commit_batch()
+            .map_err(InsertError::Store)?;
+
+        let count = events.len();
+        for event in events {
+            self.info.subscribers.send(event);
+        }
+        Ok(count)
+    }
+
     /// Insert an entry into this replica which was received from a remote peer.
     ///
     /// This will verify both the namespace and author signatures of the entry, emit an `on_insert`
@@ -417,6 +621,17 @@ where
     ) -> Result<usize, InsertError> {
         self.info.ensure_open()?;
         entry.validate_empty()?;
+        if !self.sync_interest()?.includes_id(entry.id()) {
+            return Err(InsertError::OutsideInterest);
+        }
+        let cutoff = self
+            .store
+            .store
+            .get_compaction_cutoff(&self.id())
+            .map_err(InsertError::Store)?;
+        if entry.timestamp() < cutoff {
+            return Err(InsertError::Compacted);
+        }
         let origin = InsertOrigin::Sync {
             from: received_from,
             remote_content_status: content_status,
@@ -424,6 +639,46 @@ where
         self.insert_entry(entry, origin)
     }
 
+    /// Store the content received inline for a remote entry, if any, in the docs store.
+    ///
+    /// Returns the stored content, to attach it to the [`Event::RemoteInsert`] of the entry.
+    fn put_inline_content(
+        &mut self,
+        entry: &SignedEntry,
+        inline_content: &HashMap<Hash, Bytes>,
+    ) -> anyhow::Result<Option<Bytes>> {
+        let Some(content) = inline_content.get(&entry.content_hash()) else {
+            return Ok(None);
+        };
+        if content.len() as u64 > MAX_INLINE_LIMIT {
+            return Ok(None);
+        }
+        self.store.store.put_inline_content(entry.id(), content)?;
+        Ok(Some(content.clone()))
+    }
+
+    /// The area of interest of this replica, read from the store on first use.
+    fn sync_interest(&mut self) -> Result<&AreaOfInterest, InsertError> {
+        if self.info.sync_interest.is_none() {
+            let area = self
+                .store
+                .store
+                .get_sync_interest(&self.id())
+                .map_err(InsertError::Store)?;
+            self.info.sync_interest = Some(area);
+        }
+        Ok(self.info.sync_interest.as_ref().expect("just set"))
+    }
+
+    /// The write delegations of this replica, read from the store on first use.
+    fn cached_delegations(&mut self) -> anyhow::Result<Arc<[WriteDelegation]>> {
+        if self.info.delegations.is_none() {
+            let delegations = self.store.store.get_delegations(&self.id())?;
+            self.info.delegations = Some(delegations.into());
+        }
+        Ok(self.info.delegations.clone().expect("just set"))
+    }
+
     /// Insert a signed entry into the database.
     ///
     /// Returns the number of entries removed as a consequence of this insertion.
@@ -432,13 +687,35 @@ where
         entry: SignedEntry,
         origin: InsertOrigin,
     ) -> Result<usize, InsertError> {
+        let (removed_count, event) = self.insert_entry_inner(entry, origin)?;
+        self.info.subscribers.send(event);
+        Ok(removed_count)
+    }
+
+    /// Insert a signed entry into the database without emitting the insert event.
+    ///
+    /// Returns the number of entries removed and the event to emit for this insertion.
+    fn insert_entry_inner(
+        &mut self,
+        entry: SignedEntry,
+        origin: InsertOrigin,
+    ) -> Result<(usize, Event), InsertError> {
         let namespace = self.id();
 
         #[cfg(feature = "metrics")]
         let len = entry.content_len();
 
+        let delegations = self.cached_delegations().map_err(InsertError::Store)?;
+        let max_timestamp = self.max_timestamp();
         let store = &self.store;
-        validate_entry(system_time_now(), store, namespace, &entry, &origin)?;
+        validate_entry(
+            max_timestamp,
+            store,
+            namespace,
+            &entry,
+            &origin,
+            &delegations,
+        )?;
 
         let outcome = self.store.put(entry.clone()).map_err(InsertError::Store)?;
         tracing::debug!(?origin, hash = %entry.content_hash(), ?outcome, "insert");
@@ -478,13 +755,12 @@ where
                     from,
                     should_download,
                     remote_content_status,
+                    inline_content: None,
                 }
             }
         };
 
-        self.info.subscribers.send(insert_event);
-
-        Ok(removed_count)
+        Ok((removed_count, insert_event))
     }
 
     /// Hashes the given data and inserts it.
@@ -510,23 +786,53 @@ where
     }
 
     /// Create the initial message for the set reconciliation flow with a remote peer.
-    pub fn sync_initial_message(&mut self) -> anyhow::Result<crate::ranger::Message<SignedEntry>> {
-        self.info.ensure_open().map_err(anyhow::Error::from)?;
-        self.store.initial_message().map_err(Into::into)
+    ///
+    /// Only the entries within `area` are reconciled.
+    pub fn sync_initial_message(
+        &mut self,
+        area: &AreaOfInterest,
+    ) -> anyhow::Result<crate::ranger::Message<SignedEntry>> {
+        self.info.ensure_open()?;
+        AreaStore::new(&mut self.store, area).initial_message()
     }
 
     /// Process a set reconciliation message from a remote peer.
     ///
+    /// Only the entries within `area` are sent, and received entries outside of `area` are
+    /// dropped. Both peers have to use the same area for the reconciliation to be efficient.
+    ///
+    /// Received entries older than the compaction cutoff are accepted, so peers have to agree on
+    /// the cutoff with [`store::fs::Store::adopt_compaction_cutoff`] before reconciling.
+    ///
     /// Returns the next message to be sent to the peer, if any.
     pub fn sync_process_message(
         &mut self,
         message: crate::ranger::Message<SignedEntry>,
         from_peer: PeerIdBytes,
+        area: &AreaOfInterest,
+        state: &mut SyncOutcome,
+    ) -> Result<Option<crate::ranger::Message<SignedEntry>>, anyhow::Error> {
+        self.sync_process_message_with_content(message, from_peer, area, state, HashMap::new())
+    }
+
+    /// Process a set reconciliation message from a remote peer, together with the content of
+    /// small values that was received inline with it.
+    ///
+    /// The content is stored in the docs store only for the entries that passed validation and
+    /// were inserted, before their [`Event::RemoteInsert`] events are emitted.
+    ///
+    /// See [`Self::sync_process_message`].
+    pub fn sync_process_message_with_content(
+        &mut self,
+        message: crate::ranger::Message<SignedEntry>,
+        from_peer: PeerIdBytes,
+        area: &AreaOfInterest,
         state: &mut SyncOutcome,
+        inline_content: HashMap<Hash, Bytes>,
     ) -> Result<Option<crate::ranger::Message<SignedEntry>>, anyhow::Error> {
         self.info.ensure_open()?;
         let my_namespace = self.id();
-        let now = system_time_now();
+        let max_timestamp = self.max_timestamp();
 
         // update state with incoming data.
         state.num_recv += message.value_count();
@@ -543,7 +849,9 @@ where
             .store
             .get_download_policy(&my_namespace)
             .unwrap_or_default();
-        let reply = self.store.process_message(
+        let delegations = self.cached_delegations()?;
+        let mut inserted = Vec::new();
+        let reply = AreaStore::new(&mut self.store, area).process_message(
             &Default::default(),
             message,
             // validate callback: validate incoming entries, and send to on_insert channel
@@ -552,22 +860,19 @@ where
                     from: from_peer,
                     remote_content_status: content_status,
                 };
-                validate_entry(now, store, my_namespace, entry, &origin).is_ok()
+                area.includes_id(entry.id())
+                    && validate_entry(
+                        max_timestamp,
+                        store,
+                        my_namespace,
+                        entry,
+                        &origin,
+                        &delegations,
+                    )
+                    .is_ok()
             },
             // on_insert callback: is called when an entry was actually inserted in the store
-            |_store, entry, content_status| {
-                // We use `send_with` to only clone the entry if we have active subscriptions.
-                self.info.subscribers.send_with(|| {
-                    let should_download = download_policy.matches(entry.entry());
-                    Event::RemoteInsert {
-                        from: from_peer,
-                        namespace: my_namespace,
-                        entry: entry.clone(),
-                        should_download,
-                        remote_content_status: content_status,
-                    }
-                })
-            },
+            |_store, entry, content_status| inserted.push((entry, content_status)),
             // content_status callback: get content status for outgoing entries
             |_store, entry| {
                 if let Some(cb) = cb.as_ref() {
@@ -578,6 +883,24 @@ where
             },
         )?;
 
+        // The inline content is stored before the events are emitted, so that it is available
+        // to subscribers.
+        for (entry, content_status) in inserted {
+            let content = self.put_inline_content(&entry, &inline_content)?;
+            // We use `send_with` to only create the event if we have active subscriptions.
+            self.info.subscribers.send_with(|| {
+                let should_download = download_policy.matches(entry.entry());
+                Event::RemoteInsert {
+                    from: from_peer,
+                    namespace: my_namespace,
+                    entry,
+                    should_download,
+                    remote_content_status: content_status,
+                    inline_content: content,
+                }
+            })
+        }
+
         // update state with outgoing data.
         if let Some(ref reply) = reply {
             state.num_sent += reply.value_count();
@@ -601,6 +924,119 @@ where
     pub fn secret_key(&self) -> Result<&NamespaceSecret, ReadOnly> {
         self.info.capability.secret_key()
     }
+
+    /// Get the [`WriteDelegation`]s known for this replica.
+    pub fn delegations(&mut self) -> anyhow::Result<Vec<WriteDelegation>> {
+        Ok(self.cached_delegations()?.to_vec())
+    }
+
+    /// Add a [`WriteDelegation`] to this replica.
+    ///
+    /// The delegation is verified before it is stored. From then on, entries written under the
+    /// delegation are accepted, and the delegation is sent to peers when syncing.
+    ///
+    /// Returns `true` if the delegation was not known before.
+    pub fn add_delegation(&mut self, delegation: WriteDelegation) -> Result<bool, DelegationError> {
+        if delegation.namespace() != self.id() {
+            return Err(DelegationError::NamespaceMismatch);
+        }
+        delegation.verify(&self.store)?;
+        let inserted = self.store.store.put_delegation(&delegation)?;
+        self.info.delegations = None;
+        Ok(inserted)
+    }
+
+    /// Create and add a [`WriteDelegation`] for `scope`.
+    ///
+    /// Without an `issuer`, the delegation is signed with the namespace secret, which fails if
+    /// the replica is read only. Otherwise, it is derived from a delegable delegation of
+    /// `issuer` whose scope contains `scope`.
+    pub fn delegate(
+        &mut self,
+        scope: DelegationScope,
+        issuer: Option<&Author>,
+    ) -> Result<WriteDelegation, DelegationError> {
+        let delegation = match issuer {
+            None => {
+                let secret = self.secret_key().map_err(|_| DelegationError::ReadOnly)?;
+                WriteDelegation::new(secret, scope)
+            }
+            Some(issuer) => {
+                let parent = self
+                    .delegations()?
+                    .into_iter()
+                    .find(|d| {
+                        d.author() == issuer.id()
+                            && d.scope().delegable
+                            && scope.is_within(d.scope())
+                    })
+                    .ok_or(DelegationError::NotFound)?;
+                parent.delegate(issuer, scope)?
+            }
+        };
+        self.store.store.put_delegation(&delegation)?;
+        self.info.delegations = None;
+        Ok(delegation)
+    }
+
+    /// Sign an entry for a local insert.
+    ///
+    /// Uses the namespace secret if available, and otherwise signs the entry under a
+    /// [`WriteDelegation`] of `author` that covers the entry.
+    fn sign_entry(&mut self, entry: Entry, author: &Author) -> Result<SignedEntry, InsertError> {
+        if let Ok(secret) = self.secret_key() {
+            return Ok(entry.sign(secret, author));
+        }
+        let delegations = self.cached_delegations().map_err(InsertError::Store)?;
+        if delegations.iter().any(|d| d.covers(&entry)) {
+            Ok(entry.sign_delegated(author))
+        } else {
+            Err(InsertError::ReadOnly)
+        }
+    }
+
+    /// Get the timestamp for a new local entry, according to the [`ClockMode`] of this replica.
+    ///
+    /// With a hybrid logical clock, the timestamp is later than all entries in the replica, see
+    /// [`hybrid_timestamp`].
+    fn next_timestamp(&mut self) -> Result<u64, InsertError> {
+        let now = system_time_now();
+        let namespace = self.id();
+        let store = &mut self.store.store;
+        let mode = store
+            .get_clock_mode(&namespace)
+            .map_err(InsertError::Store)?;
+        match mode {
+            ClockMode::WallClock => Ok(now),
+            ClockMode::Hybrid => {
+                let latest = store
+                    .get_latest_timestamp(namespace)
+                    .map_err(InsertError::Store)?;
+                hybrid_timestamp(now, latest)
+            }
+        }
+    }
+
+    /// Get the latest timestamp of entries accepted by this replica.
+    ///
+    /// This does not depend on the [`ClockMode`], so that peers in different modes accept the
+    /// same entries.
+    fn max_timestamp(&self) -> u64 {
+        system_time_now().saturating_add(MAX_TIMESTAMP_FUTURE_SHIFT)
+    }
+}
+
+/// Get a hybrid logical clock timestamp that is later than `latest` and the local clock `now`.
+///
+/// The timestamp is never ahead of `now` by more than [`MAX_TIMESTAMP_FUTURE_SHIFT`], so that
+/// peers in any clock mode accept it. If that is not possible, the write would not win over
+/// `latest`, so this fails with [`InsertError::ClockBehind`] instead of losing the write.
+fn hybrid_timestamp(now: u64, latest: u64) -> Result<u64, InsertError> {
+    let next = now.max(latest.saturating_add(1));
+    if next > now.saturating_add(MAX_TIMESTAMP_FUTURE_SHIFT) {
+        return Err(InsertError::ClockBehind);
+    }
+    Ok(next)
 }
 
 /// Error that occurs trying to access the [`NamespaceSecret`] of a read-only [`Capability`].
@@ -612,15 +1048,17 @@ pub struct ReadOnly;
 ///
 /// This validates that
 /// * the entry's author and namespace signatures are correct
+/// * entries without a namespace signature are covered by one of the `delegations`
 /// * the entry's namespace matches the current replica
-/// * the entry's timestamp is not more than 10 minutes in the future of our system time
+/// * the entry's timestamp is not later than `max_timestamp`
 /// * the entry is newer than an existing entry for the same key and author, if such exists.
 fn validate_entry<S: ranger::Store<SignedEntry> + PublicKeyStore>(
-    now: u64,
+    max_timestamp: u64,
     store: &S,
     expected_namespace: NamespaceId,
     entry: &SignedEntry,
     origin: &InsertOrigin,
+    delegations: &[WriteDelegation],
 ) -> Result<(), ValidationFailure> {
     // Verify the namespace
     if entry.namespace() != expected_namespace {
@@ -628,12 +1066,20 @@ fn validate_entry<S: ranger::Store<SignedEntry> + PublicKeyStore>(
     }
 
     // Verify signature for non-local entries.
-    if !matches!(origin, InsertOrigin::Local) && entry.verify(store).is_err() {
-        return Err(ValidationFailure::BadSignature);
+    let delegated = match origin {
+        InsertOrigin::Local => entry.is_delegated(store),
+        InsertOrigin::Sync { .. } => entry
+            .verify_delegated(store)
+            .map_err(|_| ValidationFailure::BadSignature)?,
+    };
+
+    // Verify that entries written under a delegation are within its scope.
+    if delegated && !delegations.iter().any(|d| d.covers(entry.entry())) {
+        return Err(ValidationFailure::NotDelegated);
     }
 
     // Verify that the timestamp of the entry is not too far in the future.
-    if entry.timestamp() > now + MAX_TIMESTAMP_FUTURE_SHIFT {
+    if entry.timestamp() > max_timestamp {
         return Err(ValidationFailure::TooFarInTheFuture);
     }
     Ok(())
@@ -661,6 +1107,21 @@ pub enum InsertError {
     /// The replica is closed, no operations may be performed.
     #[error("replica is closed")]
     Closed,
+    /// The entry is outside of the area of interest of the replica.
+    #[error("entry is outside of the area of interest")]
+    OutsideInterest,
+    /// The entry is older than the compaction cutoff of the replica.
+    ///
+    /// Tombstones older than the cutoff may have been removed, so such entries could have been
+    /// deleted already.
+    #[error("entry is older than the compaction cutoff")]
+    Compacted,
+    /// The local clock is too far behind the entries of the replica.
+    ///
+    /// A hybrid logical clock timestamp for the entry would exceed
+    /// [`MAX_TIMESTAMP_FUTURE_SHIFT`], so peers would reject it.
+    #[error("local clock is too far behind the entries of the replica")]
+    ClockBehind,
 }
 
 /// Reason why entry validation failed
@@ -678,6 +1139,9 @@ pub enum ValidationFailure {
     /// Entry has length 0 but not the empty hash, or the empty hash but not length 0.
     #[error("Entry has length 0 but not the empty hash, or the empty hash but not length 0")]
     InvalidEmptyEntry,
+    /// Entry is not signed by the namespace and not covered by a write delegation.
+    #[error("Entry is not signed by the namespace and not covered by a write delegation")]
+    NotDelegated,
 }
 
 /// A signed entry.
@@ -730,6 +1194,14 @@ impl SignedEntry {
         SignedEntry { signature, entry }
     }
 
+    /// Create a new signed entry by signing an entry with the `author` only.
+    ///
+    /// The entry is only accepted by replicas which know a [`WriteDelegation`] that covers it.
+    pub fn from_entry_delegated(entry: Entry, author: &Author) -> Self {
+        let signature = EntrySignature::from_entry_delegated(&entry, author);
+        SignedEntry { signature, entry }
+    }
+
     /// Create a new signed entries from its parts.
     pub fn from_parts(
         namespace: &NamespaceSecret,
@@ -743,6 +1215,8 @@ impl SignedEntry {
     }
 
     /// Verify the signatures on this entry.
+    ///
+    /// Entries written under a [`WriteDelegation`] fail to verify, see [`Self::verify_delegated`].
     pub fn verify<S: store::PublicKeyStore>(&self, store: &S) -> Result<(), SignatureError> {
         self.signature.verify(
             &self.entry,
@@ -751,6 +1225,33 @@ impl SignedEntry {
         )
     }
 
+    /// Verify the signatures on this entry, accepting entries written under a
+    /// [`WriteDelegation`], and return whether it was written under one.
+    ///
+    /// For delegated entries, this only verifies the signatures of the author, not that a
+    /// delegation covering the entry exists.
+    pub fn verify_delegated<S: store::PublicKeyStore>(
+        &self,
+        store: &S,
+    ) -> Result<bool, SignatureError> {
+        self.signature.verify_delegated(
+            &self.entry,
+            &self.entry.namespace().public_key(store)?,
+            &self.entry.author().public_key(store)?,
+        )
+    }
+
+    /// Whether this entry lacks a valid namespace signature, without verifying the author
+    /// signature.
+    fn is_delegated<S: store::PublicKeyStore>(&self, store: &S) -> bool {
+        match self.entry.namespace().public_key(store) {
+            Ok(namespace) => namespace
+                .verify(&self.entry.to_vec(), &self.signature.namespace_signature)
+                .is_err(),
+            Err(_) => true,
+        }
+    }
+
     /// Get the signature.
     pub fn signature(&self) -> &EntrySignature {
         &self.signature
@@ -852,6 +1353,21 @@ impl EntrySignature {
         }
     }
 
+    /// Create a new signature for an entry written under a [`WriteDelegation`].
+    ///
+    /// In place of the namespace signature, the `author` signs the entry a second time with a
+    /// separate domain.
+    pub fn from_entry_delegated(entry: &Entry, author: &Author) -> Self {
+        let bytes = entry.to_vec();
+        let author_signature = author.sign(&bytes);
+        let namespace_signature = author.sign(&delegated_signing_bytes(&bytes));
+
+        EntrySignature {
+            author_signature,
+            namespace_signature,
+        }
+    }
+
     /// Verify that this signature was created by signing the `entry` with the
     /// secret keys of the specified `author` and `namespace`.
     pub fn verify(
@@ -867,6 +1383,25 @@ impl EntrySignature {
         Ok(())
     }
 
+    /// Like [`Self::verify`], but also accepts signatures created with the key of the `author`
+    /// only, for entries written under a [`WriteDelegation`].
+    ///
+    /// Returns whether the entry was written under a [`WriteDelegation`].
+    pub fn verify_delegated(
+        &self,
+        entry: &Entry,
+        namespace: &NamespacePublicKey,
+        author: &AuthorPublicKey,
+    ) -> Result<bool, SignatureError> {
+        let bytes = entry.to_vec();
+        author.verify(&bytes, &self.author_signature)?;
+        if namespace.verify(&bytes, &self.namespace_signature).is_ok() {
+            return Ok(false);
+        }
+        author.verify(&delegated_signing_bytes(&bytes), &self.namespace_signature)?;
+        Ok(true)
+    }
+
     pub(crate) fn from_parts(namespace_sig: &[u8; 64], author_sig: &[u8; 64]) -> Self {
         let namespace_signature = Signature::from_bytes(namespace_sig);
         let author_signature = Signature::from_bytes(author_sig);
@@ -886,6 +1421,12 @@ impl EntrySignature {
     }
 }
 
+fn delegated_signing_bytes(entry_bytes: &[u8]) -> Vec<u8> {
+    let mut out = DELEGATED_ENTRY_DOMAIN.to_vec();
+    out.extend_from_slice(entry_bytes);
+    out
+}
+
 /// A single entry in a [`Replica`]
 ///
 /// An entry is identified by a key, its [`Author`], and the [`Replica`]'s
@@ -963,6 +1504,11 @@ impl Entry {
     pub fn sign(self, namespace: &NamespaceSecret, author: &Author) -> SignedEntry {
         SignedEntry::from_entry(self, namespace, author)
     }
+
+    /// Sign this entry with an [`Author`] only, to be written under a [`WriteDelegation`].
+    pub fn sign_delegated(self, author: &Author) -> SignedEntry {
+        SignedEntry::from_entry_delegated(self, author)
+    }
 }
 
 const NAMESPACE_BYTES: std::ops::Range<usize> = 0..32;
@@ -996,7 +1542,7 @@ impl RangeKey for RecordIdentifier {
     }
 }
 
-fn system_time_now() -> u64 {
+pub(crate) fn system_time_now() -> u64 {
     SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .expect("time drift")
@@ -1200,7 +1746,7 @@ mod tests {
     use crate::{
         actor::SyncHandle,
         ranger::{Range, Store as _},
-        store::{OpenError, Query, SortBy, SortDirection, Store},
+        store::{Cursor, OpenError, Query, QueryBuilder, SortBy, SortDirection, Store},
     };
 
     use super::*;
@@ -1628,6 +2174,150 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_replica_sync_area() -> Result<()> {
+        let mut alice_store = store::Store::memory();
+        let mut bob_store = store::Store::memory();
+        let mut rng = rand::thread_rng();
+        let author = Author::new(&mut rng);
+        let myspace = NamespaceSecret::new(&mut rng);
+
+        let mut alice = alice_store.new_replica(myspace.clone())?;
+        alice.hash_and_insert("/users/me/a", &author, "a")?;
+        alice.hash_and_insert("/users/you/b", &author, "b")?;
+        let mut bob = bob_store.new_replica(myspace.clone())?;
+        bob.hash_and_insert("/users/me/c", &author, "c")?;
+        bob.hash_and_insert("/users/you/d", &author, "d")?;
+        bob.hash_and_insert("/config", &author, "e")?;
+
+        let area = AreaOfInterest::prefixes(["/users/me/"]);
+        let (alice_out, bob_out) = sync_area(&mut alice, &mut bob, &area)?;
+        assert_eq!(alice_out.num_sent, 1);
+        assert_eq!(bob_out.num_sent, 1);
+
+        check_entries(&mut alice_store, &myspace.id(), &author, &["/users/me/c"])?;
+        check_entries(&mut bob_store, &myspace.id(), &author, &["/users/me/a"])?;
+        let hash = get_content_hash(&mut alice_store, myspace.id(), author.id(), b"/config")?;
+        assert!(hash.is_none());
+        let hash = get_content_hash(&mut bob_store, myspace.id(), author.id(), b"/users/you/b")?;
+        assert!(hash.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_replica_apply_batch() -> Result<()> {
+        let mut store = store::Store::memory();
+        let mut rng = rand::thread_rng();
+        let author = Author::new(&mut rng);
+        let namespace = NamespaceSecret::new(&mut rng);
+        let mut replica = store.new_replica(namespace.clone())?;
+        let (events_sender, events) = async_channel::bounded(32);
+        replica.info.subscribe(events_sender);
+
+        replica.hash_and_insert("c/x", &author, "x")?;
+        let insert = |key: &str, value: &str| BatchOp::Insert {
+            key: Bytes::copy_from_slice(key.as_bytes()),
+            hash: Hash::new(value),
+            len: value.len() as u64,
+        };
+        let ops = vec![
+            insert("a", "1"),
+            insert("b", "2"),
+            BatchOp::Delete {
+                prefix: Bytes::from_static(b"c/"),
+            },
+        ];
+        let removed = replica.apply_batch(&author, ops)?;
+        assert_eq!(removed, 1);
+
+        assert!(matches!(events.try_recv()?, Event::LocalInsert { .. }));
+        let Event::LocalBatch { entries, .. } = events.try_recv()? else {
+            panic!("expected a batch event");
+        };
+        let keys: Vec<_> = entries.iter().map(|e| e.key()).collect();
+        assert_eq!(keys, vec![&b"a"[..], &b"b"[..], &b"c/"[..]]);
+        assert!(events.try_recv().is_err());
+
+        // a newer entry for "e" makes the second write fail, so the first is rolled back
+        let id = RecordIdentifier::new(namespace.id(), author.id(), "e");
+        let record = Record::new(Hash::new("e"), 1, system_time_now() + 500_000);
+        let entry = SignedEntry::from_entry(Entry::new(id, record), &namespace, &author);
+        replica.insert_remote_entry(entry, [1u8; 32], ContentStatus::Missing)?;
+        assert!(events.try_recv().is_ok());
+        let res = replica.apply_batch(&author, vec![insert("d", "3"), insert("e", "4")]);
+        assert!(matches!(res, Err(InsertError::NewerEntryExists)));
+        assert!(events.try_recv().is_err());
+        drop(replica);
+
+        assert!(store
+            .get_exact(namespace.id(), author.id(), "d", true)?
+            .is_none());
+        assert!(store
+            .get_exact(namespace.id(), author.id(), "a", false)?
+            .is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_hybrid_clock() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let mut store = store::Store::memory();
+        let alice = Author::new(&mut rng);
+        let bob = Author::new(&mut rng);
+        let namespace = NamespaceSecret::new(&mut rng);
+        let mut replica = store.new_replica(namespace.clone())?;
+        replica
+            .store
+            .store
+            .set_clock_mode(&namespace.id(), &ClockMode::Hybrid)?;
+
+        // entries too far in the future are rejected in every clock mode
+        let t = system_time_now() + 30 * 60 * 1_000_000;
+        let entry = SignedEntry::from_parts(&namespace, &bob, b"k", Record::from_data(b"1", t));
+        let res = replica.insert_remote_entry(entry, [1u8; 32], ContentStatus::Missing);
+        assert!(matches!(
+            res,
+            Err(InsertError::Validation(
+                ValidationFailure::TooFarInTheFuture
+            ))
+        ));
+
+        // an entry from a peer whose clock is ahead, but within the accepted shift
+        let t = system_time_now() + MAX_TIMESTAMP_FUTURE_SHIFT - 1000;
+        let entry = SignedEntry::from_parts(&namespace, &bob, b"k", Record::from_data(b"1", t));
+        replica.insert_remote_entry(entry, [1u8; 32], ContentStatus::Missing)?;
+
+        // a causally later write wins even though the wall clock is behind
+        replica.hash_and_insert(b"k", &alice, b"2")?;
+        let query = Query::single_latest_per_key().build();
+        let entries = store
+            .get_many(namespace.id(), query)?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].author(), alice.id());
+        assert!(entries[0].timestamp() > t);
+
+        // the local entry is accepted by peers that use the wall clock
+        assert!(entries[0].timestamp() <= system_time_now() + MAX_TIMESTAMP_FUTURE_SHIFT);
+
+        // an entry from a peer whose clock is ahead by exactly the accepted shift
+        let mut replica = store.open_replica(&namespace.id())?;
+        let t = system_time_now() + MAX_TIMESTAMP_FUTURE_SHIFT;
+        let entry = SignedEntry::from_parts(&namespace, &bob, b"cap", Record::from_data(b"1", t));
+        replica.insert_remote_entry(entry, [1u8; 32], ContentStatus::Missing)?;
+
+        // local writes can not be later than the entry without exceeding the shift, so they
+        // fail instead of losing against it
+        let now = t - MAX_TIMESTAMP_FUTURE_SHIFT;
+        assert!(matches!(
+            hybrid_timestamp(now, t),
+            Err(InsertError::ClockBehind)
+        ));
+        assert_eq!(hybrid_timestamp(now, t - 1)?, t);
+        assert_eq!(hybrid_timestamp(now + 1, t)?, t + 1);
+        Ok(())
+    }
+
     #[test]
     fn test_replica_timestamp_sync_memory() -> Result<()> {
         let alice_store = store::Store::memory();
@@ -2206,13 +2896,14 @@ mod tests {
 
         replica1.hash_and_insert(b"foo", &author, b"init")?;
 
-        let from1 = replica1.sync_initial_message()?;
+        let area = AreaOfInterest::full();
+        let from1 = replica1.sync_initial_message(&area)?;
         let from2 = replica2
-            .sync_process_message(from1, peer1, &mut state2)
+            .sync_process_message(from1, peer1, &area, &mut state2)
             .unwrap()
             .unwrap();
         let from1 = replica1
-            .sync_process_message(from2, peer2, &mut state1)
+            .sync_process_message(from2, peer2, &area, &mut state1)
             .unwrap()
             .unwrap();
         // now we will receive the entry from rpelica1. we will insert a newer entry now, while the
@@ -2220,7 +2911,7 @@ mod tests {
         // sure that no InsertRemote event is emitted for this entry.
         replica2.hash_and_insert(b"foo", &author, b"update")?;
         let from2 = replica2
-            .sync_process_message(from1, peer1, &mut state2)
+            .sync_process_message(from1, peer1, &area, &mut state2)
             .unwrap();
         assert!(from2.is_none());
         let events1 = drain(events1);
@@ -2427,6 +3118,148 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_replica_query_pages() -> Result<()> {
+        let mut store = store::Store::memory();
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let namespace = NamespaceSecret::new(&mut rng);
+        let a1 = store.new_author(&mut rng)?;
+        let a2 = store.new_author(&mut rng)?;
+
+        let mut replica = store.new_replica(namespace.clone())?;
+        let base = system_time_now() - 1_000_000;
+        let entries = [
+            ("a", &a1, 3),
+            ("b", &a2, 1),
+            ("c", &a1, 2),
+            ("d", &a2, 4),
+            ("e", &a1, 5),
+            ("c", &a2, 6),
+        ];
+        for (key, author, offset) in entries {
+            let id = RecordIdentifier::new(namespace.id(), author.id(), key);
+            let record = Record::new(Hash::new(key), 1, base + offset);
+            let entry = SignedEntry::from_entry(Entry::new(id, record), &namespace, author);
+            replica.insert_remote_entry(entry, [1u8; 32], ContentStatus::Missing)?;
+        }
+        drop(replica);
+
+        fn keys(store: &mut Store, namespace: NamespaceId, query: Query) -> Result<Vec<String>> {
+            store
+                .get_many(namespace, query)?
+                .map(|e| e.map(|e| String::from_utf8(e.key().to_vec()).unwrap()))
+                .collect()
+        }
+
+        fn paged<K>(
+            store: &mut Store,
+            namespace: NamespaceId,
+            query: impl Fn() -> QueryBuilder<K>,
+        ) -> Result<Vec<String>>
+        where
+            Query: From<QueryBuilder<K>>,
+        {
+            let mut all = Vec::new();
+            let mut cursor = None;
+            loop {
+                let mut page = query().limit(2);
+                if let Some(cursor) = cursor.take() {
+                    page = page.after(cursor);
+                }
+                let page = store
+                    .get_many(namespace, Query::from(page))?
+                    .collect::<Result<Vec<_>>>()?;
+                all.extend(
+                    page.iter()
+                        .map(|e| String::from_utf8(e.key().to_vec()).unwrap()),
+                );
+                match page.last() {
+                    Some(last) if page.len() == 2 => cursor = Some(Cursor::from_entry(last)),
+                    _ => break,
+                }
+            }
+            assert_eq!(all, keys(store, namespace, query().into())?);
+            Ok(all)
+        }
+
+        let ns = namespace.id();
+        let key_author = || Query::all().sort_by(SortBy::KeyAuthor, SortDirection::Asc);
+        assert_eq!(
+            keys(&mut store, ns, key_author().key_range("b".."d").build())?,
+            vec!["b", "c", "c"]
+        );
+        assert_eq!(
+            keys(&mut store, ns, key_author().key_range("b"..="d").build())?,
+            vec!["b", "c", "c", "d"]
+        );
+        assert_eq!(
+            keys(&mut store, ns, key_author().key_range(..="b").build())?,
+            vec!["a", "b"]
+        );
+        assert_eq!(
+            keys(
+                &mut store,
+                ns,
+                Query::author(a1.id()).key_range("b"..).build()
+            )?,
+            vec!["c", "e"]
+        );
+
+        let by_timestamp = |direction| Query::all().sort_by(SortBy::Timestamp, direction);
+        assert_eq!(
+            keys(&mut store, ns, by_timestamp(SortDirection::Asc).build())?,
+            vec!["b", "c", "a", "d", "e", "c"]
+        );
+        assert_eq!(
+            keys(
+                &mut store,
+                ns,
+                by_timestamp(SortDirection::Desc).limit(2).build()
+            )?,
+            vec!["c", "e"]
+        );
+
+        assert_eq!(paged(&mut store, ns, Query::all)?.len(), 6);
+        assert_eq!(
+            paged(&mut store, ns, || Query::all()
+                .sort_by(SortBy::KeyAuthor, SortDirection::Desc))?,
+            vec!["e", "d", "c", "c", "b", "a"]
+        );
+        assert_eq!(
+            paged(&mut store, ns, || by_timestamp(SortDirection::Asc))?,
+            vec!["b", "c", "a", "d", "e", "c"]
+        );
+        assert_eq!(
+            paged(&mut store, ns, || by_timestamp(SortDirection::Desc)
+                .key_range("b".."e"))?,
+            vec!["c", "d", "c", "b"]
+        );
+        assert_eq!(
+            paged(&mut store, ns, Query::single_latest_per_key)?,
+            vec!["a", "b", "c", "d", "e"]
+        );
+        assert_eq!(
+            paged(&mut store, ns, || Query::single_latest_per_key()
+                .sort_direction(SortDirection::Desc))?,
+            vec!["e", "d", "c", "b", "a"]
+        );
+        assert_eq!(
+            paged(&mut store, ns, || Query::all()
+                .key_range("b"..)
+                .as_of(u64::MAX))?
+            .len(),
+            5
+        );
+
+        // a cursor from another author does not leave the bounds of the query
+        let other = Cursor::from_entry(&store.get_exact(ns, a2.id(), "d", false)?.unwrap());
+        let query = Query::author(a1.id()).after(other).build();
+        for entry in store.get_many(ns, query)? {
+            assert_eq!(entry?.author(), a1.id());
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_dl_policies_mem() -> Result<()> {
         let mut store = store::Store::memory();
@@ -2460,6 +3293,98 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_delegated_writes_memory() -> Result<()> {
+        let owner_store = store::Store::memory();
+        let contractor_store = store::Store::memory();
+        test_delegated_writes(owner_store, contractor_store)
+    }
+
+    #[test]
+    fn test_delegated_writes_fs() -> Result<()> {
+        let owner_dbfile = tempfile::NamedTempFile::new()?;
+        let owner_store = store::fs::Store::persistent(owner_dbfile.path())?;
+        let contractor_dbfile = tempfile::NamedTempFile::new()?;
+        let contractor_store = store::fs::Store::persistent(contractor_dbfile.path())?;
+        test_delegated_writes(owner_store, contractor_store)
+    }
+
+    fn test_delegated_writes(mut owner_store: Store, mut contractor_store: Store) -> Result<()> {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let namespace = NamespaceSecret::new(&mut rng);
+        let owner = owner_store.new_author(&mut rng)?;
+        let contractor = contractor_store.new_author(&mut rng)?;
+
+        let mut owner_replica = owner_store.new_replica(namespace.clone())?;
+        owner_replica.hash_and_insert(b"/config", &owner, b"owner")?;
+        let scope = DelegationScope::new(contractor.id(), "/uploads/contractor/");
+        let delegation = owner_replica.delegate(scope, None)?;
+
+        contractor_store.import_namespace(Capability::Read(namespace.id()))?;
+        let mut contractor_replica = contractor_store.open_replica(&namespace.id())?;
+        let res = contractor_replica.hash_and_insert(b"/uploads/contractor/a", &contractor, b"a");
+        assert!(matches!(res, Err(InsertError::ReadOnly)));
+
+        // with the delegation, only keys below the prefix can be written
+        assert!(contractor_replica.add_delegation(delegation.clone())?);
+        assert!(!contractor_replica.add_delegation(delegation)?);
+        contractor_replica.hash_and_insert(b"/uploads/contractor/a", &contractor, b"a")?;
+        let res = contractor_replica.hash_and_insert(b"/config", &contractor, b"evil");
+        assert!(matches!(res, Err(InsertError::ReadOnly)));
+        let res = contractor_replica.hash_and_insert(b"/uploads/contractor/b", &owner, b"b");
+        assert!(matches!(res, Err(InsertError::ReadOnly)));
+
+        // delegated entries are accepted by the owner on sync
+        sync(&mut owner_replica, &mut contractor_replica)?;
+        let entry = get_entry(
+            &mut owner_store,
+            namespace.id(),
+            contractor.id(),
+            b"/uploads/contractor/a",
+        )?;
+        assert!(entry.verify_delegated(&())?);
+        get_entry(
+            &mut contractor_store,
+            namespace.id(),
+            owner.id(),
+            b"/config",
+        )?;
+
+        // entries outside of the delegation are rejected
+        let mut owner_replica = owner_store.open_replica(&namespace.id())?;
+        let record = Record::current_from_data(b"evil");
+        let id = RecordIdentifier::new(namespace.id(), contractor.id(), b"/config");
+        let entry = Entry::new(id, record).sign_delegated(&contractor);
+        assert!(entry.verify(&()).is_err());
+        assert!(entry.verify_delegated(&())?);
+        let res = owner_replica.insert_remote_entry(entry, [1u8; 32], ContentStatus::Missing);
+        assert!(matches!(
+            res,
+            Err(InsertError::Validation(ValidationFailure::NotDelegated))
+        ));
+
+        // entries outside of the time window are rejected
+        let scope = DelegationScope::new(contractor.id(), "/expired/").not_after(1);
+        let delegation = owner_replica.delegate(scope, None)?;
+        assert_eq!(delegation.author(), contractor.id());
+        let record = Record::current_from_data(b"late");
+        let id = RecordIdentifier::new(namespace.id(), contractor.id(), b"/expired/x");
+        let entry = Entry::new(id, record).sign_delegated(&contractor);
+        let res = owner_replica.insert_remote_entry(entry, [1u8; 32], ContentStatus::Missing);
+        assert!(matches!(
+            res,
+            Err(InsertError::Validation(ValidationFailure::NotDelegated))
+        ));
+
+        // entries written within the time window are still accepted after it closed, so that
+        // peers syncing later converge
+        let record = Record::new(Hash::new(b"early"), 5, 1);
+        let id = RecordIdentifier::new(namespace.id(), contractor.id(), b"/expired/y");
+        let entry = Entry::new(id, record).sign_delegated(&contractor);
+        owner_replica.insert_remote_entry(entry, [1u8; 32], ContentStatus::Missing)?;
+        Ok(())
+    }
+
     fn assert_keys(store: &mut Store, namespace: NamespaceId, mut expected: Vec<Vec<u8>>) {
         expected.sort();
         assert_eq!(expected, get_keys_sorted(store, namespace));
@@ -2501,19 +3426,28 @@ mod tests {
     }
 
     fn sync(alice: &mut Replica, bob: &mut Replica) -> Result<(SyncOutcome, SyncOutcome)> {
+        sync_area(alice, bob, &AreaOfInterest::full())
+    }
+
+    fn sync_area(
+        alice: &mut Replica,
+        bob: &mut Replica,
+        area: &AreaOfInterest,
+    ) -> Result<(SyncOutcome, SyncOutcome)> {
         let alice_peer_id = [1u8; 32];
         let bob_peer_id = [2u8; 32];
         let mut alice_state = SyncOutcome::default();
         let mut bob_state = SyncOutcome::default();
         // Sync alice - bob
-        let mut next_to_bob = Some(alice.sync_initial_message()?);
+        let mut next_to_bob = Some(alice.sync_initial_message(area)?);
         let mut rounds = 0;
         while let Some(msg) = next_to_bob.take() {
             assert!(rounds < 100, "too many rounds");
             rounds += 1;
             println!("round {}", rounds);
-            if let Some(msg) = bob.sync_process_message(msg, alice_peer_id, &mut bob_state)? {
-                next_to_bob = alice.sync_process_message(msg, bob_peer_id, &mut alice_state)?
+            if let Some(msg) = bob.sync_process_message(msg, alice_peer_id, area, &mut bob_state)? {
+                next_to_bob =
+                    alice.sync_process_message(msg, bob_peer_id, area, &mut alice_state)?
             }
         }
         assert_eq!(alice_state.num_sent, bob_state.num_recv);
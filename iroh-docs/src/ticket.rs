@@ -4,7 +4,7 @@ use iroh_base::ticket;
 use iroh_net::NodeAddr;
 use serde::{Deserialize, Serialize};
 
-use crate::Capability;
+use crate::{Capability, Invite};
 
 /// Contains both a key (either secret or public) to a document, and a list of peers to join.
 #[derive(Serialize, Deserialize, Clone, Debug, derive_more::Display)]
@@ -61,6 +61,55 @@ impl std::str::FromStr for DocTicket {
     }
 }
 
+/// Contains an [`Invite`] to a document, and the inviting nodes to redeem it with.
+#[derive(Serialize, Deserialize, Clone, Debug, derive_more::Display)]
+#[display("{}", ticket::Ticket::serialize(self))]
+pub struct InviteTicket {
+    /// The signed invite.
+    pub invite: Invite,
+    /// A list of nodes to contact.
+    pub nodes: Vec<NodeAddr>,
+}
+
+/// Wire format for [`InviteTicket`].
+#[derive(Serialize, Deserialize)]
+enum InviteTicketWireFormat {
+    Variant0(InviteTicket),
+}
+
+impl ticket::Ticket for InviteTicket {
+    const KIND: &'static str = "invite";
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let data = InviteTicketWireFormat::Variant0(self.clone());
+        postcard::to_stdvec(&data).expect("postcard serialization failed")
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, ticket::Error> {
+        let res: InviteTicketWireFormat =
+            postcard::from_bytes(bytes).map_err(ticket::Error::Postcard)?;
+        let InviteTicketWireFormat::Variant0(res) = res;
+        if res.nodes.is_empty() {
+            return Err(ticket::Error::Verify("addressing info cannot be empty"));
+        }
+        Ok(res)
+    }
+}
+
+impl InviteTicket {
+    /// Create a new invite ticket
+    pub fn new(invite: Invite, nodes: Vec<NodeAddr>) -> Self {
+        Self { invite, nodes }
+    }
+}
+
+impl std::str::FromStr for InviteTicket {
+    type Err = ticket::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ticket::Ticket::deserialize(s)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
@@ -101,4 +150,18 @@ mod tests {
         ").unwrap();
         assert_eq_hex!(base32, expected);
     }
+
+    #[test]
+    fn test_invite_ticket_roundtrip() {
+        let mut rng = rand::thread_rng();
+        let namespace = crate::NamespaceSecret::new(&mut rng);
+        let invite = Invite::new(&namespace, crate::CapabilityKind::Write, None, true, None);
+        let node_id = iroh_net::key::SecretKey::generate().public();
+        let ticket = InviteTicket::new(invite, vec![NodeAddr::from_parts(node_id, None, vec![])]);
+
+        let parsed = InviteTicket::from_str(&ticket.to_string()).unwrap();
+        assert_eq!(parsed.invite, ticket.invite);
+        assert_eq!(parsed.nodes, ticket.nodes);
+        assert!(DocTicket::from_str(&ticket.to_string()).is_err());
+    }
 }
@@ -49,6 +49,8 @@ pub type RpcClient =
 #[derive(Debug, Clone)]
 pub struct Iroh {
     rpc: RpcClient,
+    blobs: blobs::Client,
+    tags: tags::Client,
 }
 
 impl Iroh {
@@ -59,12 +61,22 @@ impl Iroh {
     ///
     /// See also the [`Iroh`] struct documentation.
     pub fn new(rpc: RpcClient) -> Self {
-        Self { rpc }
+        let blobs = blobs::Client {
+            rpc: rpc.clone(),
+            store: None,
+        };
+        let tags = tags::Client {
+            rpc: rpc.clone(),
+            store: None,
+        };
+        Self { rpc, blobs, tags }
     }
 
-    /// Returns the blobs client.
+    /// Returns the blobs client for the default blob store.
+    ///
+    /// Use [`blobs::Client::in_store`] to operate on a named blob store.
     pub fn blobs(&self) -> &blobs::Client {
-        blobs::Client::ref_cast(&self.rpc)
+        &self.blobs
     }
 
     /// Returns the docs client.
@@ -77,9 +89,9 @@ impl Iroh {
         authors::Client::ref_cast(&self.rpc)
     }
 
-    /// Returns the tags client.
+    /// Returns the tags client for the default blob store.
     pub fn tags(&self) -> &tags::Client {
-        tags::Client::ref_cast(&self.rpc)
+        &self.tags
     }
 
     /// Returns the gossip client.
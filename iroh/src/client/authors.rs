@@ -10,10 +10,13 @@ use iroh_docs::{Author, AuthorId};
 use ref_cast::RefCast;
 
 use crate::rpc_protocol::authors::{
-    CreateRequest, DeleteRequest, ExportRequest, GetDefaultRequest, ImportRequest, ListRequest,
-    SetDefaultRequest,
+    CreateRequest, DeleteRequest, DisableKeystoreRequest, EnableKeystoreRequest, ExportRequest,
+    GetDefaultRequest, ImportRequest, KeystoreStatusRequest, ListRequest, LockRequest,
+    SetDefaultRequest, UnlockRequest,
 };
 
+pub use iroh_docs::KeystoreStatus;
+
 use super::{flatten, RpcClient};
 
 /// Iroh authors client.
@@ -81,6 +84,67 @@ impl Client {
         Ok(())
     }
 
+    /// Exports the given author as armored text, encrypted with `passphrase`.
+    pub async fn export_armored(
+        &self,
+        author: AuthorId,
+        passphrase: &str,
+    ) -> Result<Option<String>> {
+        let Some(author) = self.export(author).await? else {
+            return Ok(None);
+        };
+        Ok(Some(iroh_docs::armor_author(&author, passphrase)?))
+    }
+
+    /// Imports an author from armored text created by [`Self::export_armored`].
+    pub async fn import_armored(&self, armored: &str, passphrase: &str) -> Result<AuthorId> {
+        let author = iroh_docs::dearmor_author(armored, passphrase)?;
+        let author_id = author.id();
+        self.import(author).await?;
+        Ok(author_id)
+    }
+
+    /// Returns the status of the keystore, which protects the secret keys of authors and
+    /// documents with a passphrase.
+    pub async fn keystore_status(&self) -> Result<KeystoreStatus> {
+        let res = self.rpc.rpc(KeystoreStatusRequest).await??;
+        Ok(res.status)
+    }
+
+    /// Encrypts the stored secret keys of authors and documents with a passphrase.
+    ///
+    /// After a restart, the node can read and sync documents, but cannot write to them until it
+    /// is unlocked with [`Self::unlock`].
+    pub async fn enable_keystore(&self, passphrase: impl Into<String>) -> Result<()> {
+        let passphrase = passphrase.into();
+        self.rpc.rpc(EnableKeystoreRequest { passphrase }).await??;
+        Ok(())
+    }
+
+    /// Stores the secret keys in cleartext again.
+    pub async fn disable_keystore(&self, passphrase: impl Into<String>) -> Result<()> {
+        let passphrase = passphrase.into();
+        self.rpc
+            .rpc(DisableKeystoreRequest { passphrase })
+            .await??;
+        Ok(())
+    }
+
+    /// Unlocks the keystore, so that the secret keys can be used.
+    pub async fn unlock(&self, passphrase: impl Into<String>) -> Result<()> {
+        let passphrase = passphrase.into();
+        self.rpc.rpc(UnlockRequest { passphrase }).await??;
+        Ok(())
+    }
+
+    /// Locks the keystore.
+    ///
+    /// Writing to documents fails until the keystore is unlocked again.
+    pub async fn lock(&self) -> Result<()> {
+        self.rpc.rpc(LockRequest).await??;
+        Ok(())
+    }
+
     /// Deletes the given author by id.
     ///
     /// Warning: This permanently removes this author.
@@ -133,4 +197,88 @@ mod tests {
 
         Ok(())
     }
+
+    #[cfg(feature = "fs-store")]
+    #[tokio::test]
+    async fn test_keystore() -> Result<()> {
+        let iroh_root = tempfile::TempDir::new()?;
+        let doc_id = {
+            let node = Node::persistent(iroh_root.path())
+                .await?
+                .enable_docs()
+                .spawn()
+                .await?;
+            let author = node.authors().default().await?;
+            let doc = node.docs().create().await?;
+            doc.set_bytes(author, "key", "before").await?;
+            assert_eq!(
+                node.authors().keystore_status().await?,
+                KeystoreStatus::Disabled
+            );
+            node.authors().enable_keystore("hunter2").await?;
+            assert_eq!(
+                node.authors().keystore_status().await?,
+                KeystoreStatus::Unlocked
+            );
+            doc.set_bytes(author, "key", "enabled").await?;
+
+            let armored = node
+                .authors()
+                .export_armored(author, "export")
+                .await?
+                .expect("should have author");
+            assert!(node
+                .authors()
+                .import_armored(&armored, "wrong")
+                .await
+                .is_err());
+            assert_eq!(
+                node.authors().import_armored(&armored, "export").await?,
+                author
+            );
+
+            let id = doc.id();
+            node.shutdown().await?;
+            id
+        };
+
+        let node = Node::persistent(iroh_root.path())
+            .await?
+            .enable_docs()
+            .spawn()
+            .await?;
+        assert_eq!(
+            node.authors().keystore_status().await?,
+            KeystoreStatus::Locked
+        );
+        let author = node.authors().default().await?;
+        let authors: Vec<_> = node.authors().list().await?.try_collect().await?;
+        assert_eq!(authors, vec![author]);
+        assert!(node.authors().export(author).await.is_err());
+
+        // a locked node can read, but not write
+        let doc = node.docs().open(doc_id).await?.expect("doc exists");
+        let entry = doc
+            .get_exact(author, "key", false)
+            .await?
+            .expect("entry exists");
+        assert_eq!(&doc.content_bytes(&entry).await?[..], b"enabled");
+        assert!(doc.set_bytes(author, "key", "locked").await.is_err());
+
+        assert!(node.authors().unlock("hunter3").await.is_err());
+        node.authors().unlock("hunter2").await?;
+        doc.set_bytes(author, "key", "unlocked").await?;
+
+        node.authors().lock().await?;
+        assert!(doc.set_bytes(author, "key", "locked").await.is_err());
+
+        node.authors().disable_keystore("hunter2").await?;
+        assert_eq!(
+            node.authors().keystore_status().await?,
+            KeystoreStatus::Disabled
+        );
+        assert!(node.authors().export(author).await?.is_some());
+
+        Ok(())
+    }
 }
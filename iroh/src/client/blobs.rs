@@ -41,6 +41,9 @@
 //! These are more advanced operations that are usually not needed in normal
 //! operation.
 //!
+//! - [`in_store`](Client::in_store) returns a client for one of the additional
+//!   named blob stores of the node.
+//!
 //! - [`consistency_check`](Client::consistency_check) checks the internal
 //!   consistency of the local blob store.
 //! - [`validate`](Client::validate) validates the locally stored data against
@@ -66,6 +69,7 @@ use std::{
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
+    time::Duration,
 };
 
 use anyhow::{anyhow, Context as _, Result};
@@ -82,32 +86,35 @@ use iroh_blobs::{
     util::SetTagOption,
     BlobFormat, Hash, Tag,
 };
-use iroh_net::NodeAddr;
+use iroh_net::{NodeAddr, NodeId};
 use portable_atomic::{AtomicU64, Ordering};
 use quic_rpc::client::BoxStreamSync;
-use ref_cast::RefCast;
 use serde::{Deserialize, Serialize};
 use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
 use tokio_util::io::{ReaderStream, StreamReader};
 use tracing::warn;
 mod batch;
 pub use batch::{AddDirOpts, AddFileOpts, AddReaderOpts, Batch};
+mod upload;
+pub use upload::{Upload, UploadId};
 
 use crate::rpc_protocol::blobs::{
     AddPathRequest, AddStreamRequest, AddStreamUpdate, BatchCreateRequest, BatchCreateResponse,
     BlobStatusRequest, ConsistencyCheckRequest, CreateCollectionRequest, CreateCollectionResponse,
     DeleteRequest, DownloadRequest, ExportRequest, ListIncompleteRequest, ListRequest,
-    ReadAtRequest, ReadAtResponse, ValidateRequest,
+    ProviderEventsRequest, ReadAtRequest, ReadAtResponse, SubscribeRequest, UploadCreateRequest,
+    ValidateRequest,
 };
 use crate::rpc_protocol::node::StatusRequest;
 
 use super::{flatten, tags, Iroh, RpcClient};
 
 /// Iroh blobs client.
-#[derive(Debug, Clone, RefCast)]
-#[repr(transparent)]
+#[derive(Debug, Clone)]
 pub struct Client {
     pub(super) rpc: RpcClient,
+    /// The named blob store this client operates on, `None` for the default store.
+    pub(super) store: Option<String>,
 }
 
 impl<'a> From<&'a Iroh> for &'a RpcClient {
@@ -117,12 +124,35 @@ impl<'a> From<&'a Iroh> for &'a RpcClient {
 }
 
 impl Client {
+    /// Returns a client for the named blob store of the node.
+    ///
+    /// All operations of the returned client, including tickets created with
+    /// [`Client::share`], refer to this store. Named stores are added to a node with
+    /// [`Builder::add_blobs_store`](crate::node::Builder::add_blobs_store).
+    pub fn in_store(&self, store: impl Into<String>) -> Self {
+        Self {
+            rpc: self.rpc.clone(),
+            store: Some(store.into()),
+        }
+    }
+
+    /// The named blob store this client operates on, `None` for the default store.
+    pub fn store(&self) -> Option<&str> {
+        self.store.as_deref()
+    }
+
     /// Check if a blob is completely stored on the node.
     ///
     /// Note that this will return false for blobs that are partially stored on
     /// the node.
     pub async fn status(&self, hash: Hash) -> Result<BlobStatus> {
-        let status = self.rpc.rpc(BlobStatusRequest { hash }).await??;
+        let status = self
+            .rpc
+            .rpc(BlobStatusRequest {
+                hash,
+                store: self.store.clone(),
+            })
+            .await??;
         Ok(status.0)
     }
 
@@ -137,30 +167,104 @@ impl Client {
         }
     }
 
+    /// Subscribe to availability changes of all blobs in this store.
+    ///
+    /// The stream yields a [`BlobEvent`] whenever data for a blob starts to be added, and when a
+    /// blob becomes complete, no matter if it was added locally or downloaded from another node.
+    ///
+    /// Only changes after the node has registered the subscription are reported. Use
+    /// [`Self::subscribe_hash`] or [`Self::wait_for`] to also learn about the current status of
+    /// a blob.
+    pub async fn subscribe(&self) -> Result<impl Stream<Item = Result<BlobEvent>>> {
+        self.subscribe_inner(None).await
+    }
+
+    /// Subscribe to availability changes of a single blob in this store.
+    ///
+    /// If the blob is already stored partially or completely, this is reported as the first
+    /// event of the stream.
+    pub async fn subscribe_hash(
+        &self,
+        hash: Hash,
+    ) -> Result<impl Stream<Item = Result<BlobEvent>>> {
+        self.subscribe_inner(Some(hash)).await
+    }
+
+    async fn subscribe_inner(
+        &self,
+        hash: Option<Hash>,
+    ) -> Result<impl Stream<Item = Result<BlobEvent>>> {
+        let stream = self
+            .rpc
+            .server_streaming(SubscribeRequest {
+                hash,
+                store: self.store.clone(),
+            })
+            .await?;
+        Ok(flatten(stream).map(|res| res.map(|res| res.0)))
+    }
+
+    /// Wait until a blob is completely stored in this store.
+    ///
+    /// Returns immediately if the blob is already complete.
+    pub async fn wait_for(&self, hash: Hash) -> Result<()> {
+        let mut stream = self.subscribe_hash(hash).await?;
+        while let Some(event) = stream.next().await {
+            if let BlobEvent::Complete { .. } = event? {
+                return Ok(());
+            }
+        }
+        Err(anyhow!("subscription closed before the blob was complete"))
+    }
+
+    /// Subscribe to the events emitted while this store provides blobs to other nodes.
+    ///
+    /// The stream yields a [`ProviderEvent`] when a client connects, requests data, and while
+    /// the transfer progresses and completes. Only events emitted after the node has registered
+    /// the subscription are reported. If the subscriber falls behind, a
+    /// [`ProviderEvent::Lagged`] event reports the number of dropped events.
+    pub async fn provider_events(&self) -> Result<impl Stream<Item = Result<ProviderEvent>>> {
+        let stream = self
+            .rpc
+            .server_streaming(ProviderEventsRequest {
+                store: self.store.clone(),
+            })
+            .await?;
+        Ok(flatten(stream).map(|res| res.map(|res| res.0)))
+    }
+
     /// Create a new batch for adding data.
     ///
     /// A batch is a context in which temp tags are created and data is added to the node. Temp tags
     /// are automatically deleted when the batch is dropped, leading to the data being garbage collected
     /// unless a permanent tag is created for it.
     pub async fn batch(&self) -> Result<Batch> {
-        let (updates, mut stream) = self.rpc.bidi(BatchCreateRequest).await?;
-        let BatchCreateResponse::Id(batch) = stream.next().await.context("expected scope id")??;
+        let (updates, mut stream) = self
+            .rpc
+            .bidi(BatchCreateRequest {
+                store: self.store.clone(),
+            })
+            .await?;
+        let BatchCreateResponse::Id(batch) = stream
+            .next()
+            .await
+            .context("expected scope id, the store might not exist")??;
         let rpc = self.rpc.clone();
-        Ok(Batch::new(batch, rpc, updates, 1024))
+        Ok(Batch::new(batch, rpc, self.store.clone(), updates, 1024))
     }
 
     /// Stream the contents of a a single blob.
     ///
     /// Returns a [`Reader`], which can report the size of the blob before reading it.
     pub async fn read(&self, hash: Hash) -> Result<Reader> {
-        Reader::from_rpc_read(&self.rpc, hash).await
+        Reader::from_rpc_read_at(&self.rpc, self.store.clone(), hash, 0, ReadAtLen::All).await
     }
 
     /// Read offset + len from a single blob.
     ///
     /// If `len` is `None` it will read the full blob.
     pub async fn read_at(&self, hash: Hash, offset: u64, len: ReadAtLen) -> Result<Reader> {
-        Reader::from_rpc_read_at(&self.rpc, hash, offset, len).await
+        Reader::from_rpc_read_at(&self.rpc, self.store.clone(), hash, offset, len).await
     }
 
     /// Read all bytes of single blob.
@@ -169,20 +273,14 @@ impl Client {
     /// reading is small. If not sure, use [`Self::read`] and check the size with
     /// [`Reader::size`] before calling [`Reader::read_to_bytes`].
     pub async fn read_to_bytes(&self, hash: Hash) -> Result<Bytes> {
-        Reader::from_rpc_read(&self.rpc, hash)
-            .await?
-            .read_to_bytes()
-            .await
+        self.read(hash).await?.read_to_bytes().await
     }
 
     /// Read all bytes of single blob at `offset` for length `len`.
     ///
     /// This allocates a buffer for the full length.
     pub async fn read_at_to_bytes(&self, hash: Hash, offset: u64, len: ReadAtLen) -> Result<Bytes> {
-        Reader::from_rpc_read_at(&self.rpc, hash, offset, len)
-            .await?
-            .read_to_bytes()
-            .await
+        self.read_at(hash, offset, len).await?.read_to_bytes().await
     }
 
     /// Import a blob from a filesystem path.
@@ -205,6 +303,7 @@ impl Client {
                 in_place,
                 tag,
                 wrap,
+                store: self.store.clone(),
             })
             .await?;
         Ok(AddProgress::new(stream))
@@ -226,6 +325,7 @@ impl Client {
                 collection,
                 tag,
                 tags_to_delete,
+                store: self.store.clone(),
             })
             .await??;
         Ok((hash, tag))
@@ -248,7 +348,13 @@ impl Client {
         input: impl Stream<Item = io::Result<Bytes>> + Send + Unpin + 'static,
         tag: SetTagOption,
     ) -> anyhow::Result<AddProgress> {
-        let (mut sink, progress) = self.rpc.bidi(AddStreamRequest { tag }).await?;
+        let (mut sink, progress) = self
+            .rpc
+            .bidi(AddStreamRequest {
+                tag,
+                store: self.store.clone(),
+            })
+            .await?;
         let mut input = input.map(|chunk| match chunk {
             Ok(chunk) => Ok(AddStreamUpdate::Chunk(chunk)),
             Err(err) => {
@@ -268,6 +374,27 @@ impl Client {
         Ok(AddProgress::new(progress))
     }
 
+    /// Start a resumable upload of a blob to this store.
+    ///
+    /// Unlike with [`Self::add_stream`], the node keeps the data of an upload when the
+    /// connection breaks, so that the upload can be continued later. See [`Upload`].
+    pub async fn upload(&self) -> anyhow::Result<Upload> {
+        let res = self
+            .rpc
+            .rpc(UploadCreateRequest {
+                store: self.store.clone(),
+            })
+            .await??;
+        Ok(Upload::new(res.id, self.rpc.clone(), self.store.clone()))
+    }
+
+    /// Get a handle to a resumable upload started with [`Self::upload`].
+    ///
+    /// This does not check whether the upload exists.
+    pub fn resume_upload(&self, id: UploadId) -> Upload {
+        Upload::new(id, self.rpc.clone(), self.store.clone())
+    }
+
     /// Write a blob by passing bytes.
     pub async fn add_bytes(&self, bytes: impl Into<Bytes>) -> anyhow::Result<AddOutcome> {
         let input = futures_lite::stream::once(Ok(bytes.into()));
@@ -295,7 +422,10 @@ impl Client {
     ) -> Result<impl Stream<Item = Result<ValidateProgress>>> {
         let stream = self
             .rpc
-            .server_streaming(ValidateRequest { repair })
+            .server_streaming(ValidateRequest {
+                repair,
+                store: self.store.clone(),
+            })
             .await?;
         Ok(stream.map(|res| res.map_err(anyhow::Error::from)))
     }
@@ -309,7 +439,10 @@ impl Client {
     ) -> Result<impl Stream<Item = Result<ConsistencyCheckProgress>>> {
         let stream = self
             .rpc
-            .server_streaming(ConsistencyCheckRequest { repair })
+            .server_streaming(ConsistencyCheckRequest {
+                repair,
+                store: self.store.clone(),
+            })
             .await?;
         Ok(stream.map(|r| r.map_err(anyhow::Error::from)))
     }
@@ -323,6 +456,7 @@ impl Client {
                 nodes: vec![node],
                 tag: SetTagOption::Auto,
                 mode: DownloadMode::Queued,
+                remote_store: None,
             },
         )
         .await
@@ -337,6 +471,7 @@ impl Client {
                 nodes: vec![node],
                 tag: SetTagOption::Auto,
                 mode: DownloadMode::Queued,
+                remote_store: None,
             },
         )
         .await
@@ -353,6 +488,7 @@ impl Client {
             nodes,
             tag,
             mode,
+            remote_store,
         } = opts;
         let stream = self
             .rpc
@@ -362,6 +498,8 @@ impl Client {
                 nodes,
                 tag,
                 mode,
+                store: self.store.clone(),
+                remote_store,
             })
             .await?;
         Ok(DownloadProgress::new(
@@ -390,6 +528,7 @@ impl Client {
             path: destination,
             format,
             mode,
+            store: self.store.clone(),
         };
         let stream = self.rpc.server_streaming(req).await?;
         Ok(ExportProgress::new(
@@ -399,13 +538,23 @@ impl Client {
 
     /// List all complete blobs.
     pub async fn list(&self) -> Result<impl Stream<Item = Result<BlobInfo>>> {
-        let stream = self.rpc.server_streaming(ListRequest).await?;
+        let stream = self
+            .rpc
+            .server_streaming(ListRequest {
+                store: self.store.clone(),
+            })
+            .await?;
         Ok(flatten(stream))
     }
 
     /// List all incomplete (partial) blobs.
     pub async fn list_incomplete(&self) -> Result<impl Stream<Item = Result<IncompleteBlobInfo>>> {
-        let stream = self.rpc.server_streaming(ListIncompleteRequest).await?;
+        let stream = self
+            .rpc
+            .server_streaming(ListIncompleteRequest {
+                store: self.store.clone(),
+            })
+            .await?;
         Ok(flatten(stream))
     }
 
@@ -448,7 +597,12 @@ impl Client {
     /// if it is tagged. You should usually not do this manually, but rely on the
     /// node to remove data that is not tagged.
     pub async fn delete_blob(&self, hash: Hash) -> Result<()> {
-        self.rpc.rpc(DeleteRequest { hash }).await??;
+        self.rpc
+            .rpc(DeleteRequest {
+                hash,
+                store: self.store.clone(),
+            })
+            .await??;
         Ok(())
     }
 
@@ -461,7 +615,10 @@ impl Client {
     ) -> Result<BlobTicket> {
         let mut addr = self.rpc.rpc(StatusRequest).await??.addr;
         addr.apply_options(addr_options);
-        let ticket = BlobTicket::new(addr, hash, blob_format).expect("correct ticket");
+        let mut ticket = BlobTicket::new(addr, hash, blob_format).expect("correct ticket");
+        if let Some(store) = &self.store {
+            ticket = ticket.with_store(store.clone());
+        }
 
         Ok(ticket)
     }
@@ -469,6 +626,7 @@ impl Client {
     fn tags_client(&self) -> tags::Client {
         tags::Client {
             rpc: self.rpc.clone(),
+            store: self.store.clone(),
         }
     }
 }
@@ -531,6 +689,226 @@ pub enum BlobStatus {
     },
 }
 
+/// A change in the availability of a blob in a store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlobEvent {
+    /// Data for the blob is being added, but the blob is not complete yet.
+    Partial {
+        /// The hash of the blob.
+        hash: Hash,
+    },
+    /// The blob is now stored completely.
+    Complete {
+        /// The hash of the blob.
+        hash: Hash,
+    },
+}
+
+impl BlobEvent {
+    /// The hash of the blob this event refers to.
+    pub fn hash(&self) -> Hash {
+        match self {
+            Self::Partial { hash } | Self::Complete { hash } => *hash,
+        }
+    }
+}
+
+/// An event emitted by the node while providing blobs to other nodes.
+///
+/// This is the serializable counterpart of [`iroh_blobs::provider::Event`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProviderEvent {
+    /// A new collection or tagged blob has been added.
+    TaggedBlobAdded {
+        /// The hash of the added data.
+        hash: Hash,
+        /// The format of the added data.
+        format: BlobFormat,
+        /// The tag of the added data.
+        tag: Tag,
+    },
+    /// A new client connected to the node.
+    ClientConnected {
+        /// An unique connection id.
+        connection_id: u64,
+        /// The node id of the client, if it could be determined.
+        node_id: Option<NodeId>,
+    },
+    /// A request was received from a client.
+    GetRequestReceived {
+        /// An unique connection id.
+        connection_id: u64,
+        /// An identifier uniquely identifying this transfer request.
+        request_id: u64,
+        /// The hash for which the client wants to receive data.
+        hash: Hash,
+    },
+    /// A sequence of hashes has been found and is being transferred.
+    TransferHashSeqStarted {
+        /// An unique connection id.
+        connection_id: u64,
+        /// An identifier uniquely identifying this transfer request.
+        request_id: u64,
+        /// The number of blobs in the sequence.
+        num_blobs: u64,
+    },
+    /// A chunk of a blob was transferred.
+    ///
+    /// These events are sent on a best-effort basis, so you can not assume that you will
+    /// receive all of them.
+    TransferProgress {
+        /// An unique connection id.
+        connection_id: u64,
+        /// An identifier uniquely identifying this transfer request.
+        request_id: u64,
+        /// The hash for which we are transferring data.
+        hash: Hash,
+        /// Offset up to which we have transferred data.
+        end_offset: u64,
+    },
+    /// A blob in a sequence was transferred.
+    TransferBlobCompleted {
+        /// An unique connection id.
+        connection_id: u64,
+        /// An identifier uniquely identifying this transfer request.
+        request_id: u64,
+        /// The hash of the blob.
+        hash: Hash,
+        /// The index of the blob in the sequence.
+        index: u64,
+        /// The size of the blob transferred.
+        size: u64,
+    },
+    /// A request was completed and the data was sent to the client.
+    TransferCompleted {
+        /// An unique connection id.
+        connection_id: u64,
+        /// An identifier uniquely identifying this transfer request.
+        request_id: u64,
+        /// Statistics about the transfer.
+        stats: ProviderTransferStats,
+    },
+    /// A request was aborted because the client disconnected.
+    TransferAborted {
+        /// An unique connection id.
+        connection_id: u64,
+        /// An identifier uniquely identifying this transfer request.
+        request_id: u64,
+        /// Statistics about the transfer. This is `None` if the transfer was aborted before
+        /// any data was sent.
+        stats: Option<ProviderTransferStats>,
+    },
+    /// The subscriber could not keep up with the node, and some events were dropped.
+    Lagged {
+        /// The number of events that were dropped.
+        missed: u64,
+    },
+}
+
+impl TryFrom<iroh_blobs::provider::Event> for ProviderEvent {
+    /// Events this type does not know about are returned unchanged.
+    type Error = iroh_blobs::provider::Event;
+
+    fn try_from(event: iroh_blobs::provider::Event) -> Result<Self, Self::Error> {
+        use iroh_blobs::provider::Event;
+        let event = match event {
+            Event::TaggedBlobAdded { hash, format, tag } => {
+                Self::TaggedBlobAdded { hash, format, tag }
+            }
+            Event::ClientConnected {
+                connection_id,
+                node_id,
+            } => Self::ClientConnected {
+                connection_id,
+                node_id,
+            },
+            Event::GetRequestReceived {
+                connection_id,
+                request_id,
+                hash,
+            } => Self::GetRequestReceived {
+                connection_id,
+                request_id,
+                hash,
+            },
+            Event::TransferHashSeqStarted {
+                connection_id,
+                request_id,
+                num_blobs,
+            } => Self::TransferHashSeqStarted {
+                connection_id,
+                request_id,
+                num_blobs,
+            },
+            Event::TransferProgress {
+                connection_id,
+                request_id,
+                hash,
+                end_offset,
+            } => Self::TransferProgress {
+                connection_id,
+                request_id,
+                hash,
+                end_offset,
+            },
+            Event::TransferBlobCompleted {
+                connection_id,
+                request_id,
+                hash,
+                index,
+                size,
+            } => Self::TransferBlobCompleted {
+                connection_id,
+                request_id,
+                hash,
+                index,
+                size,
+            },
+            Event::TransferCompleted {
+                connection_id,
+                request_id,
+                stats,
+            } => Self::TransferCompleted {
+                connection_id,
+                request_id,
+                stats: (*stats).into(),
+            },
+            Event::TransferAborted {
+                connection_id,
+                request_id,
+                stats,
+            } => Self::TransferAborted {
+                connection_id,
+                request_id,
+                stats: stats.map(|stats| (*stats).into()),
+            },
+            event => return Err(event),
+        };
+        Ok(event)
+    }
+}
+
+/// Statistics about a transfer to another node, see [`ProviderEvent`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProviderTransferStats {
+    /// The number of bytes sent to the client.
+    pub bytes_sent: u64,
+    /// The number of bytes read from the store.
+    pub bytes_read: u64,
+    /// The total duration of the transfer.
+    pub duration: Duration,
+}
+
+impl From<iroh_blobs::provider::TransferStats> for ProviderTransferStats {
+    fn from(stats: iroh_blobs::provider::TransferStats) -> Self {
+        Self {
+            bytes_sent: stats.send.total().size,
+            bytes_read: stats.read.total().size,
+            duration: stats.duration,
+        }
+    }
+}
+
 /// Outcome of a blob add operation.
 #[derive(Debug, Clone)]
 pub struct AddOutcome {
@@ -888,18 +1266,25 @@ impl Reader {
         }
     }
 
+    /// Reads a blob from the default store.
     pub(crate) async fn from_rpc_read(rpc: &RpcClient, hash: Hash) -> anyhow::Result<Self> {
-        Self::from_rpc_read_at(rpc, hash, 0, ReadAtLen::All).await
+        Self::from_rpc_read_at(rpc, None, hash, 0, ReadAtLen::All).await
     }
 
     async fn from_rpc_read_at(
         rpc: &RpcClient,
+        store: Option<String>,
         hash: Hash,
         offset: u64,
         len: ReadAtLen,
     ) -> anyhow::Result<Self> {
         let stream = rpc
-            .server_streaming(ReadAtRequest { hash, offset, len })
+            .server_streaming(ReadAtRequest {
+                hash,
+                offset,
+                len,
+                store,
+            })
             .await?;
         let mut stream = flatten(stream);
 
@@ -977,6 +1362,12 @@ pub struct DownloadOptions {
     pub tag: SetTagOption,
     /// Whether to directly start the download or add it to the download queue.
     pub mode: DownloadMode,
+    /// The named blob store on the source nodes to download from, `None` for their default
+    /// store.
+    ///
+    /// Use [`BlobTicket::store`] to get the store from a ticket. Downloads from a named store are
+    /// always performed as in [`DownloadMode::Direct`].
+    pub remote_store: Option<String>,
 }
 
 /// Set the mode for whether to directly start the download or add it to the download queue.
@@ -1457,6 +1848,48 @@ mod tests {
         assert!(ev2.is_empty());
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_blob_provider_events_rpc() -> Result<()> {
+        let _guard = iroh_test::logging::setup();
+
+        let node1 = crate::node::Node::memory().spawn().await?;
+        let node2 = crate::node::Node::memory().spawn().await?;
+        let mut events = node1.blobs().provider_events().await?;
+
+        let import_outcome = node1.blobs().add_bytes(&b"hello world"[..]).await?;
+        let node1_addr = node1.net().node_addr().await?;
+        node2
+            .blobs()
+            .download(import_outcome.hash, node1_addr)
+            .await?
+            .await?;
+
+        let event = events.next().await.context("expected event")??;
+        let ProviderEvent::ClientConnected { node_id, .. } = event else {
+            panic!("unexpected event {event:?}");
+        };
+        assert_eq!(node_id, Some(node2.node_id()));
+        let event = events.next().await.context("expected event")??;
+        let ProviderEvent::GetRequestReceived { hash, .. } = event else {
+            panic!("unexpected event {event:?}");
+        };
+        assert_eq!(hash, import_outcome.hash);
+        loop {
+            match events.next().await.context("expected event")?? {
+                ProviderEvent::TransferProgress { .. } => {}
+                ProviderEvent::TransferCompleted { stats, .. } => {
+                    assert!(stats.bytes_sent >= 11);
+                    break;
+                }
+                event => panic!("unexpected event {event:?}"),
+            }
+        }
+
+        node1.shutdown().await?;
+        node2.shutdown().await?;
+        Ok(())
+    }
     /// Download a existing blob from oneself
     #[tokio::test]
     async fn test_blob_get_self_existing() -> TestResult<()> {
@@ -1478,6 +1911,7 @@ mod tests {
                     nodes: vec![node_id.into()],
                     tag: SetTagOption::Auto,
                     mode: DownloadMode::Direct,
+                    remote_store: None,
                 },
             )
             .await?
@@ -1496,6 +1930,7 @@ mod tests {
                     nodes: vec![node_id.into()],
                     tag: SetTagOption::Auto,
                     mode: DownloadMode::Queued,
+                    remote_store: None,
                 },
             )
             .await?
@@ -1528,6 +1963,7 @@ mod tests {
                     nodes: vec![node_id.into()],
                     tag: SetTagOption::Auto,
                     mode: DownloadMode::Direct,
+                    remote_store: None,
                 },
             )
             .await?
@@ -1548,6 +1984,7 @@ mod tests {
                     nodes: vec![node_id.into()],
                     tag: SetTagOption::Auto,
                     mode: DownloadMode::Queued,
+                    remote_store: None,
                 },
             )
             .await?
@@ -1607,6 +2044,7 @@ mod tests {
                     nodes: vec![node_id.into()],
                     tag: SetTagOption::Auto,
                     mode: DownloadMode::Direct,
+                    remote_store: None,
                 },
             )
             .await?
@@ -1626,6 +2064,7 @@ mod tests {
                     nodes: vec![node_id.into()],
                     tag: SetTagOption::Auto,
                     mode: DownloadMode::Queued,
+                    remote_store: None,
                 },
             )
             .await?
@@ -1638,6 +2077,116 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_blob_wait_for() -> Result<()> {
+        let _guard = iroh_test::logging::setup();
+
+        let node = crate::node::Node::memory().spawn().await?;
+
+        let data = b"hello world".to_vec();
+        let hash = Hash::new(&data);
+        let mut events = node.blobs().subscribe_hash(hash).await?;
+        let wait = tokio::task::spawn({
+            let blobs = node.blobs().clone();
+            async move { blobs.wait_for(hash).await }
+        });
+
+        node.blobs().add_bytes(data).await?;
+        tokio::time::timeout(std::time::Duration::from_secs(10), wait).await???;
+        let event = events.next().await.context("expected event")??;
+        assert_eq!(event, BlobEvent::Complete { hash });
+
+        // a subscription for a blob that is already complete reports this right away
+        let mut events = node.blobs().subscribe_hash(hash).await?;
+        let event = events.next().await.context("expected event")??;
+        assert_eq!(event, BlobEvent::Complete { hash });
+        node.blobs().wait_for(hash).await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_blob_upload_resume() -> Result<()> {
+        let _guard = iroh_test::logging::setup();
+
+        let node = crate::node::Node::memory().spawn().await?;
+
+        let data = b"hello resumable world".to_vec();
+        let upload = node.blobs().upload().await?;
+        let offset = upload
+            .append(
+                0,
+                futures_lite::stream::once(Ok(Bytes::copy_from_slice(&data[..6]))),
+            )
+            .await?;
+        assert_eq!(offset, 6);
+
+        // continue with a new handle, as if the client had reconnected
+        let upload = node.blobs().resume_upload(upload.id());
+        assert_eq!(upload.offset().await?, 6);
+        // appending at the wrong offset fails and leaves the upload untouched
+        let res = upload
+            .append(
+                0,
+                futures_lite::stream::once(Ok(Bytes::copy_from_slice(&data[..6]))),
+            )
+            .await;
+        assert!(res.is_err());
+        let offset = upload
+            .append_reader(6, std::io::Cursor::new(data[6..].to_vec()))
+            .await?;
+        assert_eq!(offset, data.len() as u64);
+
+        let upload_id = upload.id();
+        let outcome = upload.finish(SetTagOption::Auto).await?.await?;
+        assert_eq!(outcome.hash, Hash::new(&data));
+        assert_eq!(outcome.size, data.len() as u64);
+        let read = node.blobs().read_to_bytes(outcome.hash).await?;
+        assert_eq!(read, data);
+
+        // a finished upload is gone
+        assert!(node
+            .blobs()
+            .resume_upload(upload_id)
+            .offset()
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_blob_upload_expires_after_disconnect() -> Result<()> {
+        let _guard = iroh_test::logging::setup();
+
+        let ttl = Duration::from_millis(500);
+        let node = crate::node::Node::memory().upload_ttl(ttl).spawn().await?;
+
+        // append a first chunk and keep the input open
+        let upload = node.blobs().upload().await?;
+        let (tx, rx) = async_channel::bounded(1);
+        let append = tokio::spawn({
+            let upload = upload.clone();
+            async move { upload.append(0, Box::pin(rx)).await }
+        });
+        tx.send(Ok(Bytes::from_static(b"hello"))).await?;
+        while upload.offset().await? < 5 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // the client goes away in the middle of the append
+        append.abort();
+        assert!(append.await.unwrap_err().is_cancelled());
+        drop(tx);
+
+        // the upload can be resumed for a while
+        assert_eq!(upload.offset().await?, 5);
+
+        // but is removed once it was idle for too long
+        tokio::time::sleep(ttl * 3).await;
+        assert!(upload.offset().await.is_err());
+        Ok(())
+    }
+
     #[tokio::test]
     #[cfg_attr(target_os = "windows", ignore = "flaky")]
     async fn test_blob_delete_mem() -> Result<()> {
@@ -41,6 +41,8 @@ struct BatchInner {
     batch: BatchId,
     /// The rpc client.
     rpc: RpcClient,
+    /// The named blob store of the batch, `None` for the default store.
+    store: Option<String>,
     /// The stream to send drop
     #[debug(skip)]
     updates: Mutex<Buffer<UpdateSink<RpcService, RpcConnection, BatchUpdate>, BatchUpdate>>,
@@ -126,6 +128,7 @@ impl Batch {
     pub(super) fn new(
         batch: BatchId,
         rpc: RpcClient,
+        store: Option<String>,
         updates: UpdateSink<RpcService, RpcConnection, BatchUpdate>,
         buffer_size: usize,
     ) -> Self {
@@ -133,6 +136,7 @@ impl Batch {
         Self(Arc::new(BatchInner {
             batch,
             rpc,
+            store,
             updates: updates.into(),
         }))
     }
@@ -187,6 +191,7 @@ impl Batch {
             .rpc(BatchCreateTempTagRequest {
                 batch: self.0.batch,
                 content,
+                store: self.0.store.clone(),
             })
             .await??;
         // Only after success of the above call, we can create the corresponding local temp tag
@@ -251,6 +256,7 @@ impl Batch {
                 import_mode,
                 format,
                 batch: self.0.batch,
+                store: self.0.store.clone(),
             })
             .await?;
         let mut res_hash = None;
@@ -342,6 +348,7 @@ impl Batch {
             .bidi(BatchAddStreamRequest {
                 batch: self.0.batch,
                 format,
+                store: self.0.store.clone(),
             })
             .await?;
         let mut size = 0u64;
@@ -421,6 +428,7 @@ impl Batch {
                 value: tt.hash_and_format(),
                 batch: Some(self.0.batch),
                 sync: SyncMode::Full,
+                store: self.0.store.clone(),
             })
             .await??;
         Ok(tag)
@@ -435,6 +443,7 @@ impl Batch {
                 value: Some(tt.hash_and_format()),
                 batch: Some(self.0.batch),
                 sync: SyncMode::Full,
+                store: self.0.store.clone(),
             })
             .await??;
         Ok(())
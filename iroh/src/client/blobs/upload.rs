@@ -0,0 +1,140 @@
+use std::io;
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures_lite::StreamExt;
+use futures_util::{SinkExt, Stream};
+use iroh_blobs::util::SetTagOption;
+use tokio::io::AsyncRead;
+use tokio_util::{io::ReaderStream, task::AbortOnDropHandle};
+use tracing::warn;
+
+use crate::{
+    client::RpcClient,
+    rpc_protocol::blobs::{
+        UploadAbortRequest, UploadAppendRequest, UploadAppendUpdate, UploadFinishRequest,
+        UploadStatusRequest,
+    },
+};
+
+pub use crate::rpc_protocol::blobs::UploadId;
+
+use super::AddProgress;
+
+/// A resumable upload of a blob.
+///
+/// The node writes the data of an upload to disk as it arrives and keeps it when the
+/// connection breaks. To continue an interrupted upload, query the [`Self::offset`] the node
+/// has reached and [`Self::append`] the remaining data from there. The upload can also be
+/// continued from a new client, using [`Client::resume_upload`](super::Client::resume_upload)
+/// with the [`Self::id`] of the upload.
+///
+/// Uploads are kept by the node until they are finished or aborted, the node restarts, or they
+/// have been idle for longer than the node's
+/// [`upload_ttl`](crate::node::Builder::upload_ttl).
+#[derive(Debug, Clone)]
+pub struct Upload {
+    id: UploadId,
+    rpc: RpcClient,
+    store: Option<String>,
+}
+
+impl Upload {
+    pub(super) fn new(id: UploadId, rpc: RpcClient, store: Option<String>) -> Self {
+        Self { id, rpc, store }
+    }
+
+    /// The id of the upload.
+    pub fn id(&self) -> UploadId {
+        self.id
+    }
+
+    /// The number of bytes the node has written for this upload so far.
+    pub async fn offset(&self) -> Result<u64> {
+        let res = self
+            .rpc
+            .rpc(UploadStatusRequest {
+                id: self.id,
+                store: self.store.clone(),
+            })
+            .await??;
+        Ok(res.offset)
+    }
+
+    /// Append data from a reader to the upload, see [`Self::append`].
+    pub async fn append_reader(
+        &self,
+        offset: u64,
+        reader: impl AsyncRead + Unpin + Send + 'static,
+    ) -> Result<u64> {
+        const CAP: usize = 1024 * 64; // send 64KB per request by default
+        let input = ReaderStream::with_capacity(reader, CAP);
+        self.append(offset, input).await
+    }
+
+    /// Append a stream of bytes to the upload.
+    ///
+    /// `offset` must be the current [`Self::offset`] of the upload. Returns the offset of the
+    /// upload after all data has been written. If this fails, e.g. because the connection
+    /// broke, query the offset and append the remaining data from there.
+    pub async fn append(
+        &self,
+        offset: u64,
+        input: impl Stream<Item = io::Result<Bytes>> + Send + Unpin + 'static,
+    ) -> Result<u64> {
+        let (mut sink, mut progress) = self
+            .rpc
+            .bidi(UploadAppendRequest {
+                id: self.id,
+                offset,
+                store: self.store.clone(),
+            })
+            .await?;
+        // stop sending if the node reports an error or this future is dropped
+        let send = AbortOnDropHandle::new(tokio::spawn(async move {
+            let mut input = input.map(|chunk| chunk.map(UploadAppendUpdate));
+            while let Some(chunk) = input.next().await {
+                let chunk = chunk?;
+                if let Err(err) = sink.send(chunk).await {
+                    warn!("Failed to send input stream to remote: {err:?}");
+                    break;
+                }
+            }
+            sink.close().await.ok();
+            io::Result::Ok(())
+        }));
+        let mut offset = offset;
+        while let Some(res) = progress.next().await {
+            offset = res??.offset;
+        }
+        send.await??;
+        Ok(offset)
+    }
+
+    /// Import the data of the upload into the store.
+    ///
+    /// The returned [`AddProgress`] reports the progress of the import, like for
+    /// [`Client::add_stream`](super::Client::add_stream).
+    pub async fn finish(self, tag: SetTagOption) -> Result<AddProgress> {
+        let stream = self
+            .rpc
+            .server_streaming(UploadFinishRequest {
+                id: self.id,
+                tag,
+                store: self.store,
+            })
+            .await?;
+        Ok(AddProgress::new(stream))
+    }
+
+    /// Abort the upload and remove its data from the node.
+    pub async fn abort(self) -> Result<()> {
+        self.rpc
+            .rpc(UploadAbortRequest {
+                id: self.id,
+                store: self.store,
+            })
+            .await??;
+        Ok(())
+    }
+}
@@ -5,9 +5,10 @@
 //! You obtain a [`Client`] via [`Iroh::docs()`](crate::client::Iroh::docs).
 
 use std::{
+    collections::BTreeSet,
     path::{Path, PathBuf},
     pin::Pin,
-    sync::Arc,
+    sync::{Arc, Mutex},
     task::{Context, Poll},
 };
 
@@ -19,30 +20,52 @@ use iroh_base::{key::PublicKey, node_addr::AddrInfoOptions, rpc::RpcError};
 use iroh_blobs::{export::ExportProgress, store::ExportMode, Hash};
 use iroh_docs::{
     actor::OpenState,
-    store::{DownloadPolicy, Query},
-    AuthorId, Capability, CapabilityKind, ContentStatus, DocTicket, NamespaceId, PeerIdBytes,
-    RecordIdentifier,
+    store::{
+        ClockMode, Cursor, DownloadPolicy, HistoryPolicy, IndexSpec, Query, SyncPolicy,
+        TombstonePolicy,
+    },
+    AccessPolicy, AreaOfInterest, AuthorId, Capability, CapabilityKind, ContentStatus,
+    DelegationScope, DocEncryptionKey, DocTicket, InviteTicket, NamespaceId, PeerIdBytes,
+    ReadToken, RecordIdentifier, Redemption, WriteDelegation,
 };
-use iroh_net::NodeAddr;
+use iroh_net::{NodeAddr, NodeId};
 use portable_atomic::{AtomicBool, Ordering};
 use quic_rpc::message::RpcMsg;
 use ref_cast::RefCast;
 use serde::{Deserialize, Serialize};
 
 use crate::rpc_protocol::docs::{
-    CloseRequest, CreateRequest, DelRequest, DelResponse, DocListRequest, DocSubscribeRequest,
-    DropRequest, ExportFileRequest, GetDownloadPolicyRequest, GetExactRequest, GetManyRequest,
-    GetSyncPeersRequest, ImportFileRequest, ImportRequest, LeaveRequest, OpenRequest,
-    SetDownloadPolicyRequest, SetHashRequest, SetRequest, ShareRequest, StartSyncRequest,
-    StatusRequest,
+    AddDelegationRequest, BatchRequest, BatchWrite, CloseRequest, CompactTombstonesRequest,
+    CreateIndexRequest, CreateInviteRequest, CreateReadTokenRequest, CreateRequest, DelRequest,
+    DelResponse, DelegateRequest, DocListRequest, DocSubscribeRequest, DropRequest,
+    ExportBundleRequest, ExportFileRequest, FollowMigrationRequest, GetAccessPolicyRequest,
+    GetClockModeRequest, GetDownloadPolicyRequest, GetEncryptionKeyRequest, GetExactRequest,
+    GetHistoryPolicyRequest, GetInlineContentRequest, GetInlineLimitRequest, GetManyContentRequest,
+    GetManyRequest, GetSyncInterestRequest, GetSyncPeersRequest, GetSyncPolicyRequest,
+    GetTombstonePolicyRequest, GetTrustedMarkerAuthorsRequest, ImportBundleRequest,
+    ImportFileRequest, ImportInviteRequest, ImportRequest, LeaveRequest, ListDelegationsRequest,
+    ListIndexesRequest, ListRedemptionsRequest, OpenRequest, RemoveIndexRequest, RotateRequest,
+    SetAccessPolicyRequest, SetClockModeRequest, SetDownloadPolicyRequest, SetEncryptionKeyRequest,
+    SetHashRequest, SetHistoryPolicyRequest, SetInlineLimitRequest, SetReadTokenRequest,
+    SetRequest, SetSyncInterestRequest, SetSyncPolicyRequest, SetTombstonePolicyRequest,
+    SetTrustedMarkerAuthorsRequest, ShareRequest, StartSyncRequest, StatusRequest,
 };
 use crate::rpc_protocol::RpcService;
 
 #[doc(inline)]
-pub use iroh_docs::engine::{Origin, SyncEvent, SyncReason};
+pub use iroh_docs::engine::{Origin, SessionEvent, SubscribeFilter, SyncEvent, SyncReason};
 
 use super::{blobs, flatten, RpcClient};
 
+mod crdt;
+#[cfg(feature = "folder-sync")]
+mod folder;
+pub use self::crdt::CrdtValue;
+#[cfg(feature = "folder-sync")]
+pub use folder::{FolderSync, FolderSyncEvent, FolderSyncOpts};
+#[doc(inline)]
+pub use iroh_docs::crdt::{Counter, Crdt, CrdtWrite, Map, OrSet, Text};
+
 /// Iroh docs client.
 #[derive(Debug, Clone, RefCast)]
 #[repr(transparent)]
@@ -85,6 +108,40 @@ impl Client {
         Ok(doc)
     }
 
+    /// Imports a document from an invite ticket and joins all peers in the ticket.
+    ///
+    /// The document is imported with read access. The invite is redeemed with the inviting node
+    /// on the first sync, which grants write access for write invites. Use [`Self::list`] to check
+    /// the access of this node to the document.
+    pub async fn import_invite(&self, ticket: InviteTicket) -> Result<Doc> {
+        let res = self.rpc.rpc(ImportInviteRequest { ticket }).await??;
+        let doc = Doc::new(self.rpc.clone(), res.doc_id);
+        Ok(doc)
+    }
+
+    /// Imports the entries of a bundle file written by [`Doc::export_bundle`].
+    ///
+    /// The path must be absolute and valid on the node. The namespace and author signatures of
+    /// all entries are verified before any entry is inserted. The document is imported with read
+    /// access if it does not exist on this node. Entries that are older than the entries already
+    /// present are skipped.
+    ///
+    /// Returns the [`Doc`] and the number of inserted entries and imported content blobs.
+    pub async fn import_bundle(&self, path: impl AsRef<Path>) -> Result<(Doc, BundleOutcome)> {
+        let res = self
+            .rpc
+            .rpc(ImportBundleRequest {
+                path: path.as_ref().into(),
+            })
+            .await??;
+        let doc = Doc::new(self.rpc.clone(), res.doc_id);
+        let outcome = BundleOutcome {
+            entries: res.entries,
+            blobs: res.blobs,
+        };
+        Ok((doc, outcome))
+    }
+
     /// Imports a document from a ticket, creates a subscription stream and joins all peers in the ticket.
     ///
     /// Returns the [`Doc`] and a [`Stream`] of [`LiveEvent`]s.
@@ -137,6 +194,11 @@ struct DocInner {
     rpc: RpcClient,
     closed: AtomicBool,
     rt: tokio::runtime::Handle,
+    /// The encryption key of the document, once it is known to be encrypted.
+    ///
+    /// That a document is not encrypted is never cached, because other clients may enable
+    /// encryption at any time.
+    encryption: Mutex<Option<DocEncryptionKey>>,
 }
 
 impl Drop for DocInner {
@@ -158,6 +220,7 @@ impl Doc {
             id,
             closed: AtomicBool::new(false),
             rt: tokio::runtime::Handle::current(),
+            encryption: Mutex::new(None),
         }))
     }
 
@@ -191,6 +254,9 @@ impl Doc {
     }
 
     /// Sets the content of a key to a byte array.
+    ///
+    /// If the document is [encrypted](Self::enable_encryption), the key and value are encrypted
+    /// before they are sent to the node.
     pub async fn set_bytes(
         &self,
         author_id: AuthorId,
@@ -198,18 +264,28 @@ impl Doc {
         value: impl Into<Bytes>,
     ) -> Result<Hash> {
         self.ensure_open()?;
+        let (key, value): (Bytes, Bytes) = (key.into(), value.into());
+        let (key, value) = match self.encryption_key().await? {
+            Some(encryption) => (
+                encryption.encrypt_key(&key),
+                encryption.encrypt_content(&value),
+            ),
+            None => (key, value),
+        };
         let res = self
             .rpc(SetRequest {
                 doc_id: self.id(),
                 author_id,
-                key: key.into(),
-                value: value.into(),
+                key,
+                value,
             })
             .await??;
         Ok(res.entry.content_hash())
     }
 
     /// Sets an entries on the doc via its key, hash, and size.
+    ///
+    /// Not supported for encrypted documents.
     pub async fn set_hash(
         &self,
         author_id: AuthorId,
@@ -218,6 +294,7 @@ impl Doc {
         size: u64,
     ) -> Result<()> {
         self.ensure_open()?;
+        self.ensure_unencrypted().await?;
         self.rpc(SetHashRequest {
             doc_id: self.id(),
             author_id,
@@ -230,6 +307,8 @@ impl Doc {
     }
 
     /// Adds an entry from an absolute file path
+    ///
+    /// Not supported for encrypted documents.
     pub async fn import_file(
         &self,
         author: AuthorId,
@@ -238,6 +317,7 @@ impl Doc {
         in_place: bool,
     ) -> Result<ImportFileProgress> {
         self.ensure_open()?;
+        self.ensure_unencrypted().await?;
         let stream = self
             .0
             .rpc
@@ -253,6 +333,8 @@ impl Doc {
     }
 
     /// Exports an entry as a file to a given absolute path.
+    ///
+    /// Not supported for encrypted documents.
     pub async fn export_file(
         &self,
         entry: Entry,
@@ -260,6 +342,7 @@ impl Doc {
         mode: ExportMode,
     ) -> Result<ExportFileProgress> {
         self.ensure_open()?;
+        self.ensure_unencrypted().await?;
         let stream = self
             .0
             .rpc
@@ -272,25 +355,99 @@ impl Doc {
         Ok(ExportFileProgress::new(stream))
     }
 
+    /// Exports the entries matching `query` as a bundle file to a given absolute path.
+    ///
+    /// The entries keep their signatures, so the bundle can be imported on another node with
+    /// [`Client::import_bundle`] without a connection between the nodes. If `include_content` is
+    /// set, the content of the entries that is available on this node is added to the bundle.
+    ///
+    /// The entries of encrypted documents are exported as they are stored, so the importing node
+    /// needs the [`DocEncryptionKey`] to read them.
+    pub async fn export_bundle(
+        &self,
+        query: impl Into<Query>,
+        path: impl AsRef<Path>,
+        include_content: bool,
+    ) -> Result<BundleOutcome> {
+        self.ensure_open()?;
+        let res = self
+            .rpc(ExportBundleRequest {
+                doc_id: self.id(),
+                query: query.into(),
+                include_content,
+                path: path.as_ref().into(),
+            })
+            .await??;
+        Ok(BundleOutcome {
+            entries: res.entries,
+            blobs: res.blobs,
+        })
+    }
+
     /// Deletes entries that match the given `author` and key `prefix`.
     ///
     /// This inserts an empty entry with the key set to `prefix`, effectively clearing all other
     /// entries whose key starts with or is equal to the given `prefix`.
     ///
+    /// In encrypted documents, only the entry with a key equal to `prefix` is deleted.
+    ///
     /// Returns the number of entries deleted.
     pub async fn del(&self, author_id: AuthorId, prefix: impl Into<Bytes>) -> Result<usize> {
         self.ensure_open()?;
+        let prefix: Bytes = prefix.into();
+        let prefix = match self.encryption_key().await? {
+            Some(encryption) => encryption.encrypt_key(&prefix),
+            None => prefix,
+        };
         let res = self
             .rpc(DelRequest {
                 doc_id: self.id(),
                 author_id,
-                prefix: prefix.into(),
+                prefix,
             })
             .await??;
         let DelResponse { removed } = res;
         Ok(removed)
     }
 
+    /// Applies all writes of a [`Batch`] by `author_id` in a single transaction.
+    ///
+    /// Either all writes are applied or none of them. Subscribers receive the inserted entries
+    /// together in a single [`LiveEvent::InsertLocalBatch`], and peers receive them together
+    /// as well.
+    ///
+    /// Returns the number of entries deleted by the batch.
+    pub async fn apply_batch(&self, author_id: AuthorId, batch: Batch) -> Result<usize> {
+        self.ensure_open()?;
+        let writes = match self.encryption_key().await? {
+            None => batch.writes,
+            Some(encryption) => batch
+                .writes
+                .into_iter()
+                .map(|write| match write {
+                    BatchWrite::SetBytes { key, value } => Ok(BatchWrite::SetBytes {
+                        key: encryption.encrypt_key(&key),
+                        value: encryption.encrypt_content(&value),
+                    }),
+                    BatchWrite::SetHash { .. } => {
+                        Err(anyhow!("set_hash is not supported for encrypted documents"))
+                    }
+                    BatchWrite::Del { prefix } => Ok(BatchWrite::Del {
+                        prefix: encryption.encrypt_key(&prefix),
+                    }),
+                })
+                .collect::<Result<_>>()?,
+        };
+        let res = self
+            .rpc(BatchRequest {
+                doc_id: self.id(),
+                author_id,
+                writes,
+            })
+            .await??;
+        Ok(res.removed)
+    }
+
     /// Returns an entry for a key and author.
     ///
     /// Optionally also returns the entry unless it is empty (i.e. a deletion marker).
@@ -301,32 +458,123 @@ impl Doc {
         include_empty: bool,
     ) -> Result<Option<Entry>> {
         self.ensure_open()?;
+        let encryption = self.encryption_key().await?;
+        let key = match &encryption {
+            Some(encryption) => encryption.encrypt_key(key.as_ref()),
+            None => key.as_ref().to_vec().into(),
+        };
         let res = self
             .rpc(GetExactRequest {
                 author,
-                key: key.as_ref().to_vec().into(),
+                key,
                 doc_id: self.id(),
                 include_empty,
             })
             .await??;
-        Ok(res.entry.map(|entry| entry.into()))
+        res.entry
+            .map(|entry| decrypt_entry(encryption.as_ref(), entry.into()))
+            .transpose()
     }
 
     /// Returns all entries matching the query.
+    ///
+    /// Queries with a key prefix or key range filter fail for encrypted documents.
     pub async fn get_many(
         &self,
         query: impl Into<Query>,
     ) -> Result<impl Stream<Item = Result<Entry>>> {
         self.ensure_open()?;
+        let encryption = self.encryption_key().await?;
+        let query = match &encryption {
+            Some(encryption) => encryption.encrypt_query(query.into())?,
+            None => query.into(),
+        };
         let stream = self
             .0
             .rpc
             .server_streaming(GetManyRequest {
                 doc_id: self.id(),
-                query: query.into(),
+                query,
+            })
+            .await?;
+        Ok(flatten(stream).map(move |res| {
+            res.and_then(|res| decrypt_entry(encryption.as_ref(), res.entry.into()))
+        }))
+    }
+
+    /// Returns all entries matching the query, together with their content if it is available
+    /// on this node and not longer than `max_content_len`.
+    ///
+    /// Reads the content of many small entries with a single request.
+    async fn get_many_content(
+        &self,
+        query: impl Into<Query>,
+        max_content_len: u64,
+    ) -> Result<impl Stream<Item = Result<(Entry, Option<Bytes>)>>> {
+        self.ensure_open()?;
+        let encryption = self.encryption_key().await?;
+        let query = match &encryption {
+            Some(encryption) => encryption.encrypt_query(query.into())?,
+            None => query.into(),
+        };
+        let stream = self
+            .0
+            .rpc
+            .server_streaming(GetManyContentRequest {
+                doc_id: self.id(),
+                query,
+                max_content_len,
+            })
+            .await?;
+        Ok(flatten(stream).map(move |res| {
+            let res = res?;
+            let entry = decrypt_entry(encryption.as_ref(), res.entry.into())?;
+            let content = match (res.content, &encryption) {
+                (Some(content), Some(encryption)) if !iroh_docs::is_moved_to_key(entry.key()) => {
+                    Some(encryption.decrypt_content(&content)?)
+                }
+                (content, _) => content,
+            };
+            Ok((entry, content))
+        }))
+    }
+
+    /// Returns a page of the entries matching the query.
+    ///
+    /// The page contains up to [`Query::limit`] entries. If the page is full, it also contains a
+    /// cursor to fetch the next page by continuing the query with [`QueryBuilder::after`].
+    ///
+    /// [`QueryBuilder::after`]: iroh_docs::store::QueryBuilder::after
+    pub async fn get_page(&self, query: impl Into<Query>) -> Result<Page> {
+        self.ensure_open()?;
+        let encryption = self.encryption_key().await?;
+        let query = match &encryption {
+            Some(encryption) => encryption.encrypt_query(query.into())?,
+            None => query.into(),
+        };
+        let limit = query.limit();
+        let stream = self
+            .0
+            .rpc
+            .server_streaming(GetManyRequest {
+                doc_id: self.id(),
+                query,
             })
             .await?;
-        Ok(flatten(stream).map(|res| res.map(|res| res.entry.into())))
+        let mut stream = flatten(stream);
+        let mut entries = Vec::new();
+        let mut last = None;
+        while let Some(res) = stream.next().await {
+            let entry: Entry = res?.entry.into();
+            // the cursor refers to the entry as stored, with the key still encrypted
+            last = Some(Cursor::from_entry(&entry.0));
+            entries.push(decrypt_entry(encryption.as_ref(), entry)?);
+        }
+        let cursor = match limit {
+            Some(limit) if entries.len() as u64 >= limit => last,
+            _ => None,
+        };
+        Ok(Page { entries, cursor })
     }
 
     /// Returns a single entry.
@@ -351,6 +599,47 @@ impl Doc {
         Ok(res.0)
     }
 
+    /// Invites peers to this document over a ticket.
+    ///
+    /// Unlike [`Self::share`], the ticket does not contain the document capability. It contains an
+    /// invite that the invited node redeems with this node when it first syncs the document.
+    /// `expires` is an optional expiry time in microseconds since the Unix epoch. A `single_use`
+    /// invite can only be redeemed by one node, so it cannot be forwarded once redeemed. If
+    /// `invitee` is set, only the node with this id can redeem the invite. On redemption, this
+    /// node adds the invited node to a restricted [`AccessPolicy`], and hands over the document
+    /// secret for [`ShareMode::Write`] invites. Fails if this node does not have write access to
+    /// the document.
+    pub async fn invite(
+        &self,
+        mode: ShareMode,
+        expires: Option<u64>,
+        single_use: bool,
+        invitee: Option<NodeId>,
+        addr_options: AddrInfoOptions,
+    ) -> Result<InviteTicket> {
+        self.ensure_open()?;
+        let res = self
+            .rpc(CreateInviteRequest {
+                doc_id: self.id(),
+                mode,
+                expires,
+                single_use,
+                invitee,
+                addr_options,
+            })
+            .await??;
+        Ok(res.0)
+    }
+
+    /// Returns the logged redemptions of the invites to this document.
+    pub async fn redemptions(&self) -> Result<Vec<Redemption>> {
+        self.ensure_open()?;
+        let res = self
+            .rpc(ListRedemptionsRequest { doc_id: self.id() })
+            .await??;
+        Ok(res.redemptions)
+    }
+
     /// Starts to sync this document with a list of peers.
     pub async fn start_sync(&self, peers: Vec<NodeAddr>) -> Result<()> {
         self.ensure_open()?;
@@ -372,14 +661,39 @@ impl Doc {
 
     /// Subscribes to events for this document.
     pub async fn subscribe(&self) -> anyhow::Result<impl Stream<Item = anyhow::Result<LiveEvent>>> {
+        self.subscribe_with_filter(SubscribeFilter::default(), false)
+            .await
+    }
+
+    /// Subscribes to events for this document, with entry events filtered by `filter`.
+    ///
+    /// The filter is applied by the node, so events for entries that do not match are never
+    /// sent to this client. Events that do not carry entries are not filtered. If `replay` is
+    /// true, the existing entries that match the filter are emitted as [`LiveEvent::Replayed`]
+    /// before any other events.
+    ///
+    /// Prefix filters are not supported on encrypted documents.
+    pub async fn subscribe_with_filter(
+        &self,
+        mut filter: SubscribeFilter,
+        replay: bool,
+    ) -> anyhow::Result<impl Stream<Item = anyhow::Result<LiveEvent>>> {
         self.ensure_open()?;
+        let encryption = self.encryption_key().await?;
+        if let Some(encryption) = &encryption {
+            filter.key = encryption.encrypt_key_filter(filter.key)?;
+        }
         let stream = self
             .0
             .rpc
-            .try_server_streaming(DocSubscribeRequest { doc_id: self.id() })
+            .try_server_streaming(DocSubscribeRequest {
+                doc_id: self.id(),
+                filter,
+                replay,
+            })
             .await?;
-        Ok(stream.map(|res| match res {
-            Ok(res) => Ok(res.event.into()),
+        Ok(stream.map(move |res| match res {
+            Ok(res) => decrypt_event(encryption.as_ref(), res.event.into()),
             Err(err) => Err(err.into()),
         }))
     }
@@ -409,6 +723,193 @@ impl Doc {
         Ok(res.policy)
     }
 
+    /// Sets the history policy for this document.
+    ///
+    /// With history enabled, superseded entries are kept and can be queried with
+    /// [`Query`]'s `as_of` and `include_history` options.
+    pub async fn set_history_policy(&self, policy: HistoryPolicy) -> Result<()> {
+        self.rpc(SetHistoryPolicyRequest {
+            doc_id: self.id(),
+            policy,
+        })
+        .await??;
+        Ok(())
+    }
+
+    /// Returns the history policy for this document
+    pub async fn get_history_policy(&self) -> Result<HistoryPolicy> {
+        let res = self
+            .rpc(GetHistoryPolicyRequest { doc_id: self.id() })
+            .await??;
+        Ok(res.policy)
+    }
+
+    /// Sets the tombstone policy for this document.
+    ///
+    /// With [`TombstonePolicy::Expire`], [`Self::compact_tombstones`] removes the tombstones of
+    /// deleted entries once they are older than the horizon.
+    pub async fn set_tombstone_policy(&self, policy: TombstonePolicy) -> Result<()> {
+        self.rpc(SetTombstonePolicyRequest {
+            doc_id: self.id(),
+            policy,
+        })
+        .await??;
+        Ok(())
+    }
+
+    /// Returns the tombstone policy for this document
+    pub async fn get_tombstone_policy(&self) -> Result<TombstonePolicy> {
+        let res = self
+            .rpc(GetTombstonePolicyRequest { doc_id: self.id() })
+            .await??;
+        Ok(res.policy)
+    }
+
+    /// Sets the sync policy for this document.
+    ///
+    /// The policy controls the periodic resync of this document with its known peers, the number
+    /// of concurrent syncs, the backoff for peers whose sync failed, and the preferred peers. It
+    /// applies to the live sync right away.
+    pub async fn set_sync_policy(&self, policy: SyncPolicy) -> Result<()> {
+        self.rpc(SetSyncPolicyRequest {
+            doc_id: self.id(),
+            policy,
+        })
+        .await??;
+        Ok(())
+    }
+
+    /// Returns the sync policy for this document.
+    pub async fn get_sync_policy(&self) -> Result<SyncPolicy> {
+        let res = self
+            .rpc(GetSyncPolicyRequest { doc_id: self.id() })
+            .await??;
+        Ok(res.policy)
+    }
+
+    /// Sets the maximum length of values of this document that are sent inline.
+    ///
+    /// Values up to this length whose content is available on this node are sent inline with
+    /// their entries when syncing and in live updates, so that peers do not need to download them
+    /// separately. Peers store the content in their docs store only for entries that pass
+    /// validation, before they emit [`LiveEvent::InsertRemote`]. A limit of 0, the default,
+    /// disables inline values. The limit must not exceed
+    /// [`MAX_INLINE_LIMIT`](iroh_docs::store::MAX_INLINE_LIMIT).
+    pub async fn set_inline_limit(&self, limit: u64) -> Result<()> {
+        self.rpc(SetInlineLimitRequest {
+            doc_id: self.id(),
+            limit,
+        })
+        .await??;
+        Ok(())
+    }
+
+    /// Returns the maximum length of values of this document that are sent inline.
+    pub async fn get_inline_limit(&self) -> Result<u64> {
+        let res = self
+            .rpc(GetInlineLimitRequest { doc_id: self.id() })
+            .await??;
+        Ok(res.limit)
+    }
+
+    /// Creates a secondary index of this document, replacing an existing index with the same
+    /// name.
+    ///
+    /// The index is queried with [`Query::index`] and [`Self::get_many`]. Indexes are kept on
+    /// this node only, and entries are indexed once their content is available on this node, see
+    /// [`IndexSpec`]. Returns once the entries whose content is available are indexed.
+    ///
+    /// Not supported for encrypted documents.
+    pub async fn create_index(&self, name: impl Into<String>, spec: IndexSpec) -> Result<()> {
+        self.ensure_open()?;
+        self.ensure_unencrypted().await?;
+        self.rpc(CreateIndexRequest {
+            doc_id: self.id(),
+            name: name.into(),
+            spec,
+        })
+        .await??;
+        Ok(())
+    }
+
+    /// Removes a secondary index of this document.
+    ///
+    /// Returns `false` if the index did not exist.
+    pub async fn remove_index(&self, name: impl Into<String>) -> Result<bool> {
+        let res = self
+            .rpc(RemoveIndexRequest {
+                doc_id: self.id(),
+                name: name.into(),
+            })
+            .await??;
+        Ok(res.removed)
+    }
+
+    /// Returns the secondary indexes of this document by name.
+    pub async fn list_indexes(&self) -> Result<Vec<(String, IndexSpec)>> {
+        let res = self.rpc(ListIndexesRequest { doc_id: self.id() }).await??;
+        Ok(res.indexes)
+    }
+
+    /// Removes the tombstones of this document that expired according to its
+    /// [`TombstonePolicy`].
+    ///
+    /// Peers that have not synced this document for longer than the horizon drop their entries
+    /// older than the horizon on their next sync with this node, and fetch them again. Peers
+    /// whose [`TombstonePolicy`] does not allow this no longer sync this document with this node.
+    ///
+    /// Returns the number of removed tombstones.
+    pub async fn compact_tombstones(&self) -> Result<usize> {
+        self.ensure_open()?;
+        let res = self
+            .rpc(CompactTombstonesRequest { doc_id: self.id() })
+            .await??;
+        Ok(res.removed)
+    }
+
+    /// Sets the clock mode for new entries of this document.
+    ///
+    /// With [`ClockMode::Hybrid`], writes made after an entry was inserted or received always win
+    /// against it, even if the wall clock of this node is behind. The clock mode is local to this
+    /// node, peers of a document may use different modes.
+    pub async fn set_clock_mode(&self, mode: ClockMode) -> Result<()> {
+        self.rpc(SetClockModeRequest {
+            doc_id: self.id(),
+            mode,
+        })
+        .await??;
+        Ok(())
+    }
+
+    /// Returns the clock mode for this document
+    pub async fn get_clock_mode(&self) -> Result<ClockMode> {
+        let res = self
+            .rpc(GetClockModeRequest { doc_id: self.id() })
+            .await??;
+        Ok(res.mode)
+    }
+
+    /// Sets the area of interest for syncing this document.
+    ///
+    /// Sync sessions only exchange entries within the intersection of the areas of both peers.
+    /// Entries that are already stored locally are not removed.
+    pub async fn set_sync_interest(&self, area: AreaOfInterest) -> Result<()> {
+        self.rpc(SetSyncInterestRequest {
+            doc_id: self.id(),
+            area,
+        })
+        .await??;
+        Ok(())
+    }
+
+    /// Returns the area of interest for syncing this document
+    pub async fn get_sync_interest(&self) -> Result<AreaOfInterest> {
+        let res = self
+            .rpc(GetSyncInterestRequest { doc_id: self.id() })
+            .await??;
+        Ok(res.area)
+    }
+
     /// Returns sync peers for this document
     pub async fn get_sync_peers(&self) -> Result<Option<Vec<PeerIdBytes>>> {
         let res = self
@@ -416,6 +917,392 @@ impl Doc {
             .await??;
         Ok(res.peers)
     }
+
+    /// Grants write access to a part of this document, signed with the document's secret.
+    ///
+    /// Give the returned [`WriteDelegation`] to the author in `scope`, who can then write to
+    /// the document after adding it with [`Self::add_delegation`], even with a read-only
+    /// capability. Fails if this node does not have write access to the document.
+    pub async fn delegate(&self, scope: DelegationScope) -> Result<WriteDelegation> {
+        self.ensure_open()?;
+        let res = self
+            .rpc(DelegateRequest {
+                doc_id: self.id(),
+                scope,
+                issuer: None,
+            })
+            .await??;
+        Ok(res.delegation)
+    }
+
+    /// Passes on write access that was delegated to `issuer`.
+    ///
+    /// This requires a delegable [`WriteDelegation`] for `issuer` whose scope contains `scope`.
+    pub async fn delegate_as(
+        &self,
+        issuer: AuthorId,
+        scope: DelegationScope,
+    ) -> Result<WriteDelegation> {
+        self.ensure_open()?;
+        let res = self
+            .rpc(DelegateRequest {
+                doc_id: self.id(),
+                scope,
+                issuer: Some(issuer),
+            })
+            .await??;
+        Ok(res.delegation)
+    }
+
+    /// Adds a [`WriteDelegation`] to this document.
+    ///
+    /// Entries written under the delegation are accepted from then on, and the delegation is
+    /// shared with peers on sync. Returns `true` if the delegation was not known before.
+    pub async fn add_delegation(&self, delegation: WriteDelegation) -> Result<bool> {
+        self.ensure_open()?;
+        let res = self
+            .rpc(AddDelegationRequest {
+                doc_id: self.id(),
+                delegation,
+            })
+            .await??;
+        Ok(res.inserted)
+    }
+
+    /// Returns the [`WriteDelegation`]s known for this document.
+    pub async fn delegations(&self) -> Result<Vec<WriteDelegation>> {
+        self.ensure_open()?;
+        let res = self
+            .rpc(ListDelegationsRequest { doc_id: self.id() })
+            .await??;
+        Ok(res.delegations)
+    }
+
+    /// Sets the access policy for this document.
+    ///
+    /// With a restricted [`AccessPolicy`], this node only syncs the document with the nodes on
+    /// the allowlist of the policy and with nodes that present a [`ReadToken`], and no longer
+    /// broadcasts new entries or content hashes to the document's gossip swarm. Gossip
+    /// neighbors that are not on the allowlist are ignored, but they stay connected to the
+    /// swarm and learn the latest timestamps of the document's authors.
+    pub async fn set_access_policy(&self, policy: AccessPolicy) -> Result<()> {
+        self.rpc(SetAccessPolicyRequest {
+            doc_id: self.id(),
+            policy,
+        })
+        .await??;
+        Ok(())
+    }
+
+    /// Returns the access policy for this document.
+    pub async fn get_access_policy(&self) -> Result<AccessPolicy> {
+        let res = self
+            .rpc(GetAccessPolicyRequest { doc_id: self.id() })
+            .await??;
+        Ok(res.policy)
+    }
+
+    /// Creates a [`ReadToken`] that allows `node` to sync this document with peers that
+    /// restrict access to it.
+    ///
+    /// `expires` is an optional expiry time in microseconds since the Unix epoch. Fails if this
+    /// node does not have write access to the document.
+    pub async fn create_read_token(
+        &self,
+        node: PublicKey,
+        expires: Option<u64>,
+    ) -> Result<ReadToken> {
+        self.ensure_open()?;
+        let res = self
+            .rpc(CreateReadTokenRequest {
+                doc_id: self.id(),
+                node: *node.as_bytes(),
+                expires,
+            })
+            .await??;
+        Ok(res.token)
+    }
+
+    /// Sets the [`ReadToken`] this node presents to peers when syncing this document.
+    pub async fn set_read_token(&self, token: ReadToken) -> Result<()> {
+        self.ensure_open()?;
+        self.rpc(SetReadTokenRequest {
+            doc_id: self.id(),
+            token,
+        })
+        .await??;
+        Ok(())
+    }
+
+    /// Encrypts the entries of this document from now on.
+    ///
+    /// The keys and content of entries written through this handle are encrypted with a
+    /// [`DocEncryptionKey`] derived from the document secret, so peers can only read them after
+    /// adding the returned key with [`Self::set_encryption_key`]. Entries written before are not
+    /// encrypted and can no longer be read through this handle. Fails if this node does not have
+    /// write access to the document.
+    pub async fn enable_encryption(&self) -> Result<DocEncryptionKey> {
+        self.ensure_open()?;
+        let res = self
+            .rpc(SetEncryptionKeyRequest {
+                doc_id: self.id(),
+                key: None,
+            })
+            .await??;
+        *self.0.encryption.lock().unwrap() = Some(res.key.clone());
+        Ok(res.key)
+    }
+
+    /// Sets the [`DocEncryptionKey`] to read and write the entries of this document.
+    ///
+    /// Fails if a different key is already set.
+    pub async fn set_encryption_key(&self, key: DocEncryptionKey) -> Result<()> {
+        self.ensure_open()?;
+        self.rpc(SetEncryptionKeyRequest {
+            doc_id: self.id(),
+            key: Some(key.clone()),
+        })
+        .await??;
+        *self.0.encryption.lock().unwrap() = Some(key);
+        Ok(())
+    }
+
+    /// Returns the [`DocEncryptionKey`] of this document, if it is encrypted.
+    pub async fn encryption_key(&self) -> Result<Option<DocEncryptionKey>> {
+        if let Some(key) = self.0.encryption.lock().unwrap().as_ref() {
+            return Ok(Some(key.clone()));
+        }
+        let res = self
+            .rpc(GetEncryptionKeyRequest { doc_id: self.id() })
+            .await??;
+        // the key of a document can not change once it is set
+        if let Some(key) = &res.key {
+            *self.0.encryption.lock().unwrap() = Some(key.clone());
+        }
+        Ok(res.key)
+    }
+
+    /// Reads all content of an [`Entry`] of this document into a buffer.
+    ///
+    /// Unlike [`Entry::content_bytes`], this decrypts the content if the document is encrypted,
+    /// and returns content that was received inline with the entry even before it is in the blob
+    /// store.
+    pub async fn content_bytes(&self, entry: &Entry) -> Result<Bytes> {
+        let content = match entry.content_bytes(self).await {
+            Ok(content) => content,
+            Err(err) => self.inline_content(entry).await?.ok_or(err)?,
+        };
+        if iroh_docs::is_moved_to_key(entry.key()) {
+            return Ok(content);
+        }
+        match self.encryption_key().await? {
+            Some(encryption) => Ok(encryption.decrypt_content(&content)?),
+            None => Ok(content),
+        }
+    }
+
+    /// Returns the content of an entry if it was received inline with the entry.
+    async fn inline_content(&self, entry: &Entry) -> Result<Option<Bytes>> {
+        let len = entry.content_len();
+        if len == 0 || len > iroh_docs::store::MAX_INLINE_LIMIT {
+            return Ok(None);
+        }
+        let res = self
+            .rpc(GetInlineContentRequest {
+                doc_id: self.id(),
+                author: entry.author(),
+                key: entry.key().to_vec().into(),
+                hash: entry.content_hash(),
+            })
+            .await??;
+        Ok(res.content)
+    }
+
+    /// Moves this document to a new document with a fresh secret.
+    ///
+    /// Use this if the document secret was leaked. The latest entries are copied to the new
+    /// document. If `keep_authors` is set, they are signed by their original authors if this node
+    /// has their secrets, and by `author` otherwise. A marker entry signed by `author` is written
+    /// to this document, so that peers that sync it emit [`LiveEvent::Migrated`] and can follow it
+    /// with [`Self::follow_migration`]. Write access to the new document has to be shared again.
+    /// Fails if this node does not have write access to this document.
+    ///
+    /// The encryption key of an encrypted document is kept, because the entries are copied as is.
+    pub async fn rotate(&self, author: AuthorId, keep_authors: bool) -> Result<Doc> {
+        self.ensure_open()?;
+        let res = self
+            .rpc(RotateRequest {
+                doc_id: self.id(),
+                author_id: author,
+                keep_authors,
+            })
+            .await??;
+        Ok(Doc::new(self.0.rpc.clone(), res.id))
+    }
+
+    /// Starts to sync the document this document was moved to, see [`LiveEvent::Migrated`].
+    ///
+    /// The new document is imported with read access and synced with the peers of this document.
+    /// Fails if this document does not contain a marker entry pointing to `to`.
+    ///
+    /// Markers are not followed automatically: anyone who obtained the leaked secret of this
+    /// document can write one. Only follow a marker if its author is trusted, or let this node
+    /// follow the markers of trusted authors with [`Self::set_trusted_marker_authors`].
+    pub async fn follow_migration(&self, to: NamespaceId) -> Result<Doc> {
+        self.ensure_open()?;
+        self.rpc(FollowMigrationRequest {
+            doc_id: self.id(),
+            to,
+        })
+        .await??;
+        Ok(Doc::new(self.0.rpc.clone(), to))
+    }
+
+    /// Sets the authors whose moved-to markers this node follows automatically.
+    ///
+    /// When a marker of one of these authors is received from a peer, the new document is
+    /// imported and synced as with [`Self::follow_migration`]. Markers of other authors are only
+    /// reported with [`LiveEvent::Migrated`]. An empty set, the default, disables following
+    /// markers automatically.
+    pub async fn set_trusted_marker_authors(
+        &self,
+        authors: impl IntoIterator<Item = AuthorId>,
+    ) -> Result<()> {
+        self.ensure_open()?;
+        self.rpc(SetTrustedMarkerAuthorsRequest {
+            doc_id: self.id(),
+            authors: authors.into_iter().collect(),
+        })
+        .await??;
+        Ok(())
+    }
+
+    /// Returns the authors whose moved-to markers this node follows automatically.
+    pub async fn trusted_marker_authors(&self) -> Result<BTreeSet<AuthorId>> {
+        self.ensure_open()?;
+        let res = self
+            .rpc(GetTrustedMarkerAuthorsRequest { doc_id: self.id() })
+            .await??;
+        Ok(res.authors)
+    }
+
+    /// Returns the value `name` of the [`Crdt`] type `C` in this document.
+    ///
+    /// Unlike plain entries, which are last-writer-wins per author and key, the edits of all
+    /// authors to the value are merged, see [`iroh_docs::crdt`]. The value is empty until it is
+    /// first edited.
+    ///
+    /// Not supported for encrypted documents, because their keys cannot be queried by prefix.
+    pub fn crdt<C: Crdt>(&self, name: impl AsRef<[u8]>) -> CrdtValue<C> {
+        CrdtValue::new(self.clone(), name.as_ref())
+    }
+
+    /// Continuously syncs this document with a local folder.
+    ///
+    /// See [`FolderSync`] for how changes and conflicts are handled. Dropping the returned
+    /// [`FolderSync`] stops the sync.
+    ///
+    /// Not supported for encrypted documents.
+    #[cfg(feature = "folder-sync")]
+    #[cfg_attr(iroh_docsrs, doc(cfg(feature = "folder-sync")))]
+    pub async fn sync_folder(&self, opts: FolderSyncOpts) -> Result<FolderSync> {
+        self.ensure_open()?;
+        self.ensure_unencrypted().await?;
+        FolderSync::spawn(self.clone(), opts).await
+    }
+
+    async fn ensure_unencrypted(&self) -> Result<()> {
+        match self.encryption_key().await? {
+            Some(_) => Err(anyhow!("not supported for encrypted documents")),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A set of writes to apply to a document in a single transaction with [`Doc::apply_batch`].
+#[derive(Debug, Clone, Default)]
+pub struct Batch {
+    writes: Vec<BatchWrite>,
+}
+
+impl Batch {
+    /// Creates an empty batch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the content of a key to a byte array, like [`Doc::set_bytes`].
+    pub fn set_bytes(mut self, key: impl Into<Bytes>, value: impl Into<Bytes>) -> Self {
+        self.writes.push(BatchWrite::SetBytes {
+            key: key.into(),
+            value: value.into(),
+        });
+        self
+    }
+
+    /// Sets an entry via its key, hash, and size, like [`Doc::set_hash`].
+    pub fn set_hash(mut self, key: impl Into<Bytes>, hash: Hash, size: u64) -> Self {
+        self.writes.push(BatchWrite::SetHash {
+            key: key.into(),
+            hash,
+            size,
+        });
+        self
+    }
+
+    /// Deletes entries that match the key `prefix`, like [`Doc::del`].
+    pub fn del(mut self, prefix: impl Into<Bytes>) -> Self {
+        self.writes.push(BatchWrite::Del {
+            prefix: prefix.into(),
+        });
+        self
+    }
+
+    /// Returns the number of writes in this batch.
+    pub fn len(&self) -> usize {
+        self.writes.len()
+    }
+
+    /// Returns true if this batch contains no writes.
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+}
+
+fn decrypt_entry(encryption: Option<&DocEncryptionKey>, entry: Entry) -> Result<Entry> {
+    match encryption {
+        // marker entries are not encrypted, see [`Doc::rotate`]
+        Some(_) if iroh_docs::is_moved_to_key(entry.key()) => Ok(entry),
+        Some(encryption) => Ok(Entry(encryption.decrypt_entry(&entry.0)?)),
+        None => Ok(entry),
+    }
+}
+
+fn decrypt_event(encryption: Option<&DocEncryptionKey>, event: LiveEvent) -> Result<LiveEvent> {
+    Ok(match event {
+        LiveEvent::InsertLocal { entry } => LiveEvent::InsertLocal {
+            entry: decrypt_entry(encryption, entry)?,
+        },
+        LiveEvent::InsertLocalBatch { entries } => LiveEvent::InsertLocalBatch {
+            entries: entries
+                .into_iter()
+                .map(|entry| decrypt_entry(encryption, entry))
+                .collect::<Result<_>>()?,
+        },
+        LiveEvent::Replayed { entry } => LiveEvent::Replayed {
+            entry: decrypt_entry(encryption, entry)?,
+        },
+        LiveEvent::InsertRemote {
+            from,
+            entry,
+            content_status,
+        } => LiveEvent::InsertRemote {
+            from,
+            entry: decrypt_entry(encryption, entry)?,
+            content_status,
+        },
+        event => event,
+    })
 }
 
 impl<'a> From<&'a Doc> for &'a RpcClient {
@@ -424,6 +1311,24 @@ impl<'a> From<&'a Doc> for &'a RpcClient {
     }
 }
 
+/// A page of the entries matching a query, returned from [`Doc::get_page`].
+#[derive(Debug, Clone)]
+pub struct Page {
+    /// The entries of the page.
+    pub entries: Vec<Entry>,
+    /// The cursor to fetch the next page, or `None` if this is the last page.
+    pub cursor: Option<Cursor>,
+}
+
+/// Outcome of [`Doc::export_bundle`] and [`Client::import_bundle`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BundleOutcome {
+    /// The number of exported or inserted entries.
+    pub entries: usize,
+    /// The number of exported or imported content blobs.
+    pub blobs: usize,
+}
+
 /// A single entry in a [`Doc`].
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
 pub struct Entry(iroh_docs::Entry);
@@ -547,6 +1452,17 @@ pub enum LiveEvent {
         /// The inserted entry.
         entry: Entry,
     },
+    /// Several local insertions that were applied together with [`Doc::apply_batch`].
+    InsertLocalBatch {
+        /// The inserted entries, in the order of the batch.
+        entries: Vec<Entry>,
+    },
+    /// An existing entry, emitted by [`Doc::subscribe_with_filter`] when replaying matching
+    /// entries.
+    Replayed {
+        /// The existing entry.
+        entry: Entry,
+    },
     /// Received a remote insert.
     InsertRemote {
         /// The peer that sent us the entry.
@@ -567,6 +1483,8 @@ pub enum LiveEvent {
     NeighborDown(PublicKey),
     /// A set-reconciliation sync finished.
     SyncFinished(SyncEvent),
+    /// A sync session covering this and other documents with a single peer finished.
+    SessionFinished(SessionEvent),
     /// All pending content is now ready.
     ///
     /// This event signals that all queued content downloads from the last sync run have either
@@ -577,6 +1495,15 @@ pub enum LiveEvent {
     /// Receiving this event does not guarantee that all content in the document is available. If
     /// blobs failed to download, this event will still be emitted after all operations completed.
     PendingContentReady,
+    /// The document was moved to a successor namespace, see [`Doc::rotate`].
+    ///
+    /// The successor is not synced until [`Doc::follow_migration`] is called.
+    Migrated {
+        /// The id of the successor document.
+        to: NamespaceId,
+        /// The author of the marker entry.
+        author: AuthorId,
+    },
 }
 
 impl From<crate::docs::engine::LiveEvent> for LiveEvent {
@@ -585,6 +1512,14 @@ impl From<crate::docs::engine::LiveEvent> for LiveEvent {
             crate::docs::engine::LiveEvent::InsertLocal { entry } => Self::InsertLocal {
                 entry: entry.into(),
             },
+            crate::docs::engine::LiveEvent::InsertLocalBatch { entries } => {
+                Self::InsertLocalBatch {
+                    entries: entries.into_iter().map(Into::into).collect(),
+                }
+            }
+            crate::docs::engine::LiveEvent::Replayed { entry } => Self::Replayed {
+                entry: entry.into(),
+            },
             crate::docs::engine::LiveEvent::InsertRemote {
                 from,
                 entry,
@@ -598,7 +1533,13 @@ impl From<crate::docs::engine::LiveEvent> for LiveEvent {
             crate::docs::engine::LiveEvent::NeighborUp(node) => Self::NeighborUp(node),
             crate::docs::engine::LiveEvent::NeighborDown(node) => Self::NeighborDown(node),
             crate::docs::engine::LiveEvent::SyncFinished(details) => Self::SyncFinished(details),
+            crate::docs::engine::LiveEvent::SessionFinished(details) => {
+                Self::SessionFinished(details)
+            }
             crate::docs::engine::LiveEvent::PendingContentReady => Self::PendingContentReady,
+            crate::docs::engine::LiveEvent::Migrated { to, author } => {
+                Self::Migrated { to, author }
+            }
         }
     }
 }
@@ -0,0 +1,112 @@
+//! Values of conflict-free replicated data types stored in documents.
+
+use std::marker::PhantomData;
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures_lite::StreamExt;
+use iroh_docs::{
+    crdt::{Crdt, CrdtEntry, CrdtWrite},
+    store::Query,
+    AuthorId,
+};
+use tracing::debug;
+
+use super::{Batch, Doc};
+
+/// Maximum length of the content of the entries that are read together with the entries when
+/// loading a value. The content of longer entries is read on its own.
+const MAX_BATCHED_CONTENT_LEN: u64 = 64 * 1024;
+
+/// A value of a [`Crdt`] type stored in a document, created with [`Doc::crdt`].
+///
+/// [`Self::load`] merges the entries of all authors into the current value. The edit methods of
+/// the loaded value return the [`CrdtWrite`]s for an edit, which are written to the document with
+/// [`Self::apply`]:
+///
+/// ```no_run
+/// # async fn example(doc: iroh::client::Doc, author: iroh::docs::AuthorId) -> anyhow::Result<()> {
+/// use iroh::client::docs::Counter;
+///
+/// let likes = doc.crdt::<Counter>("likes");
+/// let mut counter = likes.load().await?;
+/// likes.apply(author, [counter.add(author, 1)?]).await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Entries whose content is not available on this node yet are passed to [`Crdt::materialize`]
+/// with empty content, and are ignored. Setting an [inline limit](Doc::set_inline_limit) makes the
+/// small entries of most values available as soon as they are synced.
+#[derive(Debug, Clone)]
+pub struct CrdtValue<C> {
+    doc: Doc,
+    prefix: Bytes,
+    _type: PhantomData<C>,
+}
+
+impl<C: Crdt> CrdtValue<C> {
+    pub(super) fn new(doc: Doc, name: &[u8]) -> Self {
+        Self {
+            doc,
+            prefix: iroh_docs::crdt::prefix::<C>(name),
+            _type: PhantomData,
+        }
+    }
+
+    /// Loads the entries of all authors and merges them into the current value.
+    pub async fn load(&self) -> Result<C> {
+        let query = Query::key_prefix(&self.prefix);
+        let mut entries = Box::pin(
+            self.doc
+                .get_many_content(query, MAX_BATCHED_CONTENT_LEN)
+                .await?,
+        );
+        let mut items = Vec::new();
+        while let Some(res) = entries.next().await {
+            let (entry, content) = res?;
+            let content = match content {
+                Some(content) => content,
+                None if entry.content_len() > MAX_BATCHED_CONTENT_LEN => {
+                    match self.doc.content_bytes(&entry).await {
+                        Ok(content) => content,
+                        Err(err) => {
+                            debug!(?err, "content of entry not available");
+                            Bytes::new()
+                        }
+                    }
+                }
+                None => {
+                    debug!("content of entry not available");
+                    Bytes::new()
+                }
+            };
+            items.push(CrdtEntry {
+                author: entry.author(),
+                key: Bytes::copy_from_slice(&entry.key()[self.prefix.len()..]),
+                timestamp: entry.timestamp(),
+                content,
+            });
+        }
+        Ok(C::materialize(items))
+    }
+
+    /// Writes the entries for an edit as `author` in a single batch.
+    pub async fn apply(
+        &self,
+        author: AuthorId,
+        writes: impl IntoIterator<Item = CrdtWrite>,
+    ) -> Result<()> {
+        let mut batch = Batch::new();
+        let mut empty = true;
+        for write in writes {
+            let key = [&self.prefix[..], &write.key[..]].concat();
+            batch = batch.set_bytes(key, write.content);
+            empty = false;
+        }
+        if !empty {
+            self.doc.apply_batch(author, batch).await?;
+        }
+        Ok(())
+    }
+}
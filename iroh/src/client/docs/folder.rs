@@ -0,0 +1,580 @@
+//! Continuous synchronization of a document with a local folder.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs::Metadata,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures_lite::{Stream, StreamExt};
+use iroh_blobs::{store::ExportMode, Hash};
+use iroh_docs::{store::Query, AuthorId, ContentStatus};
+use notify::{EventKind, RecursiveMode, Watcher};
+use tokio::{sync::mpsc, time::Instant};
+use tokio_util::task::AbortOnDropHandle;
+use tracing::{debug, warn};
+
+use crate::util::fs::{key_to_path, path_to_key};
+
+use super::{Doc, Entry, LiveEvent};
+
+/// How long the folder has to be unchanged before local changes are imported.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Capacity of the channel for [`FolderSyncEvent`]s.
+const EVENTS_CHANNEL_CAP: usize = 64;
+
+/// Size of the buffer for hashing local files.
+const HASH_BUF_SIZE: usize = 64 * 1024;
+
+/// Options for [`Doc::sync_folder`].
+#[derive(Debug, Clone)]
+pub struct FolderSyncOpts {
+    /// The author of the entries for local changes.
+    pub author: AuthorId,
+    /// The local folder.
+    pub root: PathBuf,
+    /// The key prefix of the entries that are synced with the folder.
+    ///
+    /// A `/` is appended if the prefix is not empty and does not end with one.
+    pub prefix: String,
+}
+
+/// An event of a [`FolderSync`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FolderSyncEvent {
+    /// A local file was created or changed, and imported into the document.
+    Imported {
+        /// The path of the file.
+        path: PathBuf,
+    },
+    /// A local file was deleted, and its entry deleted from the document.
+    Deleted {
+        /// The path of the file.
+        path: PathBuf,
+    },
+    /// An entry of the document was written to a local file.
+    Exported {
+        /// The path of the file.
+        path: PathBuf,
+    },
+    /// The entry of a local file was deleted in the document, and the file removed.
+    Removed {
+        /// The path of the file.
+        path: PathBuf,
+    },
+    /// A local file had changes that were not imported yet when a newer entry for it was
+    /// written to the document. The local file was moved to `copy` before exporting the entry.
+    Conflict {
+        /// The path of the file.
+        path: PathBuf,
+        /// The path the local version of the file was moved to.
+        copy: PathBuf,
+    },
+}
+
+/// Continuous synchronization of a document with a local folder, created with
+/// [`Doc::sync_folder`].
+///
+/// Files in the folder are mapped to entries with the key of their relative path, as with
+/// [`path_to_key`]. Changes to local files are imported into the document, and deleted files are
+/// deleted from the document. Entries inserted by peers are exported to the folder once their
+/// content is available, and entries deleted by peers are removed from the folder.
+///
+/// Conflicts are resolved by the document: the latest entry for a key always ends up in the
+/// folder. If a local file has changes that were not imported yet when a newer entry for it
+/// arrives, the local version is kept as a conflict copy next to it, which is then imported as a
+/// new file. Local changes win against deletions by peers. When the sync starts, local files that
+/// differ from their entry are imported if they were modified after the entry was written, and
+/// are otherwise handled like local changes that conflict with a newer entry.
+///
+/// Empty files are not synced, because empty entries mark deletions in documents.
+///
+/// The stream yields the changes applied in either direction. Errors for single files, e.g. a
+/// file that cannot be read or was deleted while it was imported, are logged and the file is
+/// skipped until it changes again. The sync stops on the first other error, which is yielded as
+/// the last item, or when the stream is dropped.
+#[derive(derive_more::Debug)]
+pub struct FolderSync {
+    #[debug("AbortOnDropHandle")]
+    _task: AbortOnDropHandle<()>,
+    events: mpsc::Receiver<Result<FolderSyncEvent>>,
+}
+
+impl FolderSync {
+    pub(super) async fn spawn(doc: Doc, opts: FolderSyncOpts) -> Result<Self> {
+        let root = tokio::fs::canonicalize(&opts.root).await?;
+        let mut prefix = opts.prefix;
+        if !prefix.is_empty() && !prefix.ends_with('/') {
+            prefix.push('/');
+        }
+        let (tx, events) = mpsc::channel(EVENTS_CHANNEL_CAP);
+        let actor = Actor {
+            doc,
+            author: opts.author,
+            root,
+            prefix,
+            synced: Default::default(),
+            pending: Default::default(),
+            events: tx.clone(),
+        };
+        let task = tokio::task::spawn(async move {
+            if let Err(err) = actor.run().await {
+                tx.send(Err(err)).await.ok();
+            }
+        });
+        Ok(Self {
+            _task: AbortOnDropHandle::new(task),
+            events,
+        })
+    }
+}
+
+impl Stream for FolderSync {
+    type Item = Result<FolderSyncEvent>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.events.poll_recv(cx)
+    }
+}
+
+/// The state of a local file when it was last imported or exported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileState {
+    len: u64,
+    modified: u64,
+    hash: Hash,
+}
+
+impl FileState {
+    fn new(meta: &Metadata, hash: Hash) -> Self {
+        Self {
+            len: meta.len(),
+            modified: modified(meta),
+            hash,
+        }
+    }
+
+    /// Whether the file was changed since.
+    fn is_changed(&self, meta: &Metadata) -> bool {
+        self.len != meta.len() || self.modified != modified(meta)
+    }
+}
+
+struct Actor {
+    doc: Doc,
+    author: AuthorId,
+    root: PathBuf,
+    prefix: String,
+    /// The local files as they were last imported or exported.
+    synced: HashMap<PathBuf, FileState>,
+    /// Keys of entries whose content was not available yet, by content hash.
+    pending: HashMap<Hash, HashSet<Bytes>>,
+    events: mpsc::Sender<Result<FolderSyncEvent>>,
+}
+
+impl Actor {
+    async fn run(mut self) -> Result<()> {
+        // subscribe and watch before the initial sync, so that no changes are missed
+        let mut doc_events = Box::pin(self.doc.subscribe().await?);
+        let (fs_tx, mut fs_rx) = mpsc::unbounded_channel();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                fs_tx.send(event).ok();
+            })?;
+        watcher.watch(&self.root, RecursiveMode::Recursive)?;
+
+        self.initial_sync().await?;
+
+        let mut changed = BTreeSet::new();
+        // only file system events move the deadline, so that doc events do not delay imports
+        let mut deadline = Instant::now();
+        loop {
+            tokio::select! {
+                event = doc_events.next() => match event {
+                    Some(event) => self.on_doc_event(event?).await?,
+                    None => break,
+                },
+                event = fs_rx.recv() => match event {
+                    Some(event) => {
+                        let event: notify::Event = event?;
+                        if !matches!(event.kind, EventKind::Access(_)) {
+                            changed.extend(event.paths);
+                            deadline = Instant::now() + DEBOUNCE;
+                        }
+                    }
+                    None => break,
+                },
+                _ = tokio::time::sleep_until(deadline), if !changed.is_empty() => {
+                    for path in std::mem::take(&mut changed) {
+                        let res = self.on_local_change(path.clone()).await;
+                        skip_file_error(&path, res);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn initial_sync(&mut self) -> Result<()> {
+        let mut entries = BTreeMap::new();
+        let query = Query::single_latest_per_key()
+            .key_prefix(&self.prefix)
+            .include_empty();
+        let mut stream = Box::pin(self.doc.get_many(query).await?);
+        while let Some(entry) = stream.next().await {
+            let entry = entry?;
+            if is_file_key(entry.key()) {
+                entries.insert(Bytes::copy_from_slice(entry.key()), entry);
+            }
+        }
+
+        for path in list_files(self.root.clone()).await? {
+            let key = self.key(&path)?;
+            let entry = entries.remove(&key);
+            let res = self.initial_sync_file(path.clone(), entry).await;
+            skip_file_error(&path, res);
+        }
+
+        for (key, entry) in entries {
+            if !is_deleted(&entry) {
+                let path = self.path(&key)?;
+                let res = self.export(path.clone(), entry).await;
+                skip_file_error(&path, res);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sync a local file with the latest entry for it when the sync starts.
+    async fn initial_sync_file(&mut self, path: PathBuf, entry: Option<Entry>) -> Result<()> {
+        let meta = tokio::fs::metadata(&path).await?;
+        match entry {
+            None => self.import(path, &meta).await?,
+            Some(entry) if is_deleted(&entry) => {
+                if modified(&meta) > entry.timestamp() {
+                    self.import(path, &meta).await?;
+                } else {
+                    self.remove(path).await?;
+                }
+            }
+            Some(entry) => {
+                let hash = hash_file(path.clone()).await?;
+                if hash == entry.content_hash() {
+                    self.synced.insert(path, FileState::new(&meta, hash));
+                } else if modified(&meta) > entry.timestamp() {
+                    self.import(path, &meta).await?;
+                } else {
+                    self.conflict(&path).await?;
+                    self.export(path, entry).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn on_doc_event(&mut self, event: LiveEvent) -> Result<()> {
+        match event {
+            LiveEvent::InsertRemote {
+                entry,
+                content_status,
+                ..
+            } => {
+                if !entry.key().starts_with(self.prefix.as_bytes()) {
+                    return Ok(());
+                }
+                if !is_deleted(&entry) && content_status != ContentStatus::Complete {
+                    self.pending
+                        .entry(entry.content_hash())
+                        .or_default()
+                        .insert(Bytes::copy_from_slice(entry.key()));
+                    return Ok(());
+                }
+                self.resolve(entry.key()).await?;
+            }
+            LiveEvent::ContentReady { hash } => {
+                for key in self.pending.remove(&hash).unwrap_or_default() {
+                    self.resolve(&key).await?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Apply the latest entries for `key`, and for the files with keys that start with `key`.
+    async fn resolve(&mut self, key: &[u8]) -> Result<()> {
+        let mut keys = BTreeSet::new();
+        if is_file_key(key) {
+            keys.insert(Bytes::copy_from_slice(key));
+        }
+        // entries that are deleted by a prefix deletion are removed from the document
+        for path in self.synced.keys() {
+            let file_key = self.key(path)?;
+            if file_key.starts_with(key) {
+                keys.insert(file_key);
+            }
+        }
+        for key in keys {
+            let path = self.path(&key)?;
+            let query = Query::single_latest_per_key()
+                .key_exact(&key)
+                .include_empty();
+            let entry = self.doc.get_one(query).await?;
+            let res = self.apply(path.clone(), entry).await;
+            skip_file_error(&path, res);
+        }
+        Ok(())
+    }
+
+    /// Apply the latest entry for a local file.
+    async fn apply(&mut self, path: PathBuf, entry: Option<Entry>) -> Result<()> {
+        let meta = match tokio::fs::metadata(&path).await {
+            Ok(meta) => Some(meta),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err.into()),
+        };
+        let synced = self.synced.get(&path);
+        let local_changes = match (&meta, synced) {
+            (Some(meta), Some(state)) => state.is_changed(meta),
+            (Some(meta), None) => meta.len() > 0,
+            (None, _) => false,
+        };
+        match entry {
+            Some(entry) if !is_deleted(&entry) => {
+                if !local_changes && synced.is_some_and(|s| s.hash == entry.content_hash()) {
+                    return Ok(());
+                }
+                if local_changes {
+                    self.conflict(&path).await?;
+                }
+                self.export(path, entry).await?;
+            }
+            _ => match meta {
+                Some(meta) if local_changes => self.import(path, &meta).await?,
+                Some(_) => self.remove(path).await?,
+                None => {
+                    self.synced.remove(&path);
+                }
+            },
+        }
+        Ok(())
+    }
+
+    async fn on_local_change(&mut self, path: PathBuf) -> Result<()> {
+        if !path.starts_with(&self.root) {
+            return Ok(());
+        }
+        match tokio::fs::metadata(&path).await {
+            Ok(meta) if meta.is_file() => self.on_local_file(path, meta).await?,
+            // a directory was created or moved into the folder
+            Ok(meta) if meta.is_dir() => {
+                for path in list_files(path).await? {
+                    let res = match tokio::fs::metadata(&path).await {
+                        Ok(meta) => self.on_local_file(path.clone(), meta).await,
+                        Err(err) => Err(err.into()),
+                    };
+                    skip_file_error(&path, res);
+                }
+            }
+            Ok(_) => {}
+            // a file or directory was deleted or moved out of the folder
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                let deleted: Vec<_> = self
+                    .synced
+                    .keys()
+                    .filter(|p| p.starts_with(&path))
+                    .cloned()
+                    .collect();
+                for path in deleted {
+                    self.synced.remove(&path);
+                    let key = self.key(&path)?;
+                    self.doc.del(self.author, key).await?;
+                    self.emit(FolderSyncEvent::Deleted { path }).await;
+                }
+            }
+            Err(err) => return Err(err.into()),
+        }
+        Ok(())
+    }
+
+    async fn on_local_file(&mut self, path: PathBuf, meta: Metadata) -> Result<()> {
+        let unchanged = self
+            .synced
+            .get(&path)
+            .is_some_and(|state| !state.is_changed(&meta));
+        if unchanged || meta.len() == 0 {
+            return Ok(());
+        }
+        self.import(path, &meta).await
+    }
+
+    /// Import a local file.
+    ///
+    /// `meta` is read before the import, so that changes during the import are imported again.
+    async fn import(&mut self, path: PathBuf, meta: &Metadata) -> Result<()> {
+        if meta.len() == 0 {
+            return Ok(());
+        }
+        let key = self.key(&path)?;
+        let outcome = self
+            .doc
+            .import_file(self.author, key, &path, false)
+            .await?
+            .finish()
+            .await?;
+        self.synced
+            .insert(path.clone(), FileState::new(meta, outcome.hash));
+        self.emit(FolderSyncEvent::Imported { path }).await;
+        Ok(())
+    }
+
+    /// Export an entry to a local file.
+    async fn export(&mut self, path: PathBuf, entry: Entry) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let hash = entry.content_hash();
+        let key = Bytes::copy_from_slice(entry.key());
+        let res = async {
+            self.doc
+                .export_file(entry, &path, ExportMode::Copy)
+                .await?
+                .finish()
+                .await
+        }
+        .await;
+        if let Err(err) = res {
+            // the content is not available yet if the entry was superseded before it arrived
+            debug!(
+                ?path,
+                "failed to export entry, waiting for content: {err:#}"
+            );
+            self.pending.entry(hash).or_default().insert(key);
+            return Ok(());
+        }
+        let meta = tokio::fs::metadata(&path).await?;
+        self.synced
+            .insert(path.clone(), FileState::new(&meta, hash));
+        self.emit(FolderSyncEvent::Exported { path }).await;
+        Ok(())
+    }
+
+    /// Remove a local file whose entry was deleted.
+    async fn remove(&mut self, path: PathBuf) -> Result<()> {
+        self.synced.remove(&path);
+        match tokio::fs::remove_file(&path).await {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err.into()),
+        }
+        self.emit(FolderSyncEvent::Removed { path }).await;
+        Ok(())
+    }
+
+    /// Move a local file with changes that conflict with the document to a conflict copy.
+    async fn conflict(&mut self, path: &Path) -> Result<()> {
+        let copy = conflict_path(path, self.author).await;
+        tokio::fs::rename(path, &copy).await?;
+        self.synced.remove(path);
+        self.emit(FolderSyncEvent::Conflict {
+            path: path.to_path_buf(),
+            copy,
+        })
+        .await;
+        Ok(())
+    }
+
+    async fn emit(&self, event: FolderSyncEvent) {
+        if self.events.send(Ok(event)).await.is_err() {
+            warn!("folder sync events receiver dropped");
+        }
+    }
+
+    fn key(&self, path: &Path) -> Result<Bytes> {
+        path_to_key(path, Some(self.prefix.clone()), Some(self.root.clone()))
+    }
+
+    fn path(&self, key: &[u8]) -> Result<PathBuf> {
+        key_to_path(key, Some(self.prefix.clone()), Some(self.root.clone()))
+    }
+}
+
+/// Whether `key` is the key of a file, as created by [`path_to_key`].
+fn is_file_key(key: &[u8]) -> bool {
+    key.ends_with(b"\0")
+}
+
+fn is_deleted(entry: &Entry) -> bool {
+    entry.content_hash() == Hash::EMPTY
+}
+
+/// The modification time of a file in microseconds since the Unix epoch, like entry timestamps.
+fn modified(meta: &Metadata) -> u64 {
+    meta.modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_micros() as u64)
+}
+
+/// Log an error that only affects the local file at `path`.
+///
+/// The file is synced again once it changes.
+fn skip_file_error(path: &Path, res: Result<()>) {
+    if let Err(err) = res {
+        warn!(?path, "failed to sync file, skipping: {err:#}");
+    }
+}
+
+/// Hash the content of a local file, without reading it into memory at once.
+async fn hash_file(path: PathBuf) -> Result<Hash> {
+    tokio::task::spawn_blocking(move || {
+        let mut file = std::fs::File::open(path)?;
+        let mut hasher = blake3::Hasher::new();
+        let mut buf = vec![0u8; HASH_BUF_SIZE];
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+        Ok(hasher.finalize().into())
+    })
+    .await?
+}
+
+/// A free path for the conflict copy of `path`.
+async fn conflict_path(path: &Path, author: AuthorId) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut copy = path.with_file_name(format!("{name}.conflict-{}", author.fmt_short()));
+    let mut i = 1;
+    while tokio::fs::try_exists(&copy).await.unwrap_or(true) {
+        i += 1;
+        copy = path.with_file_name(format!("{name}.conflict-{}-{i}", author.fmt_short()));
+    }
+    copy
+}
+
+/// List the files in a folder, recursively.
+async fn list_files(root: PathBuf) -> Result<Vec<PathBuf>> {
+    let files = tokio::task::spawn_blocking(move || {
+        walkdir::WalkDir::new(root)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.into_path())
+            .collect()
+    })
+    .await?;
+    Ok(files)
+}
@@ -12,38 +12,61 @@
 //! [`Client::list_hash_seq`] can be used to list all tags with a hash_seq format.
 //!
 //! [`Client::delete`] can be used to delete a tag.
+//!
+//! [`Client::in_store`] returns a client for the tags of a named blob store.
 use anyhow::Result;
 use futures_lite::{Stream, StreamExt};
 use iroh_blobs::{BlobFormat, Hash, Tag};
-use ref_cast::RefCast;
 use serde::{Deserialize, Serialize};
 
 use super::RpcClient;
 use crate::rpc_protocol::tags::{DeleteRequest, ListRequest};
 
 /// Iroh tags client.
-#[derive(Debug, Clone, RefCast)]
-#[repr(transparent)]
+#[derive(Debug, Clone)]
 pub struct Client {
     pub(super) rpc: RpcClient,
+    /// The named blob store whose tags this client operates on, `None` for the default store.
+    pub(super) store: Option<String>,
 }
 
 impl Client {
+    /// Returns a client for the tags of the named blob store of the node.
+    pub fn in_store(&self, store: impl Into<String>) -> Self {
+        Self {
+            rpc: self.rpc.clone(),
+            store: Some(store.into()),
+        }
+    }
+
     /// Lists all tags.
     pub async fn list(&self) -> Result<impl Stream<Item = Result<TagInfo>>> {
-        let stream = self.rpc.server_streaming(ListRequest::all()).await?;
+        let req = ListRequest {
+            store: self.store.clone(),
+            ..ListRequest::all()
+        };
+        let stream = self.rpc.server_streaming(req).await?;
         Ok(stream.map(|res| res.map_err(anyhow::Error::from)))
     }
 
     /// Lists all tags with a hash_seq format.
     pub async fn list_hash_seq(&self) -> Result<impl Stream<Item = Result<TagInfo>>> {
-        let stream = self.rpc.server_streaming(ListRequest::hash_seq()).await?;
+        let req = ListRequest {
+            store: self.store.clone(),
+            ..ListRequest::hash_seq()
+        };
+        let stream = self.rpc.server_streaming(req).await?;
         Ok(stream.map(|res| res.map_err(anyhow::Error::from)))
     }
 
     /// Deletes a tag.
     pub async fn delete(&self, name: Tag) -> Result<()> {
-        self.rpc.rpc(DeleteRequest { name }).await??;
+        self.rpc
+            .rpc(DeleteRequest {
+                name,
+                store: self.store.clone(),
+            })
+            .await??;
         Ok(())
     }
 }
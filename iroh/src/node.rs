@@ -35,7 +35,7 @@
 //! well, without going through [`client`](crate::client::Iroh))
 //!
 //! To shut down the node, call [`Node::shutdown`].
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::Debug;
 use std::marker::PhantomData;
 use std::net::SocketAddr;
@@ -45,8 +45,8 @@ use std::time::Duration;
 
 use anyhow::{anyhow, Result};
 use futures_lite::StreamExt;
-use futures_util::future::MapErr;
 use futures_util::future::Shared;
+use futures_util::future::{join_all, MapErr};
 use iroh_base::key::PublicKey;
 use iroh_blobs::protocol::Closed;
 use iroh_blobs::store::Store as BaoStore;
@@ -71,13 +71,14 @@ mod nodes_storage;
 mod protocol;
 mod rpc;
 mod rpc_status;
+mod uploads;
 
 pub use self::builder::{
-    Builder, DiscoveryConfig, DocsStorage, GcPolicy, ProtocolBuilder, StorageConfig,
-    DEFAULT_RPC_ADDR,
+    BlobStoreOptions, Builder, DiscoveryConfig, DocsStorage, GcPolicy, ProtocolBuilder,
+    StorageConfig, DEFAULT_RPC_ADDR,
 };
 pub use self::rpc_status::RpcStatus;
-pub use protocol::ProtocolHandler;
+pub use protocol::{blobs_store_alpn, ProtocolHandler};
 
 /// How often to save node data.
 const SAVE_NODES_INTERVAL: Duration = Duration::from_secs(30);
@@ -123,6 +124,15 @@ struct NodeInner<D> {
     cancel_token: CancellationToken,
     client: crate::client::Iroh,
     local_pool_handle: LocalPoolHandle,
+    /// Additional named blob stores, next to the default store.
+    blob_stores: BTreeMap<String, NamedBlobStore<D>>,
+}
+
+/// A named blob store hosted by the node, see [`Builder::add_blobs_store`].
+#[derive(Debug)]
+struct NamedBlobStore<D> {
+    blobs: Arc<BlobsProtocol<D>>,
+    options: BlobStoreOptions,
 }
 
 /// In memory node.
@@ -344,6 +354,37 @@ impl<D: iroh_blobs::store::Store> NodeInner<D> {
             });
         }
 
+        // Spawn a garbage collection task for each named store with GC enabled.
+        //
+        // Documents only reference the default store, so there are no additional live hashes.
+        for (name, store) in self.blob_stores.iter() {
+            let GcPolicy::Interval(gc_period) = store.options.gc_policy else {
+                continue;
+            };
+            let blobs = store.blobs.clone();
+            let handle = local_pool.spawn(move || async move {
+                blobs
+                    .store()
+                    .gc_run(
+                        iroh_blobs::store::GcConfig {
+                            period: gc_period,
+                            done_callback: None,
+                        },
+                        || async { BTreeSet::new() },
+                    )
+                    .await;
+            });
+            join_set.spawn(
+                async move {
+                    if let Err(err) = handle.await {
+                        return Err(anyhow::Error::from(err));
+                    }
+                    Ok(())
+                }
+                .instrument(info_span!("gc", store = %name)),
+            );
+        }
+
         if let Some(nodes_data_path) = nodes_data_path {
             let ep = self.endpoint.clone();
             let token = self.cancel_token.clone();
@@ -469,6 +510,13 @@ impl<D: iroh_blobs::store::Store> NodeInner<D> {
                 .close(error_code.into(), error_code.reason()),
             // Shutdown protocol handlers.
             protocols.shutdown(),
+            // Shutdown named blob stores which are not registered as protocol handlers.
+            join_all(
+                self.blob_stores
+                    .values()
+                    .filter(|store| !store.options.provide)
+                    .map(|store| store.blobs.store().shutdown())
+            ),
         );
     }
 }
@@ -547,7 +595,7 @@ mod tests {
     use iroh_blobs::{provider::AddProgress, util::SetTagOption, BlobFormat};
     use iroh_net::{key::SecretKey, relay::RelayMode, test_utils::DnsPkarrServer, NodeAddr};
 
-    use crate::client::blobs::{AddOutcome, WrapOption};
+    use crate::client::blobs::{AddOutcome, DownloadMode, DownloadOptions, WrapOption};
 
     use super::*;
 
@@ -696,6 +744,75 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_named_blob_stores() -> Result<()> {
+        let _guard = iroh_test::logging::setup();
+
+        let options = BlobStoreOptions {
+            provide: true,
+            ..Default::default()
+        };
+        let node1 = Node::memory()
+            .relay_mode(RelayMode::Disabled)
+            .bind_random_port()
+            .add_blobs_store("tenant", Default::default(), options)
+            .spawn()
+            .await?;
+        let node2 = Node::memory()
+            .relay_mode(RelayMode::Disabled)
+            .bind_random_port()
+            .spawn()
+            .await?;
+
+        // data added to a named store is not visible in the default store
+        let tenant = node1.blobs().in_store("tenant");
+        let AddOutcome { hash, .. } = tenant.add_bytes(b"foo".to_vec()).await?;
+        assert!(tenant.has(hash).await?);
+        assert!(!node1.blobs().has(hash).await?);
+        let tags: Vec<_> = node1
+            .tags()
+            .in_store("tenant")
+            .list()
+            .await?
+            .try_collect()
+            .await?;
+        assert_eq!(tags.len(), 1);
+        let tags: Vec<_> = node1.tags().list().await?.try_collect().await?;
+        assert!(tags.is_empty());
+
+        // unknown stores are rejected
+        assert!(node1.blobs().in_store("unknown").has(hash).await.is_err());
+        assert!(node1.blobs().in_store("unknown").batch().await.is_err());
+        // the tags stream has no error variant, so it is empty for unknown stores
+        let mut tags = node1.tags().in_store("unknown").list().await?;
+        assert!(tags.next().await.is_none());
+
+        // the ticket refers to the named store, and can be used to download from it
+        let ticket = tenant
+            .share(hash, BlobFormat::Raw, AddrInfoOptions::Addresses)
+            .await?;
+        assert_eq!(ticket.store(), Some("tenant"));
+        node2
+            .blobs()
+            .download_with_opts(
+                hash,
+                DownloadOptions {
+                    format: ticket.format(),
+                    nodes: vec![ticket.node_addr().clone()],
+                    tag: SetTagOption::Auto,
+                    mode: DownloadMode::Queued,
+                    remote_store: ticket.store().map(ToOwned::to_owned),
+                },
+            )
+            .await?
+            .await?;
+        assert_eq!(node2.blobs().read_to_bytes(hash).await?.as_ref(), b"foo");
+
+        node1.shutdown().await?;
+        node2.shutdown().await?;
+        Ok(())
+    }
+
     #[tokio::test]
     #[ignore = "flaky"]
     async fn test_download_via_relay_with_discovery() -> Result<()> {
@@ -1,4 +1,5 @@
 use std::{
+    collections::BTreeMap,
     net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
     path::{Path, PathBuf},
     sync::Arc,
@@ -16,7 +17,7 @@ use iroh_blobs::{
     util::local_pool::{self, LocalPool, LocalPoolHandle, PanicMode},
 };
 use iroh_docs::engine::DefaultAuthorStorage;
-use iroh_docs::net::DOCS_ALPN;
+use iroh_docs::net::{DOCS_ALPN, DOCS_ALPN_V1};
 use iroh_gossip::net::{Gossip, GOSSIP_ALPN};
 #[cfg(not(test))]
 use iroh_net::discovery::local_swarm_discovery::LocalSwarmDiscovery;
@@ -38,8 +39,9 @@ use crate::{
     client::RPC_ALPN,
     node::{
         nodes_storage::load_node_addrs,
-        protocol::{BlobsProtocol, ProtocolMap},
-        ProtocolHandler,
+        protocol::{blobs_store_alpn, BlobsProtocol, ProtocolMap},
+        uploads::{BlobUploads, DEFAULT_UPLOAD_TTL},
+        NamedBlobStore, ProtocolHandler,
     },
     rpc_protocol::RpcService,
     util::{fs::load_secret_key, path::IrohPaths},
@@ -114,6 +116,7 @@ where
     rpc_endpoint: IrohServerEndpoint,
     rpc_addr: Option<SocketAddr>,
     blobs_store: D,
+    blobs_stores: BTreeMap<String, (D, BlobStoreOptions)>,
     keylog: bool,
     relay_mode: RelayMode,
     gc_policy: GcPolicy,
@@ -128,6 +131,7 @@ where
     gc_done_callback: Option<Box<dyn Fn() + Send>>,
     blob_events: EventSender,
     transport_config: Option<TransportConfig>,
+    upload_ttl: Duration,
 }
 
 /// Configuration for storage.
@@ -243,6 +247,7 @@ impl Default for Builder<iroh_blobs::store::mem::Store> {
             addr_v6: DEFAULT_BIND_ADDR_V6,
             secret_key: SecretKey::generate(),
             blobs_store: Default::default(),
+            blobs_stores: Default::default(),
             keylog: false,
             relay_mode,
             dns_resolver: None,
@@ -256,6 +261,7 @@ impl Default for Builder<iroh_blobs::store::mem::Store> {
             gc_done_callback: None,
             blob_events: Default::default(),
             transport_config: None,
+            upload_ttl: DEFAULT_UPLOAD_TTL,
         }
     }
 }
@@ -279,6 +285,7 @@ impl<D: Map> Builder<D> {
             addr_v6: SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, DEFAULT_BIND_PORT + 1, 0, 0),
             secret_key: SecretKey::generate(),
             blobs_store,
+            blobs_stores: Default::default(),
             keylog: false,
             relay_mode,
             dns_resolver: None,
@@ -292,6 +299,7 @@ impl<D: Map> Builder<D> {
             gc_done_callback: None,
             blob_events: Default::default(),
             transport_config: None,
+            upload_ttl: DEFAULT_UPLOAD_TTL,
         }
     }
 }
@@ -310,10 +318,17 @@ where
     }
 
     /// Persist all node data in the provided directory.
+    ///
+    /// This replaces the default blob store, so it must be called before adding named blob
+    /// stores with [`Builder::add_blobs_store`].
     pub async fn persist(
         self,
         root: impl AsRef<Path>,
     ) -> Result<Builder<iroh_blobs::store::fs::Store>> {
+        anyhow::ensure!(
+            self.blobs_stores.is_empty(),
+            "named blob stores must be added after calling persist"
+        );
         let root = root.as_ref();
         let blob_dir = IrohPaths::BaoStoreDir.with_root(root);
 
@@ -339,6 +354,7 @@ where
             addr_v6: self.addr_v6,
             secret_key,
             blobs_store,
+            blobs_stores: Default::default(),
             keylog: self.keylog,
             rpc_endpoint: self.rpc_endpoint,
             rpc_addr: self.rpc_addr,
@@ -352,6 +368,7 @@ where
             gc_done_callback: self.gc_done_callback,
             blob_events: self.blob_events,
             transport_config: self.transport_config,
+            upload_ttl: self.upload_ttl,
         })
     }
 
@@ -395,6 +412,32 @@ where
         self
     }
 
+    /// Adds an additional, isolated blob store to the node under the given name.
+    ///
+    /// Next to the default store, a node can host any number of named stores.  Each named
+    /// store has its own tags, batches and garbage collection policy, see
+    /// [`BlobStoreOptions`].  Clients select a named store with
+    /// [`crate::client::blobs::Client::in_store`] and [`crate::client::tags::Client::in_store`].
+    ///
+    /// If [`BlobStoreOptions::provide`] is set, the store's content is served to other nodes on
+    /// the ALPN returned by [`blobs_store_alpn`], and tickets shared from this store refer to it
+    /// by name.
+    ///
+    /// Documents always use the default store.
+    ///
+    /// For a persistent node each store should use its own directory, e.g. by loading it with
+    /// [`iroh_blobs::store::fs::Store::load`].  Adding a store with a name that is already in
+    /// use replaces the previous store.
+    pub fn add_blobs_store(
+        mut self,
+        name: impl Into<String>,
+        store: D,
+        options: BlobStoreOptions,
+    ) -> Self {
+        self.blobs_stores.insert(name.into(), (store, options));
+        self
+    }
+
     /// Enables documents support on this node.
     pub fn enable_docs(mut self) -> Self {
         self.docs_storage = match self.storage {
@@ -506,6 +549,15 @@ where
         self
     }
 
+    /// Sets how long a resumable upload is kept without any activity.
+    ///
+    /// Uploads that are neither appended to nor queried for this long are removed, together
+    /// with their data. Defaults to one hour.
+    pub fn upload_ttl(mut self, ttl: Duration) -> Self {
+        self.upload_ttl = ttl;
+        self
+    }
+
     /// Skip verification of SSL certificates from relay servers
     ///
     /// May only be used in tests.
@@ -549,12 +601,20 @@ where
     /// Returns a [`ProtocolBuilder`], on which custom protocols can be registered with
     /// [`ProtocolBuilder::accept`]. To spawn the node, call [`ProtocolBuilder::spawn`].
     pub async fn build(self) -> Result<ProtocolBuilder<D>> {
-        // Clone the blob store to shutdown in case of error.
+        // Clone the blob stores to shutdown in case of error.
         let blobs_store = self.blobs_store.clone();
+        let blobs_stores: Vec<D> = self
+            .blobs_stores
+            .values()
+            .map(|(store, _)| store.clone())
+            .collect();
         match self.build_inner().await {
             Ok(node) => Ok(node),
             Err(err) => {
                 blobs_store.shutdown().await;
+                for store in blobs_stores {
+                    store.shutdown().await;
+                }
                 Err(err)
             }
         }
@@ -562,6 +622,10 @@ where
 
     async fn build_inner(self) -> Result<ProtocolBuilder<D>> {
         trace!("building node");
+        anyhow::ensure!(
+            self.blobs_stores.keys().all(|name| !name.is_empty()),
+            "blob store names must not be empty"
+        );
         let lp = LocalPool::new(local_pool::Config {
             panic_mode: PanicMode::LogAndContinue,
             ..Default::default()
@@ -666,6 +730,7 @@ where
             endpoint.clone(),
             gossip.clone(),
             downloader.clone(),
+            lp.handle().clone(),
         )
         .await?;
 
@@ -677,6 +742,42 @@ where
         let controller = quic_rpc::transport::boxed::Connection::new(controller);
         let client = crate::client::Iroh::new(quic_rpc::RpcClient::new(controller.clone()));
 
+        // Resumable uploads are written below this directory. Uploads do not survive a restart,
+        // so remove anything left over from a previous run.
+        let uploads_dir = match self.storage {
+            StorageConfig::Persistent(ref root) => {
+                let dir = IrohPaths::Uploads.with_root(root);
+                if dir.exists() {
+                    tokio::fs::remove_dir_all(&dir).await?;
+                }
+                tokio::fs::create_dir_all(&dir).await?;
+                Some(dir)
+            }
+            StorageConfig::Mem => None,
+        };
+
+        // Set up the named blob stores, each with its own downloader.
+        let blob_stores = self
+            .blobs_stores
+            .into_iter()
+            .map(|(name, (store, options))| {
+                let downloader = Downloader::new(store.clone(), endpoint.clone(), lp.clone());
+                let blobs = BlobsProtocol::new_with_events(
+                    store,
+                    lp.handle().clone(),
+                    self.blob_events.clone(),
+                    downloader,
+                    BlobUploads::new(uploads_dir.as_deref(), self.upload_ttl)?,
+                );
+                let store = NamedBlobStore {
+                    blobs: Arc::new(blobs),
+                    options,
+                };
+                anyhow::Ok((name, store))
+            })
+            .collect::<Result<_>>()?;
+        let uploads = BlobUploads::new(uploads_dir.as_deref(), self.upload_ttl)?;
+
         let inner = Arc::new(NodeInner {
             rpc_addr: self.rpc_addr,
             db: Default::default(),
@@ -684,6 +785,7 @@ where
             client,
             cancel_token: CancellationToken::new(),
             local_pool_handle: lp.handle().clone(),
+            blob_stores,
         });
 
         let protocol_builder = ProtocolBuilder {
@@ -700,6 +802,7 @@ where
         let protocol_builder = protocol_builder.register_iroh_protocols(
             self.blob_events,
             self.blobs_store,
+            uploads,
             gossip,
             downloader,
             docs,
@@ -815,6 +918,7 @@ impl<D: iroh_blobs::store::Store> ProtocolBuilder<D> {
         mut self,
         blob_events: EventSender,
         store: D,
+        uploads: BlobUploads,
         gossip: Gossip,
         downloader: Downloader,
         docs: Option<DocsEngine>,
@@ -825,15 +929,30 @@ impl<D: iroh_blobs::store::Store> ProtocolBuilder<D> {
             self.local_pool_handle().clone(),
             blob_events,
             downloader,
+            uploads,
         );
         self = self.accept(iroh_blobs::protocol::ALPN.to_vec(), Arc::new(blobs_proto));
 
+        // Register the named blob stores that are provided to other nodes.
+        let named_stores: Vec<_> = self
+            .inner
+            .blob_stores
+            .iter()
+            .filter(|(_, store)| store.options.provide)
+            .map(|(name, store)| (blobs_store_alpn(name), store.blobs.clone()))
+            .collect();
+        for (alpn, blobs) in named_stores {
+            self = self.accept(alpn, blobs);
+        }
+
         // Register gossip.
         self = self.accept(GOSSIP_ALPN.to_vec(), Arc::new(gossip));
 
-        // Register docs, if enabled.
+        // Register docs, if enabled. Peers speaking version 1 of the protocol are served too.
         if let Some(docs) = docs {
-            self = self.accept(DOCS_ALPN.to_vec(), Arc::new(docs));
+            let docs = Arc::new(docs);
+            self = self.accept(DOCS_ALPN.to_vec(), docs.clone());
+            self = self.accept(DOCS_ALPN_V1.to_vec(), docs);
         }
 
         self
@@ -909,6 +1028,28 @@ impl<D: iroh_blobs::store::Store> ProtocolBuilder<D> {
     }
 }
 
+/// Options for a named blob store, see [`Builder::add_blobs_store`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlobStoreOptions {
+    /// The garbage collection policy for this store.
+    ///
+    /// Defaults to [`GcPolicy::Disabled`].
+    pub gc_policy: GcPolicy,
+    /// Whether to serve the content of this store to other nodes.
+    ///
+    /// Defaults to `false`.
+    pub provide: bool,
+}
+
+impl Default for BlobStoreOptions {
+    fn default() -> Self {
+        Self {
+            gc_policy: GcPolicy::Disabled,
+            provide: false,
+        }
+    }
+}
+
 /// Policy for garbage collection.
 // Please note that this is documented in the `iroh.computer` repository under
 // `src/app/docs/reference/config/page.mdx`.  Any changes to this need to be updated there.
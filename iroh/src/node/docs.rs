@@ -2,7 +2,7 @@ use std::{ops::Deref, sync::Arc};
 
 use anyhow::Result;
 use futures_lite::future::Boxed as BoxedFuture;
-use iroh_blobs::downloader::Downloader;
+use iroh_blobs::{downloader::Downloader, util::local_pool::LocalPoolHandle};
 use iroh_gossip::net::Gossip;
 
 use iroh_docs::engine::{DefaultAuthorStorage, Engine};
@@ -22,18 +22,20 @@ impl DocsEngine {
         endpoint: Endpoint,
         gossip: Gossip,
         downloader: Downloader,
+        local_pool: LocalPoolHandle,
     ) -> anyhow::Result<Option<Self>> {
         let docs_store = match storage {
             DocsStorage::Disabled => return Ok(None),
             DocsStorage::Memory => iroh_docs::store::fs::Store::memory(),
             DocsStorage::Persistent(path) => iroh_docs::store::fs::Store::persistent(path)?,
         };
-        let engine = Engine::spawn(
+        let engine = Engine::spawn_with_local_pool(
             endpoint,
             gossip,
             docs_store,
             blobs_store,
             downloader,
+            local_pool,
             default_author_storage,
         )
         .await?;
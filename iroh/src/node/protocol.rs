@@ -1,4 +1,9 @@
-use std::{any::Any, collections::BTreeMap, fmt, sync::Arc};
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashSet},
+    fmt,
+    sync::Arc,
+};
 
 use anyhow::{anyhow, Result};
 use futures_lite::future::Boxed as BoxedFuture;
@@ -9,7 +14,7 @@ use iroh_blobs::{
         db::{DownloadProgress, GetState},
         Stats,
     },
-    provider::EventSender,
+    provider::{CustomEventSender, Event as ProviderEvent, EventSender},
     util::{
         local_pool::LocalPoolHandle,
         progress::{AsyncChannelProgressSender, ProgressSender},
@@ -18,10 +23,12 @@ use iroh_blobs::{
     HashAndFormat, TempTag,
 };
 use iroh_net::{endpoint::Connecting, Endpoint, NodeAddr};
+use tokio::sync::broadcast;
 use tracing::{debug, warn};
 
 use crate::{
     client::blobs::DownloadMode,
+    node::uploads::BlobUploads,
     rpc_protocol::blobs::{BatchId, DownloadRequest as BlobDownloadRequest},
 };
 
@@ -90,8 +97,16 @@ impl ProtocolMap {
     /// Shuts down all protocol handlers.
     ///
     /// Calls and awaits [`ProtocolHandler::shutdown`] for all registered handlers concurrently.
+    /// A handler registered for several ALPNs is shut down once.
     pub(super) async fn shutdown(&self) {
-        let handlers = self.0.values().cloned().map(ProtocolHandler::shutdown);
+        let mut seen = HashSet::new();
+        let handlers: Vec<_> = self
+            .0
+            .values()
+            .filter(|handler| seen.insert(Arc::as_ptr(handler) as *const () as usize))
+            .cloned()
+            .map(ProtocolHandler::shutdown)
+            .collect();
         join_all(handlers).await;
     }
 }
@@ -101,13 +116,54 @@ pub(crate) struct BlobsProtocol<S> {
     rt: LocalPoolHandle,
     store: S,
     events: EventSender,
+    provider_events: broadcast::Sender<ProviderEvent>,
     downloader: Downloader,
     batches: tokio::sync::Mutex<BlobBatches>,
+    uploads: BlobUploads,
 }
 
 /// Name used for logging when new node addresses are added from gossip.
 const BLOB_DOWNLOAD_SOURCE_NAME: &str = "blob_download";
 
+/// Capacity of the channel on which provider events are broadcast to RPC subscribers.
+const PROVIDER_EVENTS_CAP: usize = 1024;
+
+/// Event sender that forwards provider events to the event sender configured by the user, and
+/// broadcasts them to the subscribers of [`BlobsProtocol::subscribe_provider_events`].
+#[derive(Debug)]
+struct BroadcastEventSender {
+    inner: EventSender,
+    sender: broadcast::Sender<ProviderEvent>,
+}
+
+impl BroadcastEventSender {
+    fn broadcast(&self, event: &ProviderEvent) {
+        if self.sender.receiver_count() > 0 {
+            self.sender.send(event.clone()).ok();
+        }
+    }
+}
+
+impl CustomEventSender for BroadcastEventSender {
+    fn send(&self, event: ProviderEvent) -> BoxedFuture<()> {
+        self.broadcast(&event);
+        let inner = self.inner.clone();
+        Box::pin(async move { inner.send(|| event).await })
+    }
+
+    fn try_send(&self, event: ProviderEvent) {
+        self.broadcast(&event);
+        self.inner.try_send(|| event);
+    }
+}
+
+/// Returns the ALPN on which a named blob store is provided to other nodes.
+///
+/// The default blob store is provided on [`iroh_blobs::protocol::ALPN`].
+pub fn blobs_store_alpn(store: &str) -> Vec<u8> {
+    [iroh_blobs::protocol::ALPN, b"/", store.as_bytes()].concat()
+}
+
 /// Keeps track of all the currently active batch operations of the blobs api.
 #[derive(Debug, Default)]
 pub(crate) struct BlobBatches {
@@ -165,13 +221,21 @@ impl<S: iroh_blobs::store::Store> BlobsProtocol<S> {
         rt: LocalPoolHandle,
         events: EventSender,
         downloader: Downloader,
+        uploads: BlobUploads,
     ) -> Self {
+        let (provider_events, _) = broadcast::channel(PROVIDER_EVENTS_CAP);
+        let events = EventSender::from(BroadcastEventSender {
+            inner: events,
+            sender: provider_events.clone(),
+        });
         Self {
             rt,
             store,
             events,
+            provider_events,
             downloader,
             batches: Default::default(),
+            uploads,
         }
     }
 
@@ -179,6 +243,15 @@ impl<S: iroh_blobs::store::Store> BlobsProtocol<S> {
         &self.store
     }
 
+    /// Subscribe to the events emitted while providing blobs from this store to other nodes.
+    pub(crate) fn subscribe_provider_events(&self) -> broadcast::Receiver<ProviderEvent> {
+        self.provider_events.subscribe()
+    }
+
+    pub(crate) fn uploads(&self) -> &BlobUploads {
+        &self.uploads
+    }
+
     pub(crate) async fn batches(&self) -> tokio::sync::MutexGuard<'_, BlobBatches> {
         self.batches.lock().await
     }
@@ -195,17 +268,37 @@ impl<S: iroh_blobs::store::Store> BlobsProtocol<S> {
             nodes,
             tag,
             mode,
+            store: _,
+            remote_store,
         } = req;
         let hash_and_format = HashAndFormat { hash, format };
         let temp_tag = self.store.temp_tag(hash_and_format);
-        let stats = match mode {
-            DownloadMode::Queued => {
+        let stats = match (mode, remote_store) {
+            (DownloadMode::Queued, None) => {
                 self.download_queued(endpoint, hash_and_format, nodes, progress.clone())
                     .await?
             }
-            DownloadMode::Direct => {
-                self.download_direct_from_nodes(endpoint, hash_and_format, nodes, progress.clone())
-                    .await?
+            (DownloadMode::Direct, None) => {
+                self.download_direct_from_nodes(
+                    endpoint,
+                    hash_and_format,
+                    nodes,
+                    iroh_blobs::protocol::ALPN,
+                    progress.clone(),
+                )
+                .await?
+            }
+            // The downloader only dials the default blobs ALPN, so downloads from named
+            // remote stores are always direct.
+            (_, Some(remote_store)) => {
+                self.download_direct_from_nodes(
+                    endpoint,
+                    hash_and_format,
+                    nodes,
+                    &blobs_store_alpn(&remote_store),
+                    progress.clone(),
+                )
+                .await?
             }
         };
 
@@ -253,6 +346,7 @@ impl<S: iroh_blobs::store::Store> BlobsProtocol<S> {
         endpoint: Endpoint,
         hash_and_format: HashAndFormat,
         nodes: Vec<NodeAddr>,
+        alpn: &[u8],
         progress: AsyncChannelProgressSender<DownloadProgress>,
     ) -> Result<Stats> {
         let mut last_err = None;
@@ -282,7 +376,7 @@ impl<S: iroh_blobs::store::Store> BlobsProtocol<S> {
                                     );
                                     continue 'inner;
                                 }
-                                match endpoint.connect(node, iroh_blobs::protocol::ALPN).await {
+                                match endpoint.connect(node, alpn).await {
                                     Ok(conn) => break 'inner (conn, node_id),
                                     Err(err) => {
                                         debug!(
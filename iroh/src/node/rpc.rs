@@ -19,8 +19,8 @@ use iroh_blobs::util::progress::{AsyncChannelProgressSender, ProgressSender};
 use iroh_blobs::util::SetTagOption;
 use iroh_blobs::{
     provider::AddProgress,
-    store::{Store as BaoStore, ValidateProgress},
-    HashAndFormat,
+    store::{EntryStatus, Event as StoreEvent, Store as BaoStore, ValidateProgress},
+    Hash, HashAndFormat,
 };
 use iroh_blobs::{BlobFormat, Tag};
 use iroh_docs::net::DOCS_ALPN;
@@ -29,13 +29,14 @@ use iroh_io::AsyncSliceReader;
 use iroh_net::relay::RelayUrl;
 use iroh_net::{NodeAddr, NodeId};
 use quic_rpc::server::{RpcChannel, RpcServerError};
+use tokio::sync::broadcast::error::RecvError;
 use tokio::task::JoinSet;
 use tokio_util::either::Either;
 use tracing::{debug, info, warn};
 
 use crate::client::blobs::BlobStatus;
 use crate::client::{
-    blobs::{BlobInfo, IncompleteBlobInfo, WrapOption},
+    blobs::{BlobEvent, BlobInfo, IncompleteBlobInfo, ProviderEvent, WrapOption},
     tags::TagInfo,
     NodeStatus,
 };
@@ -43,7 +44,11 @@ use crate::node::{docs::DocsEngine, protocol::BlobsProtocol, NodeInner};
 use crate::rpc_protocol::blobs::{
     BatchAddPathRequest, BatchAddPathResponse, BatchAddStreamRequest, BatchAddStreamResponse,
     BatchAddStreamUpdate, BatchCreateRequest, BatchCreateResponse, BatchCreateTempTagRequest,
-    BatchUpdate, BlobStatusRequest, BlobStatusResponse,
+    BatchUpdate, BlobStatusRequest, BlobStatusResponse, ProviderEventsRequest,
+    ProviderEventsResponse, SubscribeRequest, SubscribeResponse, UploadAbortRequest,
+    UploadAppendRequest, UploadAppendResponse, UploadAppendUpdate, UploadCreateRequest,
+    UploadCreateResponse, UploadFinishRequest, UploadFinishResponse, UploadStatusRequest,
+    UploadStatusResponse,
 };
 use crate::rpc_protocol::tags::SyncMode;
 use crate::rpc_protocol::{
@@ -105,6 +110,20 @@ impl<D: BaoStore> Handler<D> {
             .expect("missing blobs")
     }
 
+    /// Returns the blobs protocol for the named store, or for the default store if `store` is
+    /// `None`.
+    fn blobs_in(&self, store: Option<&str>) -> Result<Arc<BlobsProtocol<D>>> {
+        match store {
+            None => Ok(self.blobs()),
+            Some(name) => self
+                .inner
+                .blob_stores
+                .get(name)
+                .map(|store| store.blobs.clone())
+                .ok_or_else(|| anyhow!("unknown blob store: {name}")),
+        }
+    }
+
     fn blobs_store(&self) -> D {
         self.blobs().store().clone()
     }
@@ -216,6 +235,23 @@ impl<D: BaoStore> Handler<D> {
             AddStream(msg) => chan.bidi_streaming(msg, self, Self::blob_add_stream).await,
             AddStreamUpdate(_msg) => Err(RpcServerError::UnexpectedUpdateMessage),
             BlobStatus(msg) => chan.rpc(msg, self, Self::blob_status).await,
+            Subscribe(msg) => chan.server_streaming(msg, self, Self::blob_subscribe).await,
+            ProviderEvents(msg) => {
+                chan.server_streaming(msg, self, Self::blob_provider_events)
+                    .await
+            }
+            UploadCreate(msg) => chan.rpc(msg, self, Self::blob_upload_create).await,
+            UploadStatus(msg) => chan.rpc(msg, self, Self::blob_upload_status).await,
+            UploadAppend(msg) => {
+                chan.bidi_streaming(msg, self, Self::blob_upload_append)
+                    .await
+            }
+            UploadAppendUpdate(_msg) => Err(RpcServerError::UnexpectedUpdateMessage),
+            UploadFinish(msg) => {
+                chan.server_streaming(msg, self, Self::blob_upload_finish)
+                    .await
+            }
+            UploadAbort(msg) => chan.rpc(msg, self, Self::blob_upload_abort).await,
             BatchCreate(msg) => chan.bidi_streaming(msg, self, Self::batch_create).await,
             BatchUpdate(_) => Err(RpcServerError::UnexpectedStartMessage),
             BatchAddStream(msg) => chan.bidi_streaming(msg, self, Self::batch_add_stream).await,
@@ -320,6 +356,36 @@ impl<D: BaoStore> Handler<D> {
                 })
                 .await
             }
+            KeystoreStatus(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.keystore_status(req).await })
+                })
+                .await
+            }
+            EnableKeystore(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.keystore_enable(req).await })
+                })
+                .await
+            }
+            DisableKeystore(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.keystore_disable(req).await })
+                })
+                .await
+            }
+            Unlock(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.keystore_unlock(req).await })
+                })
+                .await
+            }
+            Lock(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.keystore_lock(req).await })
+                })
+                .await
+            }
         }
     }
 
@@ -393,6 +459,13 @@ impl<D: BaoStore> Handler<D> {
                 })
                 .await
             }
+            Batch(msg) => {
+                let blobs_store = self.blobs_store();
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.doc_batch(&blobs_store, req).await })
+                })
+                .await
+            }
             SetHash(msg) => {
                 chan.rpc(msg, self, |handler, req| {
                     handler.with_docs(|docs| async move { docs.doc_set_hash(req).await })
@@ -405,6 +478,16 @@ impl<D: BaoStore> Handler<D> {
                 })
                 .await
             }
+            GetManyContent(msg) => {
+                let blobs_store = self.blobs_store();
+                chan.server_streaming(msg, self, |handler, req| {
+                    let local_pool = handler.local_pool_handle();
+                    handler.with_docs_stream(|docs| {
+                        docs.doc_get_many_content(blobs_store, local_pool, req)
+                    })
+                })
+                .await
+            }
             GetExact(msg) => {
                 chan.rpc(msg, self, |handler, req| {
                     handler.with_docs(|docs| async move { docs.doc_get_exact(req).await })
@@ -449,12 +532,237 @@ impl<D: BaoStore> Handler<D> {
                 })
                 .await
             }
+            SetHistoryPolicy(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.doc_set_history_policy(req).await })
+                })
+                .await
+            }
+            GetHistoryPolicy(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.doc_get_history_policy(req).await })
+                })
+                .await
+            }
+            SetTombstonePolicy(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler
+                        .with_docs(|docs| async move { docs.doc_set_tombstone_policy(req).await })
+                })
+                .await
+            }
+            GetTombstonePolicy(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler
+                        .with_docs(|docs| async move { docs.doc_get_tombstone_policy(req).await })
+                })
+                .await
+            }
+            SetSyncPolicy(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.doc_set_sync_policy(req).await })
+                })
+                .await
+            }
+            GetSyncPolicy(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.doc_get_sync_policy(req).await })
+                })
+                .await
+            }
+            SetInlineLimit(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.doc_set_inline_limit(req).await })
+                })
+                .await
+            }
+            GetInlineLimit(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.doc_get_inline_limit(req).await })
+                })
+                .await
+            }
+            GetInlineContent(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.doc_get_inline_content(req).await })
+                })
+                .await
+            }
+            CreateIndex(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.doc_create_index(req).await })
+                })
+                .await
+            }
+            RemoveIndex(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.doc_remove_index(req).await })
+                })
+                .await
+            }
+            ListIndexes(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.doc_list_indexes(req).await })
+                })
+                .await
+            }
+            CompactTombstones(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.doc_compact_tombstones(req).await })
+                })
+                .await
+            }
+            SetClockMode(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.doc_set_clock_mode(req).await })
+                })
+                .await
+            }
+            GetClockMode(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.doc_get_clock_mode(req).await })
+                })
+                .await
+            }
+            SetSyncInterest(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.doc_set_sync_interest(req).await })
+                })
+                .await
+            }
+            GetSyncInterest(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.doc_get_sync_interest(req).await })
+                })
+                .await
+            }
             GetSyncPeers(msg) => {
                 chan.rpc(msg, self, |handler, req| {
                     handler.with_docs(|docs| async move { docs.doc_get_sync_peers(req).await })
                 })
                 .await
             }
+            Delegate(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.doc_delegate(req).await })
+                })
+                .await
+            }
+            AddDelegation(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.doc_add_delegation(req).await })
+                })
+                .await
+            }
+            ListDelegations(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.doc_list_delegations(req).await })
+                })
+                .await
+            }
+            GetAccessPolicy(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.doc_get_access_policy(req).await })
+                })
+                .await
+            }
+            SetAccessPolicy(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.doc_set_access_policy(req).await })
+                })
+                .await
+            }
+            CreateReadToken(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.doc_create_read_token(req).await })
+                })
+                .await
+            }
+            SetReadToken(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.doc_set_read_token(req).await })
+                })
+                .await
+            }
+            SetEncryptionKey(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.doc_set_encryption_key(req).await })
+                })
+                .await
+            }
+            GetEncryptionKey(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.doc_get_encryption_key(req).await })
+                })
+                .await
+            }
+            CreateInvite(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.doc_create_invite(req).await })
+                })
+                .await
+            }
+            ImportInvite(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.doc_import_invite(req).await })
+                })
+                .await
+            }
+            ListRedemptions(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.doc_list_redemptions(req).await })
+                })
+                .await
+            }
+            Rotate(msg) => {
+                let blobs_store = self.blobs_store();
+                chan.rpc(msg, self, |handler, req| {
+                    handler
+                        .with_docs(|docs| async move { docs.doc_rotate(&blobs_store, req).await })
+                })
+                .await
+            }
+            FollowMigration(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move { docs.doc_follow_migration(req).await })
+                })
+                .await
+            }
+            SetTrustedMarkerAuthors(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move {
+                        docs.doc_set_trusted_marker_authors(req).await
+                    })
+                })
+                .await
+            }
+            GetTrustedMarkerAuthors(msg) => {
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move {
+                        docs.doc_get_trusted_marker_authors(req).await
+                    })
+                })
+                .await
+            }
+            ExportBundle(msg) => {
+                let blobs_store = self.blobs_store();
+                let local_pool = self.local_pool_handle();
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move {
+                        docs.doc_export_bundle(&blobs_store, &local_pool, req).await
+                    })
+                })
+                .await
+            }
+            ImportBundle(msg) => {
+                let blobs_store = self.blobs_store();
+                chan.rpc(msg, self, |handler, req| {
+                    handler.with_docs(|docs| async move {
+                        docs.doc_import_bundle(&blobs_store, req).await
+                    })
+                })
+                .await
+            }
         }
     }
 
@@ -481,7 +789,7 @@ impl<D: BaoStore> Handler<D> {
     }
 
     async fn blob_status(self, msg: BlobStatusRequest) -> RpcResult<BlobStatusResponse> {
-        let blobs = self.blobs();
+        let blobs = self.blobs_in(msg.store.as_deref())?;
         let entry = blobs.store().get(&msg.hash).await?;
         Ok(BlobStatusResponse(match entry {
             Some(entry) => {
@@ -497,10 +805,99 @@ impl<D: BaoStore> Handler<D> {
         }))
     }
 
-    async fn blob_list_impl(self, co: &Co<RpcResult<BlobInfo>>) -> io::Result<()> {
+    fn blob_subscribe(
+        self,
+        msg: SubscribeRequest,
+    ) -> impl Stream<Item = RpcResult<SubscribeResponse>> + Send + 'static {
+        let filter = msg.hash;
+        Gen::new(move |co| async move {
+            let blobs = match self.blobs_in(msg.store.as_deref()) {
+                Ok(blobs) => blobs,
+                Err(err) => {
+                    co.yield_(Err(err.into())).await;
+                    return;
+                }
+            };
+            // subscribe before looking at the current status, so that no change is missed
+            let mut events = blobs.store().subscribe();
+            if let Some(hash) = filter {
+                match current_blob_event(blobs.store(), hash).await {
+                    Ok(Some(event)) => co.yield_(Ok(SubscribeResponse(event))).await,
+                    Ok(None) => {}
+                    Err(err) => {
+                        co.yield_(Err(anyhow::Error::from(err).into())).await;
+                        return;
+                    }
+                }
+            }
+            loop {
+                let event = match events.recv().await {
+                    Ok(StoreEvent::EntryPartial { hash }) => BlobEvent::Partial { hash },
+                    Ok(StoreEvent::EntryComplete { hash }) => BlobEvent::Complete { hash },
+                    Ok(_) => continue,
+                    Err(RecvError::Closed) => break,
+                    Err(RecvError::Lagged(n)) => {
+                        let Some(hash) = filter else {
+                            let err = anyhow!("subscriber lagged behind, {n} events were dropped");
+                            co.yield_(Err(err.into())).await;
+                            break;
+                        };
+                        // for a single blob, we can catch up by looking at its current status
+                        match current_blob_event(blobs.store(), hash).await {
+                            Ok(Some(event)) => event,
+                            Ok(None) => continue,
+                            Err(err) => {
+                                co.yield_(Err(anyhow::Error::from(err).into())).await;
+                                break;
+                            }
+                        }
+                    }
+                };
+                if filter.map_or(true, |hash| hash == event.hash()) {
+                    co.yield_(Ok(SubscribeResponse(event))).await;
+                }
+            }
+        })
+    }
+
+    fn blob_provider_events(
+        self,
+        msg: ProviderEventsRequest,
+    ) -> impl Stream<Item = RpcResult<ProviderEventsResponse>> + Send + 'static {
+        Gen::new(move |co| async move {
+            let blobs = match self.blobs_in(msg.store.as_deref()) {
+                Ok(blobs) => blobs,
+                Err(err) => {
+                    co.yield_(Err(err.into())).await;
+                    return;
+                }
+            };
+            let mut events = blobs.subscribe_provider_events();
+            loop {
+                let event = match events.recv().await {
+                    Ok(event) => match ProviderEvent::try_from(event) {
+                        Ok(event) => event,
+                        Err(event) => {
+                            debug!(?event, "skipping unknown provider event");
+                            continue;
+                        }
+                    },
+                    Err(RecvError::Closed) => break,
+                    Err(RecvError::Lagged(missed)) => ProviderEvent::Lagged { missed },
+                };
+                co.yield_(Ok(ProviderEventsResponse(event))).await;
+            }
+        })
+    }
+
+    async fn blob_list_impl(
+        self,
+        msg: ListRequest,
+        co: &Co<RpcResult<BlobInfo>>,
+    ) -> anyhow::Result<()> {
         use bao_tree::io::fsm::Outboard;
 
-        let blobs = self.blobs();
+        let blobs = self.blobs_in(msg.store.as_deref())?;
         let db = blobs.store();
         for blob in db.blobs().await? {
             let blob = blob?;
@@ -517,9 +914,10 @@ impl<D: BaoStore> Handler<D> {
 
     async fn blob_list_incomplete_impl(
         self,
+        msg: ListIncompleteRequest,
         co: &Co<RpcResult<IncompleteBlobInfo>>,
-    ) -> io::Result<()> {
-        let blobs = self.blobs();
+    ) -> anyhow::Result<()> {
+        let blobs = self.blobs_in(msg.store.as_deref())?;
         let db = blobs.store();
         for hash in db.partial_blobs().await? {
             let hash = hash?;
@@ -543,10 +941,10 @@ impl<D: BaoStore> Handler<D> {
 
     fn blob_list(
         self,
-        _msg: ListRequest,
+        msg: ListRequest,
     ) -> impl Stream<Item = RpcResult<BlobInfo>> + Send + 'static {
         Gen::new(|co| async move {
-            if let Err(e) = self.blob_list_impl(&co).await {
+            if let Err(e) = self.blob_list_impl(msg, &co).await {
                 co.yield_(Err(e.into())).await;
             }
         })
@@ -554,29 +952,39 @@ impl<D: BaoStore> Handler<D> {
 
     fn blob_list_incomplete(
         self,
-        _msg: ListIncompleteRequest,
+        msg: ListIncompleteRequest,
     ) -> impl Stream<Item = RpcResult<IncompleteBlobInfo>> + Send + 'static {
         Gen::new(move |co| async move {
-            if let Err(e) = self.blob_list_incomplete_impl(&co).await {
+            if let Err(e) = self.blob_list_incomplete_impl(msg, &co).await {
                 co.yield_(Err(e.into())).await;
             }
         })
     }
 
     async fn blob_delete_tag(self, msg: TagDeleteRequest) -> RpcResult<()> {
-        self.blobs_store().set_tag(msg.name, None).await?;
+        let blobs = self.blobs_in(msg.store.as_deref())?;
+        blobs.store().set_tag(msg.name, None).await?;
         Ok(())
     }
 
     async fn blob_delete_blob(self, msg: DeleteRequest) -> RpcResult<()> {
-        self.blobs_store().delete(vec![msg.hash]).await?;
+        let blobs = self.blobs_in(msg.store.as_deref())?;
+        blobs.store().delete(vec![msg.hash]).await?;
         Ok(())
     }
 
     fn blob_list_tags(self, msg: ListTagsRequest) -> impl Stream<Item = TagInfo> + Send + 'static {
         tracing::info!("blob_list_tags");
-        let blobs = self.blobs();
+        let blobs = self.blobs_in(msg.store.as_deref());
         Gen::new(|co| async move {
+            // The tags stream has no error variant, so an unknown store lists no tags.
+            let blobs = match blobs {
+                Ok(blobs) => blobs,
+                Err(err) => {
+                    warn!("listing tags failed: {err:#}");
+                    return;
+                }
+            };
             let tags = blobs.store().tags().await.unwrap();
             #[allow(clippy::manual_flatten)]
             for item in tags {
@@ -596,13 +1004,17 @@ impl<D: BaoStore> Handler<D> {
     ) -> impl Stream<Item = ValidateProgress> + Send + 'static {
         let (tx, rx) = async_channel::bounded(1);
         let tx2 = tx.clone();
-        let blobs = self.blobs();
+        let blobs = self.blobs_in(msg.store.as_deref());
         tokio::task::spawn(async move {
-            if let Err(e) = blobs
-                .store()
-                .validate(msg.repair, AsyncChannelProgressSender::new(tx).boxed())
-                .await
-            {
+            let res = match blobs {
+                Ok(blobs) => blobs
+                    .store()
+                    .validate(msg.repair, AsyncChannelProgressSender::new(tx).boxed())
+                    .await
+                    .map_err(anyhow::Error::from),
+                Err(err) => Err(err),
+            };
+            if let Err(e) = res {
                 tx2.send(ValidateProgress::Abort(e.into())).await.ok();
             }
         });
@@ -616,13 +1028,17 @@ impl<D: BaoStore> Handler<D> {
     ) -> impl Stream<Item = ConsistencyCheckProgress> + Send + 'static {
         let (tx, rx) = async_channel::bounded(1);
         let tx2 = tx.clone();
-        let blobs = self.blobs();
+        let blobs = self.blobs_in(msg.store.as_deref());
         tokio::task::spawn(async move {
-            if let Err(e) = blobs
-                .store()
-                .consistency_check(msg.repair, AsyncChannelProgressSender::new(tx).boxed())
-                .await
-            {
+            let res = match blobs {
+                Ok(blobs) => blobs
+                    .store()
+                    .consistency_check(msg.repair, AsyncChannelProgressSender::new(tx).boxed())
+                    .await
+                    .map_err(anyhow::Error::from),
+                Err(err) => Err(err),
+            };
+            if let Err(e) = res {
                 tx2.send(ConsistencyCheckProgress::Abort(e.into()))
                     .await
                     .ok();
@@ -774,16 +1190,14 @@ impl<D: BaoStore> Handler<D> {
         let endpoint = self.inner.endpoint.clone();
         let progress = AsyncChannelProgressSender::new(sender);
 
-        let blobs_protocol = self
-            .protocols
-            .get_typed::<BlobsProtocol<D>>(iroh_blobs::protocol::ALPN)
-            .expect("missing blobs");
+        let blobs_protocol = self.blobs_in(msg.store.as_deref());
 
         self.local_pool_handle().spawn_detached(move || async move {
-            if let Err(err) = blobs_protocol
-                .download(endpoint, msg, progress.clone())
-                .await
-            {
+            let res = match blobs_protocol {
+                Ok(blobs) => blobs.download(endpoint, msg, progress.clone()).await,
+                Err(err) => Err(err),
+            };
+            if let Err(err) = res {
                 progress
                     .send(DownloadProgress::Abort(err.into()))
                     .await
@@ -798,15 +1212,20 @@ impl<D: BaoStore> Handler<D> {
         let (tx, rx) = async_channel::bounded(1024);
         let progress = AsyncChannelProgressSender::new(tx);
         self.local_pool_handle().spawn_detached(move || async move {
-            let res = iroh_blobs::export::export(
-                self.blobs().store(),
-                msg.hash,
-                msg.path,
-                msg.format,
-                msg.mode,
-                progress.clone(),
-            )
-            .await;
+            let res = match self.blobs_in(msg.store.as_deref()) {
+                Ok(blobs) => {
+                    iroh_blobs::export::export(
+                        blobs.store(),
+                        msg.hash,
+                        msg.path,
+                        msg.format,
+                        msg.mode,
+                        progress.clone(),
+                    )
+                    .await
+                }
+                Err(err) => Err(err),
+            };
             match res {
                 Ok(()) => progress.send(ExportProgress::AllDone).await.ok(),
                 Err(err) => progress.send(ExportProgress::Abort(err.into())).await.ok(),
@@ -823,7 +1242,7 @@ impl<D: BaoStore> Handler<D> {
         use iroh_blobs::store::ImportMode;
         use std::collections::BTreeMap;
 
-        let blobs = self.blobs();
+        let blobs = self.blobs_in(msg.store.as_deref())?;
         let progress = AsyncChannelProgressSender::new(progress);
         let names = Arc::new(Mutex::new(BTreeMap::new()));
         // convert import progress to provide progress
@@ -847,6 +1266,7 @@ impl<D: BaoStore> Handler<D> {
             path: root,
             in_place,
             tag,
+            store: _,
         } = msg;
         // Check that the path is absolute and exists.
         anyhow::ensure!(root.is_absolute(), "path must be absolute");
@@ -869,7 +1289,6 @@ impl<D: BaoStore> Handler<D> {
         let temp_tag = if create_collection {
             // import all files below root recursively
             let data_sources = crate::util::fs::scan_path(root, wrap)?;
-            let blobs = self.blobs();
 
             const IO_PARALLELISM: usize = 4;
             let result: Vec<_> = futures_lite::stream::iter(data_sources)
@@ -987,7 +1406,7 @@ impl<D: BaoStore> Handler<D> {
     }
 
     async fn tags_set(self, msg: tags::SetRequest) -> RpcResult<()> {
-        let blobs = self.blobs();
+        let blobs = self.blobs_in(msg.store.as_deref())?;
         blobs.store().set_tag(msg.name, msg.value).await?;
         if let SyncMode::Full = msg.sync {
             blobs.store().sync().await?;
@@ -1001,7 +1420,7 @@ impl<D: BaoStore> Handler<D> {
     }
 
     async fn tags_create(self, msg: tags::CreateRequest) -> RpcResult<Tag> {
-        let blobs = self.blobs();
+        let blobs = self.blobs_in(msg.store.as_deref())?;
         let tag = blobs.store().create_tag(msg.value).await?;
         if let SyncMode::Full = msg.sync {
             blobs.store().sync().await?;
@@ -1025,7 +1444,7 @@ impl<D: BaoStore> Handler<D> {
     }
 
     async fn batch_create_temp_tag(self, msg: BatchCreateTempTagRequest) -> RpcResult<()> {
-        let blobs = self.blobs();
+        let blobs = self.blobs_in(msg.store.as_deref())?;
         let tag = blobs.store().temp_tag(msg.content);
         blobs.batches().await.store(msg.batch, tag);
         Ok(())
@@ -1070,7 +1489,7 @@ impl<D: BaoStore> Handler<D> {
         stream: impl Stream<Item = BatchAddStreamUpdate> + Send + Unpin + 'static,
         progress: async_channel::Sender<BatchAddStreamResponse>,
     ) -> anyhow::Result<()> {
-        let blobs = self.blobs();
+        let blobs = self.blobs_in(msg.store.as_deref())?;
         let progress = AsyncChannelProgressSender::new(progress);
 
         let stream = stream.map(|item| match item {
@@ -1118,6 +1537,7 @@ impl<D: BaoStore> Handler<D> {
             import_mode,
             format,
             batch,
+            store,
         } = msg;
         // Check that the path is absolute and exists.
         anyhow::ensure!(root.is_absolute(), "path must be absolute");
@@ -1126,7 +1546,7 @@ impl<D: BaoStore> Handler<D> {
             "trying to add missing path: {}",
             root.display()
         );
-        let blobs = self.blobs();
+        let blobs = self.blobs_in(store.as_deref())?;
         let (tag, _) = blobs
             .store()
             .import_file(root, import_mode, format, import_progress)
@@ -1186,7 +1606,7 @@ impl<D: BaoStore> Handler<D> {
             ImportProgress::OutboardDone { hash, id } => Some(AddProgress::Done { hash, id }),
             _ => None,
         });
-        let blobs = self.blobs();
+        let blobs = self.blobs_in(msg.store.as_deref())?;
         let (temp_tag, _len) = blobs
             .store()
             .import_stream(stream, BlobFormat::Raw, import_progress)
@@ -1209,14 +1629,128 @@ impl<D: BaoStore> Handler<D> {
         Ok(())
     }
 
+    async fn blob_upload_create(self, msg: UploadCreateRequest) -> RpcResult<UploadCreateResponse> {
+        let blobs = self.blobs_in(msg.store.as_deref())?;
+        let id = blobs.uploads().create().await?;
+        Ok(UploadCreateResponse { id })
+    }
+
+    async fn blob_upload_status(self, msg: UploadStatusRequest) -> RpcResult<UploadStatusResponse> {
+        let blobs = self.blobs_in(msg.store.as_deref())?;
+        let offset = blobs.uploads().offset(msg.id).await?;
+        Ok(UploadStatusResponse { offset })
+    }
+
+    fn blob_upload_append(
+        self,
+        msg: UploadAppendRequest,
+        mut stream: impl Stream<Item = UploadAppendUpdate> + Send + Unpin + 'static,
+    ) -> impl Stream<Item = RpcResult<UploadAppendResponse>> {
+        Gen::new(move |co| async move {
+            let res = async {
+                let blobs = self.blobs_in(msg.store.as_deref())?;
+                let writer = blobs.uploads().resume(msg.id, msg.offset).await?;
+                while let Some(UploadAppendUpdate(chunk)) = stream.next().await {
+                    let offset = writer.write(&chunk).await?;
+                    co.yield_(Ok(UploadAppendResponse { offset })).await;
+                }
+                anyhow::Ok(())
+            }
+            .await;
+            if let Err(err) = res {
+                co.yield_(Err(err.into())).await;
+            }
+        })
+    }
+
+    fn blob_upload_finish(
+        self,
+        msg: UploadFinishRequest,
+    ) -> impl Stream<Item = UploadFinishResponse> {
+        let (tx, rx) = async_channel::bounded(32);
+        let this = self.clone();
+
+        self.local_pool_handle().spawn_detached(|| async move {
+            if let Err(err) = this.blob_upload_finish0(msg, tx.clone()).await {
+                tx.send(AddProgress::Abort(err.into())).await.ok();
+            }
+        });
+
+        rx.map(UploadFinishResponse)
+    }
+
+    async fn blob_upload_finish0(
+        self,
+        msg: UploadFinishRequest,
+        progress: async_channel::Sender<AddProgress>,
+    ) -> anyhow::Result<()> {
+        use iroh_blobs::store::ImportMode;
+
+        let progress = AsyncChannelProgressSender::new(progress);
+        let import_progress = progress.clone().with_filter_map(move |x| match x {
+            ImportProgress::Size { id, size } => Some(AddProgress::Found {
+                id,
+                name: msg.id.to_string(),
+                size,
+            }),
+            ImportProgress::OutboardProgress { id, offset } => {
+                Some(AddProgress::Progress { id, offset })
+            }
+            ImportProgress::OutboardDone { hash, id } => Some(AddProgress::Done { hash, id }),
+            _ => None,
+        });
+        let blobs = self.blobs_in(msg.store.as_deref())?;
+        let path = blobs.uploads().finish(msg.id).await?;
+        let res = blobs
+            .store()
+            .import_file(
+                path.clone(),
+                ImportMode::Copy,
+                BlobFormat::Raw,
+                import_progress,
+            )
+            .await;
+        tokio::fs::remove_file(&path).await.ok();
+        let (temp_tag, _len) = res?;
+        let hash_and_format = *temp_tag.inner();
+        let HashAndFormat { hash, format } = hash_and_format;
+        let tag = match msg.tag {
+            SetTagOption::Named(tag) => {
+                blobs
+                    .store()
+                    .set_tag(tag.clone(), Some(hash_and_format))
+                    .await?;
+                tag
+            }
+            SetTagOption::Auto => blobs.store().create_tag(hash_and_format).await?,
+        };
+        progress
+            .send(AddProgress::AllDone { hash, tag, format })
+            .await?;
+        Ok(())
+    }
+
+    async fn blob_upload_abort(self, msg: UploadAbortRequest) -> RpcResult<()> {
+        let blobs = self.blobs_in(msg.store.as_deref())?;
+        blobs.uploads().abort(msg.id).await?;
+        Ok(())
+    }
+
     fn blob_read_at(
         self,
         req: ReadAtRequest,
     ) -> impl Stream<Item = RpcResult<ReadAtResponse>> + Send + 'static {
         let (tx, rx) = async_channel::bounded(RPC_BLOB_GET_CHANNEL_CAP);
-        let db = self.blobs_store();
+        let blobs = self.blobs_in(req.store.as_deref());
         self.local_pool_handle().spawn_detached(move || async move {
-            if let Err(err) = read_loop(req, db, tx.clone(), RPC_BLOB_GET_CHUNK_SIZE).await {
+            let res = match blobs {
+                Ok(blobs) => {
+                    let db = blobs.store().clone();
+                    read_loop(req, db, tx.clone(), RPC_BLOB_GET_CHUNK_SIZE).await
+                }
+                Err(err) => Err(err),
+            };
+            if let Err(err) = res {
                 tx.send(RpcResult::Err(err.into())).await.ok();
             }
         });
@@ -1292,11 +1826,13 @@ impl<D: BaoStore> Handler<D> {
 
     fn batch_create(
         self,
-        _: BatchCreateRequest,
+        msg: BatchCreateRequest,
         mut updates: impl Stream<Item = BatchUpdate> + Send + Unpin + 'static,
     ) -> impl Stream<Item = BatchCreateResponse> {
-        let blobs = self.blobs();
+        let blobs = self.blobs_in(msg.store.as_deref());
         async move {
+            // For an unknown store the stream ends without a batch id, failing the request.
+            let blobs = blobs.ok()?;
             let batch = blobs.batches().await.create();
             tokio::spawn(async move {
                 while let Some(item) = updates.next().await {
@@ -1311,9 +1847,10 @@ impl<D: BaoStore> Handler<D> {
                 }
                 blobs.batches().await.remove(batch);
             });
-            BatchCreateResponse::Id(batch)
+            Some(BatchCreateResponse::Id(batch))
         }
         .into_stream()
+        .filter_map(|res| res)
     }
 
     fn remote_infos_iter(
@@ -1356,9 +1893,10 @@ impl<D: BaoStore> Handler<D> {
             collection,
             tag,
             tags_to_delete,
+            store,
         } = req;
 
-        let blobs = self.blobs();
+        let blobs = self.blobs_in(store.as_deref())?;
 
         let temp_tag = collection.store(blobs.store()).await?;
         let hash_and_format = temp_tag.inner();
@@ -1385,3 +1923,12 @@ impl<D: BaoStore> Handler<D> {
 fn docs_disabled() -> RpcError {
     anyhow!("docs are disabled").into()
 }
+
+/// Returns the [`BlobEvent`] that describes the current status of a blob in the store, if any.
+async fn current_blob_event<D: BaoStore>(store: &D, hash: Hash) -> io::Result<Option<BlobEvent>> {
+    Ok(match store.entry_status(&hash).await? {
+        EntryStatus::Complete => Some(BlobEvent::Complete { hash }),
+        EntryStatus::Partial => Some(BlobEvent::Partial { hash }),
+        EntryStatus::NotFound => None,
+    })
+}
@@ -1,37 +1,79 @@
 //! This module contains an impl block on [`DocsEngine`] with handlers for RPC requests
 
+use std::collections::BTreeSet;
+
 use anyhow::anyhow;
+use bytes::Bytes;
 use futures_lite::{Stream, StreamExt};
 use iroh_base::rpc::RpcResult;
-use iroh_blobs::{store::Store as BaoStore, BlobFormat};
-use iroh_docs::{Author, DocTicket, NamespaceSecret};
+use iroh_blobs::{
+    store::{EntryStatus, MapEntry, Store as BaoStore},
+    util::{local_pool::LocalPoolHandle, progress::IgnoreProgressSender},
+    BlobFormat, Hash,
+};
+use iroh_docs::{
+    actor::SyncHandle, Author, BatchOp, BundleReader, BundleRecord, BundleWriter, Capability,
+    CapabilityKind, ContentStatus, DocTicket, InviteTicket, NamespaceId, NamespaceSecret,
+    SignedEntry,
+};
+use iroh_io::AsyncSliceReader;
+use tokio::io::AsyncWrite;
 
 use crate::client::docs::ShareMode;
 use crate::node::DocsEngine;
 use crate::rpc_protocol::{
     authors::{
-        CreateRequest, CreateResponse, DeleteRequest, DeleteResponse, ExportRequest,
+        CreateRequest, CreateResponse, DeleteRequest, DeleteResponse, DisableKeystoreRequest,
+        DisableKeystoreResponse, EnableKeystoreRequest, EnableKeystoreResponse, ExportRequest,
         ExportResponse, GetDefaultRequest, GetDefaultResponse, ImportRequest, ImportResponse,
-        ListRequest as AuthorListRequest, ListResponse as AuthorListResponse, SetDefaultRequest,
-        SetDefaultResponse,
+        KeystoreStatusRequest, KeystoreStatusResponse, ListRequest as AuthorListRequest,
+        ListResponse as AuthorListResponse, LockRequest, LockResponse, SetDefaultRequest,
+        SetDefaultResponse, UnlockRequest, UnlockResponse,
     },
     docs::{
-        CloseRequest, CloseResponse, CreateRequest as DocCreateRequest,
-        CreateResponse as DocCreateResponse, DelRequest, DelResponse, DocListRequest,
-        DocSubscribeRequest, DocSubscribeResponse, DropRequest, DropResponse,
-        GetDownloadPolicyRequest, GetDownloadPolicyResponse, GetExactRequest, GetExactResponse,
-        GetManyRequest, GetManyResponse, GetSyncPeersRequest, GetSyncPeersResponse,
-        ImportRequest as DocImportRequest, ImportResponse as DocImportResponse, LeaveRequest,
-        LeaveResponse, ListResponse as DocListResponse, OpenRequest, OpenResponse,
-        SetDownloadPolicyRequest, SetDownloadPolicyResponse, SetHashRequest, SetHashResponse,
-        SetRequest, SetResponse, ShareRequest, ShareResponse, StartSyncRequest, StartSyncResponse,
-        StatusRequest, StatusResponse,
+        AddDelegationRequest, AddDelegationResponse, BatchRequest, BatchResponse, BatchWrite,
+        CloseRequest, CloseResponse, CompactTombstonesRequest, CompactTombstonesResponse,
+        CreateIndexRequest, CreateIndexResponse, CreateInviteRequest, CreateInviteResponse,
+        CreateReadTokenRequest, CreateReadTokenResponse, CreateRequest as DocCreateRequest,
+        CreateResponse as DocCreateResponse, DelRequest, DelResponse, DelegateRequest,
+        DelegateResponse, DocListRequest, DocSubscribeRequest, DocSubscribeResponse, DropRequest,
+        DropResponse, ExportBundleRequest, ExportBundleResponse, FollowMigrationRequest,
+        FollowMigrationResponse, GetAccessPolicyRequest, GetAccessPolicyResponse,
+        GetClockModeRequest, GetClockModeResponse, GetDownloadPolicyRequest,
+        GetDownloadPolicyResponse, GetEncryptionKeyRequest, GetEncryptionKeyResponse,
+        GetExactRequest, GetExactResponse, GetHistoryPolicyRequest, GetHistoryPolicyResponse,
+        GetInlineContentRequest, GetInlineContentResponse, GetInlineLimitRequest,
+        GetInlineLimitResponse, GetManyContentRequest, GetManyContentResponse, GetManyRequest,
+        GetManyResponse, GetSyncInterestRequest, GetSyncInterestResponse, GetSyncPeersRequest,
+        GetSyncPeersResponse, GetSyncPolicyRequest, GetSyncPolicyResponse,
+        GetTombstonePolicyRequest, GetTombstonePolicyResponse, GetTrustedMarkerAuthorsRequest,
+        GetTrustedMarkerAuthorsResponse, ImportBundleRequest, ImportBundleResponse,
+        ImportInviteRequest, ImportInviteResponse, ImportRequest as DocImportRequest,
+        ImportResponse as DocImportResponse, LeaveRequest, LeaveResponse, ListDelegationsRequest,
+        ListDelegationsResponse, ListIndexesRequest, ListIndexesResponse, ListRedemptionsRequest,
+        ListRedemptionsResponse, ListResponse as DocListResponse, OpenRequest, OpenResponse,
+        RemoveIndexRequest, RemoveIndexResponse, RotateRequest, RotateResponse,
+        SetAccessPolicyRequest, SetAccessPolicyResponse, SetClockModeRequest, SetClockModeResponse,
+        SetDownloadPolicyRequest, SetDownloadPolicyResponse, SetEncryptionKeyRequest,
+        SetEncryptionKeyResponse, SetHashRequest, SetHashResponse, SetHistoryPolicyRequest,
+        SetHistoryPolicyResponse, SetInlineLimitRequest, SetInlineLimitResponse,
+        SetReadTokenRequest, SetReadTokenResponse, SetRequest, SetResponse, SetSyncInterestRequest,
+        SetSyncInterestResponse, SetSyncPolicyRequest, SetSyncPolicyResponse,
+        SetTombstonePolicyRequest, SetTombstonePolicyResponse, SetTrustedMarkerAuthorsRequest,
+        SetTrustedMarkerAuthorsResponse, ShareRequest, ShareResponse, StartSyncRequest,
+        StartSyncResponse, StatusRequest, StatusResponse,
     },
 };
 
 /// Capacity for the flume channels to forward sync store iterators to async RPC streams.
 const ITER_CHANNEL_CAP: usize = 64;
 
+/// Number of bundle entries inserted at once when importing a bundle.
+const IMPORT_BATCH_SIZE: usize = 1024;
+
+/// Size of the chunks in which blob content is written to a bundle.
+const BLOB_CHUNK_SIZE: u64 = 64 * 1024;
+
 #[allow(missing_docs)]
 impl DocsEngine {
     pub async fn author_create(&self, _req: CreateRequest) -> RpcResult<CreateResponse> {
@@ -95,6 +137,40 @@ impl DocsEngine {
         Ok(DeleteResponse)
     }
 
+    pub async fn keystore_status(
+        &self,
+        _req: KeystoreStatusRequest,
+    ) -> RpcResult<KeystoreStatusResponse> {
+        let status = self.sync.keystore_status().await?;
+        Ok(KeystoreStatusResponse { status })
+    }
+
+    pub async fn keystore_enable(
+        &self,
+        req: EnableKeystoreRequest,
+    ) -> RpcResult<EnableKeystoreResponse> {
+        self.sync.enable_keystore(req.passphrase).await?;
+        Ok(EnableKeystoreResponse)
+    }
+
+    pub async fn keystore_disable(
+        &self,
+        req: DisableKeystoreRequest,
+    ) -> RpcResult<DisableKeystoreResponse> {
+        self.sync.disable_keystore(req.passphrase).await?;
+        Ok(DisableKeystoreResponse)
+    }
+
+    pub async fn keystore_unlock(&self, req: UnlockRequest) -> RpcResult<UnlockResponse> {
+        self.sync.unlock_keystore(req.passphrase).await?;
+        Ok(UnlockResponse)
+    }
+
+    pub async fn keystore_lock(&self, _req: LockRequest) -> RpcResult<LockResponse> {
+        self.sync.lock_keystore().await?;
+        Ok(LockResponse)
+    }
+
     pub async fn doc_create(&self, _req: DocCreateRequest) -> RpcResult<DocCreateResponse> {
         let namespace = NamespaceSecret::new(&mut rand::rngs::OsRng {});
         let id = namespace.id();
@@ -173,7 +249,12 @@ impl DocsEngine {
         &self,
         req: DocSubscribeRequest,
     ) -> RpcResult<impl Stream<Item = RpcResult<DocSubscribeResponse>>> {
-        let stream = self.subscribe(req.doc_id).await?;
+        let DocSubscribeRequest {
+            doc_id,
+            filter,
+            replay,
+        } = req;
+        let stream = self.subscribe_with_filter(doc_id, filter, replay).await?;
 
         Ok(stream.map(|el| {
             el.map(|event| DocSubscribeResponse { event })
@@ -181,6 +262,61 @@ impl DocsEngine {
         }))
     }
 
+    pub async fn doc_create_invite(
+        &self,
+        req: CreateInviteRequest,
+    ) -> RpcResult<CreateInviteResponse> {
+        let CreateInviteRequest {
+            doc_id,
+            mode,
+            expires,
+            single_use,
+            invitee,
+            addr_options,
+        } = req;
+        let mut me = self.endpoint.node_addr().await?;
+        me.apply_options(addr_options);
+
+        let mode = match mode {
+            ShareMode::Read => CapabilityKind::Read,
+            ShareMode::Write => CapabilityKind::Write,
+        };
+        let invite = self
+            .sync
+            .create_invite(
+                doc_id,
+                mode,
+                expires,
+                single_use,
+                invitee.map(|node| *node.as_bytes()),
+            )
+            .await?;
+        self.start_sync(doc_id, vec![]).await?;
+        Ok(CreateInviteResponse(InviteTicket::new(invite, vec![me])))
+    }
+
+    pub async fn doc_import_invite(
+        &self,
+        req: ImportInviteRequest,
+    ) -> RpcResult<ImportInviteResponse> {
+        let InviteTicket { invite, nodes } = req.ticket;
+        let doc_id = invite.namespace();
+        self.sync.import_namespace(Capability::Read(doc_id)).await?;
+        self.sync.open(doc_id, Default::default()).await?;
+        let inviters = nodes.iter().map(|node| *node.node_id.as_bytes()).collect();
+        self.sync.set_invite(doc_id, invite, inviters).await?;
+        self.start_sync(doc_id, nodes).await?;
+        Ok(ImportInviteResponse { doc_id })
+    }
+
+    pub async fn doc_list_redemptions(
+        &self,
+        req: ListRedemptionsRequest,
+    ) -> RpcResult<ListRedemptionsResponse> {
+        let redemptions = self.sync.list_redemptions(req.doc_id).await?;
+        Ok(ListRedemptionsResponse { redemptions })
+    }
+
     pub async fn doc_import(&self, req: DocImportRequest) -> RpcResult<DocImportResponse> {
         let DocImportRequest { capability } = req;
         let doc_id = self.sync.import_namespace(capability).await?;
@@ -234,6 +370,42 @@ impl DocsEngine {
         Ok(DelResponse { removed })
     }
 
+    pub async fn doc_batch<B: BaoStore>(
+        &self,
+        bao_store: &B,
+        req: BatchRequest,
+    ) -> RpcResult<BatchResponse> {
+        let BatchRequest {
+            doc_id,
+            author_id,
+            writes,
+        } = req;
+        // keep the imported blobs alive until the entries are inserted
+        let mut tags = Vec::new();
+        let mut ops = Vec::with_capacity(writes.len());
+        for write in writes {
+            let op = match write {
+                BatchWrite::SetBytes { key, value } => {
+                    let len = value.len() as u64;
+                    let tag = bao_store.import_bytes(value, BlobFormat::Raw).await?;
+                    let hash = *tag.hash();
+                    tags.push(tag);
+                    BatchOp::Insert { key, hash, len }
+                }
+                BatchWrite::SetHash { key, hash, size } => BatchOp::Insert {
+                    key,
+                    hash,
+                    len: size,
+                },
+                BatchWrite::Del { prefix } => BatchOp::Delete { prefix },
+            };
+            ops.push(op);
+        }
+        let removed = self.sync.apply_batch(doc_id, author_id, ops).await?;
+        drop(tags);
+        Ok(BatchResponse { removed })
+    }
+
     pub async fn doc_set_hash(&self, req: SetHashRequest) -> RpcResult<SetHashResponse> {
         let SetHashRequest {
             doc_id,
@@ -267,6 +439,41 @@ impl DocsEngine {
             .map(|r| r.map(|entry| GetManyResponse { entry }).map_err(Into::into))
     }
 
+    pub fn doc_get_many_content<B: BaoStore>(
+        &self,
+        bao_store: B,
+        local_pool: LocalPoolHandle,
+        req: GetManyContentRequest,
+    ) -> impl Stream<Item = RpcResult<GetManyContentResponse>> + Unpin {
+        let GetManyContentRequest {
+            doc_id,
+            query,
+            max_content_len,
+        } = req;
+        let mut entries = self.doc_get_many(GetManyRequest { doc_id, query });
+        let sync = self.sync.clone();
+        let (tx, rx) = async_channel::bounded(ITER_CHANNEL_CAP);
+        // blob readers are not `Send`, so the content is read on the local pool
+        local_pool.spawn_detached(move || async move {
+            while let Some(res) = entries.next().await {
+                let res = match res {
+                    Ok(GetManyResponse { entry }) => {
+                        read_entry_content(&bao_store, &sync, doc_id, &entry, max_content_len)
+                            .await
+                            .map(|content| GetManyContentResponse { entry, content })
+                            .map_err(|err| err.into())
+                    }
+                    Err(err) => Err(err),
+                };
+                let is_err = res.is_err();
+                if tx.send(res).await.is_err() || is_err {
+                    break;
+                }
+            }
+        });
+        rx.boxed()
+    }
+
     pub async fn doc_get_exact(&self, req: GetExactRequest) -> RpcResult<GetExactResponse> {
         let GetExactRequest {
             doc_id,
@@ -298,6 +505,142 @@ impl DocsEngine {
         Ok(GetDownloadPolicyResponse { policy })
     }
 
+    pub async fn doc_set_history_policy(
+        &self,
+        req: SetHistoryPolicyRequest,
+    ) -> RpcResult<SetHistoryPolicyResponse> {
+        self.sync.set_history_policy(req.doc_id, req.policy).await?;
+        Ok(SetHistoryPolicyResponse {})
+    }
+    pub async fn doc_get_history_policy(
+        &self,
+        req: GetHistoryPolicyRequest,
+    ) -> RpcResult<GetHistoryPolicyResponse> {
+        let policy = self.sync.get_history_policy(req.doc_id).await?;
+        Ok(GetHistoryPolicyResponse { policy })
+    }
+
+    pub async fn doc_set_tombstone_policy(
+        &self,
+        req: SetTombstonePolicyRequest,
+    ) -> RpcResult<SetTombstonePolicyResponse> {
+        self.sync
+            .set_tombstone_policy(req.doc_id, req.policy)
+            .await?;
+        Ok(SetTombstonePolicyResponse {})
+    }
+    pub async fn doc_get_tombstone_policy(
+        &self,
+        req: GetTombstonePolicyRequest,
+    ) -> RpcResult<GetTombstonePolicyResponse> {
+        let policy = self.sync.get_tombstone_policy(req.doc_id).await?;
+        Ok(GetTombstonePolicyResponse { policy })
+    }
+    pub async fn doc_set_sync_policy(
+        &self,
+        req: SetSyncPolicyRequest,
+    ) -> RpcResult<SetSyncPolicyResponse> {
+        self.set_sync_policy(req.doc_id, req.policy).await?;
+        Ok(SetSyncPolicyResponse {})
+    }
+    pub async fn doc_get_sync_policy(
+        &self,
+        req: GetSyncPolicyRequest,
+    ) -> RpcResult<GetSyncPolicyResponse> {
+        let policy = self.sync.get_sync_policy(req.doc_id).await?;
+        Ok(GetSyncPolicyResponse { policy })
+    }
+    pub async fn doc_set_inline_limit(
+        &self,
+        req: SetInlineLimitRequest,
+    ) -> RpcResult<SetInlineLimitResponse> {
+        self.sync.set_inline_limit(req.doc_id, req.limit).await?;
+        Ok(SetInlineLimitResponse {})
+    }
+    pub async fn doc_get_inline_limit(
+        &self,
+        req: GetInlineLimitRequest,
+    ) -> RpcResult<GetInlineLimitResponse> {
+        let limit = self.sync.get_inline_limit(req.doc_id).await?;
+        Ok(GetInlineLimitResponse { limit })
+    }
+    pub async fn doc_get_inline_content(
+        &self,
+        req: GetInlineContentRequest,
+    ) -> RpcResult<GetInlineContentResponse> {
+        let GetInlineContentRequest {
+            doc_id,
+            author,
+            key,
+            hash,
+        } = req;
+        let content = self
+            .sync
+            .get_inline_content(doc_id, author, key, hash)
+            .await?;
+        Ok(GetInlineContentResponse { content })
+    }
+    pub async fn doc_create_index(
+        &self,
+        req: CreateIndexRequest,
+    ) -> RpcResult<CreateIndexResponse> {
+        let CreateIndexRequest { doc_id, name, spec } = req;
+        self.create_index(doc_id, name, spec).await?;
+        Ok(CreateIndexResponse {})
+    }
+    pub async fn doc_remove_index(
+        &self,
+        req: RemoveIndexRequest,
+    ) -> RpcResult<RemoveIndexResponse> {
+        let RemoveIndexRequest { doc_id, name } = req;
+        let removed = self.remove_index(doc_id, name).await?;
+        Ok(RemoveIndexResponse { removed })
+    }
+    pub async fn doc_list_indexes(
+        &self,
+        req: ListIndexesRequest,
+    ) -> RpcResult<ListIndexesResponse> {
+        let indexes = self.sync.list_indexes(req.doc_id).await?;
+        Ok(ListIndexesResponse { indexes })
+    }
+    pub async fn doc_compact_tombstones(
+        &self,
+        req: CompactTombstonesRequest,
+    ) -> RpcResult<CompactTombstonesResponse> {
+        let removed = self.sync.compact_tombstones(req.doc_id).await?;
+        Ok(CompactTombstonesResponse { removed })
+    }
+
+    pub async fn doc_set_clock_mode(
+        &self,
+        req: SetClockModeRequest,
+    ) -> RpcResult<SetClockModeResponse> {
+        self.sync.set_clock_mode(req.doc_id, req.mode).await?;
+        Ok(SetClockModeResponse {})
+    }
+    pub async fn doc_get_clock_mode(
+        &self,
+        req: GetClockModeRequest,
+    ) -> RpcResult<GetClockModeResponse> {
+        let mode = self.sync.get_clock_mode(req.doc_id).await?;
+        Ok(GetClockModeResponse { mode })
+    }
+
+    pub async fn doc_set_sync_interest(
+        &self,
+        req: SetSyncInterestRequest,
+    ) -> RpcResult<SetSyncInterestResponse> {
+        self.sync.set_sync_interest(req.doc_id, req.area).await?;
+        Ok(SetSyncInterestResponse {})
+    }
+    pub async fn doc_get_sync_interest(
+        &self,
+        req: GetSyncInterestRequest,
+    ) -> RpcResult<GetSyncInterestResponse> {
+        let area = self.sync.get_sync_interest(req.doc_id).await?;
+        Ok(GetSyncInterestResponse { area })
+    }
+
     pub async fn doc_get_sync_peers(
         &self,
         req: GetSyncPeersRequest,
@@ -305,4 +648,371 @@ impl DocsEngine {
         let peers = self.sync.get_sync_peers(req.doc_id).await?;
         Ok(GetSyncPeersResponse { peers })
     }
+
+    pub async fn doc_delegate(&self, req: DelegateRequest) -> RpcResult<DelegateResponse> {
+        let DelegateRequest {
+            doc_id,
+            scope,
+            issuer,
+        } = req;
+        let delegation = self.sync.delegate(doc_id, scope, issuer).await?;
+        Ok(DelegateResponse { delegation })
+    }
+
+    pub async fn doc_add_delegation(
+        &self,
+        req: AddDelegationRequest,
+    ) -> RpcResult<AddDelegationResponse> {
+        let inserted = self.sync.add_delegation(req.doc_id, req.delegation).await?;
+        Ok(AddDelegationResponse { inserted })
+    }
+
+    pub async fn doc_list_delegations(
+        &self,
+        req: ListDelegationsRequest,
+    ) -> RpcResult<ListDelegationsResponse> {
+        let delegations = self.sync.list_delegations(req.doc_id).await?;
+        Ok(ListDelegationsResponse { delegations })
+    }
+
+    pub async fn doc_set_access_policy(
+        &self,
+        req: SetAccessPolicyRequest,
+    ) -> RpcResult<SetAccessPolicyResponse> {
+        self.sync.set_access_policy(req.doc_id, req.policy).await?;
+        Ok(SetAccessPolicyResponse {})
+    }
+
+    pub async fn doc_get_access_policy(
+        &self,
+        req: GetAccessPolicyRequest,
+    ) -> RpcResult<GetAccessPolicyResponse> {
+        let policy = self.sync.get_access_policy(req.doc_id).await?;
+        Ok(GetAccessPolicyResponse { policy })
+    }
+
+    pub async fn doc_create_read_token(
+        &self,
+        req: CreateReadTokenRequest,
+    ) -> RpcResult<CreateReadTokenResponse> {
+        let CreateReadTokenRequest {
+            doc_id,
+            node,
+            expires,
+        } = req;
+        let token = self.sync.create_read_token(doc_id, node, expires).await?;
+        Ok(CreateReadTokenResponse { token })
+    }
+
+    pub async fn doc_set_read_token(
+        &self,
+        req: SetReadTokenRequest,
+    ) -> RpcResult<SetReadTokenResponse> {
+        self.sync.set_read_token(req.doc_id, req.token).await?;
+        Ok(SetReadTokenResponse {})
+    }
+
+    pub async fn doc_set_encryption_key(
+        &self,
+        req: SetEncryptionKeyRequest,
+    ) -> RpcResult<SetEncryptionKeyResponse> {
+        let key = self.sync.set_encryption_key(req.doc_id, req.key).await?;
+        Ok(SetEncryptionKeyResponse { key })
+    }
+
+    pub async fn doc_get_encryption_key(
+        &self,
+        req: GetEncryptionKeyRequest,
+    ) -> RpcResult<GetEncryptionKeyResponse> {
+        let key = self.sync.get_encryption_key(req.doc_id).await?;
+        Ok(GetEncryptionKeyResponse { key })
+    }
+
+    pub async fn doc_rotate<B: BaoStore>(
+        &self,
+        bao_store: &B,
+        req: RotateRequest,
+    ) -> RpcResult<RotateResponse> {
+        let RotateRequest {
+            doc_id,
+            author_id,
+            keep_authors,
+        } = req;
+        let secret = NamespaceSecret::new(&mut rand::rngs::OsRng {});
+        let id = secret.id();
+        let copied = self
+            .sync
+            .migrate_namespace(doc_id, secret, author_id, keep_authors)
+            .await?;
+        self.sync.open(id, Default::default()).await?;
+
+        // Point the old document to the new one.
+        let content = iroh_docs::moved_to_content(&id);
+        let len = content.len();
+        let tag = bao_store.import_bytes(content, BlobFormat::Raw).await?;
+        let key = iroh_docs::moved_to_key(&id);
+        self.sync
+            .insert_local(doc_id, author_id, key, *tag.hash(), len as u64)
+            .await?;
+
+        if self.sync.get_state(doc_id).await?.sync {
+            self.start_sync(id, vec![]).await?;
+        }
+        Ok(RotateResponse { id, copied })
+    }
+
+    pub async fn doc_follow_migration(
+        &self,
+        req: FollowMigrationRequest,
+    ) -> RpcResult<FollowMigrationResponse> {
+        let FollowMigrationRequest { doc_id, to } = req;
+        self.follow_migration(doc_id, to).await?;
+        Ok(FollowMigrationResponse {})
+    }
+
+    pub async fn doc_set_trusted_marker_authors(
+        &self,
+        req: SetTrustedMarkerAuthorsRequest,
+    ) -> RpcResult<SetTrustedMarkerAuthorsResponse> {
+        let SetTrustedMarkerAuthorsRequest { doc_id, authors } = req;
+        self.sync
+            .set_trusted_marker_authors(doc_id, authors)
+            .await?;
+        Ok(SetTrustedMarkerAuthorsResponse {})
+    }
+
+    pub async fn doc_get_trusted_marker_authors(
+        &self,
+        req: GetTrustedMarkerAuthorsRequest,
+    ) -> RpcResult<GetTrustedMarkerAuthorsResponse> {
+        let authors = self.sync.get_trusted_marker_authors(req.doc_id).await?;
+        Ok(GetTrustedMarkerAuthorsResponse { authors })
+    }
+
+    pub async fn doc_export_bundle<B: BaoStore>(
+        &self,
+        bao_store: &B,
+        local_pool: &LocalPoolHandle,
+        req: ExportBundleRequest,
+    ) -> RpcResult<ExportBundleResponse> {
+        let ExportBundleRequest {
+            doc_id,
+            query,
+            include_content,
+            path,
+        } = req;
+        if !path.is_absolute() {
+            return Err(anyhow!("path must be absolute").into());
+        }
+        let file = tokio::io::BufWriter::new(tokio::fs::File::create(&path).await?);
+        let mut writer = BundleWriter::new(file, doc_id)
+            .await
+            .map_err(anyhow::Error::from)?;
+        let (tx, rx) = async_channel::bounded(ITER_CHANNEL_CAP);
+        self.sync.get_many(doc_id, query, tx).await?;
+        let mut entries = 0;
+        let mut hashes = BTreeSet::new();
+        while let Ok(entry) = rx.recv().await {
+            let entry = entry?;
+            writer
+                .write_entry(&entry)
+                .await
+                .map_err(anyhow::Error::from)?;
+            if include_content && entry.content_len() > 0 {
+                hashes.insert(entry.content_hash());
+            }
+            entries += 1;
+        }
+        // blob readers are not `Send`, so the content is written on the local pool
+        let bao_store = bao_store.clone();
+        let (writer, blobs) = local_pool
+            .spawn(move || write_complete_blobs(bao_store, hashes, writer))
+            .await
+            .map_err(anyhow::Error::from)??;
+        writer.finish().await.map_err(anyhow::Error::from)?;
+        Ok(ExportBundleResponse { entries, blobs })
+    }
+
+    pub async fn doc_import_bundle<B: BaoStore>(
+        &self,
+        bao_store: &B,
+        req: ImportBundleRequest,
+    ) -> RpcResult<ImportBundleResponse> {
+        let ImportBundleRequest { path } = req;
+        if !path.is_absolute() {
+            return Err(anyhow!("path must be absolute").into());
+        }
+        // check everything before inserting anything
+        open_bundle(&path)
+            .await?
+            .verify_all()
+            .await
+            .map_err(anyhow::Error::from)?;
+
+        // keep the imported content alive until the entries referencing it are inserted
+        let mut reader = open_bundle(&path).await?;
+        let doc_id = reader.namespace();
+        let mut tags = Vec::new();
+        while let Some(record) = reader.next_record().await.map_err(anyhow::Error::from)? {
+            let BundleRecord::Blob { hash, .. } = record else {
+                continue;
+            };
+            let (chunk_tx, chunk_rx) = async_channel::bounded(ITER_CHANNEL_CAP);
+            let import = bao_store.import_stream(
+                Box::pin(chunk_rx),
+                BlobFormat::Raw,
+                IgnoreProgressSender::default(),
+            );
+            let feed = async {
+                let chunk_tx = chunk_tx;
+                while let Some(chunk) = reader.read_blob_data().await? {
+                    if chunk_tx.send(Ok(chunk)).await.is_err() {
+                        break;
+                    }
+                }
+                anyhow::Ok(())
+            };
+            let (imported, fed) = tokio::join!(import, feed);
+            fed?;
+            let (tag, _size) = imported?;
+            if *tag.hash() != hash {
+                return Err(anyhow!("content of blob {hash} changed during import").into());
+            }
+            tags.push(tag);
+        }
+        let blobs = tags.len();
+
+        self.sync.import_namespace(Capability::Read(doc_id)).await?;
+        self.sync.open(doc_id, Default::default()).await?;
+        let mut reader = open_bundle(&path).await?;
+        let mut inserted = 0;
+        loop {
+            let mut batch = Vec::with_capacity(IMPORT_BATCH_SIZE);
+            while batch.len() < IMPORT_BATCH_SIZE {
+                match reader.next_record().await.map_err(anyhow::Error::from)? {
+                    Some(BundleRecord::Entry(entry)) => batch.push(entry),
+                    Some(_) => continue,
+                    None => break,
+                }
+            }
+            if batch.is_empty() {
+                break;
+            }
+            inserted += self.import_bundle_entries(bao_store, doc_id, batch).await?;
+        }
+        drop(tags);
+        Ok(ImportBundleResponse {
+            doc_id,
+            entries: inserted,
+            blobs,
+        })
+    }
+
+    /// Insert a batch of entries from a bundle, marking those with complete content as such.
+    async fn import_bundle_entries<B: BaoStore>(
+        &self,
+        bao_store: &B,
+        doc_id: NamespaceId,
+        entries: Vec<SignedEntry>,
+    ) -> anyhow::Result<usize> {
+        let mut complete = Vec::new();
+        let mut missing = Vec::new();
+        for entry in entries {
+            match bao_store.entry_status(&entry.content_hash()).await? {
+                EntryStatus::Complete => complete.push(entry),
+                _ => missing.push(entry),
+            }
+        }
+        let from = *self.endpoint.node_id().as_bytes();
+        let mut inserted = self
+            .sync
+            .import_entries(doc_id, complete, from, ContentStatus::Complete)
+            .await?;
+        inserted += self
+            .sync
+            .import_entries(doc_id, missing, from, ContentStatus::Missing)
+            .await?;
+        Ok(inserted)
+    }
+}
+
+/// Open the bundle at `path` and read its header.
+async fn open_bundle(
+    path: &std::path::Path,
+) -> anyhow::Result<BundleReader<tokio::io::BufReader<tokio::fs::File>>> {
+    let file = tokio::fs::File::open(path).await?;
+    let reader = BundleReader::new(tokio::io::BufReader::new(file)).await?;
+    Ok(reader)
+}
+
+/// Read the content of an entry, from the blob store or, if it was received inline and is not yet
+/// in the blob store, from the docs store.
+async fn read_entry_content<B: BaoStore>(
+    bao_store: &B,
+    sync: &SyncHandle,
+    doc_id: NamespaceId,
+    entry: &SignedEntry,
+    max_len: u64,
+) -> anyhow::Result<Option<Bytes>> {
+    let hash = entry.content_hash();
+    if let Some(content) = read_content(bao_store, hash, max_len).await? {
+        return Ok(Some(content));
+    }
+    if entry.content_len() == 0 || entry.content_len() > max_len {
+        return Ok(None);
+    }
+    let (author, key) = (entry.author(), entry.key().to_vec().into());
+    sync.get_inline_content(doc_id, author, key, hash).await
+}
+
+/// Read the content of a blob, if it is complete in `bao_store` and not longer than `max_len`.
+async fn read_content<B: BaoStore>(
+    bao_store: &B,
+    hash: Hash,
+    max_len: u64,
+) -> std::io::Result<Option<Bytes>> {
+    let Some(entry) = bao_store.get(&hash).await? else {
+        return Ok(None);
+    };
+    let size = entry.size().value();
+    if !entry.is_complete() || size > max_len {
+        return Ok(None);
+    }
+    let mut reader = entry.data_reader().await?;
+    let content = reader.read_at(0, size as usize).await?;
+    Ok(Some(content))
+}
+
+/// Write the content of the blobs in `hashes` which are complete in `bao_store` to `writer`.
+///
+/// Returns the writer and the number of blobs written.
+async fn write_complete_blobs<B: BaoStore, W: AsyncWrite + Unpin>(
+    bao_store: B,
+    hashes: BTreeSet<Hash>,
+    mut writer: BundleWriter<W>,
+) -> anyhow::Result<(BundleWriter<W>, usize)> {
+    let mut blobs = 0;
+    for hash in hashes {
+        let Some(entry) = bao_store.get(&hash).await? else {
+            continue;
+        };
+        if !entry.is_complete() {
+            continue;
+        }
+        let size = entry.size().value();
+        let mut reader = entry.data_reader().await?;
+        writer.start_blob(hash, size).await?;
+        let mut offset = 0;
+        while offset < size {
+            let len = (size - offset).min(BLOB_CHUNK_SIZE);
+            let data = reader.read_at(offset, len as usize).await?;
+            if data.is_empty() {
+                anyhow::bail!("content of blob {hash} is shorter than its size");
+            }
+            writer.write_blob_data(&data).await?;
+            offset += data.len() as u64;
+        }
+        blobs += 1;
+    }
+    Ok((writer, blobs))
 }
@@ -0,0 +1,228 @@
+//! Resumable uploads of blobs over RPC.
+//!
+//! An upload is a file on the node to which a client appends data, possibly over multiple RPC
+//! requests. Once all data is appended, the file is imported into the blob store.
+use std::{
+    collections::BTreeMap,
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    sync::{Arc, Weak},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, ensure, Result};
+use tokio::{
+    fs::File,
+    io::{AsyncSeekExt, AsyncWriteExt},
+    sync::Mutex,
+};
+use tokio_util::task::AbortOnDropHandle;
+use tracing::debug;
+
+use crate::rpc_protocol::blobs::UploadId;
+
+/// How long an upload is kept without any activity by default.
+pub(crate) const DEFAULT_UPLOAD_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Keeps track of the resumable uploads to a blob store.
+///
+/// The data of each upload is written to a file in a temporary directory. Uploads that see no
+/// activity for longer than their time to live are removed, e.g. because the client
+/// disconnected and never came back. The directory and all unfinished uploads are removed when
+/// the store is dropped.
+#[derive(derive_more::Debug)]
+pub(crate) struct BlobUploads {
+    dir: tempfile::TempDir,
+    state: Arc<std::sync::Mutex<UploadsState>>,
+    #[debug("AbortOnDropHandle")]
+    _expire_task: AbortOnDropHandle<()>,
+}
+
+#[derive(Debug, Default)]
+struct UploadsState {
+    /// Currently active uploads
+    uploads: BTreeMap<UploadId, Arc<Mutex<UploadFile>>>,
+}
+
+/// The file of a single upload.
+#[derive(Debug)]
+struct UploadFile {
+    path: PathBuf,
+    file: File,
+    /// The number of bytes appended to the upload so far.
+    offset: u64,
+    /// Incremented every time the upload is resumed, to stop previous writers.
+    generation: u64,
+    /// When the upload was last created, resumed, written to or queried.
+    last_active: Instant,
+}
+
+/// Appends data to an upload, see [`BlobUploads::resume`].
+#[derive(Debug)]
+pub(crate) struct UploadWriter {
+    upload: Arc<Mutex<UploadFile>>,
+    generation: u64,
+}
+
+impl BlobUploads {
+    /// Create a new set of uploads, storing the data below `base`, or in the system's temp
+    /// directory if `base` is `None`.
+    ///
+    /// Uploads are removed after they have been idle for `ttl`.
+    pub(crate) fn new(base: Option<&Path>, ttl: Duration) -> std::io::Result<Self> {
+        let dir = match base {
+            Some(base) => tempfile::tempdir_in(base)?,
+            None => tempfile::tempdir()?,
+        };
+        let state = Arc::new(std::sync::Mutex::new(UploadsState::default()));
+        let task = tokio::task::spawn(expire_loop(Arc::downgrade(&state), ttl));
+        Ok(Self {
+            dir,
+            state,
+            _expire_task: AbortOnDropHandle::new(task),
+        })
+    }
+
+    /// Start a new, empty upload.
+    pub(crate) async fn create(&self) -> Result<UploadId> {
+        // random, so that other clients of the node can not guess it
+        let id = UploadId(rand::random());
+        let path = self.dir.path().join(id.0.to_string());
+        let file = File::create(&path).await?;
+        let upload = UploadFile {
+            path,
+            file,
+            offset: 0,
+            generation: 0,
+            last_active: Instant::now(),
+        };
+        self.state
+            .lock()
+            .unwrap()
+            .uploads
+            .insert(id, Arc::new(Mutex::new(upload)));
+        Ok(id)
+    }
+
+    fn get(&self, id: UploadId) -> Result<Arc<Mutex<UploadFile>>> {
+        let state = self.state.lock().unwrap();
+        let upload = state
+            .uploads
+            .get(&id)
+            .ok_or_else(|| anyhow!("upload not found"))?;
+        Ok(upload.clone())
+    }
+
+    /// The number of bytes appended to an upload so far.
+    pub(crate) async fn offset(&self, id: UploadId) -> Result<u64> {
+        let upload = self.get(id)?;
+        let mut file = upload.lock().await;
+        file.last_active = Instant::now();
+        Ok(file.offset)
+    }
+
+    /// Continue appending to an upload at `offset`, which must be the current offset.
+    ///
+    /// Writers created by previous calls for the same upload fail on their next write, so
+    /// that a client can resume after its connection broke, even if the node did not notice.
+    pub(crate) async fn resume(&self, id: UploadId, offset: u64) -> Result<UploadWriter> {
+        let upload = self.get(id)?;
+        let generation = {
+            let mut file = upload.lock().await;
+            ensure!(
+                file.offset == offset,
+                "offset mismatch: upload is at {}, got {offset}",
+                file.offset
+            );
+            // drop anything a failed write might have left after the offset
+            file.file.set_len(offset).await?;
+            file.file.seek(SeekFrom::Start(offset)).await?;
+            file.generation += 1;
+            file.last_active = Instant::now();
+            file.generation
+        };
+        Ok(UploadWriter { upload, generation })
+    }
+
+    /// Complete an upload, returning the path of the file containing its data.
+    ///
+    /// The caller is responsible for removing the file.
+    pub(crate) async fn finish(&self, id: UploadId) -> Result<PathBuf> {
+        let upload = self
+            .state
+            .lock()
+            .unwrap()
+            .uploads
+            .remove(&id)
+            .ok_or_else(|| anyhow!("upload not found"))?;
+        let mut file = upload.lock().await;
+        file.generation += 1;
+        file.file.set_len(file.offset).await?;
+        file.file.sync_all().await?;
+        Ok(file.path.clone())
+    }
+
+    /// Abort an upload and remove its data.
+    pub(crate) async fn abort(&self, id: UploadId) -> Result<()> {
+        let path = self.finish(id).await?;
+        tokio::fs::remove_file(path).await?;
+        Ok(())
+    }
+}
+
+impl UploadWriter {
+    /// Append a chunk of data, returning the new offset of the upload.
+    pub(crate) async fn write(&self, chunk: &[u8]) -> Result<u64> {
+        let mut file = self.upload.lock().await;
+        ensure!(
+            file.generation == self.generation,
+            "upload was resumed or finished by another request"
+        );
+        file.file.write_all(chunk).await?;
+        file.file.flush().await?;
+        file.offset += chunk.len() as u64;
+        file.last_active = Instant::now();
+        Ok(file.offset)
+    }
+}
+
+/// Periodically remove the uploads that have been idle for longer than `ttl`.
+///
+/// Stops once the uploads are dropped.
+async fn expire_loop(state: Weak<std::sync::Mutex<UploadsState>>, ttl: Duration) {
+    let mut interval = tokio::time::interval((ttl / 2).max(Duration::from_millis(1)));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        let Some(state) = state.upgrade() else {
+            break;
+        };
+        let expired = state.lock().unwrap().expire(ttl);
+        drop(state);
+        for (id, path) in expired {
+            debug!(?id, "removing expired upload");
+            tokio::fs::remove_file(path).await.ok();
+        }
+    }
+}
+
+impl UploadsState {
+    /// Remove the uploads that have been idle for longer than `ttl`, returning their files.
+    fn expire(&mut self, ttl: Duration) -> Vec<(UploadId, PathBuf)> {
+        let mut expired = Vec::new();
+        self.uploads.retain(|id, upload| {
+            // an upload that is locked is in use right now
+            let Ok(mut file) = upload.try_lock() else {
+                return true;
+            };
+            if file.last_active.elapsed() < ttl {
+                return true;
+            }
+            // stop writers that are still around
+            file.generation += 1;
+            expired.push((*id, file.path.clone()));
+            false
+        });
+        expired
+    }
+}
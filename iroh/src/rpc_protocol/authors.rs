@@ -1,5 +1,5 @@
 use iroh_base::rpc::RpcResult;
-use iroh_docs::{Author, AuthorId};
+use iroh_docs::{Author, AuthorId, KeystoreStatus};
 use nested_enum_utils::enum_conversions;
 use quic_rpc_derive::rpc_requests;
 use serde::{Deserialize, Serialize};
@@ -25,6 +25,16 @@ pub enum Request {
     Export(ExportRequest),
     #[rpc(response = RpcResult<DeleteResponse>)]
     Delete(DeleteRequest),
+    #[rpc(response = RpcResult<KeystoreStatusResponse>)]
+    KeystoreStatus(KeystoreStatusRequest),
+    #[rpc(response = RpcResult<EnableKeystoreResponse>)]
+    EnableKeystore(EnableKeystoreRequest),
+    #[rpc(response = RpcResult<DisableKeystoreResponse>)]
+    DisableKeystore(DisableKeystoreRequest),
+    #[rpc(response = RpcResult<UnlockResponse>)]
+    Unlock(UnlockRequest),
+    #[rpc(response = RpcResult<LockResponse>)]
+    Lock(LockRequest),
 }
 
 #[allow(missing_docs)]
@@ -38,6 +48,11 @@ pub enum Response {
     Import(RpcResult<ImportResponse>),
     Export(RpcResult<ExportResponse>),
     Delete(RpcResult<DeleteResponse>),
+    KeystoreStatus(RpcResult<KeystoreStatusResponse>),
+    EnableKeystore(RpcResult<EnableKeystoreResponse>),
+    DisableKeystore(RpcResult<DisableKeystoreResponse>),
+    Unlock(RpcResult<UnlockResponse>),
+    Lock(RpcResult<LockResponse>),
 }
 
 /// List document authors for which we have a secret key.
@@ -121,3 +136,58 @@ pub struct ImportResponse {
     /// The author id of the imported author
     pub author_id: AuthorId,
 }
+
+/// Get the status of the keystore
+#[derive(Serialize, Deserialize, Debug)]
+pub struct KeystoreStatusRequest;
+
+/// Response to [`KeystoreStatusRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct KeystoreStatusResponse {
+    /// The status of the keystore
+    pub status: KeystoreStatus,
+}
+
+/// Encrypt the stored secret keys with a passphrase
+#[derive(Serialize, Deserialize, derive_more::Debug)]
+pub struct EnableKeystoreRequest {
+    /// The passphrase
+    #[debug("..")]
+    pub passphrase: String,
+}
+
+/// Response to [`EnableKeystoreRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EnableKeystoreResponse;
+
+/// Store the secret keys in cleartext again
+#[derive(Serialize, Deserialize, derive_more::Debug)]
+pub struct DisableKeystoreRequest {
+    /// The passphrase
+    #[debug("..")]
+    pub passphrase: String,
+}
+
+/// Response to [`DisableKeystoreRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DisableKeystoreResponse;
+
+/// Unlock the keystore with the passphrase
+#[derive(Serialize, Deserialize, derive_more::Debug)]
+pub struct UnlockRequest {
+    /// The passphrase
+    #[debug("..")]
+    pub passphrase: String,
+}
+
+/// Response to [`UnlockRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UnlockResponse;
+
+/// Lock the keystore
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LockRequest;
+
+/// Response to [`LockRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LockResponse;
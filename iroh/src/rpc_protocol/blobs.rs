@@ -22,7 +22,8 @@ use quic_rpc_derive::rpc_requests;
 use serde::{Deserialize, Serialize};
 
 use crate::client::blobs::{
-    BlobInfo, BlobStatus, DownloadMode, IncompleteBlobInfo, ReadAtLen, WrapOption,
+    BlobEvent, BlobInfo, BlobStatus, DownloadMode, IncompleteBlobInfo, ProviderEvent, ReadAtLen,
+    WrapOption,
 };
 
 use super::RpcService;
@@ -57,6 +58,21 @@ pub enum Request {
     CreateCollection(CreateCollectionRequest),
     #[rpc(response = RpcResult<BlobStatusResponse>)]
     BlobStatus(BlobStatusRequest),
+    #[server_streaming(response = RpcResult<SubscribeResponse>)]
+    Subscribe(SubscribeRequest),
+    #[server_streaming(response = RpcResult<ProviderEventsResponse>)]
+    ProviderEvents(ProviderEventsRequest),
+    #[rpc(response = RpcResult<UploadCreateResponse>)]
+    UploadCreate(UploadCreateRequest),
+    #[rpc(response = RpcResult<UploadStatusResponse>)]
+    UploadStatus(UploadStatusRequest),
+    #[bidi_streaming(update = UploadAppendUpdate, response = RpcResult<UploadAppendResponse>)]
+    UploadAppend(UploadAppendRequest),
+    UploadAppendUpdate(UploadAppendUpdate),
+    #[server_streaming(response = UploadFinishResponse)]
+    UploadFinish(UploadFinishRequest),
+    #[rpc(response = RpcResult<()>)]
+    UploadAbort(UploadAbortRequest),
 
     #[bidi_streaming(update = BatchUpdate, response = BatchCreateResponse)]
     BatchCreate(BatchCreateRequest),
@@ -85,6 +101,12 @@ pub enum Response {
     Validate(ValidateProgress),
     CreateCollection(RpcResult<CreateCollectionResponse>),
     BlobStatus(RpcResult<BlobStatusResponse>),
+    Subscribe(RpcResult<SubscribeResponse>),
+    ProviderEvents(RpcResult<ProviderEventsResponse>),
+    UploadCreate(RpcResult<UploadCreateResponse>),
+    UploadStatus(RpcResult<UploadStatusResponse>),
+    UploadAppend(RpcResult<UploadAppendResponse>),
+    UploadFinish(UploadFinishResponse),
     BatchCreate(BatchCreateResponse),
     BatchAddStream(BatchAddStreamResponse),
     BatchAddPath(BatchAddPathResponse),
@@ -108,6 +130,8 @@ pub struct AddPathRequest {
     pub tag: SetTagOption,
     /// Whether to wrap the added data in a collection
     pub wrap: WrapOption,
+    /// The named blob store to use, `None` for the default store.
+    pub store: Option<String>,
 }
 
 /// Wrapper around [`AddProgress`].
@@ -133,6 +157,13 @@ pub struct DownloadRequest {
     pub tag: SetTagOption,
     /// Whether to directly start the download or add it to the download queue.
     pub mode: DownloadMode,
+    /// The named blob store on the remote nodes to download from, `None` for their default
+    /// store.
+    ///
+    /// Downloads from a named remote store are always performed as in [`DownloadMode::Direct`].
+    pub remote_store: Option<String>,
+    /// The named blob store to use, `None` for the default store.
+    pub store: Option<String>,
 }
 
 /// Progress response for [`DownloadRequest`]
@@ -156,6 +187,8 @@ pub struct ExportRequest {
     ///
     /// The default is [`ExportMode::Copy`]. See [`ExportMode`] for details.
     pub mode: ExportMode,
+    /// The named blob store to use, `None` for the default store.
+    pub store: Option<String>,
 }
 
 /// Progress response for [`ExportRequest`]
@@ -167,6 +200,8 @@ pub struct ExportResponse(pub ExportProgress);
 pub struct ConsistencyCheckRequest {
     /// repair the store by dropping inconsistent blobs
     pub repair: bool,
+    /// The named blob store to use, `None` for the default store.
+    pub store: Option<String>,
 }
 
 /// A request to the node to validate the integrity of all provided data
@@ -174,15 +209,23 @@ pub struct ConsistencyCheckRequest {
 pub struct ValidateRequest {
     /// repair the store by downgrading blobs from complete to partial
     pub repair: bool,
+    /// The named blob store to use, `None` for the default store.
+    pub store: Option<String>,
 }
 
 /// List all blobs, including collections
 #[derive(Debug, Serialize, Deserialize)]
-pub struct ListRequest;
+pub struct ListRequest {
+    /// The named blob store to use, `None` for the default store.
+    pub store: Option<String>,
+}
 
 /// List all blobs, including collections
 #[derive(Debug, Serialize, Deserialize)]
-pub struct ListIncompleteRequest;
+pub struct ListIncompleteRequest {
+    /// The named blob store to use, `None` for the default store.
+    pub store: Option<String>,
+}
 
 /// Get the bytes for a hash
 #[derive(Serialize, Deserialize, Debug)]
@@ -193,6 +236,8 @@ pub struct ReadAtRequest {
     pub offset: u64,
     /// Length of the data to get
     pub len: ReadAtLen,
+    /// The named blob store to use, `None` for the default store.
+    pub store: Option<String>,
 }
 
 /// Response to [`ReadAtRequest`]
@@ -217,6 +262,8 @@ pub enum ReadAtResponse {
 pub struct AddStreamRequest {
     /// Tag to tag the data with.
     pub tag: SetTagOption,
+    /// The named blob store to use, `None` for the default store.
+    pub store: Option<String>,
 }
 
 /// Write a blob from a byte stream
@@ -232,11 +279,116 @@ pub enum AddStreamUpdate {
 #[derive(Debug, Serialize, Deserialize, derive_more::Into)]
 pub struct AddStreamResponse(pub AddProgress);
 
+/// Identifier of a resumable upload, see [`UploadCreateRequest`].
+///
+/// Ids are random, so that they can not be guessed by other clients of the node.
+#[derive(
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Serialize,
+    Deserialize,
+    Ord,
+    Clone,
+    Copy,
+    Hash,
+    derive_more::Display,
+    derive_more::FromStr,
+)]
+pub struct UploadId(pub(crate) u128);
+
+/// Start a resumable upload.
+///
+/// The data of the upload is appended with [`UploadAppendRequest`]s and imported into the
+/// store with an [`UploadFinishRequest`].
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UploadCreateRequest {
+    /// The named blob store to use, `None` for the default store.
+    pub store: Option<String>,
+}
+
+/// Response to [`UploadCreateRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UploadCreateResponse {
+    /// The id of the new upload.
+    pub id: UploadId,
+}
+
+/// Get the number of bytes appended to an upload so far.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UploadStatusRequest {
+    /// The id of the upload.
+    pub id: UploadId,
+    /// The named blob store to use, `None` for the default store.
+    pub store: Option<String>,
+}
+
+/// Response to [`UploadStatusRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UploadStatusResponse {
+    /// The number of bytes appended to the upload so far.
+    pub offset: u64,
+}
+
+/// Append data to an upload.
+///
+/// The data is sent as [`UploadAppendUpdate`]s. The node responds with the new offset of
+/// the upload after every chunk it has written.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UploadAppendRequest {
+    /// The id of the upload.
+    pub id: UploadId,
+    /// The offset at which to append, must be the current offset of the upload.
+    pub offset: u64,
+    /// The named blob store to use, `None` for the default store.
+    pub store: Option<String>,
+}
+
+/// A chunk of data to append to an upload
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UploadAppendUpdate(pub Bytes);
+
+/// Response to an [`UploadAppendUpdate`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UploadAppendResponse {
+    /// The offset of the upload after appending the chunk.
+    pub offset: u64,
+}
+
+/// Import the data of an upload into the store.
+///
+/// Will produce a stream of [`AddProgress`] messages.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UploadFinishRequest {
+    /// The id of the upload.
+    pub id: UploadId,
+    /// Tag to tag the data with.
+    pub tag: SetTagOption,
+    /// The named blob store to use, `None` for the default store.
+    pub store: Option<String>,
+}
+
+/// Wrapper around [`AddProgress`].
+#[derive(Debug, Serialize, Deserialize, derive_more::Into)]
+pub struct UploadFinishResponse(pub AddProgress);
+
+/// Abort an upload and remove its data.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UploadAbortRequest {
+    /// The id of the upload.
+    pub id: UploadId,
+    /// The named blob store to use, `None` for the default store.
+    pub store: Option<String>,
+}
+
 /// Delete a blob
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DeleteRequest {
     /// Name of the tag
     pub hash: Hash,
+    /// The named blob store to use, `None` for the default store.
+    pub store: Option<String>,
 }
 
 /// Create a collection.
@@ -248,6 +400,8 @@ pub struct CreateCollectionRequest {
     pub tag: SetTagOption,
     /// Tags that should be deleted after creation.
     pub tags_to_delete: Vec<Tag>,
+    /// The named blob store to use, `None` for the default store.
+    pub store: Option<String>,
 }
 
 /// A response to a create collection request
@@ -263,15 +417,50 @@ pub struct CreateCollectionResponse {
 pub struct BlobStatusRequest {
     /// The hash of the blob
     pub hash: Hash,
+    /// The named blob store to use, `None` for the default store.
+    pub store: Option<String>,
 }
 
 /// The response to a status request
 #[derive(Debug, Serialize, Deserialize, derive_more::From, derive_more::Into)]
 pub struct BlobStatusResponse(pub BlobStatus);
 
+/// Subscribe to availability changes of blobs in a store
+///
+/// Will produce a stream of [`SubscribeResponse`] messages.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubscribeRequest {
+    /// Only report changes for this hash, or for all blobs if `None`.
+    ///
+    /// When set, the current status of the blob is reported first, if it is stored at all.
+    pub hash: Option<Hash>,
+    /// The named blob store to use, `None` for the default store.
+    pub store: Option<String>,
+}
+
+/// Wrapper around [`BlobEvent`].
+#[derive(Debug, Serialize, Deserialize, derive_more::From, derive_more::Into)]
+pub struct SubscribeResponse(pub BlobEvent);
+
+/// A request to subscribe to the events emitted while providing blobs to other nodes.
+///
+/// Will produce a stream of [`ProviderEventsResponse`] messages.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProviderEventsRequest {
+    /// The named blob store to use, `None` for the default store.
+    pub store: Option<String>,
+}
+
+/// Wrapper around [`ProviderEvent`].
+#[derive(Debug, Serialize, Deserialize, derive_more::From, derive_more::Into)]
+pub struct ProviderEventsResponse(pub ProviderEvent);
+
 /// Request to create a new scope for temp tags
 #[derive(Debug, Serialize, Deserialize)]
-pub struct BatchCreateRequest;
+pub struct BatchCreateRequest {
+    /// The named blob store to use, `None` for the default store.
+    pub store: Option<String>,
+}
 
 /// Update to a temp tag scope
 #[derive(Debug, Serialize, Deserialize)]
@@ -296,6 +485,8 @@ pub struct BatchCreateTempTagRequest {
     pub content: HashAndFormat,
     /// Batch to create the temp tag in
     pub batch: BatchId,
+    /// The named blob store to use, `None` for the default store.
+    pub store: Option<String>,
 }
 
 /// Write a blob from a byte stream
@@ -305,6 +496,8 @@ pub struct BatchAddStreamRequest {
     pub format: BlobFormat,
     /// Batch to create the temp tag in
     pub batch: BatchId,
+    /// The named blob store to use, `None` for the default store.
+    pub store: Option<String>,
 }
 
 /// Write a blob from a byte stream
@@ -335,6 +528,8 @@ pub struct BatchAddPathRequest {
     pub format: BlobFormat,
     /// Batch to create the temp tag in
     pub batch: BatchId,
+    /// The named blob store to use, `None` for the default store.
+    pub store: Option<String>,
 }
 
 /// Response to a batch add path request
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::{collections::BTreeSet, path::PathBuf};
 
 use bytes::Bytes;
 use iroh_base::{
@@ -7,10 +7,13 @@ use iroh_base::{
 };
 use iroh_blobs::{export::ExportProgress, store::ExportMode, Hash};
 use iroh_docs::{
-    actor::OpenState, engine::LiveEvent, store::DownloadPolicy, store::Query, AuthorId, Capability,
-    CapabilityKind, DocTicket, Entry, NamespaceId, PeerIdBytes, SignedEntry,
+    actor::OpenState, engine::LiveEvent, engine::SubscribeFilter, store::ClockMode,
+    store::DownloadPolicy, store::HistoryPolicy, store::IndexSpec, store::Query, store::SyncPolicy,
+    store::TombstonePolicy, AccessPolicy, AreaOfInterest, AuthorId, Capability, CapabilityKind,
+    DelegationScope, DocEncryptionKey, DocTicket, Entry, InviteTicket, NamespaceId, PeerIdBytes,
+    ReadToken, Redemption, SignedEntry, WriteDelegation,
 };
-use iroh_net::NodeAddr;
+use iroh_net::{NodeAddr, NodeId};
 use nested_enum_utils::enum_conversions;
 use quic_rpc::pattern::try_server_streaming::StreamCreated;
 use quic_rpc_derive::rpc_requests;
@@ -44,6 +47,8 @@ pub enum Request {
     SetHash(SetHashRequest),
     #[server_streaming(response = RpcResult<GetManyResponse>)]
     Get(GetManyRequest),
+    #[server_streaming(response = RpcResult<GetManyContentResponse>)]
+    GetManyContent(GetManyContentRequest),
     #[rpc(response = RpcResult<GetExactResponse>)]
     GetExact(GetExactRequest),
     #[server_streaming(response = ImportFileResponse)]
@@ -52,6 +57,8 @@ pub enum Request {
     ExportFile(ExportFileRequest),
     #[rpc(response = RpcResult<DelResponse>)]
     Del(DelRequest),
+    #[rpc(response = RpcResult<BatchResponse>)]
+    Batch(BatchRequest),
     #[rpc(response = RpcResult<StartSyncResponse>)]
     StartSync(StartSyncRequest),
     #[rpc(response = RpcResult<LeaveResponse>)]
@@ -64,8 +71,78 @@ pub enum Request {
     GetDownloadPolicy(GetDownloadPolicyRequest),
     #[rpc(response = RpcResult<SetDownloadPolicyResponse>)]
     SetDownloadPolicy(SetDownloadPolicyRequest),
+    #[rpc(response = RpcResult<GetHistoryPolicyResponse>)]
+    GetHistoryPolicy(GetHistoryPolicyRequest),
+    #[rpc(response = RpcResult<SetHistoryPolicyResponse>)]
+    SetHistoryPolicy(SetHistoryPolicyRequest),
+    #[rpc(response = RpcResult<GetTombstonePolicyResponse>)]
+    GetTombstonePolicy(GetTombstonePolicyRequest),
+    #[rpc(response = RpcResult<SetTombstonePolicyResponse>)]
+    SetTombstonePolicy(SetTombstonePolicyRequest),
+    #[rpc(response = RpcResult<CompactTombstonesResponse>)]
+    CompactTombstones(CompactTombstonesRequest),
+    #[rpc(response = RpcResult<GetSyncPolicyResponse>)]
+    GetSyncPolicy(GetSyncPolicyRequest),
+    #[rpc(response = RpcResult<SetSyncPolicyResponse>)]
+    SetSyncPolicy(SetSyncPolicyRequest),
+    #[rpc(response = RpcResult<GetInlineLimitResponse>)]
+    GetInlineLimit(GetInlineLimitRequest),
+    #[rpc(response = RpcResult<SetInlineLimitResponse>)]
+    SetInlineLimit(SetInlineLimitRequest),
+    #[rpc(response = RpcResult<GetInlineContentResponse>)]
+    GetInlineContent(GetInlineContentRequest),
+    #[rpc(response = RpcResult<CreateIndexResponse>)]
+    CreateIndex(CreateIndexRequest),
+    #[rpc(response = RpcResult<RemoveIndexResponse>)]
+    RemoveIndex(RemoveIndexRequest),
+    #[rpc(response = RpcResult<ListIndexesResponse>)]
+    ListIndexes(ListIndexesRequest),
+    #[rpc(response = RpcResult<GetClockModeResponse>)]
+    GetClockMode(GetClockModeRequest),
+    #[rpc(response = RpcResult<SetClockModeResponse>)]
+    SetClockMode(SetClockModeRequest),
+    #[rpc(response = RpcResult<GetSyncInterestResponse>)]
+    GetSyncInterest(GetSyncInterestRequest),
+    #[rpc(response = RpcResult<SetSyncInterestResponse>)]
+    SetSyncInterest(SetSyncInterestRequest),
     #[rpc(response = RpcResult<GetSyncPeersResponse>)]
     GetSyncPeers(GetSyncPeersRequest),
+    #[rpc(response = RpcResult<DelegateResponse>)]
+    Delegate(DelegateRequest),
+    #[rpc(response = RpcResult<AddDelegationResponse>)]
+    AddDelegation(AddDelegationRequest),
+    #[rpc(response = RpcResult<ListDelegationsResponse>)]
+    ListDelegations(ListDelegationsRequest),
+    #[rpc(response = RpcResult<GetAccessPolicyResponse>)]
+    GetAccessPolicy(GetAccessPolicyRequest),
+    #[rpc(response = RpcResult<SetAccessPolicyResponse>)]
+    SetAccessPolicy(SetAccessPolicyRequest),
+    #[rpc(response = RpcResult<CreateReadTokenResponse>)]
+    CreateReadToken(CreateReadTokenRequest),
+    #[rpc(response = RpcResult<SetReadTokenResponse>)]
+    SetReadToken(SetReadTokenRequest),
+    #[rpc(response = RpcResult<SetEncryptionKeyResponse>)]
+    SetEncryptionKey(SetEncryptionKeyRequest),
+    #[rpc(response = RpcResult<GetEncryptionKeyResponse>)]
+    GetEncryptionKey(GetEncryptionKeyRequest),
+    #[rpc(response = RpcResult<RotateResponse>)]
+    Rotate(RotateRequest),
+    #[rpc(response = RpcResult<FollowMigrationResponse>)]
+    FollowMigration(FollowMigrationRequest),
+    #[rpc(response = RpcResult<SetTrustedMarkerAuthorsResponse>)]
+    SetTrustedMarkerAuthors(SetTrustedMarkerAuthorsRequest),
+    #[rpc(response = RpcResult<GetTrustedMarkerAuthorsResponse>)]
+    GetTrustedMarkerAuthors(GetTrustedMarkerAuthorsRequest),
+    #[rpc(response = RpcResult<CreateInviteResponse>)]
+    CreateInvite(CreateInviteRequest),
+    #[rpc(response = RpcResult<ImportInviteResponse>)]
+    ImportInvite(ImportInviteRequest),
+    #[rpc(response = RpcResult<ListRedemptionsResponse>)]
+    ListRedemptions(ListRedemptionsRequest),
+    #[rpc(response = RpcResult<ExportBundleResponse>)]
+    ExportBundle(ExportBundleRequest),
+    #[rpc(response = RpcResult<ImportBundleResponse>)]
+    ImportBundle(ImportBundleRequest),
 }
 
 #[allow(missing_docs)]
@@ -82,17 +159,54 @@ pub enum Response {
     Set(RpcResult<SetResponse>),
     SetHash(RpcResult<SetHashResponse>),
     Get(RpcResult<GetManyResponse>),
+    GetManyContent(RpcResult<GetManyContentResponse>),
     GetExact(RpcResult<GetExactResponse>),
     ImportFile(ImportFileResponse),
     ExportFile(ExportFileResponse),
     Del(RpcResult<DelResponse>),
+    Batch(RpcResult<BatchResponse>),
     Share(RpcResult<ShareResponse>),
     StartSync(RpcResult<StartSyncResponse>),
     Leave(RpcResult<LeaveResponse>),
     Subscribe(RpcResult<DocSubscribeResponse>),
     GetDownloadPolicy(RpcResult<GetDownloadPolicyResponse>),
     SetDownloadPolicy(RpcResult<SetDownloadPolicyResponse>),
+    GetHistoryPolicy(RpcResult<GetHistoryPolicyResponse>),
+    SetHistoryPolicy(RpcResult<SetHistoryPolicyResponse>),
+    GetTombstonePolicy(RpcResult<GetTombstonePolicyResponse>),
+    SetTombstonePolicy(RpcResult<SetTombstonePolicyResponse>),
+    CompactTombstones(RpcResult<CompactTombstonesResponse>),
+    GetSyncPolicy(RpcResult<GetSyncPolicyResponse>),
+    SetSyncPolicy(RpcResult<SetSyncPolicyResponse>),
+    GetInlineLimit(RpcResult<GetInlineLimitResponse>),
+    SetInlineLimit(RpcResult<SetInlineLimitResponse>),
+    GetInlineContent(RpcResult<GetInlineContentResponse>),
+    CreateIndex(RpcResult<CreateIndexResponse>),
+    RemoveIndex(RpcResult<RemoveIndexResponse>),
+    ListIndexes(RpcResult<ListIndexesResponse>),
+    GetClockMode(RpcResult<GetClockModeResponse>),
+    SetClockMode(RpcResult<SetClockModeResponse>),
+    GetSyncInterest(RpcResult<GetSyncInterestResponse>),
+    SetSyncInterest(RpcResult<SetSyncInterestResponse>),
     GetSyncPeers(RpcResult<GetSyncPeersResponse>),
+    Delegate(RpcResult<DelegateResponse>),
+    AddDelegation(RpcResult<AddDelegationResponse>),
+    ListDelegations(RpcResult<ListDelegationsResponse>),
+    GetAccessPolicy(RpcResult<GetAccessPolicyResponse>),
+    SetAccessPolicy(RpcResult<SetAccessPolicyResponse>),
+    CreateReadToken(RpcResult<CreateReadTokenResponse>),
+    SetReadToken(RpcResult<SetReadTokenResponse>),
+    SetEncryptionKey(RpcResult<SetEncryptionKeyResponse>),
+    GetEncryptionKey(RpcResult<GetEncryptionKeyResponse>),
+    Rotate(RpcResult<RotateResponse>),
+    FollowMigration(RpcResult<FollowMigrationResponse>),
+    SetTrustedMarkerAuthors(RpcResult<SetTrustedMarkerAuthorsResponse>),
+    GetTrustedMarkerAuthors(RpcResult<GetTrustedMarkerAuthorsResponse>),
+    CreateInvite(RpcResult<CreateInviteResponse>),
+    ImportInvite(RpcResult<ImportInviteResponse>),
+    ListRedemptions(RpcResult<ListRedemptionsResponse>),
+    ExportBundle(RpcResult<ExportBundleResponse>),
+    ImportBundle(RpcResult<ImportBundleResponse>),
     StreamCreated(RpcResult<StreamCreated>),
 }
 
@@ -101,6 +215,10 @@ pub enum Response {
 pub struct DocSubscribeRequest {
     /// The document id
     pub doc_id: NamespaceId,
+    /// Filter for the entry events
+    pub filter: SubscribeFilter,
+    /// Whether to emit the existing entries that match the filter first
+    pub replay: bool,
 }
 
 /// Response to [`DocSubscribeRequest`]
@@ -345,6 +463,50 @@ pub struct SetHashRequest {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct SetHashResponse {}
 
+/// A single write in a [`BatchRequest`]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum BatchWrite {
+    /// Set an entry to a byte array, like [`SetRequest`]
+    SetBytes {
+        /// Key of this entry.
+        key: Bytes,
+        /// Value of this entry.
+        value: Bytes,
+    },
+    /// Set an entry via its hash, like [`SetHashRequest`]
+    SetHash {
+        /// Key of this entry.
+        key: Bytes,
+        /// Hash of this entry.
+        hash: Hash,
+        /// Size of this entry.
+        size: u64,
+    },
+    /// Delete entries by prefix, like [`DelRequest`]
+    Del {
+        /// Prefix to delete.
+        prefix: Bytes,
+    },
+}
+
+/// Apply several writes to a document in a single transaction
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BatchRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+    /// Author of the entries.
+    pub author_id: AuthorId,
+    /// Writes to apply, in order.
+    pub writes: Vec<BatchWrite>,
+}
+
+/// Response to [`BatchRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BatchResponse {
+    /// The number of entries removed by the batch
+    pub removed: usize,
+}
+
 /// Get entries from a document
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GetManyRequest {
@@ -361,6 +523,26 @@ pub struct GetManyResponse {
     pub entry: SignedEntry,
 }
 
+/// Get entries from a document together with their content
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetManyContentRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+    /// Query to run
+    pub query: Query,
+    /// Content of entries longer than this is not included
+    pub max_content_len: u64,
+}
+
+/// Response to [`GetManyContentRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetManyContentResponse {
+    /// The document entry
+    pub entry: SignedEntry,
+    /// The content of the entry, if it is complete on the node and not too long
+    pub content: Option<Bytes>,
+}
+
 /// Get entries from a document
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GetExactRequest {
@@ -408,6 +590,247 @@ pub struct GetDownloadPolicyResponse {
     pub policy: DownloadPolicy,
 }
 
+/// Set a history policy
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetHistoryPolicyRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+    /// History policy
+    pub policy: HistoryPolicy,
+}
+
+/// Response to [`SetHistoryPolicyRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetHistoryPolicyResponse {}
+
+/// Get a history policy
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetHistoryPolicyRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+}
+
+/// Response to [`GetHistoryPolicyRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetHistoryPolicyResponse {
+    /// The history policy
+    pub policy: HistoryPolicy,
+}
+
+/// Set the tombstone policy of a document
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetTombstonePolicyRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+    /// Tombstone policy
+    pub policy: TombstonePolicy,
+}
+
+/// Response to [`SetTombstonePolicyRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetTombstonePolicyResponse {}
+
+/// Get the tombstone policy of a document
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetTombstonePolicyRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+}
+
+/// Response to [`GetTombstonePolicyRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetTombstonePolicyResponse {
+    /// The tombstone policy
+    pub policy: TombstonePolicy,
+}
+
+/// Set the sync policy of a document
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetSyncPolicyRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+    /// Sync policy
+    pub policy: SyncPolicy,
+}
+
+/// Response to [`SetSyncPolicyRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetSyncPolicyResponse {}
+
+/// Get the sync policy of a document
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetSyncPolicyRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+}
+
+/// Response to [`GetSyncPolicyRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetSyncPolicyResponse {
+    /// The sync policy
+    pub policy: SyncPolicy,
+}
+
+/// Set the maximum length of values of a document that are sent inline
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetInlineLimitRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+    /// Maximum length of inline values in bytes
+    pub limit: u64,
+}
+
+/// Response to [`SetInlineLimitRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetInlineLimitResponse {}
+
+/// Get the maximum length of values of a document that are sent inline
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetInlineLimitRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+}
+
+/// Response to [`GetInlineLimitRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetInlineLimitResponse {
+    /// Maximum length of inline values in bytes
+    pub limit: u64,
+}
+
+/// Get the content of an entry of a document that was received inline with the entry
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetInlineContentRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+    /// Author of the entry
+    pub author: AuthorId,
+    /// Key of the entry
+    pub key: Bytes,
+    /// Content hash of the entry
+    pub hash: Hash,
+}
+
+/// Response to [`GetInlineContentRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetInlineContentResponse {
+    /// The content, if it was received inline and is stored in the docs store
+    pub content: Option<Bytes>,
+}
+
+/// Create a secondary index of a document
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateIndexRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+    /// Name of the index
+    pub name: String,
+    /// Which entries are indexed, and how the values are extracted
+    pub spec: IndexSpec,
+}
+
+/// Response to [`CreateIndexRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateIndexResponse {}
+
+/// Remove a secondary index of a document
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RemoveIndexRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+    /// Name of the index
+    pub name: String,
+}
+
+/// Response to [`RemoveIndexRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RemoveIndexResponse {
+    /// Whether the index existed
+    pub removed: bool,
+}
+
+/// List the secondary indexes of a document
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ListIndexesRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+}
+
+/// Response to [`ListIndexesRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ListIndexesResponse {
+    /// The indexes of the document, by name
+    pub indexes: Vec<(String, IndexSpec)>,
+}
+
+/// Remove the expired tombstones of a document
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CompactTombstonesRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+}
+
+/// Response to [`CompactTombstonesRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CompactTombstonesResponse {
+    /// The number of removed tombstones
+    pub removed: usize,
+}
+
+/// Set the clock mode of a document
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetClockModeRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+    /// Clock mode
+    pub mode: ClockMode,
+}
+
+/// Response to [`SetClockModeRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetClockModeResponse {}
+
+/// Get the clock mode of a document
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetClockModeRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+}
+
+/// Response to [`GetClockModeRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetClockModeResponse {
+    /// The clock mode
+    pub mode: ClockMode,
+}
+
+/// Set the area of interest for syncing a document
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetSyncInterestRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+    /// Area of interest
+    pub area: AreaOfInterest,
+}
+
+/// Response to [`SetSyncInterestRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetSyncInterestResponse {}
+
+/// Get the area of interest for syncing a document
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetSyncInterestRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+}
+
+/// Response to [`GetSyncInterestRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetSyncInterestResponse {
+    /// The area of interest
+    pub area: AreaOfInterest,
+}
+
 /// Get peers for document
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GetSyncPeersRequest {
@@ -421,3 +844,294 @@ pub struct GetSyncPeersResponse {
     /// List of peers ids
     pub peers: Option<Vec<PeerIdBytes>>,
 }
+
+/// Create a write delegation for a document
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DelegateRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+    /// The access to delegate
+    pub scope: DelegationScope,
+    /// Author whose delegation to delegate further, or `None` to sign with the namespace secret
+    pub issuer: Option<AuthorId>,
+}
+
+/// Response to [`DelegateRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DelegateResponse {
+    /// The created delegation
+    pub delegation: WriteDelegation,
+}
+
+/// Add a write delegation to a document
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AddDelegationRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+    /// The delegation to add
+    pub delegation: WriteDelegation,
+}
+
+/// Response to [`AddDelegationRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AddDelegationResponse {
+    /// Whether the delegation was not known before
+    pub inserted: bool,
+}
+
+/// List the write delegations of a document
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ListDelegationsRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+}
+
+/// Response to [`ListDelegationsRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ListDelegationsResponse {
+    /// The delegations known for the document
+    pub delegations: Vec<WriteDelegation>,
+}
+
+/// Set the access policy of a document
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetAccessPolicyRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+    /// Access policy
+    pub policy: AccessPolicy,
+}
+
+/// Response to [`SetAccessPolicyRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetAccessPolicyResponse {}
+
+/// Get the access policy of a document
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetAccessPolicyRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+}
+
+/// Response to [`GetAccessPolicyRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetAccessPolicyResponse {
+    /// The access policy
+    pub policy: AccessPolicy,
+}
+
+/// Create a read token for a document
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateReadTokenRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+    /// The node to grant read access to
+    pub node: PeerIdBytes,
+    /// Expiry of the token, in microseconds since the Unix epoch
+    pub expires: Option<u64>,
+}
+
+/// Response to [`CreateReadTokenRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateReadTokenResponse {
+    /// The created token
+    pub token: ReadToken,
+}
+
+/// Set the read token to present to peers when syncing a document
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetReadTokenRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+    /// The read token
+    pub token: ReadToken,
+}
+
+/// Response to [`SetReadTokenRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetReadTokenResponse {}
+
+/// Set the encryption key of a document
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetEncryptionKeyRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+    /// The key, or `None` to derive the key from the document secret
+    pub key: Option<DocEncryptionKey>,
+}
+
+/// Response to [`SetEncryptionKeyRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetEncryptionKeyResponse {
+    /// The encryption key of the document
+    pub key: DocEncryptionKey,
+}
+
+/// Get the encryption key of a document
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetEncryptionKeyRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+}
+
+/// Response to [`GetEncryptionKeyRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetEncryptionKeyResponse {
+    /// The encryption key of the document, if it is encrypted
+    pub key: Option<DocEncryptionKey>,
+}
+
+/// Move a document to a new namespace
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RotateRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+    /// Author of the marker entry, and of copied entries whose author secret is not available
+    pub author_id: AuthorId,
+    /// Whether to re-sign the copied entries with their original authors
+    pub keep_authors: bool,
+}
+
+/// Response to [`RotateRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RotateResponse {
+    /// The id of the new document
+    pub id: NamespaceId,
+    /// The number of copied entries
+    pub copied: usize,
+}
+
+/// Start to sync the successor of a moved document
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FollowMigrationRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+    /// The id of the successor document
+    pub to: NamespaceId,
+}
+
+/// Response to [`FollowMigrationRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FollowMigrationResponse {}
+
+/// Set the authors whose moved-to markers are followed automatically
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetTrustedMarkerAuthorsRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+    /// The trusted authors
+    pub authors: BTreeSet<AuthorId>,
+}
+
+/// Response to [`SetTrustedMarkerAuthorsRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetTrustedMarkerAuthorsResponse {}
+
+/// Get the authors whose moved-to markers are followed automatically
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetTrustedMarkerAuthorsRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+}
+
+/// Response to [`GetTrustedMarkerAuthorsRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetTrustedMarkerAuthorsResponse {
+    /// The trusted authors
+    pub authors: BTreeSet<AuthorId>,
+}
+
+/// Create an invite ticket for a document
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateInviteRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+    /// Whether to grant read or write access to the document
+    pub mode: ShareMode,
+    /// Optional expiry time in microseconds since the Unix epoch
+    pub expires: Option<u64>,
+    /// Whether the invite can only be redeemed by a single node
+    pub single_use: bool,
+    /// The node that can redeem the invite, if it is bound to a node
+    pub invitee: Option<NodeId>,
+    /// Configuration of the addresses in the ticket.
+    pub addr_options: AddrInfoOptions,
+}
+
+/// Response to [`CreateInviteRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateInviteResponse(pub InviteTicket);
+
+/// Import a document from an invite ticket and redeem the invite
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ImportInviteRequest {
+    /// The invite ticket
+    pub ticket: InviteTicket,
+}
+
+/// Response to [`ImportInviteRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ImportInviteResponse {
+    /// The document id
+    pub doc_id: NamespaceId,
+}
+
+/// List the redemptions of the invites to a document
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ListRedemptionsRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+}
+
+/// Response to [`ListRedemptionsRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ListRedemptionsResponse {
+    /// The logged redemptions
+    pub redemptions: Vec<Redemption>,
+}
+
+/// Export entries of a document to a bundle file
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ExportBundleRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+    /// Query for the entries to export
+    pub query: Query,
+    /// Whether to include the content of the entries, if available
+    pub include_content: bool,
+    /// The filepath to where the bundle should be saved
+    ///
+    /// This should be an absolute path valid for the file system on which
+    /// the node runs.
+    pub path: PathBuf,
+}
+
+/// Response to [`ExportBundleRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ExportBundleResponse {
+    /// The number of exported entries
+    pub entries: usize,
+    /// The number of exported content blobs
+    pub blobs: usize,
+}
+
+/// Import the entries of a bundle file into a document
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ImportBundleRequest {
+    /// The filepath of the bundle
+    ///
+    /// This should be an absolute path valid for the file system on which
+    /// the node runs.
+    pub path: PathBuf,
+}
+
+/// Response to [`ImportBundleRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ImportBundleResponse {
+    /// The document id
+    pub doc_id: NamespaceId,
+    /// The number of inserted entries
+    pub entries: usize,
+    /// The number of imported content blobs
+    pub blobs: usize,
+}
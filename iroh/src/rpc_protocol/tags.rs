@@ -51,6 +51,8 @@ pub struct CreateRequest {
     pub batch: Option<BatchId>,
     /// Sync mode
     pub sync: SyncMode,
+    /// The named blob store to use, `None` for the default store.
+    pub store: Option<String>,
 }
 
 /// Set or delete a tag
@@ -64,6 +66,8 @@ pub struct SetRequest {
     pub batch: Option<BatchId>,
     /// Sync mode
     pub sync: SyncMode,
+    /// The named blob store to use, `None` for the default store.
+    pub store: Option<String>,
 }
 
 /// List all collections
@@ -75,6 +79,8 @@ pub struct ListRequest {
     pub raw: bool,
     /// List hash seq tags
     pub hash_seq: bool,
+    /// The named blob store to use, `None` for the default store.
+    pub store: Option<String>,
 }
 
 impl ListRequest {
@@ -83,6 +89,7 @@ impl ListRequest {
         Self {
             raw: true,
             hash_seq: true,
+            store: None,
         }
     }
 
@@ -91,6 +98,7 @@ impl ListRequest {
         Self {
             raw: true,
             hash_seq: false,
+            store: None,
         }
     }
 
@@ -99,6 +107,7 @@ impl ListRequest {
         Self {
             raw: false,
             hash_seq: true,
+            store: None,
         }
     }
 }
@@ -108,4 +117,6 @@ impl ListRequest {
 pub struct DeleteRequest {
     /// Name of the tag
     pub name: Tag,
+    /// The named blob store to use, `None` for the default store.
+    pub store: Option<String>,
 }
@@ -27,6 +27,9 @@ pub enum IrohPaths {
     /// Path to the [`iroh_docs::AuthorId`] of the node's default author
     #[strum(serialize = "default-author")]
     DefaultAuthor,
+    /// Path to the directory for the data of resumable blob uploads.
+    #[strum(serialize = "uploads")]
+    Uploads,
 }
 
 impl AsRef<Path> for IrohPaths {
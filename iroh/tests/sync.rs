@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap},
     future::Future,
     sync::Arc,
     time::{Duration, Instant},
@@ -12,7 +12,10 @@ use futures_util::{FutureExt, StreamExt, TryStreamExt};
 use iroh::{
     base::node_addr::AddrInfoOptions,
     client::{
-        docs::{Entry, LiveEvent, ShareMode},
+        docs::{
+            Counter, Entry, FolderSyncEvent, FolderSyncOpts, LiveEvent, OrSet, Origin, ShareMode,
+            SubscribeFilter, SyncReason, Text,
+        },
         Doc,
     },
     net::key::{PublicKey, SecretKey},
@@ -24,8 +27,11 @@ use tracing_subscriber::{prelude::*, EnvFilter};
 
 use iroh_blobs::Hash;
 use iroh_docs::{
-    store::{DownloadPolicy, FilterKind, Query},
-    AuthorId, ContentStatus,
+    store::{
+        DownloadPolicy, FieldExtractor, FilterKind, IndexSpec, Query, SyncBackoff, SyncPolicy,
+        TombstonePolicy,
+    },
+    AccessPolicy, AuthorId, ContentStatus, DelegationScope,
 };
 use iroh_net::relay::RelayMode;
 
@@ -133,6 +139,783 @@ async fn sync_simple() -> Result<()> {
     Ok(())
 }
 
+/// Test that write access delegated to an author of a read-only peer is synced and enforced.
+#[tokio::test]
+async fn sync_delegated_write() -> Result<()> {
+    setup_logging();
+    let mut rng = test_rng(b"sync_delegated_write");
+    let nodes = spawn_nodes(2, &mut rng).await?;
+    let clients = nodes.iter().map(|node| node.client()).collect::<Vec<_>>();
+    let peer0 = nodes[0].node_id();
+
+    let author0 = clients[0].authors().create().await?;
+    let author1 = clients[1].authors().create().await?;
+    let doc0 = clients[0].docs().create().await?;
+    doc0.set_bytes(author0, b"/config".to_vec(), b"v1".to_vec())
+        .await?;
+    let delegation = doc0
+        .delegate(DelegationScope::new(author1, "/uploads/contractor/"))
+        .await?;
+    let ticket = doc0
+        .share(ShareMode::Read, AddrInfoOptions::RelayAndAddresses)
+        .await?;
+    let events0 = doc0.subscribe().await?;
+
+    info!("node1: join");
+    let doc1 = clients[1].docs().import(ticket).await?;
+    let events1 = doc1.subscribe().await?;
+    wait_for_events(events1, 1, TIMEOUT, |e| match_sync_finished(e, peer0)).await?;
+    assert_eq!(doc1.delegations().await?, vec![delegation]);
+
+    info!("node1: write under delegation");
+    let key = b"/uploads/contractor/file".to_vec();
+    doc1.set_bytes(author1, key.clone(), b"data".to_vec())
+        .await?;
+    let res = doc1
+        .set_bytes(author1, b"/config".to_vec(), b"v2".to_vec())
+        .await;
+    assert!(res.is_err());
+
+    let hash = Hash::new(b"data");
+    wait_for_events(
+        events0,
+        1,
+        TIMEOUT,
+        |e| matches!(e, LiveEvent::ContentReady { hash: h } if *h == hash),
+    )
+    .await?;
+    assert_latest(&doc0, &key, b"data").await;
+    assert_latest(&doc0, b"/config", b"v1").await;
+
+    for node in nodes {
+        node.shutdown().await?;
+    }
+    Ok(())
+}
+
+/// Test that a restricted document is only synced with peers holding a read token.
+#[tokio::test]
+async fn sync_restricted_access() -> Result<()> {
+    setup_logging();
+    let mut rng = test_rng(b"sync_restricted_access");
+    let nodes = spawn_nodes(2, &mut rng).await?;
+    let clients = nodes.iter().map(|node| node.client()).collect::<Vec<_>>();
+    let peer0 = nodes[0].node_id();
+    let peer1 = nodes[1].node_id();
+
+    let author0 = clients[0].authors().create().await?;
+    let doc0 = clients[0].docs().create().await?;
+    doc0.set_bytes(author0, b"/secret".to_vec(), b"data".to_vec())
+        .await?;
+    doc0.set_access_policy(AccessPolicy::restricted([])).await?;
+    assert!(doc0.get_access_policy().await?.is_restricted());
+    let ticket = doc0
+        .share(ShareMode::Read, AddrInfoOptions::RelayAndAddresses)
+        .await?;
+    let peers = ticket.nodes.clone();
+
+    info!("node1: join without token");
+    let events0 = doc0.subscribe().await?;
+    let doc1 = clients[1].docs().import(ticket).await?;
+    let events1 = doc1.subscribe().await?;
+    wait_for_events(
+        events1,
+        1,
+        TIMEOUT,
+        |e| matches!(e, LiveEvent::SyncFinished(e) if e.peer == peer0 && e.result.is_err()),
+    )
+    .await?;
+    assert!(doc1.get_exact(author0, b"/secret", false).await?.is_none());
+    // node0 does not admit node1 as a gossip neighbor
+    let neighbor_up = wait_for_events(
+        events0,
+        1,
+        Duration::from_secs(2),
+        |e| matches!(e, LiveEvent::NeighborUp(peer) if *peer == peer1),
+    )
+    .await;
+    assert!(neighbor_up.is_err());
+
+    info!("node1: sync with token");
+    let token = doc0.create_read_token(peer1, None).await?;
+    doc1.set_read_token(token).await?;
+    let events1 = doc1.subscribe().await?;
+    doc1.start_sync(peers).await?;
+    wait_for_events(events1, 1, TIMEOUT, |e| match_sync_finished(e, peer0)).await?;
+    assert_latest(&doc1, b"/secret", b"data").await;
+
+    for node in nodes {
+        node.shutdown().await?;
+    }
+    Ok(())
+}
+
+/// Test that encrypted entries are synced, but can only be read with the encryption key.
+#[tokio::test]
+async fn sync_encrypted_doc() -> Result<()> {
+    setup_logging();
+    let mut rng = test_rng(b"sync_encrypted_doc");
+    let nodes = spawn_nodes(2, &mut rng).await?;
+    let clients = nodes.iter().map(|node| node.client()).collect::<Vec<_>>();
+
+    let author0 = clients[0].authors().create().await?;
+    let doc0 = clients[0].docs().create().await?;
+    let other = clients[0].docs().open(doc0.id()).await?.unwrap();
+    assert!(other.encryption_key().await?.is_none());
+    let key = doc0.enable_encryption().await?;
+    doc0.set_bytes(author0, b"/secret".to_vec(), b"data".to_vec())
+        .await?;
+    let entry = doc0.get_exact(author0, b"/secret", false).await?.unwrap();
+    assert_eq!(entry.key(), b"/secret");
+    assert_eq!(&doc0.content_bytes(&entry).await?[..], b"data");
+
+    // handles opened before encryption was enabled encrypt their writes as well
+    other
+        .set_bytes(author0, b"/other".to_vec(), b"other".to_vec())
+        .await?;
+    let entry = doc0.get_exact(author0, b"/other", false).await?.unwrap();
+    assert_eq!(&doc0.content_bytes(&entry).await?[..], b"other");
+    assert!(doc0
+        .set_hash(author0, b"/plain".to_vec(), Hash::EMPTY, 0)
+        .await
+        .is_err());
+    let ticket = doc0
+        .share(ShareMode::Read, AddrInfoOptions::RelayAndAddresses)
+        .await?;
+
+    info!("node1: join without key");
+    let (doc1, events1) = clients[1].docs().import_and_subscribe(ticket).await?;
+    wait_for_events(events1, 1, TIMEOUT, |e| {
+        matches!(e, LiveEvent::PendingContentReady)
+    })
+    .await?;
+    let entries: Vec<_> = doc1.get_many(Query::all()).await?.try_collect().await?;
+    assert_eq!(entries.len(), 2);
+    assert!(entries.iter().all(|entry| entry.key() != b"/secret"));
+    let content = entries[0].content_bytes(&doc1).await?;
+    assert_ne!(&content[..], b"data");
+
+    info!("node1: read with key");
+    doc1.set_encryption_key(key).await?;
+    let entry = doc1.get_exact(author0, b"/secret", false).await?.unwrap();
+    assert_eq!(&doc1.content_bytes(&entry).await?[..], b"data");
+
+    for node in nodes {
+        node.shutdown().await?;
+    }
+    Ok(())
+}
+
+/// Test that small values are sent inline with their entries.
+#[tokio::test]
+async fn sync_inline_values() -> Result<()> {
+    setup_logging();
+    let mut rng = test_rng(b"sync_inline_values");
+    let nodes = spawn_nodes(2, &mut rng).await?;
+    let clients = nodes.iter().map(|node| node.client()).collect::<Vec<_>>();
+
+    let author0 = clients[0].authors().create().await?;
+    let doc0 = clients[0].docs().create().await?;
+    assert_eq!(doc0.get_inline_limit().await?, 0);
+    doc0.set_inline_limit(1024).await?;
+    assert_eq!(doc0.get_inline_limit().await?, 1024);
+    assert!(doc0
+        .set_inline_limit(iroh_docs::store::MAX_INLINE_LIMIT + 1)
+        .await
+        .is_err());
+    doc0.set_bytes(author0, b"/small".to_vec(), b"inline".to_vec())
+        .await?;
+    doc0.set_bytes(author0, b"/large".to_vec(), vec![7u8; 2048])
+        .await?;
+    let ticket = doc0
+        .share(ShareMode::Read, AddrInfoOptions::RelayAndAddresses)
+        .await?;
+
+    info!("node1: join");
+    let (doc1, events1) = clients[1].docs().import_and_subscribe(ticket).await?;
+    // the inline content is imported into the blob store of node1
+    let small_hash = Hash::new(b"inline");
+    let events = wait_for_events(events1, 3, TIMEOUT, |e| match e {
+        LiveEvent::InsertRemote { .. } => true,
+        LiveEvent::ContentReady { hash } => *hash == small_hash,
+        _ => false,
+    })
+    .await?;
+    let small = events
+        .iter()
+        .find_map(|e| match e {
+            LiveEvent::InsertRemote {
+                entry,
+                content_status,
+                ..
+            } if entry.key() == b"/small" => Some(*content_status),
+            _ => None,
+        })
+        .expect("small value was synced");
+    assert_eq!(small, ContentStatus::Complete);
+    assert_latest(&doc1, b"/small", b"inline").await;
+
+    // live updates carry the content inline as well, and it is stored whatever the download
+    // policy, before the event is emitted
+    doc1.set_download_policy(DownloadPolicy::NothingExcept(vec![]))
+        .await?;
+    let events1 = doc1.subscribe().await?;
+    doc0.set_bytes(author0, b"/live".to_vec(), b"update".to_vec())
+        .await?;
+    let events = wait_for_events(events1, 1, TIMEOUT, |e| {
+        matches!(e, LiveEvent::InsertRemote { .. })
+    })
+    .await?;
+    let LiveEvent::InsertRemote {
+        entry,
+        content_status,
+        ..
+    } = &events[0]
+    else {
+        unreachable!("filtered for remote inserts");
+    };
+    assert_eq!(entry.key(), b"/live");
+    assert_eq!(*content_status, ContentStatus::Complete);
+    assert_eq!(&doc1.content_bytes(entry).await?[..], b"update");
+
+    for node in nodes {
+        node.shutdown().await?;
+    }
+    Ok(())
+}
+
+/// Test that concurrent edits of CRDT values by different authors are merged.
+#[tokio::test]
+async fn sync_crdt_values() -> Result<()> {
+    setup_logging();
+    let mut rng = test_rng(b"sync_crdt_values");
+    let nodes = spawn_nodes(2, &mut rng).await?;
+    let clients = nodes.iter().map(|node| node.client()).collect::<Vec<_>>();
+
+    let author0 = clients[0].authors().create().await?;
+    let author1 = clients[1].authors().create().await?;
+    let doc0 = clients[0].docs().create().await?;
+    let ticket = doc0
+        .share(ShareMode::Write, AddrInfoOptions::RelayAndAddresses)
+        .await?;
+    let doc1 = clients[1].docs().import(ticket).await?;
+
+    info!("edit concurrently");
+    let counter0 = doc0.crdt::<Counter>("likes");
+    let mut value = counter0.load().await?;
+    counter0.apply(author0, [value.add(author0, 1)?]).await?;
+    let tags0 = doc0.crdt::<OrSet<String>>("tags");
+    let mut value = tags0.load().await?;
+    tags0
+        .apply(author0, [value.insert("a".to_string())?])
+        .await?;
+
+    let counter1 = doc1.crdt::<Counter>("likes");
+    let mut value = counter1.load().await?;
+    counter1
+        .apply(author1, [value.add(author1, 3)?, value.add(author1, -1)?])
+        .await?;
+    let tags1 = doc1.crdt::<OrSet<String>>("tags");
+    let mut value = tags1.load().await?;
+    tags1
+        .apply(author1, [value.insert("b".to_string())?])
+        .await?;
+
+    info!("wait for merged values");
+    let expected = BTreeSet::from(["a".to_string(), "b".to_string()]);
+    for (counter, tags) in [(&counter0, &tags0), (&counter1, &tags1)] {
+        tokio::time::timeout(TIMEOUT, async {
+            loop {
+                let count = counter.load().await?.value();
+                let set = tags.load().await?;
+                let elements: BTreeSet<String> = set.elements().into_iter().cloned().collect();
+                if count == 3 && elements == expected {
+                    break anyhow::Ok(());
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await??;
+    }
+
+    // the content of long entries is read on its own
+    let text0 = doc0.crdt::<Text>("text");
+    let mut value = text0.load().await?;
+    let long = "x".repeat(100 * 1024);
+    text0
+        .apply(author0, [value.insert(author0, 0, &long)?])
+        .await?;
+    assert_eq!(text0.load().await?.to_string(), long);
+
+    for node in nodes {
+        node.shutdown().await?;
+    }
+    Ok(())
+}
+
+/// Test that secondary indexes are maintained for local and synced entries.
+#[tokio::test]
+async fn sync_secondary_index() -> Result<()> {
+    setup_logging();
+    let mut rng = test_rng(b"sync_secondary_index");
+    let nodes = spawn_nodes(2, &mut rng).await?;
+    let clients = nodes.iter().map(|node| node.client()).collect::<Vec<_>>();
+    let spec = IndexSpec::new("tasks/", FieldExtractor::Json("/status".to_string()));
+
+    let author0 = clients[0].authors().create().await?;
+    let doc0 = clients[0].docs().create().await?;
+    doc0.create_index("status", spec.clone()).await?;
+    doc0.set_bytes(author0, "tasks/a", r#"{"status":"open"}"#)
+        .await?;
+    doc0.set_bytes(author0, "tasks/b", r#"{"status":"done"}"#)
+        .await?;
+    doc0.set_bytes(author0, "notes/c", r#"{"status":"open"}"#)
+        .await?;
+    assert_eq!(
+        doc0.list_indexes().await?,
+        vec![("status".to_string(), spec.clone())]
+    );
+    // entries are indexed in the background
+    wait_for_index(&doc0, "open", &[b"tasks/a"]).await?;
+    wait_for_index(&doc0, "done", &[b"tasks/b"]).await?;
+
+    // entries inserted by hash are indexed too
+    let entry = doc0.get_exact(author0, "tasks/a", false).await?.unwrap();
+    doc0.set_hash(
+        author0,
+        "tasks/c",
+        entry.content_hash(),
+        entry.content_len(),
+    )
+    .await?;
+    wait_for_index(&doc0, "open", &[b"tasks/a", b"tasks/c"]).await?;
+
+    info!("node1: join");
+    let ticket = doc0
+        .share(ShareMode::Write, AddrInfoOptions::RelayAndAddresses)
+        .await?;
+    let (doc1, events1) = clients[1].docs().import_and_subscribe(ticket).await?;
+    wait_for_events(events1, 1, TIMEOUT, |e| {
+        matches!(e, LiveEvent::PendingContentReady)
+    })
+    .await?;
+    let res: Result<Vec<Entry>> = doc1
+        .get_many(Query::index("status", "open"))
+        .await?
+        .try_collect()
+        .await;
+    assert!(res.is_err(), "index does not exist on node1");
+
+    // creating the index indexes the entries that are already synced
+    doc1.create_index("status", spec).await?;
+    wait_for_index(&doc1, "open", &[b"tasks/a", b"tasks/c"]).await?;
+
+    // entries synced later are indexed once their content is available
+    doc0.set_bytes(author0, "tasks/b", r#"{"status":"open"}"#)
+        .await?;
+    wait_for_index(&doc1, "open", &[b"tasks/a", b"tasks/b", b"tasks/c"]).await?;
+    assert_index(&doc1, "done", &[]).await?;
+
+    assert!(doc1.remove_index("status").await?);
+    assert!(!doc1.remove_index("status").await?);
+    assert!(doc1.list_indexes().await?.is_empty());
+
+    for node in nodes {
+        node.shutdown().await?;
+    }
+    Ok(())
+}
+
+async fn assert_index(doc: &Doc, value: &str, keys: &[&[u8]]) -> Result<()> {
+    let entries: Vec<Entry> = doc
+        .get_many(Query::index("status", value))
+        .await?
+        .try_collect()
+        .await?;
+    let found = entries.iter().map(|e| e.key()).collect::<Vec<_>>();
+    anyhow::ensure!(found == keys, "unexpected index entries {found:?}");
+    Ok(())
+}
+
+/// Wait until the index entries for `value` are the entries with `keys`.
+async fn wait_for_index(doc: &Doc, value: &str, keys: &[&[u8]]) -> Result<()> {
+    let timeout = Instant::now() + TIMEOUT;
+    loop {
+        match assert_index(doc, value, keys).await {
+            Ok(()) => return Ok(()),
+            Err(err) if Instant::now() > timeout => {
+                return Err(err.context(format!("entries were not indexed in {TIMEOUT:?}")))
+            }
+            Err(_) => tokio::time::sleep(Duration::from_millis(50)).await,
+        }
+    }
+}
+
+/// Test that peers can follow a document to its successor after the document was rotated.
+#[tokio::test]
+async fn sync_rotate_doc() -> Result<()> {
+    setup_logging();
+    let mut rng = test_rng(b"sync_rotate_doc");
+    let nodes = spawn_nodes(2, &mut rng).await?;
+    let clients = nodes.iter().map(|node| node.client()).collect::<Vec<_>>();
+
+    let author0 = clients[0].authors().create().await?;
+    let doc0 = clients[0].docs().create().await?;
+    doc0.set_bytes(author0, b"/a".to_vec(), b"1".to_vec())
+        .await?;
+    let ticket = doc0
+        .share(ShareMode::Read, AddrInfoOptions::RelayAndAddresses)
+        .await?;
+    let (doc1, events1) = clients[1].docs().import_and_subscribe(ticket).await?;
+    wait_for_events(events1, 1, TIMEOUT, |e| {
+        matches!(e, LiveEvent::PendingContentReady)
+    })
+    .await?;
+    let events1 = doc1.subscribe().await?;
+
+    info!("node0: rotate");
+    let new0 = doc0.rotate(author0, true).await?;
+    assert_ne!(new0.id(), doc0.id());
+    assert_latest(&new0, b"/a", b"1").await;
+
+    info!("node1: receive marker");
+    let to = new0.id();
+    wait_for_events(
+        events1,
+        1,
+        TIMEOUT,
+        |e| matches!(e, LiveEvent::Migrated { to: id, author } if *id == to && *author == author0),
+    )
+    .await?;
+    // the marker is not followed automatically
+    assert!(clients[1].docs().open(to).await.is_err());
+    // only markers that exist in the document can be followed
+    let other = clients[1].docs().create().await?.id();
+    assert!(doc1.follow_migration(other).await.is_err());
+
+    info!("node1: follow");
+    let new1 = doc1.follow_migration(to).await?;
+    assert_eq!(new1.id(), to);
+    tokio::time::timeout(TIMEOUT, async {
+        loop {
+            if get_latest(&new1, b"/a").await.ok().as_deref() == Some(b"1".as_slice()) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await?;
+
+    for node in nodes {
+        node.shutdown().await?;
+    }
+    Ok(())
+}
+
+/// Test that peers follow moved-to markers of trusted authors without confirmation.
+#[tokio::test]
+async fn sync_rotate_doc_trusted_author() -> Result<()> {
+    setup_logging();
+    let mut rng = test_rng(b"sync_rotate_doc_trusted_author");
+    let nodes = spawn_nodes(2, &mut rng).await?;
+    let clients = nodes.iter().map(|node| node.client()).collect::<Vec<_>>();
+
+    let author0 = clients[0].authors().create().await?;
+    let doc0 = clients[0].docs().create().await?;
+    doc0.set_bytes(author0, b"/a".to_vec(), b"1".to_vec())
+        .await?;
+    let ticket = doc0
+        .share(ShareMode::Read, AddrInfoOptions::RelayAndAddresses)
+        .await?;
+    let (doc1, events1) = clients[1].docs().import_and_subscribe(ticket).await?;
+    wait_for_events(events1, 1, TIMEOUT, |e| {
+        matches!(e, LiveEvent::PendingContentReady)
+    })
+    .await?;
+    doc1.set_trusted_marker_authors([author0]).await?;
+    assert_eq!(
+        doc1.trusted_marker_authors().await?,
+        [author0].into_iter().collect()
+    );
+
+    info!("node0: rotate");
+    let new0 = doc0.rotate(author0, true).await?;
+    let to = new0.id();
+
+    info!("node1: follow automatically");
+    tokio::time::timeout(TIMEOUT, async {
+        loop {
+            if let Ok(Some(new1)) = clients[1].docs().open(to).await {
+                if get_latest(&new1, b"/a").await.ok().as_deref() == Some(b"1".as_slice()) {
+                    break;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await?;
+
+    for node in nodes {
+        node.shutdown().await?;
+    }
+    Ok(())
+}
+
+/// Test that a peer which missed a deletion until the tombstone was compacted drops its old
+/// entries and syncs them again, instead of bringing the deleted entry back.
+#[tokio::test]
+async fn sync_compacted_doc() -> Result<()> {
+    setup_logging();
+    let mut rng = test_rng(b"sync_compacted_doc");
+    let nodes = spawn_nodes(2, &mut rng).await?;
+    let clients = nodes.iter().map(|node| node.client()).collect::<Vec<_>>();
+    let peer0 = nodes[0].node_id();
+    let policy = TombstonePolicy::Expire {
+        horizon: Duration::from_secs(1),
+    };
+
+    let author0 = clients[0].authors().create().await?;
+    let doc0 = clients[0].docs().create().await?;
+    doc0.set_tombstone_policy(policy.clone()).await?;
+    doc0.set_bytes(author0, b"/a".to_vec(), b"1".to_vec())
+        .await?;
+    doc0.set_bytes(author0, b"/b".to_vec(), b"2".to_vec())
+        .await?;
+    let ticket = doc0
+        .share(ShareMode::Read, AddrInfoOptions::RelayAndAddresses)
+        .await?;
+    let peers = ticket.nodes.clone();
+    let (doc1, events1) = clients[1].docs().import_and_subscribe(ticket).await?;
+    wait_for_events(events1, 1, TIMEOUT, |e| {
+        matches!(e, LiveEvent::PendingContentReady)
+    })
+    .await?;
+    assert_latest(&doc1, b"/b", b"2").await;
+    doc1.set_tombstone_policy(policy).await?;
+
+    info!("node1: leave, node0: delete and compact");
+    doc1.leave().await?;
+    doc0.del(author0, b"/b".to_vec()).await?;
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert_eq!(doc0.compact_tombstones().await?, 1);
+
+    info!("node1: sync again");
+    let events1 = doc1.subscribe().await?;
+    doc1.start_sync(peers).await?;
+    wait_for_events(events1, 1, TIMEOUT, |e| match_sync_finished(e, peer0)).await?;
+    assert_latest(&doc1, b"/a", b"1").await;
+    assert!(get_latest(&doc1, b"/b").await.is_err());
+    assert!(get_latest(&doc0, b"/b").await.is_err());
+
+    for node in nodes {
+        node.shutdown().await?;
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn sync_offline_bundle() -> Result<()> {
+    setup_logging();
+    let mut rng = test_rng(b"sync_offline_bundle");
+    let nodes = spawn_nodes(2, &mut rng).await?;
+    let clients = nodes.iter().map(|node| node.client()).collect::<Vec<_>>();
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("doc.bundle");
+
+    let author0 = clients[0].authors().create().await?;
+    let doc0 = clients[0].docs().create().await?;
+    doc0.set_bytes(author0, b"/a".to_vec(), b"1".to_vec())
+        .await?;
+    doc0.set_bytes(author0, b"/b".to_vec(), b"2".to_vec())
+        .await?;
+    doc0.set_bytes(author0, b"/c".to_vec(), b"3".to_vec())
+        .await?;
+
+    info!("node0: export");
+    let outcome = doc0
+        .export_bundle(Query::key_prefix("/a"), &path, true)
+        .await?;
+    assert_eq!((outcome.entries, outcome.blobs), (1, 1));
+    let outcome = doc0.export_bundle(Query::all(), &path, true).await?;
+    assert_eq!((outcome.entries, outcome.blobs), (3, 3));
+
+    info!("node1: reject tampered bundle");
+    let mut bytes = std::fs::read(&path)?;
+    let tampered_path = dir.path().join("tampered.bundle");
+    *bytes.last_mut().unwrap() ^= 1;
+    std::fs::write(&tampered_path, bytes)?;
+    assert!(clients[1]
+        .docs()
+        .import_bundle(&tampered_path)
+        .await
+        .is_err());
+    assert!(clients[1].docs().open(doc0.id()).await.is_err());
+
+    info!("node1: import");
+    let (doc1, outcome) = clients[1].docs().import_bundle(&path).await?;
+    assert_eq!(doc1.id(), doc0.id());
+    assert_eq!((outcome.entries, outcome.blobs), (3, 3));
+    let entries = get_all_with_content(&doc1).await?;
+    let values = entries
+        .iter()
+        .map(|(entry, content)| (entry.key().to_vec(), content.to_vec()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        values,
+        vec![
+            (b"/a".to_vec(), b"1".to_vec()),
+            (b"/b".to_vec(), b"2".to_vec()),
+            (b"/c".to_vec(), b"3".to_vec()),
+        ]
+    );
+
+    // importing again inserts nothing
+    let (_, outcome) = clients[1].docs().import_bundle(&path).await?;
+    assert_eq!(outcome.entries, 0);
+
+    for node in nodes {
+        node.shutdown().await?;
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn sync_periodic_resync() -> Result<()> {
+    setup_logging();
+    let mut rng = test_rng(b"sync_periodic_resync");
+    let nodes = spawn_nodes(2, &mut rng).await?;
+    let clients = nodes.iter().map(|node| node.client()).collect::<Vec<_>>();
+    let peer0 = nodes[0].node_id();
+
+    let author0 = clients[0].authors().create().await?;
+    let doc0 = clients[0].docs().create().await?;
+    doc0.set_bytes(author0, b"/a".to_vec(), b"1".to_vec())
+        .await?;
+    let ticket = doc0
+        .share(ShareMode::Read, AddrInfoOptions::RelayAndAddresses)
+        .await?;
+    let (doc1, events1) = clients[1].docs().import_and_subscribe(ticket).await?;
+    wait_for_events(
+        events1,
+        1,
+        TIMEOUT,
+        |e| matches!(e, LiveEvent::SyncFinished(e) if e.peer == peer0 && e.result.is_ok()),
+    )
+    .await?;
+
+    let policy = SyncPolicy {
+        resync_interval: Some(Duration::from_secs(1)),
+        max_concurrent: Some(1),
+        backoff: Some(SyncBackoff::default()),
+        preferred_peers: vec![*peer0.as_bytes()],
+    };
+    doc1.set_sync_policy(policy.clone()).await?;
+    assert_eq!(doc1.get_sync_policy().await?, policy);
+    let zero = SyncPolicy {
+        max_concurrent: Some(0),
+        ..Default::default()
+    };
+    assert!(doc1.set_sync_policy(zero).await.is_err());
+    assert_eq!(doc1.get_sync_policy().await?, policy);
+
+    info!("node1: wait for periodic resync");
+    let events1 = doc1.subscribe().await?;
+    wait_for_events(events1, 2, TIMEOUT, |e| {
+        matches!(
+            e,
+            LiveEvent::SyncFinished(e)
+                if e.peer == peer0
+                    && e.origin == Origin::Connect(SyncReason::Periodic)
+                    && e.result.is_ok()
+        )
+    })
+    .await?;
+
+    for node in nodes {
+        node.shutdown().await?;
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn sync_invite_doc() -> Result<()> {
+    setup_logging();
+    let mut rng = test_rng(b"sync_invite_doc");
+    let nodes = spawn_nodes(3, &mut rng).await?;
+    let clients = nodes.iter().map(|node| node.client()).collect::<Vec<_>>();
+
+    let author0 = clients[0].authors().create().await?;
+    let doc0 = clients[0].docs().create().await?;
+    doc0.set_access_policy(AccessPolicy::restricted([])).await?;
+    doc0.set_bytes(author0, b"/a".to_vec(), b"1".to_vec())
+        .await?;
+
+    // an expired invite is rejected on import
+    let expired = doc0
+        .invite(
+            ShareMode::Read,
+            Some(1),
+            false,
+            None,
+            AddrInfoOptions::RelayAndAddresses,
+        )
+        .await?;
+    assert!(clients[1].docs().import_invite(expired).await.is_err());
+
+    let ticket = doc0
+        .invite(
+            ShareMode::Read,
+            None,
+            true,
+            None,
+            AddrInfoOptions::RelayAndAddresses,
+        )
+        .await?;
+
+    info!("node1: redeem invite");
+    let doc1 = clients[1].docs().import_invite(ticket.clone()).await?;
+    tokio::time::timeout(TIMEOUT, async {
+        while get_latest(&doc1, b"/a").await.ok().as_deref() != Some(b"1".as_slice()) {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await?;
+    let redemptions = doc0.redemptions().await?;
+    assert_eq!(redemptions.len(), 1);
+    assert_eq!(redemptions[0].invite, ticket.invite.id());
+    assert_eq!(redemptions[0].node, *nodes[1].node_id().as_bytes());
+
+    // node1 only serves node0, so that node2 can only get the document by redeeming an invite
+    doc1.set_access_policy(AccessPolicy::restricted([*nodes[0].node_id().as_bytes()]))
+        .await?;
+
+    info!("node2: redeem invite bound to node1");
+    let bound = doc0
+        .invite(
+            ShareMode::Read,
+            None,
+            false,
+            Some(nodes[1].node_id()),
+            AddrInfoOptions::RelayAndAddresses,
+        )
+        .await?;
+    let doc2 = clients[2].docs().import_invite(bound).await?;
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert!(get_latest(&doc2, b"/a").await.is_err());
+    assert_eq!(doc0.redemptions().await?.len(), 1);
+
+    info!("node2: redeem forwarded invite");
+    let doc2 = clients[2].docs().import_invite(ticket).await?;
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert!(get_latest(&doc2, b"/a").await.is_err());
+    assert_eq!(doc0.redemptions().await?.len(), 1);
+
+    for node in nodes {
+        node.shutdown().await?;
+    }
+    Ok(())
+}
+
 /// Test subscribing to replica events (without sync)
 #[tokio::test]
 async fn sync_subscribe_no_sync() -> Result<()> {
@@ -1211,6 +1994,327 @@ async fn sync_drop_doc() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn sync_subscribe_with_filter() -> Result<()> {
+    let mut rng = test_rng(b"sync_subscribe_with_filter");
+    setup_logging();
+    let node = spawn_node(0, &mut rng).await?;
+    let client = node.client();
+
+    let doc = client.docs().create().await?;
+    let author = client.authors().create().await?;
+    let other = client.authors().create().await?;
+
+    doc.set_bytes(author, b"/users/a".to_vec(), b"a".to_vec())
+        .await?;
+    doc.set_bytes(author, b"/groups/a".to_vec(), b"a".to_vec())
+        .await?;
+
+    // more entries than fit into the subscription channel
+    for i in 0..300 {
+        doc.set_bytes(author, format!("/users/b{i:03}"), b"b".to_vec())
+            .await?;
+    }
+
+    let filter = SubscribeFilter::key_prefix(b"/users/").author(author);
+    let mut sub = doc.subscribe_with_filter(filter, true).await?;
+    let ev = sub.next().await;
+    assert!(
+        matches!(ev, Some(Ok(LiveEvent::Replayed { ref entry })) if entry.key() == b"/users/a")
+    );
+    for i in 0..300 {
+        let ev = sub.next().await;
+        let key = format!("/users/b{i:03}");
+        assert!(
+            matches!(ev, Some(Ok(LiveEvent::Replayed { ref entry })) if entry.key() == key.as_bytes())
+        );
+    }
+
+    doc.set_bytes(other, b"/users/b".to_vec(), b"b".to_vec())
+        .await?;
+    doc.set_bytes(author, b"/groups/b".to_vec(), b"b".to_vec())
+        .await?;
+    doc.set_bytes(author, b"/users/c".to_vec(), b"c".to_vec())
+        .await?;
+    let ev = sub.next().await;
+    assert!(
+        matches!(ev, Some(Ok(LiveEvent::InsertLocal { ref entry })) if entry.key() == b"/users/c")
+    );
+
+    Ok(())
+}
+
+/// Test that a subscriber that does not keep up with the replay does not block writes, and
+/// receives all live events after the replay.
+#[tokio::test]
+async fn sync_subscribe_replay_slow_subscriber() -> Result<()> {
+    let mut rng = test_rng(b"sync_subscribe_replay_slow_subscriber");
+    setup_logging();
+    let node = spawn_node(0, &mut rng).await?;
+    let client = node.client();
+
+    let doc = client.docs().create().await?;
+    let author = client.authors().create().await?;
+    // more entries than fit into the channels between the node and the subscriber, so that the
+    // replay is not finished while nothing is received
+    for i in 0..1000 {
+        doc.set_bytes(author, format!("/old/{i:04}"), b"old".to_vec())
+            .await?;
+    }
+
+    let mut sub = doc
+        .subscribe_with_filter(SubscribeFilter::default(), true)
+        .await?;
+
+    // more live events than fit into the subscription channels
+    tokio::time::timeout(TIMEOUT, async {
+        for i in 0..600 {
+            doc.set_bytes(author, format!("/new/{i:03}"), b"new".to_vec())
+                .await?;
+        }
+        anyhow::Ok(())
+    })
+    .await??;
+
+    let mut replayed = 0;
+    let mut live = 0;
+    while live < 600 {
+        let event = tokio::time::timeout(TIMEOUT, sub.next())
+            .await?
+            .context("subscription ended")??;
+        match event {
+            LiveEvent::Replayed { .. } => {
+                assert_eq!(live, 0, "replayed entries are emitted first");
+                replayed += 1;
+            }
+            LiveEvent::InsertLocal { entry } => {
+                assert_eq!(entry.key(), format!("/new/{live:03}").as_bytes());
+                live += 1;
+            }
+            _ => {}
+        }
+    }
+    assert!(replayed >= 1000);
+
+    node.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn sync_folder() -> Result<()> {
+    let mut rng = test_rng(b"sync_folder");
+    setup_logging();
+    let node = spawn_node(0, &mut rng).await?;
+    let client = node.client();
+
+    let doc = client.docs().create().await?;
+    let author = client.authors().create().await?;
+    doc.set_bytes(author, b"hello.txt\0".to_vec(), b"hello".to_vec())
+        .await?;
+
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().canonicalize()?;
+    let opts = FolderSyncOpts {
+        author,
+        root: root.clone(),
+        prefix: String::new(),
+    };
+    let mut sync = doc.sync_folder(opts).await?;
+
+    // existing entries are exported
+    let event = tokio::time::timeout(TIMEOUT, sync.next())
+        .await?
+        .context("sync stopped")??;
+    let path = root.join("hello.txt");
+    assert_eq!(event, FolderSyncEvent::Exported { path: path.clone() });
+    assert_eq!(std::fs::read(&path)?, b"hello");
+
+    // new files are imported
+    let path = root.join("new.txt");
+    std::fs::write(&path, b"new")?;
+    let event = tokio::time::timeout(TIMEOUT, sync.next())
+        .await?
+        .context("sync stopped")??;
+    assert_eq!(event, FolderSyncEvent::Imported { path: path.clone() });
+    assert_latest(&doc, b"new.txt\0", b"new").await;
+
+    // deleted files are deleted
+    std::fs::remove_file(&path)?;
+    let event = tokio::time::timeout(TIMEOUT, sync.next())
+        .await?
+        .context("sync stopped")??;
+    assert_eq!(event, FolderSyncEvent::Deleted { path });
+    let entry = doc.get_exact(author, b"new.txt\0".to_vec(), false).await?;
+    assert!(entry.is_none());
+
+    Ok(())
+}
+
+/// Test that local changes are imported while the document keeps changing.
+#[tokio::test]
+async fn sync_folder_busy_doc() -> Result<()> {
+    let mut rng = test_rng(b"sync_folder_busy_doc");
+    setup_logging();
+    let node = spawn_node(0, &mut rng).await?;
+    let client = node.client();
+
+    let doc = client.docs().create().await?;
+    let author = client.authors().create().await?;
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().canonicalize()?;
+    std::fs::write(root.join("first.txt"), b"first")?;
+    let opts = FolderSyncOpts {
+        author,
+        root: root.clone(),
+        prefix: "files".to_string(),
+    };
+    let mut sync = doc.sync_folder(opts).await?;
+
+    // wait for the initial sync to finish
+    let event = tokio::time::timeout(TIMEOUT, sync.next())
+        .await?
+        .context("sync stopped")??;
+    let path = root.join("first.txt");
+    assert_eq!(event, FolderSyncEvent::Imported { path });
+
+    // write entries outside of the prefix more often than the debounce interval
+    let writer = tokio::task::spawn({
+        let doc = doc.clone();
+        async move {
+            for i in 0u64.. {
+                doc.set_bytes(author, b"other".to_vec(), i.to_be_bytes().to_vec())
+                    .await?;
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+            anyhow::Ok(())
+        }
+    });
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let path = root.join("new.txt");
+    std::fs::write(&path, b"new")?;
+    let event = tokio::time::timeout(Duration::from_secs(10), sync.next())
+        .await?
+        .context("sync stopped")??;
+    assert_eq!(event, FolderSyncEvent::Imported { path });
+    assert_latest(&doc, b"files/new.txt\0", b"new").await;
+
+    writer.abort();
+    node.shutdown().await?;
+    Ok(())
+}
+
+/// Test that entries written by peers are exported to the folder, and removed when deleted.
+#[tokio::test]
+async fn sync_folder_remote() -> Result<()> {
+    let mut rng = test_rng(b"sync_folder_remote");
+    setup_logging();
+    let nodes = spawn_nodes(2, &mut rng).await?;
+    let clients = nodes.iter().map(|node| node.client()).collect::<Vec<_>>();
+
+    let author0 = clients[0].authors().create().await?;
+    let doc0 = clients[0].docs().create().await?;
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().canonicalize()?;
+    let opts = FolderSyncOpts {
+        author: author0,
+        root: root.clone(),
+        prefix: "files".to_string(),
+    };
+    let mut sync = doc0.sync_folder(opts).await?;
+
+    let ticket = doc0
+        .share(ShareMode::Write, AddrInfoOptions::RelayAndAddresses)
+        .await?;
+    let author1 = clients[1].authors().create().await?;
+    let doc1 = clients[1].docs().import(ticket).await?;
+
+    // entries outside of the prefix are not exported
+    doc1.set_bytes(author1, b"other.txt\0".to_vec(), b"other".to_vec())
+        .await?;
+    doc1.set_bytes(author1, b"files/remote.txt\0".to_vec(), b"remote".to_vec())
+        .await?;
+    let path = root.join("remote.txt");
+    let event = tokio::time::timeout(TIMEOUT, sync.next())
+        .await?
+        .context("sync stopped")??;
+    assert_eq!(event, FolderSyncEvent::Exported { path: path.clone() });
+    assert_eq!(std::fs::read(&path)?, b"remote");
+    assert!(!root.join("other.txt").exists());
+
+    doc1.del(author1, b"files/remote.txt\0".to_vec()).await?;
+    let event = tokio::time::timeout(TIMEOUT, sync.next())
+        .await?
+        .context("sync stopped")??;
+    assert_eq!(event, FolderSyncEvent::Removed { path: path.clone() });
+    assert!(!path.exists());
+
+    for node in nodes {
+        node.shutdown().await?;
+    }
+    Ok(())
+}
+
+/// Test that local files which conflict with newer entries are kept as conflict copies.
+#[tokio::test]
+async fn sync_folder_conflict() -> Result<()> {
+    let mut rng = test_rng(b"sync_folder_conflict");
+    setup_logging();
+    let node = spawn_node(0, &mut rng).await?;
+    let client = node.client();
+
+    let doc = client.docs().create().await?;
+    let author = client.authors().create().await?;
+    doc.set_bytes(author, b"a.txt\0".to_vec(), b"entry".to_vec())
+        .await?;
+
+    // a local file that was modified before the entry was written
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().canonicalize()?;
+    let path = root.join("a.txt");
+    std::fs::write(&path, b"local")?;
+    std::fs::File::options()
+        .write(true)
+        .open(&path)?
+        .set_modified(std::time::UNIX_EPOCH + Duration::from_secs(60))?;
+
+    let opts = FolderSyncOpts {
+        author,
+        root: root.clone(),
+        prefix: String::new(),
+    };
+    let mut sync = doc.sync_folder(opts).await?;
+
+    let event = tokio::time::timeout(TIMEOUT, sync.next())
+        .await?
+        .context("sync stopped")??;
+    let FolderSyncEvent::Conflict {
+        path: conflict,
+        copy,
+    } = event
+    else {
+        bail!("expected conflict, got {event:?}");
+    };
+    assert_eq!(conflict, path);
+    let event = tokio::time::timeout(TIMEOUT, sync.next())
+        .await?
+        .context("sync stopped")??;
+    assert_eq!(event, FolderSyncEvent::Exported { path: path.clone() });
+    assert_eq!(std::fs::read(&path)?, b"entry");
+    assert_eq!(std::fs::read(&copy)?, b"local");
+
+    // the conflict copy is imported as a new file
+    let event = tokio::time::timeout(TIMEOUT, sync.next())
+        .await?
+        .context("sync stopped")??;
+    assert_eq!(event, FolderSyncEvent::Imported { path: copy.clone() });
+    let name = copy.file_name().unwrap().to_string_lossy();
+    assert_latest(&doc, format!("{name}\0").as_bytes(), b"local").await;
+    assert_latest(&doc, b"a.txt\0", b"entry").await;
+
+    Ok(())
+}
+
 async fn assert_latest(doc: &Doc, key: &[u8], value: &[u8]) {
     let content = get_latest(doc, key).await.unwrap();
     assert_eq!(content, value.to_vec());